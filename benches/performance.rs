@@ -1,6 +1,7 @@
 use chrono::Utc;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use k_line::models::{TimeInterval, Transaction};
+use k_line::models::{Side, TimeInterval, Transaction};
+use k_line::services::kline::token_shard;
 use k_line::services::KLineService;
 use std::sync::Arc;
 use std::thread;
@@ -16,13 +17,37 @@ fn benchmark_single_transaction_processing(c: &mut Criterion) {
                 price: black_box(0.15),
                 volume: black_box(100.0),
                 timestamp: Utc::now(),
-                is_buy: true,
+                side: Side::Buy,
+                is_maker: None,
+                trade_id: None,
+                source: None,
             };
             service.process_transaction(black_box(&transaction));
         })
     });
 }
 
+fn benchmark_batch_transaction_processing(c: &mut Criterion) {
+    let service = KLineService::new();
+    let now = Utc::now();
+    let transactions: Vec<Transaction> = (0..1000)
+        .map(|i| Transaction {
+            token: ["DOGE", "SHIB", "PEPE"][i % 3].to_string(),
+            price: 0.15 + (i % 10) as f64 * 0.001,
+            volume: 100.0,
+            timestamp: now,
+            side: if i % 2 == 0 { Side::Buy } else { Side::Sell },
+            is_maker: None,
+            trade_id: None,
+            source: None,
+        })
+        .collect();
+
+    c.bench_function("process_batch_1000_transactions", |b| {
+        b.iter(|| service.process_transactions(black_box(&transactions)))
+    });
+}
+
 fn benchmark_concurrent_transaction_processing(c: &mut Criterion) {
     let service = Arc::new(KLineService::new());
 
@@ -37,7 +62,10 @@ fn benchmark_concurrent_transaction_processing(c: &mut Criterion) {
                             price: 0.15 + (i as f64 * 0.01),
                             volume: 100.0 + (i as f64 * 10.0),
                             timestamp: Utc::now(),
-                            is_buy: i % 2 == 0,
+                            side: if i % 2 == 0 { Side::Buy } else { Side::Sell },
+                            is_maker: None,
+                            trade_id: None,
+                            source: None,
                         };
                         service.process_transaction(&transaction);
                     })
@@ -51,6 +79,46 @@ fn benchmark_concurrent_transaction_processing(c: &mut Criterion) {
     });
 }
 
+fn benchmark_sharded_many_token_processing(c: &mut Criterion) {
+    // Each thread writes its own tokens, like an ingest worker owning a shard
+    for shards in [1, 8] {
+        let service = Arc::new(KLineService::new().with_shards(shards));
+        let tokens: Vec<String> = (0..1000).map(|i| format!("TOKEN{}", i)).collect();
+        let tokens = Arc::new(tokens);
+
+        c.bench_function(&format!("process_many_tokens_{}_shards", shards), |b| {
+            b.iter(|| {
+                let handles: Vec<_> = (0..8)
+                    .map(|worker| {
+                        let service = Arc::clone(&service);
+                        let tokens = Arc::clone(&tokens);
+                        thread::spawn(move || {
+                            let now = Utc::now();
+                            for token in tokens.iter().filter(|token| token_shard(token, 8) == worker) {
+                                let transaction = Transaction {
+                                    token: token.clone(),
+                                    price: 0.15,
+                                    volume: 100.0,
+                                    timestamp: now,
+                                    side: Side::Buy,
+                                    is_maker: None,
+                                    trade_id: None,
+                                    source: None,
+                                };
+                                service.process_transaction(&transaction);
+                            }
+                        })
+                    })
+                    .collect();
+
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+            })
+        });
+    }
+}
+
 fn benchmark_kline_retrieval(c: &mut Criterion) {
     let service = Arc::new(KLineService::new());
 
@@ -61,7 +129,10 @@ fn benchmark_kline_retrieval(c: &mut Criterion) {
             price: 0.15 + (i as f64 * 0.0001),
             volume: 100.0,
             timestamp: Utc::now() - chrono::Duration::seconds(i),
-            is_buy: i % 2 == 0,
+            side: if i % 2 == 0 { Side::Buy } else { Side::Sell },
+            is_maker: None,
+            trade_id: None,
+            source: None,
         };
         service.process_transaction(&transaction);
     }
@@ -94,7 +165,10 @@ fn benchmark_high_frequency_updates(c: &mut Criterion) {
                     price: 0.15 + (i as f64 * 0.00001),
                     volume: 10.0 + (i as f64),
                     timestamp: Utc::now(),
-                    is_buy: i % 2 == 0,
+                    side: if i % 2 == 0 { Side::Buy } else { Side::Sell },
+                    is_maker: None,
+                    trade_id: None,
+                    source: None,
                 };
                 service.process_transaction(black_box(&transaction));
             }
@@ -114,7 +188,10 @@ fn benchmark_memory_usage(c: &mut Criterion) {
                     price: 0.15 + (i as f64 * 0.0001),
                     volume: 100.0,
                     timestamp: Utc::now() - chrono::Duration::seconds(i * 60), // One per minute
-                    is_buy: i % 2 == 0,
+                    side: if i % 2 == 0 { Side::Buy } else { Side::Sell },
+                    is_maker: None,
+                    trade_id: None,
+                    source: None,
                 };
                 service.process_transaction(&transaction);
             }
@@ -157,7 +234,10 @@ fn benchmark_websocket_simulation(c: &mut Criterion) {
                                 price: 0.15 + (j as f64 * 0.0001),
                                 volume: 100.0,
                                 timestamp: Utc::now(),
-                                is_buy: (i + j) % 2 == 0,
+                                side: if (i + j) % 2 == 0 { Side::Buy } else { Side::Sell },
+                                is_maker: None,
+                                trade_id: None,
+                                source: None,
                             };
                             service.process_transaction(&transaction);
 
@@ -181,7 +261,9 @@ fn benchmark_websocket_simulation(c: &mut Criterion) {
 criterion_group!(
     benches,
     benchmark_single_transaction_processing,
+    benchmark_batch_transaction_processing,
     benchmark_concurrent_transaction_processing,
+    benchmark_sharded_many_token_processing,
     benchmark_kline_retrieval,
     benchmark_high_frequency_updates,
     benchmark_memory_usage,
@@ -0,0 +1,528 @@
+// Generated by `cargo run --features typescript --bin typegen`. Do not edit.
+
+/**
+ * A client message with the ID the client gave it
+ */
+export type ClientRequest = { 
+/**
+ * Echoed as `ref` in the reply to the message
+ */
+id?: RequestId, } & ({ "action": "subscribe", subscription: SubscriptionType, } | { "action": "subscribe_many", subscriptions: Array<SubscriptionType>, } | { "action": "unsubscribe", subscription: SubscriptionType, } | { "action": "list_subscriptions" } | { "action": "unsubscribe_all" } | { "action": "ping", client_time?: number, } | { "action": "set_format", format: WireFormat, } | { "action": "resume", topic: string, since: number, } | { "action": "auth", api_key?: string, token?: string, } | { "action": "get_klines", token: string, interval: string, limit?: number, before?: number, candle_type?: CandleType, });
+
+/**
+ * WebSocket message types from client
+ */
+export type ClientMessage = { "action": "subscribe", subscription: SubscriptionType, } | { "action": "subscribe_many", subscriptions: Array<SubscriptionType>, } | { "action": "unsubscribe", subscription: SubscriptionType, } | { "action": "list_subscriptions" } | { "action": "unsubscribe_all" } | { "action": "ping", client_time?: number, } | { "action": "set_format", format: WireFormat, } | { "action": "resume", topic: string, since: number, } | { "action": "auth", api_key?: string, token?: string, } | { "action": "get_klines", token: string, interval: string, limit?: number, before?: number, candle_type?: CandleType, };
+
+/**
+ * Identifier a client gives a message, echoed as `ref` in the reply
+ */
+export type RequestId = number | string;
+
+/**
+ * WebSocket subscription types
+ */
+export type SubscriptionType = { "type": "transactions", tokens: Array<string>, } | { "type": "klines", token: string, interval: string, throttle_ms?: number | null, candle_type?: CandleType | null, } | { "type": "all_transactions" } | { "type": "agg_trades", token: string, } | { "type": "depth", token: string, } | { "type": "ops_metrics", api_key?: string | null, } | { "type": "admin_events", api_key?: string | null, } | { "type": "alerts" } | { "type": "listings" } | { "type": "indicators", token: string, interval: string, indicator: IndicatorKind, period?: number | null, fast?: number | null, slow?: number | null, signal?: number | null, std_dev?: number | null, } | { "type": "bars", token: string, bar_type: BarType, size?: number | null, interval?: string | null, atr_period?: number | null, } | { "type": "delta", token: string, interval: string, } | { "type": "ticker", token: string, };
+
+/**
+ * Encoding used for messages on a WebSocket connection
+ */
+export type WireFormat = "json" | "msgpack" | "cbor";
+
+/**
+ * WebSocket message types to client
+ */
+export type ServerMessage = { "type": "transaction", data: Transaction, } | { "type": "agg_trade", data: AggTrade, } | { "type": "depth_snapshot", data: DepthSnapshot, } | { "type": "depth_update", data: DepthUpdate, } | { "type": "trades", token: string, data: Array<Transaction>, } | { "type": "system_status", data: SystemStatus, } | { "type": "kline", data: KLine, } | { "type": "kline_amended", data: KLine, } | { "type": "klines_snapshot", token: string, interval: string, data: Array<KLine>, next_cursor: number | null, } | { "type": "ops_metrics", data: OpsMetrics, } | { "type": "new_token", data: NewTokenEvent, } | { "type": "alert", data: AlertTrigger, } | { "type": "token_listed", data: ListingEvent, } | { "type": "token_delisted", data: ListingEvent, } | { "type": "indicator", data: IndicatorUpdate, } | { "type": "bar", data: Bar, } | { "type": "delta", data: VolumeDelta, } | { "type": "ticker", data: Ticker, } | { "type": "config_updated", data: ConfigUpdate, } | { "type": "subscribed", subscription: SubscriptionType, } | { "type": "subscribed_many", accepted: Array<SubscriptionType>, rejected: Array<RejectedSubscription>, } | { "type": "unsubscribed", subscription: SubscriptionType, } | { "type": "subscriptions", subscriptions: Array<SubscriptionType>, } | { "type": "unsubscribed_all", subscriptions: Array<SubscriptionType>, } | { "type": "pong", server_time: string, client_time?: number, } | { "type": "server_time", server_time: string, } | { "type": "format", format: WireFormat, } | { "type": "resumed", topic: string, replayed: number, complete: boolean, } | { "type": "authenticated", scope: ApiKeyScope, } | { "type": "error", message: string, code: ErrorCode, };
+
+/**
+ * A subscription refused by subscribe_many, as the client sent it
+ */
+export type RejectedSubscription = { subscription: unknown, error: string, code: ErrorCode, };
+
+/**
+ * Machine-readable reason of an error
+ */
+export type ErrorCode = "invalid_message" | "invalid_subscription" | "invalid_interval" | "invalid_parameter" | "invalid_topic" | "unknown_token" | "subscription_limit" | "rate_limited" | "auth_required" | "auth_failed" | "forbidden";
+
+/**
+ * Notice of the server's state, sent to every session whatever it subscribes to
+ */
+export type SystemStatus = { status: SystemState, 
+/**
+ * When the maintenance starts or the sessions are closed, if known
+ */
+deadline?: string, 
+/**
+ * Explanation for users
+ */
+message?: string, };
+
+/**
+ * State of the server as announced to clients
+ */
+export type SystemState = "normal" | "maintenance" | "shutting_down";
+
+/**
+ * Access granted to an API key
+ */
+export type ApiKeyScope = "read" | "admin";
+
+/**
+ * K-line (candlestick) data structure
+ *
+ * Serialized with the derived `close_time` and `is_final` fields as well,
+ * see [`SerializedKLine`].
+ */
+export type KLine = { 
+/**
+ * Token symbol (e.g., "DOGE", "SHIB")
+ */
+token: string, 
+/**
+ * Start of the interval
+ */
+timestamp: string, 
+/**
+ * Exclusive end of the interval; just after the latest trade for trade-count and volume candles
+ */
+close_time: string, interval: TimeInterval, open: number, high: number, low: number, 
+/**
+ * Closing price (current price for open intervals)
+ */
+close: number, volume: number, 
+/**
+ * Volume of trades whose aggressor bought
+ */
+buy_volume: number, 
+/**
+ * Volume of trades whose aggressor sold
+ */
+sell_volume: number, 
+/**
+ * Same as `is_final`, kept for existing clients
+ */
+is_closed: boolean, 
+/**
+ * Whether the candle is complete and will not change any more
+ */
+is_final: boolean, 
+/**
+ * Whether the candle was closed before its interval completed, at shutdown
+ */
+is_partial?: boolean, 
+/**
+ * Time of the latest trade, for trade-count and volume candles
+ */
+last_trade_at?: string, };
+
+/**
+ * Time intervals for K-line data
+ *
+ * Besides time-aligned intervals, candles can be cut by activity: every
+ * `N` trades (`"100t"`) or every `N` units of base volume (`"5000v"`).
+ */
+export type TimeInterval = string;
+
+/**
+ * Transaction data structure for generating K-lines
+ *
+ * Deserialization rejects transactions that fail [`Transaction::validate`].
+ */
+export type Transaction = { 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Transaction price
+ */
+price: number, 
+/**
+ * Transaction volume
+ */
+volume: number, 
+/**
+ * Transaction timestamp
+ */
+timestamp: string, 
+/**
+ * Side of the aggressor
+ */
+side: Side, 
+/**
+ * Whether the reporting party provided liquidity, when the venue says so
+ */
+is_maker?: boolean | null, 
+/**
+ * Venue trade ID, used to drop replayed or retried trades
+ */
+trade_id?: TradeId | null, 
+/**
+ * Ingestion source that reported the trade, such as `mock` or a venue name
+ */
+source?: string | null, };
+
+/**
+ * Side of the aggressor, the party whose order took liquidity
+ */
+export type Side = "buy" | "sell";
+
+/**
+ * Identifier a venue assigns to a trade, numeric or opaque
+ */
+export type TradeId = number | string;
+
+/**
+ * Consecutive trades of a token at the same price and side, combined
+ */
+export type AggTrade = { 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Price shared by the trades
+ */
+price: number, 
+/**
+ * Summed volume of the trades
+ */
+volume: number, 
+/**
+ * Number of trades combined
+ */
+trades: number, 
+/**
+ * Time of the first trade
+ */
+first_trade_at: string, 
+/**
+ * Time of the last trade
+ */
+last_trade_at: string, 
+/**
+ * Side of the trades' aggressor
+ */
+side: Side, };
+
+/**
+ * Full order book of a token, best prices first
+ */
+export type DepthSnapshot = { token: string, 
+/**
+ * ID of the last update applied to the book
+ */
+update_id: number, bids: Array<PriceLevel>, asks: Array<PriceLevel>, timestamp: string, };
+
+/**
+ * Levels of a token's order book changed by one trade, best prices first
+ */
+export type DepthUpdate = { token: string, 
+/**
+ * One more than the ID of the previous update of the book
+ */
+update_id: number, bids: Array<PriceLevel>, asks: Array<PriceLevel>, timestamp: string, };
+
+/**
+ * Resting quantity at a price
+ */
+export type PriceLevel = { price: number, 
+/**
+ * Quantity at the price; 0 in an update means the level was removed
+ */
+quantity: number, };
+
+/**
+ * One time bucket of server metrics pushed to ops dashboards
+ */
+export type OpsMetrics = { 
+/**
+ * End of the bucket
+ */
+timestamp: string, 
+/**
+ * Transactions ingested per second
+ */
+ingest_rate: number, 
+/**
+ * Transactions broadcast per second
+ */
+transaction_broadcast_rate: number, 
+/**
+ * K-line updates broadcast per second
+ */
+kline_broadcast_rate: number, 
+/**
+ * Events queued for the slowest stream subscriber
+ */
+event_queue_depth: number, 
+/**
+ * Connected WebSocket sessions
+ */
+sessions: number, 
+/**
+ * Active WebSocket subscriptions
+ */
+subscriptions: number, 
+/**
+ * Messages dropped for slow WebSocket sessions since startup
+ */
+dropped_messages: number, 
+/**
+ * Sessions disconnected for falling behind since startup
+ */
+slow_consumer_disconnects: number, };
+
+/**
+ * Notice that a transaction referenced an unconfigured token
+ */
+export type NewTokenEvent = { 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Action taken under the configured policy
+ */
+action: TokenAction, 
+/**
+ * When the token was first seen
+ */
+timestamp: string, };
+
+/**
+ * What the registry did with a token the first time it was seen
+ */
+export type TokenAction = "registered" | "quarantined" | "rejected";
+
+/**
+ * Notice that a token was listed or delisted at runtime
+ */
+export type ListingEvent = { 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Base price trades are generated around; absent for delistings
+ */
+base_price?: number, 
+/**
+ * When the token was listed or delisted
+ */
+timestamp: string, };
+
+/**
+ * Notification that an alert's condition was met
+ */
+export type AlertTrigger = { 
+/**
+ * ID of the alert that triggered
+ */
+alert_id: string, 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Condition that was met
+ */
+condition: AlertCondition, 
+/**
+ * Price of the transaction that met the condition
+ */
+price: number, 
+/**
+ * Time of the transaction that met the condition
+ */
+triggered_at: string, };
+
+/**
+ * Price condition an alert waits for
+ */
+export type AlertCondition = { "type": "above", price: number, } | { "type": "below", price: number, } | { "type": "percent_move", percent: number, window_secs: number, };
+
+/**
+ * Indicator value pushed to WebSocket subscribers as a candle updates and when it closes
+ */
+export type IndicatorUpdate = { token: string, interval: TimeInterval, 
+/**
+ * Name of the indicator, such as `rsi`
+ */
+indicator: string, 
+/**
+ * Period of the indicator, or the slow period of MACD
+ */
+period: number, 
+/**
+ * Open time of the candle
+ */
+timestamp: string, 
+/**
+ * The indicator, the MACD line, or the middle Bollinger Band
+ */
+value: number, 
+/**
+ * MACD signal line
+ */
+signal?: number, 
+/**
+ * MACD minus signal
+ */
+histogram?: number, 
+/**
+ * Upper Bollinger Band
+ */
+upper?: number, 
+/**
+ * Lower Bollinger Band
+ */
+lower?: number, 
+/**
+ * Whether the candle is closed; values at an open candle change with it
+ */
+is_closed: boolean, };
+
+/**
+ * Indicator a WebSocket subscription streams
+ */
+export type IndicatorKind = "rsi" | "sma" | "ema" | "atr" | "volatility" | "macd" | "bollinger";
+
+/**
+ * A Renko brick or range bar
+ */
+export type Bar = { token: string, bar_type: BarType, 
+/**
+ * Brick size or range the bar was built with
+ */
+size: number, 
+/**
+ * Time of the first trade in the bar
+ */
+open_time: string, 
+/**
+ * Time of the trade that completed the bar
+ */
+close_time: string, open: number, high: number, low: number, close: number, volume: number, trade_count: number, };
+
+/**
+ * Kind of price-based bar
+ */
+export type BarType = "renko" | "range";
+
+/**
+ * How stored candles are presented to clients
+ */
+export type CandleType = "regular" | "heikin_ashi";
+
+/**
+ * Buy and sell pressure of a token over one candle
+ */
+export type VolumeDelta = { token: string, interval: TimeInterval, 
+/**
+ * Start of the candle
+ */
+timestamp: string, 
+/**
+ * Volume of trades whose aggressor bought
+ */
+buy_volume: number, 
+/**
+ * Volume of trades whose aggressor sold
+ */
+sell_volume: number, 
+/**
+ * Buy volume minus sell volume
+ */
+delta: number, 
+/**
+ * Sum of the deltas of this candle and every earlier one in the series
+ */
+cumulative_delta: number, is_closed: boolean, };
+
+/**
+ * Rolling 24-hour price statistics for a token
+ */
+export type Ticker = { 
+/**
+ * Token symbol
+ */
+token: string, 
+/**
+ * Latest traded price
+ */
+last_price: number, 
+/**
+ * Price at the start of the window
+ */
+open_price: number, 
+/**
+ * Highest price in the window
+ */
+high: number, 
+/**
+ * Lowest price in the window
+ */
+low: number, 
+/**
+ * Volume traded in the window
+ */
+volume: number, 
+/**
+ * Change from the open price
+ */
+price_change: number, 
+/**
+ * Change from the open price, in percent
+ */
+price_change_percent: number, 
+/**
+ * When the statistics were computed
+ */
+timestamp: string, 
+/**
+ * All-time and rolling highs and lows, when the token's trades were tracked
+ */
+extremes?: PriceExtremes, };
+
+/**
+ * All-time and rolling price extremes of a token
+ *
+ * Rolling highs and lows are missing for windows without trades.
+ */
+export type PriceExtremes = { 
+/**
+ * Highest price ever traded
+ */
+all_time_high: number, 
+/**
+ * When the all-time high was first traded
+ */
+all_time_high_at: string, 
+/**
+ * Lowest price ever traded
+ */
+all_time_low: number, 
+/**
+ * When the all-time low was first traded
+ */
+all_time_low_at: string, high_1h: number | null, low_1h: number | null, high_24h: number | null, low_24h: number | null, high_7d: number | null, low_7d: number | null, };
+
+/**
+ * Outcome of reloading the configuration files
+ */
+export type ConfigUpdate = { 
+/**
+ * Changed fields whose new values were applied
+ */
+applied: Array<string>, 
+/**
+ * Changed fields that need a restart and were ignored
+ */
+rejected: Array<string>, 
+/**
+ * When the configuration was reloaded
+ */
+timestamp: string, };
+
+export type Envelope = ServerMessage & { topic?: string, seq?: number, ref?: RequestId, };
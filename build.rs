@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC service is only built with the server
+    #[cfg(feature = "server")]
+    {
+        // Use the vendored protoc so builds don't depend on a system install
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::compile_protos("proto/kline.proto")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use ring::digest::{digest, SHA256};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::config::{ApiKeyScope, Config};
+
+/// Header carrying the API key on REST requests and WebSocket handshakes
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Reason a request was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// No key was presented and anonymous access is not allowed
+    Missing,
+    /// The presented key is not configured
+    Invalid,
+    /// The key does not grant the required scope
+    Forbidden,
+    /// The presented JSON Web Token is malformed, badly signed or expired
+    InvalidToken,
+}
+
+impl AuthError {
+    /// Client-facing description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            AuthError::Missing => "API key required",
+            AuthError::Invalid => "Invalid API key",
+            AuthError::Forbidden => "Admin scope required",
+            AuthError::InvalidToken => "Invalid or expired token",
+        }
+    }
+
+    /// REST response for the error
+    pub fn response(&self) -> HttpResponse {
+        let body = json!({ "error": self.message() });
+        match self {
+            AuthError::Forbidden => HttpResponse::Forbidden().json(body),
+            _ => HttpResponse::Unauthorized().json(body),
+        }
+    }
+}
+
+/// Checks API keys against the configured scopes
+#[derive(Debug, Clone, Default)]
+pub struct Authenticator {
+    /// Whether keys are required at all
+    enabled: bool,
+    /// Whether requests without a key get read-only access
+    allow_anonymous: bool,
+    /// SHA-256 digest of each key and the scope it grants
+    keys: Vec<(KeyDigest, ApiKeyScope)>,
+    /// Secret of the accepted HS256 JSON Web Tokens, if any
+    jwt_secret: Option<Vec<u8>>,
+}
+
+/// SHA-256 digest of an API key, so keys of any length compare in constant time
+type KeyDigest = [u8; 32];
+
+fn key_digest(key: &str) -> KeyDigest {
+    let mut key_digest = [0; 32];
+    key_digest.copy_from_slice(digest(&SHA256, key.as_bytes()).as_ref());
+    key_digest
+}
+
+/// Compare two digests without stopping at the first differing byte
+fn digests_equal(a: &KeyDigest, b: &KeyDigest) -> bool {
+    let difference = a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b));
+    std::hint::black_box(difference) == 0
+}
+
+/// Claims read from a JSON Web Token
+///
+/// `exp` is required and checked by the decoder.
+#[derive(Debug, Deserialize)]
+struct TokenClaims {
+    /// Access granted to the bearer; read-only when absent
+    #[serde(default)]
+    scope: ApiKeyScope,
+}
+
+impl Authenticator {
+    pub fn new(
+        enabled: bool,
+        allow_anonymous: bool,
+        keys: impl IntoIterator<Item = (String, ApiKeyScope)>,
+    ) -> Self {
+        Self {
+            enabled,
+            allow_anonymous,
+            keys: keys.into_iter().map(|(key, scope)| (key_digest(&key), scope)).collect(),
+            jwt_secret: None,
+        }
+    }
+
+    /// Also accept HS256 JSON Web Tokens signed with `secret`
+    pub fn with_jwt_secret(mut self, secret: Option<&str>) -> Self {
+        self.jwt_secret = secret.map(|secret| secret.as_bytes().to_vec());
+        self
+    }
+
+    /// Create an authenticator from the auth section
+    ///
+    /// `admin.api_key` is accepted as an admin-scoped key.
+    pub fn from_config(config: &Config) -> Self {
+        let keys = config
+            .auth
+            .keys
+            .iter()
+            .map(|key| (key.key.clone(), key.scope))
+            .chain(
+                config
+                    .admin
+                    .api_key
+                    .iter()
+                    .map(|key| (key.clone(), ApiKeyScope::Admin)),
+            );
+        Self::new(config.auth.enabled, config.auth.allow_anonymous, keys)
+            .with_jwt_secret(config.auth.jwt_secret.as_deref())
+    }
+
+    /// Whether keys are required
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Get the scope granted to a key
+    ///
+    /// Every configured key is compared in constant time, so response timing
+    /// does not reveal how much of a key was guessed. A key configured twice
+    /// grants the scope it was given last.
+    pub fn scope_of(&self, key: &str) -> Option<ApiKeyScope> {
+        let presented = key_digest(key);
+        self.keys.iter().fold(None, |granted, (key, scope)| {
+            if digests_equal(key, &presented) {
+                Some(*scope)
+            } else {
+                granted
+            }
+        })
+    }
+
+    /// Get the scope granted by a JSON Web Token
+    ///
+    /// The token must be signed with the configured secret and carry an `exp`
+    /// claim in the future; its optional `scope` claim defaults to `read`.
+    pub fn scope_of_token(&self, token: &str) -> Result<ApiKeyScope, AuthError> {
+        let secret = self.jwt_secret.as_ref().ok_or(AuthError::InvalidToken)?;
+        jsonwebtoken::decode::<TokenClaims>(
+            token,
+            &DecodingKey::from_secret(secret),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map(|data| data.claims.scope)
+        .map_err(|_| AuthError::InvalidToken)
+    }
+
+    /// Whether any key grants admin access
+    pub fn has_admin_keys(&self) -> bool {
+        self.keys.iter().any(|(_, scope)| *scope == ApiKeyScope::Admin)
+    }
+
+    /// Check a presented key against the scope a route requires
+    ///
+    /// Returns the key's scope, or `None` for anonymous access. Admin routes
+    /// need an admin key even when keys are not required for the rest.
+    pub fn authorize(
+        &self,
+        key: Option<&str>,
+        required: ApiKeyScope,
+    ) -> Result<Option<ApiKeyScope>, AuthError> {
+        if !self.enabled && required == ApiKeyScope::Read {
+            return Ok(key.and_then(|key| self.scope_of(key)));
+        }
+
+        match key {
+            Some(key) => {
+                let scope = self.scope_of(key).ok_or(AuthError::Invalid)?;
+                if scope < required {
+                    return Err(AuthError::Forbidden);
+                }
+                Ok(Some(scope))
+            }
+            None if self.allow_anonymous && required == ApiKeyScope::Read => Ok(None),
+            None => Err(AuthError::Missing),
+        }
+    }
+}
+
+/// Middleware requiring an API key on REST routes
+///
+/// Routes under `/admin/` need an admin key; the health check stays open for
+/// load balancers. Requests pass through when no authenticator is registered.
+pub async fn require_api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let result = match req.app_data::<web::Data<Arc<Authenticator>>>() {
+        Some(authenticator) if !req.path().ends_with("/health") => {
+            let required = if req.path().contains("/admin/") {
+                ApiKeyScope::Admin
+            } else {
+                ApiKeyScope::Read
+            };
+            let key = req
+                .headers()
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok());
+            authenticator.authorize(key, required).map(|_| ())
+        }
+        _ => Ok(()),
+    };
+
+    match result {
+        Ok(()) => next.call(req).await.map(ServiceResponse::map_into_left_body),
+        Err(e) => Ok(req.into_response(e.response()).map_into_right_body()),
+    }
+}
@@ -0,0 +1,27 @@
+use actix_cors::Cors;
+
+use crate::api::request_id::REQUEST_ID_HEADER;
+use crate::config::CorsConfig;
+
+/// CORS middleware of the REST API and the WebSocket upgrade
+///
+/// Requests from other origins are still served; browsers refuse responses
+/// lacking the CORS headers, which are only sent to allowed origins.
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .expose_headers([REQUEST_ID_HEADER])
+        .max_age(config.max_age_secs);
+    if config.any_origin() {
+        cors = cors.allow_any_origin();
+    } else {
+        for origin in &config.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+    cors
+}
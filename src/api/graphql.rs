@@ -0,0 +1,146 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use async_graphql::{Context, EmptyMutation, Error, Object, Schema, SimpleObject, Subscription};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use chrono::{DateTime, Duration, Utc};
+use futures::Stream;
+use std::sync::Arc;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+
+use crate::api::{StreamEvent, WsManager};
+use crate::models::{KLine, Symbol, Ticker, TimeInterval};
+use crate::services::KLineService;
+
+/// Default number of K-lines returned by the klines query
+const DEFAULT_LIMIT: usize = 100;
+/// Maximum number of K-lines returned by the klines query
+const MAX_LIMIT: usize = 1000;
+
+/// GraphQL schema sharing the K-line service and broadcasts with the HTTP server
+pub type KLineSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+/// Build the GraphQL schema on top of the shared services
+pub fn build_schema(kline_service: Arc<KLineService>, ws_manager: Arc<WsManager>) -> KLineSchema {
+    Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot)
+        .data(kline_service)
+        .data(ws_manager)
+        .finish()
+}
+
+/// A K-line as exposed over GraphQL
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Candle {
+    pub token: String,
+    pub interval: String,
+    pub open_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub is_closed: bool,
+}
+
+impl From<KLine> for Candle {
+    fn from(kline: KLine) -> Self {
+        Self {
+            token: kline.token,
+            interval: kline.interval.as_str().to_string(),
+            open_time: kline.timestamp,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            buy_volume: kline.buy_volume,
+            sell_volume: kline.sell_volume,
+            is_closed: kline.is_closed,
+        }
+    }
+}
+
+/// Parse an interval string into a TimeInterval
+fn parse_interval(interval: &str) -> async_graphql::Result<TimeInterval> {
+    interval
+        .parse()
+        .map_err(|_| Error::new("Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h"))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// K-lines for a token, the newest `limit` within the range in ascending order
+    async fn klines(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        interval: String,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<u32>,
+    ) -> async_graphql::Result<Vec<Candle>> {
+        let interval = parse_interval(&interval)?;
+        let token = Symbol::canonicalize(&token);
+        let end = end.unwrap_or_else(Utc::now);
+        let start = start.unwrap_or(end - Duration::hours(24));
+        let limit = limit.map_or(DEFAULT_LIMIT, |limit| limit as usize).min(MAX_LIMIT);
+
+        let klines = ctx
+            .data_unchecked::<Arc<KLineService>>()
+            .get_klines(&token, interval, start, end, None);
+        let skip = klines.len().saturating_sub(limit);
+        Ok(klines.into_iter().skip(skip).map(Candle::from).collect())
+    }
+
+    /// Tokens with K-line data
+    async fn tokens(&self, ctx: &Context<'_>) -> Vec<String> {
+        ctx.data_unchecked::<Arc<KLineService>>().get_available_tokens()
+    }
+
+    /// 24-hour statistics for a token
+    async fn ticker(&self, ctx: &Context<'_>, token: String) -> Option<Ticker> {
+        ctx.data_unchecked::<Arc<KLineService>>().get_ticker(&Symbol::canonicalize(&token))
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Live K-line updates for a token and interval
+    async fn klines(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        interval: String,
+    ) -> async_graphql::Result<impl Stream<Item = Candle>> {
+        let interval = parse_interval(&interval)?;
+        let token = Symbol::canonicalize(&token);
+        let events = ctx.data_unchecked::<Arc<WsManager>>().subscribe_events();
+
+        // Lagged receivers skip the missed updates rather than ending the stream
+        Ok(BroadcastStream::new(events).filter_map(move |event| match event {
+            Ok(StreamEvent::KLine(kline)) if kline.token == token && kline.interval == interval => {
+                Some(kline.into())
+            }
+            _ => None,
+        }))
+    }
+}
+
+/// Execute a GraphQL query
+pub async fn graphql(schema: web::Data<KLineSchema>, req: GraphQLRequest) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+/// Serve GraphQL subscriptions over the graphql-ws protocols
+pub async fn graphql_ws(
+    schema: web::Data<KLineSchema>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse> {
+    GraphQLSubscription::new(KLineSchema::clone(&schema)).start(&req, payload)
+}
@@ -0,0 +1,195 @@
+// tonic::Status is large, but it is the error type every RPC has to return
+#![allow(clippy::result_large_err)]
+
+use chrono::{DateTime, Duration, Utc};
+use futures::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use tonic::{Request, Response, Status};
+
+use crate::api::{StreamEvent, WsManager};
+use crate::models::{KLine, Side, Symbol, TimeInterval, Transaction};
+use crate::services::KLineService;
+
+/// Generated protobuf types and service definitions
+pub mod proto {
+    tonic::include_proto!("kline.v1");
+}
+
+use proto::k_line_api_server::{KLineApi, KLineApiServer};
+
+/// Default number of K-lines returned by GetKlines
+const DEFAULT_LIMIT: u32 = 100;
+/// Maximum number of K-lines returned by GetKlines
+const MAX_LIMIT: u32 = 1000;
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+impl From<KLine> for proto::Kline {
+    fn from(kline: KLine) -> Self {
+        Self {
+            token: kline.token,
+            interval: kline.interval.as_str().to_string(),
+            open_time: kline.timestamp.timestamp_millis(),
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            is_closed: kline.is_closed,
+            buy_volume: kline.buy_volume,
+            sell_volume: kline.sell_volume,
+        }
+    }
+}
+
+impl From<Side> for proto::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => proto::Side::Buy,
+            Side::Sell => proto::Side::Sell,
+        }
+    }
+}
+
+impl From<Transaction> for proto::Trade {
+    fn from(transaction: Transaction) -> Self {
+        Self {
+            token: transaction.token,
+            price: transaction.price,
+            volume: transaction.volume,
+            timestamp: transaction.timestamp.timestamp_millis(),
+            is_buy: transaction.side == Side::Buy,
+            side: proto::Side::from(transaction.side).into(),
+            is_maker: transaction.is_maker,
+            source: transaction.source,
+        }
+    }
+}
+
+/// gRPC service sharing the K-line service and broadcasts with the HTTP server
+pub struct GrpcService {
+    kline_service: Arc<KLineService>,
+    ws_manager: Arc<WsManager>,
+}
+
+impl GrpcService {
+    pub fn new(kline_service: Arc<KLineService>, ws_manager: Arc<WsManager>) -> Self {
+        Self {
+            kline_service,
+            ws_manager,
+        }
+    }
+
+    /// Wrap this service in the generated tonic server
+    pub fn into_server(self) -> KLineApiServer<Self> {
+        KLineApiServer::new(self)
+    }
+
+    fn subscribe_events(&self) -> BroadcastStream<StreamEvent> {
+        BroadcastStream::new(self.ws_manager.subscribe_events())
+    }
+}
+
+/// Parse an interval string into a TimeInterval
+fn parse_interval(interval: &str) -> Result<TimeInterval, Status> {
+    interval.parse().map_err(|_| {
+        Status::invalid_argument("Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h")
+    })
+}
+
+/// Convert milliseconds since epoch into a timestamp
+fn parse_millis(millis: i64) -> Result<DateTime<Utc>, Status> {
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| Status::invalid_argument("Timestamp out of range"))
+}
+
+#[tonic::async_trait]
+impl KLineApi for GrpcService {
+    async fn get_klines(
+        &self,
+        request: Request<proto::GetKlinesRequest>,
+    ) -> Result<Response<proto::GetKlinesResponse>, Status> {
+        let request = request.into_inner();
+        let interval = parse_interval(&request.interval)?;
+
+        let end = match request.end_time {
+            Some(millis) => parse_millis(millis)?,
+            None => Utc::now(),
+        };
+        let start = match request.start_time {
+            Some(millis) => parse_millis(millis)?,
+            None => end - Duration::hours(24),
+        };
+        let limit = request.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+        let klines = self
+            .kline_service
+            .get_klines(&Symbol::canonicalize(&request.token), interval, start, end, Some(limit))
+            .into_iter()
+            .map(proto::Kline::from)
+            .collect();
+
+        Ok(Response::new(proto::GetKlinesResponse { klines }))
+    }
+
+    async fn get_latest(
+        &self,
+        request: Request<proto::GetLatestRequest>,
+    ) -> Result<Response<proto::Kline>, Status> {
+        let request = request.into_inner();
+        let interval = parse_interval(&request.interval)?;
+
+        self.kline_service
+            .get_latest_kline(&Symbol::canonicalize(&request.token), interval)
+            .map(|kline| Response::new(kline.into()))
+            .ok_or_else(|| {
+                Status::not_found("No K-line data found for the specified token and interval")
+            })
+    }
+
+    type SubscribeKlinesStream = ResponseStream<proto::Kline>;
+
+    async fn subscribe_klines(
+        &self,
+        request: Request<proto::SubscribeKlinesRequest>,
+    ) -> Result<Response<Self::SubscribeKlinesStream>, Status> {
+        let request = request.into_inner();
+        let interval = parse_interval(&request.interval)?;
+        let token = Symbol::canonicalize(&request.token);
+
+        // Lagged receivers skip the missed updates rather than failing the stream
+        let stream = self.subscribe_events().filter_map(move |event| match event {
+            Ok(StreamEvent::KLine(kline))
+                if kline.token == token && kline.interval == interval =>
+            {
+                Some(Ok(kline.into()))
+            }
+            _ => None,
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    type SubscribeTradesStream = ResponseStream<proto::Trade>;
+
+    async fn subscribe_trades(
+        &self,
+        request: Request<proto::SubscribeTradesRequest>,
+    ) -> Result<Response<Self::SubscribeTradesStream>, Status> {
+        let tokens: Vec<String> = request.into_inner().tokens.iter().map(|token| Symbol::canonicalize(token)).collect();
+
+        let stream = self.subscribe_events().filter_map(move |event| match event {
+            Ok(StreamEvent::Transaction(transaction))
+                if tokens.is_empty() || tokens.contains(&transaction.token) =>
+            {
+                Some(Ok(transaction.into()))
+            }
+            _ => None,
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
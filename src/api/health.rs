@@ -0,0 +1,178 @@
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::api::{openapi, WsManager};
+use crate::config::Config;
+use crate::services::{KLineService, Metrics, ReplicationState};
+
+/// Outcome of one readiness check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failing,
+    /// The dependency is not used by this instance
+    Skipped,
+}
+
+/// Status of one dependency, with a human-readable explanation
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Check {
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Status of each dependency readiness depends on
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    /// Transactions keep arriving from the generator, replay or primary
+    pub ingestion: Check,
+    /// Candle storage answers, and snapshots can be written
+    pub storage: Check,
+    /// The WebSocket manager answers and can take more sessions
+    pub websocket: Check,
+}
+
+impl ReadinessChecks {
+    /// Whether no check is failing
+    pub fn ready(&self) -> bool {
+        [&self.ingestion, &self.storage, &self.websocket]
+            .iter()
+            .all(|check| check.status != CheckStatus::Failing)
+    }
+}
+
+/// Check that a transaction was ingested within `max_age_secs`
+///
+/// Only applies when transactions are expected: a source is enabled or the
+/// instance replicates a primary.
+pub fn check_ingestion(
+    expected: bool,
+    last_ingest_at: Option<DateTime<Utc>>,
+    max_age_secs: u64,
+    now: DateTime<Utc>,
+) -> Check {
+    if !expected {
+        return Check::new(CheckStatus::Skipped, "No transaction source is enabled");
+    }
+    let Some(last_ingest_at) = last_ingest_at else {
+        return Check::new(CheckStatus::Failing, "No transaction ingested yet");
+    };
+    let age = (now - last_ingest_at).num_seconds().max(0);
+    if age as u64 > max_age_secs {
+        Check::new(
+            CheckStatus::Failing,
+            format!("Last transaction ingested {}s ago, more than {}s", age, max_age_secs),
+        )
+    } else {
+        Check::new(CheckStatus::Ok, format!("Last transaction ingested {}s ago", age))
+    }
+}
+
+/// Check the in-memory candle storage and the directory snapshots are written to
+fn check_storage(kline_service: &KLineService, snapshot_path: Option<&str>) -> Check {
+    let tokens = kline_service.get_available_tokens().len();
+    let directory = snapshot_path
+        .map(|path| Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new(".")));
+    // A missing directory is created with the first snapshot
+    match directory.map(|directory| (directory, directory.metadata())) {
+        Some((directory, Ok(metadata))) if !metadata.is_dir() || metadata.permissions().readonly() => Check::new(
+            CheckStatus::Failing,
+            format!("Snapshot directory {} is not writable", directory.display()),
+        ),
+        _ => Check::new(CheckStatus::Ok, format!("In memory, {} tokens", tokens)),
+    }
+}
+
+/// Check the WebSocket manager answers and has room for more sessions
+fn check_websocket(ws_manager: &WsManager, max_connections: usize) -> Check {
+    let sessions = ws_manager.session_count();
+    let status = if sessions >= max_connections {
+        CheckStatus::Failing
+    } else {
+        CheckStatus::Ok
+    };
+    Check::new(status, format!("{} of {} sessions", sessions, max_connections))
+}
+
+/// Liveness probe: the process is up and serving requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "service",
+    responses((status = 200, description = "The service is up", body = openapi::HealthResponse))
+)]
+pub async fn liveness() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "healthy",
+        "service": "k-line-data-service",
+        "timestamp": Utc::now().to_rfc3339()
+    })))
+}
+
+/// Readiness probe: transactions are flowing and dependencies answer
+///
+/// Responds 503 while any check is failing, so orchestrators hold traffic
+/// back. Checks of services that are not registered are skipped.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "service",
+    responses(
+        (status = 200, description = "Every check passes", body = openapi::ReadinessResponse),
+        (status = 503, description = "A check is failing", body = openapi::ReadinessResponse),
+    )
+)]
+pub async fn readiness(
+    kline_service: web::Data<Arc<KLineService>>,
+    ws_manager: Option<web::Data<Arc<WsManager>>>,
+    metrics: Option<web::Data<Arc<Metrics>>>,
+    replication: Option<web::Data<Arc<ReplicationState>>>,
+    config: Option<web::Data<Config>>,
+) -> Result<HttpResponse> {
+    let config = config.map(|config| config.into_inner()).unwrap_or_default();
+    let standby = replication.is_some_and(|replication| !replication.is_primary());
+
+    let checks = ReadinessChecks {
+        ingestion: match metrics {
+            Some(metrics) => check_ingestion(
+                config.data_generation.enabled || standby,
+                metrics.last_ingest_at(),
+                config.health.max_ingest_age_secs,
+                Utc::now(),
+            ),
+            None => Check::new(CheckStatus::Skipped, "Ingestion is not measured"),
+        },
+        storage: check_storage(&kline_service, config.snapshot.path.as_deref()),
+        websocket: match ws_manager {
+            Some(ws_manager) => check_websocket(&ws_manager, config.performance.max_websocket_connections),
+            None => Check::new(CheckStatus::Skipped, "WebSocket streaming is not served"),
+        },
+    };
+
+    let ready = checks.ready();
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    Ok(response.json(json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "checks": checks,
+        "timestamp": Utc::now().to_rfc3339()
+    })))
+}
@@ -1,6 +1,15 @@
+pub mod auth;
+pub mod cors;
+pub mod graphql;
+pub mod grpc;
+pub mod health;
+pub mod openapi;
+pub mod rate_limit;
+pub mod request_id;
 pub mod rest;
+pub mod sse;
 pub mod websocket;
 
 // Re-export for convenience
 pub use rest::configure_routes;
-pub use websocket::{configure_websocket_routes, WsManager};
+pub use websocket::{configure_websocket_routes, StreamEvent, WsManager};
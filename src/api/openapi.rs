@@ -0,0 +1,407 @@
+use actix_web::{HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::api::auth::API_KEY_HEADER;
+use crate::api::{health, rest, sse};
+use crate::config::ReplicationRole;
+use crate::services::bars::{Bar, BarType};
+use crate::services::delta::VolumeDelta;
+use crate::services::indicators::{BollingerPoint, IndicatorPoint, MacdPoint, VolatilityPoint};
+use crate::services::replication::ReplicationStatus;
+use crate::services::resample::ResampledKLine;
+use crate::models::{KLine, Transaction};
+
+/// OpenAPI description of the REST API
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "K-line Data Service"),
+    paths(
+        rest::get_klines,
+        rest::get_klines_batch,
+        rest::export_klines,
+        rest::get_latest_kline,
+        rest::get_current_kline,
+        rest::resample_klines,
+        rest::get_ohlc,
+        rest::get_ticker,
+        rest::get_index,
+        rest::get_trades,
+        rest::get_depth,
+        rest::get_volume_profile,
+        rest::get_moving_average,
+        rest::get_rsi,
+        rest::get_macd,
+        rest::get_bollinger_bands,
+        rest::get_atr,
+        rest::get_volatility,
+        rest::get_delta,
+        rest::get_correlation,
+        rest::get_renko,
+        rest::get_range_bars,
+        rest::create_alert,
+        rest::list_alerts,
+        rest::get_alert,
+        rest::delete_alert,
+        rest::get_tokens,
+        rest::get_symbols,
+        health::liveness,
+        health::readiness,
+        rest::get_stats,
+        rest::get_info,
+        rest::get_replication_checksums,
+        rest::promote,
+        rest::write_snapshot,
+        rest::get_token_registry,
+        rest::register_token,
+        rest::list_token,
+        rest::delist_token,
+        rest::list_sessions,
+        rest::disconnect_session,
+        rest::set_system_status,
+        rest::amend_kline,
+        rest::verify_klines,
+        rest::get_market,
+        rest::set_market_regime,
+        rest::create_market_event,
+        sse::stream_events,
+    ),
+    modifiers(&ApiKeyAuth),
+    security(("api_key" = [])),
+)]
+pub struct ApiDoc;
+
+/// Declares the `X-API-Key` header used when `[auth]` is enabled
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+    }
+}
+
+/// Error returned by every endpoint
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// A page of candles for one token
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KLinesResponse {
+    pub token: String,
+    pub interval: String,
+    pub data: Vec<KLine>,
+    /// Value for `before` or `after` to fetch the following page, if any
+    pub next_cursor: Option<i64>,
+}
+
+/// Candles rolled up into an interval that is not stored
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResampledKLinesResponse {
+    pub token: String,
+    /// Stored interval the candles were built from
+    pub source: String,
+    pub target: String,
+    pub data: Vec<ResampledKLine>,
+}
+
+/// A page of candles for several tokens
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KLinesBatchResponse {
+    pub interval: String,
+    /// Candles per token
+    pub data: BTreeMap<String, Vec<KLine>>,
+    /// Cursor for the following page per token
+    pub next_cursors: BTreeMap<String, Option<i64>>,
+}
+
+/// A single candle
+#[derive(Debug, Serialize, ToSchema)]
+pub struct KLineResponse {
+    pub token: String,
+    pub interval: String,
+    pub data: KLine,
+}
+
+/// The open candle
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CurrentKLineResponse {
+    pub token: String,
+    pub interval: String,
+    pub data: KLine,
+    pub is_open: bool,
+}
+
+/// Recent trades for a token
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TradesResponse {
+    pub token: String,
+    pub count: usize,
+    pub data: Vec<Transaction>,
+}
+
+/// Moving average of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MovingAverageResponse {
+    pub token: String,
+    pub interval: String,
+    /// sma or ema
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub period: usize,
+    pub data: Vec<IndicatorPoint>,
+}
+
+/// Relative strength index of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RsiResponse {
+    pub token: String,
+    pub interval: String,
+    pub period: usize,
+    pub data: Vec<IndicatorPoint>,
+}
+
+/// MACD of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MacdResponse {
+    pub token: String,
+    pub interval: String,
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+    pub data: Vec<MacdPoint>,
+}
+
+/// Bollinger Bands of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BollingerResponse {
+    pub token: String,
+    pub interval: String,
+    pub period: usize,
+    pub std_dev: f64,
+    pub data: Vec<BollingerPoint>,
+}
+
+/// Average true range of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AtrResponse {
+    pub token: String,
+    pub interval: String,
+    pub period: usize,
+    pub data: Vec<IndicatorPoint>,
+}
+
+/// Realized volatility of a token's closed candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VolatilityResponse {
+    pub token: String,
+    pub interval: String,
+    pub period: usize,
+    pub data: Vec<VolatilityPoint>,
+}
+
+/// Buy and sell volume of a token's candles
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeltaResponse {
+    pub token: String,
+    pub interval: String,
+    pub data: Vec<VolumeDelta>,
+}
+
+/// Renko bricks or range bars of a token
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BarsResponse {
+    pub token: String,
+    pub bar_type: BarType,
+    /// Brick size or range, resolved from the ATR when not given
+    pub size: f64,
+    pub data: Vec<Bar>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+    pub count: usize,
+}
+
+/// Metadata of the configured tokens
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SymbolsResponse {
+    pub symbols: Vec<crate::services::symbols::SymbolInfo>,
+    pub count: usize,
+}
+
+/// Health check result
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub service: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Readiness of the instance and the status of each check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// ready or not_ready
+    pub status: String,
+    pub checks: health::ReadinessChecks,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Service statistics
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsResponse {
+    pub statistics: Statistics,
+    pub storage: crate::services::kline::StorageStats,
+    pub generator: GeneratorStats,
+    pub websocket: WebSocketStats,
+    /// Hit rate of the REST K-line query cache, when enabled
+    pub query_cache: Option<crate::services::query_cache::QueryCacheStats>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Statistics {
+    pub total_tokens: usize,
+    pub supported_tokens: Vec<String>,
+    pub supported_intervals: Vec<String>,
+}
+
+/// Status of the transaction source
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GeneratorStats {
+    /// Whether a transaction source is configured to run
+    pub enabled: Option<bool>,
+    /// mock, replay or scenario
+    pub data_source: Option<String>,
+    /// Transactions ingested since startup
+    pub transactions_ingested: u64,
+    /// Transactions dropped for repeating a recent trade ID
+    pub duplicates_dropped: u64,
+    /// Time the latest transaction was ingested
+    pub last_transaction_at: Option<DateTime<Utc>>,
+}
+
+/// WebSocket load
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebSocketStats {
+    /// Connected sessions
+    pub sessions: usize,
+    /// Subscriptions across sessions
+    pub subscriptions: usize,
+}
+
+/// Service information; the role is also sent as `X-Replication-Role`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InfoResponse {
+    pub service: String,
+    pub version: String,
+    pub role: ReplicationRole,
+    pub replication: ReplicationStatus,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Result of a promotion request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PromoteResponse {
+    pub role: ReplicationRole,
+    /// Whether the instance was a standby until this request
+    pub promoted: bool,
+}
+
+/// Result of a snapshot request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SnapshotResponse {
+    /// File the snapshot was written to
+    pub path: String,
+    pub tokens: usize,
+    pub klines: usize,
+    pub taken_at: DateTime<Utc>,
+    /// Last transaction log record the snapshot includes, when the log is enabled
+    pub wal_sequence: Option<u64>,
+}
+
+/// Token registry state
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenRegistryResponse {
+    pub policy: crate::config::UnknownTokenPolicy,
+    pub registered: Vec<String>,
+    pub unregistered: Vec<crate::services::token_registry::UnregisteredToken>,
+}
+
+/// Result of a registration request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegisterTokenResponse {
+    pub token: String,
+    /// Whether the token was not registered until this request
+    pub registered: bool,
+    /// Quarantined transactions of the token applied by this request
+    pub released: usize,
+}
+
+/// Active WebSocket sessions
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionsResponse {
+    pub count: usize,
+    pub sessions: Vec<crate::api::websocket::SessionInfo>,
+}
+
+/// Result of a disconnect request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DisconnectSessionResponse {
+    pub id: String,
+    pub disconnected: bool,
+}
+
+/// Result of a system status announcement
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SystemStatusResponse {
+    pub status: crate::api::websocket::SystemStatus,
+    /// Sessions the status was sent to
+    pub sessions: usize,
+}
+
+/// Price alerts
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertsResponse {
+    pub count: usize,
+    pub data: Vec<crate::services::alerts::Alert>,
+}
+
+/// Swagger UI, loaded from a CDN and pointed at the bundled specification
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>K-line Data Service API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/api/v1/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+/// Serve the OpenAPI specification
+pub async fn openapi_json() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ApiDoc::openapi()))
+}
+
+/// Serve the Swagger UI page
+pub async fn swagger_ui() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(SWAGGER_UI))
+}
@@ -0,0 +1,156 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use dashmap::DashMap;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api::auth::{Authenticator, API_KEY_HEADER};
+use crate::config::RateLimitConfig;
+
+/// Header carrying the bucket size
+pub const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+/// Header carrying the requests left before the client is limited
+pub const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+/// Token bucket refilled continuously at a fixed rate
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum number of tokens
+    burst: u32,
+    /// Tokens currently available
+    tokens: f64,
+    /// When the bucket was last refilled
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Take a token
+    ///
+    /// Returns the tokens left, or how long until one is available.
+    pub fn try_acquire(&mut self, now: Instant) -> Result<u32, Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst as f64);
+        self.refilled = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// Whether the bucket has refilled completely
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens + elapsed * self.rate >= self.burst as f64
+    }
+}
+
+/// Per-client token buckets for REST requests
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Bucket size
+    burst: u32,
+    /// Bucket per API key or IP address
+    buckets: DashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Create a limiter for REST requests
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self::new(config.requests_per_second, config.burst)
+    }
+
+    /// Get the bucket size
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Take a token from a client's bucket
+    pub fn check(&self, client: &str) -> Result<u32, Duration> {
+        let now = Instant::now();
+        self.buckets
+            .entry(client.to_string())
+            .or_insert_with(|| TokenBucket::new(self.rate, self.burst))
+            .try_acquire(now)
+    }
+
+    /// Forget clients whose buckets have refilled, which is the same as starting afresh
+    pub fn prune(&self) {
+        let now = Instant::now();
+        self.buckets.retain(|_, bucket| !bucket.is_full(now));
+    }
+}
+
+/// Middleware limiting REST requests per API key, or per IP address without a
+/// recognised one
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(limiter) = req.app_data::<web::Data<Arc<RateLimiter>>>().cloned() else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    // Only keys the authenticator accepts get their own bucket; anything else
+    // would let a client mint a fresh bucket per request
+    let authenticator = req.app_data::<web::Data<Arc<Authenticator>>>();
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| authenticator.is_some_and(|auth| auth.scope_of(key).is_some()));
+    let client = match key {
+        Some(key) => format!("key:{}", key),
+        None => format!(
+            "ip:{}",
+            req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default()
+        ),
+    };
+
+    let limit = HeaderValue::from(limiter.burst());
+    match limiter.check(&client) {
+        Ok(remaining) => {
+            let mut response = next.call(req).await?;
+            let headers = response.headers_mut();
+            headers.insert(RATE_LIMIT_LIMIT, limit);
+            headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(remaining));
+            Ok(response.map_into_left_body())
+        }
+        Err(retry_after) => {
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, retry_after.as_secs_f64().ceil() as u64))
+                .insert_header((RATE_LIMIT_LIMIT, limit))
+                .insert_header((RATE_LIMIT_REMAINING, 0))
+                .json(json!({ "error": "Rate limit exceeded" }));
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
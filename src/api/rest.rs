@@ -1,108 +1,1565 @@
-use actix_web::{web, HttpResponse, Result};
+// Parameter parsers fail with the 400 response to send, which is large
+#![allow(clippy::result_large_err)]
+
+use actix_web::http::header::{ContentDisposition, ETag, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified};
+use actix_web::http::StatusCode;
+use actix_web::{guard, middleware, web, Error, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder, Result};
+use bytes::Bytes;
+use futures::stream;
 use serde_json::json;
-use std::collections::HashMap;
+use chrono::{DateTime, SubsecRound, Utc};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::api::{auth, graphql, health, openapi, rate_limit, request_id};
+use crate::api::sse::stream_events;
+use crate::config::Config;
+use crate::api::websocket::{self, SystemStatus, WsManager};
+use crate::services::{
+    AmendError, KLineAmendment, KLineCursor, KLinePage, KLineQueryCache, KLineService, IndexPriceAggregator, Metrics, QuoteConverter, ReplicationState, SortOrder, SymbolTable,
+    TokenListings, TokenRegistry, TradeService, TransactionLog, TransactionPipeline,
+};
+use crate::services::alerts::{Alert, AlertService, NewAlert};
+use crate::services::archive::{ArchiveError, CandleArchive};
+use crate::services::bars::{build_bars, BarSize, BarType};
+use crate::services::candle_transform::CandleType;
+use crate::services::correlation::{self, CorrelationMatrix};
+use crate::services::delta::volume_deltas;
+use crate::services::integrity::{self, IntegrityReport};
+use crate::services::volume_profile::{self, VolumeProfile};
+use crate::services::index::IndexBreakdown;
+use crate::services::kline::OhlcSummary;
+use crate::services::resample::{resample, TargetInterval};
+use crate::services::export::{self, ExportFormat, CSV_HEADER};
+use crate::services::mock_data::NewListing;
+use crate::services::order_book::{DepthSnapshot, OrderBookSimulator};
+use crate::services::regimes::{MarketEvent, RegimeChange, RegimeEngine, RegimeStatus, ScheduledEvent};
+use crate::services::symbols::SymbolInfo;
+use crate::services::token_registry::ListingEvent;
+use crate::services::indicators::{self, BollingerParams, IndicatorCache, MacdParams, MovingAverageType};
+use crate::services::replication::{compute_checksums, CandleChecksum};
+use crate::models::{KLine, Symbol, Ticker, TimeInterval};
+
+/// Maximum number of tokens in a batch K-line query
+const MAX_BATCH_TOKENS: usize = 100;
+
+/// Records returned when a request gives no `limit`
+const DEFAULT_LIMIT: usize = 100;
+
+/// Most candles returned by one request
+const MAX_LIMIT: usize = 1000;
+
+/// Parse an interval the service aggregates candles for
+///
+/// Trade-count and volume intervals are only accepted when configured.
+fn parse_interval(kline_service: &KLineService, interval: &str) -> Result<TimeInterval, HttpResponse> {
+    TimeInterval::from_str(interval)
+        .ok()
+        .filter(|interval| kline_service.supports(*interval))
+        .ok_or_else(|| {
+            let supported: Vec<String> = kline_service
+                .intervals()
+                .iter()
+                .map(|interval| interval.to_string())
+                .collect();
+            HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid interval. Supported: {}", supported.join(", "))
+            }))
+        })
+}
+
+/// Interval, limit, cursor, order and candle type of a K-line history query
+struct KLineRange {
+    interval_str: String,
+    interval: TimeInterval,
+    limit: usize,
+    cursor: KLineCursor,
+    order: SortOrder,
+    candle_type: CandleType,
+}
+
+impl KLineRange {
+    /// Parse the query parameters shared by the K-line history endpoints
+    fn parse(kline_service: &KLineService, query: &HashMap<String, String>) -> Result<Self, HttpResponse> {
+        let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+        let interval = parse_interval(kline_service, &interval_str)?;
+
+        let limit = parse_limit(query, MAX_LIMIT);
+
+        let before = parse_millis(query, "before")?;
+        let after = parse_millis(query, "after")?;
+
+        // Newest first, except when paging forwards
+        let order = match query.get("order") {
+            Some(order) => SortOrder::from_str(order).map_err(|_| {
+                HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid order. Supported: asc, desc"
+                }))
+            })?,
+            None if after.is_some() => SortOrder::Asc,
+            None => SortOrder::Desc,
+        };
+
+        // Without a cursor, the limit applies from the end given by the order:
+        // the newest candles, or the oldest of the last 24 hours
+        let cursor = match (before, after) {
+            (Some(_), Some(_)) => {
+                return Err(HttpResponse::BadRequest().json(json!({
+                    "error": "Use either the before or the after cursor, not both"
+                })));
+            }
+            (Some(before), None) => KLineCursor::Before(before),
+            (None, Some(after)) => KLineCursor::After(after),
+            (None, None) => match order {
+                SortOrder::Asc => KLineCursor::After(Utc::now() - chrono::Duration::hours(24)),
+                SortOrder::Desc => KLineCursor::Before(DateTime::<Utc>::MAX_UTC),
+            },
+        };
+
+        Ok(Self {
+            interval_str,
+            interval,
+            limit,
+            cursor,
+            order,
+            candle_type: parse_candle_type(query)?,
+        })
+    }
+
+    /// Get a page of a token's candles in the requested order and candle type
+    ///
+    /// Pages are read through the query cache when one is registered.
+    fn klines(&self, kline_service: &KLineService, query_cache: Option<&KLineQueryCache>, token: &str) -> KLinePage {
+        let page = self.page(kline_service, query_cache, token);
+        self.present(kline_service, page)
+    }
+
+    /// Get a page like [`Self::klines`], completed with archived candles past the in-memory window
+    ///
+    /// The in-memory page is returned alone when the archive cannot be read.
+    async fn klines_with_archive(
+        &self,
+        kline_service: &KLineService,
+        query_cache: Option<&KLineQueryCache>,
+        archive: Option<&CandleArchive>,
+        token: &str,
+    ) -> KLinePage {
+        let mut page = self.page(kline_service, query_cache, token);
+        if let Some(archive) = archive {
+            if let Err(e) = self.read_through(archive, kline_service, token, &mut page).await {
+                tracing::warn!("Failed to read archived {} candles: {}", token, e);
+            }
+        }
+        self.present(kline_service, page)
+    }
+
+    /// In-memory candles of the page, oldest first
+    fn page(&self, kline_service: &KLineService, query_cache: Option<&KLineQueryCache>, token: &str) -> KLinePage {
+        match query_cache {
+            Some(query_cache) => {
+                query_cache.get_klines_page(kline_service, token, self.interval, self.cursor, self.limit)
+            }
+            None => kline_service.get_klines_page(token, self.interval, self.cursor, self.limit),
+        }
+    }
+
+    /// Apply the candle type and order to a page
+    fn present(&self, kline_service: &KLineService, mut page: KLinePage) -> KLinePage {
+        page.klines = self.candle_type.apply(kline_service, page.klines);
+        if self.order == SortOrder::Desc {
+            page.klines.reverse();
+        }
+        page
+    }
+
+    /// Fill the part of a page older than the in-memory candles from the archive
+    async fn read_through(
+        &self,
+        archive: &CandleArchive,
+        kline_service: &KLineService,
+        token: &str,
+        page: &mut KLinePage,
+    ) -> Result<(), ArchiveError> {
+        let oldest = kline_service.get_candle_range(token, self.interval).map(|(oldest, _)| oldest);
+        match self.cursor {
+            KLineCursor::Before(before) => {
+                // Only a page that ran out of in-memory candles reaches back into the archive
+                if page.next_cursor.is_some() || page.klines.len() >= self.limit {
+                    return Ok(());
+                }
+                let boundary = oldest.map_or(before, |oldest| oldest.min(before));
+                let missing = self.limit - page.klines.len();
+                let mut klines = archive.read_before(token, self.interval, boundary, missing).await?;
+                if klines.len() == missing {
+                    page.next_cursor = klines.first().map(|kline| KLineCursor::Before(kline.timestamp));
+                }
+                klines.append(&mut page.klines);
+                page.klines = klines;
+            }
+            KLineCursor::After(after) => {
+                let boundary = oldest.unwrap_or(DateTime::<Utc>::MAX_UTC);
+                if after >= boundary {
+                    return Ok(());
+                }
+                let mut klines = archive.read_after(token, self.interval, after, boundary, self.limit).await?;
+                if klines.is_empty() {
+                    return Ok(());
+                }
+                let more = klines.len() == self.limit || page.next_cursor.is_some();
+                klines.append(&mut page.klines);
+                let more = more || klines.len() > self.limit;
+                klines.truncate(self.limit);
+                page.next_cursor = more.then(|| KLineCursor::After(klines[klines.len() - 1].timestamp));
+                page.klines = klines;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How candles are serialized in K-line responses
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CandleFormat {
+    /// One JSON object per candle
+    Object,
+    /// Binance-style `[open_time, open, high, low, close, volume, close_time]` arrays,
+    /// with prices and volume optionally as strings
+    Array { string_numbers: bool },
+}
+
+impl CandleFormat {
+    /// Parse the `format` and `numbers` query parameters
+    fn parse(query: &HashMap<String, String>) -> Result<Self, HttpResponse> {
+        let string_numbers = match query.get("numbers").map(String::as_str) {
+            None | Some("number") => false,
+            Some("string") => true,
+            Some(_) => {
+                return Err(HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid numbers. Supported: number, string"
+                })));
+            }
+        };
+
+        match query.get("format").map(String::as_str) {
+            None | Some("object") => Ok(CandleFormat::Object),
+            Some("array") => Ok(CandleFormat::Array { string_numbers }),
+            Some(_) => Err(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid format. Supported: object, array"
+            }))),
+        }
+    }
+
+    /// Serialize a candle
+    fn encode(&self, kline: &KLine) -> serde_json::Value {
+        let CandleFormat::Array { string_numbers } = *self else {
+            return json!(kline);
+        };
+        let number = |value: f64| {
+            if string_numbers {
+                json!(value.to_string())
+            } else {
+                json!(value)
+            }
+        };
+        json!([
+            kline.timestamp.timestamp_millis(),
+            number(kline.open),
+            number(kline.high),
+            number(kline.low),
+            number(kline.close),
+            number(kline.volume),
+            kline.close_time().timestamp_millis()
+        ])
+    }
+
+    /// Serialize a list of candles
+    fn encode_all(&self, klines: &[KLine]) -> serde_json::Value {
+        klines.iter().map(|kline| self.encode(kline)).collect()
+    }
+}
+
+/// Parse the `candle_type` query parameter
+fn parse_candle_type(query: &HashMap<String, String>) -> Result<CandleType, HttpResponse> {
+    let Some(value) = query.get("candle_type") else {
+        return Ok(CandleType::Regular);
+    };
+    CandleType::from_str(value).map_err(|_| {
+        HttpResponse::BadRequest().json(json!({
+            "error": "Invalid candle_type. Supported: regular, heikin_ashi"
+        }))
+    })
+}
+
+/// Parse a timestamp given in milliseconds since epoch
+fn parse_millis(
+    query: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<DateTime<Utc>>, HttpResponse> {
+    let Some(value) = query.get(name) else {
+        return Ok(None);
+    };
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+        .map(Some)
+        .ok_or_else(|| {
+            HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid {}. Expected milliseconds since epoch", name)
+            }))
+        })
+}
+
+/// Token a request names, in canonical form; DOGE by default
+///
+/// Plain tokens and pairs in any case, such as `doge/usdt`, name the series
+/// they canonicalize to, see [`Symbol`].
+fn token_param(query: &HashMap<String, String>) -> String {
+    query.get("token").map_or_else(|| "DOGE".to_string(), |token| Symbol::canonicalize(token))
+}
+
+/// Cursor to send back for the following page
+fn next_cursor(page: &KLinePage) -> Option<i64> {
+    page.next_cursor.map(|cursor| cursor.timestamp().timestamp_millis())
+}
+
+/// Cache validators of a page of candles
+///
+/// Only the newest candle of a page can still change, so a hash of the query,
+/// the candle count and the newest candle stands in for a hash of the body.
+/// Last-Modified is only sent once the newest candle is closed.
+struct KLineValidators {
+    etag: EntityTag,
+    last_modified: Option<HttpDate>,
+}
+
+impl KLineValidators {
+    fn new(query: &str, klines: &[KLine]) -> Self {
+        let newest = klines.iter().max_by_key(|kline| kline.timestamp);
+        let mut hasher = DefaultHasher::new();
+        query.hash(&mut hasher);
+        klines.len().hash(&mut hasher);
+        if let Some(newest) = newest {
+            // The open candle's close time stays put while its close and volume move
+            newest.close_time().timestamp_millis().hash(&mut hasher);
+            newest.close.to_bits().hash(&mut hasher);
+            newest.volume.to_bits().hash(&mut hasher);
+            newest.is_closed.hash(&mut hasher);
+        }
+        Self {
+            // Weak, as compressed and identity bodies share it
+            etag: EntityTag::new_weak(format!("{:016x}", hasher.finish())),
+            // HTTP dates have whole seconds
+            last_modified: newest
+                .filter(|kline| kline.is_closed)
+                .map(|kline| SystemTime::from(kline.close_time().trunc_subsecs(0)).into()),
+        }
+    }
+
+    /// Whether the client's cached copy is current
+    ///
+    /// If-Modified-Since is only considered without If-None-Match.
+    fn not_modified(&self, req: &HttpRequest) -> bool {
+        match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+            None => match (req.get_header::<IfModifiedSince>(), self.last_modified) {
+                (Some(IfModifiedSince(since)), Some(last_modified)) => {
+                    SystemTime::from(last_modified) <= SystemTime::from(since)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    fn response(&self, status: StatusCode) -> HttpResponseBuilder {
+        let mut response = HttpResponse::build(status);
+        response.insert_header(ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            response.insert_header(LastModified(last_modified));
+        }
+        response
+    }
+}
+
+/// Get K-line data for a specific token and interval
+///
+/// Responses carry an ETag, and Last-Modified once the newest candle is
+/// closed, so pollers of unchanged ranges get 304 Not Modified.
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("limit" = Option<usize>, Query, description = "Candles to return, 100 by default and at most 1000"),
+        ("order" = Option<String>, Query, description = "asc or desc; desc unless paging with after"),
+        ("before" = Option<i64>, Query, description = "Return candles opened before this time (ms since epoch)"),
+        ("after" = Option<i64>, Query, description = "Return candles opened after this time (ms since epoch)"),
+        ("candle_type" = Option<String>, Query, description = "regular or heikin_ashi; regular by default"),
+        ("format" = Option<String>, Query, description = "object or array (Binance-style); object by default"),
+        ("numbers" = Option<String>, Query, description = "number or string, for prices and volume in array format"),
+        ("quote" = Option<String>, Query, description = "Quote asset to convert prices to, such as BTC; the token's own quote by default"),
+    ),
+    responses(
+        (status = 200, description = "A page of candles", body = openapi::KLinesResponse),
+        (status = 304, description = "The candles are unchanged since the client's copy"),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_klines(
+    req: HttpRequest,
+    kline_service: web::Data<Arc<KLineService>>,
+    query_cache: Option<web::Data<Arc<KLineQueryCache>>>,
+    converter: Option<web::Data<Arc<QuoteConverter>>>,
+    archive: Option<web::Data<Arc<CandleArchive>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let mut token = token_param(&query);
+    let range = match KLineRange::parse(&kline_service, &query) {
+        Ok(range) => range,
+        Err(response) => return Ok(response),
+    };
+    let format = match CandleFormat::parse(&query) {
+        Ok(format) => format,
+        Err(response) => return Ok(response),
+    };
+
+    let query_cache = query_cache.map(|query_cache| query_cache.get_ref().clone());
+    let archive = archive.map(|archive| archive.get_ref().clone());
+    let mut page = range
+        .klines_with_archive(&kline_service, query_cache.as_deref(), archive.as_deref(), &token)
+        .await;
+    if let Some(quote) = query.get("quote") {
+        let converter = converter.map(|converter| converter.get_ref().clone()).unwrap_or_default();
+        let converted = converter.quoted_token(&token, quote).and_then(|quoted| {
+            let klines = converter.convert_klines(&kline_service, &token, page.klines, quote)?;
+            Ok((quoted, klines))
+        });
+        match converted {
+            Ok((quoted, klines)) => (token, page.klines) = (quoted, klines),
+            Err(e) => return Ok(HttpResponse::BadRequest().json(json!({ "error": e.to_string() }))),
+        }
+    }
+    let validators = KLineValidators::new(req.query_string(), &page.klines);
+    if validators.not_modified(&req) {
+        return Ok(validators.response(StatusCode::NOT_MODIFIED).finish());
+    }
+
+    Ok(validators.response(StatusCode::OK).json(json!({
+        "token": token,
+        "interval": range.interval_str,
+        "data": format.encode_all(&page.klines),
+        "next_cursor": next_cursor(&page)
+    })))
+}
+
+/// Get K-line data for several tokens at once, keyed by token
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines/batch",
+    tag = "klines",
+    params(
+        ("tokens" = String, Query, description = "Comma-separated token symbols, at most 100"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("limit" = Option<usize>, Query, description = "Candles to return, 100 by default and at most 1000"),
+        ("order" = Option<String>, Query, description = "asc or desc; desc unless paging with after"),
+        ("before" = Option<i64>, Query, description = "Return candles opened before this time (ms since epoch)"),
+        ("after" = Option<i64>, Query, description = "Return candles opened after this time (ms since epoch)"),
+        ("candle_type" = Option<String>, Query, description = "regular or heikin_ashi; regular by default"),
+        ("format" = Option<String>, Query, description = "object or array (Binance-style); object by default"),
+        ("numbers" = Option<String>, Query, description = "number or string, for prices and volume in array format"),
+    ),
+    responses(
+        (status = 200, description = "A page of candles per token", body = openapi::KLinesBatchResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_klines_batch(
+    kline_service: web::Data<Arc<KLineService>>,
+    query_cache: Option<web::Data<Arc<KLineQueryCache>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let tokens: BTreeSet<String> = query
+        .get("tokens")
+        .map(|tokens| {
+            tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(Symbol::canonicalize)
+                .collect()
+        })
+        .unwrap_or_default();
+    if tokens.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Missing tokens. Expected a comma-separated list such as tokens=DOGE,SHIB"
+        })));
+    }
+    if tokens.len() > MAX_BATCH_TOKENS {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Too many tokens. Maximum {} per request", MAX_BATCH_TOKENS)
+        })));
+    }
+
+    let range = match KLineRange::parse(&kline_service, &query) {
+        Ok(range) => range,
+        Err(response) => return Ok(response),
+    };
+    let format = match CandleFormat::parse(&query) {
+        Ok(format) => format,
+        Err(response) => return Ok(response),
+    };
+
+    let query_cache = query_cache.map(|query_cache| query_cache.get_ref().clone());
+    let pages: BTreeMap<&str, KLinePage> = tokens
+        .iter()
+        .map(|token| (token.as_str(), range.klines(&kline_service, query_cache.as_deref(), token)))
+        .collect();
+    let next_cursors: BTreeMap<&str, Option<i64>> = pages
+        .iter()
+        .map(|(token, page)| (*token, next_cursor(page)))
+        .collect();
+    let data: BTreeMap<&str, serde_json::Value> = pages
+        .iter()
+        .map(|(token, page)| (*token, format.encode_all(&page.klines)))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "interval": range.interval_str,
+        "data": data,
+        "next_cursors": next_cursors
+    })))
+}
+
+/// Candles per chunk of a streamed CSV export
+const EXPORT_CHUNK_ROWS: usize = 1000;
+
+/// Download a token's candles within a time range as CSV or Parquet
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines/export",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("start" = Option<i64>, Query, description = "Range start (ms since epoch), 24 hours before end by default"),
+        ("end" = Option<i64>, Query, description = "Range end (ms since epoch), now by default"),
+        ("format" = Option<String>, Query, description = "csv or parquet; csv by default"),
+    ),
+    responses(
+        (status = 200, description = "The candles as a file download, oldest first"),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn export_klines(
+    kline_service: web::Data<Arc<KLineService>>,
+    archive: Option<web::Data<Arc<CandleArchive>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let format = match ExportFormat::from_str(query.get("format").map_or("csv", String::as_str)) {
+        Ok(format) => format,
+        Err(_) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid format. Supported: csv, parquet"
+            })));
+        }
+    };
+
+    let (start, end) = match (parse_millis(&query, "start"), parse_millis(&query, "end")) {
+        (Ok(start), Ok(end)) => {
+            let end = end.unwrap_or_else(Utc::now);
+            (start.unwrap_or(end - chrono::Duration::hours(24)), end)
+        }
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+
+    let mut klines = kline_service.get_klines(&token, interval, start, end, None);
+    // The part of the range older than the in-memory candles comes from the archive
+    if let Some(archive) = &archive {
+        // The range end is inclusive, the archive range's is not
+        let past_end = end + chrono::Duration::milliseconds(1);
+        let oldest = kline_service.get_candle_range(&token, interval).map_or(past_end, |(oldest, _)| oldest.min(past_end));
+        match archive.read_range(&token, interval, start, oldest).await {
+            Ok(mut archived) => {
+                archived.append(&mut klines);
+                klines = archived;
+            }
+            Err(e) => tracing::warn!("Failed to read archived {} candles: {}", token, e),
+        }
+    }
+    let filename = format!("{}_{}.{}", token, interval_str, format.extension());
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition::attachment(filename));
+
+    match format {
+        ExportFormat::Csv => {
+            // Stream the rows in chunks rather than building the whole file
+            let header = Bytes::from_static(CSV_HEADER.as_bytes());
+            let rows = (0..klines.len()).step_by(EXPORT_CHUNK_ROWS).map(move |offset| {
+                let chunk = &klines[offset..klines.len().min(offset + EXPORT_CHUNK_ROWS)];
+                Bytes::from(chunk.iter().map(export::csv_row).collect::<String>())
+            });
+            let body = stream::iter(std::iter::once(header).chain(rows).map(Ok::<_, Error>));
+            Ok(response.streaming(body))
+        }
+        ExportFormat::Parquet => match export::to_parquet(&klines) {
+            Ok(file) => Ok(response.body(file)),
+            Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+                "error": format!("Failed to encode Parquet: {}", e)
+            }))),
+        },
+    }
+}
+
+/// Get the latest completed K-line for a specific token and interval
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines/latest",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("candle_type" = Option<String>, Query, description = "regular or heikin_ashi; regular by default"),
+        ("format" = Option<String>, Query, description = "object or array (Binance-style); object by default"),
+        ("numbers" = Option<String>, Query, description = "number or string, for prices and volume in array format"),
+    ),
+    responses(
+        (status = 200, description = "The latest candle", body = openapi::KLineResponse),
+        (status = 400, description = "Invalid interval", body = openapi::ErrorResponse),
+        (status = 404, description = "No candles for the token and interval", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_latest_kline(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+    
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let format = match CandleFormat::parse(&query) {
+        Ok(format) => format,
+        Err(response) => return Ok(response),
+    };
+
+    let candle_type = match parse_candle_type(&query) {
+        Ok(candle_type) => candle_type,
+        Err(response) => return Ok(response),
+    };
+
+    let kline = kline_service
+        .get_latest_kline(&token, interval)
+        .and_then(|kline| candle_type.apply(&kline_service, vec![kline]).pop());
+    match kline {
+        Some(kline) => Ok(HttpResponse::Ok().json(json!({
+            "token": token,
+            "interval": interval_str,
+            "data": format.encode(&kline)
+        }))),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No K-line data found for the specified token and interval"
+        })))
+    }
+}
+
+/// Get the current (open) K-line for a specific token and interval
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines/current",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("candle_type" = Option<String>, Query, description = "regular or heikin_ashi; regular by default"),
+        ("format" = Option<String>, Query, description = "object or array (Binance-style); object by default"),
+        ("numbers" = Option<String>, Query, description = "number or string, for prices and volume in array format"),
+    ),
+    responses(
+        (status = 200, description = "The open candle", body = openapi::CurrentKLineResponse),
+        (status = 400, description = "Invalid interval", body = openapi::ErrorResponse),
+        (status = 404, description = "No open candle for the token and interval", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_current_kline(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+    
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
 
-use crate::services::KLineService;
-use crate::models::TimeInterval;
+    let format = match CandleFormat::parse(&query) {
+        Ok(format) => format,
+        Err(response) => return Ok(response),
+    };
+
+    let candle_type = match parse_candle_type(&query) {
+        Ok(candle_type) => candle_type,
+        Err(response) => return Ok(response),
+    };
+
+    let kline = kline_service
+        .get_current_kline(&token, interval)
+        .and_then(|kline| candle_type.apply(&kline_service, vec![kline]).pop());
+    match kline {
+        Some(kline) => Ok(HttpResponse::Ok().json(json!({
+            "token": token,
+            "interval": interval_str,
+            "data": format.encode(&kline),
+            "is_open": true
+        }))),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No current K-line data found for the specified token and interval"
+        })))
+    }
+}
+
+/// Roll stored candles up into an interval that is not stored, such as 2h
+#[utoipa::path(
+    get,
+    path = "/api/v1/klines/resample",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("source" = Option<String>, Query, description = "Stored interval rolled up: 1s, 1m, 5m, 15m or 1h; 1m by default"),
+        ("target" = String, Query, description = "Target interval as a count and unit (s, m, h, d), a multiple of source, at most 30d"),
+        ("limit" = Option<usize>, Query, description = "Newest target candles to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Resampled candles, oldest first", body = openapi::ResampledKLinesResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn resample_klines(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let source = match parse_interval(&kline_service, query.get("source").map_or("1m", String::as_str)) {
+        Ok(source) => source,
+        Err(response) => return Ok(response),
+    };
+    let Some(target) = query.get("target").and_then(|target| TargetInterval::from_str(target).ok()) else {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid target. Expected a count and unit such as 2h, at most 30d"
+        })));
+    };
+    if !target.accepts(source) {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Target {} must be a longer multiple of source {}", target, source)
+        })));
+    }
+    let limit = parse_limit(&query, MAX_LIMIT).max(1);
+
+    // Read back from the bucket of the newest candle just far enough for `limit` buckets
+    let data = match kline_service.get_candle_range(&token, source) {
+        Some((_, newest)) => {
+            let span = chrono::Duration::seconds((target.seconds() * (limit as u64 - 1)) as i64);
+            let start = target.bucket_start(newest) - span;
+            resample(&kline_service.get_klines(&token, source, start, newest, None), target, Utc::now())
+        }
+        None => Vec::new(),
+    };
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "source": source,
+        "target": target.to_string(),
+        "data": data
+    })))
+}
+
+/// Summarize a token's candles over any time range into one OHLCV bar
+#[utoipa::path(
+    get,
+    path = "/api/v1/ohlc",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("start" = Option<i64>, Query, description = "Range start (ms since epoch), the oldest retained candle by default"),
+        ("end" = Option<i64>, Query, description = "Range end, exclusive (ms since epoch), now by default"),
+    ),
+    responses(
+        (status = 200, description = "The summary of candles opened in the range", body = OhlcSummary),
+        (status = 400, description = "Invalid range", body = openapi::ErrorResponse),
+        (status = 404, description = "No candles in the range", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_ohlc(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let (start, end) = match (parse_millis(&query, "start"), parse_millis(&query, "end")) {
+        (Ok(start), Ok(end)) => (start.unwrap_or(DateTime::UNIX_EPOCH), end.unwrap_or_else(Utc::now)),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+    if start >= end {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid range. start must be before end"
+        })));
+    }
+
+    match kline_service.get_ohlc(&token, start, end) {
+        Some(summary) => Ok(HttpResponse::Ok().json(summary)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No K-line data found for the specified token and range"
+        }))),
+    }
+}
+
+/// Get a token's rolling 24-hour statistics, optionally in another quote asset
+#[utoipa::path(
+    get,
+    path = "/api/v1/ticker",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("quote" = Option<String>, Query, description = "Quote asset to convert prices to, such as BTC; the token's own quote by default"),
+    ),
+    responses(
+        (status = 200, description = "Statistics of the last 24 hours of 1m candles", body = Ticker),
+        (status = 400, description = "No conversion rate to the quote asset", body = openapi::ErrorResponse),
+        (status = 404, description = "No candles in the last 24 hours", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_ticker(
+    kline_service: web::Data<Arc<KLineService>>,
+    converter: Option<web::Data<Arc<QuoteConverter>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let ticker = match query.get("quote") {
+        Some(quote) => {
+            let converter = converter.map(|converter| converter.get_ref().clone()).unwrap_or_default();
+            match converter.get_ticker(&kline_service, &token, quote) {
+                Ok(ticker) => ticker,
+                Err(e) => return Ok(HttpResponse::BadRequest().json(json!({ "error": e.to_string() }))),
+            }
+        }
+        None => kline_service.get_ticker(&token),
+    };
+
+    match ticker {
+        Some(ticker) => Ok(HttpResponse::Ok().json(ticker)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No K-line data found for the specified token in the last 24 hours"
+        }))),
+    }
+}
+
+/// Get a token's composite index price and the source prices it is made of
+///
+/// The composite candles are served by the K-line endpoints under the
+/// `index_token` of the response, such as `DOGE@index`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/index",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+    ),
+    responses(
+        (status = 200, description = "The composite price and each source's latest price", body = IndexBreakdown),
+        (status = 404, description = "The index is disabled or no weighted source reported the token", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_index(
+    index: Option<web::Data<Arc<IndexPriceAggregator>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    match index.and_then(|index| index.breakdown(&token)) {
+        Some(breakdown) => Ok(HttpResponse::Ok().json(breakdown)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No index price found for the specified token"
+        }))),
+    }
+}
+
+/// Get the most recent trades for a token, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/trades",
+    tag = "trades",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("limit" = Option<usize>, Query, description = "Trades to return, 100 by default"),
+    ),
+    responses((status = 200, description = "Recent trades, oldest first", body = openapi::TradesResponse))
+)]
+pub async fn get_trades(
+    trade_service: web::Data<Arc<TradeService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+
+    let limit = parse_limit(&query, trade_service.capacity());
+
+    let trades = trade_service.recent(&token, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "count": trades.len(),
+        "data": trades
+    })))
+}
+
+/// Get the simulated order book of a token, best prices first
+#[utoipa::path(
+    get,
+    path = "/api/v1/depth",
+    tag = "trades",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("limit" = Option<usize>, Query, description = "Levels per side, all kept levels by default"),
+    ),
+    responses(
+        (status = 200, description = "Bids and asks of the book", body = DepthSnapshot),
+        (status = 404, description = "No book for the token yet", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_depth(
+    order_books: web::Data<Arc<OrderBookSimulator>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+
+    let limit: usize = query
+        .get("limit")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(order_books.levels())
+        .min(order_books.levels()); // No more than the levels kept
+
+    match order_books.snapshot(&token, limit) {
+        Some(snapshot) => Ok(HttpResponse::Ok().json(snapshot)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "No order book found for the specified token"
+        }))),
+    }
+}
+
+/// Get a token's recent traded volume binned by price level
+#[utoipa::path(
+    get,
+    path = "/api/v1/volume-profile",
+    tag = "trades",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("start" = Option<i64>, Query, description = "Only count trades at or after this time (ms since epoch)"),
+        ("end" = Option<i64>, Query, description = "Only count trades before this time (ms since epoch)"),
+        ("buckets" = Option<usize>, Query, description = "Price levels, 50 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Volume per price level, lowest price first", body = VolumeProfile),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_volume_profile(
+    trade_service: web::Data<Arc<TradeService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let (start, end) = match (parse_millis(&query, "start"), parse_millis(&query, "end")) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+
+    let buckets = match query.get("buckets").map(|s| s.parse::<usize>()) {
+        None => volume_profile::DEFAULT_BUCKETS,
+        Some(Ok(buckets)) if (1..=volume_profile::MAX_BUCKETS).contains(&buckets) => buckets,
+        Some(_) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid buckets. Expected 1 to {}", volume_profile::MAX_BUCKETS)
+            })));
+        }
+    };
+
+    // Only the trades kept in memory can be binned
+    let trades: Vec<_> = trade_service
+        .recent(&token, trade_service.capacity())
+        .into_iter()
+        .filter(|trade| start.is_none_or(|start| trade.timestamp >= start))
+        .filter(|trade| end.is_none_or(|end| trade.timestamp < end))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(VolumeProfile::from_trades(token, &trades, buckets)))
+}
+
+/// Parse the `limit` query parameter, 100 by default and at most `max`
+fn parse_limit(query: &HashMap<String, String>, max: usize) -> usize {
+    query
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_LIMIT)
+        .min(max)
+}
+
+/// Parse a period query parameter of an indicator
+fn parse_period(
+    query: &HashMap<String, String>,
+    name: &str,
+    default: usize,
+) -> Result<usize, HttpResponse> {
+    let Some(value) = query.get(name) else {
+        return Ok(default);
+    };
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|period| (1..=indicators::MAX_PERIOD).contains(period))
+        .ok_or_else(|| {
+            HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid {}. Expected 1 to {}", name, indicators::MAX_PERIOD)
+            }))
+        })
+}
+
+/// Get a simple or exponential moving average of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/ma",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("period" = Option<usize>, Query, description = "Candles per average, 20 by default and at most 500"),
+        ("type" = Option<String>, Query, description = "sma or ema; sma by default"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Moving average values, oldest first", body = openapi::MovingAverageResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_moving_average(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let kind = match MovingAverageType::from_str(query.get("type").map_or("sma", String::as_str)) {
+        Ok(kind) => kind,
+        Err(_) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid type. Supported: sma, ema"
+            })));
+        }
+    };
+
+    let period = match parse_period(&query, "period", 20) {
+        Ok(period) => period,
+        Err(response) => return Ok(response),
+    };
+
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
+
+    // Each value needs the period's candles before it
+    let klines = kline_service.get_closed_klines(&token, interval, limit + period - 1);
+    let points = indicators::moving_average(&klines, period, kind);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "type": kind.as_str(),
+        "period": period,
+        "data": points
+    })))
+}
+
+/// Get the relative strength index of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/rsi",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("period" = Option<usize>, Query, description = "Candles per average gain and loss, 14 by default and at most 500"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "RSI values, oldest first", body = openapi::RsiResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_rsi(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let period = match parse_period(&query, "period", indicators::DEFAULT_RSI_PERIOD) {
+        Ok(period) => period,
+        Err(response) => return Ok(response),
+    };
+
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
+
+    let points = indicators::recent_rsi(&kline_service, &token, interval, period, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "period": period,
+        "data": points
+    })))
+}
+
+/// Get the average true range of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/atr",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("period" = Option<usize>, Query, description = "Candles the true range is averaged over, 14 by default and at most 500"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "ATR values, oldest first", body = openapi::AtrResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_atr(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let period = match parse_period(&query, "period", indicators::DEFAULT_ATR_PERIOD) {
+        Ok(period) => period,
+        Err(response) => return Ok(response),
+    };
+
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
+
+    let points = indicators::recent_atr(&kline_service, &token, interval, period, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "period": period,
+        "data": points
+    })))
+}
 
-/// Get K-line data for a specific token and interval
-pub async fn get_klines(
+/// Get the close-to-close realized volatility of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/volatility",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("period" = Option<usize>, Query, description = "Returns per window, 20 by default, at least 2 and at most 500"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Volatility values, oldest first", body = openapi::VolatilityResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_volatility(
     kline_service: web::Data<Arc<KLineService>>,
     query: web::Query<HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    let token = query.get("token").unwrap_or(&"DOGE".to_string()).clone();
+    let token = token_param(&query);
     let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
-    
-    let interval = match TimeInterval::from_str(&interval_str) {
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
         Ok(interval) => interval,
-        Err(_) => {
+        Err(response) => return Ok(response),
+    };
+
+    // A standard deviation needs two returns
+    let period = match parse_period(&query, "period", indicators::DEFAULT_VOLATILITY_PERIOD) {
+        Ok(period) if period >= 2 => period,
+        Ok(_) => {
             return Ok(HttpResponse::BadRequest().json(json!({
-                "error": "Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h"
+                "error": format!("Invalid period. Expected 2 to {}", indicators::MAX_PERIOD)
             })));
         }
+        Err(response) => return Ok(response),
     };
 
-    let limit: usize = query
-        .get("limit")
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(100)
-        .min(1000); // Maximum 1000 records
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
 
-    // Set default time range (last 24 hours)
-    let end = chrono::Utc::now();
-    let start = end - chrono::Duration::hours(24);
+    let points = indicators::recent_volatility(&kline_service, &token, interval, period, limit);
 
-    let klines = kline_service.get_klines(&token, interval, start, end, Some(limit));
-    
     Ok(HttpResponse::Ok().json(json!({
         "token": token,
         "interval": interval_str,
-        "data": klines
+        "period": period,
+        "data": points
     })))
 }
 
-/// Get the latest completed K-line for a specific token and interval
-pub async fn get_latest_kline(
+/// Get the MACD line, signal line and histogram of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/macd",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("fast" = Option<usize>, Query, description = "Fast EMA period, 12 by default"),
+        ("slow" = Option<usize>, Query, description = "Slow EMA period, 26 by default and at most 500"),
+        ("signal" = Option<usize>, Query, description = "Signal EMA period, 9 by default"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "MACD values, oldest first", body = openapi::MacdResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_macd(
     kline_service: web::Data<Arc<KLineService>>,
+    indicator_cache: web::Data<Arc<IndicatorCache>>,
     query: web::Query<HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    let token = query.get("token").unwrap_or(&"DOGE".to_string()).clone();
+    let token = token_param(&query);
     let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
-    
-    let interval = match TimeInterval::from_str(&interval_str) {
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
         Ok(interval) => interval,
-        Err(_) => {
+        Err(response) => return Ok(response),
+    };
+
+    let defaults = MacdParams::default();
+    let params = match (
+        parse_period(&query, "fast", defaults.fast),
+        parse_period(&query, "slow", defaults.slow),
+        parse_period(&query, "signal", defaults.signal),
+    ) {
+        (Ok(fast), Ok(slow), Ok(signal)) => MacdParams { fast, slow, signal },
+        (Err(response), _, _) | (_, Err(response), _) | (_, _, Err(response)) => {
+            return Ok(response)
+        }
+    };
+    if params.fast >= params.slow {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid periods. fast must be less than slow"
+        })));
+    }
+
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
+
+    let points = indicator_cache.macd(&kline_service, &token, interval, params, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "fast": params.fast,
+        "slow": params.slow,
+        "signal": params.signal,
+        "data": points
+    })))
+}
+
+/// Get the Bollinger Bands of a token's closed candles
+#[utoipa::path(
+    get,
+    path = "/api/v1/indicators/bollinger",
+    tag = "indicators",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("period" = Option<usize>, Query, description = "Candles per band, 20 by default and at most 500"),
+        ("std_dev" = Option<f64>, Query, description = "Standard deviations from the middle band, 2 by default"),
+        ("limit" = Option<usize>, Query, description = "Values to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Band values, oldest first", body = openapi::BollingerResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_bollinger_bands(
+    kline_service: web::Data<Arc<KLineService>>,
+    indicator_cache: web::Data<Arc<IndicatorCache>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+
+    let defaults = BollingerParams::default();
+    let period = match parse_period(&query, "period", defaults.period) {
+        Ok(period) => period,
+        Err(response) => return Ok(response),
+    };
+
+    let std_dev = match query.get("std_dev").map(|s| s.parse::<f64>()) {
+        None => defaults.std_dev,
+        Some(Ok(std_dev)) if std_dev.is_finite() && std_dev > 0.0 => std_dev,
+        Some(_) => {
             return Ok(HttpResponse::BadRequest().json(json!({
-                "error": "Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h"
+                "error": "Invalid std_dev. Expected a positive number"
             })));
         }
     };
 
-    match kline_service.get_latest_kline(&token, interval) {
-        Some(kline) => Ok(HttpResponse::Ok().json(json!({
-            "token": token,
-            "interval": interval_str,
-            "data": kline
-        }))),
-        None => Ok(HttpResponse::NotFound().json(json!({
-            "error": "No K-line data found for the specified token and interval"
-        })))
-    }
+    let limit = parse_limit(&query, indicators::MAX_POINTS);
+
+    let params = BollingerParams { period, std_dev };
+    let points = indicator_cache.bollinger(&kline_service, &token, interval, params, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "period": period,
+        "std_dev": std_dev,
+        "data": points
+    })))
 }
 
-/// Get the current (open) K-line for a specific token and interval
-pub async fn get_current_kline(
+/// Get the buy and sell volume of a token's candles, with the cumulative delta
+#[utoipa::path(
+    get,
+    path = "/api/v1/delta",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m, 1h or a configured trade-count (100t) or volume (5000v) interval; 1m by default"),
+        ("limit" = Option<usize>, Query, description = "Candles to return, 100 by default and at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "The newest candles' deltas, oldest first, accumulated from the first", body = openapi::DeltaResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_delta(
     kline_service: web::Data<Arc<KLineService>>,
     query: web::Query<HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    let token = query.get("token").unwrap_or(&"DOGE".to_string()).clone();
+    let token = token_param(&query);
     let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
-    
-    let interval = match TimeInterval::from_str(&interval_str) {
+
+    let interval = match parse_interval(&kline_service, &interval_str) {
         Ok(interval) => interval,
-        Err(_) => {
+        Err(response) => return Ok(response),
+    };
+
+    let limit = parse_limit(&query, MAX_LIMIT);
+
+    let newest = KLineCursor::Before(DateTime::<Utc>::MAX_UTC);
+    let page = kline_service.get_klines_page(&token, interval, newest, limit);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "interval": interval_str,
+        "data": volume_deltas(&page.klines)
+    })))
+}
+
+/// Get the correlation of returns between every pair of tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/analytics/correlation",
+    tag = "klines",
+    params(
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m or 1h; 1m by default"),
+        ("window" = Option<String>, Query, description = "Length of the window ending now, such as 30m, 6h or 7d; 6h by default"),
+    ),
+    responses(
+        (status = 200, description = "Pearson correlation of candle-to-candle returns, with tokens sorted by name", body = CorrelationMatrix),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_correlation(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) if interval.is_time_based() => interval,
+        Ok(_) => {
             return Ok(HttpResponse::BadRequest().json(json!({
-                "error": "Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h"
+                "error": "Invalid interval. Correlation needs a time interval"
             })));
         }
+        Err(response) => return Ok(response),
+    };
+    let Some(window) = correlation::parse_window(query.get("window").map_or("6h", String::as_str)) else {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid window. Expected a length such as 30m, 6h or 7d"
+        })));
     };
 
-    match kline_service.get_current_kline(&token, interval) {
-        Some(kline) => Ok(HttpResponse::Ok().json(json!({
-            "token": token,
-            "interval": interval_str,
-            "data": kline,
-            "is_open": true
+    let tokens = kline_service.get_available_tokens();
+    match CorrelationMatrix::compute(&kline_service, tokens, interval, window, Utc::now()) {
+        Some(matrix) => Ok(HttpResponse::Ok().json(matrix)),
+        None => Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!(
+                "Invalid window. Expected 1 to {} {} candles",
+                correlation::MAX_STEPS,
+                interval_str
+            )
         }))),
-        None => Ok(HttpResponse::NotFound().json(json!({
-            "error": "No current K-line data found for the specified token and interval"
-        })))
     }
 }
 
+/// Get Renko bricks built from a token's recent trades
+#[utoipa::path(
+    get,
+    path = "/api/v1/renko",
+    tag = "bars",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("size" = Option<f64>, Query, description = "Brick size; the ATR of `interval` candles when omitted"),
+        ("interval" = Option<String>, Query, description = "Candle interval of the ATR, 1m by default"),
+        ("atr_period" = Option<usize>, Query, description = "ATR period, 14 by default and at most 500"),
+        ("limit" = Option<usize>, Query, description = "Bricks to return, 100 by default"),
+    ),
+    responses(
+        (status = 200, description = "Completed bricks, oldest first", body = openapi::BarsResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+        (status = 404, description = "Too few closed candles for an ATR-based size", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_renko(
+    kline_service: web::Data<Arc<KLineService>>,
+    trade_service: web::Data<Arc<TradeService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    Ok(bars(&kline_service, &trade_service, &query, BarType::Renko))
+}
+
+/// Get range bars built from a token's recent trades
+#[utoipa::path(
+    get,
+    path = "/api/v1/range-bars",
+    tag = "bars",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("size" = Option<f64>, Query, description = "Bar range; the ATR of `interval` candles when omitted"),
+        ("interval" = Option<String>, Query, description = "Candle interval of the ATR, 1m by default"),
+        ("atr_period" = Option<usize>, Query, description = "ATR period, 14 by default and at most 500"),
+        ("limit" = Option<usize>, Query, description = "Bars to return, 100 by default"),
+    ),
+    responses(
+        (status = 200, description = "Completed bars, oldest first", body = openapi::BarsResponse),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+        (status = 404, description = "Too few closed candles for an ATR-based size", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_range_bars(
+    kline_service: web::Data<Arc<KLineService>>,
+    trade_service: web::Data<Arc<TradeService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    Ok(bars(&kline_service, &trade_service, &query, BarType::Range))
+}
+
+/// Build bars of one type from the trades kept for a token
+fn bars(
+    kline_service: &KLineService,
+    trade_service: &TradeService,
+    query: &HashMap<String, String>,
+    bar_type: BarType,
+) -> HttpResponse {
+    let token = token_param(query);
+
+    let size = match query.get("size").map(|s| s.parse::<f64>()).transpose() {
+        Ok(size) => size,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": "Invalid size. Expected a positive number"
+            }));
+        }
+    };
+    let atr_period = match query.get("atr_period").map(|s| s.parse::<usize>()).transpose() {
+        Ok(atr_period) => atr_period,
+        Err(_) => {
+            return HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid atr_period. Expected 1 to {}", indicators::MAX_PERIOD)
+            }));
+        }
+    };
+    let bar_size = match BarSize::from_parts(size, query.get("interval").map(String::as_str), atr_period) {
+        Ok(bar_size) => bar_size,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+
+    let Some(size) = bar_size.resolve(kline_service, &token) else {
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("Not enough closed candles to compute the ATR of {}", token)
+        }));
+    };
+
+    let limit: usize = query
+        .get("limit")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100);
+
+    let trades = trade_service.recent(&token, trade_service.capacity());
+    let mut data = build_bars(&trades, bar_type, size);
+    data.drain(..data.len().saturating_sub(limit));
+
+    HttpResponse::Ok().json(json!({
+        "token": token,
+        "bar_type": bar_type,
+        "size": size,
+        "data": data
+    }))
+}
+
 /// Get list of supported tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    responses((status = 200, description = "Tokens with K-line data", body = openapi::TokensResponse))
+)]
 pub async fn get_tokens(
     kline_service: web::Data<Arc<KLineService>>,
 ) -> Result<HttpResponse> {
@@ -114,43 +1571,749 @@ pub async fn get_tokens(
     })))
 }
 
-/// Health check endpoint
-pub async fn health_check() -> Result<HttpResponse> {
+/// Get the metadata of every configured token, with the candles available for it
+///
+/// Unlike `/tokens`, configured tokens are listed before their first trade.
+#[utoipa::path(
+    get,
+    path = "/api/v1/symbols",
+    tag = "tokens",
+    responses((status = 200, description = "Metadata per configured token", body = openapi::SymbolsResponse))
+)]
+pub async fn get_symbols(
+    symbols: web::Data<Arc<SymbolTable>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    registry: web::Data<Arc<TokenRegistry>>,
+) -> Result<HttpResponse> {
+    let infos: Vec<SymbolInfo> = symbols
+        .specs()
+        .into_iter()
+        .map(|spec| SymbolInfo::new(spec, &kline_service, &registry))
+        .collect();
+
     Ok(HttpResponse::Ok().json(json!({
-        "status": "healthy",
-        "service": "k-line-data-service",
-        "timestamp": chrono::Utc::now().to_rfc3339()
+        "symbols": infos,
+        "count": infos.len()
     })))
 }
 
-/// Get service statistics
+/// Get service statistics: candle storage, generator status and WebSocket load
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats",
+    tag = "service",
+    responses((status = 200, description = "Service statistics", body = openapi::StatsResponse))
+)]
 pub async fn get_stats(
     kline_service: web::Data<Arc<KLineService>>,
+    ws_manager: Option<web::Data<Arc<WsManager>>>,
+    metrics: Option<web::Data<Arc<Metrics>>>,
+    query_cache: Option<web::Data<Arc<KLineQueryCache>>>,
+    config: Option<web::Data<Config>>,
 ) -> Result<HttpResponse> {
     let tokens = kline_service.get_available_tokens();
-    
+    let connections = ws_manager.map(|ws_manager| ws_manager.connection_stats()).unwrap_or_default();
+    let data_generation = config.as_ref().map(|config| &config.data_generation);
+
     Ok(HttpResponse::Ok().json(json!({
         "statistics": {
             "total_tokens": tokens.len(),
             "supported_tokens": tokens,
-            "supported_intervals": ["1s", "1m", "5m", "15m", "1h"]
+            "supported_intervals": kline_service.intervals()
+        },
+        "storage": kline_service.storage_stats(),
+        "generator": {
+            "enabled": data_generation.map(|data_generation| data_generation.enabled),
+            "data_source": data_generation.map(|data_generation| data_generation.data_source),
+            "transactions_ingested": metrics.as_ref().map_or(0, |metrics| metrics.transactions_ingested()),
+            "duplicates_dropped": metrics.as_ref().map_or(0, |metrics| metrics.duplicates_dropped()),
+            "last_transaction_at": metrics.as_ref().and_then(|metrics| metrics.last_ingest_at())
+        },
+        "websocket": {
+            "sessions": connections.sessions,
+            "subscriptions": connections.subscriptions
         },
+        "query_cache": query_cache.map(|query_cache| query_cache.stats()),
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
 }
 
+/// Get service information, including the replication role
+///
+/// The role is also advertised in the `X-Replication-Role` header so load
+/// balancers can route on it without parsing the body.
+#[utoipa::path(
+    get,
+    path = "/api/v1/info",
+    tag = "service",
+    responses((status = 200, description = "Service information", body = openapi::InfoResponse))
+)]
+pub async fn get_info(
+    replication: web::Data<Arc<ReplicationState>>,
+) -> Result<HttpResponse> {
+    let status = replication.status();
+    let role = if replication.is_primary() { "primary" } else { "standby" };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Replication-Role", role))
+        .json(json!({
+            "service": "k-line-data-service",
+            "version": env!("CARGO_PKG_VERSION"),
+            "role": role,
+            "replication": status,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })))
+}
+
+/// Get closed-candle checksums used by standby instances to verify replication
+#[utoipa::path(
+    get,
+    path = "/api/v1/replication/checksums",
+    tag = "replication",
+    params(("since" = Option<i64>, Query, description = "Only cover candles opened at or after this time (ms since epoch)")),
+    responses(
+        (status = 200, description = "Checksums per token and interval", body = Vec<CandleChecksum>),
+        (status = 400, description = "Invalid since timestamp", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_replication_checksums(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let since = match query.get("since").map(|s| s.parse::<i64>()) {
+        None => chrono::DateTime::<chrono::Utc>::MIN_UTC,
+        Some(Ok(millis)) => match chrono::DateTime::from_timestamp_millis(millis) {
+            Some(since) => since,
+            None => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid since timestamp"
+                })));
+            }
+        },
+        Some(Err(_)) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid since timestamp. Expected milliseconds since epoch"
+            })));
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(compute_checksums(&kline_service, since)))
+}
+
+/// Promote a standby instance to primary
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/promote",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses((status = 200, description = "The instance is primary", body = openapi::PromoteResponse))
+)]
+pub async fn promote(
+    replication: web::Data<Arc<ReplicationState>>,
+) -> Result<HttpResponse> {
+    let promoted = replication.promote();
+    if promoted {
+        tracing::warn!("Instance promoted to primary via admin request");
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "role": "primary",
+        "promoted": promoted
+    })))
+}
+
+/// Write a snapshot of every candle to the configured file
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/snapshot",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "The snapshot is written", body = openapi::SnapshotResponse),
+        (status = 409, description = "No snapshot path is configured", body = openapi::ErrorResponse),
+        (status = 500, description = "The snapshot could not be written", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn write_snapshot(
+    kline_service: web::Data<Arc<KLineService>>,
+    config: web::Data<Config>,
+    wal: Option<web::Data<Arc<TransactionLog>>>,
+) -> Result<HttpResponse> {
+    let Some(path) = config.snapshot.path.clone() else {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Snapshots are disabled. Set [snapshot] path"
+        })));
+    };
+
+    // Copy the candles with no transaction between being logged and applied,
+    // so each one is either in the snapshot or replayed after it, never both
+    let (wal_sequence, mut snapshot) = match &wal {
+        Some(wal) => {
+            // Waiting for the workers to finish applying must not block an async worker
+            let (wal, kline_service) = (wal.clone(), kline_service.clone());
+            let (sequence, snapshot) = web::block(move || wal.checkpoint(|| kline_service.snapshot())).await?;
+            (Some(sequence), snapshot)
+        }
+        None => (None, kline_service.snapshot()),
+    };
+    snapshot.wal_sequence = wal_sequence;
+    let (tokens, klines, taken_at) = (snapshot.tokens.len(), snapshot.klines.len(), snapshot.taken_at);
+    let saved_path = path.clone();
+    // Serializing and writing every candle is too slow for the async workers
+    match web::block(move || snapshot.save(Path::new(&saved_path))).await? {
+        Ok(()) => {
+            tracing::info!("Wrote snapshot of {} candles to {}", klines, path);
+            // The log segments the snapshot covers are no longer needed to restart
+            if let (Some(wal), Some(sequence)) = (wal, wal_sequence) {
+                match web::block(move || wal.remove_through(sequence)).await? {
+                    Ok(removed) => tracing::debug!("Removed {} transaction log segments", removed),
+                    Err(e) => tracing::warn!("Failed to remove transaction log segments: {}", e),
+                }
+            }
+            Ok(HttpResponse::Ok().json(json!({
+                "path": path,
+                "tokens": tokens,
+                "klines": klines,
+                "taken_at": taken_at,
+                "wal_sequence": wal_sequence
+            })))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "error": format!("Failed to write snapshot: {}", e)
+        }))),
+    }
+}
+
+/// Get the token registry: policy, registered tokens and dropped unconfigured tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/tokens",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses((status = 200, description = "Token registry state", body = openapi::TokenRegistryResponse))
+)]
+pub async fn get_token_registry(
+    registry: web::Data<Arc<TokenRegistry>>,
+) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(json!({
+        "policy": registry.policy(),
+        "registered": registry.registered_tokens(),
+        "unregistered": registry.unregistered_tokens()
+    })))
+}
+
+/// Register a quarantined or rejected token so its transactions are applied
+///
+/// The token's quarantined transactions are applied right away.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tokens/{token}/register",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(("token" = String, Path, description = "Token symbol")),
+    responses((status = 200, description = "The token is registered", body = openapi::RegisterTokenResponse))
+)]
+pub async fn register_token(
+    registry: web::Data<Arc<TokenRegistry>>,
+    pipeline: Option<web::Data<TransactionPipeline>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let token = Symbol::canonicalize(&path.into_inner());
+    let registered = registry.register(&token);
+    if registered {
+        tracing::warn!("Token {} registered via admin request", token);
+    }
+    // Quarantined trades are otherwise applied before the token's next trade
+    let released = match pipeline {
+        Some(pipeline) => {
+            let (pipeline, token) = (pipeline.get_ref().clone(), token.clone());
+            web::block(move || pipeline.release_quarantined(&token)).await?
+        }
+        None => 0,
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "token": token,
+        "registered": registered,
+        "released": released
+    })))
+}
+
+/// List a token at runtime: generate its trades and track its K-lines
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/tokens",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = NewListing,
+    responses(
+        (status = 201, description = "The token is listed", body = ListingEvent),
+        (status = 400, description = "Invalid listing", body = openapi::ErrorResponse),
+        (status = 409, description = "The token is already listed", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn list_token(
+    listings: web::Data<Arc<TokenListings>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    registry: web::Data<Arc<TokenRegistry>>,
+    ws_manager: web::Data<Arc<WsManager>>,
+    request: web::Json<NewListing>,
+) -> Result<HttpResponse> {
+    let mut listing = request.into_inner();
+    if let Err(e) = listing.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Invalid listing: {}", e)
+        })));
+    }
+    listing.symbol = Symbol::canonicalize(&listing.symbol);
+    if !listings.list(&listing.symbol, listing.base_price) {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": format!("Token {} is already listed", listing.symbol)
+        })));
+    }
+    registry.register(&listing.symbol);
+    kline_service.add_token(&listing.symbol);
+    tracing::warn!("Token {} listed via admin request", listing.symbol);
+
+    let event = ListingEvent {
+        token: listing.symbol,
+        base_price: Some(listing.base_price),
+        timestamp: Utc::now(),
+    };
+    ws_manager.broadcast_token_listed(&event);
+    Ok(HttpResponse::Created().json(event))
+}
+
+/// Delist a token at runtime: stop generating its trades and drop its K-lines
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/tokens/{symbol}",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(("symbol" = String, Path, description = "Token symbol")),
+    responses(
+        (status = 200, description = "The token is delisted", body = ListingEvent),
+        (status = 404, description = "The token is not listed", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn delist_token(
+    listings: web::Data<Arc<TokenListings>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    registry: web::Data<Arc<TokenRegistry>>,
+    ws_manager: web::Data<Arc<WsManager>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let token = Symbol::canonicalize(&path.into_inner());
+    if !listings.delist(&token) {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": format!("Token {} is not listed", token)
+        })));
+    }
+    registry.unregister(&token);
+    kline_service.remove_token(&token);
+    tracing::warn!("Token {} delisted via admin request", token);
+
+    let event = ListingEvent {
+        token,
+        base_price: None,
+        timestamp: Utc::now(),
+    };
+    ws_manager.broadcast_token_delisted(&event);
+    Ok(HttpResponse::Ok().json(event))
+}
+
+/// List the active WebSocket sessions
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/sessions",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses((status = 200, description = "Active sessions, oldest first", body = openapi::SessionsResponse))
+)]
+pub async fn list_sessions(
+    ws_manager: web::Data<Arc<WsManager>>,
+) -> Result<HttpResponse> {
+    let sessions = ws_manager.session_infos();
+    Ok(HttpResponse::Ok().json(json!({
+        "count": sessions.len(),
+        "sessions": sessions
+    })))
+}
+
+/// Force-disconnect a WebSocket session
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/sessions/{id}",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "The session is being closed", body = openapi::DisconnectSessionResponse),
+        (status = 400, description = "Invalid session ID", body = openapi::ErrorResponse),
+        (status = 404, description = "No such session", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn disconnect_session(
+    ws_manager: web::Data<Arc<WsManager>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let Ok(session_id) = Uuid::parse_str(&id) else {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Invalid session ID: {}", id)
+        })));
+    };
+    if !ws_manager.disconnect_session(session_id) {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "error": format!("Session {} not found", session_id)
+        })));
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": session_id,
+        "disconnected": true
+    })))
+}
+
+/// Announce maintenance or a shutdown to every WebSocket session
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/system-status",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = SystemStatus,
+    responses((status = 200, description = "The sessions notified", body = openapi::SystemStatusResponse))
+)]
+pub async fn set_system_status(
+    ws_manager: web::Data<Arc<WsManager>>,
+    request: web::Json<SystemStatus>,
+) -> Result<HttpResponse> {
+    let status = request.into_inner();
+    tracing::warn!("System status set to {:?} via admin request", status.status);
+    let sessions = ws_manager.broadcast_system_status(status.clone());
+    Ok(HttpResponse::Ok().json(json!({
+        "status": status,
+        "sessions": sessions
+    })))
+}
+
+/// Correct a stored candle, even a closed one, and notify its K-line subscribers
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/klines",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = KLineAmendment,
+    responses(
+        (status = 200, description = "The amended candle", body = KLine),
+        (status = 400, description = "The corrected values are not a valid candle"),
+        (status = 404, description = "No such candle is stored")
+    )
+)]
+pub async fn amend_kline(
+    pipeline: web::Data<TransactionPipeline>,
+    request: web::Json<KLineAmendment>,
+) -> Result<HttpResponse> {
+    // Amending waits for the transactions being applied, which must not block an async worker
+    let pipeline = pipeline.get_ref().clone();
+    let kline = match web::block(move || pipeline.amend(&request)).await? {
+        Ok(kline) => kline,
+        Err(error @ AmendError::NotFound) => {
+            return Ok(HttpResponse::NotFound().json(json!({ "error": error.to_string() })));
+        }
+        Err(error @ AmendError::Invalid(_)) => {
+            return Ok(HttpResponse::BadRequest().json(json!({ "error": error.to_string() })));
+        }
+    };
+    tracing::warn!(
+        "Amended {} {} candle at {} via admin request",
+        kline.token,
+        kline.interval.as_str(),
+        kline.timestamp
+    );
+    Ok(HttpResponse::Ok().json(kline))
+}
+
+/// Cross-check a token's candles against the finer candles they aggregate
+///
+/// Each closed candle is rebuilt from the next finer interval that divides it,
+/// such as 5m from 1m, and compared value by value.
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/verify",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1m, 5m, 15m or 1h; 5m by default"),
+        ("start" = Option<i64>, Query, description = "Check candles starting at or after this time (ms since epoch), 24 hours before `end` by default"),
+        ("end" = Option<i64>, Query, description = "Check candles starting at or before this time (ms since epoch), now by default"),
+    ),
+    responses(
+        (status = 200, description = "Mismatched values and gaps found", body = IntegrityReport),
+        (status = 400, description = "Invalid parameters", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn verify_klines(
+    kline_service: web::Data<Arc<KLineService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = token_param(&query);
+    let interval_str = query.get("interval").unwrap_or(&"5m".to_string()).clone();
+    let interval = match parse_interval(&kline_service, &interval_str) {
+        Ok(interval) => interval,
+        Err(response) => return Ok(response),
+    };
+    let (start, end) = match (parse_millis(&query, "start"), parse_millis(&query, "end")) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(response), _) | (_, Err(response)) => return Ok(response),
+    };
+    let now = Utc::now();
+    let end = end.unwrap_or(now);
+    let start = start.unwrap_or(end - chrono::Duration::hours(24));
+
+    match IntegrityReport::verify(&kline_service, &token, interval, start, end, now) {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None if interval.is_time_based() && interval != TimeInterval::Second1 => {
+            Ok(HttpResponse::BadRequest().json(json!({
+                "error": format!(
+                    "Invalid range. Expected an end after the start, spanning at most {} {} candles",
+                    integrity::MAX_CANDLES,
+                    interval_str
+                )
+            })))
+        }
+        None => Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid interval. Only time intervals with a finer time interval can be verified"
+        }))),
+    }
+}
+
+/// Get the market regime and scripted events of the mock generator
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/market",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses((status = 200, description = "Current regime and pending events", body = RegimeStatus))
+)]
+pub async fn get_market(regimes: web::Data<Arc<RegimeEngine>>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(regimes.status(Utc::now())))
+}
+
+/// Switch the mock generator to a market regime now
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/market/regime",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = RegimeChange,
+    responses((status = 200, description = "The regime is entered", body = RegimeStatus))
+)]
+pub async fn set_market_regime(
+    regimes: web::Data<Arc<RegimeEngine>>,
+    request: web::Json<RegimeChange>,
+) -> Result<HttpResponse> {
+    let now = Utc::now();
+    regimes.force(request.regime, now);
+    tracing::warn!("Market regime set to {:?} via admin request", request.regime);
+    Ok(HttpResponse::Ok().json(regimes.status(now)))
+}
+
+/// Script a pump or flash crash of generated prices
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/market/events",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = MarketEvent,
+    responses(
+        (status = 201, description = "The event is scheduled", body = ScheduledEvent),
+        (status = 400, description = "Invalid event", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn create_market_event(
+    regimes: web::Data<Arc<RegimeEngine>>,
+    request: web::Json<MarketEvent>,
+) -> Result<HttpResponse> {
+    let event = request.into_inner();
+    if let Err(e) = event.validate() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Invalid event: {}", e)
+        })));
+    }
+    let scheduled = regimes.schedule(event, Utc::now());
+    tracing::warn!(
+        "{:?} of {} scheduled via admin request",
+        scheduled.kind,
+        scheduled.token.as_deref().unwrap_or("all tokens")
+    );
+    Ok(HttpResponse::Created().json(scheduled))
+}
+
+/// Create a price alert
+#[utoipa::path(
+    post,
+    path = "/api/v1/alerts",
+    tag = "alerts",
+    request_body = NewAlert,
+    responses(
+        (status = 201, description = "The created alert", body = Alert),
+        (status = 400, description = "Invalid alert", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn create_alert(
+    alert_service: web::Data<Arc<AlertService>>,
+    request: web::Json<NewAlert>,
+) -> Result<HttpResponse> {
+    match alert_service.create(request.into_inner()) {
+        Ok(alert) => Ok(HttpResponse::Created().json(alert)),
+        Err(e) => Ok(HttpResponse::BadRequest().json(json!({
+            "error": format!("Invalid alert: {}", e)
+        }))),
+    }
+}
+
+/// List price alerts, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/alerts",
+    tag = "alerts",
+    params(("token" = Option<String>, Query, description = "Only list alerts for this token")),
+    responses((status = 200, description = "Pending and triggered alerts", body = openapi::AlertsResponse))
+)]
+pub async fn list_alerts(
+    alert_service: web::Data<Arc<AlertService>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let alerts = alert_service.list(query.get("token").map(String::as_str));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "count": alerts.len(),
+        "data": alerts
+    })))
+}
+
+/// Get a price alert
+#[utoipa::path(
+    get,
+    path = "/api/v1/alerts/{id}",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    responses(
+        (status = 200, description = "The alert", body = Alert),
+        (status = 404, description = "No such alert", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn get_alert(
+    alert_service: web::Data<Arc<AlertService>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    match alert_service.get(&path) {
+        Some(alert) => Ok(HttpResponse::Ok().json(alert)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "Alert not found"
+        }))),
+    }
+}
+
+/// Delete a price alert
+#[utoipa::path(
+    delete,
+    path = "/api/v1/alerts/{id}",
+    tag = "alerts",
+    params(("id" = String, Path, description = "Alert ID")),
+    responses(
+        (status = 200, description = "The deleted alert", body = Alert),
+        (status = 404, description = "No such alert", body = openapi::ErrorResponse),
+    )
+)]
+pub async fn delete_alert(
+    alert_service: web::Data<Arc<AlertService>>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    match alert_service.delete(&path) {
+        Some(alert) => Ok(HttpResponse::Ok().json(alert)),
+        None => Ok(HttpResponse::NotFound().json(json!({
+            "error": "Alert not found"
+        }))),
+    }
+}
+
 /// Configure REST API routes
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/v1")
+            .wrap(middleware::Compress::default())
+            .wrap(middleware::from_fn(auth::require_api_key))
+            .wrap(middleware::from_fn(rate_limit::rate_limit))
+            .wrap(middleware::from_fn(request_id::request_id))
             .route("/klines", web::get().to(get_klines))
+            .route("/klines/batch", web::get().to(get_klines_batch))
+            .route("/klines/export", web::get().to(export_klines))
             .route("/klines/latest", web::get().to(get_latest_kline))
             .route("/klines/current", web::get().to(get_current_kline))
+            .route("/klines/resample", web::get().to(resample_klines))
+            .route("/ohlc", web::get().to(get_ohlc))
+            .route("/ticker", web::get().to(get_ticker))
+            .route("/index", web::get().to(get_index))
+            .route("/trades", web::get().to(get_trades))
+            .route("/depth", web::get().to(get_depth))
+            .route("/volume-profile", web::get().to(get_volume_profile))
+            .route("/indicators/ma", web::get().to(get_moving_average))
+            .route("/indicators/rsi", web::get().to(get_rsi))
+            .route("/indicators/macd", web::get().to(get_macd))
+            .route("/indicators/bollinger", web::get().to(get_bollinger_bands))
+            .route("/indicators/atr", web::get().to(get_atr))
+            .route("/indicators/volatility", web::get().to(get_volatility))
+            .route("/delta", web::get().to(get_delta))
+            .route("/analytics/correlation", web::get().to(get_correlation))
+            .route("/renko", web::get().to(get_renko))
+            .route("/range-bars", web::get().to(get_range_bars))
+            .route("/alerts", web::post().to(create_alert))
+            .route("/alerts", web::get().to(list_alerts))
+            .route("/alerts/{id}", web::get().to(get_alert))
+            .route("/alerts/{id}", web::delete().to(delete_alert))
+            .route("/stream", web::get().to(stream_events))
             .route("/tokens", web::get().to(get_tokens))
+            .route("/symbols", web::get().to(get_symbols))
             .route("/stats", web::get().to(get_stats))
-            .route("/health", web::get().to(health_check))
+            // Kept for monitors configured before /healthz
+            .route("/health", web::get().to(health::liveness))
+            .route("/openapi.json", web::get().to(openapi::openapi_json))
+            .route("/docs", web::get().to(openapi::swagger_ui))
+            .route("/ws-schema", web::get().to(websocket::ws_schema))
+            .service(
+                web::resource("/graphql")
+                    .route(web::post().to(graphql::graphql))
+                    .route(
+                        web::get()
+                            .guard(guard::Header("upgrade", "websocket"))
+                            .to(graphql::graphql_ws),
+                    ),
+            )
+            .route("/info", web::get().to(get_info))
+            .route("/replication/checksums", web::get().to(get_replication_checksums))
+            .route("/admin/promote", web::post().to(promote))
+            .route("/admin/snapshot", web::post().to(write_snapshot))
+            .route("/admin/tokens", web::get().to(get_token_registry))
+            .route("/admin/tokens", web::post().to(list_token))
+            .route("/admin/tokens/{symbol}", web::delete().to(delist_token))
+            .route("/admin/tokens/{token}/register", web::post().to(register_token))
+            .route("/admin/sessions", web::get().to(list_sessions))
+            .route("/admin/sessions/{id}", web::delete().to(disconnect_session))
+            .route("/admin/system-status", web::post().to(set_system_status))
+            .route("/admin/klines", web::patch().to(amend_kline))
+            .route("/admin/verify", web::get().to(verify_klines))
+            .route("/admin/market", web::get().to(get_market))
+            .route("/admin/market/regime", web::put().to(set_market_regime))
+            .route("/admin/market/events", web::post().to(create_market_event))
     );
     
+    // Probes stay outside the API scope, free of API keys and rate limits
+    cfg.route("/healthz", web::get().to(health::liveness))
+        .route("/readyz", web::get().to(health::readiness));
+
     // Serve static files
     cfg.route("/", web::get().to(serve_index))
         .route("/websocket_test.html", web::get().to(serve_index));
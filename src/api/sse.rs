@@ -0,0 +1,88 @@
+use actix_web::{web, HttpResponse, Result};
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+
+use crate::api::{StreamEvent, WsManager};
+use crate::models::{Symbol, TimeInterval};
+
+/// Interval between SSE heartbeat comments
+const SSE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Format a single SSE event frame
+fn sse_event<T: Serialize>(event: &str, data: &T) -> Option<Bytes> {
+    serde_json::to_string(data)
+        .ok()
+        .map(|json| Bytes::from(format!("event: {}\ndata: {}\n\n", event, json)))
+}
+
+/// Stream K-line and trade events for a token over Server-Sent Events
+///
+/// Emits `kline` events for the requested interval and `trade` events for the
+/// token, with a heartbeat comment every 15 seconds to keep proxies from
+/// closing idle connections.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stream",
+    tag = "klines",
+    params(
+        ("token" = Option<String>, Query, description = "Token symbol or pair such as DOGE-USDT, DOGE by default"),
+        ("interval" = Option<String>, Query, description = "1s, 1m, 5m, 15m or 1h; 1m by default"),
+    ),
+    responses(
+        (status = 200, description = "`kline` and `trade` events", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid interval"),
+    )
+)]
+pub async fn stream_events(
+    ws_manager: web::Data<Arc<WsManager>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let token = query.get("token").map_or_else(|| "DOGE".to_string(), |token| Symbol::canonicalize(token));
+    let interval_str = query.get("interval").unwrap_or(&"1m".to_string()).clone();
+
+    let interval = match TimeInterval::from_str(&interval_str) {
+        Ok(interval) => interval,
+        Err(_) => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h"
+            })));
+        }
+    };
+
+    let receiver = ws_manager.subscribe_events();
+
+    // Lagged receivers skip the missed events rather than ending the stream
+    let events = BroadcastStream::new(receiver).filter_map(move |event| {
+        let frame = match event {
+            Ok(StreamEvent::KLine(kline)) if kline.token == token && kline.interval == interval => {
+                sse_event("kline", &kline)
+            }
+            Ok(StreamEvent::Transaction(transaction)) if transaction.token == token => {
+                sse_event("trade", &transaction)
+            }
+            _ => None,
+        };
+        async move { frame }
+    });
+
+    let heartbeats = IntervalStream::new(time::interval_at(
+        Instant::now() + SSE_HEARTBEAT_INTERVAL,
+        SSE_HEARTBEAT_INTERVAL,
+    ))
+    .map(|_| Bytes::from_static(b": heartbeat\n\n"));
+
+    let body = stream::select(events, heartbeats).map(Ok::<_, actix_web::Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body))
+}
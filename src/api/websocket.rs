@@ -1,72 +1,595 @@
-use actix::{Actor, ActorContext, AsyncContext, Handler, Message, StreamHandler};
+use actix::{Actor, ActorContext, AsyncContext, SpawnHandle, StreamHandler};
 use actix_web::{web, HttpRequest, HttpResponse, Result};
 use actix_web_actors::ws;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use bytes::Bytes;
+use bytestring::ByteString;
+use chrono::{DateTime, TimeDelta, Utc};
+use dashmap::DashMap;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use futures::{Stream, StreamExt};
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::{KLine, TimeInterval, Transaction};
-use crate::services::KLineService;
+use crate::api::auth::{AuthError, Authenticator, API_KEY_HEADER};
+use crate::api::rate_limit::TokenBucket;
+use crate::config::{ApiKeyScope, Config, SlowConsumerPolicy};
+use crate::models::{KLine, Symbol, Ticker, TimeInterval, Transaction};
+use crate::services::config_reload::ConfigUpdate;
+use crate::services::agg_trades::{AggTrade, AggTradeBuilder};
+use crate::services::order_book::{DepthSnapshot, DepthUpdate, OrderBookSimulator};
+use crate::services::alerts::AlertTrigger;
+use crate::services::bars::{Bar, BarBuilder, BarSize, BarSpec, BarType};
+use crate::services::candle_transform::{self, CandleType};
+use crate::services::delta::{DeltaTracker, VolumeDelta};
+use crate::services::indicator_stream::{IndicatorKind, IndicatorSpec, IndicatorStream};
+use crate::services::indicators::IndicatorUpdate;
+use crate::services::metrics::{ConnectionStats, OpsMetrics};
+use crate::services::token_registry::{ListingEvent, NewTokenEvent, TokenRegistry};
+use crate::services::{KLineCursor, KLineService, TradeService};
 
-/// WebSocket connection heartbeat interval
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
-/// Client timeout duration
-const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default interval between heartbeat pings
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Default time without client activity before a session is closed
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default time a session without a key has to authenticate
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Capacity of the event channel shared with non-WebSocket transports
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Default number of messages a session may fall behind on a topic
+pub const DEFAULT_SESSION_BACKLOG: usize = 256;
+/// Default number of dropped messages before a slow session is disconnected
+pub const DEFAULT_MAX_DROPPED_MESSAGES: u64 = 10_000;
+/// Default number of messages kept per topic for resume
+pub const DEFAULT_REPLAY_CAPACITY: usize = 1024;
+/// Default time a topic without subscribers or messages is kept before it is dropped
+pub const DEFAULT_TOPIC_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// Default time consecutive same-price trades are combined for agg_trades subscribers
+pub const DEFAULT_AGG_TRADE_WINDOW: TimeDelta = TimeDelta::milliseconds(100);
+/// Recent trades sent per token when subscribing to transactions
+const TRADE_SNAPSHOT_LIMIT: usize = 100;
+/// Candles returned by get_klines without a limit
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+/// Maximum number of candles returned by get_klines
+pub const MAX_HISTORY_LIMIT: usize = 1000;
+/// Maximum number of subscriptions in a subscribe_many message
+pub const MAX_SUBSCRIBE_MANY: usize = 100;
+/// Default number of subscriptions a session may hold
+pub const DEFAULT_MAX_SUBSCRIPTIONS: usize = 100;
+/// Close frame reason sent to every session when the server shuts down
+pub const SHUTDOWN_CLOSE_REASON: &str = "server_shutdown";
+
+/// State of the server as announced to clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum SystemState {
+    /// Serving as usual
+    #[default]
+    Normal,
+    /// Maintenance is planned or under way, and sessions may be closed
+    Maintenance,
+    /// The server is stopping and will close every session
+    ShuttingDown,
+}
+
+/// Notice of the server's state, sent to every session whatever it subscribes to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct SystemStatus {
+    pub status: SystemState,
+    /// When the maintenance starts or the sessions are closed, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub deadline: Option<DateTime<Utc>>,
+    /// Explanation for users
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub message: Option<String>,
+}
+
+/// Encoding used for messages on a WebSocket connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// JSON text frames
+    #[default]
+    Json,
+    /// MessagePack binary frames
+    Msgpack,
+    /// CBOR binary frames
+    Cbor,
+}
+
+impl WireFormat {
+    /// Encode a value in this format
+    pub fn encode<T: Serialize>(&self, value: &T) -> Option<Bytes> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(value).ok().map(Bytes::from),
+            WireFormat::Msgpack => rmp_serde::to_vec_named(value).ok().map(Bytes::from),
+            WireFormat::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::into_writer(value, &mut buffer).ok()?;
+                Some(Bytes::from(buffer))
+            }
+        }
+    }
+
+    /// Decode a value encoded in this format
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            WireFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            WireFormat::Msgpack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            WireFormat::Cbor => ciborium::from_reader(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(WireFormat::Json),
+            "msgpack" => Ok(WireFormat::Msgpack),
+            "cbor" => Ok(WireFormat::Cbor),
+            _ => Err(format!("Invalid format: {}", s)),
+        }
+    }
+}
 
 /// WebSocket subscription types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(tag = "type")]
 pub enum SubscriptionType {
     /// Subscribe to real-time transactions for specific tokens
     #[serde(rename = "transactions")]
     Transactions { tokens: Vec<String> },
     /// Subscribe to real-time K-line updates for specific token and interval
+    ///
+    /// With `throttle_ms`, intermediate updates are coalesced to at most one
+    /// per window; the final state of each candle is always delivered. With
+    /// `candle_type`, candles are transformed before they are sent.
     #[serde(rename = "klines")]
-    KLines { token: String, interval: String },
+    KLines {
+        token: String,
+        interval: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        throttle_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        candle_type: Option<CandleType>,
+    },
     /// Subscribe to all transactions
     #[serde(rename = "all_transactions")]
     AllTransactions,
+    /// Subscribe to a token's trades, with consecutive trades at one price and side combined
+    #[serde(rename = "agg_trades")]
+    AggTrades { token: String },
+    /// Subscribe to changes of a token's simulated order book, after a snapshot of it
+    #[serde(rename = "depth")]
+    Depth { token: String },
+    /// Subscribe to per-second server metrics (admin only)
+    #[serde(rename = "ops_metrics")]
+    OpsMetrics {
+        #[serde(default, skip_serializing)]
+        api_key: Option<String>,
+    },
+    /// Subscribe to admin notices such as new token sightings (admin only)
+    #[serde(rename = "admin_events")]
+    AdminEvents {
+        #[serde(default, skip_serializing)]
+        api_key: Option<String>,
+    },
+    /// Subscribe to triggered price alerts
+    #[serde(rename = "alerts")]
+    Alerts,
+    /// Subscribe to tokens listed and delisted at runtime
+    #[serde(rename = "listings")]
+    Listings,
+    /// Subscribe to an indicator of a token and interval, pushed as each candle updates and when it closes
+    ///
+    /// `indicator` defaults to the RSI. `period` applies to every indicator but
+    /// MACD, which takes `fast`, `slow` and `signal` periods; `std_dev` only
+    /// applies to Bollinger Bands. Omitted parameters take their REST defaults.
+    #[serde(rename = "indicators")]
+    Indicators {
+        token: String,
+        interval: String,
+        #[serde(default)]
+        indicator: IndicatorKind,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        period: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fast: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        slow: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signal: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        std_dev: Option<f64>,
+    },
+    /// Subscribe to Renko bricks or range bars of a token, pushed as trades complete them
+    ///
+    /// Bars have a fixed `size`, or else the ATR of `interval` candles over
+    /// `atr_period` when the first bar starts.
+    #[serde(rename = "bars")]
+    Bars {
+        token: String,
+        bar_type: BarType,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        atr_period: Option<usize>,
+    },
+    /// Subscribe to the buy and sell volume of a token's candles, with the running delta
+    #[serde(rename = "delta")]
+    Delta { token: String, interval: String },
+    /// Subscribe to a token's 24-hour statistics and price extremes, pushed on every trade
+    #[serde(rename = "ticker")]
+    Ticker { token: String },
+}
+
+impl SubscriptionType {
+    /// Name the subscription's tokens canonically, so pairs match however they are written
+    fn canonicalize(mut self) -> Self {
+        match &mut self {
+            SubscriptionType::Transactions { tokens } => {
+                for token in tokens {
+                    *token = Symbol::canonicalize(token);
+                }
+            }
+            SubscriptionType::KLines { token, .. }
+            | SubscriptionType::AggTrades { token }
+            | SubscriptionType::Depth { token }
+            | SubscriptionType::Indicators { token, .. }
+            | SubscriptionType::Bars { token, .. }
+            | SubscriptionType::Delta { token, .. }
+            | SubscriptionType::Ticker { token } => *token = Symbol::canonicalize(token),
+            _ => {}
+        }
+        self
+    }
+
+    /// Tokens the subscription is limited to
+    fn tokens(&self) -> impl Iterator<Item = &String> {
+        let tokens: &[String] = match self {
+            SubscriptionType::Transactions { tokens } => tokens,
+            SubscriptionType::KLines { token, .. }
+            | SubscriptionType::AggTrades { token }
+            | SubscriptionType::Depth { token }
+            | SubscriptionType::Indicators { token, .. }
+            | SubscriptionType::Bars { token, .. }
+            | SubscriptionType::Delta { token, .. }
+            | SubscriptionType::Ticker { token } => std::slice::from_ref(token),
+            _ => &[],
+        };
+        tokens.iter()
+    }
+
+    /// Admin key supplied with an admin-only subscription
+    fn admin_key(&self) -> Option<Option<&String>> {
+        match self {
+            SubscriptionType::OpsMetrics { api_key } | SubscriptionType::AdminEvents { api_key } => {
+                Some(api_key.as_ref())
+            }
+            _ => None,
+        }
+    }
+
+    /// Bars requested by a bar subscription, or why its parameters are invalid
+    /// Indicator and parameters of an indicators subscription
+    fn indicator_spec(&self) -> Option<Result<IndicatorSpec, String>> {
+        match self {
+            SubscriptionType::Indicators {
+                indicator,
+                period,
+                fast,
+                slow,
+                signal,
+                std_dev,
+                ..
+            } => Some(IndicatorSpec::from_parts(*indicator, *period, *fast, *slow, *signal, *std_dev)),
+            _ => None,
+        }
+    }
+
+    fn bar_spec(&self) -> Option<Result<BarSpec, String>> {
+        match self {
+            SubscriptionType::Bars {
+                token,
+                bar_type,
+                size,
+                interval,
+                atr_period,
+            } => Some(
+                BarSize::from_parts(*size, interval.as_deref(), *atr_period).map(|size| BarSpec {
+                    token: token.clone(),
+                    bar_type: *bar_type,
+                    size,
+                }),
+            ),
+            _ => None,
+        }
+    }
+
 }
 
 /// WebSocket message types from client
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(tag = "action")]
 pub enum ClientMessage {
     /// Subscribe to data streams
     #[serde(rename = "subscribe")]
     Subscribe { subscription: SubscriptionType },
+    /// Subscribe to several data streams at once
+    ///
+    /// Subscriptions are checked one by one, so invalid ones do not fail the rest.
+    #[serde(rename = "subscribe_many")]
+    SubscribeMany {
+        #[cfg_attr(feature = "typescript", ts(type = "Array<SubscriptionType>"))]
+        #[schemars(with = "Vec<SubscriptionType>")]
+        subscriptions: Vec<serde_json::Value>,
+    },
     /// Unsubscribe from data streams
     #[serde(rename = "unsubscribe")]
     Unsubscribe { subscription: SubscriptionType },
-    /// Ping message for heartbeat
+    /// List the session's current subscriptions
+    #[serde(rename = "list_subscriptions")]
+    ListSubscriptions,
+    /// Remove every subscription of the session
+    #[serde(rename = "unsubscribe_all")]
+    UnsubscribeAll,
+    /// Ping message for heartbeat, with the client's clock reading to echo in the pong
     #[serde(rename = "ping")]
-    Ping,
+    Ping {
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        client_time: Option<f64>,
+    },
+    /// Switch the encoding of subsequent messages
+    #[serde(rename = "set_format")]
+    SetFormat { format: WireFormat },
+    /// Subscribe to a topic and replay buffered messages after a sequence number
+    #[serde(rename = "resume")]
+    Resume { topic: String, since: u64 },
+    /// Authenticate a connection opened without an API key, with a key or a JSON Web Token
+    #[serde(rename = "auth")]
+    Auth {
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        api_key: Option<String>,
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        token: Option<String>,
+    },
+    /// Request a page of a token's candle history, answered with a klines_snapshot
+    ///
+    /// Pages hold the newest candles opened before `before` (ms since epoch),
+    /// or the newest candles without it.
+    #[serde(rename = "get_klines")]
+    GetKLines {
+        token: String,
+        interval: String,
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        limit: Option<usize>,
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        before: Option<i64>,
+        #[serde(default)]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        candle_type: Option<CandleType>,
+    },
 }
 
 /// WebSocket message types to client
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
 #[serde(tag = "type")]
 pub enum ServerMessage {
     /// Real-time transaction data
     #[serde(rename = "transaction")]
     Transaction { data: Transaction },
+    /// Consecutive trades at one price and side, combined
+    #[serde(rename = "agg_trade")]
+    AggTrade { data: AggTrade },
+    /// Order book of a token, sent after subscribing to its depth
+    #[serde(rename = "depth_snapshot")]
+    DepthSnapshot { data: DepthSnapshot },
+    /// Levels of a token's order book changed by a trade
+    #[serde(rename = "depth_update")]
+    DepthUpdate { data: DepthUpdate },
+    /// Recent trades for a token, sent oldest first after subscribing to its transactions
+    #[serde(rename = "trades")]
+    Trades { token: String, data: Vec<Transaction> },
+    /// Maintenance or shutdown notice, sent to every session
+    #[serde(rename = "system_status")]
+    SystemStatus { data: SystemStatus },
     /// Real-time K-line update
     #[serde(rename = "kline")]
     KLine { data: KLine },
+    /// A stored candle was corrected, sent on its K-line topic so clients replace that bar
+    #[serde(rename = "kline_amended")]
+    KLineAmended { data: KLine },
+    /// A page of candle history answering get_klines, oldest first
+    ///
+    /// `next_cursor` is the `before` of the following, older page, if more candles remain.
+    #[serde(rename = "klines_snapshot")]
+    KLinesSnapshot {
+        token: String,
+        interval: String,
+        data: Vec<KLine>,
+        next_cursor: Option<i64>,
+    },
+    /// Per-second server metrics
+    #[serde(rename = "ops_metrics")]
+    OpsMetrics { data: OpsMetrics },
+    /// An unconfigured token appeared in the transaction feed
+    #[serde(rename = "new_token")]
+    NewToken { data: NewTokenEvent },
+    /// A price alert triggered
+    #[serde(rename = "alert")]
+    Alert { data: AlertTrigger },
+    /// A token was listed at runtime
+    #[serde(rename = "token_listed")]
+    TokenListed { data: ListingEvent },
+    /// A token was delisted at runtime
+    #[serde(rename = "token_delisted")]
+    TokenDelisted { data: ListingEvent },
+    /// An indicator value at a closed candle
+    #[serde(rename = "indicator")]
+    Indicator { data: IndicatorUpdate },
+    /// A completed Renko brick or range bar
+    #[serde(rename = "bar")]
+    Bar { data: Bar },
+    /// Buy and sell volume of a candle, with the running delta
+    #[serde(rename = "delta")]
+    Delta { data: VolumeDelta },
+    /// 24-hour statistics and price extremes of a token
+    #[serde(rename = "ticker")]
+    Ticker { data: Ticker },
+    /// The configuration was reloaded
+    #[serde(rename = "config_updated")]
+    ConfigUpdated { data: ConfigUpdate },
     /// Subscription confirmation
     #[serde(rename = "subscribed")]
     Subscribed { subscription: SubscriptionType },
+    /// Result of subscribe_many: the subscriptions made and those refused
+    #[serde(rename = "subscribed_many")]
+    SubscribedMany {
+        accepted: Vec<SubscriptionType>,
+        rejected: Vec<RejectedSubscription>,
+    },
     /// Unsubscription confirmation
     #[serde(rename = "unsubscribed")]
     Unsubscribed { subscription: SubscriptionType },
-    /// Pong response
+    /// Current subscriptions of the session, in the order they were made
+    #[serde(rename = "subscriptions")]
+    Subscriptions { subscriptions: Vec<SubscriptionType> },
+    /// Confirmation of unsubscribe_all, with the subscriptions that were removed
+    #[serde(rename = "unsubscribed_all")]
+    UnsubscribedAll { subscriptions: Vec<SubscriptionType> },
+    /// Pong response, with the time the ping was received and the client's clock reading
     #[serde(rename = "pong")]
-    Pong,
-    /// Error message
+    Pong {
+        server_time: DateTime<Utc>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "typescript", ts(optional))]
+        client_time: Option<f64>,
+    },
+    /// The server's clock, pushed periodically when enabled
+    #[serde(rename = "server_time")]
+    ServerTime { server_time: DateTime<Utc> },
+    /// Encoding change confirmation, sent in the new format
+    #[serde(rename = "format")]
+    Format { format: WireFormat },
+    /// Resume confirmation, followed by the replayed messages
+    ///
+    /// `complete` is false when messages after `since` were already evicted
+    /// from the replay buffer.
+    #[serde(rename = "resumed")]
+    Resumed {
+        topic: String,
+        replayed: usize,
+        complete: bool,
+    },
+    /// Authentication confirmation
+    #[serde(rename = "authenticated")]
+    Authenticated { scope: ApiKeyScope },
+    /// Error message, with a machine-readable code
     #[serde(rename = "error")]
-    Error { message: String },
+    Error { message: String, code: ErrorCode },
+}
+
+/// Machine-readable reason of an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The message could not be decoded or has an unknown action
+    InvalidMessage,
+    /// A subscription could not be decoded
+    InvalidSubscription,
+    /// The interval is not aggregated by this server
+    InvalidInterval,
+    /// A subscription parameter such as a period or bar size is out of range
+    InvalidParameter,
+    /// The topic does not exist or cannot be resumed
+    InvalidTopic,
+    /// The token is not accepted by this server
+    UnknownToken,
+    /// The session or message holds too many subscriptions
+    SubscriptionLimit,
+    /// The session sent messages faster than allowed
+    RateLimited,
+    /// The session must authenticate first
+    AuthRequired,
+    /// The presented API key or token was refused
+    AuthFailed,
+    /// The session's scope does not permit the operation
+    Forbidden,
+}
+
+impl From<AuthError> for ErrorCode {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::Missing => ErrorCode::AuthRequired,
+            AuthError::Invalid | AuthError::InvalidToken => ErrorCode::AuthFailed,
+            AuthError::Forbidden => ErrorCode::Forbidden,
+        }
+    }
+}
+
+/// Identifier a client gives a message, echoed as `ref` in the reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    Text(String),
+}
+
+/// A client message with the ID the client gave it
+#[derive(Debug, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[schemars(rename = "ClientMessage")]
+pub struct ClientRequest {
+    /// Echoed as `ref` in the reply to the message
+    #[serde(default)]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub id: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: ClientMessage,
+}
+
+/// A subscription refused by subscribe_many, as the client sent it
+#[derive(Debug, Serialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct RejectedSubscription {
+    #[cfg_attr(feature = "typescript", ts(type = "unknown"))]
+    pub subscription: serde_json::Value,
+    pub error: String,
+    pub code: ErrorCode,
 }
 
 /// WebSocket session
@@ -75,322 +598,2307 @@ pub struct WsSession {
     id: Uuid,
     /// Last heartbeat time
     hb: Instant,
-    /// Current subscriptions
+    /// Interval between heartbeat pings
+    heartbeat_interval: Duration,
+    /// Time without client activity before the session is closed
+    client_timeout: Duration,
+    /// Interval between server_time messages, if they are pushed
+    server_time_interval: Option<Duration>,
+    /// Subscription made as soon as the session starts
+    initial_subscription: Option<SubscriptionType>,
+    /// Current subscriptions, at most one per stream
     subscriptions: Vec<SubscriptionType>,
+    /// Subscriptions the session may hold at once
+    max_subscriptions: usize,
     /// Reference to the WebSocket manager
-    manager: Arc<RwLock<WsManager>>,
+    manager: Arc<WsManager>,
+    /// API keys accepted by this server, if any are configured
+    authenticator: Option<Arc<Authenticator>>,
+    /// Scope of the key the session authenticated with
+    scope: Option<ApiKeyScope>,
+    /// ID of the client message being handled, if it has one
+    request_ref: Option<RequestId>,
+    /// Connection counted against the client's address, released when the session is dropped
+    _connection_slot: Option<ConnectionSlot>,
+    /// Time a session without a key has to authenticate before it is closed
+    auth_timeout: Duration,
+    /// Topic streams currently attached to this session
+    topic_streams: HashMap<Topic, SpawnHandle>,
+    /// Minimum payload size sent deflate-compressed, if the client opted in
+    compression_threshold: Option<usize>,
+    /// Encoding of messages to and from the client
+    format: WireFormat,
+    /// K-line store used to resend candles closed while the session lagged
+    kline_service: Arc<KLineService>,
+    /// Trade history sent when subscribing to transactions, if kept
+    trade_service: Option<Arc<TradeService>>,
+    /// Order books sent when subscribing to depth, if simulated
+    order_books: Option<Arc<OrderBookSimulator>>,
+    /// Registered tokens, which may be subscribed to before their first trade
+    token_registry: Option<Arc<TokenRegistry>>,
+    /// Open time of the last K-line delivered per K-line topic
+    kline_cursors: HashMap<Topic, DateTime<Utc>>,
+    /// Handling of this session when it falls behind
+    slow_consumer_policy: SlowConsumerPolicy,
+    /// Dropped messages after which the session is disconnected (0 = never)
+    max_dropped_messages: u64,
+    /// Connection details and message counters, shared with the manager
+    stats: Arc<SessionStats>,
+    /// Coalescing state for throttled K-line topics
+    kline_throttles: HashMap<Topic, KLineThrottle>,
+    /// K-line topics delivered as Heikin-Ashi candles, with the open of the latest candle
+    heikin_ashi: HashMap<Topic, Option<HeikinAshiSeed>>,
+    /// Limit on inbound messages, if rate limiting is enabled
+    rate_limit: Option<TokenBucket>,
+    /// Rate-limited messages after which the session is disconnected
+    max_rate_violations: u32,
+    /// Messages rejected by the rate limit
+    rate_violations: u32,
+}
+
+/// Connection details and message counters of a session
+#[derive(Debug)]
+pub struct SessionStats {
+    /// When the client connected
+    connected_at: DateTime<Utc>,
+    /// Address of the client, if known
+    remote_addr: Option<String>,
+    /// Messages sent to the client
+    messages_sent: AtomicU64,
+    /// Messages received from the client
+    messages_received: AtomicU64,
+    /// Messages dropped because the session fell behind
+    dropped_messages: AtomicU64,
+}
+
+impl SessionStats {
+    fn new(remote_addr: Option<String>) -> Self {
+        Self {
+            connected_at: Utc::now(),
+            remote_addr,
+            messages_sent: AtomicU64::new(0),
+            messages_received: AtomicU64::new(0),
+            dropped_messages: AtomicU64::new(0),
+        }
+    }
+
+    fn record_sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record dropped messages, returning the session's total
+    fn record_dropped(&self, count: u64) -> u64 {
+        self.dropped_messages.fetch_add(count, Ordering::Relaxed) + count
+    }
+}
+
+/// A connection counted against a client address
+///
+/// Dropping the slot releases the connection.
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    ip: IpAddr,
+    connections: Arc<DashMap<IpAddr, usize>>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.connections.remove_if_mut(&self.ip, |_, count| {
+            *count -= 1;
+            *count == 0
+        });
+    }
+}
+
+/// Address and statistics of a started session
+#[derive(Debug)]
+struct SessionHandle {
+    addr: actix::Addr<WsSession>,
+    stats: Arc<SessionStats>,
+}
+
+/// An active session as reported to operators
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SessionInfo {
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    pub connected_at: DateTime<Utc>,
+    /// Address of the client, if known
+    pub remote_addr: Option<String>,
+    /// Subscriptions in the form they were requested
+    #[schema(value_type = Vec<Object>)]
+    pub subscriptions: Vec<SubscriptionType>,
+    /// Messages sent to the client
+    pub messages_sent: u64,
+    /// Messages received from the client
+    pub messages_received: u64,
+    /// Messages dropped because the session fell behind
+    pub dropped_messages: u64,
+}
+
+/// Coalescing state for a throttled K-line topic
+struct KLineThrottle {
+    /// Minimum time between updates
+    window: Duration,
+    /// When the last update was sent
+    last_sent: Option<Instant>,
+    /// Open time of the candle the latest update belongs to
+    candle: Option<DateTime<Utc>>,
+    /// Latest update held back by the window
+    pending: Option<Arc<TopicMessage>>,
+    /// Scheduled send of the pending update
+    flush: Option<SpawnHandle>,
+}
+
+/// Heikin-Ashi open of the candle being updated on a topic
+struct HeikinAshiSeed {
+    /// Open time of the candle
+    candle: DateTime<Utc>,
+    /// Heikin-Ashi open, if an earlier candle is stored
+    open: Option<f64>,
+}
+
+impl KLineThrottle {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_sent: None,
+            candle: None,
+            pending: None,
+            flush: None,
+        }
+    }
 }
 
 impl WsSession {
-    pub fn new(manager: Arc<RwLock<WsManager>>, _kline_service: Arc<KLineService>) -> Self {
+    pub fn new(manager: Arc<WsManager>, kline_service: Arc<KLineService>) -> Self {
         let id = Uuid::new_v4();
         
         // Register this session with the manager
-        if let Ok(mut mgr) = manager.write() {
-            mgr.add_session(id);
-        }
+        manager.add_session(id);
 
         Self {
             id,
             hb: Instant::now(),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            client_timeout: DEFAULT_CLIENT_TIMEOUT,
+            server_time_interval: None,
+            initial_subscription: None,
             subscriptions: Vec::new(),
+            max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
             manager,
+            authenticator: None,
+            scope: None,
+            request_ref: None,
+            _connection_slot: None,
+            auth_timeout: DEFAULT_AUTH_TIMEOUT,
+            topic_streams: HashMap::new(),
+            compression_threshold: None,
+            format: WireFormat::Json,
+            kline_service,
+            trade_service: None,
+            order_books: None,
+            token_registry: None,
+            kline_cursors: HashMap::new(),
+            slow_consumer_policy: SlowConsumerPolicy::Conflate,
+            max_dropped_messages: DEFAULT_MAX_DROPPED_MESSAGES,
+            stats: Arc::new(SessionStats::new(None)),
+            kline_throttles: HashMap::new(),
+            heikin_ashi: HashMap::new(),
+            rate_limit: None,
+            max_rate_violations: 0,
+            rate_violations: 0,
         }
     }
 
-    /// Start heartbeat process
-    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
-        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
-            if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
-                println!("WebSocket client heartbeat failed, disconnecting!");
-                ctx.stop();
-                return;
-            }
-            ctx.ping(b"");
-        });
+    /// Set the accepted API keys and the scope granted at the handshake
+    pub fn with_auth(
+        mut self,
+        authenticator: Option<Arc<Authenticator>>,
+        scope: Option<ApiKeyScope>,
+    ) -> Self {
+        self.authenticator = authenticator;
+        self.scope = scope;
+        self
     }
 
-    /// Send message to client
-    fn send_message(&self, msg: ServerMessage, ctx: &mut ws::WebsocketContext<Self>) {
-        if let Ok(json) = serde_json::to_string(&msg) {
-            ctx.text(json);
+    /// Close the session unless it authenticates within `timeout` of connecting
+    pub fn with_auth_timeout(mut self, timeout: Duration) -> Self {
+        self.auth_timeout = timeout;
+        self
+    }
+
+    /// Whether the session may subscribe
+    fn authenticated(&self) -> bool {
+        self.scope.is_some()
+            || self.authenticator.as_ref().is_none_or(|authenticator| {
+                authenticator.authorize(None, ApiKeyScope::Read).is_ok()
+            })
+    }
+
+    /// Handle an auth message
+    fn handle_auth(
+        &mut self,
+        api_key: Option<&str>,
+        token: Option<&str>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let authenticator = self.authenticator.as_deref();
+        let scope = match (api_key, token) {
+            (Some(api_key), _) => authenticator
+                .and_then(|authenticator| authenticator.scope_of(api_key))
+                .ok_or(AuthError::Invalid),
+            (None, Some(token)) => authenticator
+                .ok_or(AuthError::InvalidToken)
+                .and_then(|authenticator| authenticator.scope_of_token(token)),
+            (None, None) => Err(AuthError::Missing),
+        };
+        match scope {
+            Ok(scope) => {
+                self.scope = Some(scope);
+                self.send_reply(ServerMessage::Authenticated { scope }, ctx);
+            }
+            Err(e) => self.send_error(e.into(), e.message(), ctx),
         }
     }
 
-    /// Handle subscription
-    fn handle_subscribe(&mut self, subscription: SubscriptionType, ctx: &mut ws::WebsocketContext<Self>) {
-        // Validate subscription
-        if let SubscriptionType::KLines { ref interval, .. } = subscription {
-            if interval.parse::<TimeInterval>().is_err() {
-                self.send_message(
-                    ServerMessage::Error {
-                        message: format!("Invalid interval: {}", interval),
-                    },
-                    ctx,
-                );
+    /// Close the session if it has not authenticated once the auth timeout passes
+    fn enforce_auth_timeout(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.authenticated() {
+            return;
+        }
+        ctx.run_later(self.auth_timeout, |act, ctx| {
+            if act.authenticated() {
                 return;
             }
-        }
+            tracing::warn!("Disconnecting WebSocket session {} that did not authenticate", act.id);
+            act.send_error(ErrorCode::AuthRequired, "Authentication timed out, disconnecting", ctx);
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some("Authentication timeout".to_string()),
+            }));
+            ctx.stop();
+        });
+    }
 
-        // Add subscription
-        self.subscriptions.push(subscription.clone());
+    /// Ping the client every `interval` and close the session after `timeout` without activity
+    pub fn with_heartbeat(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self.client_timeout = timeout;
+        self
+    }
 
-        // Register subscription with manager
-        if let Ok(mut manager) = self.manager.write() {
-            manager.add_subscription(self.id, subscription.clone());
-        }
+    /// Push the server's clock to the client every `interval`
+    pub fn with_server_time_interval(mut self, interval: Option<Duration>) -> Self {
+        self.server_time_interval = interval;
+        self
+    }
 
-        // Send confirmation
-        self.send_message(ServerMessage::Subscribed { subscription }, ctx);
+    /// Subscribe to `subscription` when the session starts, without a client message
+    pub fn with_initial_subscription(mut self, subscription: SubscriptionType) -> Self {
+        self.initial_subscription = Some(subscription);
+        self
     }
 
-    /// Handle unsubscription
-    fn handle_unsubscribe(&mut self, subscription: SubscriptionType, ctx: &mut ws::WebsocketContext<Self>) {
-        // Remove subscription
-        self.subscriptions.retain(|s| !subscription_matches(s, &subscription));
+    /// Send recent trades from `trade_service` when subscribing to transactions
+    pub fn with_trade_service(mut self, trade_service: Option<Arc<TradeService>>) -> Self {
+        self.trade_service = trade_service;
+        self
+    }
 
-        // Unregister subscription with manager
-        if let Ok(mut manager) = self.manager.write() {
-            manager.remove_subscription(self.id, &subscription);
-        }
+    /// Send the order book from `order_books` when subscribing to depth
+    pub fn with_order_books(mut self, order_books: Option<Arc<OrderBookSimulator>>) -> Self {
+        self.order_books = order_books;
+        self
+    }
 
-        // Send confirmation
-        self.send_message(ServerMessage::Unsubscribed { subscription }, ctx);
+    /// Also accept subscriptions to tokens registered in `token_registry` that have not traded yet
+    pub fn with_token_registry(mut self, token_registry: Option<Arc<TokenRegistry>>) -> Self {
+        self.token_registry = token_registry;
+        self
     }
-}
 
-impl Actor for WsSession {
-    type Context = ws::WebsocketContext<Self>;
+    /// Compress payloads of at least `threshold` bytes
+    pub fn with_compression(mut self, threshold: Option<usize>) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
 
-    fn started(&mut self, ctx: &mut Self::Context) {
-        self.hb(ctx);
-        
-        // Set the session address in the manager
-        if let Ok(mut manager) = self.manager.write() {
-            manager.set_session_addr(self.id, ctx.address());
-        }
-        
-        println!("WebSocket session {} started", self.id);
+    /// Set the initial message encoding
+    pub fn with_format(mut self, format: WireFormat) -> Self {
+        self.format = format;
+        self
     }
 
-    fn stopped(&mut self, _ctx: &mut Self::Context) {
-        // Remove session from manager
-        if let Ok(mut manager) = self.manager.write() {
-            manager.remove_session(self.id);
+    /// Set how the session is handled when it falls behind
+    pub fn with_slow_consumer_policy(mut self, policy: SlowConsumerPolicy, max_dropped_messages: u64) -> Self {
+        self.slow_consumer_policy = policy;
+        self.max_dropped_messages = max_dropped_messages;
+        self
+    }
+
+    /// Limit inbound messages, disconnecting after `max_violations` rejected ones
+    pub fn with_rate_limit(mut self, rate_limit: Option<TokenBucket>, max_violations: u32) -> Self {
+        self.rate_limit = rate_limit;
+        self.max_rate_violations = max_violations;
+        self
+    }
+
+    /// Record the address of the connected client
+    pub fn with_remote_addr(mut self, remote_addr: Option<String>) -> Self {
+        self.stats = Arc::new(SessionStats::new(remote_addr));
+        self
+    }
+
+    /// Limit the number of subscriptions the session may hold at once
+    pub fn with_max_subscriptions(mut self, max_subscriptions: usize) -> Self {
+        self.max_subscriptions = max_subscriptions;
+        self
+    }
+
+    /// Hold a connection reserved for the client's address until the session is dropped
+    pub fn with_connection_slot(mut self, slot: Option<ConnectionSlot>) -> Self {
+        self._connection_slot = slot;
+        self
+    }
+
+    /// Check an inbound message against the rate limit
+    ///
+    /// Rejected messages are answered with an error until the session has
+    /// used up its violations, then it is closed.
+    fn admit_message(&mut self, ctx: &mut ws::WebsocketContext<Self>) -> bool {
+        let Some(bucket) = &mut self.rate_limit else {
+            return true;
+        };
+        let Err(retry_after) = bucket.try_acquire(Instant::now()) else {
+            return true;
+        };
+
+        self.rate_violations += 1;
+        if self.rate_violations >= self.max_rate_violations {
+            tracing::warn!("Disconnecting WebSocket session {} for exceeding the rate limit", self.id);
+            self.send_error(ErrorCode::RateLimited, "Rate limit exceeded, disconnecting", ctx);
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some("Rate limit exceeded".to_string()),
+            }));
+            ctx.stop();
+        } else {
+            self.send_error(
+                ErrorCode::RateLimited,
+                format!("Rate limit exceeded, retry after {} ms", retry_after.as_millis().max(1)),
+                ctx,
+            );
         }
-        println!("WebSocket session {} stopped", self.id);
+        false
     }
-}
 
-impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
-    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
-        match msg {
-            Ok(ws::Message::Ping(msg)) => {
-                self.hb = Instant::now();
-                ctx.pong(&msg);
+    /// Start heartbeat process
+    fn hb(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(self.heartbeat_interval, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > act.client_timeout {
+                tracing::warn!("WebSocket client heartbeat failed, disconnecting!");
+                ctx.stop();
+                return;
             }
-            Ok(ws::Message::Pong(_)) => {
-                self.hb = Instant::now();
+            ctx.ping(b"");
+        });
+    }
+
+    /// Attach and detach topic streams to match the current subscriptions
+    fn sync_topics(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let desired = Topic::for_subscriptions(&self.subscriptions);
+
+        self.topic_streams.retain(|topic, handle| {
+            let keep = desired.contains(topic);
+            if !keep {
+                ctx.cancel_future(*handle);
             }
-            Ok(ws::Message::Text(text)) => {
-                self.hb = Instant::now();
-                
-                match serde_json::from_str::<ClientMessage>(&text) {
-                    Ok(ClientMessage::Subscribe { subscription }) => {
-                        self.handle_subscribe(subscription, ctx);
-                    }
-                    Ok(ClientMessage::Unsubscribe { subscription }) => {
-                        self.handle_unsubscribe(subscription, ctx);
-                    }
-                    Ok(ClientMessage::Ping) => {
-                        self.send_message(ServerMessage::Pong, ctx);
-                    }
-                    Err(e) => {
-                        self.send_message(
-                            ServerMessage::Error {
-                                message: format!("Invalid message format: {}", e),
-                            },
-                            ctx,
-                        );
-                    }
-                }
+            keep
+        });
+        self.kline_cursors.retain(|topic, _| desired.contains(topic));
+
+        let throttles: HashMap<Topic, Duration> = self
+            .subscriptions
+            .iter()
+            .filter_map(|subscription| match subscription {
+                SubscriptionType::KLines {
+                    token,
+                    interval,
+                    throttle_ms: Some(throttle_ms),
+                    ..
+                } if *throttle_ms > 0 => interval.parse().ok().map(|interval| {
+                    let topic = Topic::KLines {
+                        token: token.clone(),
+                        interval,
+                    };
+                    (topic, Duration::from_millis(*throttle_ms))
+                }),
+                _ => None,
+            })
+            .collect();
+        self.kline_throttles.retain(|topic, throttle| match throttles.get(topic) {
+            Some(window) => {
+                throttle.window = *window;
+                true
             }
-            Ok(ws::Message::Binary(_)) => {
-                self.send_message(
-                    ServerMessage::Error {
-                        message: "Binary messages not supported".to_string(),
-                    },
-                    ctx,
-                );
+            None => {
+                if let Some(handle) = throttle.flush.take() {
+                    ctx.cancel_future(handle);
+                }
+                false
             }
-            Ok(ws::Message::Close(reason)) => {
-                ctx.close(reason);
-                ctx.stop();
+        });
+        for (topic, window) in throttles {
+            self.kline_throttles
+                .entry(topic)
+                .or_insert_with(|| KLineThrottle::new(window));
+        }
+
+        let heikin_ashi: HashSet<Topic> = self
+            .subscriptions
+            .iter()
+            .filter_map(|subscription| match subscription {
+                SubscriptionType::KLines {
+                    token,
+                    interval,
+                    candle_type: Some(CandleType::HeikinAshi),
+                    ..
+                } => interval.parse().ok().map(|interval| Topic::KLines {
+                    token: token.clone(),
+                    interval,
+                }),
+                _ => None,
+            })
+            .collect();
+        self.heikin_ashi.retain(|topic, _| heikin_ashi.contains(topic));
+        for topic in heikin_ashi {
+            self.heikin_ashi.entry(topic).or_insert(None);
+        }
+
+        for topic in desired {
+            if !self.topic_streams.contains_key(&topic) {
+                let receiver = self.manager.subscribe_topic(&topic);
+                self.attach_topic(topic, receiver, ctx);
             }
-            _ => ctx.stop(),
         }
     }
-}
 
-/// Message for broadcasting transactions
-#[derive(Message)]
-#[rtype(result = "()")]
-pub struct BroadcastTransaction(pub Transaction);
+    /// Start delivering a topic's messages to this session
+    fn attach_topic(
+        &mut self,
+        topic: Topic,
+        receiver: broadcast::Receiver<Arc<TopicMessage>>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        if let Topic::KLines { .. } = topic {
+            self.kline_cursors.insert(topic.clone(), Utc::now());
+        }
+        let handle = ctx.add_stream(topic_stream(topic.clone(), receiver));
+        self.topic_streams.insert(topic, handle);
+    }
 
-/// Message for broadcasting K-line updates
-#[derive(Message)]
-#[rtype(result = "()")]
-pub struct BroadcastKLine(pub KLine);
+    /// Send a K-line update on a throttled topic, coalescing updates within the window
+    fn throttle_kline(
+        &mut self,
+        topic: &Topic,
+        candle: DateTime<Utc>,
+        message: Arc<TopicMessage>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let Some(throttle) = self.kline_throttles.get_mut(topic) else {
+            return;
+        };
 
-impl Handler<BroadcastTransaction> for WsSession {
-    type Result = ();
+        // A new candle means the previous one closed; its final state is never coalesced away
+        let previous = throttle.candle.replace(candle).filter(|previous| *previous != candle);
+        if previous.is_some() {
+            throttle.pending = None;
+            if let Some(handle) = throttle.flush.take() {
+                ctx.cancel_future(handle);
+            }
+        }
+        if let (Some(previous), Topic::KLines { token, interval }) = (previous, topic) {
+            let closed = self
+                .kline_service
+                .get_klines(token, *interval, previous, previous, Some(1))
+                .pop();
+            if let Some(closed) = closed {
+                // It stands in for the candle's last update, published just before this one
+                let sequence = message.sequence.clone().map(|(topic, seq)| (topic, seq.saturating_sub(1)));
+                self.send_resent_kline(topic, closed, sequence, ctx);
+            }
+        }
 
-    fn handle(&mut self, msg: BroadcastTransaction, ctx: &mut Self::Context) {
-        let transaction = msg.0;
-        
-        // Check if this session is subscribed to this transaction
-        for subscription in &self.subscriptions {
-            match subscription {
-                SubscriptionType::AllTransactions => {
-                    self.send_message(ServerMessage::Transaction { data: transaction.clone() }, ctx);
-                    break;
-                }
-                SubscriptionType::Transactions { tokens } => {
-                    if tokens.contains(&transaction.token) {
-                        self.send_message(ServerMessage::Transaction { data: transaction.clone() }, ctx);
-                        break;
-                    }
+        let Some(throttle) = self.kline_throttles.get_mut(topic) else {
+            return;
+        };
+        match throttle.last_sent.map(|sent| sent.elapsed()) {
+            Some(elapsed) if elapsed < throttle.window => {
+                throttle.pending = Some(message);
+                if throttle.flush.is_none() {
+                    let topic = topic.clone();
+                    throttle.flush = Some(ctx.run_later(throttle.window - elapsed, move |act, ctx| {
+                        act.flush_kline(&topic, ctx);
+                    }));
                 }
-                _ => {}
+            }
+            _ => {
+                throttle.last_sent = Some(Instant::now());
+                self.send_topic_message(topic, &message, ctx);
             }
         }
     }
-}
 
-impl Handler<BroadcastKLine> for WsSession {
-    type Result = ();
-
-    fn handle(&mut self, msg: BroadcastKLine, ctx: &mut Self::Context) {
-        let kline = msg.0;
-        
-        // Check if this session is subscribed to this K-line
-        for subscription in &self.subscriptions {
-            if let SubscriptionType::KLines { token, interval } = subscription {
-                if token == &kline.token && interval == kline.interval.as_str() {
-                    self.send_message(ServerMessage::KLine { data: kline.clone() }, ctx);
-                    break;
-                }
-            }
+    /// Send the update held back on a throttled topic
+    fn flush_kline(&mut self, topic: &Topic, ctx: &mut ws::WebsocketContext<Self>) {
+        let Some(throttle) = self.kline_throttles.get_mut(topic) else {
+            return;
+        };
+        throttle.flush = None;
+        if let Some(message) = throttle.pending.take() {
+            throttle.last_sent = Some(Instant::now());
+            self.send_topic_message(topic, &message, ctx);
         }
     }
-}
 
-/// WebSocket manager for handling multiple sessions
-#[derive(Debug)]
-pub struct WsManager {
-    /// Active sessions
-    sessions: HashMap<Uuid, actix::Addr<WsSession>>,
-    /// Session subscriptions
-    subscriptions: HashMap<Uuid, Vec<SubscriptionType>>,
-}
+    /// Apply the slow-consumer policy after `skipped` messages were dropped on a topic
+    fn handle_lag(&mut self, topic: &Topic, skipped: u64, ctx: &mut ws::WebsocketContext<Self>) {
+        let dropped_messages = self.stats.record_dropped(skipped);
+        self.manager.record_dropped_messages(skipped);
 
-impl WsManager {
-    pub fn new() -> Self {
-        Self {
-            sessions: HashMap::new(),
-            subscriptions: HashMap::new(),
+        let over_limit =
+            self.max_dropped_messages > 0 && dropped_messages > self.max_dropped_messages;
+        if self.slow_consumer_policy == SlowConsumerPolicy::Disconnect || over_limit {
+            tracing::warn!(
+                "Disconnecting slow WebSocket session {} after {} dropped messages",
+                self.id,
+                dropped_messages
+            );
+            self.manager.record_slow_consumer_disconnect();
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some("Slow consumer".to_string()),
+            }));
+            ctx.stop();
+            return;
         }
-    }
 
-    /// Add a new session
-    pub fn add_session(&mut self, session_id: Uuid) {
-        self.subscriptions.insert(session_id, Vec::new());
-    }
+        tracing::warn!("WebSocket session {} lagged on {}, dropped {} messages", self.id, topic, skipped);
 
-    /// Remove a session
-    pub fn remove_session(&mut self, session_id: Uuid) {
-        self.sessions.remove(&session_id);
-        self.subscriptions.remove(&session_id);
+        // Dropped updates are conflated, but candles that closed in the gap are resent
+        if let Topic::KLines { token, interval } = topic {
+            let Some(since) = self.kline_cursors.get(topic).copied() else {
+                return;
+            };
+            let closed = self
+                .kline_service
+                .get_klines(token, *interval, since, Utc::now(), None)
+                .into_iter()
+                .filter(|kline| kline.is_closed);
+            for kline in closed {
+                self.kline_cursors.insert(topic.clone(), kline.timestamp);
+                self.send_resent_kline(topic, kline, None, ctx);
+            }
+        }
     }
 
-    /// Add session address
-    pub fn set_session_addr(&mut self, session_id: Uuid, addr: actix::Addr<WsSession>) {
-        self.sessions.insert(session_id, addr);
+    /// Send a closed candle looked up again, encoded and compressed like topic messages
+    fn send_resent_kline(
+        &mut self,
+        topic: &Topic,
+        kline: KLine,
+        sequence: Option<(String, u64)>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let data = self.present_kline(topic, kline);
+        self.send_encoded(&TopicMessage::new(ServerMessage::KLine { data }, sequence), ctx);
     }
 
-    /// Add subscription for a session
-    pub fn add_subscription(&mut self, session_id: Uuid, subscription: SubscriptionType) {
-        if let Some(subs) = self.subscriptions.get_mut(&session_id) {
-            subs.push(subscription);
-        }
+    /// Send message to client
+    fn send_message(&self, msg: ServerMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        self.send_serialized(&msg, ctx);
     }
 
-    /// Remove subscription for a session
-    pub fn remove_subscription(&mut self, session_id: Uuid, subscription: &SubscriptionType) {
-        if let Some(subs) = self.subscriptions.get_mut(&session_id) {
-            subs.retain(|s| !subscription_matches(s, subscription));
-        }
+    /// Send a reply, referring to the client message being handled if it has an ID
+    fn send_reply(&self, msg: ServerMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        let envelope = Envelope {
+            message: &msg,
+            topic: None,
+            seq: None,
+            request_ref: self.request_ref.as_ref(),
+        };
+        self.send_serialized(&envelope, ctx);
     }
 
-    /// Broadcast transaction to all relevant sessions
-    pub fn broadcast_transaction(&self, transaction: &Transaction) {
-        for (session_id, addr) in &self.sessions {
-            if let Some(subscriptions) = self.subscriptions.get(session_id) {
-                let should_send = subscriptions.iter().any(|sub| match sub {
-                    SubscriptionType::AllTransactions => true,
-                    SubscriptionType::Transactions { tokens } => tokens.contains(&transaction.token),
-                    _ => false,
-                });
+    /// Send an error reply
+    fn send_error(&self, code: ErrorCode, message: impl Into<String>, ctx: &mut ws::WebsocketContext<Self>) {
+        let error = ServerMessage::Error {
+            message: message.into(),
+            code,
+        };
+        self.send_reply(error, ctx);
+    }
 
-                if should_send {
-                    addr.do_send(BroadcastTransaction(transaction.clone()));
+    fn send_serialized<T: Serialize>(&self, msg: &T, ctx: &mut ws::WebsocketContext<Self>) {
+        match self.format {
+            WireFormat::Json => {
+                if let Ok(json) = serde_json::to_string(msg) {
+                    ctx.text(json);
+                    self.stats.record_sent();
                 }
             }
-        }
-    }
-
-    /// Broadcast K-line update to all relevant sessions
-    pub fn broadcast_kline(&self, kline: &KLine) {
-        for (session_id, addr) in &self.sessions {
-            if let Some(subscriptions) = self.subscriptions.get(session_id) {
-                let should_send = subscriptions.iter().any(|sub| match sub {
-                    SubscriptionType::KLines { token, interval } => {
-                        token == &kline.token && interval == kline.interval.as_str()
-                    }
-                    _ => false,
-                });
-
-                if should_send {
-                    addr.do_send(BroadcastKLine(kline.clone()));
+            format => {
+                if let Some(bytes) = format.encode(msg) {
+                    ctx.binary(bytes);
+                    self.stats.record_sent();
                 }
             }
         }
     }
 
-    /// Get session count
-    pub fn session_count(&self) -> usize {
-        self.sessions.len()
+    /// Handle a decoded client frame, remembering its ID while it is handled
+    fn handle_client_request(
+        &mut self,
+        value: Result<serde_json::Value, String>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        // The ID is kept even when the rest of the message is invalid
+        self.request_ref = value
+            .as_ref()
+            .ok()
+            .and_then(|value| value.get("id"))
+            .and_then(|id| RequestId::deserialize(id).ok());
+        let msg = value.and_then(|value| {
+            serde_json::from_value::<ClientRequest>(value)
+                .map(|request| request.message)
+                .map_err(|e| e.to_string())
+        });
+        self.handle_client_message(msg, ctx);
+        self.request_ref = None;
     }
-}
 
-impl Default for WsManager {
-    fn default() -> Self {
-        Self::new()
-    }
+    /// Handle a decoded client message
+    fn handle_client_message(
+        &mut self,
+        msg: Result<ClientMessage, String>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        let _span = tracing::info_span!("ws_message", session_id = %self.id).entered();
+        match msg {
+            Ok(
+                ClientMessage::Subscribe { .. }
+                | ClientMessage::SubscribeMany { .. }
+                | ClientMessage::Resume { .. }
+                | ClientMessage::GetKLines { .. },
+            ) if !self.authenticated() =>
+            {
+                self.send_error(ErrorCode::AuthRequired, "Authentication required", ctx);
+            }
+            Ok(ClientMessage::Subscribe { subscription }) => {
+                self.handle_subscribe(subscription.canonicalize(), ctx);
+            }
+            Ok(ClientMessage::SubscribeMany { subscriptions }) => {
+                self.handle_subscribe_many(subscriptions, ctx);
+            }
+            Ok(ClientMessage::Unsubscribe { subscription }) => {
+                self.handle_unsubscribe(subscription.canonicalize(), ctx);
+            }
+            Ok(ClientMessage::ListSubscriptions) => {
+                let subscriptions = self.subscriptions.clone();
+                self.send_reply(ServerMessage::Subscriptions { subscriptions }, ctx);
+            }
+            Ok(ClientMessage::UnsubscribeAll) => {
+                self.handle_unsubscribe_all(ctx);
+            }
+            Ok(ClientMessage::Ping { client_time }) => {
+                let server_time = Utc::now();
+                self.send_reply(ServerMessage::Pong { server_time, client_time }, ctx);
+            }
+            Ok(ClientMessage::SetFormat { format }) => {
+                self.format = format;
+                self.send_reply(ServerMessage::Format { format }, ctx);
+            }
+            Ok(ClientMessage::Resume { topic, since }) => {
+                self.handle_resume(&topic, since, ctx);
+            }
+            Ok(ClientMessage::Auth { api_key, token }) => {
+                self.handle_auth(api_key.as_deref(), token.as_deref(), ctx);
+            }
+            Ok(ClientMessage::GetKLines {
+                token,
+                interval,
+                limit,
+                before,
+                candle_type,
+            }) => match self.kline_history(&Symbol::canonicalize(&token), interval, limit, before, candle_type) {
+                Ok(snapshot) => self.send_reply(snapshot, ctx),
+                Err((code, message)) => self.send_error(code, message, ctx),
+            },
+            Err(e) => {
+                self.send_error(ErrorCode::InvalidMessage, format!("Invalid message format: {}", e), ctx);
+            }
+        }
+    }
+
+    /// Handle subscription
+    fn handle_subscribe(&mut self, subscription: SubscriptionType, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Err((code, message)) = self.check_subscription(&subscription) {
+            self.send_error(code, message, ctx);
+            return;
+        }
+
+        // Add subscription
+        if let Err(message) = self.add_subscription(&subscription) {
+            self.send_error(ErrorCode::SubscriptionLimit, message, ctx);
+            return;
+        }
+        self.sync_topics(ctx);
+
+        // Send confirmation, then the trade history or book of the subscription
+        self.send_reply(
+            ServerMessage::Subscribed {
+                subscription: subscription.clone(),
+            },
+            ctx,
+        );
+        self.send_snapshots(std::slice::from_ref(&subscription), ctx);
+    }
+
+    /// Handle several subscriptions, confirming them in a single message
+    fn handle_subscribe_many(&mut self, subscriptions: Vec<serde_json::Value>, ctx: &mut ws::WebsocketContext<Self>) {
+        if subscriptions.len() > MAX_SUBSCRIBE_MANY {
+            self.send_error(
+                ErrorCode::SubscriptionLimit,
+                format!("Too many subscriptions: at most {} per message", MAX_SUBSCRIBE_MANY),
+                ctx,
+            );
+            return;
+        }
+
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+        for value in subscriptions {
+            let checked = serde_json::from_value::<SubscriptionType>(value.clone())
+                .map(SubscriptionType::canonicalize)
+                .map_err(|e| (ErrorCode::InvalidSubscription, format!("Invalid subscription: {}", e)))
+                .and_then(|subscription| self.check_subscription(&subscription).map(|()| subscription))
+                .and_then(|subscription| {
+                    self.add_subscription(&subscription)
+                        .map(|()| subscription)
+                        .map_err(|message| (ErrorCode::SubscriptionLimit, message))
+                });
+            match checked {
+                Ok(subscription) => accepted.push(subscription),
+                Err((code, error)) => rejected.push(RejectedSubscription {
+                    subscription: value,
+                    error,
+                    code,
+                }),
+            }
+        }
+
+        // Attach the topics of every accepted subscription at once
+        self.sync_topics(ctx);
+
+        self.send_reply(
+            ServerMessage::SubscribedMany {
+                accepted: accepted.clone(),
+                rejected,
+            },
+            ctx,
+        );
+        self.send_snapshots(&accepted, ctx);
+    }
+
+    /// Send the trade history of transaction subscriptions and the order book of
+    /// depth subscriptions, where kept
+    fn send_snapshots(&self, subscriptions: &[SubscriptionType], ctx: &mut ws::WebsocketContext<Self>) {
+        for subscription in subscriptions {
+            match subscription {
+                SubscriptionType::Transactions { tokens } => {
+                    let Some(trade_service) = &self.trade_service else {
+                        continue;
+                    };
+                    for token in tokens {
+                        let data = trade_service.recent(token, TRADE_SNAPSHOT_LIMIT);
+                        self.send_message(
+                            ServerMessage::Trades {
+                                token: token.clone(),
+                                data,
+                            },
+                            ctx,
+                        );
+                    }
+                }
+                SubscriptionType::Depth { token } => {
+                    let snapshot = self
+                        .order_books
+                        .as_ref()
+                        .and_then(|order_books| order_books.snapshot(token, order_books.levels()));
+                    if let Some(data) = snapshot {
+                        self.send_message(ServerMessage::DepthSnapshot { data }, ctx);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Add a subscription, replacing the one for the same stream if there is one
+    ///
+    /// Fails when the subscription is new and the session already holds its
+    /// maximum number of subscriptions.
+    fn add_subscription(&mut self, subscription: &SubscriptionType) -> Result<(), String> {
+        match self
+            .subscriptions
+            .iter()
+            .position(|existing| subscription_matches(existing, subscription))
+        {
+            Some(index) => self.subscriptions[index] = subscription.clone(),
+            None if self.subscriptions.len() >= self.max_subscriptions => {
+                return Err(format!(
+                    "Subscription limit reached: at most {} per session",
+                    self.max_subscriptions
+                ));
+            }
+            None => self.subscriptions.push(subscription.clone()),
+        }
+        self.manager.add_subscription(self.id, subscription.clone());
+        Ok(())
+    }
+
+    /// Check that a subscription is valid and permitted for this session
+    fn check_subscription(&self, subscription: &SubscriptionType) -> Result<(), (ErrorCode, String)> {
+        if let SubscriptionType::KLines { interval, .. }
+        | SubscriptionType::Indicators { interval, .. }
+        | SubscriptionType::Delta { interval, .. } = subscription
+        {
+            // Trade-count and volume intervals must be configured to be aggregated
+            if !interval.parse().is_ok_and(|interval| self.kline_service.supports(interval)) {
+                return Err((ErrorCode::InvalidInterval, format!("Invalid interval: {}", interval)));
+            }
+        }
+        if let Some(Err(message)) = subscription.indicator_spec() {
+            return Err((ErrorCode::InvalidParameter, message));
+        }
+        if let Some(Err(message)) = subscription.bar_spec() {
+            return Err((ErrorCode::InvalidParameter, message));
+        }
+
+        // A strict service never has data for tokens outside its allowlist, and
+        // a token without a series or registration would only hold a topic open
+        if let Some(token) = subscription.tokens().find(|token| !self.knows_token(token)) {
+            return Err((ErrorCode::UnknownToken, format!("Unknown token: {}", token)));
+        }
+
+        if let Some(api_key) = subscription.admin_key() {
+            let name = match subscription {
+                SubscriptionType::OpsMetrics { .. } => "ops_metrics",
+                _ => "admin_events",
+            };
+            // Either the session or the subscription itself may carry the admin key
+            let authenticator = self.authenticator.as_deref();
+            let key_scope = api_key.zip(authenticator).and_then(|(key, auth)| auth.scope_of(key));
+            let message = if self.scope == Some(ApiKeyScope::Admin)
+                || key_scope == Some(ApiKeyScope::Admin)
+            {
+                None
+            } else if !authenticator.is_some_and(Authenticator::has_admin_keys) {
+                Some(format!("{} is disabled on this server", name))
+            } else {
+                Some(format!("Admin authentication required for {}", name))
+            };
+            if let Some(message) = message {
+                return Err((ErrorCode::Forbidden, message));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a token may be subscribed to
+    fn knows_token(&self, token: &str) -> bool {
+        self.kline_service.accepts_token(token)
+            && (self.kline_service.has_token(token)
+                || self
+                    .token_registry
+                    .as_ref()
+                    .is_some_and(|registry| registry.is_registered(token)))
+    }
+
+    /// Read a page of candle history for get_klines
+    fn kline_history(
+        &self,
+        token: &str,
+        interval: String,
+        limit: Option<usize>,
+        before: Option<i64>,
+        candle_type: Option<CandleType>,
+    ) -> Result<ServerMessage, (ErrorCode, String)> {
+        let parsed = match interval.parse() {
+            Ok(parsed) if self.kline_service.supports(parsed) => parsed,
+            _ => return Err((ErrorCode::InvalidInterval, format!("Invalid interval: {}", interval))),
+        };
+        if !self.kline_service.accepts_token(token) {
+            return Err((ErrorCode::UnknownToken, format!("Unknown token: {}", token)));
+        }
+        let cursor = match before {
+            None => KLineCursor::Before(DateTime::<Utc>::MAX_UTC),
+            Some(before) => match DateTime::from_timestamp_millis(before) {
+                Some(before) => KLineCursor::Before(before),
+                None => return Err((ErrorCode::InvalidParameter, format!("Invalid before: {}", before))),
+            },
+        };
+        let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT).min(MAX_HISTORY_LIMIT);
+
+        let page = self.kline_service.get_klines_page(token, parsed, cursor, limit);
+        Ok(ServerMessage::KLinesSnapshot {
+            token: token.to_string(),
+            interval,
+            data: candle_type.unwrap_or_default().apply(&self.kline_service, page.klines),
+            next_cursor: page.next_cursor.map(|cursor| cursor.timestamp().timestamp_millis()),
+        })
+    }
+
+    /// Handle resume: subscribe to the topic and replay what was missed
+    fn handle_resume(&mut self, topic_name: &str, since: u64, ctx: &mut ws::WebsocketContext<Self>) {
+        let topic = match topic_name.parse::<Topic>() {
+            Ok(topic) if topic.resumable() => topic,
+            _ => {
+                self.send_error(ErrorCode::InvalidTopic, format!("Invalid resumable topic: {}", topic_name), ctx);
+                return;
+            }
+        };
+
+        // An existing subscription to the topic is kept with its options
+        let subscription = topic.subscription();
+        if let Err((code, message)) = self.check_subscription(&subscription) {
+            self.send_error(code, message, ctx);
+            return;
+        }
+        if !self.subscriptions.iter().any(|s| subscription_matches(s, &subscription)) {
+            if let Err(message) = self.add_subscription(&subscription) {
+                self.send_error(ErrorCode::SubscriptionLimit, message, ctx);
+                return;
+            }
+        }
+
+        // Subscribing and snapshotting the buffer together leaves no gap
+        // between the replayed and the live messages
+        let resumption = self.manager.resume_topic(&topic, since);
+        if !self.topic_streams.contains_key(&topic) {
+            self.attach_topic(topic.clone(), resumption.receiver, ctx);
+        }
+
+        self.send_reply(
+            ServerMessage::Resumed {
+                topic: topic.to_string(),
+                replayed: resumption.replay.len(),
+                complete: resumption.complete,
+            },
+            ctx,
+        );
+        for message in &resumption.replay {
+            self.send_topic_message(&topic, message, ctx);
+        }
+    }
+
+    /// Present a K-line in the candle type subscribed to on its topic
+    fn present_kline(&mut self, topic: &Topic, kline: KLine) -> KLine {
+        let Some(seed) = self.heikin_ashi.get_mut(topic) else {
+            return kline;
+        };
+
+        // The open only depends on earlier candles, so it is looked up once per candle
+        let open = match seed {
+            Some(seed) if seed.candle == kline.timestamp => seed.open,
+            _ => {
+                let open = candle_transform::heikin_ashi_open(
+                    &self.kline_service,
+                    &kline.token,
+                    kline.interval,
+                    kline.timestamp,
+                );
+                *seed = Some(HeikinAshiSeed {
+                    candle: kline.timestamp,
+                    open,
+                });
+                open
+            }
+        };
+        candle_transform::heikin_ashi_candle(&kline, open)
+    }
+
+    /// Send a topic message in the session format, transforming K-lines as subscribed
+    fn send_topic_message(&mut self, topic: &Topic, message: &TopicMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.heikin_ashi.contains_key(topic) {
+            match &message.message {
+                ServerMessage::KLine { data } => {
+                    let data = self.present_kline(topic, data.clone());
+                    let message = TopicMessage::new(ServerMessage::KLine { data }, message.sequence.clone());
+                    self.send_encoded(&message, ctx);
+                    return;
+                }
+                ServerMessage::KLineAmended { data } => {
+                    let data = self.present_kline(topic, data.clone());
+                    // Later candles' opens depend on the corrected one, so look them up again
+                    self.heikin_ashi.insert(topic.clone(), None);
+                    let message = TopicMessage::new(ServerMessage::KLineAmended { data }, message.sequence.clone());
+                    self.send_encoded(&message, ctx);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        self.send_encoded(message, ctx);
+    }
+
+    /// Send a topic message's shared encoding in the session format
+    fn send_encoded(&self, message: &TopicMessage, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.format != WireFormat::Json {
+            if let Some(bytes) = message.binary(self.format) {
+                ctx.binary(bytes);
+                self.stats.record_sent();
+            }
+            return;
+        }
+
+        match (message.json(), self.compression_threshold) {
+            (Some(json), Some(threshold)) if json.len() >= threshold => {
+                if let Some(compressed) = message.compressed() {
+                    ctx.binary(compressed);
+                    self.stats.record_sent();
+                }
+            }
+            (Some(json), _) => {
+                ctx.text(json);
+                self.stats.record_sent();
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Handle unsubscription
+    fn handle_unsubscribe(&mut self, subscription: SubscriptionType, ctx: &mut ws::WebsocketContext<Self>) {
+        // Remove subscription
+        self.subscriptions.retain(|s| !subscription_matches(s, &subscription));
+        self.sync_topics(ctx);
+
+        // Unregister subscription with manager
+        self.manager.remove_subscription(self.id, &subscription);
+
+        // Send confirmation
+        self.send_reply(ServerMessage::Unsubscribed { subscription }, ctx);
+    }
+
+    /// Handle removal of every subscription
+    fn handle_unsubscribe_all(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let subscriptions = std::mem::take(&mut self.subscriptions);
+        self.sync_topics(ctx);
+        self.manager.clear_subscriptions(self.id);
+
+        self.send_reply(ServerMessage::UnsubscribedAll { subscriptions }, ctx);
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.hb(ctx);
+        self.enforce_auth_timeout(ctx);
+        if let Some(interval) = self.server_time_interval {
+            ctx.run_interval(interval, |act, ctx| {
+                act.send_message(ServerMessage::ServerTime { server_time: Utc::now() }, ctx);
+            });
+        }
+        
+        // Set the session address and statistics in the manager
+        self.manager.set_session_addr(self.id, ctx.address(), self.stats.clone());
+        // Sessions opened during maintenance or a shutdown learn of it at once
+        let status = self.manager.system_status();
+        if status.status != SystemState::Normal {
+            self.send_message(ServerMessage::SystemStatus { data: status }, ctx);
+        }
+        if let Some(subscription) = self.initial_subscription.take() {
+            self.handle_subscribe(subscription, ctx);
+        }
+        
+        tracing::debug!("WebSocket session {} started", self.id);
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        // Remove session from manager
+        self.manager.remove_session(self.id);
+        tracing::debug!("WebSocket session {} stopped", self.id);
+    }
+}
+
+/// Request to close a session with a close frame
+struct Disconnect(ws::CloseReason);
+
+impl actix::Message for Disconnect {
+    type Result = ();
+}
+
+/// Request to send a system status notice to a session
+struct SystemNotice(SystemStatus);
+
+impl actix::Message for SystemNotice {
+    type Result = ();
+}
+
+impl actix::Handler<SystemNotice> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, SystemNotice(data): SystemNotice, ctx: &mut Self::Context) {
+        self.send_message(ServerMessage::SystemStatus { data }, ctx);
+    }
+}
+
+impl actix::Handler<Disconnect> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, Disconnect(reason): Disconnect, ctx: &mut Self::Context) {
+        tracing::debug!("Closing WebSocket session {}: {:?}", self.id, reason.description);
+        ctx.close(Some(reason));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Text(text)) => {
+                self.hb = Instant::now();
+                self.stats.record_received();
+                if !self.admit_message(ctx) {
+                    return;
+                }
+
+                // Text frames are always JSON, whatever the session format
+                let value = serde_json::from_str(&text).map_err(|e| e.to_string());
+                self.handle_client_request(value, ctx);
+            }
+            Ok(ws::Message::Binary(bytes)) => {
+                self.hb = Instant::now();
+                self.stats.record_received();
+                if !self.admit_message(ctx) {
+                    return;
+                }
+
+                let value = self.format.decode(&bytes);
+                self.handle_client_request(value, ctx);
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => ctx.stop(),
+        }
+    }
+}
+
+/// Topic a broadcast channel carries
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Transactions for a single token
+    Transactions(String),
+    /// Transactions for every token
+    AllTransactions,
+    /// Aggregated trades for a single token
+    AggTrades(String),
+    /// Order book changes for a single token
+    Depth(String),
+    /// K-line updates for a token and interval
+    KLines { token: String, interval: TimeInterval },
+    /// Per-second server metrics
+    OpsMetrics,
+    /// Admin notices
+    AdminEvents,
+    /// Triggered price alerts
+    Alerts,
+    /// Tokens listed and delisted at runtime
+    Listings,
+    /// Values of an indicator for a token and interval
+    Indicator {
+        token: String,
+        interval: TimeInterval,
+        spec: IndicatorSpec,
+    },
+    /// Renko bricks or range bars of a token
+    Bars(BarSpec),
+    /// Buy and sell volume of a token's candles
+    Delta { token: String, interval: TimeInterval },
+    /// 24-hour statistics of a token
+    Ticker(String),
+}
+
+impl Topic {
+    /// Topics needed to serve a set of subscriptions
+    ///
+    /// Per-token transaction topics are skipped when the set already includes
+    /// all transactions, so no session receives the same trade twice.
+    fn for_subscriptions(subscriptions: &[SubscriptionType]) -> HashSet<Topic> {
+        let all_transactions = subscriptions
+            .iter()
+            .any(|sub| matches!(sub, SubscriptionType::AllTransactions));
+
+        let mut topics = HashSet::new();
+        for subscription in subscriptions {
+            match subscription {
+                SubscriptionType::AllTransactions => {
+                    topics.insert(Topic::AllTransactions);
+                }
+                SubscriptionType::Transactions { tokens } if !all_transactions => {
+                    topics.extend(tokens.iter().cloned().map(Topic::Transactions));
+                }
+                SubscriptionType::Transactions { .. } => {}
+                SubscriptionType::AggTrades { token } => {
+                    topics.insert(Topic::AggTrades(token.clone()));
+                }
+                SubscriptionType::Depth { token } => {
+                    topics.insert(Topic::Depth(token.clone()));
+                }
+                SubscriptionType::KLines { token, interval, .. } => {
+                    if let Ok(interval) = interval.parse() {
+                        topics.insert(Topic::KLines {
+                            token: token.clone(),
+                            interval,
+                        });
+                    }
+                }
+                SubscriptionType::OpsMetrics { .. } => {
+                    topics.insert(Topic::OpsMetrics);
+                }
+                SubscriptionType::AdminEvents { .. } => {
+                    topics.insert(Topic::AdminEvents);
+                }
+                SubscriptionType::Alerts => {
+                    topics.insert(Topic::Alerts);
+                }
+                SubscriptionType::Listings => {
+                    topics.insert(Topic::Listings);
+                }
+                SubscriptionType::Indicators { token, interval, .. } => {
+                    if let (Ok(interval), Some(Ok(spec))) = (interval.parse(), subscription.indicator_spec()) {
+                        topics.insert(Topic::Indicator {
+                            token: token.clone(),
+                            interval,
+                            spec,
+                        });
+                    }
+                }
+                SubscriptionType::Bars { .. } => {
+                    if let Some(Ok(spec)) = subscription.bar_spec() {
+                        topics.insert(Topic::Bars(spec));
+                    }
+                }
+                SubscriptionType::Delta { token, interval } => {
+                    if let Ok(interval) = interval.parse() {
+                        topics.insert(Topic::Delta {
+                            token: token.clone(),
+                            interval,
+                        });
+                    }
+                }
+                SubscriptionType::Ticker { token } => {
+                    topics.insert(Topic::Ticker(token.clone()));
+                }
+            }
+        }
+        topics
+    }
+
+    /// Whether messages on this topic carry sequence numbers and can be resumed
+    pub fn resumable(&self) -> bool {
+        matches!(
+            self,
+            Topic::Transactions(_) | Topic::AllTransactions | Topic::KLines { .. }
+        )
+    }
+
+    /// Subscription that delivers this topic
+    fn subscription(&self) -> SubscriptionType {
+        match self {
+            Topic::Transactions(token) => SubscriptionType::Transactions {
+                tokens: vec![token.clone()],
+            },
+            Topic::AllTransactions => SubscriptionType::AllTransactions,
+            Topic::AggTrades(token) => SubscriptionType::AggTrades { token: token.clone() },
+            Topic::Depth(token) => SubscriptionType::Depth { token: token.clone() },
+            Topic::KLines { token, interval } => SubscriptionType::KLines {
+                token: token.clone(),
+                interval: interval.as_str().to_string(),
+                throttle_ms: None,
+                candle_type: None,
+            },
+            Topic::OpsMetrics => SubscriptionType::OpsMetrics { api_key: None },
+            Topic::AdminEvents => SubscriptionType::AdminEvents { api_key: None },
+            Topic::Alerts => SubscriptionType::Alerts,
+            Topic::Listings => SubscriptionType::Listings,
+            Topic::Indicator { token, interval, spec } => {
+                let (period, fast, slow, signal, std_dev) = match *spec {
+                    IndicatorSpec::Macd(params) => (None, Some(params.fast), Some(params.slow), Some(params.signal), None),
+                    IndicatorSpec::Bollinger(params) => (Some(params.period), None, None, None, Some(params.std_dev)),
+                    spec => (Some(spec.period()), None, None, None, None),
+                };
+                SubscriptionType::Indicators {
+                    token: token.clone(),
+                    interval: interval.as_str().to_string(),
+                    indicator: spec.kind(),
+                    period,
+                    fast,
+                    slow,
+                    signal,
+                    std_dev,
+                }
+            }
+            Topic::Bars(spec) => {
+                let (size, interval, atr_period) = match spec.size {
+                    BarSize::Fixed(size) => (Some(size), None, None),
+                    BarSize::Atr { interval, period } => (None, Some(interval.as_str().to_string()), Some(period)),
+                };
+                SubscriptionType::Bars {
+                    token: spec.token.clone(),
+                    bar_type: spec.bar_type,
+                    size,
+                    interval,
+                    atr_period,
+                }
+            }
+            Topic::Delta { token, interval } => SubscriptionType::Delta {
+                token: token.clone(),
+                interval: interval.as_str().to_string(),
+            },
+            Topic::Ticker(token) => SubscriptionType::Ticker { token: token.clone() },
+        }
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Transactions(token) => write!(f, "transactions:{}", token),
+            Topic::AllTransactions => write!(f, "all_transactions"),
+            Topic::AggTrades(token) => write!(f, "agg_trades:{}", token),
+            Topic::Depth(token) => write!(f, "depth:{}", token),
+            Topic::KLines { token, interval } => write!(f, "klines:{}:{}", token, interval.as_str()),
+            Topic::OpsMetrics => write!(f, "ops_metrics"),
+            Topic::AdminEvents => write!(f, "admin_events"),
+            Topic::Alerts => write!(f, "alerts"),
+            Topic::Listings => write!(f, "listings"),
+            Topic::Indicator { token, interval, spec } => {
+                write!(f, "indicator:{}:{}:{}", token, interval.as_str(), spec)
+            }
+            Topic::Bars(spec) => write!(f, "bars:{}:{}:{}", spec.token, spec.bar_type.as_str(), spec.size),
+            Topic::Delta { token, interval } => write!(f, "delta:{}:{}", token, interval.as_str()),
+            Topic::Ticker(token) => write!(f, "ticker:{}", token),
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["transactions", token] if !token.is_empty() => Ok(Topic::Transactions(token.to_string())),
+            ["all_transactions"] => Ok(Topic::AllTransactions),
+            ["agg_trades", token] if !token.is_empty() => Ok(Topic::AggTrades(token.to_string())),
+            ["depth", token] if !token.is_empty() => Ok(Topic::Depth(token.to_string())),
+            ["klines", token, interval] if !token.is_empty() => Ok(Topic::KLines {
+                token: token.to_string(),
+                interval: interval.parse()?,
+            }),
+            ["delta", token, interval] if !token.is_empty() => Ok(Topic::Delta {
+                token: token.to_string(),
+                interval: interval.parse()?,
+            }),
+            ["ticker", token] if !token.is_empty() => Ok(Topic::Ticker(token.to_string())),
+            ["ops_metrics"] => Ok(Topic::OpsMetrics),
+            ["admin_events"] => Ok(Topic::AdminEvents),
+            ["alerts"] => Ok(Topic::Alerts),
+            ["listings"] => Ok(Topic::Listings),
+            ["indicator", token, interval, spec @ ..] if !token.is_empty() => Ok(Topic::Indicator {
+                token: token.to_string(),
+                interval: interval.parse()?,
+                spec: spec.join(":").parse()?,
+            }),
+            ["bars", token, bar_type, size @ ..] if !token.is_empty() => {
+                let invalid = || format!("Invalid topic: {}", s);
+                let size = match size {
+                    [size] => BarSize::Fixed(size.parse().map_err(|_| invalid())?),
+                    ["atr", interval, period] => BarSize::Atr {
+                        interval: interval.parse()?,
+                        period: period.parse().map_err(|_| invalid())?,
+                    },
+                    _ => return Err(invalid()),
+                };
+                Ok(Topic::Bars(BarSpec {
+                    token: token.to_string(),
+                    bar_type: bar_type.parse()?,
+                    size,
+                }))
+            }
+            _ => Err(format!("Invalid topic: {}", s)),
+        }
+    }
+}
+
+/// A message published on a topic
+///
+/// Each encoding is computed on first use and shared by every subscriber.
+/// Messages on resumable topics are tagged with the topic and a sequence number.
+#[derive(Debug)]
+pub struct TopicMessage {
+    message: ServerMessage,
+    sequence: Option<(String, u64)>,
+    json: OnceLock<Option<ByteString>>,
+    compressed: OnceLock<Option<Bytes>>,
+    msgpack: OnceLock<Option<Bytes>>,
+    cbor: OnceLock<Option<Bytes>>,
+}
+
+impl TopicMessage {
+    fn new(message: ServerMessage, sequence: Option<(String, u64)>) -> Arc<Self> {
+        Arc::new(Self {
+            message,
+            sequence,
+            json: OnceLock::new(),
+            compressed: OnceLock::new(),
+            msgpack: OnceLock::new(),
+            cbor: OnceLock::new(),
+        })
+    }
+
+    /// The JSON encoding of this message
+    pub fn json(&self) -> Option<ByteString> {
+        self.json
+            .get_or_init(|| serde_json::to_string(self).ok().map(ByteString::from))
+            .clone()
+    }
+
+    /// The raw-deflate compressed JSON encoding of this message
+    pub fn compressed(&self) -> Option<Bytes> {
+        self.compressed
+            .get_or_init(|| self.json().and_then(|json| deflate(json.as_bytes())))
+            .clone()
+    }
+
+    /// The encoding of this message in the given format
+    pub fn binary(&self, format: WireFormat) -> Option<Bytes> {
+        let cache = match format {
+            WireFormat::Json => return self.json().map(|json| json.into_bytes()),
+            WireFormat::Msgpack => &self.msgpack,
+            WireFormat::Cbor => &self.cbor,
+        };
+        cache.get_or_init(|| format.encode(self)).clone()
+    }
+
+    /// Sequence number on the topic, if the topic is resumable
+    pub fn seq(&self) -> Option<u64> {
+        self.sequence.as_ref().map(|(_, seq)| *seq)
+    }
+}
+
+/// A server message as sent, tagged with the topic and sequence number on resumable topics
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "ServerMessage")]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    message: &'a ServerMessage,
+    /// Resumable topic the message was published on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<&'a str>,
+    /// Sequence number of the message on its topic, for resuming after it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+    /// ID of the client message this answers
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    request_ref: Option<&'a RequestId>,
+}
+
+impl Serialize for TopicMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Envelope {
+            message: &self.message,
+            topic: self.sequence.as_ref().map(|(topic, _)| topic.as_str()),
+            seq: self.seq(),
+            request_ref: None,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Broadcast channel and replay state for one topic
+#[derive(Debug)]
+struct TopicChannel {
+    sender: broadcast::Sender<Arc<TopicMessage>>,
+    /// Sequence number of the last published message
+    last_seq: u64,
+    /// Most recent messages, oldest first
+    replay: VecDeque<Arc<TopicMessage>>,
+    /// When the topic was last subscribed or published to
+    last_active: Instant,
+}
+
+impl TopicChannel {
+    fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+            last_seq: 0,
+            replay: VecDeque::new(),
+            last_active: Instant::now(),
+        }
+    }
+
+    /// Whether nobody has subscribed or published for `timeout`
+    fn is_idle(&self, now: Instant, timeout: Duration) -> bool {
+        self.sender.receiver_count() == 0 && now.saturating_duration_since(self.last_active) >= timeout
+    }
+}
+
+/// Live receiver plus the buffered messages a resuming session missed
+#[derive(Debug)]
+pub struct Resumption {
+    pub receiver: broadcast::Receiver<Arc<TopicMessage>>,
+    /// Buffered messages after the requested sequence number, oldest first
+    pub replay: Vec<Arc<TopicMessage>>,
+    /// Whether the replay covers every message after the requested sequence number
+    pub complete: bool,
+}
+
+/// Compress a payload with raw deflate
+fn deflate(payload: &[u8]) -> Option<Bytes> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(payload).ok()?;
+    encoder.finish().ok().map(Bytes::from)
+}
+
+/// A topic message, or the number of messages dropped because the session lagged
+type TopicItem = (Topic, Result<Arc<TopicMessage>, BroadcastStreamRecvError>);
+
+/// Stream a topic's messages, tagged with the topic
+///
+/// The broadcast receiver is the session's bounded queue for the topic: once
+/// the session falls behind by the channel capacity, the oldest messages are
+/// dropped and reported as lag.
+fn topic_stream(
+    topic: Topic,
+    receiver: broadcast::Receiver<Arc<TopicMessage>>,
+) -> impl Stream<Item = TopicItem> {
+    BroadcastStream::new(receiver).map(move |item| (topic.clone(), item))
+}
+
+impl StreamHandler<TopicItem> for WsSession {
+    fn handle(&mut self, (topic, item): TopicItem, ctx: &mut Self::Context) {
+        match item {
+            Ok(message) => {
+                if let ServerMessage::KLine { data } = &message.message {
+                    let candle = data.timestamp;
+                    self.kline_cursors.insert(topic.clone(), candle);
+                    if self.kline_throttles.contains_key(&topic) {
+                        self.throttle_kline(&topic, candle, message, ctx);
+                        return;
+                    }
+                }
+                self.send_topic_message(&topic, &message, ctx);
+            }
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                self.handle_lag(&topic, skipped, ctx);
+            }
+        }
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {
+        // A topic channel closing must not end the session
+    }
+}
+
+/// Event published to non-WebSocket transports (e.g. gRPC streams)
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// A processed transaction
+    Transaction(Transaction),
+    /// An updated K-line
+    KLine(KLine),
+}
+
+/// WebSocket manager for handling multiple sessions
+///
+/// Fan-out uses one tokio broadcast channel per topic; sessions subscribe to the
+/// topics they need, so publishing never takes a lock across sessions.
+#[derive(Debug)]
+pub struct WsManager {
+    /// Active sessions
+    sessions: DashMap<Uuid, SessionHandle>,
+    /// Session subscriptions
+    subscriptions: DashMap<Uuid, Vec<SubscriptionType>>,
+    /// Broadcast channel per topic, created on first subscription
+    topics: DashMap<Topic, TopicChannel>,
+    /// Messages kept per resumable topic
+    replay_capacity: usize,
+    /// Messages a session may fall behind on a topic
+    session_backlog: usize,
+    /// Time a topic without subscribers or messages is kept
+    topic_idle_timeout: Duration,
+    /// Messages dropped for slow sessions
+    dropped_messages: AtomicU64,
+    /// Sessions disconnected for falling behind
+    slow_consumer_disconnects: AtomicU64,
+    /// Every broadcast, for transports that filter on their own
+    events: broadcast::Sender<StreamEvent>,
+    /// Bar builders per token for subscribed bar topics, started on the first trade
+    bars: DashMap<String, HashMap<BarSpec, Option<BarBuilder>>>,
+    /// Aggregate builder per token for subscribed agg_trades topics
+    agg_trades: DashMap<String, AggTradeBuilder>,
+    /// Running delta per subscribed delta topic
+    deltas: DashMap<Topic, DeltaTracker>,
+    /// Running indicator state per subscribed indicator topic
+    indicators: DashMap<Topic, IndicatorStream>,
+    /// Time consecutive same-price trades are combined for
+    agg_trade_window: TimeDelta,
+    /// Open connections per client address
+    connections_per_ip: Arc<DashMap<IpAddr, usize>>,
+    /// Connections allowed from one address (0 = unlimited)
+    max_connections_per_ip: usize,
+    /// Last announced state of the server
+    system_status: RwLock<SystemStatus>,
+}
+
+impl WsManager {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            sessions: DashMap::new(),
+            subscriptions: DashMap::new(),
+            topics: DashMap::new(),
+            replay_capacity: DEFAULT_REPLAY_CAPACITY,
+            session_backlog: DEFAULT_SESSION_BACKLOG,
+            topic_idle_timeout: DEFAULT_TOPIC_IDLE_TIMEOUT,
+            dropped_messages: AtomicU64::new(0),
+            slow_consumer_disconnects: AtomicU64::new(0),
+            events,
+            bars: DashMap::new(),
+            agg_trades: DashMap::new(),
+            deltas: DashMap::new(),
+            indicators: DashMap::new(),
+            agg_trade_window: DEFAULT_AGG_TRADE_WINDOW,
+            connections_per_ip: Arc::new(DashMap::new()),
+            max_connections_per_ip: 0,
+            system_status: RwLock::new(SystemStatus::default()),
+        }
+    }
+
+    /// Set the number of connections allowed from one address (0 = unlimited)
+    pub fn with_max_connections_per_ip(mut self, max_connections: usize) -> Self {
+        self.max_connections_per_ip = max_connections;
+        self
+    }
+
+    /// Reserve a connection for a client address
+    ///
+    /// Returns `None` when the address already has the maximum number of
+    /// connections.
+    pub fn reserve_connection(&self, ip: IpAddr) -> Option<ConnectionSlot> {
+        let mut count = self.connections_per_ip.entry(ip).or_insert(0);
+        if self.max_connections_per_ip > 0 && *count >= self.max_connections_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot {
+            ip,
+            connections: self.connections_per_ip.clone(),
+        })
+    }
+
+    /// Get the number of open connections from an address
+    pub fn connections_from(&self, ip: IpAddr) -> usize {
+        self.connections_per_ip.get(&ip).map_or(0, |count| *count)
+    }
+
+    /// Set the time consecutive same-price trades are combined for
+    pub fn with_agg_trade_window(mut self, window: Duration) -> Self {
+        self.agg_trade_window = TimeDelta::from_std(window).unwrap_or(DEFAULT_AGG_TRADE_WINDOW);
+        self
+    }
+
+    /// Time consecutive same-price trades are combined for
+    pub fn agg_trade_window(&self) -> Duration {
+        self.agg_trade_window.to_std().unwrap_or_default()
+    }
+
+    /// Set the number of messages a session may fall behind on a topic
+    pub fn with_session_backlog(mut self, session_backlog: usize) -> Self {
+        self.session_backlog = session_backlog;
+        self
+    }
+
+    /// Set the number of messages kept per resumable topic
+    pub fn with_replay_capacity(mut self, replay_capacity: usize) -> Self {
+        self.replay_capacity = replay_capacity;
+        self
+    }
+
+    /// Set the time a topic without subscribers or messages is kept, replay buffer included
+    pub fn with_topic_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.topic_idle_timeout = timeout;
+        self
+    }
+
+    /// Get the number of topics with a channel
+    pub fn topic_count(&self) -> usize {
+        self.topics.len()
+    }
+
+    /// Drop topics nobody has subscribed or published to for the idle timeout
+    ///
+    /// Resumable topics outlive their last subscriber so reconnecting clients
+    /// can catch up, but not indefinitely. The builders and running state of
+    /// dropped topics go with them.
+    pub fn prune_idle_topics(&self) {
+        let now = Instant::now();
+        self.topics
+            .retain(|_, channel| !channel.is_idle(now, self.topic_idle_timeout));
+        self.agg_trades
+            .retain(|token, _| self.topics.contains_key(&Topic::AggTrades(token.clone())));
+        self.deltas.retain(|topic, _| self.topics.contains_key(topic));
+        self.indicators.retain(|topic, _| self.topics.contains_key(topic));
+        self.bars.retain(|_, builders| {
+            builders.retain(|spec, _| self.topics.contains_key(&Topic::Bars(spec.clone())));
+            !builders.is_empty()
+        });
+    }
+
+    /// Subscribe to every transaction and K-line broadcast
+    pub fn subscribe_events(&self) -> broadcast::Receiver<StreamEvent> {
+        self.events.subscribe()
+    }
+
+    /// Subscribe to a single topic
+    pub fn subscribe_topic(&self, topic: &Topic) -> broadcast::Receiver<Arc<TopicMessage>> {
+        if let Topic::Bars(spec) = topic {
+            self.bars
+                .entry(spec.token.clone())
+                .or_default()
+                .entry(spec.clone())
+                .or_insert(None);
+        }
+        if let Topic::AggTrades(token) = topic {
+            self.agg_trades
+                .entry(token.clone())
+                .or_insert_with(|| AggTradeBuilder::new(self.agg_trade_window));
+        }
+        if let Topic::Delta { .. } = topic {
+            self.deltas.entry(topic.clone()).or_default();
+        }
+        if let Topic::Indicator { spec, .. } = topic {
+            self.indicators
+                .entry(topic.clone())
+                .or_insert_with(|| IndicatorStream::new(*spec));
+        }
+        let mut channel = self
+            .topics
+            .entry(topic.clone())
+            .or_insert_with(|| TopicChannel::new(self.session_backlog));
+        channel.last_active = Instant::now();
+        channel.sender.subscribe()
+    }
+
+    /// Subscribe to a topic and collect buffered messages after `since`
+    pub fn resume_topic(&self, topic: &Topic, since: u64) -> Resumption {
+        let mut channel = self
+            .topics
+            .entry(topic.clone())
+            .or_insert_with(|| TopicChannel::new(self.session_backlog));
+        channel.last_active = Instant::now();
+        let oldest_missing = channel.last_seq - channel.replay.len() as u64;
+
+        Resumption {
+            receiver: channel.sender.subscribe(),
+            replay: channel
+                .replay
+                .iter()
+                .filter(|message| message.seq().is_some_and(|seq| seq > since))
+                .cloned()
+                .collect(),
+            // A sequence ahead of the topic means the server restarted
+            complete: since >= oldest_missing && since <= channel.last_seq,
+        }
+    }
+
+    /// Publish a message on a topic, if anyone has subscribed to it
+    ///
+    /// Resumable topics keep their channel, sequence and replay buffer after
+    /// the last subscriber leaves so reconnecting clients can catch up.
+    fn publish(&self, topic: &Topic, message: impl FnOnce() -> ServerMessage) {
+        let delivered = {
+            let Some(mut channel) = self.topics.get_mut(topic) else {
+                return;
+            };
+            channel.last_active = Instant::now();
+
+            if !topic.resumable() {
+                channel.sender.send(TopicMessage::new(message(), None)).is_ok()
+            } else {
+                channel.last_seq += 1;
+                let message = TopicMessage::new(message(), Some((topic.to_string(), channel.last_seq)));
+                if self.replay_capacity > 0 {
+                    if channel.replay.len() >= self.replay_capacity {
+                        channel.replay.pop_front();
+                    }
+                    channel.replay.push_back(message.clone());
+                }
+                // Sending only fails when nobody is listening
+                let _ = channel.sender.send(message);
+                true
+            }
+        };
+
+        // Drop non-resumable channels whose subscribers have all gone away
+        if !delivered {
+            self.topics
+                .remove_if(topic, |_, channel| channel.sender.receiver_count() == 0);
+        }
+    }
+
+    /// Add a new session
+    pub fn add_session(&self, session_id: Uuid) {
+        self.subscriptions.insert(session_id, Vec::new());
+    }
+
+    /// Remove a session
+    pub fn remove_session(&self, session_id: Uuid) {
+        self.sessions.remove(&session_id);
+        self.subscriptions.remove(&session_id);
+    }
+
+    /// Add session address and statistics
+    pub fn set_session_addr(&self, session_id: Uuid, addr: actix::Addr<WsSession>, stats: Arc<SessionStats>) {
+        self.sessions.insert(session_id, SessionHandle { addr, stats });
+    }
+
+    /// Describe the active sessions, oldest first
+    pub fn session_infos(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<SessionInfo> = self
+            .sessions
+            .iter()
+            .map(|session| {
+                let stats = &session.stats;
+                SessionInfo {
+                    id: *session.key(),
+                    connected_at: stats.connected_at,
+                    remote_addr: stats.remote_addr.clone(),
+                    subscriptions: self
+                        .subscriptions
+                        .get(session.key())
+                        .map(|subs| subs.clone())
+                        .unwrap_or_default(),
+                    messages_sent: stats.messages_sent.load(Ordering::Relaxed),
+                    messages_received: stats.messages_received.load(Ordering::Relaxed),
+                    dropped_messages: stats.dropped_messages.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        sessions.sort_by_key(|session| session.connected_at);
+        sessions
+    }
+
+    /// Close a session, returning whether it was connected
+    pub fn disconnect_session(&self, session_id: Uuid) -> bool {
+        let Some(session) = self.sessions.get(&session_id) else {
+            return false;
+        };
+        tracing::warn!("Disconnecting WebSocket session {} via admin request", session_id);
+        session.addr.do_send(Disconnect(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some("Disconnected by administrator".to_string()),
+        }));
+        true
+    }
+
+    /// Send a system status notice to every session, returning how many there were
+    ///
+    /// The status is kept and sent to sessions opened later until a `normal` status replaces it.
+    pub fn broadcast_system_status(&self, status: SystemStatus) -> usize {
+        *self.system_status.write().unwrap_or_else(PoisonError::into_inner) = status.clone();
+        for session in self.sessions.iter() {
+            session.addr.do_send(SystemNotice(status.clone()));
+        }
+        self.sessions.len()
+    }
+
+    /// Get the last announced state of the server
+    pub fn system_status(&self) -> SystemStatus {
+        self.system_status.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Close every session with a `server_shutdown` close frame, returning how many there were
+    pub fn close_all_sessions(&self) -> usize {
+        for session in self.sessions.iter() {
+            session.addr.do_send(Disconnect(ws::CloseReason {
+                code: ws::CloseCode::Away,
+                description: Some(SHUTDOWN_CLOSE_REASON.to_string()),
+            }));
+        }
+        self.sessions.len()
+    }
+
+    /// Add subscription for a session, replacing the one for the same stream
+    pub fn add_subscription(&self, session_id: Uuid, subscription: SubscriptionType) {
+        if let Some(mut subs) = self.subscriptions.get_mut(&session_id) {
+            match subs.iter_mut().find(|s| subscription_matches(s, &subscription)) {
+                Some(existing) => *existing = subscription,
+                None => subs.push(subscription),
+            }
+        }
+    }
+
+    /// Remove subscription for a session
+    pub fn remove_subscription(&self, session_id: Uuid, subscription: &SubscriptionType) {
+        if let Some(mut subs) = self.subscriptions.get_mut(&session_id) {
+            subs.retain(|s| !subscription_matches(s, subscription));
+        }
+    }
+
+    /// Remove every subscription of a session
+    pub fn clear_subscriptions(&self, session_id: Uuid) {
+        if let Some(mut subs) = self.subscriptions.get_mut(&session_id) {
+            subs.clear();
+        }
+    }
+
+    /// Broadcast transaction to all relevant sessions
+    pub fn broadcast_transaction(&self, transaction: &Transaction) {
+        // Sending only fails when nobody is listening
+        let _ = self.events.send(StreamEvent::Transaction(transaction.clone()));
+
+        self.publish(&Topic::Transactions(transaction.token.clone()), || {
+            ServerMessage::Transaction { data: transaction.clone() }
+        });
+        self.publish(&Topic::AllTransactions, || ServerMessage::Transaction {
+            data: transaction.clone(),
+        });
+    }
+
+    /// Broadcast K-line update to all relevant sessions
+    pub fn broadcast_kline(&self, kline: &KLine) {
+        let _ = self.events.send(StreamEvent::KLine(kline.clone()));
+
+        let topic = Topic::KLines {
+            token: kline.token.clone(),
+            interval: kline.interval,
+        };
+        self.publish(&topic, || ServerMessage::KLine { data: kline.clone() });
+        self.broadcast_delta(kline);
+    }
+
+    /// Push a corrected candle to its K-line subscribers
+    ///
+    /// Indicator state of the token and interval is rebuilt from the stored
+    /// candles on its next update, so it includes the correction.
+    pub fn broadcast_kline_amended(&self, kline: &KLine) {
+        let topic = Topic::KLines {
+            token: kline.token.clone(),
+            interval: kline.interval,
+        };
+        self.publish(&topic, || ServerMessage::KLineAmended { data: kline.clone() });
+
+        for mut entry in self.indicators.iter_mut() {
+            if matches!(entry.key(), Topic::Indicator { token, interval, .. } if *token == kline.token && *interval == kline.interval)
+            {
+                let spec = entry.value().spec();
+                *entry.value_mut() = IndicatorStream::new(spec);
+            }
+        }
+    }
+
+    /// Push the delta of an updated candle to its delta subscribers
+    ///
+    /// The running delta starts with the first update after the topic is
+    /// subscribed, and is dropped with the topic.
+    fn broadcast_delta(&self, kline: &KLine) {
+        let topic = Topic::Delta {
+            token: kline.token.clone(),
+            interval: kline.interval,
+        };
+        // Collect first: publishing locks the topic map entry
+        let delta = match self.deltas.get_mut(&topic) {
+            Some(mut tracker) => tracker.update(kline),
+            None => return,
+        };
+        if !self.topics.contains_key(&topic) {
+            self.deltas.remove(&topic);
+        } else if let Some(data) = delta {
+            self.publish(&topic, || ServerMessage::Delta { data });
+        }
+    }
+
+    /// Push a token's ticker to its ticker subscribers after a trade
+    ///
+    /// The ticker is only computed while the token has subscribers.
+    pub fn broadcast_ticker(&self, kline_service: &KLineService, token: &str) {
+        let topic = Topic::Ticker(token.to_string());
+        if !self.topics.contains_key(&topic) {
+            return;
+        }
+        if let Some(data) = kline_service.get_ticker(token) {
+            self.publish(&topic, || ServerMessage::Ticker { data });
+        }
+    }
+
+    /// Broadcast ops metrics to sessions subscribed to them
+    pub fn broadcast_ops_metrics(&self, metrics: &OpsMetrics) {
+        self.publish(&Topic::OpsMetrics, || ServerMessage::OpsMetrics {
+            data: metrics.clone(),
+        });
+    }
+
+    /// Notify admin subscribers that an unconfigured token appeared
+    pub fn broadcast_new_token(&self, event: &NewTokenEvent) {
+        self.publish(&Topic::AdminEvents, || ServerMessage::NewToken {
+            data: event.clone(),
+        });
+    }
+
+    /// Broadcast a triggered price alert to sessions subscribed to alerts
+    pub fn broadcast_alert(&self, trigger: &AlertTrigger) {
+        self.publish(&Topic::Alerts, || ServerMessage::Alert {
+            data: trigger.clone(),
+        });
+    }
+
+    /// Notify subscribers that a token was listed at runtime
+    pub fn broadcast_token_listed(&self, event: &ListingEvent) {
+        self.publish(&Topic::Listings, || ServerMessage::TokenListed {
+            data: event.clone(),
+        });
+    }
+
+    /// Notify subscribers that a token was delisted at runtime
+    pub fn broadcast_token_delisted(&self, event: &ListingEvent) {
+        self.publish(&Topic::Listings, || ServerMessage::TokenDelisted {
+            data: event.clone(),
+        });
+    }
+
+    /// Push the indicators of an updated or newly closed candle to their subscribers
+    ///
+    /// Indicator state starts from the closed candles stored when the topic
+    /// is first updated, and is dropped with the topic.
+    pub fn broadcast_indicators(&self, kline_service: &KLineService, kline: &KLine) {
+        // Collect first: publishing locks the topic map entry
+        let mut updates = Vec::new();
+        self.indicators.retain(|topic, stream| {
+            if !self.topics.contains_key(topic) {
+                return false;
+            }
+            if matches!(topic, Topic::Indicator { token, interval, .. } if *token == kline.token && *interval == kline.interval)
+            {
+                if let Some(values) = stream.update(kline_service, kline) {
+                    updates.push((topic.clone(), stream.spec(), values));
+                }
+            }
+            true
+        });
+
+        for (topic, spec, values) in updates {
+            self.publish(&topic, || ServerMessage::Indicator {
+                data: IndicatorUpdate {
+                    token: kline.token.clone(),
+                    interval: kline.interval,
+                    indicator: spec.kind().as_str().to_string(),
+                    period: spec.period(),
+                    timestamp: kline.timestamp,
+                    value: values.value,
+                    signal: values.signal,
+                    histogram: values.histogram,
+                    upper: values.upper,
+                    lower: values.lower,
+                    is_closed: kline.is_closed,
+                },
+            });
+        }
+    }
+
+    /// Feed a trade to the token's bar builders and push the bars it completed
+    ///
+    /// Builders start on the first trade after a bar topic is subscribed, once
+    /// their size can be resolved, and are dropped with the topic.
+    pub fn broadcast_bars(&self, kline_service: &KLineService, transaction: &Transaction) {
+        // Collect first: publishing locks the topic map entry
+        let completed: Vec<(BarSpec, Vec<Bar>)> = {
+            let Some(mut builders) = self.bars.get_mut(&transaction.token) else {
+                return;
+            };
+            builders
+                .iter_mut()
+                .filter_map(|(spec, builder)| {
+                    if builder.is_none() {
+                        let size = spec.size.resolve(kline_service, &spec.token)?;
+                        *builder = Some(BarBuilder::new(spec.token.clone(), spec.bar_type, size));
+                    }
+                    let bars = builder.as_mut()?.push(transaction);
+                    (!bars.is_empty()).then(|| (spec.clone(), bars))
+                })
+                .collect()
+        };
+
+        for (spec, bars) in completed {
+            let topic = Topic::Bars(spec.clone());
+            for bar in bars {
+                self.publish(&topic, || ServerMessage::Bar { data: bar });
+            }
+            if !self.topics.contains_key(&topic) {
+                self.remove_bar_builder(&spec);
+            }
+        }
+    }
+
+    /// Feed a trade to the token's aggregate builder and push the aggregate it completed
+    pub fn broadcast_agg_trade(&self, transaction: &Transaction) {
+        // Collect first: publishing locks the topic map entry
+        let completed = {
+            let Some(mut builder) = self.agg_trades.get_mut(&transaction.token) else {
+                return;
+            };
+            builder.push(transaction)
+        };
+        if let Some(agg_trade) = completed {
+            self.publish_agg_trade(agg_trade);
+        }
+    }
+
+    /// Push the levels of a token's order book changed by a trade
+    pub fn broadcast_depth(&self, update: &DepthUpdate) {
+        let topic = Topic::Depth(update.token.clone());
+        self.publish(&topic, || ServerMessage::DepthUpdate { data: update.clone() });
+    }
+
+    /// Push the aggregates whose window has passed
+    ///
+    /// Called periodically so an aggregate is delivered even when no further
+    /// trade for its token arrives.
+    pub fn flush_agg_trades(&self) {
+        let now = Utc::now();
+        let expired: Vec<AggTrade> = self
+            .agg_trades
+            .iter_mut()
+            .filter_map(|mut builder| builder.flush_expired(now))
+            .collect();
+        for agg_trade in expired {
+            self.publish_agg_trade(agg_trade);
+        }
+        self.agg_trades
+            .retain(|token, _| self.topics.contains_key(&Topic::AggTrades(token.clone())));
+    }
+
+    fn publish_agg_trade(&self, agg_trade: AggTrade) {
+        let topic = Topic::AggTrades(agg_trade.token.clone());
+        self.publish(&topic, || ServerMessage::AggTrade { data: agg_trade });
+    }
+
+    /// Drop the builder of a bar topic nobody subscribes to any more
+    fn remove_bar_builder(&self, spec: &BarSpec) {
+        if let Some(mut builders) = self.bars.get_mut(&spec.token) {
+            builders.remove(spec);
+        }
+        self.bars.remove_if(&spec.token, |_, builders| builders.is_empty());
+    }
+
+    /// Notify admin subscribers that the configuration was reloaded
+    pub fn broadcast_config_updated(&self, update: &ConfigUpdate) {
+        self.publish(&Topic::AdminEvents, || ServerMessage::ConfigUpdated {
+            data: update.clone(),
+        });
+    }
+
+    /// Get session count
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Get the total number of subscriptions across sessions
+    pub fn subscription_count(&self) -> usize {
+        self.subscriptions.iter().map(|subs| subs.len()).sum()
+    }
+
+    /// Get the number of events queued for the slowest stream subscriber
+    pub fn event_queue_depth(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Record messages dropped for a slow session
+    pub fn record_dropped_messages(&self, count: u64) {
+        self.dropped_messages.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record a session disconnected for falling behind
+    pub fn record_slow_consumer_disconnect(&self) {
+        self.slow_consumer_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get a snapshot of connection statistics
+    pub fn connection_stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            event_queue_depth: self.event_queue_depth(),
+            sessions: self.session_count(),
+            subscriptions: self.subscription_count(),
+            dropped_messages: self.dropped_messages.load(Ordering::Relaxed),
+            slow_consumer_disconnects: self.slow_consumer_disconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for WsManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Check if two subscriptions match
 fn subscription_matches(a: &SubscriptionType, b: &SubscriptionType) -> bool {
     match (a, b) {
         (SubscriptionType::AllTransactions, SubscriptionType::AllTransactions) => true,
+        (SubscriptionType::AggTrades { token: token_a }, SubscriptionType::AggTrades { token: token_b }) => {
+            token_a == token_b
+        }
+        (SubscriptionType::Depth { token: token_a }, SubscriptionType::Depth { token: token_b }) => token_a == token_b,
+        (SubscriptionType::OpsMetrics { .. }, SubscriptionType::OpsMetrics { .. }) => true,
+        (SubscriptionType::AdminEvents { .. }, SubscriptionType::AdminEvents { .. }) => true,
+        (SubscriptionType::Alerts, SubscriptionType::Alerts) => true,
+        (SubscriptionType::Listings, SubscriptionType::Listings) => true,
+        (
+            SubscriptionType::Indicators { token: token_a, interval: interval_a, .. },
+            SubscriptionType::Indicators { token: token_b, interval: interval_b, .. },
+        ) => token_a == token_b && interval_a == interval_b && a.indicator_spec() == b.indicator_spec(),
         (
             SubscriptionType::Transactions { tokens: tokens_a },
             SubscriptionType::Transactions { tokens: tokens_b },
         ) => tokens_a == tokens_b,
         (
-            SubscriptionType::KLines { token: token_a, interval: interval_a },
-            SubscriptionType::KLines { token: token_b, interval: interval_b },
+            SubscriptionType::KLines { token: token_a, interval: interval_a, .. },
+            SubscriptionType::KLines { token: token_b, interval: interval_b, .. },
+        ) => token_a == token_b && interval_a == interval_b,
+        (SubscriptionType::Bars { .. }, SubscriptionType::Bars { .. }) => a.bar_spec() == b.bar_spec(),
+        (
+            SubscriptionType::Delta { token: token_a, interval: interval_a },
+            SubscriptionType::Delta { token: token_b, interval: interval_b },
         ) => token_a == token_b && interval_a == interval_b,
+        (SubscriptionType::Ticker { token: token_a }, SubscriptionType::Ticker { token: token_b }) => token_a == token_b,
         _ => false,
     }
 }
@@ -399,21 +2907,227 @@ fn subscription_matches(a: &SubscriptionType, b: &SubscriptionType) -> bool {
 pub async fn websocket_handler(
     req: HttpRequest,
     stream: web::Payload,
-    manager: web::Data<Arc<RwLock<WsManager>>>,
+    manager: web::Data<Arc<WsManager>>,
     kline_service: web::Data<Arc<KLineService>>,
+    config: Option<web::Data<Config>>,
+    authenticator: Option<web::Data<Arc<Authenticator>>>,
+    query: web::Query<HashMap<String, String>>,
 ) -> Result<HttpResponse> {
-    let session = WsSession::new(manager.get_ref().clone(), kline_service.get_ref().clone());
-    let _session_id = session.id;
-    
-    let resp = ws::start(session, &req, stream)?;
-    
-    // Note: We can't set the session address here because ws::start consumes the session
-    // The session address will be set when the session starts via the Actor::started method
-    
-    Ok(resp)
+    match open_session(&req, manager, kline_service, config, authenticator, &query) {
+        Ok(session) => ws::start(session, &req, stream),
+        Err(response) => Ok(response),
+    }
+}
+
+/// WebSocket endpoint streaming the candles of one token and interval
+///
+/// The stream is subscribed to on connect, so clients need not send any message.
+#[allow(clippy::too_many_arguments)]
+pub async fn kline_stream_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    path: web::Path<(String, String)>,
+    manager: web::Data<Arc<WsManager>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    config: Option<web::Data<Config>>,
+    authenticator: Option<web::Data<Arc<Authenticator>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let (token, interval) = path.into_inner();
+    let subscription = SubscriptionType::KLines {
+        token,
+        interval,
+        throttle_ms: None,
+        candle_type: None,
+    };
+    start_stream(subscription, req, stream, manager, kline_service, config, authenticator, query)
+}
+
+/// WebSocket endpoint streaming the trades of one token
+///
+/// The stream is subscribed to on connect, so clients need not send any message.
+#[allow(clippy::too_many_arguments)]
+pub async fn trade_stream_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    path: web::Path<String>,
+    manager: web::Data<Arc<WsManager>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    config: Option<web::Data<Config>>,
+    authenticator: Option<web::Data<Arc<Authenticator>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let subscription = SubscriptionType::Transactions {
+        tokens: vec![path.into_inner()],
+    };
+    start_stream(subscription, req, stream, manager, kline_service, config, authenticator, query)
+}
+
+/// Start a session subscribed to `subscription` once the handshake is accepted
+///
+/// Stream endpoints have no auth message, so the connection itself must be
+/// authorized, and the subscription is checked before the upgrade.
+#[allow(clippy::too_many_arguments)]
+fn start_stream(
+    subscription: SubscriptionType,
+    req: HttpRequest,
+    stream: web::Payload,
+    manager: web::Data<Arc<WsManager>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    config: Option<web::Data<Config>>,
+    authenticator: Option<web::Data<Arc<Authenticator>>>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse> {
+    let session = match open_session(&req, manager, kline_service, config, authenticator, &query) {
+        Ok(session) => session,
+        Err(response) => return Ok(response),
+    };
+    if !session.authenticated() {
+        return Ok(AuthError::Missing.response());
+    }
+    let subscription = subscription.canonicalize();
+    if let Err((code, message)) = session.check_subscription(&subscription) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": message,
+            "code": code,
+        })));
+    }
+    ws::start(session.with_initial_subscription(subscription), &req, stream)
+}
+
+/// Build the session for an accepted connection, or the response refusing it
+#[allow(clippy::result_large_err)]
+fn open_session(
+    req: &HttpRequest,
+    manager: web::Data<Arc<WsManager>>,
+    kline_service: web::Data<Arc<KLineService>>,
+    config: Option<web::Data<Config>>,
+    authenticator: Option<web::Data<Arc<Authenticator>>>,
+    query: &HashMap<String, String>,
+) -> std::result::Result<WsSession, HttpResponse> {
+    let authenticator = authenticator.map(|authenticator| authenticator.get_ref().clone());
+
+    // Connections without a key may still authenticate with an auth message
+    let api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(query.get("api_key").map(String::as_str));
+    let scope = match &authenticator {
+        Some(authenticator) => match authenticator.authorize(api_key, ApiKeyScope::Read) {
+            Ok(scope) => scope,
+            Err(AuthError::Missing) => None,
+            Err(e) => return Err(e.response()),
+        },
+        None => None,
+    };
+
+    // Compression is opt-in per connection and only offered when enabled
+    let compression_threshold = config
+        .as_ref()
+        .filter(|config| config.performance.websocket_compression)
+        .filter(|_| query.get("compression").map(String::as_str) == Some("deflate"))
+        .map(|config| config.performance.websocket_compression_threshold);
+
+    let format = match query.get("format").map(|format| format.parse::<WireFormat>()) {
+        None => WireFormat::Json,
+        Some(Ok(format)) => format,
+        Some(Err(_)) => {
+            return Err(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Invalid format. Supported: json, msgpack, cbor"
+            })));
+        }
+    };
+
+    // Connections are counted per address from the moment the handshake is accepted
+    let connection_slot = match req.peer_addr() {
+        Some(addr) => match manager.reserve_connection(addr.ip()) {
+            Some(slot) => Some(slot),
+            None => {
+                tracing::warn!("Rejecting WebSocket connection from {}: too many connections", addr.ip());
+                return Err(HttpResponse::TooManyRequests().json(serde_json::json!({
+                    "error": "Too many connections from this address"
+                })));
+            }
+        },
+        None => None,
+    };
+
+    let mut session = WsSession::new(manager.get_ref().clone(), kline_service.get_ref().clone())
+        .with_auth(authenticator, scope)
+        .with_trade_service(
+            req.app_data::<web::Data<Arc<TradeService>>>()
+                .map(|trade_service| trade_service.get_ref().clone()),
+        )
+        .with_order_books(
+            req.app_data::<web::Data<Arc<OrderBookSimulator>>>()
+                .map(|order_books| order_books.get_ref().clone()),
+        )
+        .with_token_registry(
+            req.app_data::<web::Data<Arc<TokenRegistry>>>()
+                .map(|token_registry| token_registry.get_ref().clone()),
+        )
+        .with_compression(compression_threshold)
+        .with_format(format)
+        .with_remote_addr(req.peer_addr().map(|addr| addr.to_string()))
+        .with_connection_slot(connection_slot);
+    if let Some(config) = &config {
+        let rate_limit = &config.rate_limit;
+        let performance = &config.performance;
+        session = session
+            .with_heartbeat(
+                Duration::from_secs(performance.websocket_heartbeat_interval),
+                Duration::from_secs(performance.client_timeout),
+            )
+            .with_auth_timeout(Duration::from_secs(config.auth.websocket_auth_timeout))
+            .with_slow_consumer_policy(
+                performance.websocket_slow_consumer_policy,
+                performance.websocket_max_dropped_messages,
+            )
+            .with_max_subscriptions(performance.websocket_max_subscriptions)
+            .with_server_time_interval(
+                (performance.websocket_server_time_interval_ms > 0)
+                    .then(|| Duration::from_millis(performance.websocket_server_time_interval_ms)),
+            )
+            .with_rate_limit(
+                rate_limit.enabled.then(|| {
+                    TokenBucket::new(rate_limit.websocket_messages_per_second, rate_limit.websocket_burst)
+                }),
+                rate_limit.websocket_max_violations,
+            );
+    }
+    // The session address is set in the manager when the session starts
+    Ok(session)
+}
+
+/// JSON Schemas of the messages clients send and of the messages the server sends
+///
+/// Client messages are described as they are accepted, server messages as
+/// they are written, with the topic and sequence number of resumable topics
+/// and the ID of the client message they answer.
+pub fn protocol_schema() -> serde_json::Value {
+    let client = SchemaSettings::draft2020_12()
+        .for_deserialize()
+        .into_generator()
+        .into_root_schema_for::<ClientRequest>();
+    let server = SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<Envelope>();
+    serde_json::json!({
+        "client_message": client,
+        "server_message": server,
+    })
+}
+
+/// Serve the JSON Schemas of the WebSocket protocol messages
+pub async fn ws_schema() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(protocol_schema()))
 }
 
 /// Configure WebSocket routes
 pub fn configure_websocket_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/ws", web::get().to(websocket_handler));
+    cfg.route("/ws", web::get().to(websocket_handler))
+        .route("/ws/klines/{token}/{interval}", web::get().to(kline_stream_handler))
+        .route("/ws/trades/{token}", web::get().to(trade_stream_handler));
 } 
\ No newline at end of file
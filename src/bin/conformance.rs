@@ -0,0 +1,53 @@
+use std::process::ExitCode;
+use std::time::Duration;
+
+use k_line::conformance::{self, ConformanceOptions};
+
+const USAGE: &str = "Usage: conformance [--url ws://127.0.0.1:8080/ws] [--admin-key KEY] [--traffic-timeout SECONDS]";
+
+/// Parse command line arguments into run options
+fn parse_args() -> Result<ConformanceOptions, String> {
+    let mut options = ConformanceOptions::new("ws://127.0.0.1:8080/ws");
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--url" => options.url = value()?,
+            "--admin-key" => options.admin_api_key = Some(value()?),
+            "--traffic-timeout" => {
+                let seconds = value()?
+                    .parse()
+                    .map_err(|_| "Invalid --traffic-timeout".to_string())?;
+                options.traffic_timeout = Duration::from_secs(seconds);
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let report = conformance::run(&options).await;
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize report: {}", e),
+    }
+
+    if report.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,18 @@
+use std::process::ExitCode;
+
+use k_line::typescript::{self, DEFINITIONS_PATH};
+
+/// Write the TypeScript declarations of the WebSocket messages, to the given path or the default one
+fn main() -> ExitCode {
+    let path = std::env::args().nth(1).unwrap_or_else(|| DEFINITIONS_PATH.to_string());
+    match std::fs::write(&path, typescript::definitions()) {
+        Ok(()) => {
+            println!("Wrote {}", path);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
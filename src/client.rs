@@ -0,0 +1,464 @@
+use awc::ws;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::models::{KLine, Ticker, TimeInterval, Transaction};
+use crate::services::SortOrder;
+
+/// Header carrying the API key on requests and WebSocket upgrades
+const API_KEY_HEADER: &str = "X-API-Key";
+/// Time allowed for a request before it fails
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before reconnecting a dropped subscription, doubled after each failed attempt
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Longest delay between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Largest response body decoded, enough for a full page of candles
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+type Connection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+/// Error of a client request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    /// The request could not be sent or its response read
+    Request(String),
+    /// The server answered with an error status
+    Status { status: u16, message: String },
+    /// The response was not in the expected shape
+    Decode(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Request(e) => write!(f, "Request failed: {}", e),
+            ClientError::Status { status, message } => write!(f, "Server returned {}: {}", status, message),
+            ClientError::Decode(e) => write!(f, "Invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Parameters of a `GET /api/v1/klines` request
+#[derive(Debug, Clone, PartialEq)]
+pub struct KLineQuery {
+    /// Token symbol or pair
+    pub token: String,
+    /// Candle interval
+    pub interval: TimeInterval,
+    /// Candles to return; the server's default when unset
+    pub limit: Option<usize>,
+    /// Order of the candles; newest first unless paging with `after`
+    pub order: Option<SortOrder>,
+    /// Only candles opened before this time
+    pub before: Option<DateTime<Utc>>,
+    /// Only candles opened after this time
+    pub after: Option<DateTime<Utc>>,
+}
+
+impl KLineQuery {
+    /// Query the latest candles of a token
+    pub fn new(token: impl Into<String>, interval: TimeInterval) -> Self {
+        Self {
+            token: token.into(),
+            interval,
+            limit: None,
+            order: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Set the number of candles to return
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Set the order of the candles
+    pub fn with_order(mut self, order: SortOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// Only return candles opened before a time
+    pub fn with_before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    /// Only return candles opened after a time
+    pub fn with_after(mut self, after: DateTime<Utc>) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![("token", self.token.clone()), ("interval", self.interval.as_str().into_owned())];
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(order) = self.order {
+            params.push(("order", order.as_str().to_string()));
+        }
+        if let Some(before) = self.before {
+            params.push(("before", before.timestamp_millis().to_string()));
+        }
+        if let Some(after) = self.after {
+            params.push(("after", after.timestamp_millis().to_string()));
+        }
+        params
+    }
+}
+
+/// A page of candles returned by `GET /api/v1/klines`
+#[derive(Debug, Clone, Deserialize)]
+pub struct KLinesResponse {
+    /// Token the candles are for
+    pub token: String,
+    /// Interval of the candles
+    pub interval: String,
+    /// Candles in the page
+    #[serde(rename = "data")]
+    pub klines: Vec<KLine>,
+    /// Cursor for the following page in ms since epoch, if more candles remain
+    pub next_cursor: Option<i64>,
+}
+
+impl KLinesResponse {
+    /// Query for the page following this one, if more candles remain
+    pub fn next_page(&self, query: &KLineQuery) -> Option<KLineQuery> {
+        let cursor = DateTime::from_timestamp_millis(self.next_cursor?)?;
+        let query = query.clone();
+        Some(match query.after {
+            Some(_) => query.with_after(cursor),
+            None => query.with_before(cursor),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Tokens {
+    tokens: Vec<String>,
+}
+
+/// A message received on a subscription
+#[derive(Deserialize)]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    topic: Option<String>,
+    seq: Option<u64>,
+    complete: Option<bool>,
+    message: Option<String>,
+    data: Option<serde_json::Value>,
+}
+
+/// Typed client of the REST API and WebSocket subscriptions
+///
+/// Requests and subscriptions use `awc`, so they must run on the actix
+/// runtime, for example under `#[actix_web::main]` or `actix_rt::System`.
+#[derive(Debug, Clone)]
+pub struct KLineClient {
+    base_url: String,
+    api_key: Option<String>,
+    timeout: Duration,
+    reconnect_delay: Duration,
+}
+
+impl KLineClient {
+    /// Create a client of the server at a base URL, such as `http://localhost:8080`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            timeout: DEFAULT_TIMEOUT,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+        }
+    }
+
+    /// Authenticate requests and subscriptions with an API key
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Set the time allowed for a request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the delay before reconnecting a dropped subscription
+    pub fn with_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Get a page of candles
+    pub async fn get_klines(&self, query: &KLineQuery) -> Result<KLinesResponse, ClientError> {
+        self.get("/klines", &query.params()).await
+    }
+
+    /// Get the open candle of a token, if it has traded in the current interval
+    pub async fn get_current_kline(&self, token: &str, interval: TimeInterval) -> Result<Option<KLine>, ClientError> {
+        self.get_kline("/klines/current", token, interval).await
+    }
+
+    /// Get the most recent candle of a token, open or closed
+    pub async fn get_latest_kline(&self, token: &str, interval: TimeInterval) -> Result<Option<KLine>, ClientError> {
+        self.get_kline("/klines/latest", token, interval).await
+    }
+
+    /// Get the 24-hour ticker of a token, if it traded in the last 24 hours
+    pub async fn get_ticker(&self, token: &str) -> Result<Option<Ticker>, ClientError> {
+        found(self.get("/ticker", &[("token", token.to_string())]).await)
+    }
+
+    /// Get the tokens with candles
+    pub async fn get_tokens(&self) -> Result<Vec<String>, ClientError> {
+        let tokens: Tokens = self.get("/tokens", &[]).await?;
+        Ok(tokens.tokens)
+    }
+
+    /// Stream the candle updates of a token and interval
+    ///
+    /// Every update of the open candle is yielded, followed by the closed
+    /// candle. A dropped connection is reopened with backoff and the updates
+    /// missed meanwhile are replayed from the server's buffer, without
+    /// duplicates. The stream never ends.
+    pub fn subscribe_klines(&self, token: &str, interval: TimeInterval) -> impl Stream<Item = KLine> + Unpin {
+        let subscription = json!({
+            "type": "klines",
+            "token": token,
+            "interval": interval.as_str(),
+        });
+        self.subscribe(subscription, "kline", 1)
+    }
+
+    /// Stream the trades of some tokens, reconnecting like [`subscribe_klines`](Self::subscribe_klines)
+    pub fn subscribe_transactions(&self, tokens: &[&str]) -> impl Stream<Item = Transaction> + Unpin {
+        let subscription = json!({
+            "type": "transactions",
+            "tokens": tokens,
+        });
+        self.subscribe(subscription, "transaction", tokens.len())
+    }
+
+    fn subscribe<T: DeserializeOwned + 'static>(
+        &self,
+        subscription: serde_json::Value,
+        message_type: &'static str,
+        topics: usize,
+    ) -> impl Stream<Item = T> + Unpin {
+        let state = Subscription {
+            client: self.clone(),
+            subscription,
+            message_type,
+            topics,
+            sequences: HashMap::new(),
+            connection: None,
+        };
+        Box::pin(stream::unfold(state, |mut state| async move {
+            let item = state.next().await;
+            Some((item, state))
+        }))
+    }
+
+    async fn get_kline(&self, path: &str, token: &str, interval: TimeInterval) -> Result<Option<KLine>, ClientError> {
+        let params = [("token", token.to_string()), ("interval", interval.as_str().into_owned())];
+        let response: Option<Data<KLine>> = found(self.get(path, &params).await)?;
+        Ok(response.map(|response| response.data))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T, ClientError> {
+        let url = format!("{}/api/v1{}", self.base_url, path);
+        let mut response = self
+            .http()
+            .get(url)
+            .query(&params)
+            .map_err(|e| ClientError::Request(e.to_string()))?
+            .send()
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+        let body = response
+            .body()
+            .limit(MAX_RESPONSE_SIZE)
+            .await
+            .map_err(|e| ClientError::Request(e.to_string()))?;
+
+        if !response.status().is_success() {
+            // Errors are answered as {"error": "..."}
+            let message = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|error| error["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| String::from_utf8_lossy(&body).trim().to_string());
+            return Err(ClientError::Status {
+                status: response.status().as_u16(),
+                message,
+            });
+        }
+        serde_json::from_slice(&body).map_err(|e| ClientError::Decode(e.to_string()))
+    }
+
+    /// HTTP client that authenticates with the configured API key
+    fn http(&self) -> awc::Client {
+        let mut builder = awc::Client::builder().timeout(self.timeout);
+        if let Some(api_key) = &self.api_key {
+            builder = builder.add_default_header((API_KEY_HEADER, api_key.as_str()));
+        }
+        builder.finish()
+    }
+
+    fn ws_url(&self) -> String {
+        format!("{}/ws", self.base_url.replacen("http", "ws", 1))
+    }
+}
+
+/// Treat a 404 as a missing resource
+fn found<T>(result: Result<T, ClientError>) -> Result<Option<T>, ClientError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ClientError::Status { status: 404, .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// State of a subscription stream across reconnections
+struct Subscription {
+    client: KLineClient,
+    subscription: serde_json::Value,
+    message_type: &'static str,
+    /// Topics the subscription covers
+    topics: usize,
+    /// Last sequence number received per topic
+    sequences: HashMap<String, u64>,
+    connection: Option<Connection>,
+}
+
+impl Subscription {
+    /// Wait for the next item, reconnecting as often as needed
+    async fn next<T: DeserializeOwned>(&mut self) -> T {
+        loop {
+            let frame = match self.connection.as_mut() {
+                Some(connection) => connection.next().await,
+                None => {
+                    self.connection = Some(self.reconnect().await);
+                    continue;
+                }
+            };
+            match frame {
+                Some(Ok(ws::Frame::Text(bytes))) => {
+                    if let Some(item) = self.accept(&bytes) {
+                        return item;
+                    }
+                }
+                Some(Ok(ws::Frame::Ping(payload))) => {
+                    if let Some(connection) = self.connection.as_mut() {
+                        let _ = connection.send(ws::Message::Pong(payload)).await;
+                    }
+                }
+                Some(Ok(ws::Frame::Close(_))) | Some(Err(_)) | None => {
+                    tracing::warn!("Subscription to {} disconnected", self.client.ws_url());
+                    self.connection = None;
+                    tokio::time::sleep(self.client.reconnect_delay).await;
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    /// Connect until it succeeds, backing off between attempts
+    async fn reconnect(&self) -> Connection {
+        let mut delay = self.client.reconnect_delay;
+        loop {
+            match self.connect().await {
+                Ok(connection) => return connection,
+                Err(e) => tracing::warn!("Failed to subscribe to {}: {}", self.client.ws_url(), e),
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY.max(self.client.reconnect_delay));
+        }
+    }
+
+    /// Open a connection and resume or subscribe to the topics
+    ///
+    /// Resuming a topic subscribes to it and replays what was missed, so the
+    /// subscription itself is only sent again while some topic has not been
+    /// received yet.
+    async fn connect(&self) -> Result<Connection, String> {
+        let (_, mut connection) = self
+            .client
+            .http()
+            .ws(self.client.ws_url())
+            .connect()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut messages: Vec<serde_json::Value> = self
+            .sequences
+            .iter()
+            .map(|(topic, since)| json!({"action": "resume", "topic": topic, "since": since}))
+            .collect();
+        if self.sequences.len() < self.topics {
+            messages.push(json!({"action": "subscribe", "subscription": self.subscription}));
+        }
+        for message in messages {
+            connection
+                .send(ws::Message::Text(message.to_string().into()))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(connection)
+    }
+
+    /// Decode a message of the subscribed type not received before
+    fn accept<T: DeserializeOwned>(&mut self, bytes: &[u8]) -> Option<T> {
+        let message: Message = serde_json::from_slice(bytes).ok()?;
+        match message.kind.as_str() {
+            "resumed" if message.complete == Some(false) => {
+                // The server restarted or evicted the missed messages, so its
+                // sequence numbers no longer follow the last one received
+                let topic = message.topic?;
+                tracing::warn!("Messages missed on {} could not all be replayed", topic);
+                self.sequences.remove(&topic);
+                None
+            }
+            "error" => {
+                tracing::warn!("Subscription error: {}", message.message.unwrap_or_default());
+                None
+            }
+            kind if kind == self.message_type => {
+                if let (Some(topic), Some(seq)) = (message.topic, message.seq) {
+                    let last = self.sequences.entry(topic).or_default();
+                    if seq <= *last {
+                        return None;
+                    }
+                    *last = seq;
+                }
+                match serde_json::from_value(message.data?) {
+                    Ok(item) => Some(item),
+                    Err(e) => {
+                        tracing::warn!("Invalid {} message: {}", self.message_type, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
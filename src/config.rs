@@ -1,7 +1,25 @@
+#[cfg(feature = "server")]
+use redis::IntoConnectionInfo;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::Path;
+use tracing_subscriber::EnvFilter;
+
+use crate::models::{Symbol, TimeInterval};
+#[cfg(feature = "server")]
+use crate::services::archive::ArchiveLocation;
+#[cfg(feature = "server")]
+use crate::services::influx::InfluxEndpoint;
+use crate::services::{dedup, mock_data, order_book, replay, symbols};
+
+mod partial;
+
+pub use partial::PartialConfig;
+
+/// Prefix of environment variables that override configuration fields
+pub const ENV_PREFIX: &str = "KLINE__";
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +34,60 @@ pub struct Config {
     pub performance: PerformanceConfig,
     /// Data generation configuration
     pub data_generation: DataGenerationConfig,
+    /// Replication configuration
+    #[serde(default)]
+    pub replication: ReplicationConfig,
+    /// gRPC server configuration
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    /// Admin access configuration
+    #[serde(default)]
+    pub admin: AdminConfig,
+    /// API key authentication configuration
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Per-client rate limiting configuration
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Outbound webhook configuration
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    /// Simulated order book configuration
+    #[serde(default)]
+    pub order_book: OrderBookConfig,
+    /// K-line snapshot configuration
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    /// Liveness and readiness check configuration
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// OpenTelemetry export configuration
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Quote currency conversion configuration
+    #[serde(default)]
+    pub conversion: ConversionConfig,
+    /// Composite index price configuration
+    #[serde(default)]
+    pub index: IndexConfig,
+    /// Cross-instance broadcast fan-out configuration
+    #[serde(default)]
+    pub fanout: FanoutConfig,
+    /// Shared candle storage configuration
+    #[serde(default)]
+    pub storage: StorageConfig,
+    /// Write-ahead transaction log configuration
+    #[serde(default)]
+    pub wal: WalConfig,
+    /// Evicted candle archival configuration
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    /// ClickHouse analytics sink configuration
+    #[serde(default)]
+    pub clickhouse: ClickHouseConfig,
+    /// InfluxDB line protocol export configuration
+    #[serde(default)]
+    pub influx: InfluxConfig,
 }
 
 /// Server configuration
@@ -27,17 +99,114 @@ pub struct ServerConfig {
     pub port: u16,
     /// Number of worker threads
     pub workers: Option<usize>,
+    /// TLS termination for HTTPS and wss://
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Cross-origin access for browser clients
+    #[serde(default)]
+    pub cors: CorsConfig,
+}
+
+/// TLS configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// Whether to serve HTTPS instead of plain HTTP
+    pub enabled: bool,
+    /// PEM certificate chain
+    pub cert_path: Option<String>,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path: Option<String>,
+}
+
+/// CORS configuration of the REST API and the WebSocket upgrade
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins browsers may call from, such as `https://app.example.com`, or
+    /// `*` for any; cross-origin requests are refused when empty
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests
+    pub allowed_headers: Vec<String>,
+    /// Whether cookies and credentials may be sent; needs explicit origins
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response
+    pub max_age_secs: usize,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["Content-Type", "Authorization", "X-API-Key", "X-Request-Id"].map(String::from).to_vec(),
+            allow_credentials: false,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Whether any origin may call
+    pub fn any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.allow_credentials && self.any_origin() {
+            return Err("CORS credentials require explicit server.cors.allowed_origins, not \"*\"".to_string());
+        }
+        for origin in self.allowed_origins.iter().filter(|origin| *origin != "*") {
+            if !(origin.starts_with("http://") || origin.starts_with("https://")) || origin.ends_with('/') {
+                return Err(format!(
+                    "CORS origin {} must be a scheme and host such as https://app.example.com",
+                    origin
+                ));
+            }
+        }
+        // Methods and headers are checked by the server's HTTP types
+        #[cfg(feature = "server")]
+        for method in &self.allowed_methods {
+            if actix_web::http::Method::from_bytes(method.as_bytes()).is_err() {
+                return Err(format!("Invalid CORS method: {}", method));
+            }
+        }
+        #[cfg(feature = "server")]
+        for header in &self.allowed_headers {
+            if actix_web::http::header::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return Err(format!("Invalid CORS header: {}", header));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Token configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenConfig {
-    /// Token symbol
+    /// Token symbol, or a pair such as `DOGE-USDT`; plain tokens are quoted in USD
     pub symbol: String,
     /// Base price for mock data generation
     pub base_price: f64,
     /// Volatility percentage for mock data generation
     pub volatility: f64,
+    /// Decimal places prices are rounded to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_precision: Option<u32>,
+    /// Decimal places volumes are rounded to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity_precision: Option<u32>,
+    /// Smallest price increment; prices are rounded to a multiple of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_size: Option<f64>,
+    /// Mean trades per second under Poisson arrivals; one per generation interval when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrival_rate: Option<f64>,
+    /// Trade size distribution; `data_generation.volume` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<VolumeConfig>,
 }
 
 /// Tokens configuration
@@ -45,15 +214,169 @@ pub struct TokenConfig {
 pub struct TokensConfig {
     /// Supported tokens
     pub supported_tokens: Vec<TokenConfig>,
+    /// Auto-generated token universe for scale testing
+    #[serde(default)]
+    pub synthetic_tokens: Option<SyntheticTokensConfig>,
+    /// How transactions for tokens missing from the configuration are handled
+    #[serde(default)]
+    pub unknown_token_policy: UnknownTokenPolicy,
+    /// Whether the K-line service accepts tokens outside the registry
+    #[serde(default)]
+    pub validation: TokenValidation,
+}
+
+/// Policy for transactions that reference an unconfigured token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownTokenPolicy {
+    /// Drop the transactions
+    Reject,
+    /// Start a new series on the first trade
+    #[default]
+    AutoRegister,
+    /// Hold the latest transactions back until an admin registers the token, then apply them
+    Quarantine,
+}
+
+/// How strictly the K-line service checks transaction tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenValidation {
+    /// Any token starts a new series on its first trade
+    #[default]
+    Permissive,
+    /// Tokens outside the configured and registered ones are refused with an error
+    Strict,
+}
+
+/// Synthetic token universe configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticTokensConfig {
+    /// Number of tokens to generate
+    pub count: usize,
+    /// Symbol prefix; symbols are the prefix plus a zero-padded index
+    #[serde(default = "default_synthetic_prefix")]
+    pub prefix: String,
+    /// Base price range, spread log-uniformly across the generated tokens
+    #[serde(default = "default_synthetic_price_range")]
+    pub price_range: (f64, f64),
+    /// Volatility percentage for every generated token
+    #[serde(default = "default_synthetic_volatility")]
+    pub volatility: f64,
+}
+
+fn default_synthetic_prefix() -> String {
+    "TOK".to_string()
+}
+
+fn default_synthetic_price_range() -> (f64, f64) {
+    (0.0001, 10.0)
+}
+
+fn default_synthetic_volatility() -> f64 {
+    5.0
+}
+
+impl SyntheticTokensConfig {
+    /// Generate the token configurations
+    ///
+    /// Prices are spaced geometrically between the range bounds so the universe
+    /// covers every order of magnitude and is identical across restarts.
+    pub fn generate(&self) -> Vec<TokenConfig> {
+        let width = self.count.to_string().len();
+        let (min_price, max_price) = self.price_range;
+        let steps = self.count.saturating_sub(1).max(1) as f64;
+
+        (0..self.count)
+            .map(|i| TokenConfig {
+                symbol: format!("{}{:0width$}", self.prefix, i + 1, width = width),
+                base_price: min_price * (max_price / min_price).powf(i as f64 / steps),
+                volatility: self.volatility,
+                price_precision: None,
+                quantity_precision: None,
+                tick_size: None,
+                arrival_rate: None,
+                volume: None,
+            })
+            .collect()
+    }
+}
+
+impl TokensConfig {
+    /// Append the synthetic universe to the supported tokens
+    ///
+    /// Explicitly configured tokens win over generated ones with the same symbol.
+    pub fn expand_synthetic_tokens(&mut self) {
+        let Some(synthetic) = &self.synthetic_tokens else {
+            return;
+        };
+
+        let generated: Vec<TokenConfig> = synthetic
+            .generate()
+            .into_iter()
+            .filter(|token| {
+                !self
+                    .supported_tokens
+                    .iter()
+                    .any(|existing| existing.symbol == token.symbol)
+            })
+            .collect();
+        self.supported_tokens.extend(generated);
+    }
+
+    /// Rewrite symbols to their canonical form, so `doge/usdt` names the same
+    /// series as the `DOGE-USDT` of ingested trades and requests
+    pub fn canonicalize_symbols(&mut self) {
+        for token in &mut self.supported_tokens {
+            token.symbol = Symbol::canonicalize(&token.symbol);
+        }
+    }
 }
 
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingConfig {
-    /// Log level
+    /// Log level or filter directives (`RUST_LOG` takes precedence)
     pub level: String,
     /// Whether to output to file
     pub file_output: bool,
+    /// Output format
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Log file path; rotated files get a date suffix
+    #[serde(default = "default_log_file_path")]
+    pub file_path: String,
+    /// How often the log file is rotated
+    #[serde(default)]
+    pub rotation: LogRotation,
+}
+
+/// Log output format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+/// Log file rotation period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    /// Write a single file
+    Never,
+}
+
+fn default_log_file_path() -> String {
+    "logs/k-line.log".to_string()
 }
 
 /// Performance configuration
@@ -69,6 +392,119 @@ pub struct PerformanceConfig {
     pub kline_retention_hours: u64,
     /// Maximum WebSocket connections
     pub max_websocket_connections: usize,
+    /// Maximum WebSocket connections from one IP address (0 = unlimited)
+    #[serde(default)]
+    pub max_websocket_connections_per_ip: usize,
+    /// Deflate-compress large WebSocket payloads for clients that opt in
+    #[serde(default)]
+    pub websocket_compression: bool,
+    /// Minimum payload size (bytes) that is compressed
+    #[serde(default = "default_compression_threshold")]
+    pub websocket_compression_threshold: usize,
+    /// Messages kept per transaction/K-line topic for resume after reconnect
+    #[serde(default = "default_replay_buffer")]
+    pub websocket_replay_buffer: usize,
+    /// Messages a session may fall behind on a topic before the oldest are dropped
+    #[serde(default = "default_session_backlog")]
+    pub websocket_session_backlog: usize,
+    /// Subscriptions a WebSocket session may hold at once
+    #[serde(default = "default_max_subscriptions")]
+    pub websocket_max_subscriptions: usize,
+    /// How sessions that fall behind are handled
+    #[serde(default)]
+    pub websocket_slow_consumer_policy: SlowConsumerPolicy,
+    /// Dropped messages after which a conflating session is disconnected (0 = never)
+    #[serde(default = "default_max_dropped_messages")]
+    pub websocket_max_dropped_messages: u64,
+    /// Time (milliseconds) consecutive same-price trades are combined for agg_trades subscribers
+    #[serde(default = "default_agg_trade_window_ms")]
+    pub websocket_agg_trade_window_ms: u64,
+    /// Time (milliseconds) between server_time messages pushed to every session (0 = never)
+    #[serde(default)]
+    pub websocket_server_time_interval_ms: u64,
+    /// Recent trades kept per token for the trade tape
+    #[serde(default = "default_trade_history_size")]
+    pub trade_history_size: usize,
+    /// Trade-count ("100t") and volume ("5000v") intervals aggregated besides the time intervals
+    #[serde(default)]
+    pub kline_activity_intervals: Vec<TimeInterval>,
+    /// Tasks applying ingested transactions; each token is handled by one of them
+    #[serde(default = "default_ingest_workers")]
+    pub ingest_workers: usize,
+    /// Transactions queued per ingest worker before sources have to wait
+    #[serde(default = "default_ingest_queue_capacity")]
+    pub ingest_queue_capacity: usize,
+    /// Partition candles into one shard per ingest worker, each written by its worker only
+    #[serde(default)]
+    pub sharded_processing: bool,
+    /// Time (milliseconds) REST K-line pages are served from cache at most (0 = no cache)
+    #[serde(default = "default_query_cache_ttl_ms")]
+    pub kline_query_cache_ttl_ms: u64,
+    /// K-line pages kept in the REST query cache
+    #[serde(default = "default_query_cache_capacity")]
+    pub kline_query_cache_capacity: usize,
+    /// Trade IDs remembered per token to drop replayed or retried trades (0 = no deduplication)
+    #[serde(default = "default_trade_dedup_window")]
+    pub trade_dedup_window: usize,
+}
+
+/// Handling of WebSocket sessions that cannot keep up with their subscriptions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    /// Drop the oldest updates and resend candles that closed in the gap
+    #[default]
+    Conflate,
+    /// Disconnect the session as soon as it falls behind
+    Disconnect,
+}
+
+fn default_compression_threshold() -> usize {
+    1024
+}
+
+fn default_replay_buffer() -> usize {
+    1024
+}
+
+fn default_session_backlog() -> usize {
+    256
+}
+
+fn default_max_subscriptions() -> usize {
+    100
+}
+
+fn default_max_dropped_messages() -> u64 {
+    10_000
+}
+
+fn default_agg_trade_window_ms() -> u64 {
+    100
+}
+
+fn default_trade_history_size() -> usize {
+    1000
+}
+
+fn default_ingest_workers() -> usize {
+    4
+}
+
+fn default_ingest_queue_capacity() -> usize {
+    1024
+}
+
+fn default_query_cache_ttl_ms() -> u64 {
+    1000
+}
+
+fn default_query_cache_capacity() -> usize {
+    1024
+}
+
+fn default_trade_dedup_window() -> usize {
+    dedup::DEFAULT_DEDUP_WINDOW
 }
 
 /// Data generation configuration
@@ -76,19 +512,902 @@ pub struct PerformanceConfig {
 pub struct DataGenerationConfig {
     /// Whether to enable data generation
     pub enabled: bool,
+    /// Where ingested transactions come from
+    #[serde(default)]
+    pub data_source: DataSource,
+    /// Recorded transactions fed through the pipeline in replay mode
+    #[serde(default)]
+    pub replay: ReplayConfig,
+    /// Scripted market scenario run by the mock generator in scenario mode
+    #[serde(default)]
+    pub scenario: ScenarioConfig,
     /// Generation interval (milliseconds)
     pub interval_ms: u64,
     /// Price volatility (percentage)
     pub volatility: f64,
     /// Volume range
     pub volume_range: (f64, f64),
+    /// How trade arrival times are generated
+    #[serde(default)]
+    pub arrival: ArrivalProcess,
+    /// Trade size distribution of tokens without their own
+    #[serde(default)]
+    pub volume: VolumeConfig,
+    /// Hours of history generated for every token at startup, before live trades
+    #[serde(default)]
+    pub backfill_hours: u64,
+    /// Market regimes that shape generated prices and volumes
+    #[serde(default)]
+    pub regimes: RegimeConfig,
+}
+
+/// Market regime of generated prices
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum MarketRegime {
+    Bull,
+    Bear,
+    #[default]
+    Sideways,
+    Crash,
+}
+
+impl MarketRegime {
+    pub const ALL: [MarketRegime; 4] = [
+        MarketRegime::Bull,
+        MarketRegime::Bear,
+        MarketRegime::Sideways,
+        MarketRegime::Crash,
+    ];
+}
+
+/// Price and volume behaviour of a market regime
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegimeParams {
+    /// Fractional move of each token's price level per generated trade
+    pub drift: f64,
+    /// Factor applied to `data_generation.volatility`
+    pub volatility_multiplier: f64,
+    /// Factor applied to generated volumes
+    pub volume_multiplier: f64,
+    /// Relative weights of the regime entered at each step, this one included
+    pub transitions: BTreeMap<MarketRegime, f64>,
+}
+
+impl RegimeParams {
+    fn new(drift: f64, volatility_multiplier: f64, volume_multiplier: f64, transitions: &[(MarketRegime, f64)]) -> Self {
+        Self {
+            drift,
+            volatility_multiplier,
+            volume_multiplier,
+            transitions: transitions.iter().copied().collect(),
+        }
+    }
+
+    fn validate(&self, regime: MarketRegime) -> Result<(), String> {
+        if !self.drift.is_finite() || self.drift.abs() >= 1.0 {
+            return Err(format!("Drift of the {:?} regime must be between -1 and 1", regime));
+        }
+        let valid_multiplier = |multiplier: f64| multiplier.is_finite() && multiplier >= 0.0;
+        if !valid_multiplier(self.volatility_multiplier) || !valid_multiplier(self.volume_multiplier) {
+            return Err(format!("Multipliers of the {:?} regime must not be negative", regime));
+        }
+        if self.transitions.values().any(|weight| !weight.is_finite() || *weight < 0.0)
+            || self.transitions.values().sum::<f64>() <= 0.0
+        {
+            return Err(format!(
+                "Transitions of the {:?} regime need non-negative weights with a positive sum",
+                regime
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Markov chain of market regimes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegimeConfig {
+    /// Whether prices follow the regimes; scripted events apply either way
+    pub enabled: bool,
+    /// Seconds between transitions
+    pub step_secs: u64,
+    /// Regime at startup
+    pub initial: MarketRegime,
+    pub bull: RegimeParams,
+    pub bear: RegimeParams,
+    pub sideways: RegimeParams,
+    pub crash: RegimeParams,
+}
+
+impl RegimeConfig {
+    /// Behaviour of a regime
+    pub fn params(&self, regime: MarketRegime) -> &RegimeParams {
+        match regime {
+            MarketRegime::Bull => &self.bull,
+            MarketRegime::Bear => &self.bear,
+            MarketRegime::Sideways => &self.sideways,
+            MarketRegime::Crash => &self.crash,
+        }
+    }
+}
+
+impl Default for RegimeConfig {
+    fn default() -> Self {
+        use MarketRegime::*;
+        Self {
+            enabled: false,
+            step_secs: 30,
+            initial: Sideways,
+            bull: RegimeParams::new(0.0002, 0.8, 1.5, &[(Bull, 0.9), (Sideways, 0.08), (Crash, 0.02)]),
+            bear: RegimeParams::new(-0.0002, 1.2, 1.2, &[(Bear, 0.9), (Sideways, 0.08), (Crash, 0.02)]),
+            sideways: RegimeParams::new(0.0, 0.5, 0.7, &[(Sideways, 0.8), (Bull, 0.1), (Bear, 0.1)]),
+            crash: RegimeParams::new(-0.002, 3.0, 4.0, &[(Crash, 0.5), (Bear, 0.3), (Sideways, 0.2)]),
+        }
+    }
+}
+
+/// Source of the transactions fed through the pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSource {
+    /// Transactions are generated by the mock generator
+    #[default]
+    Mock,
+    /// Transactions are read from a recorded CSV or JSONL file
+    Replay,
+    /// Transactions are generated by the mock generator following a scenario file
+    Scenario,
+}
+
+/// Replay of recorded transactions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    /// CSV or JSONL file of transactions, in time order
+    pub path: Option<String>,
+    /// Factor by which the recorded gaps between transactions are shortened
+    pub speed: f64,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self { path: None, speed: 1.0 }
+    }
+}
+
+/// Scripted market scenario
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenarioConfig {
+    /// TOML or JSON file of timed market events
+    pub path: Option<String>,
+}
+
+/// Timing of generated trades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrivalProcess {
+    /// Every token trades once per generation interval
+    #[default]
+    Fixed,
+    /// Each token trades after exponentially distributed gaps, at its `arrival_rate`
+    Poisson,
+}
+
+/// Shape of generated trade sizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeDistribution {
+    /// Uniform within `data_generation.volume_range`
+    #[default]
+    Uniform,
+    /// Log-normal with parameters `mu` and `sigma`, so most trades are small and a few large
+    LogNormal,
+}
+
+/// Trade size distribution, with occasional outsized whale trades
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VolumeConfig {
+    pub distribution: VolumeDistribution,
+    /// Mean of the logarithm of log-normal volumes; the median volume is e^mu
+    pub mu: f64,
+    /// Standard deviation of the logarithm of log-normal volumes
+    pub sigma: f64,
+    /// Chance of each trade being a whale trade
+    pub whale_probability: f64,
+    /// Factor applied to the volume of whale trades
+    pub whale_multiplier: f64,
+}
+
+impl VolumeConfig {
+    fn validate(&self, owner: &str) -> Result<(), String> {
+        if !self.mu.is_finite() || !self.sigma.is_finite() || self.sigma < 0.0 {
+            return Err(format!("Volume mu of {} must be finite and sigma not negative", owner));
+        }
+        if !(0.0..=1.0).contains(&self.whale_probability) {
+            return Err(format!("Whale probability of {} must be between 0.0 and 1.0", owner));
+        }
+        if !self.whale_multiplier.is_finite() || self.whale_multiplier < 1.0 {
+            return Err(format!("Whale multiplier of {} must be at least 1", owner));
+        }
+        Ok(())
+    }
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        Self {
+            distribution: VolumeDistribution::Uniform,
+            mu: 5.5,
+            sigma: 1.0,
+            whale_probability: 0.0,
+            whale_multiplier: 100.0,
+        }
+    }
+}
+
+/// Role of this instance in a primary/standby pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationRole {
+    /// Generates data and serves clients
+    Primary,
+    /// Replicates from the primary and waits for promotion
+    Standby,
+}
+
+/// Replication configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicationConfig {
+    /// Role this instance starts in
+    pub role: ReplicationRole,
+    /// Base URL of the primary instance (standby only)
+    pub primary_url: Option<String>,
+    /// Primary health check interval (milliseconds)
+    pub health_check_interval_ms: u64,
+    /// Consecutive failed health checks before the primary is considered down
+    pub failover_threshold: u32,
+    /// Whether to promote automatically when the primary is down
+    pub auto_failover: bool,
+    /// Candle checksum comparison interval (seconds)
+    pub checksum_interval_secs: u64,
+    /// API key presented to the primary when it requires authentication
+    pub api_key: Option<String>,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            role: ReplicationRole::Primary,
+            primary_url: None,
+            health_check_interval_ms: 1000,
+            failover_threshold: 3,
+            auto_failover: false,
+            checksum_interval_secs: 60,
+            api_key: None,
+        }
+    }
+}
+
+/// gRPC server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// Whether to start the gRPC server
+    pub enabled: bool,
+    /// Listen port (the listen address is shared with the HTTP server)
+    pub port: u16,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 50051,
+        }
+    }
+}
+
+/// Simulated order book configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrderBookConfig {
+    /// Whether to keep a synthetic order book per token, updated by each trade
+    pub enabled: bool,
+    /// Price levels kept on each side of a book
+    pub levels: usize,
+}
+
+impl Default for OrderBookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            levels: order_book::DEFAULT_DEPTH_LEVELS,
+        }
+    }
+}
+
+/// K-line snapshot configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// JSON file written by `POST /api/v1/admin/snapshot` and restored at startup
+    pub path: Option<String>,
+}
+
+/// When the transaction log is synced to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    /// After every append; nothing is lost on a power failure, at the cost of throughput
+    Always,
+    /// Every `fsync_interval_ms`
+    #[default]
+    Interval,
+    /// Left to the operating system; only a process crash is survived
+    Never,
+}
+
+/// Write-ahead transaction log configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalConfig {
+    /// Log every ingested transaction and replay the log at startup
+    pub enabled: bool,
+    /// Directory holding the log segments
+    pub dir: String,
+    /// Size at which a segment is closed and the next one started
+    pub segment_max_bytes: u64,
+    pub fsync: FsyncPolicy,
+    /// How often the log is synced under the `interval` policy (milliseconds)
+    pub fsync_interval_ms: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: "data/wal".to_string(),
+            segment_max_bytes: 64 * 1024 * 1024,
+            fsync: FsyncPolicy::Interval,
+            fsync_interval_ms: 1000,
+        }
+    }
+}
+
+/// Object store archival of candles evicted by retention pruning
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Archive evicted candles and read ranges past the in-memory window from the archive
+    pub enabled: bool,
+    /// `s3://bucket/prefix` or `file:///directory` to archive to
+    pub url: String,
+    /// S3-compatible endpoint such as a MinIO server; AWS when unset
+    pub endpoint: Option<String>,
+    /// Bucket region; taken from `AWS_REGION` when unset
+    pub region: Option<String>,
+    /// Credentials; taken from the `AWS_*` environment variables when unset
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Allow a plain HTTP endpoint, for local MinIO servers
+    pub allow_http: bool,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "file:///var/lib/k-line/archive".to_string(),
+            endpoint: None,
+            region: None,
+            access_key_id: None,
+            secret_access_key: None,
+            allow_http: false,
+        }
+    }
+}
+
+/// ClickHouse sink of trades and closed candles for analytics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickHouseConfig {
+    /// Insert every ingested trade and closed candle into ClickHouse
+    pub enabled: bool,
+    /// Base URL of the ClickHouse HTTP interface
+    pub url: String,
+    /// Database holding the tables
+    pub database: String,
+    /// Credentials; the server's default user when unset
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Table trades are inserted into
+    pub trades_table: String,
+    /// Table closed candles are inserted into
+    pub klines_table: String,
+    /// Rows inserted in one request; a full batch is flushed at once
+    pub batch_size: usize,
+    /// Longest time rows wait before their batch is flushed
+    pub flush_interval_ms: u64,
+    /// Rows queued for the writer before new ones are dropped
+    pub queue_capacity: usize,
+    /// Timeout of a single insert request
+    pub timeout_ms: u64,
+    /// Insert attempts of a batch before it is written to the dead-letter file
+    pub max_attempts: u32,
+    /// JSON lines file keeping the batches that could not be inserted
+    pub dead_letter_path: String,
+}
+
+impl Default for ClickHouseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://localhost:8123".to_string(),
+            database: "default".to_string(),
+            user: None,
+            password: None,
+            trades_table: "trades".to_string(),
+            klines_table: "klines".to_string(),
+            batch_size: 10000,
+            flush_interval_ms: 1000,
+            queue_capacity: 100000,
+            timeout_ms: 10000,
+            max_attempts: 3,
+            dead_letter_path: "data/clickhouse-dead-letter.jsonl".to_string(),
+        }
+    }
+}
+
+/// Export of closed candles and ticker snapshots in InfluxDB line protocol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfluxConfig {
+    /// Write closed candles and periodic ticker snapshots to the endpoint
+    pub enabled: bool,
+    /// InfluxDB write URL, or a `tcp://`, `udp://` or `unix://` socket such as a Telegraf listener
+    pub endpoint: String,
+    /// API token sent as `Authorization: Token <token>` to HTTP endpoints
+    pub token: Option<String>,
+    /// Measurement closed candles are written to
+    pub kline_measurement: String,
+    /// Measurement ticker snapshots are written to
+    pub ticker_measurement: String,
+    /// Seconds between ticker snapshots of every token; 0 writes none
+    pub ticker_interval_secs: u64,
+    /// Lines written in one request or socket write
+    pub batch_size: usize,
+    /// Longest time lines wait before their batch is written
+    pub flush_interval_ms: u64,
+    /// Lines queued for the exporter before new ones are dropped
+    pub queue_capacity: usize,
+    /// Timeout of a single HTTP write
+    pub timeout_ms: u64,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:8086/api/v2/write?org=k-line&bucket=k-line".to_string(),
+            token: None,
+            kline_measurement: "kline".to_string(),
+            ticker_measurement: "ticker".to_string(),
+            ticker_interval_secs: 10,
+            batch_size: 5000,
+            flush_interval_ms: 1000,
+            queue_capacity: 50000,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Liveness and readiness check configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds without an ingested transaction after which the instance is not ready
+    pub max_ingest_age_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { max_ingest_age_secs: 30 }
+    }
+}
+
+/// OpenTelemetry export configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Export spans and metrics to an OTLP collector
+    pub enabled: bool,
+    /// OTLP gRPC endpoint of the collector
+    pub endpoint: String,
+    /// `service.name` reported with every span and metric
+    pub service_name: String,
+    /// Fraction of traces exported, from 0 to 1
+    pub sample_ratio: f64,
+    /// Interval (seconds) between metric exports
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4317".to_string(),
+            service_name: "k-line-data-service".to_string(),
+            sample_ratio: 1.0,
+            metrics_interval_secs: 60,
+        }
+    }
+}
+
+/// Quote currency conversion configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionConfig {
+    /// Rates prices can be converted with; each also converts the other way
+    pub rates: Vec<ConversionRateConfig>,
+}
+
+/// Rate converting prices quoted in one asset into another
+///
+/// Exactly one of `rate` and `pair` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionRateConfig {
+    /// Quote asset prices are given in, such as USDT
+    pub from: String,
+    /// Quote asset prices are converted to, such as EUR
+    pub to: String,
+    /// Fixed amount of `to` per unit of `from`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+    /// Pair of the two assets whose candles give the rate, such as BTC-USDT
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pair: Option<String>,
+}
+
+impl ConversionRateConfig {
+    fn validate(&self) -> Result<(), String> {
+        let owner = format!("Conversion from {} to {}", self.from, self.to);
+        let valid = |asset: &str| !asset.is_empty() && !asset.contains(|c: char| c.is_whitespace() || c == ':');
+        if !valid(&self.from) || !valid(&self.to) || self.from.eq_ignore_ascii_case(&self.to) {
+            return Err(format!("{} must name two different assets", owner));
+        }
+        match (self.rate, &self.pair) {
+            (Some(rate), None) if rate.is_finite() && rate > 0.0 => Ok(()),
+            (Some(_), None) => Err(format!("{} must have a positive rate", owner)),
+            (None, Some(pair)) => {
+                let symbol: Symbol = pair.parse()?;
+                let assets = [symbol.base(), symbol.quote()];
+                let (from, to) = (self.from.to_uppercase(), self.to.to_uppercase());
+                if assets == [from.as_str(), to.as_str()] || assets == [to.as_str(), from.as_str()] {
+                    Ok(())
+                } else {
+                    Err(format!("{} needs a pair of the two assets, not {}", owner, pair))
+                }
+            }
+            _ => Err(format!("{} needs exactly one of rate and pair", owner)),
+        }
+    }
+}
+
+/// Composite index price configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Store a weighted composite of the sources' prices as `<token>@index`
+    pub enabled: bool,
+    /// Weight of each ingestion source; sources not listed are left out
+    pub weights: BTreeMap<String, f64>,
+    /// Time (ms) after which a source's last price no longer counts
+    pub stale_after_ms: u64,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            weights: BTreeMap::from([(mock_data::MOCK_SOURCE.to_string(), 1.0)]),
+            stale_after_ms: 60_000,
+        }
+    }
+}
+
+/// Default pub/sub channel instances share broadcasts on
+pub const DEFAULT_FANOUT_CHANNEL: &str = "k-line:broadcasts";
+
+/// Cross-instance broadcast fan-out over Redis pub/sub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanoutConfig {
+    /// Share broadcasts with the other instances behind the load balancer
+    pub enabled: bool,
+    /// Redis server the instances share
+    pub redis_url: String,
+    /// Pub/sub channel the instances publish to
+    pub channel: String,
+    /// Publish the trades and candles this instance processes
+    pub publish: bool,
+    /// Rebroadcast the trades and candles of other instances to local sessions
+    pub subscribe: bool,
+    /// Broadcasts waiting to be published; further ones are dropped
+    pub queue_capacity: usize,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            channel: DEFAULT_FANOUT_CHANNEL.to_string(),
+            publish: true,
+            subscribe: true,
+            queue_capacity: 10_000,
+        }
+    }
+}
+
+/// Where candles are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Only in this instance's memory
+    #[default]
+    Memory,
+    /// Also in Redis sorted sets shared with other instances
+    Redis,
+}
+
+/// Part an instance plays in shared candle storage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageRole {
+    /// Ingests trades and writes the candles they build
+    #[default]
+    Leader,
+    /// Ingests nothing and serves the candles the leader wrote
+    Follower,
+}
+
+/// Default prefix of the candle keys in Redis
+pub const DEFAULT_KEY_PREFIX: &str = "kline";
+
+/// Shared candle storage configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub role: StorageRole,
+    /// Redis server holding the shared candles
+    pub redis_url: String,
+    /// Prefix of the `<prefix>:<token>:<interval>` sorted set keys
+    pub key_prefix: String,
+    /// How often a follower reads the candles written since its last sync (milliseconds)
+    pub sync_interval_ms: u64,
+    /// Candle updates waiting to be written; further ones are dropped
+    pub queue_capacity: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Memory,
+            role: StorageRole::Leader,
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            key_prefix: DEFAULT_KEY_PREFIX.to_string(),
+            sync_interval_ms: 500,
+            queue_capacity: 10_000,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Whether this instance serves another instance's candles instead of ingesting
+    pub fn is_follower(&self) -> bool {
+        self.backend == StorageBackend::Redis && self.role == StorageRole::Follower
+    }
+}
+
+/// Admin access configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Key required for admin-only features; they are disabled when unset
+    pub api_key: Option<String>,
+}
+
+/// API key authentication configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Require an API key on REST and WebSocket routes
+    pub enabled: bool,
+    /// Allow requests without a key, with read-only access
+    pub allow_anonymous: bool,
+    /// TOML file with additional `[[keys]]` entries
+    pub keys_file: Option<String>,
+    /// Accepted API keys
+    pub keys: Vec<ApiKeyConfig>,
+    /// Secret of the HS256 JSON Web Tokens accepted by the WebSocket `auth` message
+    pub jwt_secret: Option<String>,
+    /// Seconds a WebSocket connection without a key has to authenticate before it is closed
+    pub websocket_auth_timeout: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_anonymous: false,
+            keys_file: None,
+            keys: Vec::new(),
+            jwt_secret: None,
+            websocket_auth_timeout: 10,
+        }
+    }
+}
+
+/// A single API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Key value, sent in the `X-API-Key` header
+    pub key: String,
+    /// Access granted to the key
+    #[serde(default)]
+    pub scope: ApiKeyScope,
+    /// Label identifying the key's owner in logs
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Access granted to an API key
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Market data endpoints and subscriptions
+    #[default]
+    Read,
+    /// Everything, including admin endpoints and subscriptions
+    Admin,
+}
+
+/// Per-client rate limiting configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Whether requests and WebSocket messages are rate limited
+    pub enabled: bool,
+    /// REST requests allowed per second for each API key or IP address
+    pub requests_per_second: f64,
+    /// REST requests allowed in a burst
+    pub burst: u32,
+    /// Inbound WebSocket messages allowed per second for each session
+    pub websocket_messages_per_second: f64,
+    /// Inbound WebSocket messages allowed in a burst
+    pub websocket_burst: u32,
+    /// Rate-limited WebSocket messages after which the session is disconnected
+    pub websocket_max_violations: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_second: 20.0,
+            burst: 40,
+            websocket_messages_per_second: 10.0,
+            websocket_burst: 20,
+            websocket_max_violations: 10,
+        }
+    }
+}
+
+/// Outbound webhook configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Key for the HMAC-SHA256 signature of each payload; unsigned when unset
+    pub secret: Option<String>,
+    /// Delivery attempts after the first one fails
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each further failure
+    pub initial_backoff_ms: u64,
+    /// Timeout of a single delivery attempt
+    pub timeout_ms: u64,
+    /// Endpoints notified of events
+    pub targets: Vec<WebhookTargetConfig>,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            max_retries: 5,
+            initial_backoff_ms: 500,
+            timeout_ms: 5000,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// An endpoint notified of webhook events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookTargetConfig {
+    /// URL the payloads are POSTed to
+    pub url: String,
+    /// Events to send; all events when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEventType>,
+    /// Tokens to send events for; all tokens when empty
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Intervals to send candle closes for; all intervals when empty
+    #[serde(default)]
+    pub intervals: Vec<String>,
+}
+
+/// Kind of event delivered by webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+    /// A K-line closed
+    KlineClosed,
+    /// A price alert triggered
+    Alert,
+}
+
+impl WebhookEventType {
+    /// Name of the event, as used in payloads and configuration
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::KlineClosed => "kline_closed",
+            WebhookEventType::Alert => "alert",
+        }
+    }
+}
+
+/// Contents of an `auth.keys_file`
+#[derive(Debug, Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKeyConfig>,
+}
+
+impl AuthConfig {
+    /// Append the keys from `keys_file`, if configured
+    pub fn load_keys_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.keys_file else {
+            return Ok(());
+        };
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read auth keys file {}: {}", path, e))?;
+        let file: KeysFile = toml::from_str(&content)?;
+        self.keys.extend(file.keys);
+        Ok(())
+    }
 }
 
 impl Config {
     /// Load configuration from TOML files
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        // Start with default configuration
-        let mut config = Self::load_from_file("config/default.toml")?;
+        // Start with the built-in defaults, overridden by the default file if mounted
+        let mut config = Self::default();
+        if Path::new("config/default.toml").exists() {
+            config = config.merge_with(PartialConfig::load_from_file("config/default.toml")?);
+        }
 
         // Get environment (default to development)
         let env = env::var("RUST_ENV").unwrap_or_else(|_| "development".to_string());
@@ -96,46 +1415,88 @@ impl Config {
         // Try to load environment-specific configuration
         let env_config_path = format!("config/{}.toml", env);
         if Path::new(&env_config_path).exists() {
-            let env_config = Self::load_from_file(&env_config_path)?;
+            let env_config = PartialConfig::load_from_file(&env_config_path)?;
             config = config.merge_with(env_config);
         }
 
+        // Environment variables take precedence over the files
+        config = config.apply_env_overrides(env::vars())?;
+
+        // Read API keys kept outside the main configuration
+        config.auth.load_keys_file()?;
+
         // Validate configuration
         config.validate()?;
 
+        // Generate the synthetic token universe, if configured
+        config.tokens.expand_synthetic_tokens();
+        config.tokens.canonicalize_symbols();
+
         Ok(config)
     }
 
-    /// Load configuration from a specific TOML file
-    fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
-        Ok(config)
+    /// Merge a partial configuration into this one (set fields take precedence)
+    pub fn merge_with(mut self, other: PartialConfig) -> Self {
+        other.apply(&mut self);
+        self
     }
 
-    /// Merge this configuration with another (other takes precedence)
-    fn merge_with(mut self, other: Config) -> Self {
-        // Simple field-by-field merge
-        if other.server.host != self.server.host {
-            self.server.host = other.server.host;
-        }
-        if other.server.port != self.server.port {
-            self.server.port = other.server.port;
-        }
-        if other.server.workers.is_some() {
-            self.server.workers = other.server.workers;
+    /// Override fields from `KLINE__`-prefixed variables
+    ///
+    /// Path segments are separated by `__`, so `KLINE__SERVER__PORT=9090` sets
+    /// `server.port`. Field names are case-insensitive, while keys of map fields
+    /// (`index.weights`, regime `transitions`) are taken as written. Values are
+    /// parsed as TOML (numbers, booleans, arrays, inline tables) and fall back to
+    /// plain strings, also when the field does not accept the parsed type.
+    pub fn apply_env_overrides(
+        self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut overrides: Vec<(String, String)> = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                name.strip_prefix(ENV_PREFIX)
+                    .map(|path| (path.to_string(), value))
+            })
+            .collect();
+        if overrides.is_empty() {
+            return Ok(self);
         }
+        // Apply parent tables before the fields inside them
+        overrides.sort();
 
-        // Merge other sections as needed
-        if !other.tokens.supported_tokens.is_empty() {
-            self.tokens = other.tokens;
-        }
+        let mut root = toml::Value::try_from(&self)?;
+        for (path, raw) in overrides {
+            let segments = env_path_segments(&path);
+            let (field, parents) = segments
+                .split_last()
+                .filter(|(field, _)| !field.is_empty())
+                .ok_or_else(|| format!("Invalid configuration override {}{}", ENV_PREFIX, path))?;
 
-        self.logging = other.logging;
-        self.performance = other.performance;
-        self.data_generation = other.data_generation;
+            let mut table = root
+                .as_table_mut()
+                .ok_or("Configuration is not a table")?;
+            for segment in parents {
+                table = table
+                    .entry(segment.clone())
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .ok_or_else(|| format!("{}{} does not name a table", ENV_PREFIX, path))?;
+            }
+            let value = parse_env_value(&raw);
+            let typed = !value.is_str();
+            table.insert(field.clone(), value);
 
-        self
+            // Secrets such as `123456` parse as numbers; retry those as strings
+            if typed && root.clone().try_into::<Config>().is_err() {
+                set_env_value(&mut root, &segments, toml::Value::String(raw));
+            }
+        }
+
+        let config = root
+            .try_into()
+            .map_err(|e| format!("Invalid configuration override: {}", e))?;
+        Ok(config)
     }
 
     /// Validate configuration values
@@ -148,9 +1509,302 @@ impl Config {
             return Err("Volatility must be between 0.0 and 1.0".to_string());
         }
 
+        if EnvFilter::try_new(&self.logging.level).is_err() {
+            return Err(format!("Invalid log level: {}", self.logging.level));
+        }
+
+        if self.performance.websocket_heartbeat_interval == 0 {
+            return Err("WebSocket heartbeat interval must be greater than 0".to_string());
+        }
+
+        if self.performance.client_timeout <= self.performance.websocket_heartbeat_interval {
+            return Err("Client timeout must be greater than the WebSocket heartbeat interval".to_string());
+        }
+
+        if self.data_generation.interval_ms == 0 {
+            return Err("Data generation interval must be greater than 0".to_string());
+        }
+
         if self.data_generation.volume_range.0 >= self.data_generation.volume_range.1 {
             return Err("Volume range minimum must be less than maximum".to_string());
         }
+        self.data_generation.volume.validate("data_generation")?;
+
+        if self.performance.kline_retention_hours > 0
+            && self.data_generation.backfill_hours > self.performance.kline_retention_hours
+        {
+            return Err("Backfill hours must not exceed the K-line retention".to_string());
+        }
+
+        if self.data_generation.data_source == DataSource::Replay {
+            if self.data_generation.replay.path.is_none() {
+                return Err("Replay data source requires data_generation.replay.path".to_string());
+            }
+            let speed = self.data_generation.replay.speed;
+            if !speed.is_finite() || speed < replay::MIN_REPLAY_SPEED {
+                return Err(format!("Replay speed must be at least {}", replay::MIN_REPLAY_SPEED));
+            }
+        }
+
+        if self.data_generation.data_source == DataSource::Scenario && self.data_generation.scenario.path.is_none() {
+            return Err("Scenario data source requires data_generation.scenario.path".to_string());
+        }
+
+        if self.data_generation.regimes.step_secs == 0 {
+            return Err("Regime step must be greater than 0".to_string());
+        }
+        for regime in MarketRegime::ALL {
+            self.data_generation.regimes.params(regime).validate(regime)?;
+        }
+
+        if self.replication.role == ReplicationRole::Standby
+            && self.replication.primary_url.is_none()
+        {
+            return Err("Standby role requires replication.primary_url".to_string());
+        }
+
+        if let Some(synthetic) = &self.tokens.synthetic_tokens {
+            let (min_price, max_price) = synthetic.price_range;
+            if synthetic.count == 0 || synthetic.prefix.is_empty() {
+                return Err("Synthetic tokens require a count and a prefix".to_string());
+            }
+            if min_price <= 0.0 || min_price > max_price {
+                return Err("Synthetic token price range must be positive and ordered".to_string());
+            }
+        }
+
+        for token in &self.tokens.supported_tokens {
+            token.symbol.parse::<Symbol>()?;
+            symbols::validate_token(token)?;
+            if token
+                .arrival_rate
+                .is_some_and(|rate| !rate.is_finite() || rate <= 0.0)
+            {
+                return Err(format!("arrival_rate of {} must be positive", token.symbol));
+            }
+            if let Some(volume) = &token.volume {
+                volume.validate(&token.symbol)?;
+            }
+        }
+
+        if self.tokens.validation == TokenValidation::Strict
+            && self.tokens.unknown_token_policy == UnknownTokenPolicy::AutoRegister
+        {
+            return Err("Strict token validation requires unknown_token_policy reject or quarantine".to_string());
+        }
+
+        if self.server.tls.enabled
+            && (self.server.tls.cert_path.is_none() || self.server.tls.key_path.is_none())
+        {
+            return Err("TLS requires server.tls.cert_path and server.tls.key_path".to_string());
+        }
+        self.server.cors.validate()?;
+
+        if self.grpc.enabled && self.grpc.port == self.server.port {
+            return Err("gRPC port must differ from the HTTP server port".to_string());
+        }
+
+        if self.replication.failover_threshold == 0 {
+            return Err("Failover threshold must be greater than 0".to_string());
+        }
+
+        if self.performance.websocket_session_backlog == 0 {
+            return Err("WebSocket session backlog must be greater than 0".to_string());
+        }
+
+        if self.performance.websocket_max_subscriptions == 0 {
+            return Err("WebSocket max subscriptions must be greater than 0".to_string());
+        }
+
+        if self.performance.websocket_agg_trade_window_ms == 0 {
+            return Err("Aggregate trade window must be greater than 0".to_string());
+        }
+
+        if self.order_book.levels == 0 {
+            return Err("Order book levels must be greater than 0".to_string());
+        }
+
+        if self.performance.trade_history_size == 0 {
+            return Err("Trade history size must be greater than 0".to_string());
+        }
+
+        if self.performance.ingest_workers == 0 || self.performance.ingest_queue_capacity == 0 {
+            return Err("Ingest workers and queue capacity must be greater than 0".to_string());
+        }
+
+        if self.performance.kline_query_cache_ttl_ms > 0 && self.performance.kline_query_cache_capacity == 0 {
+            return Err("K-line query cache capacity must be greater than 0".to_string());
+        }
+
+        if let Some(interval) = self
+            .performance
+            .kline_activity_intervals
+            .iter()
+            .find(|interval| interval.is_time_based())
+        {
+            return Err(format!(
+                "K-line activity interval {} must be a trade-count (\"100t\") or volume (\"5000v\") interval",
+                interval
+            ));
+        }
+
+        if self.rate_limit.enabled
+            && (self.rate_limit.requests_per_second <= 0.0
+                || self.rate_limit.websocket_messages_per_second <= 0.0
+                || self.rate_limit.burst == 0
+                || self.rate_limit.websocket_burst == 0)
+        {
+            return Err("Rate limits and bursts must be greater than 0".to_string());
+        }
+
+        if self.health.max_ingest_age_secs == 0 {
+            return Err("Health max_ingest_age_secs must be greater than 0".to_string());
+        }
+
+        if self.telemetry.enabled {
+            if !self.telemetry.endpoint.starts_with("http://") && !self.telemetry.endpoint.starts_with("https://") {
+                return Err(format!("Invalid telemetry endpoint: {}", self.telemetry.endpoint));
+            }
+            if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+                return Err("Telemetry sample_ratio must be between 0 and 1".to_string());
+            }
+            if self.telemetry.metrics_interval_secs == 0 {
+                return Err("Telemetry metrics_interval_secs must be greater than 0".to_string());
+            }
+        }
+
+        if self.webhooks.initial_backoff_ms == 0 || self.webhooks.timeout_ms == 0 {
+            return Err("Webhook backoff and timeout must be greater than 0".to_string());
+        }
+
+        for target in &self.webhooks.targets {
+            if !target.url.starts_with("http://") && !target.url.starts_with("https://") {
+                return Err(format!("Invalid webhook URL: {}", target.url));
+            }
+            if let Some(interval) = target
+                .intervals
+                .iter()
+                .find(|interval| interval.parse::<TimeInterval>().is_err())
+            {
+                return Err(format!("Invalid webhook interval: {}", interval));
+            }
+        }
+
+        for rate in &self.conversion.rates {
+            rate.validate()?;
+        }
+
+        if self.fanout.enabled {
+            #[cfg(feature = "server")]
+            if self.fanout.redis_url.as_str().into_connection_info().is_err() {
+                return Err(format!("Invalid fanout Redis URL: {}", self.fanout.redis_url));
+            }
+            if self.fanout.channel.is_empty() || self.fanout.queue_capacity == 0 {
+                return Err("Fanout channel must not be empty and queue_capacity must be greater than 0".to_string());
+            }
+        }
+
+        if self.wal.enabled {
+            if self.wal.dir.is_empty() || self.wal.segment_max_bytes == 0 {
+                return Err("WAL dir must not be empty and segment_max_bytes must be greater than 0".to_string());
+            }
+            if self.wal.fsync == FsyncPolicy::Interval && self.wal.fsync_interval_ms == 0 {
+                return Err("WAL fsync_interval_ms must be greater than 0 with the interval policy".to_string());
+            }
+        }
+
+        if self.archive.enabled {
+            #[cfg(feature = "server")]
+            ArchiveLocation::parse(&self.archive.url).map_err(|e| e.to_string())?;
+            if self.performance.kline_retention_hours == 0 {
+                return Err("Archiving needs performance.kline_retention_hours greater than 0".to_string());
+            }
+        }
+
+        if self.clickhouse.enabled {
+            let clickhouse = &self.clickhouse;
+            if !clickhouse.url.starts_with("http://") && !clickhouse.url.starts_with("https://") {
+                return Err(format!("Invalid ClickHouse URL: {}", clickhouse.url));
+            }
+            let names = [&clickhouse.database, &clickhouse.trades_table, &clickhouse.klines_table];
+            if let Some(name) = names
+                .iter()
+                .find(|name| name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+            {
+                return Err(format!("Invalid ClickHouse database or table name: {:?}", name));
+            }
+            if clickhouse.batch_size == 0
+                || clickhouse.flush_interval_ms == 0
+                || clickhouse.queue_capacity == 0
+                || clickhouse.max_attempts == 0
+            {
+                return Err(
+                    "ClickHouse batch_size, flush_interval_ms, queue_capacity and max_attempts must be greater than 0"
+                        .to_string(),
+                );
+            }
+            if clickhouse.dead_letter_path.is_empty() {
+                return Err("ClickHouse dead_letter_path must not be empty".to_string());
+            }
+        }
+
+        if self.influx.enabled {
+            #[cfg(feature = "server")]
+            InfluxEndpoint::parse(&self.influx.endpoint)?;
+            if self.influx.kline_measurement.is_empty() || self.influx.ticker_measurement.is_empty() {
+                return Err("Influx measurement names must not be empty".to_string());
+            }
+            if self.influx.batch_size == 0 || self.influx.flush_interval_ms == 0 || self.influx.queue_capacity == 0 {
+                return Err("Influx batch_size, flush_interval_ms and queue_capacity must be greater than 0".to_string());
+            }
+        }
+
+        if self.storage.backend == StorageBackend::Redis {
+            #[cfg(feature = "server")]
+            if self.storage.redis_url.as_str().into_connection_info().is_err() {
+                return Err(format!("Invalid storage Redis URL: {}", self.storage.redis_url));
+            }
+            if self.storage.key_prefix.is_empty() || self.storage.sync_interval_ms == 0 || self.storage.queue_capacity == 0 {
+                return Err(
+                    "Storage key_prefix must not be empty and sync_interval_ms and queue_capacity must be greater than 0"
+                        .to_string(),
+                );
+            }
+        } else if self.storage.role == StorageRole::Follower {
+            return Err("Storage followers need the redis backend".to_string());
+        }
+
+        if self.index.enabled {
+            if self.index.weights.values().any(|weight| !weight.is_finite() || *weight < 0.0)
+                || self.index.weights.values().sum::<f64>() <= 0.0
+            {
+                return Err("Index weights must not be negative and at least one must be positive".to_string());
+            }
+            if self.index.stale_after_ms == 0 {
+                return Err("Index stale_after_ms must be greater than 0".to_string());
+            }
+        }
+
+        if self.auth.keys.iter().any(|key| key.key.is_empty()) {
+            return Err("API keys must not be empty".to_string());
+        }
+
+        if self.auth.jwt_secret.as_ref().is_some_and(|secret| secret.is_empty()) {
+            return Err("JWT secret must not be empty".to_string());
+        }
+
+        if self.auth.websocket_auth_timeout == 0 {
+            return Err("WebSocket auth timeout must be greater than 0".to_string());
+        }
+
+        if self.auth.enabled
+            && !self.auth.allow_anonymous
+            && self.auth.keys.is_empty()
+            && self.auth.jwt_secret.is_none()
+            && self.admin.api_key.is_none()
+        {
+            return Err("Authentication is enabled but no API keys are configured".to_string());
+        }
 
         Ok(())
     }
@@ -173,6 +1827,47 @@ impl Config {
     }
 }
 
+/// Fields whose values are maps, so the segment after them is a key kept as written
+const ENV_MAP_FIELDS: [&str; 2] = ["weights", "transitions"];
+
+/// Split an override path into table keys, lowercasing struct field names only
+fn env_path_segments(path: &str) -> Vec<String> {
+    let mut in_map = false;
+    path.split("__")
+        .map(|segment| {
+            let key = if in_map {
+                segment.to_string()
+            } else {
+                segment.to_lowercase()
+            };
+            in_map = !in_map && ENV_MAP_FIELDS.contains(&key.as_str());
+            key
+        })
+        .collect()
+}
+
+/// Replace the value at an override path that has already been set
+fn set_env_value(root: &mut toml::Value, segments: &[String], value: toml::Value) {
+    let Some((field, parents)) = segments.split_last() else {
+        return;
+    };
+    let mut table = root.as_table_mut();
+    for segment in parents {
+        table = table.and_then(|table| table.get_mut(segment)).and_then(toml::Value::as_table_mut);
+    }
+    if let Some(table) = table {
+        table.insert(field.clone(), value);
+    }
+}
+
+/// Parse an override value as TOML, keeping it as a string otherwise
+fn parse_env_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -180,6 +1875,8 @@ impl Default for Config {
                 host: "127.0.0.1".to_string(),
                 port: 8080,
                 workers: None,
+                tls: TlsConfig::default(),
+                cors: CorsConfig::default(),
             },
             tokens: TokensConfig {
                 supported_tokens: vec![
@@ -187,22 +1884,43 @@ impl Default for Config {
                         symbol: "DOGE".to_string(),
                         base_price: 0.15,
                         volatility: 5.0,
+                        price_precision: None,
+                        quantity_precision: None,
+                        tick_size: None,
+                        arrival_rate: None,
+                        volume: None,
                     },
                     TokenConfig {
                         symbol: "SHIB".to_string(),
                         base_price: 0.00005,
                         volatility: 8.0,
+                        price_precision: None,
+                        quantity_precision: None,
+                        tick_size: None,
+                        arrival_rate: None,
+                        volume: None,
                     },
                     TokenConfig {
                         symbol: "PEPE".to_string(),
                         base_price: 0.000008,
                         volatility: 10.0,
+                        price_precision: None,
+                        quantity_precision: None,
+                        tick_size: None,
+                        arrival_rate: None,
+                        volume: None,
                     },
                 ],
+                synthetic_tokens: None,
+                unknown_token_policy: UnknownTokenPolicy::AutoRegister,
+                validation: TokenValidation::Permissive,
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 file_output: false,
+                format: LogFormat::Text,
+                file_path: default_log_file_path(),
+                rotation: LogRotation::Daily,
             },
             performance: PerformanceConfig {
                 worker_threads: 4,
@@ -210,13 +1928,56 @@ impl Default for Config {
                 client_timeout: 10,
                 kline_retention_hours: 24,
                 max_websocket_connections: 1000,
+                max_websocket_connections_per_ip: 0,
+                websocket_compression: false,
+                websocket_compression_threshold: default_compression_threshold(),
+                websocket_replay_buffer: default_replay_buffer(),
+                websocket_session_backlog: default_session_backlog(),
+                websocket_max_subscriptions: default_max_subscriptions(),
+                websocket_slow_consumer_policy: SlowConsumerPolicy::Conflate,
+                websocket_max_dropped_messages: default_max_dropped_messages(),
+                websocket_agg_trade_window_ms: default_agg_trade_window_ms(),
+                websocket_server_time_interval_ms: 0,
+                trade_history_size: default_trade_history_size(),
+                kline_activity_intervals: Vec::new(),
+                ingest_workers: default_ingest_workers(),
+                ingest_queue_capacity: default_ingest_queue_capacity(),
+                sharded_processing: false,
+                kline_query_cache_ttl_ms: default_query_cache_ttl_ms(),
+                kline_query_cache_capacity: default_query_cache_capacity(),
+                trade_dedup_window: default_trade_dedup_window(),
             },
             data_generation: DataGenerationConfig {
                 enabled: true,
+                data_source: DataSource::Mock,
+                replay: ReplayConfig::default(),
+                scenario: ScenarioConfig::default(),
                 interval_ms: 100,
                 volatility: 0.02,
                 volume_range: (100.0, 1000.0),
+                arrival: ArrivalProcess::Fixed,
+                volume: VolumeConfig::default(),
+                backfill_hours: 0,
+                regimes: RegimeConfig::default(),
             },
+            replication: ReplicationConfig::default(),
+            grpc: GrpcConfig::default(),
+            admin: AdminConfig::default(),
+            auth: AuthConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            order_book: OrderBookConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            health: HealthConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            conversion: ConversionConfig::default(),
+            index: IndexConfig::default(),
+            fanout: FanoutConfig::default(),
+            storage: StorageConfig::default(),
+            wal: WalConfig::default(),
+            archive: ArchiveConfig::default(),
+            clickhouse: ClickHouseConfig::default(),
+            influx: InfluxConfig::default(),
         }
     }
 }
@@ -244,6 +2005,197 @@ mod tests {
         let mut invalid_config = Config::default();
         invalid_config.server.port = 0;
         assert!(invalid_config.validate().is_err());
+
+        let mut cors_config = Config::default();
+        cors_config.server.cors.allowed_origins = vec!["https://app.example.com".to_string()];
+        cors_config.server.cors.allow_credentials = true;
+        assert!(cors_config.validate().is_ok());
+        cors_config.server.cors.allowed_origins.push("*".to_string());
+        assert!(cors_config.validate().is_err());
+        cors_config.server.cors.allowed_origins = vec!["app.example.com".to_string()];
+        assert!(cors_config.validate().is_err());
+        cors_config.server.cors.allowed_origins.clear();
+        cors_config.server.cors.allowed_methods.push("NOT A METHOD".to_string());
+        #[cfg(feature = "server")]
+        assert!(cors_config.validate().is_err());
+
+        let mut activity_config = Config::default();
+        activity_config.performance.kline_activity_intervals = vec![TimeInterval::Trades(100), TimeInterval::Volume(5000)];
+        assert!(activity_config.validate().is_ok());
+        activity_config.performance.kline_activity_intervals.push(TimeInterval::Minute1);
+        assert!(activity_config.validate().is_err());
+
+        let mut precision_config = Config::default();
+        precision_config.tokens.supported_tokens[0].price_precision = Some(4);
+        precision_config.tokens.supported_tokens[0].tick_size = Some(0.0005);
+        assert!(precision_config.validate().is_ok());
+        precision_config.tokens.supported_tokens[0].tick_size = Some(0.00005);
+        assert!(precision_config.validate().is_err());
+        precision_config.tokens.supported_tokens[0].tick_size = Some(0.0);
+        assert!(precision_config.validate().is_err());
+        precision_config.tokens.supported_tokens[0].tick_size = None;
+        precision_config.tokens.supported_tokens[0].quantity_precision = Some(16);
+        assert!(precision_config.validate().is_err());
+
+        let mut pair_config = Config::default();
+        pair_config.tokens.supported_tokens[0].symbol = "doge/usdt".to_string();
+        assert!(pair_config.validate().is_ok());
+        pair_config.tokens.canonicalize_symbols();
+        assert_eq!(pair_config.tokens.supported_tokens[0].symbol, "DOGE-USDT");
+        pair_config.tokens.supported_tokens[0].symbol = "DOGE-".to_string();
+        assert!(pair_config.validate().is_err());
+
+        let mut strict_config = Config::default();
+        strict_config.tokens.validation = TokenValidation::Strict;
+        assert!(strict_config.validate().is_err());
+        strict_config.tokens.unknown_token_policy = UnknownTokenPolicy::Quarantine;
+        assert!(strict_config.validate().is_ok());
+
+        let mut standby_config = Config::default();
+        standby_config.replication.role = ReplicationRole::Standby;
+        assert!(standby_config.validate().is_err());
+        standby_config.replication.primary_url = Some("http://primary:8080".to_string());
+        assert!(standby_config.validate().is_ok());
+
+        let mut replay_config = Config::default();
+        replay_config.data_generation.data_source = DataSource::Replay;
+        assert!(replay_config.validate().is_err());
+        replay_config.data_generation.replay.path = Some("incident.jsonl".to_string());
+        assert!(replay_config.validate().is_ok());
+        replay_config.data_generation.replay.speed = 0.0;
+        assert!(replay_config.validate().is_err());
+
+        let mut scenario_config = Config::default();
+        scenario_config.data_generation.data_source = DataSource::Scenario;
+        assert!(scenario_config.validate().is_err());
+        scenario_config.data_generation.scenario.path = Some("pump.toml".to_string());
+        assert!(scenario_config.validate().is_ok());
+
+        let mut logging_config = Config::default();
+        logging_config.logging.level = "k_line=debug,actix_web=warn".to_string();
+        assert!(logging_config.validate().is_ok());
+        logging_config.logging.level = "k_line=verbose".to_string();
+        assert!(logging_config.validate().is_err());
+
+        let mut heartbeat_config = Config::default();
+        heartbeat_config.performance.client_timeout = heartbeat_config.performance.websocket_heartbeat_interval;
+        assert!(heartbeat_config.validate().is_err());
+
+        let mut health_config = Config::default();
+        health_config.health.max_ingest_age_secs = 0;
+        assert!(health_config.validate().is_err());
+
+        let mut telemetry_config = Config::default();
+        telemetry_config.telemetry.sample_ratio = 2.0;
+        assert!(telemetry_config.validate().is_ok());
+        telemetry_config.telemetry.enabled = true;
+        assert!(telemetry_config.validate().is_err());
+        telemetry_config.telemetry.sample_ratio = 0.1;
+        assert!(telemetry_config.validate().is_ok());
+        telemetry_config.telemetry.endpoint = "collector:4317".to_string();
+        assert!(telemetry_config.validate().is_err());
+
+        let mut webhook_config = Config::default();
+        webhook_config.webhooks.targets.push(WebhookTargetConfig {
+            url: "https://example.com/hook".to_string(),
+            events: vec![WebhookEventType::KlineClosed],
+            tokens: Vec::new(),
+            intervals: vec!["1m".to_string()],
+        });
+        assert!(webhook_config.validate().is_ok());
+        webhook_config.webhooks.targets[0].intervals.push("2m".to_string());
+        assert!(webhook_config.validate().is_err());
+
+        let mut conversion_config = Config::default();
+        conversion_config.conversion.rates.push(ConversionRateConfig {
+            from: "USDT".to_string(),
+            to: "BTC".to_string(),
+            rate: None,
+            pair: Some("BTC-USDT".to_string()),
+        });
+        assert!(conversion_config.validate().is_ok());
+        conversion_config.conversion.rates[0].rate = Some(0.00001);
+        assert!(conversion_config.validate().is_err());
+        conversion_config.conversion.rates[0].pair = Some("ETH-USDT".to_string());
+        conversion_config.conversion.rates[0].rate = None;
+        assert!(conversion_config.validate().is_err());
+
+        let mut index_config = Config::default();
+        index_config.index.weights.insert("mock".to_string(), 0.0);
+        assert!(index_config.validate().is_ok());
+        index_config.index.enabled = true;
+        assert!(index_config.validate().is_err());
+        index_config.index.weights.insert("replay".to_string(), 2.0);
+        assert!(index_config.validate().is_ok());
+
+        let mut fanout_config = Config::default();
+        fanout_config.fanout.enabled = true;
+        assert!(fanout_config.validate().is_ok());
+        fanout_config.fanout.redis_url = "http://127.0.0.1:6379".to_string();
+        #[cfg(feature = "server")]
+        assert!(fanout_config.validate().is_err());
+
+        let mut storage_config = Config::default();
+        storage_config.storage.role = StorageRole::Follower;
+        assert!(storage_config.validate().is_err());
+        storage_config.storage.backend = StorageBackend::Redis;
+        assert!(storage_config.validate().is_ok());
+        storage_config.storage.sync_interval_ms = 0;
+        assert!(storage_config.validate().is_err());
+
+        let mut wal_config = Config::default();
+        wal_config.wal.enabled = true;
+        assert!(wal_config.validate().is_ok());
+        wal_config.wal.fsync_interval_ms = 0;
+        assert!(wal_config.validate().is_err());
+        wal_config.wal.fsync = FsyncPolicy::Always;
+        assert!(wal_config.validate().is_ok());
+
+        let mut archive_config = Config::default();
+        archive_config.archive.enabled = true;
+        archive_config.archive.url = "s3://k-line-archive/candles".to_string();
+        assert!(archive_config.validate().is_ok());
+        archive_config.archive.url = "gs://k-line-archive".to_string();
+        #[cfg(feature = "server")]
+        assert!(archive_config.validate().is_err());
+        archive_config.archive.url = "file:///var/lib/k-line/archive".to_string();
+        archive_config.performance.kline_retention_hours = 0;
+        assert!(archive_config.validate().is_err());
+
+        let mut clickhouse_config = Config::default();
+        clickhouse_config.clickhouse.enabled = true;
+        assert!(clickhouse_config.validate().is_ok());
+        clickhouse_config.clickhouse.trades_table = "trades; DROP TABLE klines".to_string();
+        assert!(clickhouse_config.validate().is_err());
+        clickhouse_config.clickhouse.trades_table = "trades".to_string();
+        clickhouse_config.clickhouse.batch_size = 0;
+        assert!(clickhouse_config.validate().is_err());
+
+        let mut influx_config = Config::default();
+        influx_config.influx.enabled = true;
+        assert!(influx_config.validate().is_ok());
+        influx_config.influx.endpoint = "udp://127.0.0.1:8094".to_string();
+        assert!(influx_config.validate().is_ok());
+        influx_config.influx.endpoint = "udp://telegraf".to_string();
+        #[cfg(feature = "server")]
+        assert!(influx_config.validate().is_err());
+        influx_config.influx.endpoint = "unix:///tmp/telegraf.sock".to_string();
+        influx_config.influx.kline_measurement = String::new();
+        assert!(influx_config.validate().is_err());
+
+        let mut auth_config = Config::default();
+        auth_config.auth.enabled = true;
+        auth_config.auth.jwt_secret = Some("secret".to_string());
+        assert!(auth_config.validate().is_ok());
+        auth_config.auth.websocket_auth_timeout = 0;
+        assert!(auth_config.validate().is_err());
+        auth_config.auth.websocket_auth_timeout = 10;
+        auth_config.auth.jwt_secret = Some(String::new());
+        assert!(auth_config.validate().is_err());
+
+        let mut subscriptions_config = Config::default();
+        subscriptions_config.performance.websocket_max_subscriptions = 0;
+        assert!(subscriptions_config.validate().is_err());
     }
 
     #[test]
@@ -258,4 +2210,103 @@ mod tests {
         assert!(doge_info.is_some());
         assert_eq!(doge_info.unwrap().base_price, 0.15);
     }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = [
+            ("KLINE__SERVER__PORT", "9090"),
+            ("KLINE__DATA_GENERATION__ENABLED", "false"),
+            ("KLINE__DATA_GENERATION__VOLUME_RANGE", "[10.0, 20.0]"),
+            ("KLINE__REPLICATION__PRIMARY_URL", "http://primary:8080"),
+            ("KLINE__ADMIN__API_KEY", "secret"),
+            ("OTHER__SERVER__PORT", "1"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let config = Config::default().apply_env_overrides(vars).unwrap();
+        assert_eq!(config.server.port, 9090);
+        assert!(!config.data_generation.enabled);
+        assert_eq!(config.data_generation.volume_range, (10.0, 20.0));
+        assert_eq!(config.replication.primary_url.as_deref(), Some("http://primary:8080"));
+        assert_eq!(config.admin.api_key.as_deref(), Some("secret"));
+
+        let invalid = [("KLINE__SERVER__PORT".to_string(), "not-a-port".to_string())];
+        assert!(Config::default().apply_env_overrides(invalid).is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_keep_strings_and_map_keys() {
+        let vars = [
+            ("KLINE__ADMIN__API_KEY", "123456"),
+            ("KLINE__REPLICATION__PRIMARY_URL", "true"),
+            ("KLINE__INDEX__WEIGHTS__Binance", "2.0"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let config = Config::default().apply_env_overrides(vars).unwrap();
+        assert_eq!(config.admin.api_key.as_deref(), Some("123456"));
+        assert_eq!(config.replication.primary_url.as_deref(), Some("true"));
+        assert_eq!(config.index.weights.get("Binance"), Some(&2.0));
+
+        // Numbers are still rejected where a string cannot stand in for them
+        let invalid = [("KLINE__SERVER__PORT".to_string(), "99999999".to_string())];
+        assert!(Config::default().apply_env_overrides(invalid).is_err());
+    }
+
+    #[test]
+    fn test_partial_config_merge() {
+        let partial: PartialConfig = toml::from_str("[server]\nport = 9090\n").unwrap();
+        let config = Config::default().merge_with(partial);
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.logging.level, "info");
+        assert_eq!(config.performance.websocket_session_backlog, 256);
+
+        let partial: PartialConfig = toml::from_str(
+            "[performance]\nwebsocket_compression = true\n[admin]\napi_key = \"secret\"\n",
+        )
+        .unwrap();
+        let config = Config::default().merge_with(partial);
+        assert!(config.performance.websocket_compression);
+        assert_eq!(config.performance.client_timeout, 10);
+        assert_eq!(config.admin.api_key.as_deref(), Some("secret"));
+
+        // The shipped files parse as partial configurations
+        for path in ["config/default.toml", "config/development.toml", "config/production.toml"] {
+            let partial = PartialConfig::load_from_file(path).unwrap();
+            assert!(Config::default().merge_with(partial).validate().is_ok(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_synthetic_tokens() {
+        let mut config = Config::default();
+        config.tokens.synthetic_tokens = Some(SyntheticTokensConfig {
+            count: 5000,
+            prefix: "TOK".to_string(),
+            price_range: (0.0001, 10.0),
+            volatility: 5.0,
+        });
+        assert!(config.validate().is_ok());
+
+        config.tokens.expand_synthetic_tokens();
+        let tokens = config.get_supported_tokens();
+        assert_eq!(tokens.len(), 5003);
+        assert_eq!(tokens[3], "TOK0001");
+        assert_eq!(tokens[5002], "TOK5000");
+
+        let first = config.get_token_info("TOK0001").unwrap();
+        let last = config.get_token_info("TOK5000").unwrap();
+        assert!((first.base_price - 0.0001).abs() < 1e-12);
+        assert!((last.base_price - 10.0).abs() < 1e-9);
+
+        let mut invalid_config = Config::default();
+        invalid_config.tokens.synthetic_tokens = Some(SyntheticTokensConfig {
+            count: 10,
+            prefix: "TOK".to_string(),
+            price_range: (1.0, 0.5),
+            volatility: 5.0,
+        });
+        assert!(invalid_config.validate().is_err());
+    }
 }
@@ -0,0 +1,702 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::models::TimeInterval;
+
+use super::{
+    AdminConfig, ApiKeyConfig, ArchiveConfig, ArrivalProcess, AuthConfig, ClickHouseConfig, Config, ConversionConfig, ConversionRateConfig, CorsConfig, DataGenerationConfig, DataSource, FanoutConfig, FsyncPolicy,
+    GrpcConfig, HealthConfig, IndexConfig, InfluxConfig, LogFormat, LogRotation, LoggingConfig, OrderBookConfig, PerformanceConfig, RegimeConfig,
+    RateLimitConfig, ReplicationConfig, ReplayConfig, ReplicationRole, ScenarioConfig, ServerConfig, SlowConsumerPolicy,
+    SnapshotConfig, StorageBackend, StorageConfig, StorageRole, SyntheticTokensConfig, TelemetryConfig, TlsConfig, TokenConfig, TokenValidation, TokensConfig,
+    UnknownTokenPolicy, VolumeConfig, WalConfig, WebhookTargetConfig, WebhooksConfig,
+};
+
+/// Configuration file in which every section and field is optional
+///
+/// Only the fields present in the file override the configuration it is
+/// merged into, so environment files can be as small as a single setting.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialConfig {
+    pub server: Option<PartialServerConfig>,
+    pub tokens: Option<PartialTokensConfig>,
+    pub logging: Option<PartialLoggingConfig>,
+    pub performance: Option<PartialPerformanceConfig>,
+    pub data_generation: Option<PartialDataGenerationConfig>,
+    pub replication: Option<PartialReplicationConfig>,
+    pub grpc: Option<PartialGrpcConfig>,
+    pub admin: Option<PartialAdminConfig>,
+    pub auth: Option<PartialAuthConfig>,
+    pub rate_limit: Option<PartialRateLimitConfig>,
+    pub webhooks: Option<PartialWebhooksConfig>,
+    pub order_book: Option<PartialOrderBookConfig>,
+    pub snapshot: Option<PartialSnapshotConfig>,
+    pub health: Option<PartialHealthConfig>,
+    pub telemetry: Option<PartialTelemetryConfig>,
+    pub conversion: Option<PartialConversionConfig>,
+    pub index: Option<PartialIndexConfig>,
+    pub fanout: Option<PartialFanoutConfig>,
+    pub storage: Option<PartialStorageConfig>,
+    pub wal: Option<PartialWalConfig>,
+    pub archive: Option<PartialArchiveConfig>,
+    pub clickhouse: Option<PartialClickHouseConfig>,
+    pub influx: Option<PartialInfluxConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialServerConfig {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub tls: Option<PartialTlsConfig>,
+    pub cors: Option<PartialCorsConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialTlsConfig {
+    pub enabled: Option<bool>,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialCorsConfig {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub allow_credentials: Option<bool>,
+    pub max_age_secs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialTokensConfig {
+    pub supported_tokens: Option<Vec<TokenConfig>>,
+    pub synthetic_tokens: Option<SyntheticTokensConfig>,
+    pub unknown_token_policy: Option<UnknownTokenPolicy>,
+    pub validation: Option<TokenValidation>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialLoggingConfig {
+    pub level: Option<String>,
+    pub file_output: Option<bool>,
+    pub format: Option<LogFormat>,
+    pub file_path: Option<String>,
+    pub rotation: Option<LogRotation>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialPerformanceConfig {
+    pub worker_threads: Option<usize>,
+    pub websocket_heartbeat_interval: Option<u64>,
+    pub client_timeout: Option<u64>,
+    pub kline_retention_hours: Option<u64>,
+    pub max_websocket_connections: Option<usize>,
+    pub max_websocket_connections_per_ip: Option<usize>,
+    pub websocket_compression: Option<bool>,
+    pub websocket_compression_threshold: Option<usize>,
+    pub websocket_replay_buffer: Option<usize>,
+    pub websocket_session_backlog: Option<usize>,
+    pub websocket_max_subscriptions: Option<usize>,
+    pub websocket_slow_consumer_policy: Option<SlowConsumerPolicy>,
+    pub websocket_max_dropped_messages: Option<u64>,
+    pub websocket_agg_trade_window_ms: Option<u64>,
+    pub websocket_server_time_interval_ms: Option<u64>,
+    pub trade_history_size: Option<usize>,
+    pub kline_activity_intervals: Option<Vec<TimeInterval>>,
+    pub ingest_workers: Option<usize>,
+    pub ingest_queue_capacity: Option<usize>,
+    pub sharded_processing: Option<bool>,
+    pub kline_query_cache_ttl_ms: Option<u64>,
+    pub kline_query_cache_capacity: Option<usize>,
+    pub trade_dedup_window: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialDataGenerationConfig {
+    pub enabled: Option<bool>,
+    pub data_source: Option<DataSource>,
+    pub replay: Option<ReplayConfig>,
+    pub scenario: Option<ScenarioConfig>,
+    pub interval_ms: Option<u64>,
+    pub volatility: Option<f64>,
+    pub volume_range: Option<(f64, f64)>,
+    pub arrival: Option<ArrivalProcess>,
+    pub volume: Option<VolumeConfig>,
+    pub backfill_hours: Option<u64>,
+    pub regimes: Option<RegimeConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialReplicationConfig {
+    pub role: Option<ReplicationRole>,
+    pub primary_url: Option<String>,
+    pub health_check_interval_ms: Option<u64>,
+    pub failover_threshold: Option<u32>,
+    pub auto_failover: Option<bool>,
+    pub checksum_interval_secs: Option<u64>,
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialGrpcConfig {
+    pub enabled: Option<bool>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialOrderBookConfig {
+    pub enabled: Option<bool>,
+    pub levels: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialSnapshotConfig {
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialHealthConfig {
+    pub max_ingest_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialTelemetryConfig {
+    pub enabled: Option<bool>,
+    pub endpoint: Option<String>,
+    pub service_name: Option<String>,
+    pub sample_ratio: Option<f64>,
+    pub metrics_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialConversionConfig {
+    pub rates: Option<Vec<ConversionRateConfig>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialIndexConfig {
+    pub enabled: Option<bool>,
+    pub weights: Option<BTreeMap<String, f64>>,
+    pub stale_after_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialFanoutConfig {
+    pub enabled: Option<bool>,
+    pub redis_url: Option<String>,
+    pub channel: Option<String>,
+    pub publish: Option<bool>,
+    pub subscribe: Option<bool>,
+    pub queue_capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialStorageConfig {
+    pub backend: Option<StorageBackend>,
+    pub role: Option<StorageRole>,
+    pub redis_url: Option<String>,
+    pub key_prefix: Option<String>,
+    pub sync_interval_ms: Option<u64>,
+    pub queue_capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialWalConfig {
+    pub enabled: Option<bool>,
+    pub dir: Option<String>,
+    pub segment_max_bytes: Option<u64>,
+    pub fsync: Option<FsyncPolicy>,
+    pub fsync_interval_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialArchiveConfig {
+    pub enabled: Option<bool>,
+    pub url: Option<String>,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub allow_http: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialClickHouseConfig {
+    pub enabled: Option<bool>,
+    pub url: Option<String>,
+    pub database: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub trades_table: Option<String>,
+    pub klines_table: Option<String>,
+    pub batch_size: Option<usize>,
+    pub flush_interval_ms: Option<u64>,
+    pub queue_capacity: Option<usize>,
+    pub timeout_ms: Option<u64>,
+    pub max_attempts: Option<u32>,
+    pub dead_letter_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialInfluxConfig {
+    pub enabled: Option<bool>,
+    pub endpoint: Option<String>,
+    pub token: Option<String>,
+    pub kline_measurement: Option<String>,
+    pub ticker_measurement: Option<String>,
+    pub ticker_interval_secs: Option<u64>,
+    pub batch_size: Option<usize>,
+    pub flush_interval_ms: Option<u64>,
+    pub queue_capacity: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialAdminConfig {
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialAuthConfig {
+    pub enabled: Option<bool>,
+    pub allow_anonymous: Option<bool>,
+    pub keys_file: Option<String>,
+    pub keys: Option<Vec<ApiKeyConfig>>,
+    pub jwt_secret: Option<String>,
+    pub websocket_auth_timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialRateLimitConfig {
+    pub enabled: Option<bool>,
+    pub requests_per_second: Option<f64>,
+    pub burst: Option<u32>,
+    pub websocket_messages_per_second: Option<f64>,
+    pub websocket_burst: Option<u32>,
+    pub websocket_max_violations: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialWebhooksConfig {
+    pub secret: Option<String>,
+    pub max_retries: Option<u32>,
+    pub initial_backoff_ms: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    pub targets: Option<Vec<WebhookTargetConfig>>,
+}
+
+/// Overwrite `target` with `value` when the field was set
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// Overwrite an optional `target` when the field was set
+fn set_some<T>(target: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *target = value;
+    }
+}
+
+impl PartialConfig {
+    /// Load a partial configuration from a TOML file
+    pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let config: PartialConfig = toml::from_str(&content)?;
+        Ok(config)
+    }
+
+    /// Apply the fields that were set to a full configuration
+    pub fn apply(self, config: &mut Config) {
+        if let Some(server) = self.server {
+            server.apply(&mut config.server);
+        }
+        if let Some(tokens) = self.tokens {
+            tokens.apply(&mut config.tokens);
+        }
+        if let Some(logging) = self.logging {
+            logging.apply(&mut config.logging);
+        }
+        if let Some(performance) = self.performance {
+            performance.apply(&mut config.performance);
+        }
+        if let Some(data_generation) = self.data_generation {
+            data_generation.apply(&mut config.data_generation);
+        }
+        if let Some(replication) = self.replication {
+            replication.apply(&mut config.replication);
+        }
+        if let Some(grpc) = self.grpc {
+            grpc.apply(&mut config.grpc);
+        }
+        if let Some(admin) = self.admin {
+            admin.apply(&mut config.admin);
+        }
+        if let Some(auth) = self.auth {
+            auth.apply(&mut config.auth);
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            rate_limit.apply(&mut config.rate_limit);
+        }
+        if let Some(webhooks) = self.webhooks {
+            webhooks.apply(&mut config.webhooks);
+        }
+        if let Some(order_book) = self.order_book {
+            order_book.apply(&mut config.order_book);
+        }
+        if let Some(snapshot) = self.snapshot {
+            snapshot.apply(&mut config.snapshot);
+        }
+        if let Some(health) = self.health {
+            health.apply(&mut config.health);
+        }
+        if let Some(telemetry) = self.telemetry {
+            telemetry.apply(&mut config.telemetry);
+        }
+        if let Some(conversion) = self.conversion {
+            conversion.apply(&mut config.conversion);
+        }
+        if let Some(index) = self.index {
+            index.apply(&mut config.index);
+        }
+        if let Some(fanout) = self.fanout {
+            fanout.apply(&mut config.fanout);
+        }
+        if let Some(storage) = self.storage {
+            storage.apply(&mut config.storage);
+        }
+        if let Some(wal) = self.wal {
+            wal.apply(&mut config.wal);
+        }
+        if let Some(archive) = self.archive {
+            archive.apply(&mut config.archive);
+        }
+        if let Some(clickhouse) = self.clickhouse {
+            clickhouse.apply(&mut config.clickhouse);
+        }
+        if let Some(influx) = self.influx {
+            influx.apply(&mut config.influx);
+        }
+    }
+}
+
+impl PartialServerConfig {
+    fn apply(self, server: &mut ServerConfig) {
+        set(&mut server.host, self.host);
+        set(&mut server.port, self.port);
+        set_some(&mut server.workers, self.workers);
+        if let Some(tls) = self.tls {
+            tls.apply(&mut server.tls);
+        }
+        if let Some(cors) = self.cors {
+            cors.apply(&mut server.cors);
+        }
+    }
+}
+
+impl PartialTlsConfig {
+    fn apply(self, tls: &mut TlsConfig) {
+        set(&mut tls.enabled, self.enabled);
+        set_some(&mut tls.cert_path, self.cert_path);
+        set_some(&mut tls.key_path, self.key_path);
+    }
+}
+
+impl PartialCorsConfig {
+    fn apply(self, cors: &mut CorsConfig) {
+        set(&mut cors.allowed_origins, self.allowed_origins);
+        set(&mut cors.allowed_methods, self.allowed_methods);
+        set(&mut cors.allowed_headers, self.allowed_headers);
+        set(&mut cors.allow_credentials, self.allow_credentials);
+        set(&mut cors.max_age_secs, self.max_age_secs);
+    }
+}
+
+impl PartialTokensConfig {
+    fn apply(self, tokens: &mut TokensConfig) {
+        set(&mut tokens.supported_tokens, self.supported_tokens);
+        set_some(&mut tokens.synthetic_tokens, self.synthetic_tokens);
+        set(&mut tokens.unknown_token_policy, self.unknown_token_policy);
+        set(&mut tokens.validation, self.validation);
+    }
+}
+
+impl PartialLoggingConfig {
+    fn apply(self, logging: &mut LoggingConfig) {
+        set(&mut logging.level, self.level);
+        set(&mut logging.file_output, self.file_output);
+        set(&mut logging.format, self.format);
+        set(&mut logging.file_path, self.file_path);
+        set(&mut logging.rotation, self.rotation);
+    }
+}
+
+impl PartialPerformanceConfig {
+    fn apply(self, performance: &mut PerformanceConfig) {
+        set(&mut performance.worker_threads, self.worker_threads);
+        set(&mut performance.websocket_heartbeat_interval, self.websocket_heartbeat_interval);
+        set(&mut performance.client_timeout, self.client_timeout);
+        set(&mut performance.kline_retention_hours, self.kline_retention_hours);
+        set(&mut performance.max_websocket_connections, self.max_websocket_connections);
+        set(
+            &mut performance.max_websocket_connections_per_ip,
+            self.max_websocket_connections_per_ip,
+        );
+        set(&mut performance.websocket_compression, self.websocket_compression);
+        set(
+            &mut performance.websocket_compression_threshold,
+            self.websocket_compression_threshold,
+        );
+        set(&mut performance.websocket_replay_buffer, self.websocket_replay_buffer);
+        set(&mut performance.websocket_session_backlog, self.websocket_session_backlog);
+        set(&mut performance.websocket_max_subscriptions, self.websocket_max_subscriptions);
+        set(
+            &mut performance.websocket_slow_consumer_policy,
+            self.websocket_slow_consumer_policy,
+        );
+        set(
+            &mut performance.websocket_max_dropped_messages,
+            self.websocket_max_dropped_messages,
+        );
+        set(
+            &mut performance.websocket_agg_trade_window_ms,
+            self.websocket_agg_trade_window_ms,
+        );
+        set(
+            &mut performance.websocket_server_time_interval_ms,
+            self.websocket_server_time_interval_ms,
+        );
+        set(&mut performance.trade_history_size, self.trade_history_size);
+        set(&mut performance.kline_activity_intervals, self.kline_activity_intervals);
+        set(&mut performance.ingest_workers, self.ingest_workers);
+        set(&mut performance.ingest_queue_capacity, self.ingest_queue_capacity);
+        set(&mut performance.sharded_processing, self.sharded_processing);
+        set(&mut performance.kline_query_cache_ttl_ms, self.kline_query_cache_ttl_ms);
+        set(&mut performance.kline_query_cache_capacity, self.kline_query_cache_capacity);
+        set(&mut performance.trade_dedup_window, self.trade_dedup_window);
+    }
+}
+
+impl PartialDataGenerationConfig {
+    fn apply(self, data_generation: &mut DataGenerationConfig) {
+        set(&mut data_generation.enabled, self.enabled);
+        set(&mut data_generation.data_source, self.data_source);
+        set(&mut data_generation.replay, self.replay);
+        set(&mut data_generation.scenario, self.scenario);
+        set(&mut data_generation.interval_ms, self.interval_ms);
+        set(&mut data_generation.volatility, self.volatility);
+        set(&mut data_generation.volume_range, self.volume_range);
+        set(&mut data_generation.arrival, self.arrival);
+        set(&mut data_generation.volume, self.volume);
+        set(&mut data_generation.backfill_hours, self.backfill_hours);
+        set(&mut data_generation.regimes, self.regimes);
+    }
+}
+
+impl PartialReplicationConfig {
+    fn apply(self, replication: &mut ReplicationConfig) {
+        set(&mut replication.role, self.role);
+        set_some(&mut replication.primary_url, self.primary_url);
+        set(&mut replication.health_check_interval_ms, self.health_check_interval_ms);
+        set(&mut replication.failover_threshold, self.failover_threshold);
+        set(&mut replication.auto_failover, self.auto_failover);
+        set(&mut replication.checksum_interval_secs, self.checksum_interval_secs);
+        set_some(&mut replication.api_key, self.api_key);
+    }
+}
+
+impl PartialGrpcConfig {
+    fn apply(self, grpc: &mut GrpcConfig) {
+        set(&mut grpc.enabled, self.enabled);
+        set(&mut grpc.port, self.port);
+    }
+}
+
+impl PartialOrderBookConfig {
+    fn apply(self, order_book: &mut OrderBookConfig) {
+        set(&mut order_book.enabled, self.enabled);
+        set(&mut order_book.levels, self.levels);
+    }
+}
+
+impl PartialSnapshotConfig {
+    fn apply(self, snapshot: &mut SnapshotConfig) {
+        set_some(&mut snapshot.path, self.path);
+    }
+}
+
+impl PartialHealthConfig {
+    fn apply(self, health: &mut HealthConfig) {
+        set(&mut health.max_ingest_age_secs, self.max_ingest_age_secs);
+    }
+}
+
+impl PartialTelemetryConfig {
+    fn apply(self, telemetry: &mut TelemetryConfig) {
+        set(&mut telemetry.enabled, self.enabled);
+        set(&mut telemetry.endpoint, self.endpoint);
+        set(&mut telemetry.service_name, self.service_name);
+        set(&mut telemetry.sample_ratio, self.sample_ratio);
+        set(&mut telemetry.metrics_interval_secs, self.metrics_interval_secs);
+    }
+}
+
+impl PartialConversionConfig {
+    fn apply(self, conversion: &mut ConversionConfig) {
+        set(&mut conversion.rates, self.rates);
+    }
+}
+
+impl PartialIndexConfig {
+    fn apply(self, index: &mut IndexConfig) {
+        set(&mut index.enabled, self.enabled);
+        set(&mut index.weights, self.weights);
+        set(&mut index.stale_after_ms, self.stale_after_ms);
+    }
+}
+
+impl PartialFanoutConfig {
+    fn apply(self, fanout: &mut FanoutConfig) {
+        set(&mut fanout.enabled, self.enabled);
+        set(&mut fanout.redis_url, self.redis_url);
+        set(&mut fanout.channel, self.channel);
+        set(&mut fanout.publish, self.publish);
+        set(&mut fanout.subscribe, self.subscribe);
+        set(&mut fanout.queue_capacity, self.queue_capacity);
+    }
+}
+
+impl PartialStorageConfig {
+    fn apply(self, storage: &mut StorageConfig) {
+        set(&mut storage.backend, self.backend);
+        set(&mut storage.role, self.role);
+        set(&mut storage.redis_url, self.redis_url);
+        set(&mut storage.key_prefix, self.key_prefix);
+        set(&mut storage.sync_interval_ms, self.sync_interval_ms);
+        set(&mut storage.queue_capacity, self.queue_capacity);
+    }
+}
+
+impl PartialWalConfig {
+    fn apply(self, wal: &mut WalConfig) {
+        set(&mut wal.enabled, self.enabled);
+        set(&mut wal.dir, self.dir);
+        set(&mut wal.segment_max_bytes, self.segment_max_bytes);
+        set(&mut wal.fsync, self.fsync);
+        set(&mut wal.fsync_interval_ms, self.fsync_interval_ms);
+    }
+}
+
+impl PartialArchiveConfig {
+    fn apply(self, archive: &mut ArchiveConfig) {
+        set(&mut archive.enabled, self.enabled);
+        set(&mut archive.url, self.url);
+        set_some(&mut archive.endpoint, self.endpoint);
+        set_some(&mut archive.region, self.region);
+        set_some(&mut archive.access_key_id, self.access_key_id);
+        set_some(&mut archive.secret_access_key, self.secret_access_key);
+        set(&mut archive.allow_http, self.allow_http);
+    }
+}
+
+impl PartialClickHouseConfig {
+    fn apply(self, clickhouse: &mut ClickHouseConfig) {
+        set(&mut clickhouse.enabled, self.enabled);
+        set(&mut clickhouse.url, self.url);
+        set(&mut clickhouse.database, self.database);
+        set_some(&mut clickhouse.user, self.user);
+        set_some(&mut clickhouse.password, self.password);
+        set(&mut clickhouse.trades_table, self.trades_table);
+        set(&mut clickhouse.klines_table, self.klines_table);
+        set(&mut clickhouse.batch_size, self.batch_size);
+        set(&mut clickhouse.flush_interval_ms, self.flush_interval_ms);
+        set(&mut clickhouse.queue_capacity, self.queue_capacity);
+        set(&mut clickhouse.timeout_ms, self.timeout_ms);
+        set(&mut clickhouse.max_attempts, self.max_attempts);
+        set(&mut clickhouse.dead_letter_path, self.dead_letter_path);
+    }
+}
+
+impl PartialInfluxConfig {
+    fn apply(self, influx: &mut InfluxConfig) {
+        set(&mut influx.enabled, self.enabled);
+        set(&mut influx.endpoint, self.endpoint);
+        set_some(&mut influx.token, self.token);
+        set(&mut influx.kline_measurement, self.kline_measurement);
+        set(&mut influx.ticker_measurement, self.ticker_measurement);
+        set(&mut influx.ticker_interval_secs, self.ticker_interval_secs);
+        set(&mut influx.batch_size, self.batch_size);
+        set(&mut influx.flush_interval_ms, self.flush_interval_ms);
+        set(&mut influx.queue_capacity, self.queue_capacity);
+        set(&mut influx.timeout_ms, self.timeout_ms);
+    }
+}
+
+impl PartialAdminConfig {
+    fn apply(self, admin: &mut AdminConfig) {
+        set_some(&mut admin.api_key, self.api_key);
+    }
+}
+
+impl PartialAuthConfig {
+    fn apply(self, auth: &mut AuthConfig) {
+        set(&mut auth.enabled, self.enabled);
+        set(&mut auth.allow_anonymous, self.allow_anonymous);
+        set_some(&mut auth.keys_file, self.keys_file);
+        set(&mut auth.keys, self.keys);
+        set_some(&mut auth.jwt_secret, self.jwt_secret);
+        set(&mut auth.websocket_auth_timeout, self.websocket_auth_timeout);
+    }
+}
+
+impl PartialRateLimitConfig {
+    fn apply(self, rate_limit: &mut RateLimitConfig) {
+        set(&mut rate_limit.enabled, self.enabled);
+        set(&mut rate_limit.requests_per_second, self.requests_per_second);
+        set(&mut rate_limit.burst, self.burst);
+        set(
+            &mut rate_limit.websocket_messages_per_second,
+            self.websocket_messages_per_second,
+        );
+        set(&mut rate_limit.websocket_burst, self.websocket_burst);
+        set(&mut rate_limit.websocket_max_violations, self.websocket_max_violations);
+    }
+}
+
+impl PartialWebhooksConfig {
+    fn apply(self, webhooks: &mut WebhooksConfig) {
+        set_some(&mut webhooks.secret, self.secret);
+        set(&mut webhooks.max_retries, self.max_retries);
+        set(&mut webhooks.initial_backoff_ms, self.initial_backoff_ms);
+        set(&mut webhooks.timeout_ms, self.timeout_ms);
+        set(&mut webhooks.targets, self.targets);
+    }
+}
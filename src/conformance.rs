@@ -0,0 +1,659 @@
+use awc::ws;
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
+
+use crate::api::websocket::WireFormat;
+
+type Connection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+/// Options for a conformance run
+#[derive(Debug, Clone)]
+pub struct ConformanceOptions {
+    /// WebSocket endpoint, e.g. `ws://127.0.0.1:8080/ws`
+    pub url: String,
+    /// Admin key, enables the admin subscription cases
+    pub admin_api_key: Option<String>,
+    /// How long to wait for a protocol response
+    pub response_timeout: Duration,
+    /// How long to wait for live data before skipping the delivery cases
+    pub traffic_timeout: Duration,
+}
+
+impl ConformanceOptions {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            admin_api_key: None,
+            response_timeout: Duration::from_secs(5),
+            traffic_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Result of a single case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// Outcome of a single conformance case
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseReport {
+    /// Case name
+    pub name: String,
+    /// Pass, fail or skip
+    pub status: CaseStatus,
+    /// Failure or skip reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Time taken (milliseconds)
+    pub duration_ms: u64,
+}
+
+/// Machine-readable report of a conformance run
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceReport {
+    /// Endpoint under test
+    pub target: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub cases: Vec<CaseReport>,
+}
+
+impl ConformanceReport {
+    fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            cases: Vec::new(),
+        }
+    }
+
+    /// Whether every case passed or was skipped
+    pub fn success(&self) -> bool {
+        self.failed == 0
+    }
+
+    fn record(&mut self, name: &str, started: Instant, outcome: Result<Outcome, String>) {
+        let (status, detail) = match outcome {
+            Ok(Outcome::Pass) => (CaseStatus::Passed, None),
+            Ok(Outcome::Skip(reason)) => (CaseStatus::Skipped, Some(reason)),
+            Err(reason) => (CaseStatus::Failed, Some(reason)),
+        };
+        match status {
+            CaseStatus::Passed => self.passed += 1,
+            CaseStatus::Failed => self.failed += 1,
+            CaseStatus::Skipped => self.skipped += 1,
+        }
+        self.cases.push(CaseReport {
+            name: name.to_string(),
+            status,
+            detail,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+/// Non-failing outcome of a case
+enum Outcome {
+    Pass,
+    Skip(String),
+}
+
+type CaseResult = Result<Outcome, String>;
+
+/// A protocol client speaking one wire format
+struct Client {
+    connection: Connection,
+    format: WireFormat,
+    response_timeout: Duration,
+}
+
+impl Client {
+    async fn connect(options: &ConformanceOptions, query: Option<&str>) -> Result<Self, String> {
+        let url = match query {
+            Some(query) if options.url.contains('?') => format!("{}&{}", options.url, query),
+            Some(query) => format!("{}?{}", options.url, query),
+            None => options.url.clone(),
+        };
+        let (_, connection) = awc::Client::new()
+            .ws(url)
+            .connect()
+            .await
+            .map_err(|e| format!("connect failed: {}", e))?;
+
+        Ok(Self {
+            connection,
+            format: WireFormat::Json,
+            response_timeout: options.response_timeout,
+        })
+    }
+
+    /// Send a client message in the session format
+    async fn send(&mut self, message: Value) -> Result<(), String> {
+        let frame = match self.format {
+            WireFormat::Json => ws::Message::Text(message.to_string().into()),
+            format => ws::Message::Binary(
+                format.encode(&message).ok_or("failed to encode message")?,
+            ),
+        };
+        self.send_frame(frame).await
+    }
+
+    async fn send_frame(&mut self, frame: ws::Message) -> Result<(), String> {
+        self.connection
+            .send(frame)
+            .await
+            .map_err(|e| format!("send failed: {}", e))
+    }
+
+    /// Receive the next data message within the response timeout
+    async fn recv(&mut self) -> Result<Value, String> {
+        self.recv_within(self.response_timeout).await
+    }
+
+    /// Receive the next data message, skipping control frames
+    async fn recv_within(&mut self, limit: Duration) -> Result<Value, String> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let frame = timeout(remaining, self.connection.next())
+                .await
+                .map_err(|_| "timed out waiting for a message".to_string())?
+                .ok_or("connection closed")?
+                .map_err(|e| format!("protocol error: {}", e))?;
+
+            match frame {
+                ws::Frame::Text(bytes) => {
+                    return serde_json::from_slice(&bytes).map_err(|e| format!("invalid JSON: {}", e));
+                }
+                ws::Frame::Binary(bytes) => {
+                    if self.format == WireFormat::Json {
+                        return Err("unexpected binary frame in JSON format".to_string());
+                    }
+                    return self.format.decode(&bytes);
+                }
+                ws::Frame::Close(reason) => return Err(format!("connection closed: {:?}", reason)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Receive messages until one has the given type
+    async fn recv_type(&mut self, message_type: &str, limit: Duration) -> Result<Value, String> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = self.recv_within(remaining).await?;
+            if message["type"] == message_type {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Send a message and expect a response of the given type
+    async fn request(&mut self, message: Value, expected: &str) -> Result<Value, String> {
+        self.send(message).await?;
+        let response = self.recv().await?;
+        expect_type(&response, expected)?;
+        Ok(response)
+    }
+}
+
+fn expect_type(message: &Value, expected: &str) -> Result<(), String> {
+    if message["type"] == expected {
+        Ok(())
+    } else {
+        Err(format!("expected '{}' message, got {}", expected, message))
+    }
+}
+
+fn subscribe(subscription: Value) -> Value {
+    json!({"action": "subscribe", "subscription": subscription})
+}
+
+fn unsubscribe(subscription: Value) -> Value {
+    json!({"action": "unsubscribe", "subscription": subscription})
+}
+
+/// Run every conformance case against a running instance
+pub async fn run(options: &ConformanceOptions) -> ConformanceReport {
+    let mut report = ConformanceReport::new(&options.url);
+
+    macro_rules! case {
+        ($name:literal, $case:expr) => {{
+            let started = Instant::now();
+            let outcome = $case.await;
+            report.record($name, started, outcome);
+        }};
+    }
+
+    case!("ping_pong", ping_pong(options));
+    case!("subscribe_all_transactions", subscribe_all_transactions(options));
+    case!("subscribe_token_transactions", subscribe_token_transactions(options));
+    case!("subscribe_klines", subscribe_klines(options));
+    case!("subscribe_klines_throttled", subscribe_klines_throttled(options));
+    case!("subscribe_invalid_interval", subscribe_invalid_interval(options));
+    case!("unsubscribe", unsubscribe_confirmation(options));
+    case!("subscribe_many", subscribe_many(options));
+    case!("duplicate_subscription", duplicate_subscription(options));
+    case!("list_and_unsubscribe_all", list_and_unsubscribe_all(options));
+    case!("invalid_json", invalid_json(options));
+    case!("unknown_action", unknown_action(options));
+    case!("request_id_echoed", request_id_echoed(options));
+    case!("get_klines", get_klines(options));
+    case!("admin_subscription_requires_key", admin_requires_key(options));
+    case!("admin_subscription_with_key", admin_with_key(options));
+    case!("set_format", set_format(options));
+    case!("format_query_parameter", format_query(options));
+    case!("invalid_format_rejected", invalid_format(options));
+    case!("resume_confirmation", resume_confirmation(options));
+    case!("resume_invalid_topic", resume_invalid_topic(options));
+    case!("transaction_delivery", transaction_delivery(options));
+    case!("kline_delivery", kline_delivery(options));
+    case!("sequence_numbers", sequence_numbers(options));
+    case!("resume_replay", resume_replay(options));
+
+    report
+}
+
+async fn ping_pong(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let response = client
+        .request(json!({"action": "ping", "client_time": 1700000000000.0}), "pong")
+        .await?;
+    if !response["server_time"].is_string() || response["client_time"] != 1700000000000.0 {
+        return Err(format!("pong without timestamps: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_all_transactions(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let response = client
+        .request(subscribe(json!({"type": "all_transactions"})), "subscribed")
+        .await?;
+    if response["subscription"]["type"] != "all_transactions" {
+        return Err(format!("subscription not echoed: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_token_transactions(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let subscription = json!({"type": "transactions", "tokens": ["DOGE", "SHIB"]});
+    let response = client.request(subscribe(subscription.clone()), "subscribed").await?;
+    if response["subscription"] != subscription {
+        return Err(format!("subscription not echoed: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_klines(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    for interval in ["1s", "1m", "5m", "15m", "1h"] {
+        client
+            .request(
+                subscribe(json!({"type": "klines", "token": "DOGE", "interval": interval})),
+                "subscribed",
+            )
+            .await?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_klines_throttled(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let subscription = json!({"type": "klines", "token": "DOGE", "interval": "1m", "throttle_ms": 500});
+    let response = client.request(subscribe(subscription.clone()), "subscribed").await?;
+    if response["subscription"] != subscription {
+        return Err(format!("subscription not echoed: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_invalid_interval(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client
+        .request(
+            subscribe(json!({"type": "klines", "token": "DOGE", "interval": "2m"})),
+            "error",
+        )
+        .await?;
+    Ok(Outcome::Pass)
+}
+
+async fn unsubscribe_confirmation(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let subscription = json!({"type": "klines", "token": "DOGE", "interval": "1m"});
+    client.request(subscribe(subscription.clone()), "subscribed").await?;
+    let response = client.request(unsubscribe(subscription.clone()), "unsubscribed").await?;
+    if response["subscription"] != subscription {
+        return Err(format!("subscription not echoed: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn subscribe_many(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let valid = json!({"type": "alerts"});
+    let invalid = json!({"type": "klines", "token": "DOGE", "interval": "2m"});
+    let response = client
+        .request(
+            json!({"action": "subscribe_many", "subscriptions": [valid.clone(), invalid.clone()]}),
+            "subscribed_many",
+        )
+        .await?;
+    if response["accepted"] != json!([valid]) {
+        return Err(format!("accepted subscriptions not listed: {}", response));
+    }
+    if response["rejected"][0]["subscription"] != invalid || !response["rejected"][0]["error"].is_string() {
+        return Err(format!("rejected subscription not reported: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn duplicate_subscription(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let subscription = json!({"type": "listings"});
+    for _ in 0..2 {
+        client.request(subscribe(subscription.clone()), "subscribed").await?;
+    }
+
+    let response = client
+        .request(json!({"action": "list_subscriptions"}), "subscriptions")
+        .await?;
+    if response["subscriptions"] != json!([subscription]) {
+        return Err(format!("duplicate subscription listed: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn list_and_unsubscribe_all(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    // Topics without market data, so no broadcast gets between the responses
+    let subscriptions = [json!({"type": "alerts"}), json!({"type": "listings"})];
+    for subscription in &subscriptions {
+        client.request(subscribe(subscription.clone()), "subscribed").await?;
+    }
+
+    let response = client
+        .request(json!({"action": "list_subscriptions"}), "subscriptions")
+        .await?;
+    if response["subscriptions"] != json!(subscriptions) {
+        return Err(format!("subscriptions not listed: {}", response));
+    }
+    let response = client
+        .request(json!({"action": "unsubscribe_all"}), "unsubscribed_all")
+        .await?;
+    if response["subscriptions"] != json!(subscriptions) {
+        return Err(format!("removed subscriptions not echoed: {}", response));
+    }
+    let response = client
+        .request(json!({"action": "list_subscriptions"}), "subscriptions")
+        .await?;
+    if response["subscriptions"] != json!([]) {
+        return Err(format!("subscriptions left after unsubscribe_all: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn invalid_json(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client.send_frame(ws::Message::Text("{not json".into())).await?;
+    expect_type(&client.recv().await?, "error")?;
+
+    // The session stays usable after an error
+    client.request(json!({"action": "ping"}), "pong").await?;
+    Ok(Outcome::Pass)
+}
+
+async fn unknown_action(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let response = client
+        .request(json!({"action": "teleport", "id": "conformance"}), "error")
+        .await?;
+    if response["code"] != "invalid_message" || response["ref"] != "conformance" {
+        return Err(format!("error code or request ID missing: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn request_id_echoed(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let response = client.request(json!({"action": "ping", "id": 42}), "pong").await?;
+    if response["ref"] != 42 {
+        return Err(format!("request ID not echoed: {}", response));
+    }
+    let subscription = json!({"type": "listings"});
+    let response = client
+        .request(json!({"action": "subscribe", "id": "sub-1", "subscription": subscription}), "subscribed")
+        .await?;
+    if response["ref"] != "sub-1" {
+        return Err(format!("request ID not echoed: {}", response));
+    }
+    let response = client.request(json!({"action": "ping"}), "pong").await?;
+    if response.get("ref").is_some() {
+        return Err(format!("ref sent without a request ID: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn get_klines(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let request = json!({"action": "get_klines", "id": "history", "token": "DOGE", "interval": "1m", "limit": 5});
+    let response = client.request(request, "klines_snapshot").await?;
+    if response["ref"] != "history" || response["token"] != "DOGE" || response["interval"] != "1m" {
+        return Err(format!("unexpected history response: {}", response));
+    }
+    match response["data"].as_array() {
+        Some(klines) if klines.len() <= 5 => Ok(Outcome::Pass),
+        _ => Err(format!("history limit not applied: {}", response)),
+    }
+}
+
+async fn admin_requires_key(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    for subscription_type in ["ops_metrics", "admin_events"] {
+        client
+            .request(subscribe(json!({"type": subscription_type})), "error")
+            .await?;
+        client
+            .request(
+                subscribe(json!({"type": subscription_type, "api_key": "conformance-wrong-key"})),
+                "error",
+            )
+            .await?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn admin_with_key(options: &ConformanceOptions) -> CaseResult {
+    let Some(api_key) = &options.admin_api_key else {
+        return Ok(Outcome::Skip("no admin key provided".to_string()));
+    };
+
+    let mut client = Client::connect(options, None).await?;
+    for subscription_type in ["ops_metrics", "admin_events"] {
+        let response = client
+            .request(
+                subscribe(json!({"type": subscription_type, "api_key": api_key})),
+                "subscribed",
+            )
+            .await?;
+        if response["subscription"].get("api_key").is_some() {
+            return Err("admin key echoed back to the client".to_string());
+        }
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn set_format(options: &ConformanceOptions) -> CaseResult {
+    for format in [WireFormat::Msgpack, WireFormat::Cbor] {
+        let mut client = Client::connect(options, None).await?;
+        client.send(json!({"action": "set_format", "format": format})).await?;
+
+        // The confirmation is already in the new format
+        client.format = format;
+        let response = client.recv().await?;
+        expect_type(&response, "format")?;
+        client.request(json!({"action": "ping"}), "pong").await?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn format_query(options: &ConformanceOptions) -> CaseResult {
+    for (query, format) in [("format=msgpack", WireFormat::Msgpack), ("format=cbor", WireFormat::Cbor)] {
+        let mut client = Client::connect(options, Some(query)).await?;
+        client.format = format;
+        client.request(json!({"action": "ping"}), "pong").await?;
+
+        // Text frames are JSON regardless of the session format
+        client.send_frame(ws::Message::Text(json!({"action": "ping"}).to_string().into())).await?;
+        expect_type(&client.recv().await?, "pong")?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn invalid_format(options: &ConformanceOptions) -> CaseResult {
+    match Client::connect(options, Some("format=xml")).await {
+        Ok(_) => Err("handshake accepted an unsupported format".to_string()),
+        Err(_) => Ok(Outcome::Pass),
+    }
+}
+
+async fn transaction_delivery(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client
+        .request(subscribe(json!({"type": "all_transactions"})), "subscribed")
+        .await?;
+
+    let transaction = match client.recv_type("transaction", options.traffic_timeout).await {
+        Ok(transaction) => transaction,
+        Err(_) => return Ok(Outcome::Skip("no transaction traffic".to_string())),
+    };
+    for field in ["token", "price", "volume", "timestamp"] {
+        if transaction["data"].get(field).is_none() {
+            return Err(format!("transaction missing '{}': {}", field, transaction));
+        }
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn kline_delivery(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client
+        .request(subscribe(json!({"type": "all_transactions"})), "subscribed")
+        .await?;
+    let token = match client.recv_type("transaction", options.traffic_timeout).await {
+        Ok(transaction) => transaction["data"]["token"].clone(),
+        Err(_) => return Ok(Outcome::Skip("no transaction traffic".to_string())),
+    };
+
+    let subscription = json!({"type": "klines", "token": token, "interval": "1s"});
+    client.send(subscribe(subscription.clone())).await?;
+    client.recv_type("subscribed", options.response_timeout).await?;
+
+    let kline = match client.recv_type("kline", options.traffic_timeout).await {
+        Ok(kline) => kline,
+        Err(_) => return Ok(Outcome::Skip(format!("no K-line traffic for {}", token))),
+    };
+    if kline["data"]["token"] != token || kline["data"]["interval"] != "1s" {
+        return Err(format!("K-line does not match the subscription: {}", kline));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn resume_confirmation(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    let response = client
+        .request(
+            json!({"action": "resume", "topic": "klines:DOGE:1m", "since": 0}),
+            "resumed",
+        )
+        .await?;
+    if response["topic"] != "klines:DOGE:1m" || !response["replayed"].is_u64() {
+        return Err(format!("malformed resume confirmation: {}", response));
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn resume_invalid_topic(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    for topic in ["ops_metrics", "admin_events", "klines:DOGE:2m", "bogus"] {
+        client
+            .request(json!({"action": "resume", "topic": topic, "since": 0}), "error")
+            .await?;
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn sequence_numbers(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client
+        .request(subscribe(json!({"type": "all_transactions"})), "subscribed")
+        .await?;
+
+    let mut previous: Option<u64> = None;
+    for _ in 0..2 {
+        let transaction = match client.recv_type("transaction", options.traffic_timeout).await {
+            Ok(transaction) => transaction,
+            Err(_) => return Ok(Outcome::Skip("no transaction traffic".to_string())),
+        };
+        if transaction["topic"] != "all_transactions" {
+            return Err(format!("transaction missing topic: {}", transaction));
+        }
+        let seq = transaction["seq"]
+            .as_u64()
+            .ok_or(format!("transaction missing seq: {}", transaction))?;
+        if let Some(previous) = previous {
+            if seq != previous + 1 {
+                return Err(format!("sequence jumped from {} to {}", previous, seq));
+            }
+        }
+        previous = Some(seq);
+    }
+    Ok(Outcome::Pass)
+}
+
+async fn resume_replay(options: &ConformanceOptions) -> CaseResult {
+    let mut client = Client::connect(options, None).await?;
+    client
+        .request(subscribe(json!({"type": "all_transactions"})), "subscribed")
+        .await?;
+    let seen = match client.recv_type("transaction", options.traffic_timeout).await {
+        Ok(transaction) => transaction,
+        Err(_) => return Ok(Outcome::Skip("no transaction traffic".to_string())),
+    };
+    let seq = seen["seq"]
+        .as_u64()
+        .ok_or(format!("transaction missing seq: {}", seen))?;
+    drop(client);
+
+    // Reconnect and ask for everything from the last message seen onwards
+    let mut client = Client::connect(options, None).await?;
+    let response = client
+        .request(
+            json!({"action": "resume", "topic": "all_transactions", "since": seq - 1}),
+            "resumed",
+        )
+        .await?;
+    if response["complete"] != true {
+        return Err(format!("replay buffer lost recent messages: {}", response));
+    }
+
+    let replayed = client.recv_type("transaction", options.response_timeout).await?;
+    if replayed["seq"] != seq || replayed["data"] != seen["data"] {
+        return Err(format!("expected seq {} to be replayed, got {}", seq, replayed));
+    }
+    Ok(Outcome::Pass)
+}
@@ -1,9 +1,23 @@
+#[cfg(feature = "server")]
 pub mod api;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod config;
+#[cfg(feature = "server")]
+pub mod conformance;
+#[cfg(feature = "server")]
+pub mod logging;
 pub mod models;
 pub mod services;
+#[cfg(feature = "server")]
+pub mod telemetry;
+#[cfg(feature = "server")]
+pub mod tls;
+#[cfg(feature = "typescript")]
+pub mod typescript;
 
 // Re-export commonly used items
+#[cfg(feature = "server")]
 pub use api::{configure_routes, configure_websocket_routes, WsManager};
-pub use models::{KLine, TimeInterval, Transaction};
+pub use models::{KLine, Side, TimeInterval, Transaction};
 pub use services::{KLineService, MockDataGenerator};
@@ -0,0 +1,98 @@
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_log::AsLog;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+use crate::config::{LogFormat, LogRotation, LoggingConfig};
+use crate::telemetry::Telemetry;
+
+/// Changes the log filter of a running process
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogLevelHandle {
+    /// Replace the filter with a level or filter directives
+    pub fn set_level(&self, level: &str) -> Result<(), String> {
+        let filter = EnvFilter::try_new(level).map_err(|e| e.to_string())?;
+        self.handle.reload(filter).map_err(|e| e.to_string())?;
+        // Records from the `log` crate are dropped above the bridge's max level
+        tracing_log::log::set_max_level(LevelFilter::current().as_log());
+        Ok(())
+    }
+}
+
+/// Installed logging; file output stops when this is dropped
+pub struct Logging {
+    /// Handle for changing the level at runtime
+    pub level: LogLevelHandle,
+    /// Flushes the file writer on drop
+    _file_guard: Option<WorkerGuard>,
+}
+
+/// Install the global tracing subscriber from the logging configuration
+///
+/// `RUST_LOG`, when set, takes precedence over the configured level. Spans
+/// enabled by the level are also exported through `telemetry`, when given.
+pub fn init(config: &LoggingConfig, telemetry: Option<&Telemetry>) -> Result<Logging, Box<dyn std::error::Error>> {
+    let filter = match std::env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::try_new(directives)?,
+        Err(_) => EnvFilter::try_new(&config.level)?,
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let stdout = match config.format {
+        LogFormat::Text => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer().json().boxed(),
+    };
+
+    let (file, file_guard) = if config.file_output {
+        let (writer, guard) = tracing_appender::non_blocking(file_appender(config)?);
+        let layer = match config.format {
+            LogFormat::Text => fmt::layer().with_ansi(false).with_writer(writer).boxed(),
+            LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+        };
+        (Some(layer), Some(guard))
+    } else {
+        (None, None)
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stdout)
+        .with(file)
+        .with(telemetry.map(|telemetry| telemetry.layer()))
+        .try_init()?;
+
+    Ok(Logging {
+        level: LogLevelHandle { handle },
+        _file_guard: file_guard,
+    })
+}
+
+/// Open the rolling log file, creating its directory
+fn file_appender(config: &LoggingConfig) -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
+    let path = Path::new(&config.file_path);
+    let directory = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid log file path: {}", config.file_path))?;
+
+    let rotation = match config.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy())
+        .build(directory)?;
+    Ok(appender)
+}
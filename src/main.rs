@@ -1,108 +1,706 @@
-use actix_web::{web, App, HttpServer, middleware::Logger};
-use std::str::FromStr;
-use std::sync::{Arc, RwLock};
-use tokio::task;
+use actix_web::{dev::ServerHandle, web, App, HttpServer, middleware::Logger};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::{self, JoinHandle};
 
 use k_line::{
-    KLineService, MockDataGenerator, WsManager,
+    KLineService, MockDataGenerator, Transaction, WsManager,
     configure_routes, configure_websocket_routes,
-    config::Config
+    api::{auth::Authenticator, cors::cors, graphql::build_schema, grpc::GrpcService, rate_limit::RateLimiter, websocket::{SystemState, SystemStatus}},
+    config::{Config, DataSource, FsyncPolicy, StorageBackend, StorageRole, TokenValidation},
+    logging,
+    telemetry::Telemetry,
+    tls::load_server_config,
+    services::{
+        indicators::IndicatorCache,
+        kline::SnapshotError,
+        clickhouse::run_clickhouse_sink,
+        influx::{run_influx_exporter, run_ticker_snapshots},
+        webhooks::deliver_webhooks,
+        AlertService, CandleArchive, ClickHouseWriter, ConfigWatcher, FanoutPublisher, IndexPriceAggregator, InfluxWriter, Metrics, OpsMetricsSampler, OrderBookSimulator, ReplicationState, Replicator,
+        IngestSender, KLineQueryCache, KLineSnapshot, KLineStoreWriter, QuoteConverter, RedisFanout, RedisKLineStore, Scenario, SymbolTable, TokenRegistry, TradeDeduplicator, TradeService, TransactionLog, TransactionPipeline, TransactionReplayer, WebhookDispatcher,
+    },
 };
 
+/// Time given to sessions to deliver the final candles before they are closed
+const SHUTDOWN_DRAIN: Duration = Duration::from_millis(200);
+
+/// Time given to ingest workers to apply queued transactions at shutdown
+const INGEST_DRAIN: Duration = Duration::from_secs(1);
+
+/// Queue a transaction for the ingest workers unless this instance is a standby
+async fn ingest_on_primary(ingest: IngestSender, replication: Arc<ReplicationState>, transaction: Transaction) {
+    if replication.is_primary() && ingest.send(transaction).await.is_err() {
+        tracing::warn!("Ingest workers stopped, dropping transaction");
+    }
+}
+
+/// Wait for SIGTERM or Ctrl-C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Stop ingesting, close the open candles and notify clients, then stop the HTTP server
+async fn shutdown(
+    ingestion: Vec<JoinHandle<()>>,
+    ingest_workers: Vec<JoinHandle<()>>,
+    kline_service: Arc<KLineService>,
+    ws_manager: Arc<WsManager>,
+    wal: Option<Arc<TransactionLog>>,
+    server: ServerHandle,
+) {
+    tracing::info!("Shutting down");
+    // Clients can move to another replica before their sessions are closed
+    ws_manager.broadcast_system_status(SystemStatus {
+        status: SystemState::ShuttingDown,
+        deadline: Some(chrono::Utc::now() + INGEST_DRAIN + SHUTDOWN_DRAIN),
+        message: None,
+    });
+    for task in ingestion {
+        task.abort();
+    }
+
+    // Workers stop once the aborted sources drop their senders and the queues are drained
+    if tokio::time::timeout(INGEST_DRAIN, futures::future::join_all(ingest_workers)).await.is_err() {
+        tracing::warn!("Ingest workers did not drain their queues in {:?}", INGEST_DRAIN);
+    }
+
+    // Subscribers receive the final state of every candle, marked partial
+    let closed = kline_service.close_open_klines();
+    for kline in &closed {
+        ws_manager.broadcast_kline(kline);
+    }
+    tracing::info!("Closed {} open candles as partial", closed.len());
+
+    // Every transaction the workers applied is on disk before the process exits
+    if let Some(wal) = wal {
+        if let Ok(Err(e)) = task::spawn_blocking(move || wal.sync()).await {
+            tracing::error!("Failed to sync the transaction log: {}", e);
+        }
+    }
+
+    tokio::time::sleep(SHUTDOWN_DRAIN).await;
+    let sessions = ws_manager.close_all_sessions();
+    tracing::info!("Closed {} WebSocket sessions", sessions);
+
+    server.stop(true).await;
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize logger
-    env_logger::init();
-
     // Load configuration
-    let config = Config::load().unwrap_or_else(|e| {
-        eprintln!("Failed to load configuration: {}", e);
-        eprintln!("Using default configuration");
-        Config::default()
-    });
+    let (config, load_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(e.to_string())),
+    };
 
-    println!("Configuration loaded:");
-    println!("  Server: {}:{}", config.server.host, config.server.port);
-    println!("  Supported tokens: {:?}", config.get_supported_tokens());
-    println!("  Data generation enabled: {}", config.data_generation.enabled);
-    println!("  Data generation interval: {}ms", config.data_generation.interval_ms);
-    println!("  Volatility: {:.2}%", config.data_generation.volatility * 100.0);
+    // Initialize telemetry export and logging from the configuration
+    let telemetry = Telemetry::init(&config.telemetry).map_err(|e| std::io::Error::other(e.to_string()))?;
+    let logging =
+        logging::init(&config.logging, telemetry.as_ref()).map_err(|e| std::io::Error::other(e.to_string()))?;
+    if let Some(e) = load_error {
+        tracing::error!("Failed to load configuration: {}", e);
+        tracing::warn!("Using default configuration");
+    }
+
+    tracing::info!("Configuration loaded:");
+    tracing::info!("  Server: {}:{}", config.server.host, config.server.port);
+    let supported_tokens = config.get_supported_tokens();
+    if supported_tokens.len() > 20 {
+        tracing::info!("  Supported tokens: {} configured", supported_tokens.len());
+    } else {
+        tracing::info!("  Supported tokens: {:?}", supported_tokens);
+    }
+    tracing::info!("  Data generation enabled: {}", config.data_generation.enabled);
+    tracing::info!("  Data source: {:?}", config.data_generation.data_source);
+    tracing::info!("  Data generation interval: {}ms", config.data_generation.interval_ms);
+    tracing::info!("  Trade arrivals: {:?}", config.data_generation.arrival);
+    if config.data_generation.regimes.enabled {
+        tracing::info!("  Market regimes: starting {:?}, stepping every {}s", config.data_generation.regimes.initial, config.data_generation.regimes.step_secs);
+    }
+    tracing::info!("  Volatility: {:.2}%", config.data_generation.volatility * 100.0);
+    tracing::info!(
+        "  Ingest workers: {}{}",
+        config.performance.ingest_workers,
+        if config.performance.sharded_processing { " (sharded)" } else { "" }
+    );
 
     // Create services
-    let kline_service = Arc::new(KLineService::new());
-    let ws_manager = Arc::new(RwLock::new(WsManager::new()));
+    let token_registry = Arc::new(TokenRegistry::from_config(&config));
+    let mut kline_service =
+        KLineService::new().with_activity_intervals(config.performance.kline_activity_intervals.iter().copied());
+    if config.performance.sharded_processing {
+        kline_service = kline_service.with_shards(config.performance.ingest_workers);
+    }
+    if config.tokens.validation == TokenValidation::Strict {
+        kline_service = kline_service.with_allowlist(token_registry.clone());
+    }
+    let kline_service = Arc::new(kline_service);
+
+    // Warm restart from the last snapshot; a missing file just means a cold start
+    let mut restored = false;
+    let mut wal_sequence = 0;
+    if let Some(path) = &config.snapshot.path {
+        match KLineSnapshot::load(std::path::Path::new(path)) {
+            Ok(snapshot) => {
+                let taken_at = snapshot.taken_at;
+                wal_sequence = snapshot.wal_sequence.unwrap_or_default();
+                let klines = kline_service.restore(snapshot);
+                tracing::info!("Restored {} candles from snapshot {} taken at {}", klines, path, taken_at);
+                restored = true;
+            }
+            Err(SnapshotError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!("No snapshot at {}, starting empty", path);
+            }
+            // Starting empty would let the next snapshot overwrite the unreadable one
+            Err(e) => return Err(std::io::Error::other(e.to_string())),
+        }
+    }
+    // A log that cannot be opened would silently lose what it should keep
+    let wal = match config.wal.enabled {
+        true => Some(Arc::new(TransactionLog::open(&config.wal)?)),
+        false => None,
+    };
+    // Candles evicted by retention pruning are archived and read back for older ranges
+    let archive = match config.archive.enabled {
+        true => Some(Arc::new(
+            CandleArchive::from_config(&config.archive).map_err(|e| std::io::Error::other(e.to_string()))?,
+        )),
+        false => None,
+    };
+    let trade_service = Arc::new(TradeService::new(config.performance.trade_history_size));
+    let order_books = Arc::new(OrderBookSimulator::new(config.order_book.levels));
+    let indicator_cache = Arc::new(IndicatorCache::default());
+    let query_cache = (config.performance.kline_query_cache_ttl_ms > 0).then(|| {
+        Arc::new(KLineQueryCache::new(
+            Duration::from_millis(config.performance.kline_query_cache_ttl_ms),
+            config.performance.kline_query_cache_capacity,
+        ))
+    });
+    let quote_converter = Arc::new(QuoteConverter::from_config(&config.conversion.rates));
+    let index = IndexPriceAggregator::from_config(&config.index).map(Arc::new);
+    let (webhook_sender, webhook_receiver) = mpsc::unbounded_channel();
+    let alert_service = Arc::new(AlertService::new().with_webhook_sender(webhook_sender.clone()));
+    actix_web::rt::spawn(deliver_webhooks(webhook_receiver, config.webhooks.clone()));
+    let ws_manager = Arc::new(
+        WsManager::new()
+            .with_replay_capacity(config.performance.websocket_replay_buffer)
+            .with_session_backlog(config.performance.websocket_session_backlog)
+            .with_agg_trade_window(Duration::from_millis(config.performance.websocket_agg_trade_window_ms))
+            .with_max_connections_per_ip(config.performance.max_websocket_connections_per_ip),
+    );
+    let replication_state = Arc::new(ReplicationState::new(config.replication.role));
+    let metrics = Arc::new(Metrics::new());
+    if let Some(telemetry) = &telemetry {
+        telemetry.observe(metrics.clone(), ws_manager.clone());
+        tracing::info!("  Telemetry: exporting to {}", config.telemetry.endpoint);
+    }
+    let symbols = Arc::new(SymbolTable::from_config(&config));
+
+    // Share broadcasts with the other instances behind the load balancer
+    let fanout = match config.fanout.enabled {
+        true => Some(Arc::new(
+            RedisFanout::new(&config.fanout).map_err(|e| std::io::Error::other(e.to_string()))?,
+        )),
+        false => None,
+    };
+    let fanout_publisher = fanout.as_ref().filter(|_| config.fanout.publish).map(|fanout| {
+        let (publisher, receiver) = FanoutPublisher::channel(config.fanout.queue_capacity);
+        task::spawn(fanout.clone().run_publisher(receiver));
+        publisher
+    });
+    if let Some(fanout) = fanout.filter(|_| config.fanout.subscribe) {
+        tracing::info!("  Fanout: instance {} on {}", fanout.origin(), config.fanout.channel);
+        task::spawn(fanout.run_subscriber(ws_manager.clone()));
+    }
+
+    // Share candles in Redis: the leader writes them, followers serve them
+    let kline_store = match config.storage.backend {
+        StorageBackend::Redis => Some(Arc::new(
+            RedisKLineStore::new(&config.storage).map_err(|e| std::io::Error::other(e.to_string()))?,
+        )),
+        StorageBackend::Memory => None,
+    };
+    let (store_writer, store_receiver) = match &kline_store {
+        Some(_) if config.storage.role == StorageRole::Leader => {
+            let (writer, receiver) = KLineStoreWriter::channel(config.storage.queue_capacity);
+            (Some(writer), Some(receiver))
+        }
+        _ => (None, None),
+    };
+
+    // Insert trades and closed candles into ClickHouse for analytics
+    let clickhouse_writer = config.clickhouse.enabled.then(|| {
+        let (writer, receiver) = ClickHouseWriter::channel(config.clickhouse.queue_capacity);
+        actix_web::rt::spawn(run_clickhouse_sink(receiver, config.clickhouse.clone()));
+        tracing::info!("Inserting trades and closed candles into ClickHouse at {}", config.clickhouse.url);
+        writer
+    });
+
+    // Export closed candles and ticker snapshots in InfluxDB line protocol
+    let influx_writer = config.influx.enabled.then(|| {
+        let (writer, receiver) = InfluxWriter::channel(&config.influx);
+        actix_web::rt::spawn(run_influx_exporter(receiver, config.influx.clone()));
+        if config.influx.ticker_interval_secs > 0 {
+            task::spawn(run_ticker_snapshots(
+                kline_service.clone(),
+                writer.clone(),
+                Duration::from_secs(config.influx.ticker_interval_secs),
+            ));
+        }
+        tracing::info!("Exporting closed candles and tickers to {}", config.influx.endpoint);
+        writer
+    });
+
+    let pipeline = TransactionPipeline::new(kline_service.clone(), ws_manager.clone())
+        .with_metrics(metrics.clone())
+        .with_trade_service(trade_service.clone())
+        .with_order_books(config.order_book.enabled.then(|| order_books.clone()))
+        .with_alerts(alert_service.clone())
+        .with_webhooks(Arc::new(WebhookDispatcher::new(config.webhooks.targets.clone(), webhook_sender.clone())))
+        .with_token_registry(token_registry.clone())
+        .with_symbols(symbols.clone())
+        .with_query_cache(query_cache.clone())
+        .with_deduplicator(
+            (config.performance.trade_dedup_window > 0)
+                .then(|| Arc::new(TradeDeduplicator::new(config.performance.trade_dedup_window))),
+        )
+        .with_index(index.clone())
+        .with_fanout(fanout_publisher)
+        .with_store(store_writer)
+        .with_wal(wal.clone())
+        .with_clickhouse(clickhouse_writer)
+        .with_influx(influx_writer);
+    // Amendments made over the admin API take the same path as ingested trades
+    let amendment_pipeline = pipeline.clone();
+    let authenticator = Arc::new(Authenticator::from_config(&config));
+    let rate_limiter = config
+        .rate_limit
+        .enabled
+        .then(|| Arc::new(RateLimiter::from_config(&config.rate_limit)));
+    tracing::info!("  Replication role: {:?}", config.replication.role);
+    tracing::info!("  Candle storage: {:?} ({:?})", config.storage.backend, config.storage.role);
+    tracing::info!("  Unknown token policy: {:?}", config.tokens.unknown_token_policy);
+    tracing::info!("  Token validation: {:?}", config.tokens.validation);
+    tracing::info!(
+        "  API key auth: {} ({} keys, anonymous {})",
+        if config.auth.enabled { "enabled" } else { "disabled" },
+        config.auth.keys.len(),
+        if config.auth.allow_anonymous { "allowed" } else { "denied" }
+    );
+    if config.rate_limit.enabled {
+        tracing::info!(
+            "  Rate limit: {}/s (burst {}) REST, {}/s (burst {}) WebSocket",
+            config.rate_limit.requests_per_second,
+            config.rate_limit.burst,
+            config.rate_limit.websocket_messages_per_second,
+            config.rate_limit.websocket_burst
+        );
+    }
     
+    // Settings that can be reloaded at runtime are published on this channel
+    let (config_sender, config_receiver) = watch::channel(Arc::new(config.clone()));
+
     // Create mock data generator with configuration
     let mock_generator = MockDataGenerator::new_with_config(&config);
+    let token_listings = mock_generator.listings();
+    let regime_engine = mock_generator.regimes();
     
+    // Tasks feeding transactions into the pipeline, stopped first at shutdown
+    let mut ingestion = Vec::new();
+
+    // Rebuild the state the snapshot missed from the transactions logged after it
+    if let Some(wal) = &wal {
+        let started = std::time::Instant::now();
+        let entries = wal.read_after(wal_sequence)?;
+        let applied = pipeline.replay_wal(entries.into_iter());
+        tracing::info!(
+            "Replayed {} logged transactions and amendments after record {} in {:.1?}",
+            applied,
+            wal_sequence,
+            started.elapsed()
+        );
+        restored |= applied > 0;
+    }
+
+    // Fill the charts with history before live trades start
+    let generating = config.data_generation.enabled
+        && config.data_generation.data_source == DataSource::Mock
+        && !config.storage.is_follower();
+    let backfill_hours = config.data_generation.backfill_hours;
+    if generating && backfill_hours > 0 && replication_state.is_primary() && !restored {
+        let started = std::time::Instant::now();
+        let applied = pipeline.backfill(mock_generator.generate_backfill(
+            backfill_hours,
+            config.data_generation.interval_ms,
+            chrono::Utc::now(),
+        ));
+        tracing::info!(
+            "Backfilled {} transactions over {} hours in {:.1?}",
+            applied,
+            backfill_hours,
+            started.elapsed()
+        );
+    }
+
+    // Candles in memory by now are written before any live update
+    if let (Some(kline_store), Some(receiver)) = (&kline_store, store_receiver) {
+        task::spawn(kline_store.clone().run_writer(kline_service.clone(), receiver));
+    }
+
+    // Every source queues its transactions for the same pool of workers
+    let (ingest, ingest_workers) = pipeline.spawn_workers(
+        config.performance.ingest_workers,
+        config.performance.ingest_queue_capacity,
+    );
+
     // Start mock data generation in background if enabled
-    if config.data_generation.enabled {
-        let kline_service_clone = kline_service.clone();
-        let ws_manager_clone = ws_manager.clone();
-        let generation_interval = config.data_generation.interval_ms;
+    if let Some(kline_store) = kline_store.as_ref().filter(|_| config.storage.is_follower()) {
+        // Followers ingest nothing and serve the leader's candles
+        tracing::info!("Following candles in Redis at {}", config.storage.redis_url);
+        task::spawn(
+            kline_store
+                .clone()
+                .run_follower(kline_service.clone(), Duration::from_millis(config.storage.sync_interval_ms)),
+        );
+    } else if generating {
+        let ingest_clone = ingest.clone();
+        let replication_clone = replication_state.clone();
+        let config_clone = config_receiver.clone();
         
-        task::spawn(async move {
-            mock_generator.start_continuous_generation(
-                move |transaction| {
-                    // Process transaction and update K-lines
-                    kline_service_clone.process_transaction(&transaction);
-                    
-                    // Broadcast transaction to WebSocket clients
-                    if let Ok(manager) = ws_manager_clone.read() {
-                        manager.broadcast_transaction(&transaction);
+        ingestion.push(task::spawn(async move {
+            mock_generator.start_reloadable_generation(
+                // Only the primary generates data; a standby replicates it
+                move |transaction| ingest_on_primary(ingest_clone.clone(), replication_clone.clone(), transaction),
+                config_clone,
+            ).await;
+        }));
+    } else if config.data_generation.enabled && config.data_generation.data_source == DataSource::Replay {
+        // A replay that cannot be loaded would leave the server silently empty
+        let replayer = TransactionReplayer::from_config(&config.data_generation.replay)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        tracing::info!(
+            "Replaying {} transactions from {} over {:.1?}",
+            replayer.len(),
+            config.data_generation.replay.path.as_deref().unwrap_or_default(),
+            replayer.duration()
+        );
+        let ingest_clone = ingest.clone();
+        let replication_clone = replication_state.clone();
+
+        ingestion.push(task::spawn(async move {
+            let played = replayer
+                // Like generated data, a replay only feeds the primary
+                .run(move |transaction| ingest_on_primary(ingest_clone.clone(), replication_clone.clone(), transaction))
+                .await;
+            tracing::info!("Replay finished after {} transactions", played);
+        }));
+    } else if config.data_generation.enabled && config.data_generation.data_source == DataSource::Scenario {
+        // A scenario that cannot be loaded would leave acceptance tests running against nothing
+        let path = config.data_generation.scenario.path.as_deref().unwrap_or_default();
+        let scenario = Scenario::load(std::path::Path::new(path))
+            .and_then(|scenario| scenario.check_tokens(&mock_generator.get_available_tokens()).map(|_| scenario))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        tracing::info!(
+            "Running scenario {} with {} events over {:.1?}",
+            path,
+            scenario.events.len(),
+            scenario.duration()
+        );
+        let ingest_clone = ingest.clone();
+        let replication_clone = replication_state.clone();
+        let interval_ms = config.data_generation.interval_ms;
+
+        ingestion.push(task::spawn(async move {
+            let played = mock_generator
+                // Like generated data, a scenario only feeds the primary
+                .run_scenario(&scenario, interval_ms, move |transaction| {
+                    ingest_on_primary(ingest_clone.clone(), replication_clone.clone(), transaction)
+                })
+                .await;
+            tracing::info!("Scenario finished after {} transactions", played);
+        }));
+    } else {
+        tracing::info!("Mock data generation is disabled");
+    }
+
+    // Replicate from the primary when starting as a standby
+    if !replication_state.is_primary() {
+        let replicator = Replicator::new(
+            config.replication.clone(),
+            replication_state.clone(),
+            kline_service.clone(),
+        );
+        let ingest_clone = ingest.clone();
+
+        ingestion.push(actix_web::rt::spawn(async move {
+            replicator
+                .run(move |transaction| {
+                    let ingest = ingest_clone.clone();
+                    async move {
+                        if ingest.send(transaction).await.is_err() {
+                            tracing::warn!("Ingest workers stopped, dropping replicated transaction");
+                        }
                     }
-                    
-                    // Get updated K-lines and broadcast them
-                    for interval in ["1s", "1m", "5m", "15m", "1h"] {
-                        if let Ok(interval_enum) = k_line::TimeInterval::from_str(interval) {
-                            if let Some(kline) = kline_service_clone.get_current_kline(&transaction.token, interval_enum) {
-                                if let Ok(manager) = ws_manager_clone.read() {
-                                    manager.broadcast_kline(&kline);
-                                }
+                })
+                .await;
+        }));
+    }
+    // Sources hold their own senders, so the workers stop once every source has
+    drop(ingest);
+
+    // Push per-second ops metrics to admin subscribers
+    {
+        let ws_manager_clone = ws_manager.clone();
+        let mut sampler = OpsMetricsSampler::new(metrics.clone());
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let sample = sampler.sample(ws_manager_clone.connection_stats());
+                ws_manager_clone.broadcast_ops_metrics(&sample);
+            }
+        });
+    }
+
+    // Deliver aggregated trades whose window passed without a further trade
+    {
+        let ws_manager_clone = ws_manager.clone();
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(ws_manager_clone.agg_trade_window());
+            loop {
+                interval.tick().await;
+                ws_manager_clone.flush_agg_trades();
+            }
+        });
+    }
+
+    // Drop topics nobody has subscribed or published to for a while
+    {
+        let ws_manager_clone = ws_manager.clone();
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                ws_manager_clone.prune_idle_topics();
+            }
+        });
+    }
+
+    // Sync the transaction log to disk under the interval policy
+    if let Some(wal) = wal.clone().filter(|_| config.wal.fsync == FsyncPolicy::Interval) {
+        let fsync_interval = Duration::from_millis(config.wal.fsync_interval_ms);
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(fsync_interval);
+            loop {
+                interval.tick().await;
+                let wal = wal.clone();
+                if let Ok(Err(e)) = task::spawn_blocking(move || wal.sync()).await {
+                    tracing::error!("Failed to sync the transaction log: {}", e);
+                }
+            }
+        });
+    }
+
+    // Drop candles older than the retention period
+    {
+        let kline_service_clone = kline_service.clone();
+        let config_clone = config_receiver.clone();
+        // Only the leader trims the shared candles
+        let kline_store_clone = kline_store.clone().filter(|_| config.storage.role == StorageRole::Leader);
+        let archive_clone = archive.clone();
+
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let retention_hours = config_clone.borrow().performance.kline_retention_hours;
+                if retention_hours == 0 {
+                    continue;
+                }
+                let cutoff = chrono::Utc::now() - chrono::Duration::hours(retention_hours as i64);
+                let removed = match &archive_clone {
+                    Some(archive) => {
+                        let evicted = kline_service_clone.drain_klines_before(cutoff);
+                        let removed = evicted.len();
+                        if let Err(e) = archive.archive(&evicted).await {
+                            // Kept in memory until the next pruning tries again
+                            tracing::warn!("Failed to archive {} evicted K-lines: {}", removed, e);
+                            for kline in evicted {
+                                kline_service_clone.insert_kline(kline);
                             }
                         }
+                        removed
                     }
-                    
-                    println!("Processed transaction: {} {} @ {}", 
-                        transaction.token, 
-                        transaction.volume, 
-                        transaction.price
-                    );
-                },
-                generation_interval,
-            ).await;
+                    None => kline_service_clone.remove_klines_before(cutoff),
+                };
+                if removed > 0 {
+                    tracing::debug!("Removed {} K-lines older than {} hours", removed, retention_hours);
+                }
+                if let Some(kline_store) = &kline_store_clone {
+                    let tokens = kline_service_clone.get_available_tokens();
+                    if let Err(e) = kline_store.remove_before(&tokens, &kline_service_clone.intervals(), cutoff).await {
+                        tracing::warn!("Failed to remove old candles from Redis: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    // Apply configuration file changes without restarting
+    if std::path::Path::new("config").is_dir() {
+        let watcher = ConfigWatcher::new(
+            "config",
+            config_sender,
+            ws_manager.clone(),
+            token_registry.clone(),
+        )
+        .with_log_level(logging.level.clone())
+        .with_symbols(symbols.clone());
+        task::spawn(async move {
+            if let Err(e) = watcher.run().await {
+                tracing::warn!("Configuration hot reload is unavailable: {}", e);
+            }
         });
-    } else {
-        println!("Mock data generation is disabled");
+    }
+
+    // Forget rate-limited clients once their buckets have refilled
+    if let Some(rate_limiter) = rate_limiter.clone() {
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                rate_limiter.prune();
+            }
+        });
+    }
+
+    // Start the gRPC server alongside the HTTP server if enabled
+    if config.grpc.enabled {
+        let grpc_address = format!("{}:{}", config.server.host, config.grpc.port);
+        let grpc_service = GrpcService::new(kline_service.clone(), ws_manager.clone());
+
+        match grpc_address.parse() {
+            Ok(addr) => {
+                tracing::info!("Starting gRPC service on {}", grpc_address);
+                task::spawn(async move {
+                    if let Err(e) = tonic::transport::Server::builder()
+                        .add_service(grpc_service.into_server())
+                        .serve(addr)
+                        .await
+                    {
+                        tracing::error!("gRPC server error: {}", e);
+                    }
+                });
+            }
+            Err(e) => tracing::error!("Invalid gRPC address {}: {}", grpc_address, e),
+        }
     }
 
     let server_address = format!("{}:{}", config.server.host, config.server.port);
-    println!("Starting K-line data service on http://{}", server_address);
-    println!("Available endpoints:");
-    println!("  REST API:");
-    println!("    GET /api/v1/klines?token=DOGE&interval=1m");
-    println!("    GET /api/v1/klines/latest?token=DOGE&interval=1m");
-    println!("    GET /api/v1/klines/current?token=DOGE&interval=1m");
-    println!("    GET /api/v1/tokens");
-    println!("  WebSocket:");
-    println!("    WS  /ws");
-    println!();
-    println!("WebSocket subscription examples:");
-    println!("  Subscribe to all transactions: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"all_transactions\"}}}}");
-    println!("  Subscribe to DOGE transactions: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"transactions\",\"tokens\":[\"DOGE\"]}}}}");
-    println!("  Subscribe to DOGE 1m K-lines: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"klines\",\"token\":\"DOGE\",\"interval\":\"1m\"}}}}");
+    let scheme = if config.server.tls.enabled { "https" } else { "http" };
+    tracing::info!("Starting K-line data service on {}://{}", scheme, server_address);
+    tracing::info!("Available endpoints:");
+    tracing::info!("  REST API:");
+    tracing::info!("    GET /api/v1/klines?token=DOGE&interval=1m");
+    tracing::info!("    GET /api/v1/klines/batch?tokens=DOGE,SHIB,PEPE&interval=1m");
+    tracing::info!("    GET /api/v1/klines/export?token=DOGE&interval=1m&format=csv");
+    tracing::info!("    GET /api/v1/klines/latest?token=DOGE&interval=1m");
+    tracing::info!("    GET /api/v1/klines/current?token=DOGE&interval=1m");
+    tracing::info!("    GET /api/v1/ticker?token=DOGE, GET /api/v1/index?token=DOGE");
+    tracing::info!("    GET /api/v1/trades?token=DOGE&limit=100");
+    tracing::info!("    GET /api/v1/depth?token=DOGE&limit=20");
+    tracing::info!("    GET /api/v1/indicators/{{ma,rsi,macd,bollinger}}?token=DOGE&interval=1m");
+    tracing::info!("    GET /api/v1/{{renko,range-bars}}?token=DOGE&size=0.001");
+    tracing::info!("    POST/GET /api/v1/alerts, GET/DELETE /api/v1/alerts/{{id}}");
+    tracing::info!("    GET /api/v1/tokens");
+    tracing::info!("    GET /api/v1/symbols");
+    tracing::info!("    GET /api/v1/stream?token=DOGE&interval=1m (Server-Sent Events)");
+    tracing::info!("    GET /api/v1/info");
+    tracing::info!("    GET /api/v1/openapi.json (Swagger UI at /api/v1/docs)");
+    tracing::info!("    POST /api/v1/graphql (subscriptions over WebSocket on the same path)");
+    tracing::info!("    POST /api/v1/admin/promote");
+    tracing::info!("    GET/POST /api/v1/admin/tokens, DELETE /api/v1/admin/tokens/{{symbol}}");
+    tracing::info!("    POST /api/v1/admin/tokens/{{token}}/register");
+    tracing::info!("    GET /api/v1/admin/sessions, DELETE /api/v1/admin/sessions/{{id}}");
+    tracing::info!("    GET /api/v1/admin/market, PUT /api/v1/admin/market/regime, POST /api/v1/admin/market/events");
+    tracing::info!("  WebSocket:");
+    tracing::info!("    WS  /ws{}", if config.server.tls.enabled { " (wss://)" } else { "" });
+    tracing::info!("WebSocket subscription examples:");
+    tracing::info!("  Subscribe to all transactions: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"all_transactions\"}}}}");
+    tracing::info!("  Subscribe to DOGE transactions: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"transactions\",\"tokens\":[\"DOGE\"]}}}}");
+    tracing::info!("  Subscribe to DOGE 1m K-lines: {{\"action\":\"subscribe\",\"subscription\":{{\"type\":\"klines\",\"token\":\"DOGE\",\"interval\":\"1m\"}}}}");
 
     // Configure server based on configuration
     let workers = config.server.workers;
     let server_config = config.clone();
+    let graphql_schema = build_schema(kline_service.clone(), ws_manager.clone());
+    let shutdown_kline_service = kline_service.clone();
+    let shutdown_ws_manager = ws_manager.clone();
+    let shutdown_wal = wal.clone();
 
     // Start HTTP server with configuration
     let mut server = HttpServer::new(move || {
-        App::new()
+        let mut app = App::new()
             .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(trade_service.clone()))
+            .app_data(web::Data::new(order_books.clone()))
+            .app_data(web::Data::new(indicator_cache.clone()))
+            .app_data(web::Data::new(quote_converter.clone()))
+            .app_data(web::Data::new(alert_service.clone()))
             .app_data(web::Data::new(ws_manager.clone()))
+            .app_data(web::Data::new(metrics.clone()))
+            .app_data(web::Data::new(replication_state.clone()))
+            .app_data(web::Data::new(token_registry.clone()))
+            .app_data(web::Data::new(token_listings.clone()))
+            .app_data(web::Data::new(regime_engine.clone()))
+            .app_data(web::Data::new(symbols.clone()))
+            .app_data(web::Data::new(authenticator.clone()))
+            .app_data(web::Data::new(amendment_pipeline.clone()))
             .app_data(web::Data::new(server_config.clone()))
-            .wrap(Logger::default())
+            .app_data(web::Data::new(graphql_schema.clone()));
+        if let Some(rate_limiter) = &rate_limiter {
+            app = app.app_data(web::Data::new(rate_limiter.clone()));
+        }
+        if let Some(query_cache) = &query_cache {
+            app = app.app_data(web::Data::new(query_cache.clone()));
+        }
+        if let Some(index) = &index {
+            app = app.app_data(web::Data::new(index.clone()));
+        }
+        if let Some(wal) = &wal {
+            app = app.app_data(web::Data::new(wal.clone()));
+        }
+        if let Some(archive) = &archive {
+            app = app.app_data(web::Data::new(archive.clone()));
+        }
+        // The default format, plus the ID set by the request ID middleware
+        app.wrap(cors(&server_config.server.cors))
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{x-request-id}o"#))
             .configure(configure_routes)
             .configure(configure_websocket_routes)
     });
@@ -111,8 +709,28 @@ async fn main() -> std::io::Result<()> {
         server = server.workers(workers);
     }
 
-    server
-        .bind(&server_address)?
-        .run()
-        .await
+    // Signals are handled below so candles are closed and clients notified first
+    server = server.disable_signals();
+
+    // WebSocket upgrades share the listener, so wss:// comes with HTTPS
+    let server = if config.server.tls.enabled {
+        let tls_config = load_server_config(&config.server.tls)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        server.bind_rustls_0_23(&server_address, tls_config)?
+    } else {
+        server.bind(&server_address)?
+    };
+
+    let server = server.run();
+    let server_handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        shutdown(ingestion, ingest_workers, shutdown_kline_service, shutdown_ws_manager, shutdown_wal, server_handle).await;
+    });
+
+    let result = server.await;
+    if let Some(telemetry) = &telemetry {
+        telemetry.shutdown();
+    }
+    result
 }
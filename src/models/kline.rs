@@ -1,9 +1,22 @@
 use super::time_interval::TimeInterval;
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use super::transaction::{Side, Transaction};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(feature = "server")]
+use std::borrow::Cow;
+#[cfg(feature = "server")]
+use utoipa::openapi::schema::Schema;
+#[cfg(feature = "server")]
+use utoipa::openapi::RefOr;
+#[cfg(feature = "server")]
+use utoipa::{PartialSchema, ToSchema};
 
 /// K-line (candlestick) data structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serialized with the derived `close_time` and `is_final` fields as well,
+/// see [`SerializedKLine`].
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS), ts(as = "SerializedKLine<'static>"))]
 pub struct KLine {
     /// Token symbol (e.g., "DOGE", "SHIB")
     pub token: String,
@@ -21,8 +34,20 @@ pub struct KLine {
     pub close: f64,
     /// Trading volume
     pub volume: f64,
+    /// Volume of trades whose aggressor bought
+    #[serde(default)]
+    pub buy_volume: f64,
+    /// Volume of trades whose aggressor sold
+    #[serde(default)]
+    pub sell_volume: f64,
     /// Whether this K-line is closed (interval completed)
     pub is_closed: bool,
+    /// Whether this K-line was closed before its interval completed, at shutdown
+    #[serde(default)]
+    pub is_partial: bool,
+    /// Time of the latest trade, for trade-count and volume candles
+    #[serde(default)]
+    pub last_trade_at: Option<DateTime<Utc>>,
 }
 
 impl KLine {
@@ -43,7 +68,11 @@ impl KLine {
             low: price,
             close: price,
             volume,
+            buy_volume: 0.0,
+            sell_volume: 0.0,
             is_closed: false,
+            is_partial: false,
+            last_trade_at: (!interval.is_time_based()).then_some(timestamp),
         }
     }
 
@@ -57,15 +86,136 @@ impl KLine {
         }
     }
 
+    /// Update K-line with a trade, counting its volume on the aggressor's side
+    pub fn apply(&mut self, transaction: &Transaction) {
+        if !self.is_closed {
+            self.update(transaction.price, transaction.volume);
+            match transaction.side {
+                Side::Buy => self.buy_volume += transaction.volume,
+                Side::Sell => self.sell_volume += transaction.volume,
+            }
+        }
+    }
+
     /// Close this K-line (mark as completed)
     pub fn close(&mut self) {
         self.is_closed = true;
     }
+
+    /// Close this K-line before its interval completed
+    pub fn close_partial(&mut self) {
+        self.is_closed = true;
+        self.is_partial = true;
+    }
+
+    /// Last millisecond covered by this K-line's interval
+    ///
+    /// Trade-count and volume candles have no fixed span and end at their
+    /// latest trade.
+    pub fn close_time(&self) -> DateTime<Utc> {
+        match self.interval.duration_seconds() {
+            Some(seconds) => self.timestamp + Duration::seconds(seconds as i64) - Duration::milliseconds(1),
+            None => self.last_trade_at.unwrap_or(self.timestamp),
+        }
+    }
+}
+
+/// A K-line as serialized, with the fields chart libraries key bars by
+///
+/// Deserializing a [`KLine`] ignores the derived fields.
+#[derive(Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS), ts(rename = "KLine"))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+struct SerializedKLine<'a> {
+    /// Token symbol (e.g., "DOGE", "SHIB")
+    token: &'a str,
+    /// Start of the interval
+    timestamp: DateTime<Utc>,
+    /// Exclusive end of the interval; just after the latest trade for trade-count and volume candles
+    close_time: DateTime<Utc>,
+    interval: TimeInterval,
+    open: f64,
+    high: f64,
+    low: f64,
+    /// Closing price (current price for open intervals)
+    close: f64,
+    volume: f64,
+    /// Volume of trades whose aggressor bought
+    buy_volume: f64,
+    /// Volume of trades whose aggressor sold
+    sell_volume: f64,
+    /// Same as `is_final`, kept for existing clients
+    is_closed: bool,
+    /// Whether the candle is complete and will not change any more
+    is_final: bool,
+    /// Whether the candle was closed before its interval completed, at shutdown
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[cfg_attr(feature = "typescript", ts(as = "Option<bool>", optional))]
+    is_partial: bool,
+    /// Time of the latest trade, for trade-count and volume candles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    last_trade_at: Option<DateTime<Utc>>,
+}
+
+impl<'a> From<&'a KLine> for SerializedKLine<'a> {
+    fn from(kline: &'a KLine) -> Self {
+        Self {
+            token: &kline.token,
+            timestamp: kline.timestamp,
+            close_time: kline.close_time() + Duration::milliseconds(1),
+            interval: kline.interval,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            buy_volume: kline.buy_volume,
+            sell_volume: kline.sell_volume,
+            is_closed: kline.is_closed,
+            is_final: kline.is_closed,
+            is_partial: kline.is_partial,
+            last_trade_at: kline.last_trade_at,
+        }
+    }
+}
+
+impl Serialize for KLine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedKLine::from(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "server")]
+impl PartialSchema for KLine {
+    fn schema() -> RefOr<Schema> {
+        SerializedKLine::schema()
+    }
+}
+
+#[cfg(feature = "server")]
+impl ToSchema for KLine {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        SerializedKLine::schemas(schemas)
+    }
+}
+
+#[cfg(feature = "server")]
+impl schemars::JsonSchema for KLine {
+    fn schema_name() -> Cow<'static, str> {
+        "KLine".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        SerializedKLine::json_schema(generator)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::Timelike;
 
     #[test]
     fn test_time_interval_as_str() {
@@ -104,4 +254,12 @@ mod tests {
         assert_eq!(kline.volume, 100.0);
         assert!(!kline.is_closed);
     }
+
+    #[test]
+    fn test_kline_close_time() {
+        let open = Utc::now().with_nanosecond(0).unwrap();
+        let kline = KLine::new("DOGE".to_string(), open, TimeInterval::Minute5, 1.0, 100.0);
+
+        assert_eq!(kline.close_time(), open + Duration::minutes(5) - Duration::milliseconds(1));
+    }
 }
@@ -1,8 +1,12 @@
 pub mod kline;
+pub mod symbol;
+pub mod ticker;
 pub mod time_interval;
 pub mod transaction;
 
 // Re-export for convenience
 pub use kline::KLine;
+pub use symbol::Symbol;
+pub use ticker::Ticker;
 pub use time_interval::TimeInterval;
-pub use transaction::Transaction;
+pub use transaction::{Side, TradeId, Transaction, TransactionError};
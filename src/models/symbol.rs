@@ -0,0 +1,117 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Quote asset of tokens named without one
+pub const DEFAULT_QUOTE: &str = "USD";
+
+/// Characters separating the base and quote assets of a pair, as in `DOGE-USDT`
+pub const PAIR_SEPARATORS: [char; 2] = ['-', '/'];
+
+/// Character separating a series derived from a pair, as in `DOGE@index`
+pub const SERIES_SEPARATOR: char = '@';
+
+/// Trading pair of a base asset priced in a quote asset
+///
+/// The canonical form is `BASE-QUOTE` in upper case, such as `DOGE-USDT`.
+/// Plain tokens like `DOGE` are pairs quoted in [`DEFAULT_QUOTE`], and keep
+/// their plain name as canonical form, so `doge`, `DOGE/USD` and `DOGE-USD`
+/// all name the series existing clients know as `DOGE`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    base: String,
+    quote: String,
+}
+
+impl Symbol {
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self {
+            base: base.to_uppercase(),
+            quote: quote.to_uppercase(),
+        }
+    }
+
+    /// Asset being priced
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Asset prices are given in
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+
+    /// Canonical form of a token or pair, or the token as given if it is not a valid symbol
+    ///
+    /// The suffix of a derived series is kept in lower case, so `doge@INDEX`
+    /// becomes `DOGE@index`.
+    pub fn canonicalize(token: &str) -> String {
+        if let Some((pair, series)) = token.split_once(SERIES_SEPARATOR) {
+            return format!("{}{}{}", Self::canonicalize(pair), SERIES_SEPARATOR, series.to_lowercase());
+        }
+        token.parse::<Symbol>().map_or_else(|_| token.to_string(), |symbol| symbol.to_string())
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid symbol: {:?}", s);
+        let valid = |asset: &str| !asset.is_empty() && !asset.contains(|c: char| c == ':' || c == SERIES_SEPARATOR || c.is_whitespace());
+        let (base, quote) = s.split_once(PAIR_SEPARATORS).unwrap_or((s, DEFAULT_QUOTE));
+        if !valid(base) || !valid(quote) || quote.contains(PAIR_SEPARATORS) {
+            return Err(invalid());
+        }
+        Ok(Symbol::new(base, quote))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.quote == DEFAULT_QUOTE {
+            f.write_str(&self.base)
+        } else {
+            write!(f, "{}-{}", self.base, self.quote)
+        }
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_parsing() {
+        let symbol: Symbol = "doge/usdt".parse().unwrap();
+        assert_eq!((symbol.base(), symbol.quote()), ("DOGE", "USDT"));
+        assert_eq!(symbol.to_string(), "DOGE-USDT");
+
+        // Plain tokens are quoted in the default quote and keep their name
+        let symbol: Symbol = "DOGE".parse().unwrap();
+        assert_eq!((symbol.base(), symbol.quote()), ("DOGE", DEFAULT_QUOTE));
+        assert_eq!(symbol.to_string(), "DOGE");
+        assert_eq!(Symbol::canonicalize("doge-usd"), "DOGE");
+
+        assert_eq!(Symbol::canonicalize("doge/usd@INDEX"), "DOGE@index");
+
+        for invalid in ["", "-USDT", "DOGE-", "DOGE-USDT-X", "DO GE", "a:b", "DOGE@index"] {
+            assert!(invalid.parse::<Symbol>().is_err(), "{}", invalid);
+        }
+        assert_eq!(Symbol::canonicalize("DOGE-"), "DOGE-");
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::KLine;
+
+/// Rolling 24-hour price statistics for a token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(async_graphql::SimpleObject, schemars::JsonSchema))]
+pub struct Ticker {
+    /// Token symbol
+    pub token: String,
+    /// Latest traded price
+    pub last_price: f64,
+    /// Price at the start of the window
+    pub open_price: f64,
+    /// Highest price in the window
+    pub high: f64,
+    /// Lowest price in the window
+    pub low: f64,
+    /// Volume traded in the window
+    pub volume: f64,
+    /// Change from the open price
+    pub price_change: f64,
+    /// Change from the open price, in percent
+    pub price_change_percent: f64,
+    /// When the statistics were computed
+    pub timestamp: DateTime<Utc>,
+    /// All-time and rolling highs and lows, when the token's trades were tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub extremes: Option<PriceExtremes>,
+}
+
+/// All-time and rolling price extremes of a token
+///
+/// Rolling highs and lows are missing for windows without trades.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(async_graphql::SimpleObject, schemars::JsonSchema))]
+pub struct PriceExtremes {
+    /// Highest price ever traded
+    pub all_time_high: f64,
+    /// When the all-time high was first traded
+    pub all_time_high_at: DateTime<Utc>,
+    /// Lowest price ever traded
+    pub all_time_low: f64,
+    /// When the all-time low was first traded
+    pub all_time_low_at: DateTime<Utc>,
+    pub high_1h: Option<f64>,
+    pub low_1h: Option<f64>,
+    pub high_24h: Option<f64>,
+    pub low_24h: Option<f64>,
+    pub high_7d: Option<f64>,
+    pub low_7d: Option<f64>,
+}
+
+impl Ticker {
+    /// Summarize a token's candles, sorted oldest first, as of `now`
+    pub fn from_klines(token: &str, klines: &[KLine], now: DateTime<Utc>) -> Option<Self> {
+        let (first, last) = (klines.first()?, klines.last()?);
+        let price_change = last.close - first.open;
+        Some(Self {
+            token: token.to_string(),
+            last_price: last.close,
+            open_price: first.open,
+            high: klines.iter().map(|kline| kline.high).fold(f64::MIN, f64::max),
+            low: klines.iter().map(|kline| kline.low).fold(f64::MAX, f64::min),
+            volume: klines.iter().map(|kline| kline.volume).sum(),
+            price_change,
+            price_change_percent: price_change / first.open * 100.0,
+            timestamp: now,
+            extremes: None,
+        })
+    }
+}
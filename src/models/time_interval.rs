@@ -1,57 +1,166 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::str::FromStr;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "server")]
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+#[cfg(feature = "server")]
+use utoipa::openapi::RefOr;
+#[cfg(feature = "server")]
+use utoipa::{PartialSchema, ToSchema};
 
 /// Time intervals for K-line data
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// Besides time-aligned intervals, candles can be cut by activity: every
+/// `N` trades (`"100t"`) or every `N` units of base volume (`"5000v"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS), ts(type = "string"))]
 pub enum TimeInterval {
-    #[serde(rename = "1s")]
     Second1,
-    #[serde(rename = "1m")]
     Minute1,
-    #[serde(rename = "5m")]
     Minute5,
-    #[serde(rename = "15m")]
     Minute15,
-    #[serde(rename = "1h")]
     Hour1,
+    /// One candle per this many trades
+    Trades(u32),
+    /// One candle per this much base volume
+    Volume(u64),
 }
 
 impl FromStr for TimeInterval {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid time interval: {}", s);
         match s {
             "1s" => Ok(Self::Second1),
             "1m" => Ok(Self::Minute1),
             "5m" => Ok(Self::Minute5),
             "15m" => Ok(Self::Minute15),
             "1h" => Ok(Self::Hour1),
-            _ => Err(format!("Invalid time interval: {}", s)),
+            _ => {
+                if let Some(trades) = s.strip_suffix('t').and_then(parse_count) {
+                    u32::try_from(trades).map(Self::Trades).map_err(|_| invalid())
+                } else if let Some(volume) = s.strip_suffix('v').and_then(parse_count) {
+                    Ok(Self::Volume(volume))
+                } else {
+                    Err(invalid())
+                }
+            }
         }
     }
 }
 
+/// Parse the positive count of a trade-count or volume interval
+fn parse_count(digits: &str) -> Option<u64> {
+    // u64 parsing also accepts a leading sign
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|count| *count > 0)
+}
+
 impl TimeInterval {
+    /// All supported time-aligned intervals, from finest to coarsest
+    pub fn all() -> [TimeInterval; 5] {
+        [
+            Self::Second1,
+            Self::Minute1,
+            Self::Minute5,
+            Self::Minute15,
+            Self::Hour1,
+        ]
+    }
+
     /// Convert to string
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> Cow<'static, str> {
         match self {
-            Self::Second1 => "1s",
-            Self::Minute1 => "1m",
-            Self::Minute5 => "5m",
-            Self::Minute15 => "15m",
-            Self::Hour1 => "1h",
+            Self::Second1 => Cow::Borrowed("1s"),
+            Self::Minute1 => Cow::Borrowed("1m"),
+            Self::Minute5 => Cow::Borrowed("5m"),
+            Self::Minute15 => Cow::Borrowed("15m"),
+            Self::Hour1 => Cow::Borrowed("1h"),
+            Self::Trades(trades) => Cow::Owned(format!("{}t", trades)),
+            Self::Volume(volume) => Cow::Owned(format!("{}v", volume)),
         }
     }
 
-    /// Get duration in seconds
-    pub fn duration_seconds(&self) -> u64 {
+    /// Get duration in seconds, or `None` for trade-count and volume intervals
+    pub fn duration_seconds(&self) -> Option<u64> {
         match self {
-            Self::Second1 => 1,
-            Self::Minute1 => 60,
-            Self::Minute5 => 300,
-            Self::Minute15 => 900,
-            Self::Hour1 => 3600,
+            Self::Second1 => Some(1),
+            Self::Minute1 => Some(60),
+            Self::Minute5 => Some(300),
+            Self::Minute15 => Some(900),
+            Self::Hour1 => Some(3600),
+            Self::Trades(_) | Self::Volume(_) => None,
         }
     }
+
+    /// Whether candles are aligned to wall-clock time
+    pub fn is_time_based(&self) -> bool {
+        self.duration_seconds().is_some()
+    }
+
+    /// Whether a trade-count or volume candle with these totals is complete
+    ///
+    /// Time-aligned candles complete with the clock instead, so never here.
+    pub fn is_full(&self, trades: u64, volume: f64) -> bool {
+        match *self {
+            Self::Trades(limit) => trades >= u64::from(limit),
+            Self::Volume(limit) => volume >= limit as f64,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for TimeInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.as_str())
+    }
+}
+
+impl Serialize for TimeInterval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeInterval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let interval = Cow::<str>::deserialize(deserializer)?;
+        interval.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Description of the interval strings in API schemas
+#[cfg(feature = "server")]
+const INTERVAL_DESCRIPTION: &str = "1s, 1m, 5m, 15m or 1h, or N trades (\"100t\") or N base volume (\"5000v\")";
+
+#[cfg(feature = "server")]
+impl PartialSchema for TimeInterval {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some(INTERVAL_DESCRIPTION))
+            .into()
+    }
+}
+
+#[cfg(feature = "server")]
+impl ToSchema for TimeInterval {}
+
+#[cfg(feature = "server")]
+impl schemars::JsonSchema for TimeInterval {
+    fn schema_name() -> Cow<'static, str> {
+        "TimeInterval".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": INTERVAL_DESCRIPTION,
+        })
+    }
 }
@@ -1,8 +1,100 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reason a transaction's values were rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionError {
+    /// The price is not a finite positive number
+    InvalidPrice(f64),
+    /// The volume is not a finite positive number
+    InvalidVolume(f64),
+    /// Neither `side` nor the legacy `is_buy` was given
+    MissingSide,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::InvalidPrice(price) => {
+                write!(f, "Invalid price: {}. Expected a finite positive number", price)
+            }
+            TransactionError::InvalidVolume(volume) => {
+                write!(f, "Invalid volume: {}. Expected a finite positive number", volume)
+            }
+            TransactionError::MissingSide => write!(f, "Missing side. Expected \"buy\" or \"sell\""),
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+/// Side of the aggressor, the party whose order took liquidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "buy" => Ok(Side::Buy),
+            "sell" => Ok(Side::Sell),
+            _ => Err(format!("Invalid side: {}. Expected buy or sell", s)),
+        }
+    }
+}
+
+/// Identifier a venue assigns to a trade, numeric or opaque
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum TradeId {
+    Number(u64),
+    Text(String),
+}
+
+impl fmt::Display for TradeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeId::Number(id) => write!(f, "{}", id),
+            TradeId::Text(id) => f.write_str(id),
+        }
+    }
+}
 
 /// Transaction data structure for generating K-lines
+///
+/// Deserialization rejects transactions that fail [`Transaction::validate`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(try_from = "UncheckedTransaction")]
 pub struct Transaction {
     /// Token symbol
     pub token: String,
@@ -12,20 +104,99 @@ pub struct Transaction {
     pub volume: f64,
     /// Transaction timestamp
     pub timestamp: DateTime<Utc>,
-    /// Whether this is a buy (true) or sell (false)
-    pub is_buy: bool,
+    /// Side of the aggressor
+    pub side: Side,
+    /// Whether the reporting party provided liquidity, when the venue says so
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_maker: Option<bool>,
+    /// Venue trade ID, used to drop replayed or retried trades
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trade_id: Option<TradeId>,
+    /// Ingestion source that reported the trade, such as `mock` or a venue name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Wire form of a transaction, before its values are checked
+#[derive(Deserialize)]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+struct UncheckedTransaction {
+    token: String,
+    price: f64,
+    volume: f64,
+    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    side: Option<Side>,
+    /// Side of recordings made before `side`, true for buys
+    #[serde(default)]
+    is_buy: Option<bool>,
+    #[serde(default)]
+    is_maker: Option<bool>,
+    #[serde(default)]
+    trade_id: Option<TradeId>,
+    #[serde(default)]
+    source: Option<String>,
+}
+
+impl TryFrom<UncheckedTransaction> for Transaction {
+    type Error = TransactionError;
+
+    fn try_from(unchecked: UncheckedTransaction) -> Result<Self, Self::Error> {
+        let side = match (unchecked.side, unchecked.is_buy) {
+            (Some(side), _) => side,
+            (None, Some(true)) => Side::Buy,
+            (None, Some(false)) => Side::Sell,
+            (None, None) => return Err(TransactionError::MissingSide),
+        };
+        let transaction = Transaction {
+            token: unchecked.token,
+            price: unchecked.price,
+            volume: unchecked.volume,
+            timestamp: unchecked.timestamp,
+            side,
+            is_maker: unchecked.is_maker,
+            trade_id: unchecked.trade_id,
+            source: unchecked.source,
+        };
+        transaction.validate()?;
+        Ok(transaction)
+    }
 }
 
 impl Transaction {
     /// Create a new transaction
-    pub fn new(token: String, price: f64, volume: f64, is_buy: bool) -> Self {
+    ///
+    /// The price and volume are not checked; use [`Transaction::try_new`] for
+    /// values from outside the process.
+    pub fn new(token: String, price: f64, volume: f64, side: Side) -> Self {
         Self {
             token,
             price,
             volume,
             timestamp: Utc::now(),
-            is_buy,
+            side,
+            is_maker: None,
+            trade_id: None,
+            source: None,
+        }
+    }
+
+    /// Create a new transaction, rejecting prices and volumes that would corrupt candles
+    pub fn try_new(token: String, price: f64, volume: f64, side: Side) -> Result<Self, TransactionError> {
+        let transaction = Self::new(token, price, volume, side);
+        transaction.validate()?;
+        Ok(transaction)
+    }
+
+    /// Check that the price and volume are finite and positive
+    pub fn validate(&self) -> Result<(), TransactionError> {
+        if !(self.price.is_finite() && self.price > 0.0) {
+            return Err(TransactionError::InvalidPrice(self.price));
         }
+        if !(self.volume.is_finite() && self.volume > 0.0) {
+            return Err(TransactionError::InvalidVolume(self.volume));
+        }
+        Ok(())
     }
 }
 
@@ -35,13 +206,96 @@ mod tests {
 
     #[test]
     fn test_transaction_new() {
-        let transaction = Transaction::new("DOGE".to_string(), 1.0, 100.0, true);
+        let transaction = Transaction::new("DOGE".to_string(), 1.0, 100.0, Side::Buy);
 
         assert_eq!(transaction.token, "DOGE");
         assert_eq!(transaction.price, 1.0);
         assert_eq!(transaction.volume, 100.0);
-        assert!(transaction.is_buy);
+        assert_eq!(transaction.side, Side::Buy);
+        assert_eq!(transaction.is_maker, None);
         assert!(transaction.timestamp <= Utc::now());
         assert!(transaction.timestamp >= Utc::now() - chrono::Duration::seconds(1));
     }
+
+    #[test]
+    fn test_transaction_try_new_boundaries() {
+        let try_new = |price, volume| Transaction::try_new("DOGE".to_string(), price, volume, Side::Buy);
+
+        assert!(try_new(f64::MIN_POSITIVE, f64::MIN_POSITIVE).is_ok());
+        assert!(try_new(f64::MAX, f64::MAX).is_ok());
+
+        for price in [0.0, -0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(try_new(price, 100.0), Err(TransactionError::InvalidPrice(_))));
+        }
+        for volume in [0.0, -0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(try_new(1.0, volume), Err(TransactionError::InvalidVolume(_))));
+        }
+
+        // The price is checked first
+        assert_eq!(try_new(-1.0, -1.0).unwrap_err(), TransactionError::InvalidPrice(-1.0));
+    }
+
+    #[test]
+    fn test_transaction_deserialization_is_validated() {
+        let json = |price: &str, volume: &str| {
+            format!(
+                r#"{{"token":"DOGE","price":{},"volume":{},"timestamp":"2024-01-01T00:00:00Z","side":"buy"}}"#,
+                price, volume
+            )
+        };
+
+        let transaction: Transaction = serde_json::from_str(&json("0.15", "100.0")).unwrap();
+        assert_eq!(transaction.price, 0.15);
+
+        let error = serde_json::from_str::<Transaction>(&json("0", "100.0")).unwrap_err();
+        assert!(error.to_string().contains("Invalid price: 0"));
+        let error = serde_json::from_str::<Transaction>(&json("0.15", "-5")).unwrap_err();
+        assert!(error.to_string().contains("Invalid volume: -5"));
+    }
+
+    #[test]
+    fn test_trade_id_wire_form() {
+        let json = |trade_id: &str| {
+            format!(
+                r#"{{"token":"DOGE","price":0.15,"volume":100.0,"timestamp":"2024-01-01T00:00:00Z","side":"buy"{}}}"#,
+                trade_id
+            )
+        };
+
+        let transaction: Transaction = serde_json::from_str(&json("")).unwrap();
+        assert_eq!(transaction.trade_id, None);
+        assert!(!serde_json::to_string(&transaction).unwrap().contains("trade_id"));
+
+        let transaction: Transaction = serde_json::from_str(&json(r#","trade_id":42"#)).unwrap();
+        assert_eq!(transaction.trade_id, Some(TradeId::Number(42)));
+        let transaction: Transaction = serde_json::from_str(&json(r#","trade_id":"a-42""#)).unwrap();
+        assert_eq!(transaction.trade_id, Some(TradeId::Text("a-42".to_string())));
+        assert!(serde_json::to_string(&transaction).unwrap().contains(r#""trade_id":"a-42""#));
+    }
+
+    #[test]
+    fn test_side_wire_form() {
+        let json = |side: &str| {
+            format!(
+                r#"{{"token":"DOGE","price":0.15,"volume":100.0,"timestamp":"2024-01-01T00:00:00Z"{}}}"#,
+                side
+            )
+        };
+
+        let transaction: Transaction = serde_json::from_str(&json(r#","side":"sell","is_maker":true"#)).unwrap();
+        assert_eq!((transaction.side, transaction.is_maker), (Side::Sell, Some(true)));
+        let serialized = serde_json::to_string(&transaction).unwrap();
+        assert!(serialized.contains(r#""side":"sell""#) && serialized.contains(r#""is_maker":true"#));
+        assert!(!serialized.contains("is_buy"));
+
+        // Recordings made before sides were named still load
+        let transaction: Transaction = serde_json::from_str(&json(r#","is_buy":false"#)).unwrap();
+        assert_eq!(transaction.side, Side::Sell);
+        assert!(!serde_json::to_string(&transaction).unwrap().contains("is_maker"));
+
+        let error = serde_json::from_str::<Transaction>(&json("")).unwrap_err();
+        assert!(error.to_string().contains("Missing side"));
+        assert!(serde_json::from_str::<Transaction>(&json(r#","side":"long""#)).is_err());
+        assert_eq!("SELL".parse::<Side>(), Ok(Side::Sell));
+    }
 }
@@ -0,0 +1,83 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::models::{Side, Transaction};
+
+/// Consecutive trades of a token at the same price and side, combined
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct AggTrade {
+    /// Token symbol
+    pub token: String,
+    /// Price shared by the trades
+    pub price: f64,
+    /// Summed volume of the trades
+    pub volume: f64,
+    /// Number of trades combined
+    pub trades: u64,
+    /// Time of the first trade
+    pub first_trade_at: DateTime<Utc>,
+    /// Time of the last trade
+    pub last_trade_at: DateTime<Utc>,
+    /// Side of the trades' aggressor
+    pub side: Side,
+}
+
+impl AggTrade {
+    fn new(transaction: &Transaction) -> Self {
+        Self {
+            token: transaction.token.clone(),
+            price: transaction.price,
+            volume: transaction.volume,
+            trades: 1,
+            first_trade_at: transaction.timestamp,
+            last_trade_at: transaction.timestamp,
+            side: transaction.side,
+        }
+    }
+
+    /// Whether a trade continues this aggregate
+    fn accepts(&self, transaction: &Transaction, window: TimeDelta) -> bool {
+        transaction.price == self.price
+            && transaction.side == self.side
+            && transaction.timestamp - self.first_trade_at < window
+    }
+}
+
+/// Combines consecutive trades of one token into aggregate records
+///
+/// An aggregate is complete once a trade at another price or side arrives,
+/// or once its window has passed since the first trade.
+#[derive(Debug)]
+pub struct AggTradeBuilder {
+    window: TimeDelta,
+    pending: Option<AggTrade>,
+}
+
+impl AggTradeBuilder {
+    /// Create a builder that combines trades within `window` of the first
+    pub fn new(window: TimeDelta) -> Self {
+        Self { window, pending: None }
+    }
+
+    /// Add a trade, returning the aggregate it completed
+    pub fn push(&mut self, transaction: &Transaction) -> Option<AggTrade> {
+        if let Some(pending) = &mut self.pending {
+            if pending.accepts(transaction, self.window) {
+                pending.volume += transaction.volume;
+                pending.trades += 1;
+                pending.last_trade_at = pending.last_trade_at.max(transaction.timestamp);
+                return None;
+            }
+        }
+        self.pending.replace(AggTrade::new(transaction))
+    }
+
+    /// Take the pending aggregate if its window has passed at `now`
+    pub fn flush_expired(&mut self, now: DateTime<Utc>) -> Option<AggTrade> {
+        self.pending
+            .take_if(|pending| now - pending.first_trade_at >= self.window)
+    }
+}
@@ -0,0 +1,313 @@
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{Symbol, Transaction};
+use crate::services::webhooks::{WebhookDelivery, WebhookEvent};
+
+/// Longest window a percent-move alert may watch
+pub const MAX_ALERT_WINDOW_SECS: u64 = 86_400;
+
+/// Price condition an alert waits for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// The price reaches or exceeds a level
+    Above { price: f64 },
+    /// The price reaches or falls below a level
+    Below { price: f64 },
+    /// The price moves by at least `percent` from its low or high within the window
+    PercentMove { percent: f64, window_secs: u64 },
+}
+
+impl AlertCondition {
+    /// Check the condition's parameters
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            AlertCondition::Above { price } | AlertCondition::Below { price } => {
+                if !price.is_finite() || price <= 0.0 {
+                    return Err("price must be a positive number".to_string());
+                }
+            }
+            AlertCondition::PercentMove { percent, window_secs } => {
+                if !percent.is_finite() || percent <= 0.0 {
+                    return Err("percent must be a positive number".to_string());
+                }
+                if window_secs == 0 || window_secs > MAX_ALERT_WINDOW_SECS {
+                    return Err(format!(
+                        "window_secs must be between 1 and {}",
+                        MAX_ALERT_WINDOW_SECS
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Window of recent prices the condition needs
+    fn window(&self) -> Option<Duration> {
+        match self {
+            AlertCondition::PercentMove { window_secs, .. } => {
+                Some(Duration::seconds(*window_secs as i64))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Request to create an alert
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewAlert {
+    /// Token symbol
+    pub token: String,
+    /// Condition that triggers the alert
+    pub condition: AlertCondition,
+    /// URL the trigger is POSTed to, in addition to WebSocket delivery
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+/// A price alert; alerts trigger once and are then kept for reference
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Alert {
+    /// Alert ID
+    pub id: String,
+    /// Token symbol
+    pub token: String,
+    /// Condition that triggers the alert
+    pub condition: AlertCondition,
+    /// URL the trigger is POSTed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// When the alert was created
+    pub created_at: DateTime<Utc>,
+    /// When the alert triggered, if it has
+    pub triggered_at: Option<DateTime<Utc>>,
+}
+
+/// Notification that an alert's condition was met
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct AlertTrigger {
+    /// ID of the alert that triggered
+    pub alert_id: String,
+    /// Token symbol
+    pub token: String,
+    /// Condition that was met
+    pub condition: AlertCondition,
+    /// Price of the transaction that met the condition
+    pub price: f64,
+    /// Time of the transaction that met the condition
+    pub triggered_at: DateTime<Utc>,
+}
+
+/// Storage backend for alerts
+pub trait AlertStore: Send + Sync + fmt::Debug {
+    /// Insert or replace an alert
+    fn insert(&self, alert: Alert);
+    /// Get an alert by ID
+    fn get(&self, id: &str) -> Option<Alert>;
+    /// Get every alert
+    fn list(&self) -> Vec<Alert>;
+    /// Remove an alert by ID
+    fn remove(&self, id: &str) -> Option<Alert>;
+}
+
+/// Alerts kept in memory, lost on restart
+#[derive(Debug, Default)]
+pub struct InMemoryAlertStore {
+    alerts: DashMap<String, Alert>,
+}
+
+impl AlertStore for InMemoryAlertStore {
+    fn insert(&self, alert: Alert) {
+        self.alerts.insert(alert.id.clone(), alert);
+    }
+
+    fn get(&self, id: &str) -> Option<Alert> {
+        self.alerts.get(id).map(|alert| alert.clone())
+    }
+
+    fn list(&self) -> Vec<Alert> {
+        self.alerts.iter().map(|alert| alert.clone()).collect()
+    }
+
+    fn remove(&self, id: &str) -> Option<Alert> {
+        self.alerts.remove(id).map(|(_, alert)| alert)
+    }
+}
+
+/// Creates price alerts and evaluates them against processed transactions
+#[derive(Debug)]
+pub struct AlertService {
+    /// Alert storage
+    store: Arc<dyn AlertStore>,
+    /// Recent prices per token, kept for percent-move alerts
+    prices: DashMap<String, VecDeque<(DateTime<Utc>, f64)>>,
+    /// Queue of triggers to POST to webhooks
+    webhooks: Option<mpsc::UnboundedSender<WebhookDelivery>>,
+}
+
+impl AlertService {
+    /// Create a service keeping alerts in memory
+    pub fn new() -> Self {
+        Self::with_store(Arc::new(InMemoryAlertStore::default()))
+    }
+
+    /// Create a service on top of an alert store
+    pub fn with_store(store: Arc<dyn AlertStore>) -> Self {
+        Self {
+            store,
+            prices: DashMap::new(),
+            webhooks: None,
+        }
+    }
+
+    /// Queue triggers of alerts with a webhook for delivery
+    pub fn with_webhook_sender(mut self, sender: mpsc::UnboundedSender<WebhookDelivery>) -> Self {
+        self.webhooks = Some(sender);
+        self
+    }
+
+    /// Validate and store a new alert
+    pub fn create(&self, request: NewAlert) -> Result<Alert, String> {
+        let token = request.token.trim();
+        if token.is_empty() {
+            return Err("token must not be empty".to_string());
+        }
+        request.condition.validate()?;
+        if let Some(url) = &request.webhook_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err("webhook_url must be an http:// or https:// URL".to_string());
+            }
+        }
+
+        let alert = Alert {
+            id: Uuid::new_v4().to_string(),
+            token: Symbol::canonicalize(token),
+            condition: request.condition,
+            webhook_url: request.webhook_url,
+            created_at: Utc::now(),
+            triggered_at: None,
+        };
+        self.store.insert(alert.clone());
+        Ok(alert)
+    }
+
+    /// Get an alert by ID
+    pub fn get(&self, id: &str) -> Option<Alert> {
+        self.store.get(id)
+    }
+
+    /// Get all alerts, optionally for a single token, oldest first
+    pub fn list(&self, token: Option<&str>) -> Vec<Alert> {
+        let token = token.map(Symbol::canonicalize);
+        let mut alerts: Vec<Alert> = self
+            .store
+            .list()
+            .into_iter()
+            .filter(|alert| token.as_ref().is_none_or(|token| alert.token == *token))
+            .collect();
+        alerts.sort_by_key(|alert| alert.created_at);
+        alerts
+    }
+
+    /// Delete an alert by ID
+    pub fn delete(&self, id: &str) -> Option<Alert> {
+        self.store.remove(id)
+    }
+
+    /// Check the token's pending alerts against a transaction
+    ///
+    /// Returns the alerts that triggered, which are marked as triggered and
+    /// queued for webhook delivery.
+    pub fn evaluate(&self, transaction: &Transaction) -> Vec<AlertTrigger> {
+        let pending: Vec<Alert> = self
+            .store
+            .list()
+            .into_iter()
+            .filter(|alert| alert.token == transaction.token && alert.triggered_at.is_none())
+            .collect();
+
+        self.record_price(transaction, &pending);
+        let price = transaction.price;
+
+        let mut triggers = Vec::new();
+        for mut alert in pending {
+            let met = match alert.condition {
+                AlertCondition::Above { price: level } => price >= level,
+                AlertCondition::Below { price: level } => price <= level,
+                AlertCondition::PercentMove { percent, window_secs } => {
+                    let (low, high) = self
+                        .range_within(&transaction.token, transaction.timestamp, window_secs)
+                        .unwrap_or((price, price));
+                    (price - low) / low * 100.0 >= percent || (high - price) / high * 100.0 >= percent
+                }
+            };
+            if !met {
+                continue;
+            }
+
+            alert.triggered_at = Some(transaction.timestamp);
+            let trigger = AlertTrigger {
+                alert_id: alert.id.clone(),
+                token: alert.token.clone(),
+                condition: alert.condition.clone(),
+                price,
+                triggered_at: transaction.timestamp,
+            };
+            if let (Some(webhooks), Some(url)) = (&self.webhooks, &alert.webhook_url) {
+                // Sending only fails once the delivery task has stopped
+                let _ = webhooks.send(WebhookDelivery {
+                    url: url.clone(),
+                    event: WebhookEvent::Alert(trigger.clone()),
+                });
+            }
+            self.store.insert(alert);
+            triggers.push(trigger);
+        }
+        triggers
+    }
+
+    /// Record the price while a pending alert watches a window of prices
+    fn record_price(&self, transaction: &Transaction, pending: &[Alert]) {
+        let Some(window) = pending.iter().filter_map(|alert| alert.condition.window()).max() else {
+            self.prices.remove(&transaction.token);
+            return;
+        };
+
+        let mut prices = self.prices.entry(transaction.token.clone()).or_default();
+        prices.push_back((transaction.timestamp, transaction.price));
+        let cutoff = transaction.timestamp - window;
+        while prices.front().is_some_and(|(timestamp, _)| *timestamp < cutoff) {
+            prices.pop_front();
+        }
+    }
+
+    /// Lowest and highest recorded price within a window ending at `now`
+    fn range_within(&self, token: &str, now: DateTime<Utc>, window_secs: u64) -> Option<(f64, f64)> {
+        let prices = self.prices.get(token)?;
+        let cutoff = now - Duration::seconds(window_secs as i64);
+        prices
+            .iter()
+            .filter(|(timestamp, _)| *timestamp >= cutoff)
+            .fold(None, |range, &(_, price)| match range {
+                None => Some((price, price)),
+                Some((low, high)) => Some((f64::min(low, price), f64::max(high, price))),
+            })
+    }
+}
+
+impl Default for AlertService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::{DateTime, Duration, DurationRound, NaiveDateTime, Utc};
+use futures::TryStreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use parquet::errors::ParquetError;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::ArchiveConfig;
+use crate::models::{KLine, TimeInterval};
+use crate::services::export;
+
+/// Format of the hour an archive object covers, which is also its file name
+const HOUR_FORMAT: &str = "%Y-%m-%dT%H";
+
+/// Where an archive is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveLocation {
+    /// A bucket, with a key prefix possibly empty
+    S3 { bucket: String, prefix: String },
+    /// A local directory
+    Local(PathBuf),
+}
+
+impl ArchiveLocation {
+    /// Parse an `s3://bucket/prefix` or `file:///directory` URL
+    pub fn parse(url: &str) -> Result<Self, ArchiveError> {
+        let invalid = || ArchiveError::InvalidUrl(url.to_string());
+        match url.split_once("://").ok_or_else(invalid)? {
+            ("s3", location) => {
+                let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+                if bucket.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(ArchiveLocation::S3 {
+                    bucket: bucket.to_string(),
+                    prefix: prefix.trim_matches('/').to_string(),
+                })
+            }
+            ("file", directory) if !directory.is_empty() => Ok(ArchiveLocation::Local(PathBuf::from(directory))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Reason candles could not be archived or read back
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The URL is neither an `s3://` nor a `file://` URL
+    InvalidUrl(String),
+    /// The local archive directory could not be created
+    Io(std::io::Error),
+    /// The object store could not be reached or refused the request
+    Store(object_store::Error),
+    /// An archived object is not a valid candle file
+    Parquet(ParquetError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::InvalidUrl(url) => {
+                write!(f, "Invalid archive URL: {}. Expected s3://bucket/prefix or file:///directory", url)
+            }
+            ArchiveError::Io(e) => write!(f, "Cannot create archive directory: {}", e),
+            ArchiveError::Store(e) => write!(f, "Archive store error: {}", e),
+            ArchiveError::Parquet(e) => write!(f, "Invalid archived candles: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+impl From<object_store::Error> for ArchiveError {
+    fn from(e: object_store::Error) -> Self {
+        ArchiveError::Store(e)
+    }
+}
+
+impl From<ParquetError> for ArchiveError {
+    fn from(e: ParquetError) -> Self {
+        ArchiveError::Parquet(e)
+    }
+}
+
+/// Archive of candles evicted from memory, in an object store
+///
+/// Candles are kept as Parquet objects in the format of exports, one per
+/// token, interval and hour of their open time, keyed
+/// `<prefix>/<token>/<interval>/<YYYY-MM-DDTHH>.parquet`. Archiving merges
+/// candles into the objects already there, so an hour can be evicted over
+/// several prunings.
+#[derive(Debug)]
+pub struct CandleArchive {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl CandleArchive {
+    /// Create an archive keeping its objects under `prefix` of a store
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        Self {
+            store,
+            prefix: Path::from(prefix),
+        }
+    }
+
+    /// Create the configured archive; nothing connects until it is used
+    pub fn from_config(config: &ArchiveConfig) -> Result<Self, ArchiveError> {
+        match ArchiveLocation::parse(&config.url)? {
+            ArchiveLocation::S3 { bucket, prefix } => {
+                let mut builder = AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .with_allow_http(config.allow_http);
+                if let Some(endpoint) = &config.endpoint {
+                    builder = builder.with_endpoint(endpoint);
+                }
+                if let Some(region) = &config.region {
+                    builder = builder.with_region(region);
+                }
+                if let Some(access_key_id) = &config.access_key_id {
+                    builder = builder.with_access_key_id(access_key_id);
+                }
+                if let Some(secret_access_key) = &config.secret_access_key {
+                    builder = builder.with_secret_access_key(secret_access_key);
+                }
+                Ok(Self::new(Arc::new(builder.build()?), &prefix))
+            }
+            ArchiveLocation::Local(directory) => {
+                std::fs::create_dir_all(&directory).map_err(ArchiveError::Io)?;
+                Ok(Self::new(Arc::new(LocalFileSystem::new_with_prefix(directory)?), ""))
+            }
+        }
+    }
+
+    /// Prefix of a series' objects
+    fn series(&self, token: &str, interval: TimeInterval) -> Path {
+        self.prefix.child(token).child(interval.as_str().as_ref())
+    }
+
+    /// Key of the object holding a series' candles opened in an hour
+    pub fn object_path(&self, token: &str, interval: TimeInterval, hour: DateTime<Utc>) -> Path {
+        self.series(token, interval)
+            .child(format!("{}.parquet", hour.format(HOUR_FORMAT)))
+    }
+
+    /// Archive candles, merging them into the hourly objects already stored
+    ///
+    /// Returns the number of objects written. A candle replaces an archived
+    /// one with the same open time.
+    pub async fn archive(&self, klines: &[KLine]) -> Result<usize, ArchiveError> {
+        let mut objects: HashMap<Path, Vec<&KLine>> = HashMap::new();
+        for kline in klines {
+            objects
+                .entry(self.object_path(&kline.token, kline.interval, hour_of(kline.timestamp)))
+                .or_default()
+                .push(kline);
+        }
+
+        for (path, klines) in &objects {
+            let mut merged: BTreeMap<DateTime<Utc>, KLine> = self
+                .read_object(path)
+                .await?
+                .into_iter()
+                .map(|kline| (kline.timestamp, kline))
+                .collect();
+            merged.extend(klines.iter().map(|kline| (kline.timestamp, (*kline).clone())));
+            let merged: Vec<KLine> = merged.into_values().collect();
+            self.store.put(path, PutPayload::from(export::to_parquet(&merged)?)).await?;
+        }
+        Ok(objects.len())
+    }
+
+    /// Candles of an object, none if it does not exist
+    async fn read_object(&self, path: &Path) -> Result<Vec<KLine>, ArchiveError> {
+        match self.store.get(path).await {
+            Ok(object) => Ok(export::from_parquet(object.bytes().await?)?),
+            Err(object_store::Error::NotFound { .. }) => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Objects of a series with the hour each covers, oldest first
+    async fn hours(&self, token: &str, interval: TimeInterval) -> Result<Vec<(DateTime<Utc>, Path)>, ArchiveError> {
+        let objects: Vec<_> = self.store.list(Some(&self.series(token, interval))).try_collect().await?;
+        let mut hours: Vec<(DateTime<Utc>, Path)> = objects
+            .into_iter()
+            .filter_map(|object| {
+                let stem = object.location.filename()?.strip_suffix(".parquet")?;
+                let hour = NaiveDateTime::parse_from_str(&format!("{}:00", stem), "%Y-%m-%dT%H:%M").ok()?;
+                Some((hour.and_utc(), object.location))
+            })
+            .collect();
+        hours.sort_by_key(|(hour, _)| *hour);
+        Ok(hours)
+    }
+
+    /// Archived candles opened at or after `start` and before `end`, oldest first
+    pub async fn read_range(
+        &self,
+        token: &str,
+        interval: TimeInterval,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<KLine>, ArchiveError> {
+        let mut klines = Vec::new();
+        for (hour, path) in self.hours(token, interval).await? {
+            if hour + Duration::hours(1) <= start || hour >= end {
+                continue;
+            }
+            klines.extend(
+                self.read_object(&path)
+                    .await?
+                    .into_iter()
+                    .filter(|kline| kline.timestamp >= start && kline.timestamp < end),
+            );
+        }
+        Ok(klines)
+    }
+
+    /// The `limit` newest archived candles opened before `before`, oldest first
+    pub async fn read_before(
+        &self,
+        token: &str,
+        interval: TimeInterval,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<KLine>, ArchiveError> {
+        let mut klines = Vec::new();
+        for (hour, path) in self.hours(token, interval).await?.into_iter().rev() {
+            if klines.len() >= limit {
+                break;
+            }
+            if hour >= before {
+                continue;
+            }
+            let mut object = self.read_object(&path).await?;
+            object.retain(|kline| kline.timestamp < before);
+            klines.splice(0..0, object);
+        }
+        Ok(klines.split_off(klines.len().saturating_sub(limit)))
+    }
+
+    /// The `limit` oldest archived candles opened after `after` and before `before`, oldest first
+    pub async fn read_after(
+        &self,
+        token: &str,
+        interval: TimeInterval,
+        after: DateTime<Utc>,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<KLine>, ArchiveError> {
+        let mut klines = Vec::new();
+        for (hour, path) in self.hours(token, interval).await? {
+            if klines.len() >= limit || hour >= before {
+                break;
+            }
+            if hour + Duration::hours(1) <= after {
+                continue;
+            }
+            let object = self.read_object(&path).await?;
+            klines.extend(object.into_iter().filter(|kline| kline.timestamp > after && kline.timestamp < before));
+        }
+        klines.truncate(limit);
+        Ok(klines)
+    }
+}
+
+/// Start of the hour a time falls in
+fn hour_of(timestamp: DateTime<Utc>) -> DateTime<Utc> {
+    timestamp.duration_trunc(Duration::hours(1)).unwrap_or(timestamp)
+}
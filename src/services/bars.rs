@@ -0,0 +1,275 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::models::{TimeInterval, Transaction};
+use crate::services::{indicators, KLineService};
+
+/// Default ATR period for ATR-based bar sizes
+pub const DEFAULT_ATR_PERIOD: usize = 14;
+
+/// Default candle interval the ATR of ATR-based bar sizes is computed on
+pub const DEFAULT_ATR_INTERVAL: TimeInterval = TimeInterval::Minute1;
+
+/// Most Renko bricks emitted for a single trade
+///
+/// A price jump across more bricks only emits the last ones, so a tiny brick
+/// size cannot flood subscribers; later bricks still line up with the grid.
+pub const MAX_BRICKS_PER_TRADE: usize = 100;
+
+/// Relative tolerance for prices that land exactly on a brick or range boundary
+const TOLERANCE: f64 = 1e-9;
+
+/// Kind of price-based bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BarType {
+    /// Fixed-height bricks started each time the price moves a brick beyond the last one
+    Renko,
+    /// Bars closed once their high-low range reaches the size
+    Range,
+}
+
+impl BarType {
+    /// Name of the bar type, as used in subscriptions
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BarType::Renko => "renko",
+            BarType::Range => "range",
+        }
+    }
+}
+
+impl FromStr for BarType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "renko" => Ok(BarType::Renko),
+            "range" => Ok(BarType::Range),
+            _ => Err(format!("Invalid bar type: {}", s)),
+        }
+    }
+}
+
+/// Brick size or range of price-based bars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BarSize {
+    /// A fixed price step
+    Fixed(f64),
+    /// The token's ATR over closed candles, taken when bar construction starts
+    Atr { interval: TimeInterval, period: usize },
+}
+
+impl BarSize {
+    /// Size from request parameters: a fixed `size`, or else the ATR of `interval` candles
+    pub fn from_parts(size: Option<f64>, interval: Option<&str>, atr_period: Option<usize>) -> Result<Self, String> {
+        if let Some(size) = size {
+            return if size.is_finite() && size > 0.0 {
+                Ok(BarSize::Fixed(size))
+            } else {
+                Err(format!("Invalid size: {}. Expected a positive number", size))
+            };
+        }
+
+        let interval = interval.map(str::parse).transpose()?.unwrap_or(DEFAULT_ATR_INTERVAL);
+        let period = atr_period.unwrap_or(DEFAULT_ATR_PERIOD);
+        if !(1..=indicators::MAX_PERIOD).contains(&period) {
+            return Err(format!("Invalid atr_period: {}. Expected 1 to {}", period, indicators::MAX_PERIOD));
+        }
+        Ok(BarSize::Atr { interval, period })
+    }
+
+    /// Price step for a token, or `None` while too few candles closed for the ATR
+    pub fn resolve(&self, kline_service: &KLineService, token: &str) -> Option<f64> {
+        match *self {
+            BarSize::Fixed(size) => Some(size),
+            BarSize::Atr { interval, period } => {
+                indicators::latest_atr(kline_service, token, interval, period).filter(|atr| *atr > 0.0)
+            }
+        }
+    }
+}
+
+impl Eq for BarSize {}
+
+impl Hash for BarSize {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            BarSize::Fixed(size) => size.to_bits().hash(state),
+            BarSize::Atr { interval, period } => {
+                interval.hash(state);
+                period.hash(state);
+            }
+        }
+    }
+}
+
+impl fmt::Display for BarSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarSize::Fixed(size) => write!(f, "{}", size),
+            BarSize::Atr { interval, period } => write!(f, "atr:{}:{}", interval.as_str(), period),
+        }
+    }
+}
+
+/// Bars of one type and size for a token
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BarSpec {
+    pub token: String,
+    pub bar_type: BarType,
+    pub size: BarSize,
+}
+
+/// A Renko brick or range bar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct Bar {
+    pub token: String,
+    pub bar_type: BarType,
+    /// Brick size or range the bar was built with
+    pub size: f64,
+    /// Time of the first trade in the bar
+    pub open_time: DateTime<Utc>,
+    /// Time of the trade that completed the bar
+    pub close_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trade_count: u64,
+}
+
+/// Builds bars of one type and size from a token's trades
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    token: String,
+    bar_type: BarType,
+    size: f64,
+    /// Trades since the last completed bar
+    forming: Option<Bar>,
+    /// Low and high of the last Renko brick, or the first price before any brick
+    last_brick: Option<(f64, f64)>,
+}
+
+impl BarBuilder {
+    /// Create a builder for bars of a fixed size
+    pub fn new(token: String, bar_type: BarType, size: f64) -> Self {
+        Self {
+            token,
+            bar_type,
+            size,
+            forming: None,
+            last_brick: None,
+        }
+    }
+
+    /// Price step the bars are built with
+    pub fn size(&self) -> f64 {
+        self.size
+    }
+
+    /// Add a trade, returning the bars it completed
+    pub fn push(&mut self, transaction: &Transaction) -> Vec<Bar> {
+        let price = transaction.price;
+        let forming = self.forming.get_or_insert_with(|| Bar {
+            token: self.token.clone(),
+            bar_type: self.bar_type,
+            size: self.size,
+            open_time: transaction.timestamp,
+            close_time: transaction.timestamp,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 0.0,
+            trade_count: 0,
+        });
+        forming.high = forming.high.max(price);
+        forming.low = forming.low.min(price);
+        forming.close = price;
+        forming.close_time = transaction.timestamp;
+        forming.volume += transaction.volume;
+        forming.trade_count += 1;
+
+        match self.bar_type {
+            BarType::Range => {
+                if forming.high - forming.low >= self.size * (1.0 - TOLERANCE) {
+                    self.forming.take().into_iter().collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            BarType::Renko => self.renko_bricks(transaction),
+        }
+    }
+
+    /// Bricks completed by a trade at `transaction.price`
+    ///
+    /// A brick continues the trend once the price moves a brick beyond the
+    /// last one, and reverses once it moves a brick below its opposite edge.
+    fn renko_bricks(&mut self, transaction: &Transaction) -> Vec<Bar> {
+        let price = transaction.price;
+        let (low, high) = *self.last_brick.get_or_insert((price, price));
+        let steps = |distance: f64| (distance / self.size + TOLERANCE).floor().max(0.0) as usize;
+
+        let (start, direction, count) = match (steps(price - high), steps(low - price)) {
+            (up, _) if up > 0 => (high, 1.0, up),
+            (_, down) if down > 0 => (low, -1.0, down),
+            _ => return Vec::new(),
+        };
+
+        let Some(forming) = self.forming.take() else {
+            return Vec::new();
+        };
+        let skipped = count.saturating_sub(MAX_BRICKS_PER_TRADE);
+        let bricks: Vec<Bar> = (skipped..count)
+            .map(|index| {
+                let open = start + direction * self.size * index as f64;
+                let close = open + direction * self.size;
+                // Trades are counted towards the first brick they completed
+                let (open_time, volume, trade_count) = if index == skipped {
+                    (forming.open_time, forming.volume, forming.trade_count)
+                } else {
+                    (transaction.timestamp, 0.0, 0)
+                };
+                Bar {
+                    open_time,
+                    open,
+                    high: open.max(close),
+                    low: open.min(close),
+                    close,
+                    volume,
+                    trade_count,
+                    ..forming.clone()
+                }
+            })
+            .collect();
+
+        if let Some(last) = bricks.last() {
+            self.last_brick = Some((last.low, last.high));
+        }
+        bricks
+    }
+}
+
+/// Bars built from consecutive trades of one token, oldest first
+pub fn build_bars(transactions: &[Transaction], bar_type: BarType, size: f64) -> Vec<Bar> {
+    let Some(first) = transactions.first() else {
+        return Vec::new();
+    };
+    let mut builder = BarBuilder::new(first.token.clone(), bar_type, size);
+    transactions
+        .iter()
+        .flat_map(|transaction| builder.push(transaction))
+        .collect()
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::models::{KLine, TimeInterval};
+use crate::services::{KLineCursor, KLineService};
+
+/// Candles before the first requested one used to seed Heikin-Ashi opens
+///
+/// Each open averages the previous candle's open and close, so the influence
+/// of the seed halves with every candle and is negligible after this many.
+pub const HEIKIN_ASHI_WARMUP: usize = 64;
+
+/// How stored candles are presented to clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CandleType {
+    /// Candles as aggregated from trades
+    #[default]
+    Regular,
+    /// Heikin-Ashi candles derived from the regular ones
+    HeikinAshi,
+}
+
+impl CandleType {
+    /// Name of the candle type, as used in queries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandleType::Regular => "regular",
+            CandleType::HeikinAshi => "heikin_ashi",
+        }
+    }
+
+    /// Present candles of one token and interval, sorted oldest first
+    ///
+    /// Heikin-Ashi opens depend on the previous candle, so the candles stored
+    /// before the first one are read from the service as warm-up.
+    pub fn apply(&self, kline_service: &KLineService, klines: Vec<KLine>) -> Vec<KLine> {
+        match (self, klines.first()) {
+            (CandleType::HeikinAshi, Some(first)) => {
+                let open = heikin_ashi_open(kline_service, &first.token, first.interval, first.timestamp);
+                heikin_ashi_from(&klines, open)
+            }
+            _ => klines,
+        }
+    }
+}
+
+impl FromStr for CandleType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regular" => Ok(CandleType::Regular),
+            "heikin_ashi" => Ok(CandleType::HeikinAshi),
+            _ => Err(format!("Invalid candle type: {}", s)),
+        }
+    }
+}
+
+/// Heikin-Ashi candle for a regular candle, given its Heikin-Ashi open
+///
+/// Without an open (no earlier candle), the midpoint of the regular open and
+/// close is used.
+pub fn heikin_ashi_candle(kline: &KLine, open: Option<f64>) -> KLine {
+    let close = (kline.open + kline.high + kline.low + kline.close) / 4.0;
+    let open = open.unwrap_or((kline.open + kline.close) / 2.0);
+    KLine {
+        open,
+        high: kline.high.max(open).max(close),
+        low: kline.low.min(open).min(close),
+        close,
+        ..kline.clone()
+    }
+}
+
+/// Heikin-Ashi open of the candle following `previous`
+pub fn next_heikin_ashi_open(previous: &KLine) -> f64 {
+    (previous.open + previous.close) / 2.0
+}
+
+/// Heikin-Ashi candles for consecutive regular candles, oldest first
+pub fn heikin_ashi(klines: &[KLine]) -> Vec<KLine> {
+    heikin_ashi_from(klines, None)
+}
+
+fn heikin_ashi_from(klines: &[KLine], mut open: Option<f64>) -> Vec<KLine> {
+    klines
+        .iter()
+        .map(|kline| {
+            let candle = heikin_ashi_candle(kline, open);
+            open = Some(next_heikin_ashi_open(&candle));
+            candle
+        })
+        .collect()
+}
+
+/// Heikin-Ashi open of a token's candle opened at `timestamp`
+///
+/// Returns `None` when no earlier candle is stored.
+pub fn heikin_ashi_open(
+    kline_service: &KLineService,
+    token: &str,
+    interval: TimeInterval,
+    timestamp: DateTime<Utc>,
+) -> Option<f64> {
+    let previous = kline_service
+        .get_klines_page(token, interval, KLineCursor::Before(timestamp), HEIKIN_ASHI_WARMUP)
+        .klines;
+    heikin_ashi(&previous).last().map(next_heikin_ashi_open)
+}
@@ -0,0 +1,290 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::config::ClickHouseConfig;
+use crate::models::{KLine, Transaction};
+
+/// Format of the `DateTime64(3)` columns
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Delay before the second insert attempt of a batch, doubled after each further failure
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Something to insert into ClickHouse
+#[derive(Debug, Clone)]
+pub enum ClickHouseRecord {
+    /// An ingested trade
+    Trade(Transaction),
+    /// A closed candle
+    Kline(KLine),
+}
+
+/// Queue of rows to insert into ClickHouse, handed to the transaction pipeline
+///
+/// Writing never waits: when ClickHouse is slow or down and the queue is
+/// full, rows are dropped.
+#[derive(Debug, Clone)]
+pub struct ClickHouseWriter {
+    sender: mpsc::Sender<ClickHouseRecord>,
+}
+
+impl ClickHouseWriter {
+    /// Create a writer queueing up to `capacity` rows, with the receiving end of its queue
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<ClickHouseRecord>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (Self { sender }, receiver)
+    }
+
+    /// Queue an ingested trade
+    pub fn write_trade(&self, transaction: &Transaction) {
+        self.write(ClickHouseRecord::Trade(transaction.clone()));
+    }
+
+    /// Queue a closed candle
+    pub fn write_kline(&self, kline: &KLine) {
+        self.write(ClickHouseRecord::Kline(kline.clone()));
+    }
+
+    fn write(&self, record: ClickHouseRecord) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(record) {
+            tracing::debug!("ClickHouse queue full, dropping row");
+        }
+    }
+}
+
+/// Row of the trades table
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeRow {
+    pub token: String,
+    pub price: f64,
+    pub volume: f64,
+    pub side: &'static str,
+    pub timestamp: String,
+    pub trade_id: Option<String>,
+    pub is_maker: Option<bool>,
+    pub source: Option<String>,
+}
+
+impl From<&Transaction> for TradeRow {
+    fn from(transaction: &Transaction) -> Self {
+        Self {
+            token: transaction.token.clone(),
+            price: transaction.price,
+            volume: transaction.volume,
+            side: transaction.side.as_str(),
+            timestamp: format_timestamp(transaction.timestamp),
+            trade_id: transaction.trade_id.as_ref().map(|id| id.to_string()),
+            is_maker: transaction.is_maker,
+            source: transaction.source.clone(),
+        }
+    }
+}
+
+/// Row of the candles table
+#[derive(Debug, Clone, Serialize)]
+pub struct KLineRow {
+    pub token: String,
+    pub interval: String,
+    pub open_time: String,
+    pub close_time: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub is_partial: bool,
+}
+
+impl From<&KLine> for KLineRow {
+    fn from(kline: &KLine) -> Self {
+        Self {
+            token: kline.token.clone(),
+            interval: kline.interval.as_str().into_owned(),
+            open_time: format_timestamp(kline.timestamp),
+            close_time: format_timestamp(kline.close_time()),
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            buy_volume: kline.buy_volume,
+            sell_volume: kline.sell_volume,
+            is_partial: kline.is_partial,
+        }
+    }
+}
+
+/// A batch that could not be inserted, as kept in the dead-letter file
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a, T> {
+    table: &'a str,
+    failed_at: DateTime<Utc>,
+    error: &'a str,
+    rows: &'a [T],
+}
+
+/// Inserts rows through the ClickHouse HTTP interface
+///
+/// Rows are sent as `JSONEachRow` in one `INSERT` per batch. A batch
+/// still failing after `max_attempts` is appended to the dead-letter file,
+/// one JSON line per batch, so it can be inserted by hand later.
+#[derive(Clone)]
+pub struct ClickHouseSink {
+    config: ClickHouseConfig,
+    client: awc::Client,
+}
+
+impl ClickHouseSink {
+    /// Create a sink for the configured server; must be used on the actix runtime
+    pub fn new(config: ClickHouseConfig) -> Self {
+        let client = awc::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .finish();
+        Self { config, client }
+    }
+
+    /// Statement inserting a batch into a table
+    pub fn insert_query(&self, table: &str) -> String {
+        format!("INSERT INTO {}.{} FORMAT JSONEachRow", self.config.database, table)
+    }
+
+    /// Insert rows into a table, retrying with exponential backoff and dead-lettering them if it keeps failing
+    ///
+    /// Returns whether the rows were inserted.
+    pub async fn insert<T: Serialize>(&self, table: &str, rows: &[T]) -> bool {
+        if rows.is_empty() {
+            return true;
+        }
+        let mut body = Vec::new();
+        for row in rows {
+            if let Err(e) = serde_json::to_writer(&mut body, row) {
+                tracing::error!("Failed to encode ClickHouse row: {}", e);
+                return false;
+            }
+            body.push(b'\n');
+        }
+
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut attempt = 1;
+        let error = loop {
+            let error = match self.send(table, body.clone()).await {
+                Ok(()) => return true,
+                Err(e) => e,
+            };
+            if attempt >= self.config.max_attempts {
+                break error;
+            }
+            tracing::debug!("ClickHouse insert into {} failed ({}), retrying in {:?}", table, error, delay);
+            actix_web::rt::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        };
+
+        tracing::warn!(
+            "Giving up on inserting {} rows into ClickHouse table {} after {} attempts: {}",
+            rows.len(),
+            table,
+            attempt,
+            error
+        );
+        if let Err(e) = self.dead_letter(table, rows, &error) {
+            tracing::error!("Failed to write {} rows to the ClickHouse dead-letter file: {}", rows.len(), e);
+        }
+        false
+    }
+
+    async fn send(&self, table: &str, body: Vec<u8>) -> Result<(), String> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .query(&[("query", self.insert_query(table))])
+            .map_err(|e| e.to_string())?
+            .content_type("application/x-ndjson");
+        if let Some(user) = &self.config.user {
+            request = request.insert_header(("X-ClickHouse-User", user.as_str()));
+        }
+        if let Some(password) = &self.config.password {
+            request = request.insert_header(("X-ClickHouse-Key", password.as_str()));
+        }
+
+        match request.send_body(body).await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(mut response) => {
+                let message = response.body().await.map(|body| String::from_utf8_lossy(&body).trim().to_string());
+                Err(format!("status {}: {}", response.status(), message.unwrap_or_default()))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Append a failed batch to the dead-letter file
+    fn dead_letter<T: Serialize>(&self, table: &str, rows: &[T], error: &str) -> io::Result<()> {
+        let path = Path::new(&self.config.dead_letter_path);
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let letter = DeadLetter {
+            table,
+            failed_at: Utc::now(),
+            error,
+            rows,
+        };
+        let mut line = serde_json::to_vec(&letter)?;
+        line.push(b'\n');
+        OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)
+    }
+}
+
+/// Insert queued rows in batches until the writers are dropped
+///
+/// A table's batch is flushed once it reaches `batch_size` rows, and every
+/// batch at least every `flush_interval_ms`. Rows left when the queue closes
+/// are flushed before returning. Must run on the actix runtime, as the HTTP
+/// client is not `Send`.
+pub async fn run_clickhouse_sink(mut receiver: mpsc::Receiver<ClickHouseRecord>, config: ClickHouseConfig) {
+    let (batch_size, flush_interval) = (config.batch_size.max(1), Duration::from_millis(config.flush_interval_ms));
+    let (trades_table, klines_table) = (config.trades_table.clone(), config.klines_table.clone());
+    let sink = ClickHouseSink::new(config);
+    let mut trades: Vec<TradeRow> = Vec::new();
+    let mut klines: Vec<KLineRow> = Vec::new();
+    let mut ticker = actix_web::rt::time::interval(flush_interval);
+
+    loop {
+        tokio::select! {
+            record = receiver.recv() => match record {
+                Some(ClickHouseRecord::Trade(transaction)) => {
+                    trades.push(TradeRow::from(&transaction));
+                    if trades.len() >= batch_size {
+                        sink.insert(&trades_table, &std::mem::take(&mut trades)).await;
+                    }
+                }
+                Some(ClickHouseRecord::Kline(kline)) => {
+                    klines.push(KLineRow::from(&kline));
+                    if klines.len() >= batch_size {
+                        sink.insert(&klines_table, &std::mem::take(&mut klines)).await;
+                    }
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                sink.insert(&trades_table, &std::mem::take(&mut trades)).await;
+                sink.insert(&klines_table, &std::mem::take(&mut klines)).await;
+            }
+        }
+    }
+
+    sink.insert(&trades_table, &trades).await;
+    sink.insert(&klines_table, &klines).await;
+    tracing::info!("ClickHouse sink stopped");
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
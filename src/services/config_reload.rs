@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use notify::{RecursiveMode, Watcher};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+use crate::api::WsManager;
+use crate::config::Config;
+use crate::logging::LogLevelHandle;
+use crate::services::{SymbolTable, TokenRegistry};
+
+/// Fields that take effect without a restart
+pub const RELOADABLE_FIELDS: &[&str] = &[
+    "tokens.supported_tokens",
+    "tokens.synthetic_tokens",
+    "data_generation.interval_ms",
+    "data_generation.volatility",
+    "data_generation.arrival",
+    "logging.level",
+    "performance.kline_retention_hours",
+];
+
+/// Editors often save a file in several writes, so changes are batched
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Outcome of reloading the configuration files
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+pub struct ConfigUpdate {
+    /// Changed fields whose new values were applied
+    pub applied: Vec<String>,
+    /// Changed fields that need a restart and were ignored
+    pub rejected: Vec<String>,
+    /// When the configuration was reloaded
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Apply the reloadable fields of a freshly loaded configuration
+///
+/// Returns the configuration to run with and the changed fields, split into
+/// the ones applied and the ones that need a restart.
+pub fn reconcile(current: &Config, loaded: &Config) -> (Config, ConfigUpdate) {
+    let mut changed = Vec::new();
+    if let (Ok(current), Ok(loaded)) = (toml::Value::try_from(current), toml::Value::try_from(loaded)) {
+        diff_paths("", &current, &loaded, &mut changed);
+    }
+    let (applied, rejected): (Vec<String>, Vec<String>) = changed
+        .into_iter()
+        .partition(|path| RELOADABLE_FIELDS.contains(&path.as_str()));
+
+    let mut updated = current.clone();
+    updated.tokens.supported_tokens = loaded.tokens.supported_tokens.clone();
+    updated.tokens.synthetic_tokens = loaded.tokens.synthetic_tokens.clone();
+    updated.data_generation.interval_ms = loaded.data_generation.interval_ms;
+    updated.data_generation.volatility = loaded.data_generation.volatility;
+    updated.data_generation.arrival = loaded.data_generation.arrival;
+    updated.logging.level = loaded.logging.level.clone();
+    updated.performance.kline_retention_hours = loaded.performance.kline_retention_hours;
+
+    let update = ConfigUpdate {
+        applied,
+        rejected,
+        timestamp: Utc::now(),
+    };
+    (updated, update)
+}
+
+/// Collect the dotted paths of fields that differ between two values
+fn diff_paths(prefix: &str, a: &toml::Value, b: &toml::Value, out: &mut Vec<String>) {
+    match (a, b) {
+        (toml::Value::Table(a), toml::Value::Table(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => diff_paths(&path, a, b, out),
+                    _ => out.push(path),
+                }
+            }
+        }
+        (a, b) if a != b => out.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// Reloads the configuration when files in the config directory change
+pub struct ConfigWatcher {
+    /// Directory to watch
+    dir: PathBuf,
+    /// Configuration currently in effect
+    current: Config,
+    /// Publishes applied configurations to running tasks
+    sender: watch::Sender<Arc<Config>>,
+    /// Notifies admin subscribers of reloads
+    ws_manager: Arc<WsManager>,
+    /// Registers tokens added to the configuration
+    token_registry: Arc<TokenRegistry>,
+    /// Applies log level changes, if logging was installed by the process
+    log_level: Option<LogLevelHandle>,
+    /// Takes price and quantity rules of reloaded tokens, if rounding is in use
+    symbols: Option<Arc<SymbolTable>>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        sender: watch::Sender<Arc<Config>>,
+        ws_manager: Arc<WsManager>,
+        token_registry: Arc<TokenRegistry>,
+    ) -> Self {
+        let current = sender.borrow().as_ref().clone();
+        Self {
+            dir: dir.into(),
+            current,
+            sender,
+            ws_manager,
+            token_registry,
+            log_level: None,
+            symbols: None,
+        }
+    }
+
+    /// Apply log level changes through the given handle
+    pub fn with_log_level(mut self, log_level: LogLevelHandle) -> Self {
+        self.log_level = Some(log_level);
+        self
+    }
+
+    /// Keep the price and quantity rules of a symbol table up to date
+    pub fn with_symbols(mut self, symbols: Arc<SymbolTable>) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// Watch the config directory until the process exits
+    pub async fn run(mut self) -> notify::Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if matches!(event, Ok(event) if !event.kind.is_access()) {
+                let _ = tx.send(());
+            }
+        })?;
+        watcher.watch(&self.dir, RecursiveMode::NonRecursive)?;
+        tracing::info!("Watching {} for configuration changes", self.dir.display());
+
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+            self.reload();
+        }
+        Ok(())
+    }
+
+    /// Load the configuration files and apply what can change at runtime
+    fn reload(&mut self) {
+        let loaded = match Config::load() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!("Ignoring invalid configuration change: {}", e);
+                return;
+            }
+        };
+
+        let (updated, update) = reconcile(&self.current, &loaded);
+        if update.applied.is_empty() && update.rejected.is_empty() {
+            return;
+        }
+        if !update.rejected.is_empty() {
+            tracing::warn!(
+                "Configuration changes to {} require a restart and were ignored",
+                update.rejected.join(", ")
+            );
+        }
+
+        if !update.applied.is_empty() {
+            if let Some(log_level) = &self.log_level {
+                if let Err(e) = log_level.set_level(&updated.logging.level) {
+                    tracing::warn!("Failed to change the log level: {}", e);
+                }
+            }
+            for token in updated.get_supported_tokens() {
+                self.token_registry.register(&token);
+            }
+            if let Some(symbols) = &self.symbols {
+                symbols.apply_config(&updated);
+            }
+            self.sender.send_replace(Arc::new(updated.clone()));
+            self.current = updated;
+            tracing::info!("Applied configuration changes to {}", update.applied.join(", "));
+        }
+
+        self.ws_manager.broadcast_config_updated(&update);
+    }
+}
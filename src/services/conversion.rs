@@ -0,0 +1,205 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::config::ConversionRateConfig;
+use crate::models::{KLine, Symbol, Ticker, TimeInterval};
+use crate::services::{KLineCursor, KLineService};
+
+/// Where the rate between two quote assets comes from
+#[derive(Debug, Clone, PartialEq)]
+enum RateSource {
+    /// Fixed amount of the target asset per unit of the source asset
+    Static(f64),
+    /// Close of a pair's candles, inverted when the pair is priced in the source asset
+    Pair { symbol: String, invert: bool },
+}
+
+impl RateSource {
+    /// Source of the rate converting the other way
+    fn inverse(&self) -> Self {
+        match self {
+            RateSource::Static(rate) => RateSource::Static(1.0 / rate),
+            RateSource::Pair { symbol, invert } => RateSource::Pair {
+                symbol: symbol.clone(),
+                invert: !invert,
+            },
+        }
+    }
+}
+
+/// Reason prices could not be converted
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// The token is not a valid symbol
+    InvalidSymbol(String),
+    /// No rate is configured between the two quote assets
+    NoRate { from: String, to: String },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::InvalidSymbol(e) => f.write_str(e),
+            ConversionError::NoRate { from, to } => write!(f, "No conversion rate from {} to {}", from, to),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Serves prices of a pair in another quote asset
+///
+/// A candle is converted at the rate of its open time: a fixed rate, or the
+/// close of the rate pair's candle covering that time. Volumes are amounts
+/// of the base asset and stay as they are. Candles older than the rate
+/// pair's history have no rate and are left out.
+#[derive(Debug, Default)]
+pub struct QuoteConverter {
+    sources: HashMap<(String, String), RateSource>,
+}
+
+impl QuoteConverter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a converter with the configured rates
+    pub fn from_config(rates: &[ConversionRateConfig]) -> Self {
+        rates.iter().fold(Self::new(), |converter, rate| match (&rate.pair, rate.rate) {
+            (Some(pair), _) => converter.with_pair(&rate.from, &rate.to, pair),
+            (None, Some(fixed)) => converter.with_rate(&rate.from, &rate.to, fixed),
+            (None, None) => converter,
+        })
+    }
+
+    /// Convert from `from` to `to` at a fixed amount of `to` per unit of `from`
+    pub fn with_rate(mut self, from: &str, to: &str, rate: f64) -> Self {
+        self.insert(from, to, RateSource::Static(rate));
+        self
+    }
+
+    /// Convert between the two quote assets of `pair` at its candles' closes
+    pub fn with_pair(mut self, from: &str, to: &str, pair: &str) -> Self {
+        let symbol = Symbol::canonicalize(pair);
+        // BTC-USDT prices BTC in USDT, so converting USDT to BTC divides by them
+        let invert = pair.parse::<Symbol>().is_ok_and(|pair| pair.base().eq_ignore_ascii_case(to));
+        self.insert(from, to, RateSource::Pair { symbol, invert });
+        self
+    }
+
+    fn insert(&mut self, from: &str, to: &str, source: RateSource) {
+        let (from, to) = (from.to_uppercase(), to.to_uppercase());
+        self.sources.insert((to.clone(), from.clone()), source.inverse());
+        self.sources.insert((from, to), source);
+    }
+
+    /// Rate source converting `token`'s prices to `quote`, or `None` if it is already quoted in it
+    fn source(&self, token: &str, quote: &str) -> Result<Option<(Symbol, &RateSource)>, ConversionError> {
+        let symbol: Symbol = token.parse().map_err(ConversionError::InvalidSymbol)?;
+        let quote = quote.to_uppercase();
+        if symbol.quote() == quote {
+            return Ok(None);
+        }
+        let source = self
+            .sources
+            .get(&(symbol.quote().to_string(), quote.clone()))
+            .ok_or_else(|| ConversionError::NoRate {
+                from: symbol.quote().to_string(),
+                to: quote.clone(),
+            })?;
+        Ok(Some((Symbol::new(symbol.base(), &quote), source)))
+    }
+
+    /// Name of `token`'s pair quoted in `quote`, if its prices can be converted to it
+    pub fn quoted_token(&self, token: &str, quote: &str) -> Result<String, ConversionError> {
+        Ok(match self.source(token, quote)? {
+            Some((converted, _)) => converted.to_string(),
+            None => token.to_string(),
+        })
+    }
+
+    /// Convert `token`'s candles to prices quoted in `quote`
+    pub fn convert_klines(
+        &self,
+        kline_service: &KLineService,
+        token: &str,
+        klines: Vec<KLine>,
+        quote: &str,
+    ) -> Result<Vec<KLine>, ConversionError> {
+        let Some((converted, source)) = self.source(token, quote)? else {
+            return Ok(klines);
+        };
+        let token = converted.to_string();
+        let rates = rates_at(kline_service, source, &klines);
+        Ok(klines
+            .into_iter()
+            .zip(rates)
+            .filter_map(|(kline, rate)| {
+                let rate = rate?;
+                Some(KLine {
+                    token: token.clone(),
+                    open: kline.open * rate,
+                    high: kline.high * rate,
+                    low: kline.low * rate,
+                    close: kline.close * rate,
+                    ..kline
+                })
+            })
+            .collect())
+    }
+
+    /// Compute 24-hour statistics for `token` as [`KLineService::get_ticker`] would, quoted in `quote`
+    pub fn get_ticker(
+        &self,
+        kline_service: &KLineService,
+        token: &str,
+        quote: &str,
+    ) -> Result<Option<Ticker>, ConversionError> {
+        let Some((converted, _)) = self.source(token, quote)? else {
+            return Ok(kline_service.get_ticker(token));
+        };
+        let now = Utc::now();
+        let klines = kline_service.get_klines(token, TimeInterval::Minute1, now - Duration::hours(24), now, None);
+        let klines = self.convert_klines(kline_service, token, klines, quote)?;
+        Ok(Ticker::from_klines(&converted.to_string(), &klines, now))
+    }
+}
+
+/// Rate at the open time of each candle, if one is known
+fn rates_at(kline_service: &KLineService, source: &RateSource, klines: &[KLine]) -> Vec<Option<f64>> {
+    let (symbol, invert) = match source {
+        RateSource::Static(rate) => return vec![Some(*rate); klines.len()],
+        RateSource::Pair { symbol, invert } => (symbol, *invert),
+    };
+    let (Some(start), Some(end)) = (
+        klines.iter().map(|kline| kline.timestamp).min(),
+        klines.iter().map(|kline| kline.timestamp).max(),
+    ) else {
+        return Vec::new();
+    };
+
+    // Candles of activity intervals start at any time, so they take the 1m candle covering them
+    let interval = klines
+        .first()
+        .map(|kline| kline.interval)
+        .filter(TimeInterval::is_time_based)
+        .unwrap_or(TimeInterval::Minute1);
+    let mut closes: Vec<(DateTime<Utc>, f64)> = kline_service
+        .get_klines_page(symbol, interval, KLineCursor::Before(start), 1)
+        .klines
+        .into_iter()
+        .chain(kline_service.get_klines(symbol, interval, start, end, None))
+        .filter(|pair| pair.close > 0.0)
+        .map(|pair| (pair.timestamp, if invert { 1.0 / pair.close } else { pair.close }))
+        .collect();
+    closes.sort_by_key(|(timestamp, _)| *timestamp);
+
+    klines
+        .iter()
+        .map(|kline| {
+            let covering = closes.partition_point(|(timestamp, _)| *timestamp <= kline.timestamp);
+            covering.checked_sub(1).map(|index| closes[index].1)
+        })
+        .collect()
+}
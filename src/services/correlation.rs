@@ -0,0 +1,127 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::models::{KLine, TimeInterval};
+use crate::services::KLineService;
+
+/// Most candles per token a correlation window may span
+pub const MAX_STEPS: i64 = 10_000;
+
+/// Pearson correlation of candle-to-candle returns between tokens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct CorrelationMatrix {
+    pub interval: TimeInterval,
+    /// Start of the first candle in the window
+    pub start: DateTime<Utc>,
+    /// End of the window
+    pub end: DateTime<Utc>,
+    /// Tokens in the order of the matrix's rows and columns
+    pub tokens: Vec<String>,
+    /// Correlation of each pair of tokens, missing where either token's price
+    /// did not move or they have fewer than two returns in common
+    pub matrix: Vec<Vec<Option<f64>>>,
+}
+
+/// Parse a window length such as `90s`, `30m`, `6h` or `7d`
+pub fn parse_window(window: &str) -> Option<TimeDelta> {
+    let split = window.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = window.split_at(split);
+    let amount: i64 = amount.parse().ok().filter(|amount| *amount > 0)?;
+    match unit {
+        "s" => TimeDelta::try_seconds(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        _ => None,
+    }
+}
+
+impl CorrelationMatrix {
+    /// Correlate the tokens' returns over the `interval` candles of the window ending at `end`
+    ///
+    /// A candle without trades keeps the previous close, so its return is zero.
+    /// Returns `None` for intervals not aligned to time, and for windows of
+    /// more than [`MAX_STEPS`] candles.
+    pub fn compute(
+        kline_service: &KLineService,
+        mut tokens: Vec<String>,
+        interval: TimeInterval,
+        window: TimeDelta,
+        end: DateTime<Utc>,
+    ) -> Option<Self> {
+        let step = TimeDelta::seconds(interval.duration_seconds()? as i64);
+        let steps = window.num_seconds() / step.num_seconds();
+        if !(1..=MAX_STEPS).contains(&steps) {
+            return None;
+        }
+        let start = (end - window).duration_trunc(step).ok()?;
+        let candles = ((end - start).num_seconds() / step.num_seconds()) as usize + 1;
+
+        tokens.sort();
+        let returns: Vec<Vec<Option<f64>>> = tokens
+            .iter()
+            .map(|token| {
+                let klines = kline_service.get_klines(token, interval, start, end, None);
+                candle_returns(&klines, start, step, candles)
+            })
+            .collect();
+
+        let matrix = returns
+            .iter()
+            .map(|a| returns.iter().map(|b| pearson(a, b)).collect())
+            .collect();
+        Some(Self {
+            interval,
+            start,
+            end,
+            tokens,
+            matrix,
+        })
+    }
+}
+
+/// Return of each grid step from the previous one, with closes carried over steps without a candle
+fn candle_returns(klines: &[KLine], start: DateTime<Utc>, step: TimeDelta, candles: usize) -> Vec<Option<f64>> {
+    let mut closes = vec![None; candles];
+    for kline in klines {
+        let index = ((kline.timestamp - start).num_seconds() / step.num_seconds()) as usize;
+        if let Some(close) = closes.get_mut(index) {
+            *close = Some(kline.close);
+        }
+    }
+    let mut previous = None;
+    closes
+        .into_iter()
+        .map(|close| {
+            let close = close.or(previous);
+            let change = match (previous, close) {
+                (Some(previous), Some(close)) if previous > 0.0 => Some(close / previous - 1.0),
+                _ => None,
+            };
+            previous = close;
+            change
+        })
+        .collect()
+}
+
+/// Pearson correlation over the steps where both series have a return
+fn pearson(a: &[Option<f64>], b: &[Option<f64>]) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = a.iter().zip(b).filter_map(|(a, b)| Some(((*a)?, (*b)?))).collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / n;
+    let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / n;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (a, b) in &pairs {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+    }
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return None;
+    }
+    Some((covariance / (variance_a * variance_b).sqrt()).clamp(-1.0, 1.0))
+}
@@ -0,0 +1,64 @@
+use dashmap::DashMap;
+use std::collections::{HashSet, VecDeque};
+
+use crate::models::{TradeId, Transaction};
+
+/// Default number of trade IDs remembered per token
+pub const DEFAULT_DEDUP_WINDOW: usize = 10_000;
+
+/// Trade IDs of one token, oldest first
+#[derive(Debug, Default)]
+struct RecentTradeIds {
+    order: VecDeque<TradeId>,
+    ids: HashSet<TradeId>,
+}
+
+/// Sliding window of recent trade IDs, so replayed or retried trades are applied once
+///
+/// Each token remembers the IDs of its latest `window` trades; a trade whose
+/// ID is among them is a duplicate. Trades without an ID always pass, and an
+/// ID is only compared with trades of the same token, as venues number
+/// trades per market.
+#[derive(Debug)]
+pub struct TradeDeduplicator {
+    window: usize,
+    recent: DashMap<String, RecentTradeIds>,
+}
+
+impl TradeDeduplicator {
+    /// Create a deduplicator remembering the latest `window` trade IDs of each token
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            recent: DashMap::new(),
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Record the transaction's trade ID, returning false if it was already seen
+    pub fn admit(&self, transaction: &Transaction) -> bool {
+        let Some(trade_id) = &transaction.trade_id else {
+            return true;
+        };
+        let mut recent = self.recent.entry(transaction.token.clone()).or_default();
+        if !recent.ids.insert(trade_id.clone()) {
+            return false;
+        }
+        recent.order.push_back(trade_id.clone());
+        if recent.order.len() > self.window {
+            if let Some(oldest) = recent.order.pop_front() {
+                recent.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+impl Default for TradeDeduplicator {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUP_WINDOW)
+    }
+}
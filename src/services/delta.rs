@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{KLine, TimeInterval};
+
+/// Buy and sell pressure of a token over one candle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct VolumeDelta {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Start of the candle
+    pub timestamp: DateTime<Utc>,
+    /// Volume of trades whose aggressor bought
+    pub buy_volume: f64,
+    /// Volume of trades whose aggressor sold
+    pub sell_volume: f64,
+    /// Buy volume minus sell volume
+    pub delta: f64,
+    /// Sum of the deltas of this candle and every earlier one in the series
+    pub cumulative_delta: f64,
+    pub is_closed: bool,
+}
+
+impl VolumeDelta {
+    /// Delta of a candle, following earlier candles whose deltas sum to `cumulative_before`
+    pub fn of(kline: &KLine, cumulative_before: f64) -> Self {
+        let delta = kline.buy_volume - kline.sell_volume;
+        Self {
+            token: kline.token.clone(),
+            interval: kline.interval,
+            timestamp: kline.timestamp,
+            buy_volume: kline.buy_volume,
+            sell_volume: kline.sell_volume,
+            delta,
+            cumulative_delta: cumulative_before + delta,
+            is_closed: kline.is_closed,
+        }
+    }
+}
+
+/// Deltas of consecutive candles, oldest first, accumulated from the first candle
+pub fn volume_deltas(klines: &[KLine]) -> Vec<VolumeDelta> {
+    let mut cumulative = 0.0;
+    klines
+        .iter()
+        .map(|kline| {
+            let delta = VolumeDelta::of(kline, cumulative);
+            cumulative = delta.cumulative_delta;
+            delta
+        })
+        .collect()
+}
+
+/// Running cumulative delta of one token and interval, fed with candle updates
+///
+/// Updates repeat the open candle until the next one starts, so a candle's
+/// delta is only added to the running total once a later candle is seen.
+#[derive(Debug, Clone, Default)]
+pub struct DeltaTracker {
+    /// Start and delta of the newest candle seen
+    open: Option<(DateTime<Utc>, f64)>,
+    /// Sum of the deltas of the candles before it
+    closed: f64,
+}
+
+impl DeltaTracker {
+    /// Apply an update of a candle, returning its delta, or `None` for a candle older than the newest
+    pub fn update(&mut self, kline: &KLine) -> Option<VolumeDelta> {
+        match self.open {
+            Some((open, _)) if kline.timestamp < open => return None,
+            Some((open, delta)) if kline.timestamp > open => self.closed += delta,
+            _ => {}
+        }
+        let delta = VolumeDelta::of(kline, self.closed);
+        self.open = Some((kline.timestamp, delta.delta));
+        Some(delta)
+    }
+}
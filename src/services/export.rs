@@ -0,0 +1,159 @@
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use bytes::Bytes;
+use chrono::DateTime;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::RowAccessor;
+use parquet::schema::parser::parse_message_type;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::models::{KLine, TimeInterval};
+
+/// Column header of CSV exports
+pub const CSV_HEADER: &str = "token,interval,open_time,open,high,low,close,volume,is_closed\n";
+
+/// Parquet schema of candle exports, with the same columns as the CSV
+const PARQUET_SCHEMA: &str = "
+    message kline {
+        REQUIRED BYTE_ARRAY token (UTF8);
+        REQUIRED BYTE_ARRAY interval (UTF8);
+        REQUIRED INT64 open_time (TIMESTAMP(MILLIS,true));
+        REQUIRED DOUBLE open;
+        REQUIRED DOUBLE high;
+        REQUIRED DOUBLE low;
+        REQUIRED DOUBLE close;
+        REQUIRED DOUBLE volume;
+        REQUIRED BOOLEAN is_closed;
+    }
+";
+
+/// File format of a candle export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// MIME type of the exported file
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// File extension of the exported file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!("Invalid export format: {}", s)),
+        }
+    }
+}
+
+/// Format a candle as a CSV row, with the open time in milliseconds since epoch
+pub fn csv_row(kline: &KLine) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{},{}\n",
+        kline.token,
+        kline.interval.as_str(),
+        kline.timestamp.timestamp_millis(),
+        kline.open,
+        kline.high,
+        kline.low,
+        kline.close,
+        kline.volume,
+        kline.is_closed
+    )
+}
+
+/// Encode candles as a Parquet file with a single row group
+pub fn to_parquet(klines: &[KLine]) -> ParquetResult<Vec<u8>> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(Vec::new(), schema, properties)?;
+
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match index {
+            0 | 1 => {
+                let values: Vec<ByteArray> = klines
+                    .iter()
+                    .map(|kline| match index {
+                        0 => kline.token.as_str().into(),
+                        _ => kline.interval.as_str().as_ref().into(),
+                    })
+                    .collect();
+                column.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+            }
+            2 => {
+                let values: Vec<i64> = klines
+                    .iter()
+                    .map(|kline| kline.timestamp.timestamp_millis())
+                    .collect();
+                column.typed::<Int64Type>().write_batch(&values, None, None)?;
+            }
+            3..=7 => {
+                let values: Vec<f64> = klines
+                    .iter()
+                    .map(|kline| match index {
+                        3 => kline.open,
+                        4 => kline.high,
+                        5 => kline.low,
+                        6 => kline.close,
+                        _ => kline.volume,
+                    })
+                    .collect();
+                column.typed::<DoubleType>().write_batch(&values, None, None)?;
+            }
+            8 => {
+                let values: Vec<bool> = klines.iter().map(|kline| kline.is_closed).collect();
+                column.typed::<BoolType>().write_batch(&values, None, None)?;
+            }
+            _ => return Err(ParquetError::General(format!("Unexpected column {}", index))),
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+
+    writer.into_inner()
+}
+
+/// Decode candles from a file written by [`to_parquet`]
+///
+/// The file has no side split of the volume, so the candles have none either.
+pub fn from_parquet(file: Bytes) -> ParquetResult<Vec<KLine>> {
+    let reader = SerializedFileReader::new(file)?;
+    let mut klines = Vec::new();
+    for row in reader.get_row_iter(None)? {
+        let row = row?;
+        let interval = TimeInterval::from_str(row.get_string(1)?).map_err(ParquetError::General)?;
+        let timestamp = DateTime::from_timestamp_millis(row.get_timestamp_millis(2)?)
+            .ok_or_else(|| ParquetError::General("Open time out of range".to_string()))?;
+        let mut kline = KLine::new(row.get_string(0)?.clone(), timestamp, interval, row.get_double(3)?, row.get_double(7)?);
+        kline.high = row.get_double(4)?;
+        kline.low = row.get_double(5)?;
+        kline.close = row.get_double(6)?;
+        kline.is_closed = row.get_bool(8)?;
+        klines.push(kline);
+    }
+    Ok(klines)
+}
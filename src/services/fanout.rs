@@ -0,0 +1,181 @@
+use futures::StreamExt;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time;
+use uuid::Uuid;
+
+use crate::api::WsManager;
+use crate::config::FanoutConfig;
+use crate::models::{KLine, Transaction};
+
+pub use crate::config::DEFAULT_FANOUT_CHANNEL;
+
+/// Longest wait between attempts to reach Redis
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// A broadcast shared with the other instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum FanoutEvent {
+    /// A processed transaction
+    Transaction(Transaction),
+    /// An updated K-line
+    Kline(KLine),
+}
+
+/// Wire form of a broadcast, tagged with the instance that published it
+#[derive(Debug, Serialize, Deserialize)]
+struct FanoutMessage {
+    origin: Uuid,
+    event: FanoutEvent,
+}
+
+/// Queue of broadcasts to publish, handed to the transaction pipeline
+///
+/// Publishing never waits: when Redis is slow or down and the queue is full,
+/// broadcasts are dropped for the other instances, never for local sessions.
+#[derive(Debug, Clone)]
+pub struct FanoutPublisher {
+    sender: mpsc::Sender<FanoutEvent>,
+}
+
+impl FanoutPublisher {
+    /// Create a publisher queueing up to `capacity` broadcasts, with the receiving end of its queue
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<FanoutEvent>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (Self { sender }, receiver)
+    }
+
+    /// Queue a broadcast for the other instances
+    pub fn publish(&self, event: FanoutEvent) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(event) {
+            tracing::debug!("Fanout queue full, dropping broadcast");
+        }
+    }
+}
+
+/// Bridge sharing broadcasts between instances over Redis pub/sub
+///
+/// Every instance publishes the trades and candles it processes to one
+/// channel and rebroadcasts the others' to its local WebSocket sessions.
+/// Messages carry the ID of the instance that published them, so an
+/// instance skips its own.
+#[derive(Debug)]
+pub struct RedisFanout {
+    client: redis::Client,
+    channel: String,
+    origin: Uuid,
+}
+
+impl RedisFanout {
+    /// Create a bridge to the configured Redis server; nothing connects until it runs
+    pub fn new(config: &FanoutConfig) -> Result<Self, redis::RedisError> {
+        Ok(Self {
+            client: redis::Client::open(config.redis_url.as_str())?,
+            channel: config.channel.clone(),
+            origin: Uuid::new_v4(),
+        })
+    }
+
+    /// ID other instances know this instance's messages by
+    pub fn origin(&self) -> Uuid {
+        self.origin
+    }
+
+    /// Wire form of a broadcast published by this instance
+    pub fn encode(&self, event: FanoutEvent) -> String {
+        let message = FanoutMessage {
+            origin: self.origin,
+            event,
+        };
+        serde_json::to_string(&message).unwrap_or_default()
+    }
+
+    /// Broadcast of another instance, or `None` for this instance's own and malformed messages
+    pub fn decode(&self, payload: &str) -> Option<FanoutEvent> {
+        match serde_json::from_str::<FanoutMessage>(payload) {
+            Ok(message) => (message.origin != self.origin).then_some(message.event),
+            Err(e) => {
+                tracing::warn!("Ignoring malformed fanout message: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Publish queued broadcasts until every publisher is dropped, reconnecting after failures
+    ///
+    /// Broadcasts that fail to publish are lost for the other instances.
+    pub async fn run_publisher(self: Arc<Self>, mut receiver: mpsc::Receiver<FanoutEvent>) {
+        let mut retry_delay = Duration::from_secs(1);
+        loop {
+            let mut connection = match self.client.get_multiplexed_async_connection().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("Failed to connect to Redis for fanout publishing: {}", e);
+                    time::sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            retry_delay = Duration::from_secs(1);
+            tracing::info!("Publishing broadcasts to Redis channel {}", self.channel);
+
+            loop {
+                let Some(event) = receiver.recv().await else {
+                    return;
+                };
+                let payload = self.encode(event);
+                if let Err(e) = connection.publish::<_, _, ()>(&self.channel, payload).await {
+                    tracing::warn!("Failed to publish to Redis, reconnecting: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Rebroadcast other instances' trades and candles to local sessions, reconnecting after failures
+    pub async fn run_subscriber(self: Arc<Self>, ws_manager: Arc<WsManager>) {
+        let mut retry_delay = Duration::from_secs(1);
+        loop {
+            let mut pubsub = match self.client.get_async_pubsub().await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    tracing::warn!("Failed to connect to Redis for fanout: {}", e);
+                    time::sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            if let Err(e) = pubsub.subscribe(&self.channel).await {
+                tracing::warn!("Failed to subscribe to Redis channel {}: {}", self.channel, e);
+                time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                continue;
+            }
+            retry_delay = Duration::from_secs(1);
+            tracing::info!("Rebroadcasting Redis channel {} to local sessions", self.channel);
+
+            let mut messages = pubsub.on_message();
+            while let Some(message) = messages.next().await {
+                let Ok(payload) = message.get_payload::<String>() else {
+                    continue;
+                };
+                if let Some(event) = self.decode(&payload) {
+                    rebroadcast(&ws_manager, &event);
+                }
+            }
+            tracing::warn!("Redis fanout subscription closed, reconnecting");
+        }
+    }
+}
+
+/// Deliver another instance's broadcast to this instance's sessions
+pub fn rebroadcast(ws_manager: &WsManager, event: &FanoutEvent) {
+    match event {
+        FanoutEvent::Transaction(transaction) => ws_manager.broadcast_transaction(transaction),
+        FanoutEvent::Kline(kline) => ws_manager.broadcast_kline(kline),
+    }
+}
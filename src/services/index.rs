@@ -0,0 +1,148 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::config::IndexConfig;
+use crate::models::symbol::SERIES_SEPARATOR;
+use crate::models::Transaction;
+
+/// Suffix of the series holding a token's composite price
+pub const INDEX_SUFFIX: &str = "@index";
+
+/// Name of the series holding `token`'s composite price, such as `DOGE@index`
+pub fn index_token(token: &str) -> String {
+    format!("{}{}", token, INDEX_SUFFIX)
+}
+
+/// Latest price one source reported for a token
+#[derive(Debug, Clone, Copy)]
+struct SourceQuote {
+    price: f64,
+    timestamp: DateTime<Utc>,
+}
+
+/// A source's part in a token's composite price
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct IndexComponent {
+    pub source: String,
+    pub weight: f64,
+    /// Latest price the source reported
+    pub price: f64,
+    /// Time of that price
+    pub timestamp: DateTime<Utc>,
+    /// Whether the price is too old to count towards the composite
+    pub stale: bool,
+}
+
+/// A token's composite price with the prices it was made of
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct IndexBreakdown {
+    pub token: String,
+    /// Series the composite candles are stored under
+    pub index_token: String,
+    /// Weighted average of the fresh sources' prices, if any is fresh
+    pub price: Option<f64>,
+    /// Time of the latest price of any source, which staleness is judged from
+    pub timestamp: DateTime<Utc>,
+    pub components: Vec<IndexComponent>,
+}
+
+/// Weighted composite of the prices several ingestion sources report for a token
+///
+/// Each trade of a weighted source updates that source's latest price and
+/// yields a trade of the composite, a weighted average of the sources whose
+/// latest price is recent enough, with the trade's time, side and volume.
+/// Staleness is judged by trade timestamps rather than the clock, so replays
+/// and backfills build the same composite as live feeds.
+#[derive(Debug)]
+pub struct IndexPriceAggregator {
+    weights: BTreeMap<String, f64>,
+    stale_after: TimeDelta,
+    /// Latest price of each source, per token
+    quotes: DashMap<String, BTreeMap<String, SourceQuote>>,
+}
+
+impl IndexPriceAggregator {
+    /// Create an aggregator weighting sources by `weights`, ignoring prices older than `stale_after`
+    pub fn new(weights: BTreeMap<String, f64>, stale_after: Duration) -> Self {
+        Self {
+            weights,
+            stale_after: TimeDelta::from_std(stale_after).unwrap_or(TimeDelta::MAX),
+            quotes: DashMap::new(),
+        }
+    }
+
+    /// Create the configured aggregator, if the index is enabled
+    pub fn from_config(config: &IndexConfig) -> Option<Self> {
+        config
+            .enabled
+            .then(|| Self::new(config.weights.clone(), Duration::from_millis(config.stale_after_ms)))
+    }
+
+    /// Record a source's trade, returning the composite trade it moves the index to
+    ///
+    /// Trades of unweighted sources, without a source, or of derived series
+    /// such as the index itself are ignored.
+    pub fn observe(&self, transaction: &Transaction) -> Option<Transaction> {
+        let source = transaction.source.as_ref().filter(|source| self.weights.contains_key(*source))?;
+        if transaction.token.contains(SERIES_SEPARATOR) {
+            return None;
+        }
+        let mut quotes = self.quotes.entry(transaction.token.clone()).or_default();
+        let quote = SourceQuote {
+            price: transaction.price,
+            timestamp: transaction.timestamp,
+        };
+        // A source's late trade must not replace a newer price
+        let latest = quotes.entry(source.clone()).or_insert(quote);
+        if latest.timestamp <= quote.timestamp {
+            *latest = quote;
+        }
+        let price = self.composite(&quotes, transaction.timestamp)?;
+
+        let mut index = Transaction::new(index_token(&transaction.token), price, transaction.volume, transaction.side);
+        index.timestamp = transaction.timestamp;
+        Some(index)
+    }
+
+    /// Weighted average of the prices not older than `stale_after` before `now`
+    fn composite(&self, quotes: &BTreeMap<String, SourceQuote>, now: DateTime<Utc>) -> Option<f64> {
+        let (weighted, total) = quotes
+            .iter()
+            .filter(|(_, quote)| !self.is_stale(quote, now))
+            .filter_map(|(source, quote)| Some((self.weights.get(source)?, quote.price)))
+            .fold((0.0, 0.0), |(weighted, total), (weight, price)| (weighted + weight * price, total + weight));
+        (total > 0.0).then(|| weighted / total)
+    }
+
+    fn is_stale(&self, quote: &SourceQuote, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(quote.timestamp) > self.stale_after
+    }
+
+    /// The composite price of a token and its sources' latest prices
+    pub fn breakdown(&self, token: &str) -> Option<IndexBreakdown> {
+        let quotes = self.quotes.get(token)?;
+        let timestamp = quotes.values().map(|quote| quote.timestamp).max()?;
+        let components = quotes
+            .iter()
+            .map(|(source, quote)| IndexComponent {
+                source: source.clone(),
+                weight: self.weights.get(source).copied().unwrap_or_default(),
+                price: quote.price,
+                timestamp: quote.timestamp,
+                stale: self.is_stale(quote, timestamp),
+            })
+            .collect();
+        Some(IndexBreakdown {
+            token: token.to_string(),
+            index_token: index_token(token),
+            price: self.composite(&quotes, timestamp),
+            timestamp,
+            components,
+        })
+    }
+}
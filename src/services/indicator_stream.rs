@@ -0,0 +1,520 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::models::KLine;
+use crate::services::indicators::{
+    self, BollingerParams, MacdParams, DEFAULT_ATR_PERIOD, DEFAULT_RSI_PERIOD, DEFAULT_VOLATILITY_PERIOD,
+    SMOOTHING_WARMUP,
+};
+use crate::services::KLineService;
+
+/// Indicator a WebSocket subscription streams
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorKind {
+    #[default]
+    Rsi,
+    Sma,
+    Ema,
+    Atr,
+    Volatility,
+    Macd,
+    Bollinger,
+}
+
+impl IndicatorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndicatorKind::Rsi => "rsi",
+            IndicatorKind::Sma => "sma",
+            IndicatorKind::Ema => "ema",
+            IndicatorKind::Atr => "atr",
+            IndicatorKind::Volatility => "volatility",
+            IndicatorKind::Macd => "macd",
+            IndicatorKind::Bollinger => "bollinger",
+        }
+    }
+}
+
+impl FromStr for IndicatorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rsi" => Ok(IndicatorKind::Rsi),
+            "sma" => Ok(IndicatorKind::Sma),
+            "ema" => Ok(IndicatorKind::Ema),
+            "atr" => Ok(IndicatorKind::Atr),
+            "volatility" => Ok(IndicatorKind::Volatility),
+            "macd" => Ok(IndicatorKind::Macd),
+            "bollinger" => Ok(IndicatorKind::Bollinger),
+            _ => Err(format!("Invalid indicator: {}", s)),
+        }
+    }
+}
+
+/// An indicator with its parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndicatorSpec {
+    Rsi { period: usize },
+    Sma { period: usize },
+    Ema { period: usize },
+    Atr { period: usize },
+    Volatility { period: usize },
+    Macd(MacdParams),
+    Bollinger(BollingerParams),
+}
+
+impl IndicatorSpec {
+    /// Indicator from subscription parameters, with each parameter's default when omitted
+    ///
+    /// `period` applies to every indicator but MACD, which takes `fast`,
+    /// `slow` and `signal` periods; `std_dev` only applies to Bollinger Bands.
+    pub fn from_parts(
+        kind: IndicatorKind,
+        period: Option<usize>,
+        fast: Option<usize>,
+        slow: Option<usize>,
+        signal: Option<usize>,
+        std_dev: Option<f64>,
+    ) -> Result<Self, String> {
+        let check = |name: &str, value: usize, min: usize| {
+            if (min..=indicators::MAX_PERIOD).contains(&value) {
+                Ok(value)
+            } else {
+                Err(format!("Invalid {}: {}. Expected {} to {}", name, value, min, indicators::MAX_PERIOD))
+            }
+        };
+        let period = |default: usize, min: usize| check("period", period.unwrap_or(default), min);
+
+        Ok(match kind {
+            IndicatorKind::Rsi => IndicatorSpec::Rsi {
+                period: period(DEFAULT_RSI_PERIOD, 1)?,
+            },
+            IndicatorKind::Sma => IndicatorSpec::Sma { period: period(20, 1)? },
+            IndicatorKind::Ema => IndicatorSpec::Ema { period: period(20, 1)? },
+            IndicatorKind::Atr => IndicatorSpec::Atr {
+                period: period(DEFAULT_ATR_PERIOD, 1)?,
+            },
+            IndicatorKind::Volatility => IndicatorSpec::Volatility {
+                period: period(DEFAULT_VOLATILITY_PERIOD, 2)?,
+            },
+            IndicatorKind::Macd => {
+                let defaults = MacdParams::default();
+                let params = MacdParams {
+                    fast: check("fast", fast.unwrap_or(defaults.fast), 1)?,
+                    slow: check("slow", slow.unwrap_or(defaults.slow), 1)?,
+                    signal: check("signal", signal.unwrap_or(defaults.signal), 1)?,
+                };
+                if params.fast >= params.slow {
+                    return Err("Invalid MACD periods. Expected fast below slow".to_string());
+                }
+                IndicatorSpec::Macd(params)
+            }
+            IndicatorKind::Bollinger => {
+                let defaults = BollingerParams::default();
+                let std_dev = std_dev.unwrap_or(defaults.std_dev);
+                if !(std_dev.is_finite() && std_dev > 0.0) {
+                    return Err(format!("Invalid std_dev: {}. Expected a positive number", std_dev));
+                }
+                IndicatorSpec::Bollinger(BollingerParams {
+                    period: period(defaults.period, 1)?,
+                    std_dev,
+                })
+            }
+        })
+    }
+
+    pub fn kind(&self) -> IndicatorKind {
+        match self {
+            IndicatorSpec::Rsi { .. } => IndicatorKind::Rsi,
+            IndicatorSpec::Sma { .. } => IndicatorKind::Sma,
+            IndicatorSpec::Ema { .. } => IndicatorKind::Ema,
+            IndicatorSpec::Atr { .. } => IndicatorKind::Atr,
+            IndicatorSpec::Volatility { .. } => IndicatorKind::Volatility,
+            IndicatorSpec::Macd(_) => IndicatorKind::Macd,
+            IndicatorSpec::Bollinger(_) => IndicatorKind::Bollinger,
+        }
+    }
+
+    /// Period of the indicator, or the slow period of MACD
+    pub fn period(&self) -> usize {
+        match *self {
+            IndicatorSpec::Rsi { period }
+            | IndicatorSpec::Sma { period }
+            | IndicatorSpec::Ema { period }
+            | IndicatorSpec::Atr { period }
+            | IndicatorSpec::Volatility { period } => period,
+            IndicatorSpec::Macd(params) => params.slow,
+            IndicatorSpec::Bollinger(params) => params.period,
+        }
+    }
+
+    /// Closed candles replayed to start streaming, enough for smoothed values to settle
+    fn warmup(&self) -> usize {
+        match *self {
+            IndicatorSpec::Sma { period } | IndicatorSpec::Bollinger(BollingerParams { period, .. }) => period,
+            IndicatorSpec::Volatility { period } => period + 1,
+            IndicatorSpec::Ema { period } => period + SMOOTHING_WARMUP,
+            IndicatorSpec::Rsi { period } | IndicatorSpec::Atr { period } => period + SMOOTHING_WARMUP + 1,
+            IndicatorSpec::Macd(params) => params.slow + params.signal + SMOOTHING_WARMUP,
+        }
+    }
+}
+
+impl Eq for IndicatorSpec {}
+
+impl Hash for IndicatorSpec {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind().hash(state);
+        match self {
+            IndicatorSpec::Macd(params) => (params.fast, params.slow, params.signal).hash(state),
+            IndicatorSpec::Bollinger(params) => (params.period, params.std_dev.to_bits()).hash(state),
+            spec => spec.period().hash(state),
+        }
+    }
+}
+
+impl fmt::Display for IndicatorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndicatorSpec::Macd(params) => write!(f, "macd:{}:{}:{}", params.fast, params.slow, params.signal),
+            IndicatorSpec::Bollinger(params) => write!(f, "bollinger:{}:{}", params.period, params.std_dev),
+            spec => write!(f, "{}:{}", spec.kind().as_str(), spec.period()),
+        }
+    }
+}
+
+impl FromStr for IndicatorSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid indicator: {}", s);
+        let number = |part: &str| part.parse::<usize>().map_err(|_| invalid());
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["macd", fast, slow, signal] => IndicatorSpec::from_parts(
+                IndicatorKind::Macd,
+                None,
+                Some(number(fast)?),
+                Some(number(slow)?),
+                Some(number(signal)?),
+                None,
+            ),
+            ["bollinger", period, std_dev] => IndicatorSpec::from_parts(
+                IndicatorKind::Bollinger,
+                Some(number(period)?),
+                None,
+                None,
+                None,
+                Some(std_dev.parse().map_err(|_| invalid())?),
+            ),
+            [kind, period] if !matches!(*kind, "macd" | "bollinger") => {
+                IndicatorSpec::from_parts(kind.parse()?, Some(number(period)?), None, None, None, None)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Values of an indicator at one candle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndicatorValues {
+    /// The indicator, the MACD line, or the middle Bollinger Band
+    pub value: f64,
+    /// MACD signal line
+    pub signal: Option<f64>,
+    /// MACD minus signal
+    pub histogram: Option<f64>,
+    /// Upper Bollinger Band
+    pub upper: Option<f64>,
+    /// Lower Bollinger Band
+    pub lower: Option<f64>,
+}
+
+impl IndicatorValues {
+    fn single(value: f64) -> Self {
+        Self {
+            value,
+            signal: None,
+            histogram: None,
+            upper: None,
+            lower: None,
+        }
+    }
+}
+
+/// Exponential average seeded with the mean of its first `period` values
+///
+/// With Wilder smoothing the weight of a new value is `1 / period` rather
+/// than `2 / (period + 1)`.
+#[derive(Debug, Clone)]
+struct Smoother {
+    period: usize,
+    alpha: f64,
+    seed: Vec<f64>,
+    average: Option<f64>,
+}
+
+impl Smoother {
+    fn exponential(period: usize) -> Self {
+        Self::new(period, 2.0 / (period as f64 + 1.0))
+    }
+
+    fn wilder(period: usize) -> Self {
+        Self::new(period, 1.0 / period as f64)
+    }
+
+    fn new(period: usize, alpha: f64) -> Self {
+        Self {
+            period,
+            alpha,
+            seed: Vec::with_capacity(period),
+            average: None,
+        }
+    }
+
+    fn next(&mut self, value: f64) -> Option<f64> {
+        match &mut self.average {
+            Some(average) => *average += self.alpha * (value - *average),
+            None => {
+                self.seed.push(value);
+                if self.seed.len() == self.period {
+                    self.average = Some(self.seed.iter().sum::<f64>() / self.period as f64);
+                }
+            }
+        }
+        self.average
+    }
+}
+
+/// The last `period` values
+#[derive(Debug, Clone)]
+struct Window {
+    period: usize,
+    values: VecDeque<f64>,
+}
+
+impl Window {
+    fn new(period: usize) -> Self {
+        Self {
+            period,
+            values: VecDeque::with_capacity(period + 1),
+        }
+    }
+
+    /// Add a value, returning the full window's values once it holds `period` of them
+    fn next(&mut self, value: f64) -> Option<&VecDeque<f64>> {
+        self.values.push_back(value);
+        if self.values.len() > self.period {
+            self.values.pop_front();
+        }
+        (self.values.len() == self.period).then_some(&self.values)
+    }
+}
+
+/// Running state of an indicator over consecutive candles
+#[derive(Debug, Clone)]
+enum Calculator {
+    Sma(Window),
+    Ema(Smoother),
+    Rsi {
+        previous: Option<f64>,
+        gains: Smoother,
+        losses: Smoother,
+    },
+    Atr {
+        previous: Option<f64>,
+        ranges: Smoother,
+    },
+    Volatility {
+        previous: Option<f64>,
+        returns: Window,
+    },
+    Macd {
+        fast: Smoother,
+        slow: Smoother,
+        signal: Smoother,
+    },
+    Bollinger {
+        closes: Window,
+        std_dev: f64,
+    },
+}
+
+impl Calculator {
+    fn new(spec: IndicatorSpec) -> Self {
+        match spec {
+            IndicatorSpec::Sma { period } => Calculator::Sma(Window::new(period)),
+            IndicatorSpec::Ema { period } => Calculator::Ema(Smoother::exponential(period)),
+            IndicatorSpec::Rsi { period } => Calculator::Rsi {
+                previous: None,
+                gains: Smoother::wilder(period),
+                losses: Smoother::wilder(period),
+            },
+            IndicatorSpec::Atr { period } => Calculator::Atr {
+                previous: None,
+                ranges: Smoother::wilder(period),
+            },
+            IndicatorSpec::Volatility { period } => Calculator::Volatility {
+                previous: None,
+                returns: Window::new(period),
+            },
+            IndicatorSpec::Macd(params) => Calculator::Macd {
+                fast: Smoother::exponential(params.fast),
+                slow: Smoother::exponential(params.slow),
+                signal: Smoother::exponential(params.signal),
+            },
+            IndicatorSpec::Bollinger(params) => Calculator::Bollinger {
+                closes: Window::new(params.period),
+                std_dev: params.std_dev,
+            },
+        }
+    }
+
+    /// Apply the next candle, returning the indicator at it once enough candles were seen
+    fn next(&mut self, kline: &KLine) -> Option<IndicatorValues> {
+        let close = kline.close;
+        match self {
+            Calculator::Sma(window) => {
+                let values = window.next(close)?;
+                Some(IndicatorValues::single(values.iter().sum::<f64>() / values.len() as f64))
+            }
+            Calculator::Ema(smoother) => smoother.next(close).map(IndicatorValues::single),
+            Calculator::Rsi { previous, gains, losses } => {
+                let change = close - previous.replace(close)?;
+                let (gain, loss) = (gains.next(change.max(0.0)), losses.next((-change).max(0.0)));
+                let (gain, loss) = (gain?, loss?);
+                let rsi = if loss == 0.0 {
+                    if gain == 0.0 { 50.0 } else { 100.0 }
+                } else {
+                    100.0 - 100.0 / (1.0 + gain / loss)
+                };
+                Some(IndicatorValues::single(rsi))
+            }
+            Calculator::Atr { previous, ranges } => {
+                let previous = previous.replace(close)?;
+                let range = (kline.high - kline.low)
+                    .max((kline.high - previous).abs())
+                    .max((kline.low - previous).abs());
+                ranges.next(range).map(IndicatorValues::single)
+            }
+            Calculator::Volatility { previous, returns } => {
+                let previous = previous.replace(close)?;
+                let window = returns.next((close / previous).ln())?;
+                let mean = window.iter().sum::<f64>() / window.len() as f64;
+                let variance =
+                    window.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (window.len() - 1) as f64;
+                Some(IndicatorValues::single(variance.sqrt()))
+            }
+            Calculator::Macd { fast, slow, signal } => {
+                let (fast, slow) = (fast.next(close), slow.next(close));
+                let line = fast? - slow?;
+                let signal = signal.next(line)?;
+                Some(IndicatorValues {
+                    value: line,
+                    signal: Some(signal),
+                    histogram: Some(line - signal),
+                    upper: None,
+                    lower: None,
+                })
+            }
+            Calculator::Bollinger { closes, std_dev } => {
+                let window = closes.next(close)?;
+                let middle = window.iter().sum::<f64>() / window.len() as f64;
+                let variance = window.iter().map(|value| (value - middle).powi(2)).sum::<f64>() / window.len() as f64;
+                let width = *std_dev * variance.sqrt();
+                Some(IndicatorValues {
+                    value: middle,
+                    signal: None,
+                    histogram: None,
+                    upper: Some(middle + width),
+                    lower: Some(middle - width),
+                })
+            }
+        }
+    }
+}
+
+/// An indicator of one token and interval, updated as its candles change
+///
+/// Closed candles are folded into running state once, replaying recent
+/// history only when streaming starts; an open candle's value is computed
+/// from that state without recomputing the series.
+#[derive(Debug, Clone)]
+pub struct IndicatorStream {
+    spec: IndicatorSpec,
+    calculator: Calculator,
+    seeded: bool,
+    /// Open time of the newest closed candle applied, and the values at it
+    closed: Option<(DateTime<Utc>, Option<IndicatorValues>)>,
+    /// Open time of the newest open candle seen
+    open: Option<DateTime<Utc>>,
+}
+
+impl IndicatorStream {
+    pub fn new(spec: IndicatorSpec) -> Self {
+        Self {
+            spec,
+            calculator: Calculator::new(spec),
+            seeded: false,
+            closed: None,
+            open: None,
+        }
+    }
+
+    pub fn spec(&self) -> IndicatorSpec {
+        self.spec
+    }
+
+    /// Indicator values at an updated or closed candle
+    ///
+    /// Returns `None` until enough candles closed, and for candles older than
+    /// the newest closed candle applied.
+    pub fn update(&mut self, kline_service: &KLineService, kline: &KLine) -> Option<IndicatorValues> {
+        if !self.seeded {
+            self.seeded = true;
+            let history = kline_service.get_closed_klines(&kline.token, kline.interval, self.spec.warmup());
+            self.apply_closed(&history);
+        }
+        let last_closed = self.closed.map(|(timestamp, _)| timestamp);
+
+        if kline.is_closed {
+            return match self.closed {
+                Some((timestamp, values)) if kline.timestamp <= timestamp => {
+                    values.filter(|_| kline.timestamp == timestamp)
+                }
+                _ => {
+                    self.apply_closed(std::slice::from_ref(kline));
+                    self.closed.and_then(|(_, values)| values)
+                }
+            };
+        }
+
+        // A new open candle follows candles that closed since the last one
+        if self.open != Some(kline.timestamp) {
+            self.open = Some(kline.timestamp);
+            let start = last_closed.unwrap_or(DateTime::<Utc>::MIN_UTC);
+            let mut missed = kline_service.get_klines(&kline.token, kline.interval, start, kline.timestamp, None);
+            missed.retain(|candle| candle.is_closed && Some(candle.timestamp) > last_closed);
+            self.apply_closed(&missed);
+        }
+        if self.closed.is_some_and(|(timestamp, _)| kline.timestamp <= timestamp) {
+            return None;
+        }
+        self.calculator.clone().next(kline)
+    }
+
+    fn apply_closed(&mut self, klines: &[KLine]) {
+        for kline in klines {
+            let values = self.calculator.next(kline);
+            self.closed = Some((kline.timestamp, values));
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::models::{KLine, TimeInterval};
+use crate::services::KLineService;
+
+/// Longest period an indicator may be computed over
+pub const MAX_PERIOD: usize = 500;
+
+/// Default RSI period
+pub const DEFAULT_RSI_PERIOD: usize = 14;
+
+/// Default ATR period
+pub const DEFAULT_ATR_PERIOD: usize = 14;
+
+/// Default number of returns realized volatility is measured over
+pub const DEFAULT_VOLATILITY_PERIOD: usize = 20;
+
+/// Seconds in a year of round-the-clock trading, for annualizing volatility
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// Most values a cached indicator series holds
+pub const MAX_POINTS: usize = 1000;
+
+/// Default number of series kept by an [`IndicatorCache`]
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// Candles smoothed before the first RSI or MACD value that is reported
+///
+/// Wilder and exponential smoothing depend on all earlier candles; starting
+/// this far back lets values settle, so they barely depend on where the
+/// window starts.
+pub const SMOOTHING_WARMUP: usize = 100;
+
+/// Kind of moving average
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovingAverageType {
+    /// Simple moving average
+    Sma,
+    /// Exponential moving average, seeded with the SMA of the first period
+    Ema,
+}
+
+impl MovingAverageType {
+    /// Name of the type, as used in queries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovingAverageType::Sma => "sma",
+            MovingAverageType::Ema => "ema",
+        }
+    }
+}
+
+impl FromStr for MovingAverageType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sma" => Ok(MovingAverageType::Sma),
+            "ema" => Ok(MovingAverageType::Ema),
+            _ => Err(format!("Invalid moving average type: {}", s)),
+        }
+    }
+}
+
+/// Indicator value at the close of a candle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct IndicatorPoint {
+    /// Open time of the candle the value was computed at
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+}
+
+/// MACD values at the close of a candle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct MacdPoint {
+    /// Open time of the candle the values were computed at
+    pub timestamp: DateTime<Utc>,
+    /// Fast EMA minus slow EMA
+    pub macd: f64,
+    /// EMA of the MACD line
+    pub signal: f64,
+    /// MACD minus signal
+    pub histogram: f64,
+}
+
+/// Bollinger Bands at the close of a candle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct BollingerPoint {
+    /// Open time of the candle the bands were computed at
+    pub timestamp: DateTime<Utc>,
+    /// SMA of the closing prices
+    pub middle: f64,
+    pub upper: f64,
+    pub lower: f64,
+}
+
+/// Realized volatility at the close of a candle
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct VolatilityPoint {
+    /// Open time of the candle the volatility was computed at
+    pub timestamp: DateTime<Utc>,
+    /// Standard deviation of the log returns per candle
+    pub volatility: f64,
+    /// Volatility scaled to a year of trading, for time intervals only
+    pub annualized: Option<f64>,
+}
+
+/// MACD periods
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacdParams {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Default for MacdParams {
+    fn default() -> Self {
+        Self {
+            fast: 12,
+            slow: 26,
+            signal: 9,
+        }
+    }
+}
+
+/// Bollinger Bands period and width
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BollingerParams {
+    pub period: usize,
+    /// Standard deviations between the middle and the outer bands
+    pub std_dev: f64,
+}
+
+impl Default for BollingerParams {
+    fn default() -> Self {
+        Self {
+            period: 20,
+            std_dev: 2.0,
+        }
+    }
+}
+
+/// Indicator value pushed to WebSocket subscribers as a candle updates and when it closes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct IndicatorUpdate {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Name of the indicator, such as `rsi`
+    pub indicator: String,
+    /// Period of the indicator, or the slow period of MACD
+    pub period: usize,
+    /// Open time of the candle
+    pub timestamp: DateTime<Utc>,
+    /// The indicator, the MACD line, or the middle Bollinger Band
+    pub value: f64,
+    /// MACD signal line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub signal: Option<f64>,
+    /// MACD minus signal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub histogram: Option<f64>,
+    /// Upper Bollinger Band
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub upper: Option<f64>,
+    /// Lower Bollinger Band
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub lower: Option<f64>,
+    /// Whether the candle is closed; values at an open candle change with it
+    pub is_closed: bool,
+}
+
+/// Closing prices of a candle slice
+pub fn closes(klines: &[KLine]) -> Vec<f64> {
+    klines.iter().map(|kline| kline.close).collect()
+}
+
+/// Simple moving average of `values`
+///
+/// Returns one value per full window, so `values.len() - period + 1` values,
+/// or none when there are fewer than `period` values.
+pub fn sma(values: &[f64], period: usize) -> Vec<f64> {
+    if period == 0 || values.len() < period {
+        return Vec::new();
+    }
+
+    let mut sum: f64 = values[..period].iter().sum();
+    let mut averages = Vec::with_capacity(values.len() - period + 1);
+    averages.push(sum / period as f64);
+    for i in period..values.len() {
+        sum += values[i] - values[i - period];
+        averages.push(sum / period as f64);
+    }
+    averages
+}
+
+/// Exponential moving average of `values` with smoothing `2 / (period + 1)`
+///
+/// Seeded with the SMA of the first `period` values, so it is aligned with
+/// [`sma`] and returns the same number of values.
+pub fn ema(values: &[f64], period: usize) -> Vec<f64> {
+    let Some(&seed) = sma(&values[..values.len().min(period)], period).first() else {
+        return Vec::new();
+    };
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut averages = Vec::with_capacity(values.len() - period + 1);
+    averages.push(seed);
+    let mut average = seed;
+    for value in &values[period..] {
+        average += alpha * (value - average);
+        averages.push(average);
+    }
+    averages
+}
+
+/// Relative strength index of `values` with Wilder smoothing
+///
+/// Average gains and losses start as the mean of the first `period` changes,
+/// so one value is returned per value after the first `period`. A window
+/// without losses has an RSI of 100, and one without any change 50.
+pub fn rsi(values: &[f64], period: usize) -> Vec<f64> {
+    if period == 0 || values.len() <= period {
+        return Vec::new();
+    }
+
+    let changes: Vec<f64> = values.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let (mut avg_gain, mut avg_loss) = changes[..period].iter().fold((0.0, 0.0), |(gain, loss), change| {
+        (gain + change.max(0.0), loss + (-change).max(0.0))
+    });
+    avg_gain /= period as f64;
+    avg_loss /= period as f64;
+
+    let index = |avg_gain: f64, avg_loss: f64| {
+        if avg_loss == 0.0 {
+            if avg_gain == 0.0 { 50.0 } else { 100.0 }
+        } else {
+            100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
+        }
+    };
+
+    let mut values = Vec::with_capacity(changes.len() - period + 1);
+    values.push(index(avg_gain, avg_loss));
+    let smoothing = (period - 1) as f64;
+    for change in &changes[period..] {
+        avg_gain = (avg_gain * smoothing + change.max(0.0)) / period as f64;
+        avg_loss = (avg_loss * smoothing + (-change).max(0.0)) / period as f64;
+        values.push(index(avg_gain, avg_loss));
+    }
+    values
+}
+
+/// MACD line, signal line and histogram of `values`
+///
+/// Returns one `(macd, signal, histogram)` per value once both EMAs and the
+/// signal EMA have a full window, so `values.len() - slow - signal + 2` values.
+pub fn macd(values: &[f64], params: MacdParams) -> Vec<(f64, f64, f64)> {
+    if params.fast >= params.slow {
+        return Vec::new();
+    }
+
+    let fast = ema(values, params.fast);
+    let slow = ema(values, params.slow);
+    // The fast EMA starts slow - fast values earlier
+    let offset = params.slow - params.fast;
+    let line: Vec<f64> = slow
+        .iter()
+        .enumerate()
+        .map(|(i, slow)| fast[i + offset] - slow)
+        .collect();
+
+    let signal = ema(&line, params.signal);
+    let skip = line.len().saturating_sub(signal.len());
+    line[skip..]
+        .iter()
+        .zip(signal)
+        .map(|(macd, signal)| (*macd, signal, macd - signal))
+        .collect()
+}
+
+/// Middle, upper and lower Bollinger Bands of `values`
+///
+/// Bands are the SMA plus and minus `std_dev` population standard deviations
+/// of each window, returned like [`sma`].
+pub fn bollinger_bands(values: &[f64], params: BollingerParams) -> Vec<(f64, f64, f64)> {
+    let period = params.period;
+    sma(values, period)
+        .into_iter()
+        .zip(values.windows(period.max(1)))
+        .map(|(middle, window)| {
+            let variance = window.iter().map(|value| (value - middle).powi(2)).sum::<f64>() / period as f64;
+            let width = params.std_dev * variance.sqrt();
+            (middle, middle + width, middle - width)
+        })
+        .collect()
+}
+
+/// Average true range of consecutive candles with Wilder smoothing
+///
+/// The true range of a candle also covers the gap from the previous close, so
+/// one value is returned per candle after the first `period`.
+pub fn average_true_range(klines: &[KLine], period: usize) -> Vec<f64> {
+    if period == 0 || klines.len() <= period {
+        return Vec::new();
+    }
+
+    let ranges: Vec<f64> = klines
+        .windows(2)
+        .map(|pair| {
+            let (previous, kline) = (pair[0].close, &pair[1]);
+            (kline.high - kline.low)
+                .max((kline.high - previous).abs())
+                .max((kline.low - previous).abs())
+        })
+        .collect();
+    let mut atr = ranges[..period].iter().sum::<f64>() / period as f64;
+
+    let mut values = Vec::with_capacity(ranges.len() - period + 1);
+    values.push(atr);
+    for range in &ranges[period..] {
+        atr = (atr * (period - 1) as f64 + range) / period as f64;
+        values.push(atr);
+    }
+    values
+}
+
+/// Close-to-close realized volatility of `values`
+///
+/// The sample standard deviation of the log returns over each window of
+/// `period` returns, so one value is returned per value after the first
+/// `period`. Needs a period of at least 2, and positive values.
+pub fn realized_volatility(values: &[f64], period: usize) -> Vec<f64> {
+    if period < 2 || values.len() <= period {
+        return Vec::new();
+    }
+
+    let returns: Vec<f64> = values.windows(2).map(|pair| (pair[1] / pair[0]).ln()).collect();
+    returns
+        .windows(period)
+        .map(|window| {
+            let mean = window.iter().sum::<f64>() / period as f64;
+            let variance = window.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (period - 1) as f64;
+            variance.sqrt()
+        })
+        .collect()
+}
+
+/// Factor scaling the volatility of one candle of a time interval to a year
+pub fn annualization_factor(interval: TimeInterval) -> Option<f64> {
+    interval
+        .duration_seconds()
+        .map(|seconds| (SECONDS_PER_YEAR / seconds as f64).sqrt())
+}
+
+/// Pair the trailing values of an indicator with the candles they were computed at
+///
+/// Indicators return fewer values than candles while they warm up, so the
+/// values line up with the last `values.len()` candles.
+fn align<T>(klines: &[KLine], values: Vec<T>) -> impl Iterator<Item = (&KLine, T)> {
+    let skip = klines.len().saturating_sub(values.len());
+    klines[skip..].iter().zip(values)
+}
+
+/// Pair the trailing values of an indicator with the timestamps of their candles
+pub fn to_points(klines: &[KLine], values: Vec<f64>) -> Vec<IndicatorPoint> {
+    align(klines, values)
+        .map(|(kline, value)| IndicatorPoint {
+            timestamp: kline.timestamp,
+            value,
+        })
+        .collect()
+}
+
+/// Moving average of the candles' closing prices
+pub fn moving_average(klines: &[KLine], period: usize, kind: MovingAverageType) -> Vec<IndicatorPoint> {
+    let closes = closes(klines);
+    let values = match kind {
+        MovingAverageType::Sma => sma(&closes, period),
+        MovingAverageType::Ema => ema(&closes, period),
+    };
+    to_points(klines, values)
+}
+
+/// RSI of the candles' closing prices
+pub fn relative_strength(klines: &[KLine], period: usize) -> Vec<IndicatorPoint> {
+    to_points(klines, rsi(&closes(klines), period))
+}
+
+/// RSI at a token's `limit` most recent closed candles, warmed up over earlier candles
+pub fn recent_rsi(
+    kline_service: &KLineService,
+    token: &str,
+    interval: TimeInterval,
+    period: usize,
+    limit: usize,
+) -> Vec<IndicatorPoint> {
+    let klines = kline_service.get_closed_klines(token, interval, limit + period + SMOOTHING_WARMUP);
+    let mut points = relative_strength(&klines, period);
+    points.drain(..points.len().saturating_sub(limit));
+    points
+}
+
+/// ATR at a token's most recent closed candle, warmed up over earlier candles
+///
+/// Returns `None` until more than `period` candles have closed.
+pub fn latest_atr(
+    kline_service: &KLineService,
+    token: &str,
+    interval: TimeInterval,
+    period: usize,
+) -> Option<f64> {
+    let klines = kline_service.get_closed_klines(token, interval, period + SMOOTHING_WARMUP + 1);
+    average_true_range(&klines, period).pop()
+}
+
+/// ATR at a token's `limit` most recent closed candles, warmed up over earlier candles
+pub fn recent_atr(
+    kline_service: &KLineService,
+    token: &str,
+    interval: TimeInterval,
+    period: usize,
+    limit: usize,
+) -> Vec<IndicatorPoint> {
+    let klines = kline_service.get_closed_klines(token, interval, limit + period + SMOOTHING_WARMUP);
+    let mut points = to_points(&klines, average_true_range(&klines, period));
+    points.drain(..points.len().saturating_sub(limit));
+    points
+}
+
+/// Realized volatility at a token's `limit` most recent closed candles
+pub fn recent_volatility(
+    kline_service: &KLineService,
+    token: &str,
+    interval: TimeInterval,
+    period: usize,
+    limit: usize,
+) -> Vec<VolatilityPoint> {
+    let klines = kline_service.get_closed_klines(token, interval, limit + period);
+    let factor = annualization_factor(interval);
+    align(&klines, realized_volatility(&closes(&klines), period))
+        .map(|(kline, volatility)| VolatilityPoint {
+            timestamp: kline.timestamp,
+            volatility,
+            annualized: factor.map(|factor| volatility * factor),
+        })
+        .collect()
+}
+
+/// MACD of the candles' closing prices
+pub fn macd_points(klines: &[KLine], params: MacdParams) -> Vec<MacdPoint> {
+    align(klines, macd(&closes(klines), params))
+        .map(|(kline, (macd, signal, histogram))| MacdPoint {
+            timestamp: kline.timestamp,
+            macd,
+            signal,
+            histogram,
+        })
+        .collect()
+}
+
+/// Bollinger Bands of the candles' closing prices
+pub fn bollinger_points(klines: &[KLine], params: BollingerParams) -> Vec<BollingerPoint> {
+    align(klines, bollinger_bands(&closes(klines), params))
+        .map(|(kline, (middle, upper, lower))| BollingerPoint {
+            timestamp: kline.timestamp,
+            middle,
+            upper,
+            lower,
+        })
+        .collect()
+}
+
+/// Series computed per query, with the last closed candle it covers
+#[derive(Debug)]
+struct SeriesCache<T> {
+    entries: DashMap<String, (DateTime<Utc>, Arc<Vec<T>>)>,
+}
+
+impl<T> SeriesCache<T> {
+    fn new() -> Self {
+        Self {
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Get the series cached at `last_closed`, computing it when missing or stale
+    fn get_or_compute(
+        &self,
+        key: String,
+        last_closed: DateTime<Utc>,
+        capacity: usize,
+        compute: impl FnOnce() -> Vec<T>,
+    ) -> Arc<Vec<T>> {
+        if let Some(entry) = self.entries.get(&key) {
+            if entry.0 == last_closed {
+                return entry.1.clone();
+            }
+        }
+
+        let series = Arc::new(compute());
+        if self.entries.len() >= capacity && !self.entries.contains_key(&key) {
+            // Make room by dropping the series of some other query
+            let evicted = self.entries.iter().next().map(|entry| entry.key().clone());
+            if let Some(evicted) = evicted {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.entries.insert(key, (last_closed, series.clone()));
+        series
+    }
+}
+
+/// Indicator series reused across requests until another candle closes
+///
+/// Each series covers the newest [`MAX_POINTS`] closed candles of a token,
+/// interval and set of parameters. It is only recomputed once a new candle
+/// has closed, so repeated queries within an interval cost a slice copy
+/// rather than a pass over the whole window.
+#[derive(Debug)]
+pub struct IndicatorCache {
+    /// Series kept per indicator
+    capacity: usize,
+    macd: SeriesCache<MacdPoint>,
+    bollinger: SeriesCache<BollingerPoint>,
+}
+
+impl IndicatorCache {
+    /// Create a cache keeping up to `capacity` series per indicator
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            macd: SeriesCache::new(),
+            bollinger: SeriesCache::new(),
+        }
+    }
+
+    /// MACD at a token's `limit` most recent closed candles
+    pub fn macd(
+        &self,
+        kline_service: &KLineService,
+        token: &str,
+        interval: TimeInterval,
+        params: MacdParams,
+        limit: usize,
+    ) -> Vec<MacdPoint> {
+        let Some(last_closed) = kline_service.get_last_closed_timestamp(token, interval) else {
+            return Vec::new();
+        };
+        let key = format!("{}:{}:{}:{}:{}", token, interval.as_str(), params.fast, params.slow, params.signal);
+        let series = self.macd.get_or_compute(key, last_closed, self.capacity, || {
+            let warmup = params.slow + params.signal + SMOOTHING_WARMUP;
+            let klines = kline_service.get_closed_klines(token, interval, MAX_POINTS + warmup);
+            macd_points(&klines, params)
+        });
+        tail(&series, limit)
+    }
+
+    /// Bollinger Bands at a token's `limit` most recent closed candles
+    pub fn bollinger(
+        &self,
+        kline_service: &KLineService,
+        token: &str,
+        interval: TimeInterval,
+        params: BollingerParams,
+        limit: usize,
+    ) -> Vec<BollingerPoint> {
+        let Some(last_closed) = kline_service.get_last_closed_timestamp(token, interval) else {
+            return Vec::new();
+        };
+        let key = format!("{}:{}:{}:{}", token, interval.as_str(), params.period, params.std_dev);
+        let series = self.bollinger.get_or_compute(key, last_closed, self.capacity, || {
+            let klines = kline_service.get_closed_klines(token, interval, MAX_POINTS + params.period - 1);
+            bollinger_points(&klines, params)
+        });
+        tail(&series, limit)
+    }
+}
+
+impl Default for IndicatorCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+/// Copy the last `limit` values of a series
+fn tail<T: Clone>(series: &[T], limit: usize) -> Vec<T> {
+    series[series.len().saturating_sub(limit)..].to_vec()
+}
@@ -0,0 +1,343 @@
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::config::InfluxConfig;
+use crate::models::{KLine, Ticker};
+use crate::services::KLineService;
+
+/// Largest UDP datagram written; batches are split at line boundaries to fit
+const MAX_DATAGRAM: usize = 8192;
+
+/// Where line protocol is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InfluxEndpoint {
+    /// An InfluxDB write URL, lines are POSTed to
+    Http(String),
+    /// A TCP listener, such as Telegraf's `socket_listener`
+    Tcp(String),
+    /// A UDP listener, one datagram per chunk of lines
+    Udp(String),
+    /// A Unix stream socket
+    Unix(PathBuf),
+}
+
+impl InfluxEndpoint {
+    /// Parse an `http(s)://`, `tcp://host:port`, `udp://host:port` or `unix:///path` endpoint
+    pub fn parse(endpoint: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid Influx endpoint: {}. Expected an http(s):// URL, tcp://host:port, udp://host:port or unix:///path",
+                endpoint
+            )
+        };
+        let has_port = |address: &str| {
+            address
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        };
+        match endpoint.split_once("://").ok_or_else(invalid)? {
+            ("http" | "https", rest) if !rest.is_empty() => Ok(InfluxEndpoint::Http(endpoint.to_string())),
+            ("tcp", address) if has_port(address) => Ok(InfluxEndpoint::Tcp(address.to_string())),
+            ("udp", address) if has_port(address) => Ok(InfluxEndpoint::Udp(address.to_string())),
+            ("unix", path) if path.starts_with('/') => Ok(InfluxEndpoint::Unix(PathBuf::from(path))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Queue of lines to export, handed to the transaction pipeline and the ticker snapshots
+///
+/// Writing never waits: when the endpoint is slow or down and the queue is
+/// full, lines are dropped.
+#[derive(Debug, Clone)]
+pub struct InfluxWriter {
+    sender: mpsc::Sender<String>,
+    kline_measurement: Arc<str>,
+    ticker_measurement: Arc<str>,
+}
+
+impl InfluxWriter {
+    /// Create a writer for the configured measurements, with the receiving end of its queue
+    pub fn channel(config: &InfluxConfig) -> (Self, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let writer = Self {
+            sender,
+            kline_measurement: config.kline_measurement.as_str().into(),
+            ticker_measurement: config.ticker_measurement.as_str().into(),
+        };
+        (writer, receiver)
+    }
+
+    /// Queue a closed candle
+    pub fn write_kline(&self, kline: &KLine) {
+        self.write(kline_line(&self.kline_measurement, kline));
+    }
+
+    /// Queue a ticker snapshot
+    pub fn write_ticker(&self, ticker: &Ticker) {
+        self.write(ticker_line(&self.ticker_measurement, ticker));
+    }
+
+    fn write(&self, line: String) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(line) {
+            tracing::debug!("Influx queue full, dropping line");
+        }
+    }
+}
+
+/// Line of a candle, tagged with its token and interval and stamped with its open time
+pub fn kline_line(measurement: &str, kline: &KLine) -> String {
+    let interval = kline.interval.as_str();
+    line(
+        measurement,
+        &[("token", &kline.token), ("interval", &interval)],
+        &[
+            ("open", kline.open),
+            ("high", kline.high),
+            ("low", kline.low),
+            ("close", kline.close),
+            ("volume", kline.volume),
+            ("buy_volume", kline.buy_volume),
+            ("sell_volume", kline.sell_volume),
+        ],
+        &[("is_partial", kline.is_partial)],
+        kline.timestamp,
+    )
+}
+
+/// Line of a ticker, tagged with its token and stamped with the time it was computed
+pub fn ticker_line(measurement: &str, ticker: &Ticker) -> String {
+    line(
+        measurement,
+        &[("token", &ticker.token)],
+        &[
+            ("last_price", ticker.last_price),
+            ("open_price", ticker.open_price),
+            ("high", ticker.high),
+            ("low", ticker.low),
+            ("volume", ticker.volume),
+            ("price_change", ticker.price_change),
+            ("price_change_percent", ticker.price_change_percent),
+        ],
+        &[],
+        ticker.timestamp,
+    )
+}
+
+/// A line with nanosecond precision; non-finite fields are left out, as line protocol has no such values
+fn line(
+    measurement: &str,
+    tags: &[(&str, &str)],
+    fields: &[(&str, f64)],
+    flags: &[(&str, bool)],
+    timestamp: DateTime<Utc>,
+) -> String {
+    let mut line = escape(measurement, &[',', ' ']);
+    for (key, value) in tags {
+        let _ = write!(line, ",{}={}", key, escape(value, &[',', '=', ' ']));
+    }
+    let fields = fields
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(key, value)| format!("{}={}", key, value))
+        .chain(flags.iter().map(|(key, value)| format!("{}={}", key, value)));
+    for (index, field) in fields.enumerate() {
+        line.push(if index == 0 { ' ' } else { ',' });
+        line.push_str(&field);
+    }
+    let _ = write!(line, " {}", timestamp.timestamp_nanos_opt().unwrap_or_default());
+    line
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Open connection to an endpoint; sockets reconnect on the write after a failure
+enum Connection {
+    Http {
+        client: awc::Client,
+        url: String,
+        token: Option<String>,
+    },
+    Tcp(String, Option<TcpStream>),
+    Udp(String, Option<UdpSocket>),
+    #[cfg(unix)]
+    Unix(PathBuf, Option<tokio::net::UnixStream>),
+}
+
+impl Connection {
+    fn new(config: &InfluxConfig) -> Result<Self, String> {
+        Ok(match InfluxEndpoint::parse(&config.endpoint)? {
+            InfluxEndpoint::Http(url) => Connection::Http {
+                client: awc::Client::builder()
+                    .timeout(Duration::from_millis(config.timeout_ms))
+                    .finish(),
+                url,
+                token: config.token.clone(),
+            },
+            InfluxEndpoint::Tcp(address) => Connection::Tcp(address, None),
+            InfluxEndpoint::Udp(address) => Connection::Udp(address, None),
+            #[cfg(unix)]
+            InfluxEndpoint::Unix(path) => Connection::Unix(path, None),
+            #[cfg(not(unix))]
+            InfluxEndpoint::Unix(_) => return Err("Unix sockets are not supported on this platform".to_string()),
+        })
+    }
+
+    /// Write a batch of newline-terminated lines
+    async fn send(&mut self, batch: &[u8]) -> Result<(), String> {
+        match self {
+            Connection::Http { client, url, token } => {
+                let mut request = client.post(url.as_str()).content_type("text/plain; charset=utf-8");
+                if let Some(token) = token {
+                    request = request.insert_header(("Authorization", format!("Token {}", token)));
+                }
+                match request.send_body(batch.to_vec()).await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(mut response) => {
+                        let message = response.body().await.map(|body| String::from_utf8_lossy(&body).trim().to_string());
+                        Err(format!("status {}: {}", response.status(), message.unwrap_or_default()))
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Connection::Tcp(address, stream) => {
+                if stream.is_none() {
+                    *stream = Some(TcpStream::connect(address.as_str()).await.map_err(|e| e.to_string())?);
+                }
+                write_stream(stream, batch).await
+            }
+            Connection::Udp(address, socket) => {
+                if socket.is_none() {
+                    let bound = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+                    bound.connect(address.as_str()).await.map_err(|e| e.to_string())?;
+                    *socket = Some(bound);
+                }
+                let Some(connected) = socket.as_ref() else {
+                    return Ok(());
+                };
+                for datagram in datagrams(batch) {
+                    if let Err(e) = connected.send(datagram).await {
+                        *socket = None;
+                        return Err(e.to_string());
+                    }
+                }
+                Ok(())
+            }
+            #[cfg(unix)]
+            Connection::Unix(path, stream) => {
+                if stream.is_none() {
+                    *stream = Some(tokio::net::UnixStream::connect(&*path).await.map_err(|e| e.to_string())?);
+                }
+                write_stream(stream, batch).await
+            }
+        }
+    }
+}
+
+/// Write to a connected stream, dropping it on failure so the next write reconnects
+async fn write_stream<S: AsyncWrite + Unpin>(stream: &mut Option<S>, batch: &[u8]) -> Result<(), String> {
+    let Some(connected) = stream.as_mut() else {
+        return Ok(());
+    };
+    if let Err(e) = connected.write_all(batch).await {
+        *stream = None;
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+/// Chunks of a batch no longer than [`MAX_DATAGRAM`], split after a newline where possible
+fn datagrams(batch: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = batch;
+    while !rest.is_empty() {
+        let end = if rest.len() <= MAX_DATAGRAM {
+            rest.len()
+        } else {
+            rest[..MAX_DATAGRAM]
+                .iter()
+                .rposition(|byte| *byte == b'\n')
+                .map_or(MAX_DATAGRAM, |newline| newline + 1)
+        };
+        let (chunk, remainder) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = remainder;
+    }
+    chunks
+}
+
+/// Write queued lines to the configured endpoint in batches until the writers are dropped
+///
+/// A batch is written once it reaches `batch_size` lines, and at least every
+/// `flush_interval_ms`. A batch that fails to write is logged and dropped.
+/// Must run on the actix runtime, as the HTTP client is not `Send`.
+pub async fn run_influx_exporter(mut receiver: mpsc::Receiver<String>, config: InfluxConfig) {
+    let mut connection = match Connection::new(&config) {
+        Ok(connection) => connection,
+        Err(e) => {
+            tracing::error!("Influx export disabled: {}", e);
+            return;
+        }
+    };
+    let batch_size = config.batch_size.max(1);
+    let mut batch = Vec::new();
+    let mut lines = 0;
+    let mut ticker = actix_web::rt::time::interval(Duration::from_millis(config.flush_interval_ms));
+
+    loop {
+        let flush = tokio::select! {
+            line = receiver.recv() => match line {
+                Some(line) => {
+                    batch.extend_from_slice(line.as_bytes());
+                    batch.push(b'\n');
+                    lines += 1;
+                    lines >= batch_size
+                }
+                None => break,
+            },
+            _ = ticker.tick() => true,
+        };
+        if flush && lines > 0 {
+            if let Err(e) = connection.send(&batch).await {
+                tracing::warn!("Dropping {} lines that could not be written to {}: {}", lines, config.endpoint, e);
+            }
+            batch.clear();
+            lines = 0;
+        }
+    }
+
+    if lines > 0 {
+        if let Err(e) = connection.send(&batch).await {
+            tracing::warn!("Dropping {} lines that could not be written to {}: {}", lines, config.endpoint, e);
+        }
+    }
+    tracing::info!("Influx exporter stopped");
+}
+
+/// Queue a ticker snapshot of every token at a fixed period, forever
+pub async fn run_ticker_snapshots(kline_service: Arc<KLineService>, writer: InfluxWriter, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        for token in kline_service.get_available_tokens() {
+            if let Some(ticker) = kline_service.get_ticker(&token) {
+                writer.write_ticker(&ticker);
+            }
+        }
+    }
+}
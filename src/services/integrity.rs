@@ -0,0 +1,172 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::models::{KLine, TimeInterval};
+use crate::services::resample::{resample, ResampledKLine, TargetInterval};
+use crate::services::KLineService;
+
+/// Most candles of the verified interval one check may span
+pub const MAX_CANDLES: i64 = 10_000;
+
+/// Relative difference below which rebuilt and stored values are equal
+///
+/// Volumes are summed in a different order than they were accumulated, so
+/// they may differ in the last bits. The difference is relative to the values
+/// alone, so prices of tokens trading far below 1 are compared as strictly.
+const TOLERANCE: f64 = 1e-9;
+
+/// Value of a candle that differs from the one rebuilt from finer candles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CandleField {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+}
+
+/// A stored candle value that disagrees with its constituent candles
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct CandleMismatch {
+    /// Start of the candle
+    pub timestamp: DateTime<Utc>,
+    pub field: CandleField,
+    /// Value of the stored candle
+    pub stored: f64,
+    /// Value rebuilt from the constituent candles
+    pub expected: f64,
+}
+
+/// Which side of a check has no candles for a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// Finer candles exist, but the candle of the verified interval does not
+    MissingCandle,
+    /// The candle exists, but none of the finer candles it is made of do
+    MissingConstituents,
+}
+
+/// A bucket stored for only one of the two intervals
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct CandleGap {
+    /// Start of the bucket
+    pub timestamp: DateTime<Utc>,
+    pub kind: GapKind,
+}
+
+/// Result of cross-checking an interval's candles against the finer candles they aggregate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct IntegrityReport {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Finer interval the candles are rebuilt from
+    pub source_interval: TimeInterval,
+    /// Start of the first bucket checked
+    pub start: DateTime<Utc>,
+    /// End of the range checked
+    pub end: DateTime<Utc>,
+    /// Closed candles compared with their rebuilt values
+    pub candles_checked: usize,
+    pub mismatches: Vec<CandleMismatch>,
+    pub gaps: Vec<CandleGap>,
+    /// Whether no mismatches or gaps were found
+    pub consistent: bool,
+}
+
+impl IntegrityReport {
+    /// Check the closed `interval` candles of a token starting within `start..=end`
+    ///
+    /// Each candle is compared with the one rebuilt from the coarsest finer
+    /// interval that divides it, such as 5m from 1m. Candles still open are
+    /// only checked for gaps, as trades may update them mid-check. Returns
+    /// `None` for intervals without a finer time interval, and for ranges of
+    /// more than [`MAX_CANDLES`] candles.
+    pub fn verify(
+        kline_service: &KLineService,
+        token: &str,
+        interval: TimeInterval,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Option<Self> {
+        let target: TargetInterval = interval.as_str().parse().ok()?;
+        let source_interval = TimeInterval::all().into_iter().rev().find(|source| target.accepts(*source))?;
+        let start = target.bucket_start(start);
+        let candles = (end - start).num_seconds() / target.seconds() as i64 + 1;
+        if !(1..=MAX_CANDLES).contains(&candles) {
+            return None;
+        }
+
+        // Finer candles of the last bucket may start after `end`
+        let bucket_end = target.bucket_start(end) + TimeDelta::seconds(target.seconds() as i64) - TimeDelta::milliseconds(1);
+        let stored: BTreeMap<DateTime<Utc>, KLine> = kline_service
+            .get_klines(token, interval, start, end, None)
+            .into_iter()
+            .map(|kline| (kline.timestamp, kline))
+            .collect();
+        let rebuilt: BTreeMap<DateTime<Utc>, ResampledKLine> =
+            resample(&kline_service.get_klines(token, source_interval, start, bucket_end, None), target, now)
+                .into_iter()
+                .map(|kline| (kline.timestamp, kline))
+                .collect();
+
+        let mut report = Self {
+            token: token.to_string(),
+            interval,
+            source_interval,
+            start,
+            end,
+            candles_checked: 0,
+            mismatches: Vec::new(),
+            gaps: Vec::new(),
+            consistent: false,
+        };
+        for timestamp in stored.keys().chain(rebuilt.keys()).collect::<BTreeSet<_>>() {
+            match (stored.get(timestamp), rebuilt.get(timestamp)) {
+                (Some(kline), Some(expected)) if kline.is_closed => {
+                    report.candles_checked += 1;
+                    report.compare(kline, expected);
+                }
+                (Some(_), Some(_)) => {}
+                (Some(_), None) => report.gaps.push(CandleGap {
+                    timestamp: *timestamp,
+                    kind: GapKind::MissingConstituents,
+                }),
+                (None, _) => report.gaps.push(CandleGap {
+                    timestamp: *timestamp,
+                    kind: GapKind::MissingCandle,
+                }),
+            }
+        }
+        report.consistent = report.mismatches.is_empty() && report.gaps.is_empty();
+        Some(report)
+    }
+
+    fn compare(&mut self, kline: &KLine, expected: &ResampledKLine) {
+        let fields = [
+            (CandleField::Open, kline.open, expected.open),
+            (CandleField::High, kline.high, expected.high),
+            (CandleField::Low, kline.low, expected.low),
+            (CandleField::Close, kline.close, expected.close),
+            (CandleField::Volume, kline.volume, expected.volume),
+        ];
+        for (field, stored, expected) in fields {
+            if (stored - expected).abs() > TOLERANCE * stored.abs().max(expected.abs()) {
+                self.mismatches.push(CandleMismatch {
+                    timestamp: kline.timestamp,
+                    field,
+                    stored,
+                    expected,
+                });
+            }
+        }
+    }
+}
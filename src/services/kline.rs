@@ -1,83 +1,644 @@
-use crate::models::{KLine, TimeInterval, Transaction};
-use chrono::{DateTime, Duration, Timelike, Utc};
+use crate::models::{KLine, Ticker, TimeInterval, Transaction, TransactionError};
+use crate::models::symbol::SERIES_SEPARATOR;
+use crate::models::ticker::PriceExtremes;
+use crate::services::price_stats::PriceStats;
+use crate::services::TokenRegistry;
+use chrono::{DateTime, Duration, DurationRound, TimeDelta, Timelike, Utc};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::mem;
+use std::sync::Arc;
+
+/// Position to continue paging a token's K-line history from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KLineCursor {
+    /// Candles opened strictly before the timestamp, paging towards older candles
+    Before(DateTime<Utc>),
+    /// Candles opened strictly after the timestamp, paging towards newer candles
+    After(DateTime<Utc>),
+}
+
+impl KLineCursor {
+    /// Get the timestamp the cursor points at
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            KLineCursor::Before(timestamp) | KLineCursor::After(timestamp) => *timestamp,
+        }
+    }
+}
+
+/// Order of K-lines in a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Oldest first
+    Asc,
+    /// Newest first
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    /// Name of the order in query strings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(format!("Invalid order: {}", s)),
+        }
+    }
+}
+
+/// One page of K-lines, sorted by timestamp
+#[derive(Debug, Clone)]
+pub struct KLinePage {
+    /// Candles in the page
+    pub klines: Vec<KLine>,
+    /// Cursor for the following page, if more candles remain
+    pub next_cursor: Option<KLineCursor>,
+}
+
+/// Open trade-count or volume candle of a token
+#[derive(Debug, Clone, Copy)]
+struct ActivityBucket {
+    /// Key of the open candle
+    start: DateTime<Utc>,
+    /// Trades in the open candle
+    trades: u64,
+}
+
+/// Reason a transaction was not applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum IngestError {
+    /// The token is not in the allowlist of a strict service
+    UnknownToken(String),
+    /// The price or volume would corrupt candles
+    InvalidTransaction(TransactionError),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::UnknownToken(token) => write!(f, "Unknown token: {}", token),
+            IngestError::InvalidTransaction(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for IngestError {}
+
+/// Corrected values of a stored candle, sent by a source that revised its data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct KLineAmendment {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Start of the candle to correct
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    /// Corrected volume of trades whose aggressor bought, given with `sell_volume`;
+    /// the stored split is scaled to `volume` when both are omitted, or
+    /// `volume` split evenly if nothing was stored
+    #[serde(default)]
+    pub buy_volume: Option<f64>,
+    /// Corrected volume of trades whose aggressor sold, given with `buy_volume`;
+    /// the stored split is scaled to `volume` when both are omitted, or
+    /// `volume` split evenly if nothing was stored
+    #[serde(default)]
+    pub sell_volume: Option<f64>,
+}
+
+/// Reason a candle amendment was not applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmendError {
+    /// No candle is stored for the token, interval and timestamp
+    NotFound,
+    /// The corrected values are not a valid candle
+    Invalid(String),
+}
+
+impl fmt::Display for AmendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmendError::NotFound => write!(f, "No candle found for the specified token, interval and timestamp"),
+            AmendError::Invalid(message) => write!(f, "Invalid amendment: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AmendError {}
+
+impl KLineAmendment {
+    /// Check that the values form a candle: positive prices within the high and
+    /// low, and non-negative volumes with any buy and sell split adding up to `volume`
+    pub fn validate(&self) -> Result<(), String> {
+        let prices = [self.open, self.high, self.low, self.close];
+        if prices.iter().any(|price| !price.is_finite() || *price <= 0.0) {
+            return Err("prices must be positive".to_string());
+        }
+        if self.high < self.open.max(self.close).max(self.low) || self.low > self.open.min(self.close) {
+            return Err("high and low must bound the open and close".to_string());
+        }
+        let volumes = [Some(self.volume), self.buy_volume, self.sell_volume];
+        if volumes.iter().flatten().any(|volume| !volume.is_finite() || *volume < 0.0) {
+            return Err("volumes must not be negative".to_string());
+        }
+        match (self.buy_volume, self.sell_volume) {
+            (None, None) => {}
+            (Some(buy), Some(sell)) => {
+                if (buy + sell - self.volume).abs() > self.volume * 1e-9 {
+                    return Err("buy_volume and sell_volume must add up to volume".to_string());
+                }
+            }
+            _ => return Err("buy_volume and sell_volume must be given together".to_string()),
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of processing a batch of transactions
+#[derive(Debug, Clone, Default)]
+pub struct BatchOutcome {
+    /// Transactions applied to the candles
+    pub applied: usize,
+    /// K-lines closed by the batch, grouped by token
+    pub closed: Vec<KLine>,
+    /// Position in the batch and reason of each transaction that was not applied
+    pub rejected: Vec<(usize, IngestError)>,
+}
+
+/// Candles stored for one token and interval
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct SeriesStats {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Open and closed candles kept
+    pub candles: usize,
+    /// Open time of the oldest candle kept
+    pub oldest: DateTime<Utc>,
+    /// Open time of the newest candle
+    pub newest: DateTime<Utc>,
+}
+
+/// Size of the candle storage
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct StorageStats {
+    pub total_candles: usize,
+    /// Rough estimate of the memory held by candles and their map entries
+    pub estimated_memory_bytes: usize,
+    /// Every series with at least one candle, by token then interval
+    pub series: Vec<SeriesStats>,
+}
+
+/// One OHLCV bar summarizing a token's candles over a time range
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct OhlcSummary {
+    pub token: String,
+    /// Open time of the first candle in the range
+    pub start: DateTime<Utc>,
+    /// Open time of the last candle in the range
+    pub end: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    /// Stored candles folded into the summary
+    pub candles: usize,
+}
+
+/// Trades counted so far in an open trade-count or volume candle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityBucketSnapshot {
+    pub token: String,
+    pub interval: TimeInterval,
+    /// Key of the open candle
+    pub start: DateTime<Utc>,
+    /// Trades in the open candle
+    pub trades: u64,
+}
+
+/// Every candle of a K-line service, for warm restarts and migrations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KLineSnapshot {
+    /// Time the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Tracked tokens, including those without candles yet
+    pub tokens: Vec<String>,
+    /// Open and closed candles of every token and interval
+    pub klines: Vec<KLine>,
+    /// Progress of the open trade-count and volume candles
+    #[serde(default)]
+    pub activity_buckets: Vec<ActivityBucketSnapshot>,
+    /// Last transaction log record the candles include; the whole log is replayed without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wal_sequence: Option<u64>,
+}
+
+/// Reason a snapshot could not be written or read
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The file could not be written or read
+    Io(std::io::Error),
+    /// The file is not a valid snapshot
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "Cannot access snapshot file: {}", e),
+            SnapshotError::Invalid(message) => write!(f, "Invalid snapshot: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl KLineSnapshot {
+    /// Write the snapshot as JSON, replacing the file only once it is complete
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let json = serde_json::to_vec(self).map_err(|e| SnapshotError::Invalid(e.to_string()))?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`KLineSnapshot::save`]
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let content = fs::read(path)?;
+        serde_json::from_slice(&content).map_err(|e| SnapshotError::Invalid(e.to_string()))
+    }
+}
+
+/// Shard of `shards` a token's candles are kept in, stable across restarts
+///
+/// Ingestion workers route tokens with the same function, so with as many
+/// workers as shards each worker is the only writer of its shard.
+pub fn token_shard(token: &str, shards: usize) -> usize {
+    // FNV-1a, like the checksum
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in token.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % shards.max(1) as u64) as usize
+}
+
+/// Candles of one token: interval -> timestamp -> KLine
+type TokenKLines = DashMap<TimeInterval, DashMap<DateTime<Utc>, KLine>>;
+
+/// Candles of a subset of the tokens
+#[derive(Debug, Default)]
+struct KLineShard {
+    /// Storage for K-lines: token -> interval -> timestamp -> KLine
+    klines: DashMap<String, TokenKLines>,
+    /// Open trade-count and volume candle per token and interval
+    activity_buckets: DashMap<(String, TimeInterval), ActivityBucket>,
+    /// All-time and rolling price extremes per token
+    price_stats: DashMap<String, PriceStats>,
+}
 
 /// K-line data service using DashMap for high-performance concurrent access
+///
+/// Tokens are partitioned into shards by [`token_shard`], so writers of
+/// unrelated tokens do not contend on the same maps.
 #[derive(Debug)]
 pub struct KLineService {
-    /// Storage for K-lines: token -> interval -> timestamp -> KLine
-    /// Using DashMap for lock-free concurrent access
-    klines: DashMap<String, DashMap<TimeInterval, DashMap<DateTime<Utc>, KLine>>>,
+    /// Storage of each shard, using DashMap for lock-free concurrent access
+    shards: Vec<KLineShard>,
+    /// Trade-count and volume intervals aggregated besides the time intervals
+    activity_intervals: Vec<TimeInterval>,
+    /// Registry whose registered tokens are the only ones accepted, in strict mode
+    allowlist: Option<Arc<TokenRegistry>>,
 }
 
 impl KLineService {
     /// Create a new K-line service
     pub fn new() -> Self {
         Self {
-            klines: DashMap::new(),
+            shards: vec![KLineShard::default()],
+            activity_intervals: Vec::new(),
+            allowlist: None,
+        }
+    }
+
+    /// Partition tokens into `shards` shards, dropping any stored candles
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        self.shards = (0..shards.max(1)).map(|_| KLineShard::default()).collect();
+        self
+    }
+
+    /// Number of shards tokens are partitioned into
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self, token: &str) -> &KLineShard {
+        &self.shards[token_shard(token, self.shards.len())]
+    }
+
+    fn klines(&self, token: &str) -> &DashMap<String, TokenKLines> {
+        &self.shard(token).klines
+    }
+
+    /// Also aggregate trade-count and volume candles for these intervals
+    ///
+    /// Time-aligned intervals are always aggregated and are ignored here.
+    pub fn with_activity_intervals(mut self, intervals: impl IntoIterator<Item = TimeInterval>) -> Self {
+        for interval in intervals {
+            if !interval.is_time_based() && !self.activity_intervals.contains(&interval) {
+                self.activity_intervals.push(interval);
+            }
         }
+        self
+    }
+
+    /// Only accept transactions for tokens registered with a registry
+    ///
+    /// Without an allowlist, any token starts a new series on its first trade.
+    pub fn with_allowlist(mut self, registry: Arc<TokenRegistry>) -> Self {
+        self.allowlist = Some(registry);
+        self
+    }
+
+    /// Every interval candles are aggregated for
+    pub fn intervals(&self) -> Vec<TimeInterval> {
+        TimeInterval::all()
+            .into_iter()
+            .chain(self.activity_intervals.iter().copied())
+            .collect()
+    }
+
+    /// Whether candles are aggregated for an interval
+    pub fn supports(&self, interval: TimeInterval) -> bool {
+        interval.is_time_based() || self.activity_intervals.contains(&interval)
     }
 
     /// Process a transaction and update K-lines
-    pub fn process_transaction(&self, transaction: &Transaction) {
+    ///
+    /// Returns the K-lines the transaction closed by starting a new interval.
+    /// Invalid transactions and those for tokens outside the allowlist are dropped; use
+    /// [`Self::try_process_transaction`] to learn about them.
+    pub fn process_transaction(&self, transaction: &Transaction) -> Vec<KLine> {
+        self.try_process_transaction(transaction).unwrap_or_default()
+    }
+
+    /// Process a transaction, failing if its values are invalid or its token
+    /// is outside the allowlist
+    ///
+    /// Returns the K-lines the transaction closed by starting a new interval.
+    pub fn try_process_transaction(&self, transaction: &Transaction) -> Result<Vec<KLine>, IngestError> {
+        self.check_transaction(transaction)?;
+        self.record_price(transaction);
+
         // Update K-lines for all supported intervals
-        for interval in [
-            TimeInterval::Second1,
-            TimeInterval::Minute1,
-            TimeInterval::Minute5,
-            TimeInterval::Minute15,
-            TimeInterval::Hour1,
-        ] {
-            self.update_kline_for_interval(transaction, interval);
+        let mut closed = Vec::new();
+        for interval in TimeInterval::all() {
+            closed.extend(self.update_kline_for_interval(transaction, interval));
         }
+        for &interval in &self.activity_intervals {
+            closed.extend(self.update_activity_kline(transaction, interval));
+        }
+        Ok(closed)
     }
 
-    /// Update K-line for a specific interval
-    fn update_kline_for_interval(&self, transaction: &Transaction, interval: TimeInterval) {
+    /// Process a batch of transactions, as if each were processed in turn
+    ///
+    /// Transactions are grouped by token, and consecutive trades of a token
+    /// that fall into the same candle are applied in a single map lookup per
+    /// interval, with one check for candles to close. Invalid transactions and
+    /// those for tokens outside the allowlist are skipped and reported.
+    pub fn process_transactions(&self, transactions: &[Transaction]) -> BatchOutcome {
+        let mut outcome = BatchOutcome::default();
+        // Tokens in the order they first appear, and where each one's batch is
+        let mut tokens: Vec<(&str, Vec<&Transaction>)> = Vec::new();
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for (index, transaction) in transactions.iter().enumerate() {
+            if let Err(e) = self.check_transaction(transaction) {
+                outcome.rejected.push((index, e));
+                continue;
+            }
+            let position = *positions.entry(&transaction.token).or_insert_with(|| {
+                tokens.push((&transaction.token, Vec::new()));
+                tokens.len() - 1
+            });
+            tokens[position].1.push(transaction);
+            self.record_price(transaction);
+            outcome.applied += 1;
+        }
+
+        for (token, batch) in tokens {
+            for interval in TimeInterval::all() {
+                outcome.closed.extend(self.update_klines_for_interval(token, &batch, interval));
+            }
+            for &interval in &self.activity_intervals {
+                for transaction in &batch {
+                    outcome.closed.extend(self.update_activity_kline(transaction, interval));
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Include a transaction's price in its token's extremes
+    fn record_price(&self, transaction: &Transaction) {
+        match self.shard(&transaction.token).price_stats.entry(transaction.token.clone()) {
+            Entry::Occupied(mut entry) => entry.get_mut().record(transaction),
+            Entry::Vacant(entry) => {
+                entry.insert(PriceStats::new(transaction));
+            }
+        }
+    }
+
+    /// All-time and rolling price extremes of a token as of now
+    ///
+    /// Only trades processed since startup are tracked; restored candles are not.
+    pub fn get_price_extremes(&self, token: &str) -> Option<PriceExtremes> {
+        let stats = self.shard(token).price_stats.get(token)?;
+        Some(stats.extremes(Utc::now()))
+    }
+
+    /// Check that a transaction can be applied
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), IngestError> {
+        transaction.validate().map_err(IngestError::InvalidTransaction)?;
+        if !self.accepts_token(&transaction.token) {
+            return Err(IngestError::UnknownToken(transaction.token.clone()));
+        }
+        Ok(())
+    }
+
+    /// Whether transactions for a token are accepted, as they are for any token without an allowlist
+    ///
+    /// Derived series such as `DOGE@index` are accepted along with their pair.
+    pub fn accepts_token(&self, token: &str) -> bool {
+        let pair = token.split_once(SERIES_SEPARATOR).map_or(token, |(pair, _)| pair);
+        self.allowlist
+            .as_ref()
+            .is_none_or(|registry| registry.is_registered(pair))
+    }
+
+    /// Apply trades of one token to the K-lines of an interval, returning the K-lines they closed
+    fn update_klines_for_interval(&self, token: &str, batch: &[&Transaction], interval: TimeInterval) -> Vec<KLine> {
+        let token_klines = self.klines(token).entry(token.to_string()).or_default();
+        let interval_klines = token_klines.entry(interval).or_default();
+
+        let starts: Vec<DateTime<Utc>> = batch
+            .iter()
+            .map(|transaction| self.get_interval_start(transaction.timestamp, interval))
+            .collect();
+        let mut closed = Vec::new();
+        let mut position = 0;
+        while position < batch.len() {
+            let interval_start = starts[position];
+            let run = starts[position..].iter().take_while(|start| **start == interval_start).count();
+            let trades = &batch[position..position + run];
+            position += run;
+
+            if !interval_klines.contains_key(&interval_start) {
+                closed.extend(self.close_expired_klines(&interval_klines, interval_start, interval));
+            }
+            let mut kline = interval_klines.entry(interval_start).or_insert_with(|| {
+                KLine::new(token.to_string(), interval_start, interval, trades[0].price, 0.0)
+            });
+            // A new candle already opens at the first price, so only the volume is left to add
+            for transaction in trades {
+                kline.apply(transaction);
+            }
+        }
+        closed
+    }
+
+    /// Update a trade-count or volume K-line, returning the K-line it closed
+    ///
+    /// Like time-aligned candles, a full candle stays open until the next
+    /// trade starts a new one. Candles are keyed by the millisecond of their
+    /// first trade, moved past the previous candle when trades share one, so
+    /// keys stay unique and usable as cursors.
+    fn update_activity_kline(&self, transaction: &Transaction, interval: TimeInterval) -> Option<KLine> {
+        let shard = self.shard(&transaction.token);
+        let token_klines = shard.klines.entry(transaction.token.clone()).or_default();
+        let interval_klines = token_klines.entry(interval).or_default();
+        let mut bucket = shard
+            .activity_buckets
+            .entry((transaction.token.clone(), interval))
+            .or_insert(ActivityBucket {
+                start: DateTime::<Utc>::MIN_UTC,
+                trades: 0,
+            });
+
+        let mut closed = None;
+        match interval_klines.get_mut(&bucket.start) {
+            Some(mut kline) if !interval.is_full(bucket.trades, kline.volume) => {
+                kline.apply(transaction);
+                kline.last_trade_at = Some(transaction.timestamp);
+                bucket.trades += 1;
+                return None;
+            }
+            Some(mut kline) => {
+                kline.close();
+                closed = Some(kline.clone());
+            }
+            None => {}
+        }
+
+        let millisecond = transaction
+            .timestamp
+            .with_nanosecond(transaction.timestamp.nanosecond() / 1_000_000 * 1_000_000)
+            .unwrap_or(transaction.timestamp);
+        bucket.start = millisecond.max(bucket.start + Duration::milliseconds(1));
+        bucket.trades = 1;
+
+        let mut kline = KLine::new(transaction.token.clone(), bucket.start, interval, transaction.price, 0.0);
+        kline.apply(transaction);
+        kline.last_trade_at = Some(transaction.timestamp);
+        interval_klines.insert(bucket.start, kline);
+
+        closed
+    }
+
+    /// Update K-line for a specific interval, returning the K-lines it closed
+    fn update_kline_for_interval(&self, transaction: &Transaction, interval: TimeInterval) -> Vec<KLine> {
         let interval_start = self.get_interval_start(transaction.timestamp, interval);
 
         // Get or create token-level map
-        let token_klines = self.klines.entry(transaction.token.clone()).or_default();
+        let token_klines = self.klines(&transaction.token).entry(transaction.token.clone()).or_default();
 
         // Get or create interval-level map
         let interval_klines = token_klines.entry(interval).or_default();
 
-        // Close expired K-lines before updating
-        self.close_expired_klines(&interval_klines, interval_start, interval);
+        // Close expired K-lines before updating; they can only expire when a
+        // trade starts a new interval, so the scan is skipped otherwise
+        let closed = if interval_klines.contains_key(&interval_start) {
+            Vec::new()
+        } else {
+            self.close_expired_klines(&interval_klines, interval_start, interval)
+        };
 
         // Update or create K-line for this interval
         interval_klines
             .entry(interval_start)
-            .and_modify(|kline| {
-                kline.update(transaction.price, transaction.volume);
-            })
-            .or_insert_with(|| {
-                KLine::new(
-                    transaction.token.clone(),
-                    interval_start,
-                    interval,
-                    transaction.price,
-                    transaction.volume,
-                )
-            });
+            .or_insert_with(|| KLine::new(transaction.token.clone(), interval_start, interval, transaction.price, 0.0))
+            .apply(transaction);
+
+        closed
     }
 
-    /// Close K-lines that have expired (interval has passed)
+    /// Close K-lines that have expired (interval has passed), returning them
     fn close_expired_klines(
         &self,
         interval_klines: &DashMap<DateTime<Utc>, KLine>,
         current_interval_start: DateTime<Utc>,
         interval: TimeInterval,
-    ) {
-        let interval_duration = Duration::seconds(interval.duration_seconds() as i64);
+    ) -> Vec<KLine> {
+        let Some(seconds) = interval.duration_seconds() else {
+            return Vec::new();
+        };
+        let interval_duration = Duration::seconds(seconds as i64);
 
         // Iterate through all K-lines and close expired ones
+        let mut closed = Vec::new();
         for mut kline_ref in interval_klines.iter_mut() {
             let kline = kline_ref.value_mut();
             if kline.timestamp + interval_duration <= current_interval_start && !kline.is_closed {
                 kline.close();
+                closed.push(kline.clone());
             }
         }
+        closed
     }
 
     /// Get the start timestamp for an interval
@@ -126,10 +687,16 @@ impl KLineService {
                     .and_then(|t| t.with_nanosecond(0))
                     .unwrap_or(timestamp)
             }
+            // Trade-count and volume candles are keyed by their first trade
+            TimeInterval::Trades(_) | TimeInterval::Volume(_) => timestamp,
         }
     }
 
-    /// Get K-lines for a token and interval within a time range
+    /// Get K-lines for a token and interval within a time range, oldest first
+    ///
+    /// With a `limit`, the newest `limit` K-lines of the range are kept, as in
+    /// the first page of [`Self::get_klines_page`].
+    #[tracing::instrument(level = "debug", skip(self))]
     pub fn get_klines(
         &self,
         token: &str,
@@ -140,7 +707,7 @@ impl KLineService {
     ) -> Vec<KLine> {
         let mut result = Vec::new();
 
-        if let Some(token_klines) = self.klines.get(token) {
+        if let Some(token_klines) = self.klines(token).get(token) {
             if let Some(interval_klines) = token_klines.get(&interval) {
                 for kline_ref in interval_klines.iter() {
                     let (timestamp, kline) = kline_ref.pair();
@@ -152,19 +719,92 @@ impl KLineService {
         }
 
         // Sort by timestamp
-        result.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        result.sort_by_key(|a| a.timestamp);
 
-        // Apply limit if specified
+        // Keep the newest candles if limited
         if let Some(limit) = limit {
-            result.truncate(limit);
+            result.drain(..result.len().saturating_sub(limit));
         }
 
         result
     }
 
+    /// Get up to `limit` K-lines next to a cursor
+    ///
+    /// `Before` pages return the newest candles older than the cursor and
+    /// `After` pages the oldest candles newer than it, so following
+    /// `next_cursor` walks the retained history without gaps or repeats.
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn get_klines_page(
+        &self,
+        token: &str,
+        interval: TimeInterval,
+        cursor: KLineCursor,
+        limit: usize,
+    ) -> KLinePage {
+        let mut klines = match cursor {
+            KLineCursor::Before(before) => {
+                self.get_klines(token, interval, DateTime::<Utc>::MIN_UTC, before, None)
+            }
+            KLineCursor::After(after) => {
+                self.get_klines(token, interval, after, DateTime::<Utc>::MAX_UTC, None)
+            }
+        };
+        klines.retain(|kline| kline.timestamp != cursor.timestamp());
+
+        let more = klines.len() > limit;
+        let next_cursor = match cursor {
+            KLineCursor::Before(_) => {
+                klines.drain(..klines.len().saturating_sub(limit));
+                klines.first().map(|kline| KLineCursor::Before(kline.timestamp))
+            }
+            KLineCursor::After(_) => {
+                klines.truncate(limit);
+                klines.last().map(|kline| KLineCursor::After(kline.timestamp))
+            }
+        };
+
+        KLinePage {
+            klines,
+            next_cursor: next_cursor.filter(|_| more),
+        }
+    }
+
+    /// Get a token's `limit` most recent closed K-lines, oldest first
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn get_closed_klines(&self, token: &str, interval: TimeInterval, limit: usize) -> Vec<KLine> {
+        // At most one candle per interval is still open
+        let mut klines = self
+            .get_klines_page(token, interval, KLineCursor::Before(DateTime::<Utc>::MAX_UTC), limit + 1)
+            .klines;
+        klines.retain(|kline| kline.is_closed);
+        klines.drain(..klines.len().saturating_sub(limit));
+        klines
+    }
+
+    /// Get the open times of a token's earliest and latest stored K-lines
+    pub fn get_candle_range(&self, token: &str, interval: TimeInterval) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let token_klines = self.klines(token).get(token)?;
+        let interval_klines = token_klines.get(&interval)?;
+        let earliest = interval_klines.iter().map(|kline_ref| *kline_ref.key()).min()?;
+        let latest = interval_klines.iter().map(|kline_ref| *kline_ref.key()).max()?;
+        Some((earliest, latest))
+    }
+
+    /// Get the open time of a token's most recent closed K-line
+    pub fn get_last_closed_timestamp(&self, token: &str, interval: TimeInterval) -> Option<DateTime<Utc>> {
+        let token_klines = self.klines(token).get(token)?;
+        let interval_klines = token_klines.get(&interval)?;
+        interval_klines
+            .iter()
+            .filter(|kline_ref| kline_ref.value().is_closed)
+            .map(|kline_ref| *kline_ref.key())
+            .max()
+    }
+
     /// Get the latest K-line for a token and interval
     pub fn get_latest_kline(&self, token: &str, interval: TimeInterval) -> Option<KLine> {
-        if let Some(token_klines) = self.klines.get(token) {
+        if let Some(token_klines) = self.klines(token).get(token) {
             if let Some(interval_klines) = token_klines.get(&interval) {
                 // Find the most recent K-line
                 interval_klines
@@ -179,17 +819,117 @@ impl KLineService {
         }
     }
 
+    /// Compute 24-hour statistics for a token from its 1m candles
+    pub fn get_ticker(&self, token: &str) -> Option<Ticker> {
+        let now = Utc::now();
+        let klines = self.get_klines(token, TimeInterval::Minute1, now - Duration::hours(24), now, None);
+        let mut ticker = Ticker::from_klines(token, &klines, now)?;
+        ticker.extremes = self.shard(token).price_stats.get(token).map(|stats| stats.extremes(now));
+        Some(ticker)
+    }
+
+    /// Summarize a token's candles opened in `[start, end)` into one OHLCV bar
+    ///
+    /// The range is covered with the coarsest time candles fitting inside it
+    /// and finer ones towards its edges, down to 1s candles, so long ranges
+    /// fold few candles. Returns `None` when no candle opened in the range.
+    pub fn get_ohlc(&self, token: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<OhlcSummary> {
+        let mut klines = Vec::new();
+        self.collect_range(token, &TimeInterval::all(), start, end, &mut klines);
+        klines.sort_by_key(|kline| kline.timestamp);
+        let (first, last) = (klines.first()?, klines.last()?);
+
+        Some(OhlcSummary {
+            token: token.to_string(),
+            start: first.timestamp,
+            end: last.timestamp,
+            open: first.open,
+            high: klines.iter().map(|kline| kline.high).fold(f64::MIN, f64::max),
+            low: klines.iter().map(|kline| kline.low).fold(f64::MAX, f64::min),
+            close: last.close,
+            volume: klines.iter().map(|kline| kline.volume).sum(),
+            candles: klines.len(),
+        })
+    }
+
+    /// Collect candles opened in `[start, end)`, using the last of `intervals` where aligned
+    fn collect_range(
+        &self,
+        token: &str,
+        intervals: &[TimeInterval],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        klines: &mut Vec<KLine>,
+    ) {
+        let Some((interval, finer)) = intervals.split_last() else {
+            return;
+        };
+        if start >= end {
+            return;
+        }
+        let in_range = |from: DateTime<Utc>, to: DateTime<Utc>| {
+            self.get_klines(token, *interval, from, to, None)
+                .into_iter()
+                .filter(move |kline| kline.timestamp < to)
+        };
+        if finer.is_empty() {
+            klines.extend(in_range(start, end));
+            return;
+        }
+
+        // The part of the range made of whole candles of this interval
+        let span = TimeDelta::seconds(interval.duration_seconds().unwrap_or(1) as i64);
+        let aligned = start.duration_trunc(span).ok().zip(end.duration_trunc(span).ok());
+        let Some((aligned_start, aligned_end)) = aligned
+            .map(|(floor, aligned_end)| (if floor < start { floor + span } else { floor }, aligned_end))
+            .filter(|(aligned_start, aligned_end)| aligned_start < aligned_end)
+        else {
+            return self.collect_range(token, finer, start, end, klines);
+        };
+        klines.extend(in_range(aligned_start, aligned_end));
+        self.collect_range(token, finer, start, aligned_start, klines);
+        self.collect_range(token, finer, aligned_end, end, klines);
+    }
+
     /// Get all available tokens
     pub fn get_available_tokens(&self) -> Vec<String> {
-        self.klines
+        self.shards
             .iter()
-            .map(|entry| entry.key().clone())
+            .flat_map(|shard| shard.klines.iter().map(|entry| entry.key().clone()))
             .collect()
     }
 
+    /// Whether a token has a series, even one without candles yet
+    pub fn has_token(&self, token: &str) -> bool {
+        self.klines(token).contains_key(token)
+    }
+
+    /// Track a token before its first transaction, so it is listed as available
+    ///
+    /// Returns `false` if the token is already tracked.
+    pub fn add_token(&self, token: &str) -> bool {
+        match self.klines(token).entry(token.to_string()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(DashMap::new());
+                true
+            }
+        }
+    }
+
+    /// Drop every K-line of a token
+    ///
+    /// Returns `false` if the token was not tracked.
+    pub fn remove_token(&self, token: &str) -> bool {
+        let shard = self.shard(token);
+        shard.activity_buckets.retain(|(bucket_token, _), _| bucket_token != token);
+        shard.price_stats.remove(token);
+        shard.klines.remove(token).is_some()
+    }
+
     /// Get current open K-line for a token and interval
     pub fn get_current_kline(&self, token: &str, interval: TimeInterval) -> Option<KLine> {
-        if let Some(token_klines) = self.klines.get(token) {
+        if let Some(token_klines) = self.klines(token).get(token) {
             if let Some(interval_klines) = token_klines.get(&interval) {
                 // Find the most recent open K-line
                 interval_klines
@@ -204,6 +944,244 @@ impl KLineService {
             None
         }
     }
+
+    /// Close every open K-line as partial, returning them
+    ///
+    /// Used at shutdown so the final state of each candle is delivered.
+    pub fn close_open_klines(&self) -> Vec<KLine> {
+        let mut closed = Vec::new();
+        for shard in &self.shards {
+            for token_klines in shard.klines.iter() {
+                for interval_klines in token_klines.iter() {
+                    for mut kline in interval_klines.iter_mut() {
+                        if !kline.is_closed {
+                            kline.close_partial();
+                            closed.push(kline.clone());
+                        }
+                    }
+                }
+            }
+            shard.activity_buckets.clear();
+        }
+        closed
+    }
+
+    /// Remove K-lines whose interval ended before `cutoff`
+    ///
+    /// Trade-count and volume K-lines are removed once closed with their last
+    /// trade before `cutoff`. Returns the number of K-lines removed.
+    pub fn remove_klines_before(&self, cutoff: DateTime<Utc>) -> usize {
+        self.drain_klines_before(cutoff).len()
+    }
+
+    /// Remove K-lines like [`Self::remove_klines_before`], returning them
+    pub fn drain_klines_before(&self, cutoff: DateTime<Utc>) -> Vec<KLine> {
+        let mut removed = Vec::new();
+        for token_klines in self.shards.iter().flat_map(|shard| shard.klines.iter()) {
+            for interval_klines in token_klines.iter() {
+                let expired = |kline: &KLine| match kline.interval.duration_seconds() {
+                    Some(seconds) => kline.timestamp + Duration::seconds(seconds as i64) < cutoff,
+                    None => kline.is_closed && kline.close_time() < cutoff,
+                };
+                let timestamps: Vec<DateTime<Utc>> = interval_klines
+                    .iter()
+                    .filter(|kline| expired(kline.value()))
+                    .map(|kline| *kline.key())
+                    .collect();
+                removed.extend(timestamps.iter().filter_map(|timestamp| interval_klines.remove(timestamp)).map(|(_, kline)| kline));
+            }
+        }
+        removed
+    }
+
+    /// Count the candles of every series and estimate the memory they use
+    pub fn storage_stats(&self) -> StorageStats {
+        // A candle, its key and the hash map slot holding both
+        let entry_size = mem::size_of::<(DateTime<Utc>, KLine)>() + mem::size_of::<u64>();
+        let mut series = Vec::new();
+        let mut estimated_memory_bytes = 0;
+        for token_klines in self.shards.iter().flat_map(|shard| shard.klines.iter()) {
+            let token = token_klines.key();
+            for interval_klines in token_klines.iter() {
+                let range = interval_klines.iter().map(|kline| *kline.key()).fold(None, |range, timestamp| {
+                    let (oldest, newest) = range.unwrap_or((timestamp, timestamp));
+                    Some((timestamp.min(oldest), timestamp.max(newest)))
+                });
+                let Some((oldest, newest)) = range else {
+                    continue;
+                };
+                estimated_memory_bytes += interval_klines.len() * (entry_size + token.len());
+                series.push(SeriesStats {
+                    token: token.clone(),
+                    interval: *interval_klines.key(),
+                    candles: interval_klines.len(),
+                    oldest,
+                    newest,
+                });
+            }
+        }
+
+        let intervals = self.intervals();
+        let position = |interval: &TimeInterval| intervals.iter().position(|i| i == interval);
+        series.sort_by(|a, b| (&a.token, position(&a.interval)).cmp(&(&b.token, position(&b.interval))));
+        StorageStats {
+            total_candles: series.iter().map(|series| series.candles).sum(),
+            estimated_memory_bytes,
+            series,
+        }
+    }
+
+    /// Copy every tracked token and candle into a serializable snapshot
+    pub fn snapshot(&self) -> KLineSnapshot {
+        let mut snapshot = KLineSnapshot {
+            taken_at: Utc::now(),
+            tokens: Vec::new(),
+            klines: Vec::new(),
+            activity_buckets: Vec::new(),
+            wal_sequence: None,
+        };
+        for shard in &self.shards {
+            for token_klines in shard.klines.iter() {
+                snapshot.tokens.push(token_klines.key().clone());
+                for interval_klines in token_klines.iter() {
+                    snapshot.klines.extend(interval_klines.iter().map(|kline| kline.value().clone()));
+                }
+            }
+            snapshot.activity_buckets.extend(shard.activity_buckets.iter().map(|bucket| {
+                let (token, interval) = bucket.key().clone();
+                ActivityBucketSnapshot {
+                    token,
+                    interval,
+                    start: bucket.start,
+                    trades: bucket.trades,
+                }
+            }));
+        }
+        snapshot.tokens.sort();
+        snapshot.klines.sort_by(|a, b| (&a.token, a.timestamp).cmp(&(&b.token, b.timestamp)));
+        snapshot
+    }
+
+    /// Replace every candle with those of a snapshot, returning how many were restored
+    ///
+    /// Candles of trade-count and volume intervals that are no longer
+    /// aggregated are skipped. Tokens are repartitioned into this service's
+    /// shards, so a snapshot can be restored with a different shard count.
+    pub fn restore(&self, snapshot: KLineSnapshot) -> usize {
+        for shard in &self.shards {
+            shard.klines.clear();
+            shard.activity_buckets.clear();
+        }
+
+        for token in &snapshot.tokens {
+            self.add_token(token);
+        }
+        let mut restored = 0;
+        for kline in snapshot.klines {
+            if self.insert_kline(kline) {
+                restored += 1;
+            }
+        }
+        for bucket in snapshot.activity_buckets.into_iter().filter(|bucket| self.supports(bucket.interval)) {
+            self.shard(&bucket.token).activity_buckets.insert(
+                (bucket.token, bucket.interval),
+                ActivityBucket {
+                    start: bucket.start,
+                    trades: bucket.trades,
+                },
+            );
+        }
+        restored
+    }
+
+    /// Store a K-line as it is, replacing the one with the same token, interval and timestamp
+    ///
+    /// Used to mirror candles built elsewhere. Returns `false`, storing
+    /// nothing, for trade-count and volume intervals that are not aggregated.
+    pub fn insert_kline(&self, kline: KLine) -> bool {
+        if !self.supports(kline.interval) {
+            return false;
+        }
+        self.klines(&kline.token)
+            .entry(kline.token.clone())
+            .or_default()
+            .entry(kline.interval)
+            .or_default()
+            .insert(kline.timestamp, kline);
+        true
+    }
+
+    /// Correct the values of a stored candle, whether open or closed
+    ///
+    /// The candle keeps its closed and partial state. Returns the amended
+    /// candle; later trades of an open candle build on the corrected values.
+    pub fn amend_kline(&self, amendment: &KLineAmendment) -> Result<KLine, AmendError> {
+        amendment.validate().map_err(AmendError::Invalid)?;
+        let token_klines = self.klines(&amendment.token).get(&amendment.token).ok_or(AmendError::NotFound)?;
+        let interval_klines = token_klines.get(&amendment.interval).ok_or(AmendError::NotFound)?;
+        let mut kline = interval_klines.get_mut(&amendment.timestamp).ok_or(AmendError::NotFound)?;
+
+        match (amendment.buy_volume, amendment.sell_volume) {
+            (Some(buy_volume), Some(sell_volume)) => {
+                kline.buy_volume = buy_volume;
+                kline.sell_volume = sell_volume;
+            }
+            // Without a stored split to scale, neither side can be told apart
+            _ => match kline.buy_volume + kline.sell_volume {
+                stored if stored > 0.0 => {
+                    let scale = amendment.volume / stored;
+                    kline.buy_volume *= scale;
+                    kline.sell_volume *= scale;
+                }
+                _ => {
+                    kline.buy_volume = amendment.volume / 2.0;
+                    kline.sell_volume = amendment.volume / 2.0;
+                }
+            },
+        }
+        kline.open = amendment.open;
+        kline.high = amendment.high;
+        kline.low = amendment.low;
+        kline.close = amendment.close;
+        kline.volume = amendment.volume;
+        Ok(kline.clone())
+    }
+
+    /// Compute a checksum over closed K-lines starting at or after `since`
+    ///
+    /// Returns the number of candles covered and an FNV-1a hash of their
+    /// timestamps and OHLCV values, so two instances can cheaply compare state.
+    pub fn checksum(
+        &self,
+        token: &str,
+        interval: TimeInterval,
+        since: DateTime<Utc>,
+    ) -> (usize, u64) {
+        // get_klines returns candles sorted by timestamp
+        let klines: Vec<KLine> = self
+            .get_klines(token, interval, since, DateTime::<Utc>::MAX_UTC, None)
+            .into_iter()
+            .filter(|kline| kline.is_closed)
+            .collect();
+
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for kline in &klines {
+            let fields = [
+                kline.timestamp.timestamp_millis() as u64,
+                kline.open.to_bits(),
+                kline.high.to_bits(),
+                kline.low.to_bits(),
+                kline.close.to_bits(),
+                kline.volume.to_bits(),
+            ];
+            for byte in fields.iter().flat_map(|field| field.to_le_bytes()) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+
+        (klines.len(), hash)
+    }
 }
 
 impl Default for KLineService {
@@ -0,0 +1,279 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time;
+
+use crate::config::StorageConfig;
+use crate::models::{KLine, TimeInterval};
+use crate::services::KLineService;
+
+pub use crate::config::DEFAULT_KEY_PREFIX;
+
+/// Candle updates written to Redis in one round trip
+const WRITE_BATCH: usize = 512;
+
+/// Longest wait between attempts to reach Redis
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Queue of candle updates to write to shared storage, handed to the transaction pipeline
+///
+/// Writing never waits: when Redis is slow or down and the queue is full,
+/// updates are dropped. A dropped update of an open candle is superseded by
+/// its next one.
+#[derive(Debug, Clone)]
+pub struct KLineStoreWriter {
+    sender: mpsc::Sender<KLine>,
+}
+
+impl KLineStoreWriter {
+    /// Create a writer queueing up to `capacity` updates, with the receiving end of its queue
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<KLine>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (Self { sender }, receiver)
+    }
+
+    /// Queue a candle's latest state for shared storage
+    pub fn write(&self, kline: KLine) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(kline) {
+            tracing::debug!("Storage queue full, dropping candle update");
+        }
+    }
+}
+
+/// Candle storage shared by instances in Redis
+///
+/// Each series is a sorted set keyed `<prefix>:<token>:<interval>` holding
+/// the JSON form of its candles, scored by their open time in milliseconds,
+/// and `<prefix>:tokens` lists the tokens with candles. A single leader
+/// ingests trades and writes every candle they update; followers mirror
+/// the sets into memory, so any of them serves the leader's candles.
+#[derive(Debug)]
+pub struct RedisKLineStore {
+    client: redis::Client,
+    key_prefix: String,
+}
+
+impl RedisKLineStore {
+    /// Create a store on the configured Redis server; nothing connects until it is used
+    pub fn new(config: &StorageConfig) -> Result<Self, redis::RedisError> {
+        Ok(Self {
+            client: redis::Client::open(config.redis_url.as_str())?,
+            key_prefix: config.key_prefix.clone(),
+        })
+    }
+
+    /// Key of the sorted set holding a series' candles
+    pub fn key(&self, token: &str, interval: TimeInterval) -> String {
+        format!("{}:{}:{}", self.key_prefix, token, interval)
+    }
+
+    /// Key of the set listing the tokens with candles
+    pub fn tokens_key(&self) -> String {
+        format!("{}:tokens", self.key_prefix)
+    }
+
+    /// Stored form of a candle
+    pub fn encode(kline: &KLine) -> String {
+        serde_json::to_string(kline).unwrap_or_default()
+    }
+
+    /// Candle of a stored member, or `None` if it is malformed
+    pub fn decode(member: &str) -> Option<KLine> {
+        match serde_json::from_str(member) {
+            Ok(kline) => Some(kline),
+            Err(e) => {
+                tracing::warn!("Ignoring malformed stored candle: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Replace the stored state of each candle in one atomic round trip
+    pub async fn write(
+        &self,
+        connection: &mut impl redis::aio::ConnectionLike,
+        klines: &[KLine],
+    ) -> redis::RedisResult<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for kline in klines {
+            let key = self.key(&kline.token, kline.interval);
+            let score = kline.timestamp.timestamp_millis();
+            pipe.zrembyscore(&key, score, score)
+                .ignore()
+                .zadd(&key, Self::encode(kline), score)
+                .ignore()
+                .sadd(self.tokens_key(), &kline.token)
+                .ignore();
+        }
+        pipe.query_async(connection).await
+    }
+
+    /// Candles of each series opened at or after the given time, or all of them without one
+    pub async fn read_since(
+        &self,
+        connection: &mut impl redis::aio::ConnectionLike,
+        series: &[(String, TimeInterval, Option<DateTime<Utc>>)],
+    ) -> redis::RedisResult<Vec<KLine>> {
+        if series.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for (token, interval, since) in series {
+            let min = since.map_or("-inf".to_string(), |since| since.timestamp_millis().to_string());
+            pipe.zrangebyscore(self.key(token, *interval), min, "+inf");
+        }
+        let members: Vec<Vec<String>> = pipe.query_async(connection).await?;
+        Ok(members.iter().flatten().filter_map(|member| Self::decode(member)).collect())
+    }
+
+    /// Tokens with stored candles
+    pub async fn tokens(&self, connection: &mut impl redis::aio::ConnectionLike) -> redis::RedisResult<Vec<String>> {
+        redis::cmd("SMEMBERS").arg(self.tokens_key()).query_async(connection).await
+    }
+
+    /// Remove the stored candles of time-based series whose interval ended before `cutoff`
+    pub async fn remove_before(
+        &self,
+        tokens: &[String],
+        intervals: &[TimeInterval],
+        cutoff: DateTime<Utc>,
+    ) -> redis::RedisResult<()> {
+        let mut connection = self.client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        for token in tokens {
+            for interval in intervals {
+                let Some(seconds) = interval.duration_seconds() else {
+                    continue;
+                };
+                let max = (cutoff - ChronoDuration::seconds(seconds as i64)).timestamp_millis();
+                pipe.zrembyscore(self.key(token, *interval), "-inf", format!("({}", max)).ignore();
+            }
+        }
+        pipe.query_async(&mut connection).await
+    }
+
+    /// Write queued candle updates until every writer is dropped, reconnecting after failures
+    ///
+    /// Every candle already in memory is written first, so candles restored
+    /// from a snapshot or backfilled before the leader started are shared too.
+    /// Updates that fail to be written are lost until the candle updates again.
+    pub async fn run_writer(self: Arc<Self>, kline_service: Arc<KLineService>, mut receiver: mpsc::Receiver<KLine>) {
+        let mut pending = kline_service.snapshot().klines;
+        let mut retry_delay = Duration::from_secs(1);
+        loop {
+            let mut connection = match self.client.get_multiplexed_async_connection().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("Failed to connect to Redis for candle storage: {}", e);
+                    time::sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            retry_delay = Duration::from_secs(1);
+            tracing::info!("Writing candles to Redis under {}:*", self.key_prefix);
+
+            loop {
+                let mut failed = false;
+                while !pending.is_empty() {
+                    let batch: Vec<KLine> = pending.drain(..pending.len().min(WRITE_BATCH)).collect();
+                    if let Err(e) = self.write(&mut connection, &batch).await {
+                        tracing::warn!("Failed to write candles to Redis, reconnecting: {}", e);
+                        failed = true;
+                        break;
+                    }
+                }
+                if failed {
+                    break;
+                }
+                let Some(kline) = receiver.recv().await else {
+                    return;
+                };
+                let mut batch = vec![kline];
+                while batch.len() < WRITE_BATCH {
+                    match receiver.try_recv() {
+                        Ok(kline) => batch.push(kline),
+                        Err(_) => break,
+                    }
+                }
+                pending = latest_states(batch);
+            }
+        }
+    }
+
+    /// Mirror the stored candles into memory every `sync_interval`, reconnecting after failures
+    ///
+    /// Each sync reads every series from its latest candle in memory on, which
+    /// picks up the open candle's updates and any candle opened since.
+    pub async fn run_follower(self: Arc<Self>, kline_service: Arc<KLineService>, sync_interval: Duration) {
+        let mut retry_delay = Duration::from_secs(1);
+        loop {
+            let mut connection = match self.client.get_multiplexed_async_connection().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("Failed to connect to Redis for candle storage: {}", e);
+                    time::sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            retry_delay = Duration::from_secs(1);
+            tracing::info!("Following candles in Redis under {}:*", self.key_prefix);
+
+            let mut ticker = time::interval(sync_interval);
+            loop {
+                ticker.tick().await;
+                match self.sync(&mut connection, &kline_service).await {
+                    Ok(synced) if synced > 0 => tracing::trace!("Synced {} candles from Redis", synced),
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to sync candles from Redis, reconnecting: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy the candles written since the last sync into memory, returning how many were copied
+    pub async fn sync(
+        &self,
+        connection: &mut impl redis::aio::ConnectionLike,
+        kline_service: &KLineService,
+    ) -> redis::RedisResult<usize> {
+        let intervals = kline_service.intervals();
+        let mut series = Vec::new();
+        for token in self.tokens(connection).await? {
+            for interval in &intervals {
+                let since = kline_service.get_latest_kline(&token, *interval).map(|kline| kline.timestamp);
+                series.push((token.clone(), *interval, since));
+            }
+        }
+        let mut synced = 0;
+        for kline in self.read_since(connection, &series).await? {
+            if kline_service.insert_kline(kline) {
+                synced += 1;
+            }
+        }
+        Ok(synced)
+    }
+}
+
+/// Last queued state of each candle, in the order they were first queued
+fn latest_states(updates: Vec<KLine>) -> Vec<KLine> {
+    let mut positions = HashMap::new();
+    let mut latest: Vec<KLine> = Vec::with_capacity(updates.len());
+    for kline in updates {
+        match positions.get(&(kline.token.clone(), kline.interval, kline.timestamp)) {
+            Some(&position) => latest[position] = kline,
+            None => {
+                positions.insert((kline.token.clone(), kline.interval, kline.timestamp), latest.len());
+                latest.push(kline);
+            }
+        }
+    }
+    latest
+}
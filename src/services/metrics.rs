@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Cumulative counters for server activity
+#[derive(Debug, Default)]
+pub struct Metrics {
+    transactions_ingested: AtomicU64,
+    transactions_broadcast: AtomicU64,
+    klines_broadcast: AtomicU64,
+    /// Transactions dropped as replays of a recent trade ID
+    duplicates_dropped: AtomicU64,
+    /// Time of the latest ingested transaction, in milliseconds since epoch (0 = none yet)
+    last_ingest_millis: AtomicI64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a transaction applied to the K-line service
+    pub fn record_ingest(&self) {
+        self.transactions_ingested.fetch_add(1, Ordering::Relaxed);
+        self.last_ingest_millis.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// Transactions applied to the K-line service since startup
+    pub fn transactions_ingested(&self) -> u64 {
+        self.transactions_ingested.load(Ordering::Relaxed)
+    }
+
+    /// Time the latest transaction was ingested, if any
+    pub fn last_ingest_at(&self) -> Option<DateTime<Utc>> {
+        match self.last_ingest_millis.load(Ordering::Relaxed) {
+            0 => None,
+            millis => DateTime::from_timestamp_millis(millis),
+        }
+    }
+
+    /// Record a transaction dropped as a duplicate
+    pub fn record_duplicate(&self) {
+        self.duplicates_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Transactions dropped as duplicates since startup
+    pub fn duplicates_dropped(&self) -> u64 {
+        self.duplicates_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Record a transaction broadcast
+    pub fn record_transaction_broadcast(&self) {
+        self.transactions_broadcast.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a K-line update broadcast
+    pub fn record_kline_broadcast(&self) {
+        self.klines_broadcast.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn totals(&self) -> [u64; 3] {
+        [
+            self.transactions_ingested.load(Ordering::Relaxed),
+            self.transactions_broadcast.load(Ordering::Relaxed),
+            self.klines_broadcast.load(Ordering::Relaxed),
+        ]
+    }
+}
+
+/// One time bucket of server metrics pushed to ops dashboards
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct OpsMetrics {
+    /// End of the bucket
+    pub timestamp: DateTime<Utc>,
+    /// Transactions ingested per second
+    pub ingest_rate: f64,
+    /// Transactions broadcast per second
+    pub transaction_broadcast_rate: f64,
+    /// K-line updates broadcast per second
+    pub kline_broadcast_rate: f64,
+    /// Events queued for the slowest stream subscriber
+    pub event_queue_depth: usize,
+    /// Connected WebSocket sessions
+    pub sessions: usize,
+    /// Active WebSocket subscriptions
+    pub subscriptions: usize,
+    /// Messages dropped for slow WebSocket sessions since startup
+    pub dropped_messages: u64,
+    /// Sessions disconnected for falling behind since startup
+    pub slow_consumer_disconnects: u64,
+}
+
+/// Point-in-time connection statistics from the streaming layer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionStats {
+    /// Events queued for the slowest stream subscriber
+    pub event_queue_depth: usize,
+    /// Connected WebSocket sessions
+    pub sessions: usize,
+    /// Active WebSocket subscriptions
+    pub subscriptions: usize,
+    /// Messages dropped for slow WebSocket sessions since startup
+    pub dropped_messages: u64,
+    /// Sessions disconnected for falling behind since startup
+    pub slow_consumer_disconnects: u64,
+}
+
+/// Turns cumulative counters into per-bucket rates
+#[derive(Debug)]
+pub struct OpsMetricsSampler {
+    metrics: Arc<Metrics>,
+    last_totals: [u64; 3],
+    last_sample: Instant,
+}
+
+impl OpsMetricsSampler {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        let last_totals = metrics.totals();
+        Self {
+            metrics,
+            last_totals,
+            last_sample: Instant::now(),
+        }
+    }
+
+    /// Close the current bucket and compute its rates
+    pub fn sample(&mut self, connections: ConnectionStats) -> OpsMetrics {
+        let totals = self.metrics.totals();
+        let elapsed = self.last_sample.elapsed().as_secs_f64().max(f64::EPSILON);
+        let rate = |i: usize| (totals[i] - self.last_totals[i]) as f64 / elapsed;
+
+        let sample = OpsMetrics {
+            timestamp: Utc::now(),
+            ingest_rate: rate(0),
+            transaction_broadcast_rate: rate(1),
+            kline_broadcast_rate: rate(2),
+            event_queue_depth: connections.event_queue_depth,
+            sessions: connections.sessions,
+            subscriptions: connections.subscriptions,
+            dropped_messages: connections.dropped_messages,
+            slow_consumer_disconnects: connections.slow_consumer_disconnects,
+        };
+
+        self.last_totals = totals;
+        self.last_sample = Instant::now();
+        sample
+    }
+}
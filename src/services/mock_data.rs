@@ -1,109 +1,540 @@
-use rand::Rng;
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::Duration;
+use tokio::sync::watch;
 use tokio::time;
-use crate::models::Transaction;
-use crate::config::Config;
+use crate::models::{Side, Symbol, TradeId, Transaction};
+use crate::config::{ArrivalProcess, Config, RegimeConfig, VolumeConfig, VolumeDistribution};
+use crate::services::regimes::{RegimeEngine, TradeShape};
+use crate::services::scenario::Scenario;
+
+/// Shortest spacing of a token's backfilled trades
+pub const MIN_BACKFILL_STEP: Duration = Duration::from_secs(1);
+
+/// Source of generated transactions
+pub const MOCK_SOURCE: &str = "mock";
+
+/// Request to list a token at runtime
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct NewListing {
+    /// Token symbol, or a pair such as `DOGE-USDT`
+    pub symbol: String,
+    /// Price trades are generated around
+    pub base_price: f64,
+}
+
+impl NewListing {
+    /// Check the symbol and base price
+    pub fn validate(&self) -> Result<(), String> {
+        self.symbol.parse::<Symbol>()?;
+        if !self.base_price.is_finite() || self.base_price <= 0.0 {
+            return Err(format!("Invalid base_price: {}. Expected a positive number", self.base_price));
+        }
+        Ok(())
+    }
+}
+
+/// Tokens a generator produces trades for, shared with the admin API
+///
+/// Tokens listed or delisted at runtime take precedence over the configured
+/// token list, so they survive configuration reloads.
+#[derive(Debug, Default)]
+pub struct TokenListings {
+    inner: RwLock<Listings>,
+}
+
+#[derive(Debug, Default)]
+struct Listings {
+    /// Tokens and base prices from the configuration
+    configured: Vec<(String, f64)>,
+    /// Tokens listed at runtime, with their base prices
+    listed: Vec<(String, f64)>,
+    /// Tokens delisted at runtime
+    delisted: HashSet<String>,
+}
+
+impl Listings {
+    fn contains(&self, token: &str) -> bool {
+        self.listed.iter().any(|(t, _)| t == token)
+            || (!self.delisted.contains(token) && self.configured.iter().any(|(t, _)| t == token))
+    }
+}
+
+impl TokenListings {
+    /// Create listings for a configured token list
+    pub fn new(configured: Vec<(String, f64)>) -> Self {
+        Self {
+            inner: RwLock::new(Listings {
+                configured,
+                ..Listings::default()
+            }),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Listings> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Listings> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the configured token list, keeping runtime listings
+    pub fn set_configured(&self, configured: Vec<(String, f64)>) {
+        self.write().configured = configured;
+    }
+
+    /// Get the listed tokens and their base prices, configured tokens first
+    pub fn tokens(&self) -> Vec<(String, f64)> {
+        let listings = self.read();
+        listings
+            .configured
+            .iter()
+            .filter(|(token, _)| {
+                !listings.delisted.contains(token) && !listings.listed.iter().any(|(t, _)| t == token)
+            })
+            .chain(&listings.listed)
+            .cloned()
+            .collect()
+    }
+
+    /// Whether trades are generated for a token
+    pub fn contains(&self, token: &str) -> bool {
+        self.read().contains(token)
+    }
+
+    /// Start generating trades for a token around a base price
+    ///
+    /// Returns `false` if the token is already listed.
+    pub fn list(&self, token: &str, base_price: f64) -> bool {
+        let mut listings = self.write();
+        if listings.contains(token) {
+            return false;
+        }
+        listings.delisted.remove(token);
+        listings.listed.push((token.to_string(), base_price));
+        true
+    }
+
+    /// Stop generating trades for a token
+    ///
+    /// Returns `false` if the token is not listed.
+    pub fn delist(&self, token: &str) -> bool {
+        let mut listings = self.write();
+        if !listings.contains(token) {
+            return false;
+        }
+        listings.listed.retain(|(t, _)| t != token);
+        listings.delisted.insert(token.to_string());
+        true
+    }
+}
 
 /// Mock data generator for meme tokens
 #[derive(Debug)]
 pub struct MockDataGenerator {
-    /// Base prices for different tokens
-    base_prices: Vec<(String, f64)>,
+    /// Tokens and their base prices
+    listings: Arc<TokenListings>,
     /// Price volatility (percentage)
     volatility: f64,
     /// Volume range (min, max)
     volume_range: (f64, f64),
+    /// Timing of generated trades
+    arrival: ArrivalProcess,
+    /// Mean trades per second of tokens with a configured rate
+    arrival_rates: HashMap<String, f64>,
+    /// Trade size distribution of tokens without their own
+    volume: VolumeConfig,
+    /// Trade size distributions of tokens with their own
+    token_volumes: HashMap<String, VolumeConfig>,
+    /// Market regimes and scripted events shaping prices and volumes
+    regimes: Arc<RegimeEngine>,
+    /// ID of the next generated trade, counting up from 1
+    next_trade_id: AtomicU64,
+}
+
+/// Decides when each token trades
+#[derive(Debug)]
+enum ArrivalSchedule {
+    /// Every token trades on each tick
+    Fixed(time::Interval),
+    /// Each token trades at its own next arrival time
+    Poisson {
+        /// Mean gap of tokens without a configured rate
+        interval: Duration,
+        next: HashMap<String, time::Instant>,
+    },
+}
+
+impl ArrivalSchedule {
+    /// Wait for the next trades, returning the tokens that trade with their base prices
+    async fn next(&mut self, generator: &MockDataGenerator) -> Vec<(String, f64)> {
+        match self {
+            ArrivalSchedule::Fixed(interval) => {
+                interval.tick().await;
+                generator.listings.tokens()
+            }
+            ArrivalSchedule::Poisson { interval, next } => {
+                let tokens = generator.listings.tokens();
+                let now = time::Instant::now();
+                next.retain(|token, _| tokens.iter().any(|(listed, _)| listed == token));
+                for (token, _) in &tokens {
+                    if !next.contains_key(token) {
+                        next.insert(token.clone(), now + generator.arrival_gap(token, *interval));
+                    }
+                }
+
+                let earliest = next.iter().min_by_key(|(_, at)| **at).map(|(token, at)| (token.clone(), *at));
+                let Some((token, at)) = earliest else {
+                    // Every token was delisted; check again later
+                    time::sleep(*interval).await;
+                    return Vec::new();
+                };
+                time::sleep_until(at).await;
+                next.insert(token.clone(), at + generator.arrival_gap(&token, *interval));
+                tokens.into_iter().filter(|(listed, _)| *listed == token).collect()
+            }
+        }
+    }
 }
 
 impl MockDataGenerator {
     /// Create a new mock data generator
     pub fn new() -> Self {
         Self {
-            base_prices: vec![
+            listings: Arc::new(TokenListings::new(vec![
                 ("DOGE".to_string(), 0.15),
                 ("SHIB".to_string(), 0.00001),
                 ("PEPE".to_string(), 0.000001),
-            ],
+            ])),
             volatility: 0.02, // 2% volatility
             volume_range: (100.0, 1000.0),
+            arrival: ArrivalProcess::Fixed,
+            arrival_rates: HashMap::new(),
+            volume: VolumeConfig::default(),
+            token_volumes: HashMap::new(),
+            regimes: Arc::new(RegimeEngine::new(RegimeConfig::default())),
+            next_trade_id: AtomicU64::new(1),
         }
     }
 
     /// Create a new mock data generator with configuration
     pub fn new_with_config(config: &Config) -> Self {
-        let base_prices = if config.tokens.supported_tokens.is_empty() {
-            // Use default tokens if none configured
-            vec![
-                ("DOGE".to_string(), 0.15),
-                ("SHIB".to_string(), 0.00001),
-                ("PEPE".to_string(), 0.000001),
-            ]
-        } else {
-            // Use configured tokens
-            config.tokens.supported_tokens
-                .iter()
-                .map(|token| (token.symbol.clone(), token.base_price))
-                .collect()
+        let mut generator = Self::new();
+        generator.apply_config(config);
+        generator
+    }
+
+    /// Shared token list, for listing and delisting tokens at runtime
+    pub fn listings(&self) -> Arc<TokenListings> {
+        self.listings.clone()
+    }
+
+    /// Shared regime engine, for inspecting regimes and scripting events at runtime
+    pub fn regimes(&self) -> Arc<RegimeEngine> {
+        self.regimes.clone()
+    }
+
+    /// Take the token list, volatility, volumes, arrival process and regimes from a configuration
+    pub fn apply_config(&mut self, config: &Config) {
+        // Keep the default tokens if none are configured
+        if !config.tokens.supported_tokens.is_empty() {
+            self.listings.set_configured(
+                config.tokens.supported_tokens
+                    .iter()
+                    .map(|token| (token.symbol.clone(), token.base_price))
+                    .collect(),
+            );
+        }
+        self.volatility = config.data_generation.volatility;
+        self.volume_range = config.data_generation.volume_range;
+        self.arrival = config.data_generation.arrival;
+        self.arrival_rates = config
+            .tokens
+            .supported_tokens
+            .iter()
+            .filter_map(|token| Some((token.symbol.clone(), token.arrival_rate?)))
+            .collect();
+        self.volume = config.data_generation.volume.clone();
+        self.token_volumes = config
+            .tokens
+            .supported_tokens
+            .iter()
+            .filter_map(|token| Some((token.symbol.clone(), token.volume.clone()?)))
+            .collect();
+        self.regimes.set_config(config.data_generation.regimes.clone());
+    }
+
+    /// Draw the time until a token's next trade under Poisson arrivals
+    ///
+    /// Gaps are exponentially distributed with the token's `arrival_rate`, or
+    /// a mean of `interval` for tokens without one.
+    pub fn arrival_gap(&self, token: &str, interval: Duration) -> Duration {
+        let rate = self
+            .arrival_rates
+            .get(token)
+            .copied()
+            .unwrap_or_else(|| 1.0 / interval.as_secs_f64());
+        // 1 - [0, 1) keeps the logarithm finite
+        let uniform = 1.0 - rand::thread_rng().gen::<f64>();
+        Duration::from_secs_f64(-uniform.ln() / rate)
+    }
+
+    /// Draw the size of a token's next trade, before regime scaling
+    ///
+    /// Sizes are uniform within the volume range or log-normal, depending on
+    /// the token's volume distribution, and whale trades multiply them.
+    pub fn trade_volume(&self, token: &str) -> f64 {
+        self.draw_volume(token, &mut rand::thread_rng())
+    }
+
+    fn draw_volume(&self, token: &str, rng: &mut impl Rng) -> f64 {
+        let config = self.token_volumes.get(token).unwrap_or(&self.volume);
+
+        let volume = match config.distribution {
+            VolumeDistribution::Uniform => rng.gen_range(self.volume_range.0..self.volume_range.1),
+            VolumeDistribution::LogNormal => {
+                // Box-Muller transform; 1 - [0, 1) keeps the logarithm finite
+                let uniform = 1.0 - rng.gen::<f64>();
+                let normal = (-2.0 * uniform.ln()).sqrt() * (std::f64::consts::TAU * rng.gen::<f64>()).cos();
+                (config.mu + config.sigma * normal).exp()
+            }
         };
 
-        Self {
-            base_prices,
-            volatility: config.data_generation.volatility,
-            volume_range: config.data_generation.volume_range,
+        if config.whale_probability > 0.0 && rng.gen_bool(config.whale_probability) {
+            volume * config.whale_multiplier
+        } else {
+            volume
+        }
+    }
+
+    fn schedule(&self, interval_ms: u64) -> ArrivalSchedule {
+        let interval = Duration::from_millis(interval_ms);
+        match self.arrival {
+            ArrivalProcess::Fixed => ArrivalSchedule::Fixed(time::interval(interval)),
+            ArrivalProcess::Poisson => ArrivalSchedule::Poisson {
+                interval,
+                next: HashMap::new(),
+            },
         }
     }
 
     /// Generate a random transaction for a specific token
     pub fn generate_transaction(&self, token: &str) -> Option<Transaction> {
         // Find base price for the token
-        let base_price = self.base_prices
-            .iter()
+        let base_price = self.listings.tokens()
+            .into_iter()
             .find(|(t, _)| t == token)
-            .map(|(_, p)| *p)?;
+            .map(|(_, p)| p)?;
 
-        let mut rng = rand::thread_rng();
+        Some(self.generate_with_base_price(token, base_price))
+    }
+
+    /// Generate a random transaction around a known base price
+    ///
+    /// The current regime and scripted events move the price trades are
+    /// generated around and scale the volatility and volume.
+    fn generate_with_base_price(&self, token: &str, base_price: f64) -> Transaction {
+        let now = Utc::now();
+        let shape = self.regimes.next_trade(token, base_price, now);
+        self.generate_with_shape(token, shape, now, &mut rand::thread_rng())
+    }
 
+    /// Generate a random transaction at `timestamp` around a shaped price
+    fn generate_with_shape(
+        &self,
+        token: &str,
+        shape: TradeShape,
+        timestamp: DateTime<Utc>,
+        rng: &mut impl Rng,
+    ) -> Transaction {
         // Generate random price change within volatility range
-        let price_change = rng.gen_range(-self.volatility..self.volatility);
-        let price = base_price * (1.0 + price_change);
+        let volatility = self.volatility * shape.volatility_multiplier;
+        let price_change = if volatility > 0.0 {
+            rng.gen_range(-volatility..volatility)
+        } else {
+            0.0
+        };
+        let price = shape.price * (1.0 + price_change);
 
-        // Generate random volume
-        let volume = rng.gen_range(self.volume_range.0..self.volume_range.1);
+        let volume = self.draw_volume(token, rng) * shape.volume_multiplier;
 
         // Randomly decide if it's a buy or sell
-        let is_buy = rng.gen_bool(0.5);
+        let side = if rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
 
-        Some(Transaction::new(token.to_string(), price, volume, is_buy))
+        let mut transaction = Transaction::new(token.to_string(), price, volume, side);
+        transaction.timestamp = timestamp;
+        transaction.trade_id = Some(TradeId::Number(self.next_trade_id.fetch_add(1, Ordering::Relaxed)));
+        transaction.source = Some(MOCK_SOURCE.to_string());
+        transaction
+    }
+
+    /// Generate the trades of every token over the `hours` before `end`, oldest first
+    ///
+    /// Each token trades once per `interval_ms`, or once per
+    /// [`MIN_BACKFILL_STEP`] if that is longer, around its base price. Regimes
+    /// and scripted events only shape live trades.
+    pub fn generate_backfill(
+        &self,
+        hours: u64,
+        interval_ms: u64,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = Transaction> + '_ {
+        let step = TimeDelta::from_std(Duration::from_millis(interval_ms).max(MIN_BACKFILL_STEP))
+            .unwrap_or(TimeDelta::seconds(1));
+        let start = end - TimeDelta::hours(hours as i64);
+        let tokens = self.listings.tokens();
+        let neutral = |base_price| TradeShape {
+            price: base_price,
+            volatility_multiplier: 1.0,
+            volume_multiplier: 1.0,
+        };
+
+        std::iter::successors(Some(start), move |at| Some(*at + step))
+            .take_while(move |at| *at < end)
+            .flat_map(move |at| {
+                tokens
+                    .clone()
+                    .into_iter()
+                    .map(move |(token, base_price)| {
+                        self.generate_with_shape(&token, neutral(base_price), at, &mut rand::thread_rng())
+                    })
+            })
+    }
+
+    /// Generate the trades of a scenario on its virtual timeline, starting at `start`
+    ///
+    /// Every listed token trades once per `interval_ms` unless halted, shaped
+    /// by the scenario instead of the regimes. Trades are drawn from the
+    /// scenario's seed, so the same scenario and settings give the same trades.
+    pub fn generate_scenario(&self, scenario: &Scenario, interval_ms: u64, start: DateTime<Utc>) -> Vec<Transaction> {
+        let mut rng = StdRng::seed_from_u64(scenario.seed);
+        let step = Duration::from_millis(interval_ms.max(1));
+        let end = scenario.duration();
+        let tokens = self.listings.tokens();
+
+        let mut transactions = Vec::new();
+        let mut offset = Duration::ZERO;
+        while offset < end {
+            let timestamp = start + TimeDelta::from_std(offset).unwrap_or_default();
+            for (token, base_price) in &tokens {
+                let state = scenario.state(token, *base_price, offset);
+                if state.halted {
+                    continue;
+                }
+                let shape = TradeShape {
+                    price: state.price,
+                    volatility_multiplier: state.volatility_multiplier,
+                    volume_multiplier: state.volume_multiplier,
+                };
+                transactions.push(self.generate_with_shape(token, shape, timestamp, &mut rng));
+            }
+            offset += step;
+        }
+        transactions
+    }
+
+    /// Play the trades of a scenario in real time, returning how many were played
+    ///
+    /// The virtual timeline starts now, and each trade is passed to the
+    /// callback once its offset into the scenario has elapsed. The next trade
+    /// waits for the future the callback returns.
+    pub async fn run_scenario<F, Fut>(&self, scenario: &Scenario, interval_ms: u64, mut callback: F) -> usize
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let started = time::Instant::now();
+        let started_at = Utc::now();
+        let transactions = self.generate_scenario(scenario, interval_ms, started_at);
+        let played = transactions.len();
+        for transaction in transactions {
+            let offset = (transaction.timestamp - started_at).to_std().unwrap_or_default();
+            time::sleep_until(started + offset).await;
+            callback(transaction).await;
+        }
+        played
     }
 
     /// Generate a random transaction for any available token
+    ///
+    /// Panics if every token was delisted.
     pub fn generate_random_transaction(&self) -> Transaction {
         let mut rng = rand::thread_rng();
-        let token_index = rng.gen_range(0..self.base_prices.len());
-        let (token, _) = &self.base_prices[token_index];
+        let tokens = self.listings.tokens();
+        let token_index = rng.gen_range(0..tokens.len());
+        let (token, base_price) = &tokens[token_index];
         
-        self.generate_transaction(token).unwrap()
+        self.generate_with_base_price(token, *base_price)
     }
 
     /// Get all available tokens
     pub fn get_available_tokens(&self) -> Vec<String> {
-        self.base_prices.iter().map(|(token, _)| token.clone()).collect()
+        self.listings.tokens().into_iter().map(|(token, _)| token).collect()
     }
 
     /// Start continuous data generation
-    pub async fn start_continuous_generation<F>(&self, mut callback: F, interval_ms: u64)
+    ///
+    /// With fixed arrivals every token trades each `interval_ms`; with Poisson
+    /// arrivals tokens trade independently, `interval_ms` apart on average
+    /// unless they have their own rate. Generation waits for the future the
+    /// callback returns, so a slow consumer slows it down.
+    pub async fn start_continuous_generation<F, Fut>(&self, mut callback: F, interval_ms: u64)
     where
-        F: FnMut(Transaction) + Send + 'static,
+        F: FnMut(Transaction) -> Fut + Send + 'static,
+        Fut: Future<Output = ()>,
     {
-        let mut interval = time::interval(Duration::from_millis(interval_ms));
-        
+        let mut schedule = self.schedule(interval_ms);
+
         loop {
-            interval.tick().await;
-            
-            // Generate transactions for all tokens
-            for (token, _) in &self.base_prices {
-                if let Some(transaction) = self.generate_transaction(token) {
-                    callback(transaction);
+            for (token, base_price) in schedule.next(self).await {
+                callback(self.generate_with_base_price(&token, base_price)).await;
+            }
+        }
+    }
+
+    /// Start continuous data generation that follows configuration reloads
+    ///
+    /// The token list, volatility, generation interval and arrival process are
+    /// taken from every configuration published on the channel.
+    pub async fn start_reloadable_generation<F, Fut>(
+        mut self,
+        mut callback: F,
+        mut config: watch::Receiver<Arc<Config>>,
+    ) where
+        F: FnMut(Transaction) -> Fut + Send + 'static,
+        Fut: Future<Output = ()>,
+    {
+        let mut interval_ms = config.borrow().data_generation.interval_ms;
+        let mut schedule = self.schedule(interval_ms);
+
+        loop {
+            tokio::select! {
+                tokens = schedule.next(&self) => {
+                    for (token, base_price) in tokens {
+                        callback(self.generate_with_base_price(&token, base_price)).await;
+                    }
+                }
+                changed = config.changed() => {
+                    if changed.is_err() {
+                        // The sender is gone, so keep generating with the last settings
+                        self.start_continuous_generation(callback, interval_ms).await;
+                        return;
+                    }
+                    let config = config.borrow_and_update().clone();
+                    let arrival = self.arrival;
+                    self.apply_config(&config);
+                    if config.data_generation.interval_ms != interval_ms || self.arrival != arrival {
+                        interval_ms = config.data_generation.interval_ms;
+                        schedule = self.schedule(interval_ms);
+                    }
                 }
             }
         }
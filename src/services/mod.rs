@@ -1,6 +1,85 @@
+pub mod agg_trades;
+#[cfg(feature = "server")]
+pub mod alerts;
+#[cfg(feature = "server")]
+pub mod archive;
+pub mod bars;
+pub mod candle_transform;
+#[cfg(feature = "server")]
+pub mod clickhouse;
+#[cfg(feature = "server")]
+pub mod config_reload;
+pub mod conversion;
+pub mod correlation;
+pub mod dedup;
+pub mod delta;
+#[cfg(feature = "server")]
+pub mod export;
+#[cfg(feature = "server")]
+pub mod fanout;
+pub mod index;
+#[cfg(feature = "server")]
+pub mod influx;
+pub mod indicator_stream;
+pub mod indicators;
+pub mod integrity;
 pub mod kline;
+#[cfg(feature = "server")]
+pub mod kline_store;
+pub mod metrics;
 pub mod mock_data;
+pub mod order_book;
+#[cfg(feature = "server")]
+pub mod pipeline;
+pub mod price_stats;
+pub mod query_cache;
+pub mod regimes;
+pub mod replay;
+pub mod resample;
+#[cfg(feature = "server")]
+pub mod replication;
+pub mod scenario;
+pub mod symbols;
+pub mod token_registry;
+pub mod trades;
+pub mod volume_profile;
+pub mod wal;
+#[cfg(feature = "server")]
+pub mod webhooks;
 
 // Re-export for convenience
-pub use kline::KLineService;
-pub use mock_data::MockDataGenerator;
+#[cfg(feature = "server")]
+pub use alerts::AlertService;
+#[cfg(feature = "server")]
+pub use archive::CandleArchive;
+#[cfg(feature = "server")]
+pub use clickhouse::ClickHouseWriter;
+#[cfg(feature = "server")]
+pub use config_reload::ConfigWatcher;
+pub use conversion::QuoteConverter;
+pub use dedup::TradeDeduplicator;
+#[cfg(feature = "server")]
+pub use fanout::{FanoutPublisher, RedisFanout};
+pub use index::IndexPriceAggregator;
+#[cfg(feature = "server")]
+pub use influx::InfluxWriter;
+#[cfg(feature = "server")]
+pub use kline_store::{KLineStoreWriter, RedisKLineStore};
+pub use kline::{AmendError, BatchOutcome, IngestError, KLineAmendment, KLineCursor, KLinePage, KLineService, KLineSnapshot, SortOrder};
+pub use metrics::{Metrics, OpsMetricsSampler};
+pub use mock_data::{MockDataGenerator, TokenListings};
+pub use order_book::OrderBookSimulator;
+#[cfg(feature = "server")]
+pub use pipeline::{IngestSender, TransactionPipeline};
+pub use query_cache::KLineQueryCache;
+pub use regimes::RegimeEngine;
+pub use replay::TransactionReplayer;
+#[cfg(feature = "server")]
+pub use replication::{ReplicationState, Replicator};
+pub use scenario::Scenario;
+pub use symbols::SymbolTable;
+pub use token_registry::TokenRegistry;
+pub use trades::TradeService;
+#[cfg(feature = "server")]
+pub use webhooks::WebhookDispatcher;
+pub use wal::{TransactionLog, WalEntry};
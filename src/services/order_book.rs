@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::models::{Side, Transaction};
+
+/// Default number of price levels kept on each side of a book
+pub const DEFAULT_DEPTH_LEVELS: usize = 20;
+
+/// Random quantity changes and cancels applied to a book per trade
+const NOISE_EVENTS: usize = 3;
+
+/// Decimal places book quantities are rounded to
+const QUANTITY_DECIMALS: i32 = 4;
+
+/// Resting quantity at a price
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct PriceLevel {
+    pub price: f64,
+    /// Quantity at the price; 0 in an update means the level was removed
+    pub quantity: f64,
+}
+
+/// Full order book of a token, best prices first
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct DepthSnapshot {
+    pub token: String,
+    /// ID of the last update applied to the book
+    pub update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Levels of a token's order book changed by one trade, best prices first
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct DepthUpdate {
+    pub token: String,
+    /// One more than the ID of the previous update of the book
+    pub update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Synthetic L2 book of a token, with prices stored as multiples of the tick
+#[derive(Debug, Clone)]
+struct OrderBook {
+    tick: f64,
+    /// Decimal places of the tick, so prices print without floating-point noise
+    decimals: i32,
+    bids: BTreeMap<i64, f64>,
+    asks: BTreeMap<i64, f64>,
+    update_id: u64,
+}
+
+impl OrderBook {
+    /// Create an empty book with a tick of a thousandth of the price's magnitude
+    fn new(price: f64) -> Self {
+        let exponent = price.log10().floor() as i32 - 3;
+        Self {
+            tick: 10f64.powi(exponent),
+            decimals: (-exponent).max(0),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            update_id: 0,
+        }
+    }
+
+    fn index(&self, price: f64) -> i64 {
+        (price / self.tick).round() as i64
+    }
+
+    fn price(&self, index: i64) -> f64 {
+        round_to(index as f64 * self.tick, self.decimals)
+    }
+
+    /// Fill resting liquidity at the trade price, then restore `levels` levels per side
+    fn apply_trade(&mut self, transaction: &Transaction, levels: usize) {
+        let traded = self.index(transaction.price);
+
+        // The aggressor sweeps the passive side up to the trade price, and the
+        // book stays uncrossed around it
+        let passive = if transaction.side == Side::Buy {
+            self.asks.retain(|&index, _| index >= traded);
+            self.bids.retain(|&index, _| index < traded);
+            &mut self.asks
+        } else {
+            self.bids.retain(|&index, _| index <= traded);
+            self.asks.retain(|&index, _| index > traded);
+            &mut self.bids
+        };
+        if let Some(quantity) = passive.get_mut(&traded) {
+            *quantity = round_to(*quantity - transaction.volume, QUANTITY_DECIMALS);
+            if *quantity <= 0.0 {
+                passive.remove(&traded);
+            }
+        }
+
+        // Missing levels near the trade are added and levels beyond the depth cancelled
+        let mut rng = rand::thread_rng();
+        for offset in 1..=levels as i64 {
+            for (side, index) in [(&mut self.bids, traded - offset), (&mut self.asks, traded + offset)] {
+                side.entry(index)
+                    .or_insert_with(|| round_to(transaction.volume * rng.gen_range(0.5..5.0), QUANTITY_DECIMALS));
+            }
+        }
+        while self.bids.len() > levels {
+            self.bids.pop_first();
+        }
+        while self.asks.len() > levels {
+            self.asks.pop_last();
+        }
+
+        // Random adds and cancels of resting orders
+        for _ in 0..NOISE_EVENTS {
+            let side = if rng.gen_bool(0.5) { &mut self.bids } else { &mut self.asks };
+            if side.is_empty() {
+                continue;
+            }
+            let position = rng.gen_range(0..side.len());
+            let Some((&index, quantity)) = side.iter_mut().nth(position) else {
+                continue;
+            };
+            if rng.gen_bool(0.25) {
+                side.remove(&index);
+            } else {
+                *quantity = round_to(*quantity * rng.gen_range(0.5..1.5), QUANTITY_DECIMALS);
+            }
+        }
+        self.update_id += 1;
+    }
+
+    /// Levels of a side, best first, limited to `limit`
+    fn levels(&self, side: &BTreeMap<i64, f64>, descending: bool, limit: usize) -> Vec<PriceLevel> {
+        let level = |(&index, &quantity): (&i64, &f64)| PriceLevel {
+            price: self.price(index),
+            quantity,
+        };
+        if descending {
+            side.iter().rev().take(limit).map(level).collect()
+        } else {
+            side.iter().take(limit).map(level).collect()
+        }
+    }
+
+    /// Levels of a side that differ from `before`, best first
+    fn changes(&self, before: &BTreeMap<i64, f64>, after: &BTreeMap<i64, f64>, descending: bool) -> Vec<PriceLevel> {
+        let mut indices: Vec<i64> = before
+            .keys()
+            .chain(after.keys())
+            .copied()
+            .filter(|index| before.get(index) != after.get(index))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if descending {
+            indices.reverse();
+        }
+        indices
+            .into_iter()
+            .map(|index| PriceLevel {
+                price: self.price(index),
+                quantity: after.get(&index).copied().unwrap_or(0.0),
+            })
+            .collect()
+    }
+}
+
+fn round_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Synthetic order books that follow the trades of each token
+///
+/// Every trade fills the resting quantity it crossed, re-centres the book on
+/// the trade price and applies a few random adds and cancels.
+#[derive(Debug)]
+pub struct OrderBookSimulator {
+    books: DashMap<String, OrderBook>,
+    levels: usize,
+}
+
+impl OrderBookSimulator {
+    /// Create a simulator keeping `levels` price levels on each side
+    pub fn new(levels: usize) -> Self {
+        Self {
+            books: DashMap::new(),
+            levels,
+        }
+    }
+
+    /// Price levels kept on each side
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+
+    /// Apply a trade to its token's book, returning the levels it changed
+    ///
+    /// The first trade of a token creates its book. Trades without a positive
+    /// price are ignored.
+    pub fn apply_trade(&self, transaction: &Transaction) -> Option<DepthUpdate> {
+        if !(transaction.price > 0.0 && transaction.price.is_finite()) {
+            return None;
+        }
+        let mut book = self
+            .books
+            .entry(transaction.token.clone())
+            .or_insert_with(|| OrderBook::new(transaction.price));
+        let (bids, asks) = (book.bids.clone(), book.asks.clone());
+        book.apply_trade(transaction, self.levels);
+
+        let bid_changes = book.changes(&bids, &book.bids, true);
+        let ask_changes = book.changes(&asks, &book.asks, false);
+        Some(DepthUpdate {
+            token: transaction.token.clone(),
+            update_id: book.update_id,
+            bids: bid_changes,
+            asks: ask_changes,
+            timestamp: Utc::now(),
+        })
+    }
+
+    /// Get a token's book, with at most `limit` levels per side
+    pub fn snapshot(&self, token: &str, limit: usize) -> Option<DepthSnapshot> {
+        let book = self.books.get(token)?;
+        Some(DepthSnapshot {
+            token: token.to_string(),
+            update_id: book.update_id,
+            bids: book.levels(&book.bids, true, limit),
+            asks: book.levels(&book.asks, false, limit),
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+impl Default for OrderBookSimulator {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEPTH_LEVELS)
+    }
+}
@@ -0,0 +1,555 @@
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::SendError, error::TrySendError};
+use tokio::task::{self, JoinHandle};
+
+use crate::api::WsManager;
+use crate::models::{KLine, Symbol, Transaction};
+use crate::services::fanout::{FanoutEvent, FanoutPublisher};
+use crate::services::kline::token_shard;
+use crate::services::clickhouse::ClickHouseWriter;
+use crate::services::influx::InfluxWriter;
+use crate::services::kline_store::KLineStoreWriter;
+use crate::services::webhooks::WebhookEvent;
+use crate::services::{
+    AlertService, AmendError, IndexPriceAggregator, IngestError, KLineAmendment, KLineQueryCache, KLineService, Metrics,
+    OrderBookSimulator, SymbolTable, TokenRegistry, TradeDeduplicator, TradeService, TransactionLog, WalEntry,
+    WebhookDispatcher,
+};
+
+/// Transactions applied to the candles at once during backfill
+pub const BACKFILL_BATCH: usize = 4096;
+
+/// Services a transaction passes through once it is ingested
+///
+/// Every ingestion source feeds the same pipeline, so generated, replayed and
+/// replicated trades update candles and reach subscribers the same way.
+#[derive(Clone)]
+pub struct TransactionPipeline {
+    kline_service: Arc<KLineService>,
+    ws_manager: Arc<WsManager>,
+    metrics: Arc<Metrics>,
+    /// Recent trades for the trade tape, when kept
+    trade_service: Option<Arc<TradeService>>,
+    /// Order books moved by each trade, when simulated
+    order_books: Option<Arc<OrderBookSimulator>>,
+    alert_service: Option<Arc<AlertService>>,
+    webhook_dispatcher: Option<Arc<WebhookDispatcher>>,
+    /// Unknown-token policy; every token is accepted without one
+    token_registry: Option<Arc<TokenRegistry>>,
+    /// Per-token rounding; transactions are taken as they are without one
+    symbols: Option<Arc<SymbolTable>>,
+    /// Cached REST pages dropped as their candles update
+    query_cache: Option<Arc<KLineQueryCache>>,
+    /// Recent trade IDs; transactions are never dropped as duplicates without one
+    deduplicator: Option<Arc<TradeDeduplicator>>,
+    /// Composite price of each token's sources, when kept
+    index: Option<Arc<IndexPriceAggregator>>,
+    /// Queue of broadcasts shared with other instances, when fanned out
+    fanout: Option<FanoutPublisher>,
+    /// Queue of candle updates written to shared storage, when this instance leads it
+    store: Option<KLineStoreWriter>,
+    /// Durable log transactions are appended to before they are applied
+    wal: Option<Arc<TransactionLog>>,
+    /// Queue of trades and closed candles inserted into ClickHouse, when kept
+    clickhouse: Option<ClickHouseWriter>,
+    /// Queue of closed candles exported in InfluxDB line protocol, when exported
+    influx: Option<InfluxWriter>,
+}
+
+impl TransactionPipeline {
+    /// Create a pipeline that updates candles and broadcasts to WebSocket clients
+    pub fn new(kline_service: Arc<KLineService>, ws_manager: Arc<WsManager>) -> Self {
+        Self {
+            kline_service,
+            ws_manager,
+            metrics: Arc::new(Metrics::new()),
+            trade_service: None,
+            order_books: None,
+            alert_service: None,
+            webhook_dispatcher: None,
+            token_registry: None,
+            symbols: None,
+            query_cache: None,
+            deduplicator: None,
+            index: None,
+            fanout: None,
+            store: None,
+            wal: None,
+            clickhouse: None,
+            influx: None,
+        }
+    }
+
+    /// Count ingested transactions and broadcasts in shared metrics
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Record transactions in the trade history
+    pub fn with_trade_service(mut self, trade_service: Arc<TradeService>) -> Self {
+        self.trade_service = Some(trade_service);
+        self
+    }
+
+    /// Move simulated order books with each trade
+    pub fn with_order_books(mut self, order_books: Option<Arc<OrderBookSimulator>>) -> Self {
+        self.order_books = order_books;
+        self
+    }
+
+    /// Evaluate price alerts against each trade
+    pub fn with_alerts(mut self, alert_service: Arc<AlertService>) -> Self {
+        self.alert_service = Some(alert_service);
+        self
+    }
+
+    /// Queue closed candles and alert triggers for webhook targets
+    pub fn with_webhooks(mut self, webhook_dispatcher: Arc<WebhookDispatcher>) -> Self {
+        self.webhook_dispatcher = Some(webhook_dispatcher);
+        self
+    }
+
+    /// Apply the unknown-token policy before a new series can start
+    pub fn with_token_registry(mut self, token_registry: Arc<TokenRegistry>) -> Self {
+        self.token_registry = Some(token_registry);
+        self
+    }
+
+    /// Round transactions to each token's tick size and precisions
+    pub fn with_symbols(mut self, symbols: Arc<SymbolTable>) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// Drop cached K-line pages covering the candles each trade updates
+    pub fn with_query_cache(mut self, query_cache: Option<Arc<KLineQueryCache>>) -> Self {
+        self.query_cache = query_cache;
+        self
+    }
+
+    /// Drop transactions repeating a recent trade ID
+    pub fn with_deduplicator(mut self, deduplicator: Option<Arc<TradeDeduplicator>>) -> Self {
+        self.deduplicator = deduplicator;
+        self
+    }
+
+    /// Build composite `<token>@index` candles from the prices of each token's sources
+    pub fn with_index(mut self, index: Option<Arc<IndexPriceAggregator>>) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Share broadcast trades and candles with other instances
+    pub fn with_fanout(mut self, fanout: Option<FanoutPublisher>) -> Self {
+        self.fanout = fanout;
+        self
+    }
+
+    /// Write every candle update to shared storage for follower instances
+    pub fn with_store(mut self, store: Option<KLineStoreWriter>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Log every admitted transaction before applying it, so a restart can replay it
+    pub fn with_wal(mut self, wal: Option<Arc<TransactionLog>>) -> Self {
+        self.wal = wal;
+        self
+    }
+
+    /// Insert every applied trade and closed candle into ClickHouse
+    pub fn with_clickhouse(mut self, clickhouse: Option<ClickHouseWriter>) -> Self {
+        self.clickhouse = clickhouse;
+        self
+    }
+
+    /// Export every closed candle in InfluxDB line protocol
+    pub fn with_influx(mut self, influx: Option<InfluxWriter>) -> Self {
+        self.influx = influx;
+        self
+    }
+
+    /// Append transactions to the write-ahead log, if kept
+    ///
+    /// A failed write is logged and the transactions are still applied:
+    /// they would only be missing from a replay.
+    fn log(&self, transactions: &[Transaction]) {
+        if let Some(Err(e)) = self.wal.as_ref().map(|wal| wal.append(transactions)) {
+            tracing::error!("Failed to append {} transactions to the log: {}", transactions.len(), e);
+        }
+    }
+
+    /// Whether handling a transaction can wait on the write-ahead log
+    ///
+    /// Logged transactions wait for checkpoints and amendments holding the
+    /// apply barrier, and for the disk when every append is synced.
+    fn waits_on_wal(&self) -> bool {
+        self.wal.is_some()
+    }
+
+    /// Whether the transaction repeats a recent trade ID, counting it if so
+    ///
+    /// The trade ID is recorded otherwise, so only call this for transactions
+    /// that were admitted and passed validation.
+    fn is_duplicate(&self, transaction: &Transaction) -> bool {
+        let duplicate = self.deduplicator.as_ref().is_some_and(|deduplicator| !deduplicator.admit(transaction));
+        if let (true, Some(trade_id)) = (duplicate, &transaction.trade_id) {
+            tracing::debug!("Dropped duplicate trade {} of {}", trade_id, transaction.token);
+            self.metrics.record_duplicate();
+        }
+        duplicate
+    }
+
+    /// Name the transaction's pair canonically, then round it to the token's rules
+    fn normalize(&self, transaction: &Transaction) -> Transaction {
+        let mut transaction = transaction.clone();
+        transaction.token = Symbol::canonicalize(&transaction.token);
+        match &self.symbols {
+            Some(symbols) => symbols.normalize(&transaction),
+            None => transaction,
+        }
+    }
+
+    /// Apply a transaction to the K-line service and broadcast the updates
+    ///
+    /// Transactions dropped by the unknown-token policy or as duplicates are
+    /// not errors; a strict K-line service refusing the token is.
+    pub fn handle(&self, transaction: &Transaction) -> Result<(), IngestError> {
+        let _span = tracing::info_span!("transaction", token = %transaction.token).entered();
+
+        // Name the pair canonically and round to the token's tick size and
+        // precisions before anything sees the trade
+        let transaction = &self.normalize(transaction);
+
+        // Apply the unknown-token policy before a new series can start
+        if let Some(token_registry) = &self.token_registry {
+            let admission = token_registry.admit_transaction(transaction);
+            if let Some(event) = &admission.event {
+                tracing::warn!("New token {} seen in transaction feed: {:?}", event.token, event.action);
+                self.ws_manager.broadcast_new_token(event);
+            }
+            if !admission.accepted {
+                return Ok(());
+            }
+            // Trades held back until the token was registered come first
+            self.release_quarantined(&transaction.token);
+        }
+
+        self.apply(transaction)
+    }
+
+    /// Apply the transactions quarantined for a token now that it is registered
+    ///
+    /// Returns the number of transactions applied.
+    pub fn release_quarantined(&self, token: &str) -> usize {
+        let held = self
+            .token_registry
+            .as_ref()
+            .map(|token_registry| token_registry.release(token))
+            .unwrap_or_default();
+        if !held.is_empty() {
+            tracing::info!("Applying {} quarantined transactions of {}", held.len(), token);
+        }
+        held.iter()
+            .filter(|transaction| match self.apply(transaction) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Rejected quarantined transaction: {}", e);
+                    false
+                }
+            })
+            .count()
+    }
+
+    /// Log and apply an admitted transaction and pass its updates on
+    fn apply(&self, transaction: &Transaction) -> Result<(), IngestError> {
+        // Replayed or retried trades must not count twice, while a rejected
+        // trade leaves its ID free for a corrected retry
+        self.kline_service.check_transaction(transaction)?;
+        if self.is_duplicate(transaction) {
+            return Ok(());
+        }
+
+        // A snapshot taken meanwhile must see the transaction either logged
+        // and applied or neither
+        let applying = self.wal.as_ref().map(|wal| wal.begin_apply());
+        self.log(std::slice::from_ref(transaction));
+
+        // Process transaction and update K-lines
+        let closed = self.kline_service.try_process_transaction(transaction);
+        drop(applying);
+        let closed = closed?;
+        if let Some(trade_service) = &self.trade_service {
+            trade_service.record(transaction);
+        }
+        self.metrics.record_ingest();
+        if let Some(clickhouse) = &self.clickhouse {
+            clickhouse.write_trade(transaction);
+        }
+
+        // Broadcast transaction to WebSocket clients
+        self.ws_manager.broadcast_transaction(transaction);
+        self.metrics.record_transaction_broadcast();
+        if let Some(fanout) = &self.fanout {
+            fanout.publish(FanoutEvent::Transaction(transaction.clone()));
+        }
+        self.ws_manager.broadcast_bars(&self.kline_service, transaction);
+        self.ws_manager.broadcast_agg_trade(transaction);
+        if let Some(update) = self.order_books.as_ref().and_then(|books| books.apply_trade(transaction)) {
+            self.ws_manager.broadcast_depth(&update);
+        }
+
+        self.publish_klines(&transaction.token, closed);
+
+        // Move the composite price of the token's sources
+        if let Some(index) = self.index.as_ref().and_then(|index| index.observe(transaction)) {
+            match self.kline_service.try_process_transaction(&index) {
+                Ok(closed) => self.publish_klines(&index.token, closed),
+                Err(e) => tracing::debug!("Index of {} not updated: {}", transaction.token, e),
+            }
+        }
+
+        // Notify alert subscribers; per-alert webhooks are queued by the alert service
+        let triggers = self.alert_service.as_ref().map(|alerts| alerts.evaluate(transaction)).unwrap_or_default();
+        for trigger in triggers {
+            tracing::info!("Alert {} triggered for {} at {}", trigger.alert_id, trigger.token, trigger.price);
+            self.ws_manager.broadcast_alert(&trigger);
+            if let Some(webhook_dispatcher) = &self.webhook_dispatcher {
+                webhook_dispatcher.dispatch(&WebhookEvent::Alert(trigger));
+            }
+        }
+        Ok(())
+    }
+
+    /// Correct a stored candle and pass the correction on like any candle update
+    ///
+    /// The amendment is logged so a restart replays it, written to shared
+    /// storage and, once the candle is closed, to the analytics sinks, and
+    /// broadcast as `kline_amended`. Transactions being applied meanwhile are
+    /// waited for, so the amendment takes the same place in the log as in the candles.
+    pub fn amend(&self, amendment: &KLineAmendment) -> Result<KLine, AmendError> {
+        let mut amendment = amendment.clone();
+        amendment.token = Symbol::canonicalize(&amendment.token);
+
+        let paused = self.wal.as_ref().map(|wal| wal.pause_apply());
+        let kline = self.kline_service.amend_kline(&amendment)?;
+        if let Some(Err(e)) = self.wal.as_ref().map(|wal| wal.append_amendment(&amendment)) {
+            tracing::error!("Failed to append the amendment of {} to the log: {}", amendment.token, e);
+        }
+        drop(paused);
+
+        if let Some(query_cache) = &self.query_cache {
+            query_cache.invalidate(&kline);
+        }
+        if let Some(store) = &self.store {
+            store.write(kline.clone());
+        }
+        if kline.is_closed {
+            if let Some(clickhouse) = &self.clickhouse {
+                clickhouse.write_kline(&kline);
+            }
+            if let Some(influx) = &self.influx {
+                influx.write_kline(&kline);
+            }
+        }
+        self.ws_manager.broadcast_kline_amended(&kline);
+        Ok(kline)
+    }
+
+    /// Broadcast a token's open candles after an update, and the candles it closed
+    fn publish_klines(&self, token: &str, closed: Vec<KLine>) {
+        for interval in self.kline_service.intervals() {
+            if let Some(kline) = self.kline_service.get_current_kline(token, interval) {
+                if let Some(query_cache) = &self.query_cache {
+                    query_cache.invalidate(&kline);
+                }
+                self.ws_manager.broadcast_kline(&kline);
+                self.ws_manager.broadcast_indicators(&self.kline_service, &kline);
+                self.metrics.record_kline_broadcast();
+                if let Some(store) = &self.store {
+                    store.write(kline.clone());
+                }
+                if let Some(fanout) = &self.fanout {
+                    fanout.publish(FanoutEvent::Kline(kline));
+                }
+            }
+        }
+        self.ws_manager.broadcast_ticker(&self.kline_service, token);
+
+        // Push indicators of closed candles and queue them for webhook targets
+        for kline in closed {
+            if let Some(query_cache) = &self.query_cache {
+                query_cache.invalidate(&kline);
+            }
+            if let Some(store) = &self.store {
+                store.write(kline.clone());
+            }
+            if let Some(clickhouse) = &self.clickhouse {
+                clickhouse.write_kline(&kline);
+            }
+            if let Some(influx) = &self.influx {
+                influx.write_kline(&kline);
+            }
+            self.ws_manager.broadcast_indicators(&self.kline_service, &kline);
+            if let Some(webhook_dispatcher) = &self.webhook_dispatcher {
+                webhook_dispatcher.dispatch(&WebhookEvent::KlineClosed(kline));
+            }
+        }
+    }
+
+    /// Apply generated history to the candles and trade history
+    ///
+    /// Nothing is broadcast, and alerts and webhooks only see live trades.
+    /// Transactions are applied in batches of [`BACKFILL_BATCH`]. Returns the
+    /// number of transactions applied.
+    pub fn backfill(&self, transactions: impl Iterator<Item = Transaction>) -> usize {
+        self.apply_history(transactions, true)
+    }
+
+    /// Apply entries read back from the write-ahead log, without logging them again
+    ///
+    /// Transactions are applied like a backfill, and each amendment once the
+    /// transactions logged before it are. Returns the number of entries applied.
+    pub fn replay_wal(&self, entries: impl Iterator<Item = WalEntry>) -> usize {
+        let mut applied = 0;
+        let mut transactions = Vec::new();
+        for entry in entries {
+            match entry {
+                WalEntry::Transaction(transaction) => transactions.push(transaction),
+                WalEntry::Amendment(amendment) => {
+                    applied += self.apply_history(transactions.drain(..), false);
+                    match self.kline_service.amend_kline(&amendment) {
+                        Ok(_) => applied += 1,
+                        Err(e) => tracing::warn!("Skipped logged amendment of {}: {}", amendment.token, e),
+                    }
+                }
+            }
+        }
+        applied + self.apply_history(transactions.into_iter(), false)
+    }
+
+    fn apply_history(&self, transactions: impl Iterator<Item = Transaction>, log: bool) -> usize {
+        let mut applied = 0;
+        let mut batch = Vec::with_capacity(BACKFILL_BATCH);
+        let mut transactions = transactions.peekable();
+        while transactions.peek().is_some() {
+            batch.clear();
+            batch.extend(
+                transactions
+                    .by_ref()
+                    .take(BACKFILL_BATCH)
+                    .map(|transaction| self.normalize(&transaction))
+                    .filter(|transaction| {
+                        self.token_registry
+                            .as_ref()
+                            .is_none_or(|token_registry| token_registry.admit(&transaction.token).accepted)
+                    })
+                    .filter(|transaction| {
+                        self.kline_service.check_transaction(transaction).is_ok() && !self.is_duplicate(transaction)
+                    }),
+            );
+
+            let applying = self.wal.as_ref().filter(|_| log).map(|wal| wal.begin_apply());
+            if log {
+                self.log(&batch);
+            }
+            let outcome = self.kline_service.process_transactions(&batch);
+            drop(applying);
+            if let Some(trade_service) = &self.trade_service {
+                let mut rejected = outcome.rejected.iter().map(|(index, _)| *index).peekable();
+                for (index, transaction) in batch.iter().enumerate() {
+                    if rejected.next_if_eq(&index).is_none() {
+                        trade_service.record(transaction);
+                    }
+                }
+            }
+            applied += outcome.applied;
+
+            if let Some(index) = &self.index {
+                let index_batch: Vec<Transaction> = batch.iter().filter_map(|transaction| index.observe(transaction)).collect();
+                self.kline_service.process_transactions(&index_batch);
+            }
+        }
+        applied
+    }
+
+    /// Start `workers` tasks handling transactions sent to the returned sender
+    ///
+    /// Each token is handled by the worker of its [`token_shard`], so its
+    /// transactions keep their order, and with a K-line service of as many
+    /// shards every worker writes to its own shard only. Every worker queues
+    /// at most `capacity` transactions; sending to a full queue waits, slowing
+    /// the source down. Workers stop once every sender is dropped and their
+    /// queues are drained.
+    pub fn spawn_workers(self, workers: usize, capacity: usize) -> (IngestSender, Vec<JoinHandle<()>>) {
+        let (queues, handles) = (0..workers.max(1))
+            .map(|_| {
+                let (sender, mut receiver) = mpsc::channel::<Transaction>(capacity.max(1));
+                let pipeline = self.clone();
+                let handle = task::spawn(async move {
+                    while let Some(transaction) = receiver.recv().await {
+                        let result = if pipeline.waits_on_wal() {
+                            // Waiting for the log must not hold up the async workers
+                            let (blocking, transaction) = (pipeline.clone(), transaction.clone());
+                            match task::spawn_blocking(move || blocking.handle(&transaction)).await {
+                                Ok(result) => result,
+                                Err(e) => {
+                                    tracing::error!("Transaction handler failed: {}", e);
+                                    continue;
+                                }
+                            }
+                        } else {
+                            pipeline.handle(&transaction)
+                        };
+                        match result {
+                            Ok(()) => tracing::debug!(
+                                "Processed transaction: {} {} @ {}",
+                                transaction.token,
+                                transaction.volume,
+                                transaction.price
+                            ),
+                            Err(e) => tracing::warn!("Rejected transaction: {}", e),
+                        }
+                    }
+                });
+                (sender, handle)
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        (IngestSender { queues: queues.into() }, handles)
+    }
+}
+
+/// Sending side of the pipeline's worker queues, shared by ingestion sources
+#[derive(Debug, Clone)]
+pub struct IngestSender {
+    queues: Arc<[mpsc::Sender<Transaction>]>,
+}
+
+impl IngestSender {
+    /// Name the transaction's pair canonically and pick the worker of that name
+    ///
+    /// Every spelling of a pair must reach the same worker, or its
+    /// transactions could be applied out of order by two workers.
+    fn queue(&self, transaction: &mut Transaction) -> &mpsc::Sender<Transaction> {
+        transaction.token = Symbol::canonicalize(&transaction.token);
+        &self.queues[token_shard(&transaction.token, self.queues.len())]
+    }
+
+    /// Queue a transaction, waiting while its worker's queue is full
+    ///
+    /// Fails once the workers have stopped.
+    pub async fn send(&self, mut transaction: Transaction) -> Result<(), SendError<Transaction>> {
+        self.queue(&mut transaction).send(transaction).await
+    }
+
+    /// Queue a transaction without waiting, failing if its worker's queue is full
+    pub fn try_send(&self, mut transaction: Transaction) -> Result<(), TrySendError<Transaction>> {
+        self.queue(&mut transaction).try_send(transaction)
+    }
+
+    /// Transactions queued and not yet taken by a worker
+    pub fn queued(&self) -> usize {
+        self.queues.iter().map(|queue| queue.max_capacity() - queue.capacity()).sum()
+    }
+}
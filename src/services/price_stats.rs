@@ -0,0 +1,124 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use std::collections::VecDeque;
+
+use crate::models::ticker::PriceExtremes;
+use crate::models::Transaction;
+
+/// Slots each rolling window is divided into
+///
+/// A window's range covers whole slots, so it may include trades up to one
+/// slot older than the window.
+const SLOTS: i32 = 60;
+
+/// High and low of the trades within a trailing time window
+#[derive(Debug, Clone)]
+struct RollingRange {
+    window: TimeDelta,
+    slot: TimeDelta,
+    /// Start, high and low of each slot with trades, oldest first
+    slots: VecDeque<(DateTime<Utc>, f64, f64)>,
+}
+
+impl RollingRange {
+    fn new(window: TimeDelta) -> Self {
+        Self {
+            window,
+            slot: window / SLOTS,
+            slots: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, timestamp: DateTime<Utc>, price: f64) {
+        let start = timestamp.duration_trunc(self.slot).unwrap_or(timestamp);
+        let position = self.slots.partition_point(|(slot_start, _, _)| *slot_start < start);
+        match self.slots.get_mut(position) {
+            Some((slot_start, high, low)) if *slot_start == start => {
+                *high = high.max(price);
+                *low = low.min(price);
+            }
+            _ => self.slots.insert(position, (start, price, price)),
+        }
+
+        // Slots ending before the newest one's window are never read again
+        let newest = self.slots.back().map_or(start, |(slot_start, _, _)| *slot_start);
+        while self
+            .slots
+            .front()
+            .is_some_and(|(slot_start, _, _)| *slot_start + self.slot <= newest - self.window)
+        {
+            self.slots.pop_front();
+        }
+    }
+
+    /// High and low of the slots overlapping the window ending at `now`
+    fn range(&self, now: DateTime<Utc>) -> Option<(f64, f64)> {
+        self.slots
+            .iter()
+            .filter(|(slot_start, _, _)| *slot_start + self.slot > now - self.window && *slot_start <= now)
+            .fold(None, |range, &(_, high, low)| match range {
+                Some((range_high, range_low)) => Some((high.max(range_high), low.min(range_low))),
+                None => Some((high, low)),
+            })
+    }
+}
+
+/// All-time and rolling price extremes of one token, updated on every trade
+///
+/// Rolling windows keep one high and low per slot rather than every trade, so
+/// each token costs a bounded amount of memory however actively it trades.
+#[derive(Debug, Clone)]
+pub struct PriceStats {
+    all_time_high: (f64, DateTime<Utc>),
+    all_time_low: (f64, DateTime<Utc>),
+    hour: RollingRange,
+    day: RollingRange,
+    week: RollingRange,
+}
+
+impl PriceStats {
+    /// Start tracking a token from its first trade
+    pub fn new(transaction: &Transaction) -> Self {
+        let mut stats = Self {
+            all_time_high: (transaction.price, transaction.timestamp),
+            all_time_low: (transaction.price, transaction.timestamp),
+            hour: RollingRange::new(TimeDelta::hours(1)),
+            day: RollingRange::new(TimeDelta::hours(24)),
+            week: RollingRange::new(TimeDelta::days(7)),
+        };
+        stats.record(transaction);
+        stats
+    }
+
+    /// Include a trade, which may be older than trades already recorded
+    pub fn record(&mut self, transaction: &Transaction) {
+        let (price, timestamp) = (transaction.price, transaction.timestamp);
+        if price > self.all_time_high.0 {
+            self.all_time_high = (price, timestamp);
+        }
+        if price < self.all_time_low.0 {
+            self.all_time_low = (price, timestamp);
+        }
+        for range in [&mut self.hour, &mut self.day, &mut self.week] {
+            range.record(timestamp, price);
+        }
+    }
+
+    /// Extremes as of `now`, without rolling highs and lows for windows without trades
+    pub fn extremes(&self, now: DateTime<Utc>) -> PriceExtremes {
+        let (high_1h, low_1h) = self.hour.range(now).unzip();
+        let (high_24h, low_24h) = self.day.range(now).unzip();
+        let (high_7d, low_7d) = self.week.range(now).unzip();
+        PriceExtremes {
+            all_time_high: self.all_time_high.0,
+            all_time_high_at: self.all_time_high.1,
+            all_time_low: self.all_time_low.0,
+            all_time_low_at: self.all_time_low.1,
+            high_1h,
+            low_1h,
+            high_24h,
+            low_24h,
+            high_7d,
+            low_7d,
+        }
+    }
+}
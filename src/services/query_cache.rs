@@ -0,0 +1,165 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::models::{KLine, TimeInterval};
+use crate::services::{KLineCursor, KLinePage, KLineService};
+
+/// Default number of pages kept by a [`KLineQueryCache`]
+pub const DEFAULT_QUERY_CACHE_CAPACITY: usize = 1024;
+
+/// Query of one page of a token's candles, as cached per token
+type PageQuery = (TimeInterval, KLineCursor, usize);
+
+/// A cached page with the time it was read from the K-line service
+#[derive(Debug)]
+struct CachedPage {
+    page: KLinePage,
+    cached_at: Instant,
+}
+
+/// Hit and miss counts of a [`KLineQueryCache`]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct QueryCacheStats {
+    /// Pages currently cached
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Cached pages dropped because a candle they cover was updated
+    pub invalidations: u64,
+    /// Share of lookups answered from the cache, 0 before the first lookup
+    pub hit_rate: f64,
+}
+
+/// Pages of K-line history reused across requests for a short time
+///
+/// Polling clients repeat the same queries many times a second. Pages are
+/// keyed by token, interval, cursor and limit, and dropped once a candle in
+/// their range is updated or after the TTL, which bounds staleness from
+/// writes that bypass the transaction pipeline such as restores and
+/// retention cleanup.
+#[derive(Debug)]
+pub struct KLineQueryCache {
+    ttl: Duration,
+    capacity: usize,
+    /// Cached pages per token, so an update only scans its token's pages
+    pages: DashMap<String, HashMap<PageQuery, CachedPage>>,
+    len: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl KLineQueryCache {
+    /// Create a cache keeping up to `capacity` pages for at most `ttl`
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            pages: DashMap::new(),
+            len: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a page of candles as [`KLineService::get_klines_page`] would
+    pub fn get_klines_page(
+        &self,
+        kline_service: &KLineService,
+        token: &str,
+        interval: TimeInterval,
+        cursor: KLineCursor,
+        limit: usize,
+    ) -> KLinePage {
+        let query = (interval, cursor, limit);
+        if let Some(cached) = self.pages.get(token).and_then(|pages| {
+            pages
+                .get(&query)
+                .filter(|cached| cached.cached_at.elapsed() < self.ttl)
+                .map(|cached| cached.page.clone())
+        }) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return cached;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let page = kline_service.get_klines_page(token, interval, cursor, limit);
+        if self.len.load(Ordering::Relaxed) >= self.capacity {
+            self.evict();
+        }
+        let cached = CachedPage {
+            page: page.clone(),
+            cached_at: Instant::now(),
+        };
+        // Counted under the token's lock, so a concurrent invalidation cannot subtract it first
+        let mut pages = self.pages.entry(token.to_string()).or_default();
+        if pages.insert(query, cached).is_none() {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        page
+    }
+
+    /// Drop the cached pages whose range covers an updated candle
+    pub fn invalidate(&self, kline: &KLine) {
+        let Some(mut pages) = self.pages.get_mut(&kline.token) else {
+            return;
+        };
+        let before = pages.len();
+        pages.retain(|(interval, cursor, _), _| {
+            let covered = match cursor {
+                KLineCursor::Before(before) => kline.timestamp < *before,
+                KLineCursor::After(after) => kline.timestamp > *after,
+            };
+            *interval != kline.interval || !covered
+        });
+        let removed = before - pages.len();
+        if removed > 0 {
+            self.len.fetch_sub(removed, Ordering::Relaxed);
+            self.invalidations.fetch_add(removed as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Make room by dropping expired pages, or the pages of some token if none expired
+    fn evict(&self) {
+        let mut removed = 0;
+        for mut pages in self.pages.iter_mut() {
+            let before = pages.len();
+            pages.retain(|_, cached| cached.cached_at.elapsed() < self.ttl);
+            removed += before - pages.len();
+        }
+        if removed == 0 {
+            let token = self.pages.iter().next().map(|pages| pages.key().clone());
+            if let Some((_, pages)) = token.and_then(|token| self.pages.remove(&token)) {
+                removed = pages.len();
+            }
+        }
+        self.len.fetch_sub(removed, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        QueryCacheStats {
+            entries: self.len.load(Ordering::Relaxed),
+            hits,
+            misses,
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+}
+
+impl Default for KLineQueryCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), DEFAULT_QUERY_CACHE_CAPACITY)
+    }
+}
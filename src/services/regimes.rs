@@ -0,0 +1,319 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::config::{MarketRegime, RegimeConfig};
+
+/// Bounds of a token's price level relative to its base price, so long
+/// trends cannot drive prices to zero or overflow
+const LEVEL_RANGE: (f64, f64) = (0.01, 100.0);
+
+/// Largest pump magnitude accepted, as a fraction of the price
+pub const MAX_PUMP_MAGNITUDE: f64 = 10.0;
+
+/// Shape of a scripted price move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum MarketEventKind {
+    /// Prices rise by the magnitude
+    Pump,
+    /// Prices fall by the magnitude
+    FlashCrash,
+}
+
+/// Request to script a one-off price move
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct MarketEvent {
+    /// Token to move; every token when omitted
+    #[serde(default)]
+    pub token: Option<String>,
+    pub kind: MarketEventKind,
+    /// Size of the move as a fraction of the price, such as 0.3 for 30%
+    pub magnitude: f64,
+    /// Seconds over which prices move
+    pub duration_secs: u64,
+    /// Whether prices move back over another `duration_secs` afterwards
+    #[serde(default)]
+    pub revert: bool,
+    /// Seconds until the move starts
+    #[serde(default)]
+    pub start_in_secs: u64,
+}
+
+impl MarketEvent {
+    /// Check the magnitude and duration
+    pub fn validate(&self) -> Result<(), String> {
+        let max_magnitude = match self.kind {
+            MarketEventKind::Pump => MAX_PUMP_MAGNITUDE,
+            MarketEventKind::FlashCrash => 1.0,
+        };
+        if !self.magnitude.is_finite() || self.magnitude <= 0.0 || self.magnitude >= max_magnitude {
+            return Err(format!(
+                "Invalid magnitude: {}. Expected more than 0 and less than {}",
+                self.magnitude, max_magnitude
+            ));
+        }
+        if self.duration_secs == 0 {
+            return Err("Invalid duration_secs: expected more than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Request to enter a market regime
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct RegimeChange {
+    pub regime: MarketRegime,
+}
+
+/// A scripted price move, pending or in progress
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ScheduledEvent {
+    /// Token moved; every token when absent
+    pub token: Option<String>,
+    pub kind: MarketEventKind,
+    pub magnitude: f64,
+    pub revert: bool,
+    pub starts_at: DateTime<Utc>,
+    /// When prices reach the full move
+    pub peaks_at: DateTime<Utc>,
+    /// When the event is over, after reverting if it does
+    pub ends_at: DateTime<Utc>,
+}
+
+impl ScheduledEvent {
+    fn applies_to(&self, token: &str) -> bool {
+        self.token.as_deref().is_none_or(|event_token| event_token == token)
+    }
+
+    /// Price factor of the full move
+    fn peak_factor(&self) -> f64 {
+        match self.kind {
+            MarketEventKind::Pump => 1.0 + self.magnitude,
+            MarketEventKind::FlashCrash => 1.0 - self.magnitude,
+        }
+    }
+
+    /// Price factor at `now`, ramping linearly to the full move and back
+    fn factor(&self, now: DateTime<Utc>) -> f64 {
+        let progress = if now < self.starts_at {
+            0.0
+        } else if now < self.peaks_at {
+            fraction(now - self.starts_at, self.peaks_at - self.starts_at)
+        } else if self.revert && now < self.ends_at {
+            1.0 - fraction(now - self.peaks_at, self.ends_at - self.peaks_at)
+        } else if self.revert {
+            0.0
+        } else {
+            1.0
+        };
+        1.0 + (self.peak_factor() - 1.0) * progress
+    }
+}
+
+fn fraction(elapsed: TimeDelta, total: TimeDelta) -> f64 {
+    elapsed.num_milliseconds() as f64 / total.num_milliseconds().max(1) as f64
+}
+
+/// Current regime and scripted events
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct RegimeStatus {
+    /// Whether prices follow the regimes
+    pub enabled: bool,
+    pub regime: MarketRegime,
+    /// When the current regime was entered
+    pub since: DateTime<Utc>,
+    /// Scripted events not yet over, in start order
+    pub events: Vec<ScheduledEvent>,
+}
+
+/// Center and spread of the next generated trade of a token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradeShape {
+    /// Price trades are generated around
+    pub price: f64,
+    /// Factor applied to the generator's volatility
+    pub volatility_multiplier: f64,
+    /// Factor applied to generated volumes
+    pub volume_multiplier: f64,
+}
+
+#[derive(Debug)]
+struct RegimeState {
+    config: RegimeConfig,
+    regime: MarketRegime,
+    since: DateTime<Utc>,
+    next_step: DateTime<Utc>,
+    /// Price level of each token relative to its base price, moved by drift and finished events
+    levels: HashMap<String, f64>,
+    events: Vec<ScheduledEvent>,
+}
+
+impl RegimeState {
+    /// Take the Markov steps due by `now` and fold finished events into the levels
+    fn advance(&mut self, now: DateTime<Utc>) {
+        if self.config.enabled {
+            let step = TimeDelta::seconds(self.config.step_secs as i64);
+            while self.next_step <= now {
+                let next = self.draw_transition();
+                if next != self.regime {
+                    tracing::info!("Market regime changed from {:?} to {:?}", self.regime, next);
+                    self.regime = next;
+                    self.since = self.next_step;
+                }
+                self.next_step += step;
+            }
+        }
+
+        let (finished, pending): (Vec<ScheduledEvent>, Vec<ScheduledEvent>) =
+            self.events.drain(..).partition(|event| event.ends_at <= now);
+        self.events = pending;
+        for event in finished.iter().filter(|event| !event.revert) {
+            for (token, level) in self.levels.iter_mut() {
+                if event.applies_to(token) {
+                    *level = (*level * event.peak_factor()).clamp(LEVEL_RANGE.0, LEVEL_RANGE.1);
+                }
+            }
+        }
+    }
+
+    /// Draw the regime of the next step from the current regime's transition weights
+    fn draw_transition(&self) -> MarketRegime {
+        let transitions = &self.config.params(self.regime).transitions;
+        let total: f64 = transitions.values().sum();
+        if total <= 0.0 {
+            return self.regime;
+        }
+        let mut remaining = rand::thread_rng().gen_range(0.0..total);
+        for (regime, weight) in transitions {
+            if remaining < *weight {
+                return *regime;
+            }
+            remaining -= weight;
+        }
+        self.regime
+    }
+}
+
+/// Markov chain of market regimes and scripted price moves, shared by the
+/// mock generator and the admin API
+///
+/// Each generated trade moves its token's price level by the regime's drift;
+/// trades are generated around that level with the regime's volatility and
+/// volume multipliers, times the factor of any scripted event in progress.
+#[derive(Debug)]
+pub struct RegimeEngine {
+    state: Mutex<RegimeState>,
+}
+
+impl RegimeEngine {
+    /// Create an engine in the configured initial regime
+    pub fn new(config: RegimeConfig) -> Self {
+        let now = Utc::now();
+        Self {
+            state: Mutex::new(RegimeState {
+                regime: config.initial,
+                since: now,
+                next_step: now + TimeDelta::seconds(config.step_secs as i64),
+                config,
+                levels: HashMap::new(),
+                events: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RegimeState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the regime parameters, keeping the current regime
+    pub fn set_config(&self, config: RegimeConfig) {
+        let mut state = self.lock();
+        if config.enabled && !state.config.enabled || config.step_secs != state.config.step_secs {
+            state.next_step = Utc::now() + TimeDelta::seconds(config.step_secs as i64);
+        }
+        state.config = config;
+    }
+
+    /// Enter a regime now, restarting the step timer
+    pub fn force(&self, regime: MarketRegime, now: DateTime<Utc>) {
+        let mut state = self.lock();
+        state.regime = regime;
+        state.since = now;
+        state.next_step = now + TimeDelta::seconds(state.config.step_secs as i64);
+    }
+
+    /// Script a price move, returning it with its times
+    ///
+    /// The event should be validated first.
+    pub fn schedule(&self, event: MarketEvent, now: DateTime<Utc>) -> ScheduledEvent {
+        let starts_at = now + TimeDelta::seconds(event.start_in_secs as i64);
+        let duration = TimeDelta::seconds(event.duration_secs as i64);
+        let peaks_at = starts_at + duration;
+        let scheduled = ScheduledEvent {
+            token: event.token,
+            kind: event.kind,
+            magnitude: event.magnitude,
+            revert: event.revert,
+            starts_at,
+            peaks_at,
+            ends_at: if event.revert { peaks_at + duration } else { peaks_at },
+        };
+        let mut state = self.lock();
+        state.events.push(scheduled.clone());
+        state.events.sort_by_key(|event| event.starts_at);
+        scheduled
+    }
+
+    /// Get the current regime and scripted events at `now`
+    pub fn status(&self, now: DateTime<Utc>) -> RegimeStatus {
+        let mut state = self.lock();
+        state.advance(now);
+        RegimeStatus {
+            enabled: state.config.enabled,
+            regime: state.regime,
+            since: state.since,
+            events: state.events.clone(),
+        }
+    }
+
+    /// Shape the next trade of a token at `now`, applying one trade's drift
+    pub fn next_trade(&self, token: &str, base_price: f64, now: DateTime<Utc>) -> TradeShape {
+        let mut state = self.lock();
+        state.advance(now);
+
+        let (drift, volatility_multiplier, volume_multiplier) = if state.config.enabled {
+            let params = state.config.params(state.regime);
+            (params.drift, params.volatility_multiplier, params.volume_multiplier)
+        } else {
+            (0.0, 1.0, 1.0)
+        };
+        let event_factor: f64 = state
+            .events
+            .iter()
+            .filter(|event| event.applies_to(token))
+            .map(|event| event.factor(now))
+            .product();
+
+        let level = state.levels.entry(token.to_string()).or_insert(1.0);
+        *level = (*level * (1.0 + drift)).clamp(LEVEL_RANGE.0, LEVEL_RANGE.1);
+        TradeShape {
+            price: base_price * *level * event_factor,
+            volatility_multiplier,
+            volume_multiplier,
+        }
+    }
+}
+
+impl Default for RegimeEngine {
+    fn default() -> Self {
+        Self::new(RegimeConfig::default())
+    }
+}
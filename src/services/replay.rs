@@ -0,0 +1,285 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::config::ReplayConfig;
+use crate::models::{Side, TradeId, Transaction};
+
+/// Slowest replay speed, so scaled gaps stay within the range of timestamps
+pub const MIN_REPLAY_SPEED: f64 = 0.001;
+
+/// Columns a CSV replay file must have, in any order
+pub const CSV_COLUMNS: [&str; 4] = ["token", "price", "volume", "timestamp"];
+
+/// Columns holding the side, of which a CSV replay file must have one
+///
+/// `is_buy` is the column of recordings made before `side`.
+pub const CSV_SIDE_COLUMNS: [&str; 2] = ["side", "is_buy"];
+
+/// Optional CSV column holding whether the reporting party was the maker
+pub const CSV_IS_MAKER_COLUMN: &str = "is_maker";
+
+/// Optional CSV column holding each trade's venue ID
+pub const CSV_TRADE_ID_COLUMN: &str = "trade_id";
+
+/// Optional CSV column holding the source that reported each trade
+pub const CSV_SOURCE_COLUMN: &str = "source";
+
+/// Source of replayed transactions recorded without one
+pub const REPLAY_SOURCE: &str = "replay";
+
+/// File format of recorded transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// One JSON transaction per line
+    Jsonl,
+}
+
+impl ReplayFormat {
+    /// Format of a file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(ReplayFormat::Csv),
+            "jsonl" | "ndjson" => Some(ReplayFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// Reason recorded transactions could not be loaded
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The file extension is not one of the supported formats
+    UnsupportedFormat(String),
+    /// A line could not be parsed into a valid transaction
+    InvalidLine { line: usize, message: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "Cannot read replay file: {}", e),
+            ReplayError::UnsupportedFormat(path) => {
+                write!(f, "Unsupported replay file: {}. Expected a .csv, .jsonl or .ndjson file", path)
+            }
+            ReplayError::InvalidLine { line, message } => write!(f, "Invalid transaction on line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// Parse transactions from CSV with a header row naming [`CSV_COLUMNS`] and
+/// one of [`CSV_SIDE_COLUMNS`]
+///
+/// Timestamps are RFC 3339 or milliseconds since the Unix epoch; the side is
+/// `buy`/`sell`, or `true`/`false` for buys and sells. The optional
+/// [`CSV_IS_MAKER_COLUMN`] is `true`/`false`, [`CSV_TRADE_ID_COLUMN`] holds
+/// numeric or opaque trade IDs and [`CSV_SOURCE_COLUMN`] source names; each
+/// is left empty for trades without one.
+/// Other columns are ignored.
+pub fn parse_csv(content: &str) -> Result<Vec<Transaction>, ReplayError> {
+    let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<&str> = header.split(',').map(str::trim).collect();
+    let mut positions = [0; CSV_COLUMNS.len()];
+    for (position, column) in positions.iter_mut().zip(CSV_COLUMNS) {
+        *position = header.iter().position(|name| *name == column).ok_or_else(|| ReplayError::InvalidLine {
+            line: 1,
+            message: format!("missing column {}", column),
+        })?;
+    }
+    let column = |name: &str| header.iter().position(|column| *column == name);
+    let side_position = CSV_SIDE_COLUMNS.into_iter().find_map(column).ok_or_else(|| ReplayError::InvalidLine {
+        line: 1,
+        message: format!("missing column {}", CSV_SIDE_COLUMNS[0]),
+    })?;
+    let optional_positions = [
+        column(CSV_IS_MAKER_COLUMN),
+        column(CSV_TRADE_ID_COLUMN),
+        column(CSV_SOURCE_COLUMN),
+    ];
+
+    lines
+        .map(|(index, line)| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |position: usize| fields.get(position).copied().unwrap_or_default();
+            let [token, price, volume, timestamp] = positions.map(field);
+            let [is_maker, trade_id, source] =
+                optional_positions.map(|position| position.map(field).unwrap_or_default());
+            parse_csv_row(token, price, volume, timestamp, field(side_position), [is_maker, trade_id, source])
+                .map_err(|message| ReplayError::InvalidLine { line: index + 1, message })
+        })
+        .collect()
+}
+
+fn parse_csv_row(
+    token: &str,
+    price: &str,
+    volume: &str,
+    timestamp: &str,
+    side: &str,
+    [is_maker, trade_id, source]: [&str; 3],
+) -> Result<Transaction, String> {
+    if token.is_empty() {
+        return Err("missing token".to_string());
+    }
+    let price = price.parse().map_err(|_| format!("invalid price {:?}", price))?;
+    let volume = volume.parse().map_err(|_| format!("invalid volume {:?}", volume))?;
+    let timestamp = match timestamp.parse::<i64>() {
+        Ok(millis) => DateTime::from_timestamp_millis(millis),
+        Err(_) => DateTime::parse_from_rfc3339(timestamp).ok().map(|t| t.with_timezone(&Utc)),
+    }
+    .ok_or_else(|| format!("invalid timestamp {:?}", timestamp))?;
+    let side = match side.to_lowercase().as_str() {
+        "true" | "buy" => Side::Buy,
+        "false" | "sell" => Side::Sell,
+        _ => return Err(format!("invalid side {:?}", side)),
+    };
+    let is_maker = match is_maker.to_lowercase().as_str() {
+        "" => None,
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => return Err(format!("invalid is_maker {:?}", is_maker)),
+    };
+    let trade_id = match trade_id {
+        "" => None,
+        id => Some(id.parse().map_or_else(|_| TradeId::Text(id.to_string()), TradeId::Number)),
+    };
+
+    let transaction = Transaction {
+        token: token.to_string(),
+        price,
+        volume,
+        timestamp,
+        side,
+        is_maker,
+        trade_id,
+        source: Some(source).filter(|source| !source.is_empty()).map(str::to_string),
+    };
+    transaction.validate().map_err(|e| e.to_string())?;
+    Ok(transaction)
+}
+
+/// Parse transactions from JSON lines in the wire form of [`Transaction`]
+pub fn parse_jsonl(content: &str) -> Result<Vec<Transaction>, ReplayError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| ReplayError::InvalidLine {
+                line: index + 1,
+                message: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Load the transactions of a CSV or JSONL file, in file order
+pub fn load(path: &Path) -> Result<Vec<Transaction>, ReplayError> {
+    let format =
+        ReplayFormat::from_path(path).ok_or_else(|| ReplayError::UnsupportedFormat(path.display().to_string()))?;
+    let content = fs::read_to_string(path)?;
+    match format {
+        ReplayFormat::Csv => parse_csv(&content),
+        ReplayFormat::Jsonl => parse_jsonl(&content),
+    }
+}
+
+/// Plays recorded transactions back with their original spacing
+///
+/// The gaps between recorded timestamps are divided by the speed, and each
+/// transaction is stamped with the time it is played, so candles build up as
+/// they would from a live feed. Transactions recorded out of order are played
+/// without a wait, and those recorded without a source are tagged
+/// [`REPLAY_SOURCE`].
+#[derive(Debug, Clone)]
+pub struct TransactionReplayer {
+    transactions: Vec<Transaction>,
+    speed: f64,
+}
+
+impl TransactionReplayer {
+    /// Create a replayer of transactions at a speed multiplier of at least [`MIN_REPLAY_SPEED`]
+    pub fn new(mut transactions: Vec<Transaction>, speed: f64) -> Self {
+        for transaction in &mut transactions {
+            transaction.source.get_or_insert_with(|| REPLAY_SOURCE.to_string());
+        }
+        Self { transactions, speed }
+    }
+
+    /// Load the configured replay file
+    pub fn from_config(config: &ReplayConfig) -> Result<Self, ReplayError> {
+        let path = config.path.as_deref().unwrap_or_default();
+        Ok(Self::new(load(Path::new(path))?, config.speed))
+    }
+
+    /// Number of transactions to play
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Whether there are no transactions to play
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Time from the first to the last transaction after scaling by the speed
+    pub fn duration(&self) -> Duration {
+        self.offsets().last().copied().unwrap_or_default()
+    }
+
+    /// Scaled time of each transaction after the first, never going backwards
+    fn offsets(&self) -> Vec<Duration> {
+        let Some(first) = self.transactions.first() else {
+            return Vec::new();
+        };
+        let mut latest = Duration::ZERO;
+        self.transactions
+            .iter()
+            .map(|transaction| {
+                let recorded = (transaction.timestamp - first.timestamp).to_std().unwrap_or_default();
+                latest = latest.max(recorded.div_f64(self.speed));
+                latest
+            })
+            .collect()
+    }
+
+    /// Play every transaction through the callback, returning how many were played
+    ///
+    /// The next transaction waits for the future the callback returns.
+    pub async fn run<F, Fut>(self, mut callback: F) -> usize
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let offsets = self.offsets();
+        let started = Instant::now();
+        let started_at = Utc::now();
+        let mut played = 0;
+        for (mut transaction, offset) in self.transactions.into_iter().zip(offsets) {
+            time::sleep_until(started + offset).await;
+            transaction.timestamp = started_at + TimeDelta::from_std(offset).unwrap_or_default();
+            callback(transaction).await;
+            played += 1;
+        }
+        played
+    }
+}
@@ -0,0 +1,352 @@
+use awc::ws;
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time;
+use utoipa::ToSchema;
+
+use crate::api::auth::API_KEY_HEADER;
+use crate::config::{ReplicationConfig, ReplicationRole};
+use crate::models::{TimeInterval, Transaction};
+use crate::services::KLineService;
+
+/// Closed-candle checksum for a token and interval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CandleChecksum {
+    /// Token symbol
+    pub token: String,
+    /// Time interval
+    pub interval: TimeInterval,
+    /// Number of closed candles covered by the checksum
+    pub count: usize,
+    /// Checksum over the covered candles
+    pub checksum: u64,
+}
+
+/// Compute checksums of closed candles for every token and interval
+pub fn compute_checksums(kline_service: &KLineService, since: DateTime<Utc>) -> Vec<CandleChecksum> {
+    let mut tokens = kline_service.get_available_tokens();
+    tokens.sort();
+
+    let mut checksums = Vec::new();
+    for token in tokens {
+        for interval in TimeInterval::all() {
+            let (count, checksum) = kline_service.checksum(&token, interval, since);
+            checksums.push(CandleChecksum {
+                token: token.clone(),
+                interval,
+                count,
+                checksum,
+            });
+        }
+    }
+    checksums
+}
+
+/// Snapshot of the replication state, as reported by the info endpoint
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReplicationStatus {
+    /// Current role of this instance
+    pub role: ReplicationRole,
+    /// Whether the standby stream to the primary is connected
+    pub connected_to_primary: bool,
+    /// Start of the window fully replicated since the last (re)connect
+    pub synced_since: Option<DateTime<Utc>>,
+    /// Transactions received from the primary
+    pub replicated_transactions: u64,
+    /// Checksum comparisons that did not match the primary
+    pub checksum_mismatches: u64,
+    /// Consecutive failed primary health checks
+    pub primary_failures: u32,
+}
+
+/// Shared replication state for this instance
+#[derive(Debug)]
+pub struct ReplicationState {
+    role: RwLock<ReplicationRole>,
+    connected: AtomicBool,
+    synced_since: RwLock<Option<DateTime<Utc>>>,
+    replicated_transactions: AtomicU64,
+    checksum_mismatches: AtomicU64,
+    primary_failures: AtomicU32,
+}
+
+impl ReplicationState {
+    /// Create replication state starting in the given role
+    pub fn new(role: ReplicationRole) -> Self {
+        Self {
+            role: RwLock::new(role),
+            connected: AtomicBool::new(false),
+            synced_since: RwLock::new(None),
+            replicated_transactions: AtomicU64::new(0),
+            checksum_mismatches: AtomicU64::new(0),
+            primary_failures: AtomicU32::new(0),
+        }
+    }
+
+    /// Current role
+    pub fn role(&self) -> ReplicationRole {
+        self.role
+            .read()
+            .map(|role| *role)
+            .unwrap_or(ReplicationRole::Standby)
+    }
+
+    /// Whether this instance is currently the primary
+    pub fn is_primary(&self) -> bool {
+        self.role() == ReplicationRole::Primary
+    }
+
+    /// Promote this instance to primary, returning false if it already was
+    pub fn promote(&self) -> bool {
+        match self.role.write() {
+            Ok(mut role) if *role == ReplicationRole::Standby => {
+                *role = ReplicationRole::Primary;
+                self.connected.store(false, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record the result of a primary health check, returning the failure streak
+    pub fn record_health_check(&self, healthy: bool) -> u32 {
+        if healthy {
+            self.primary_failures.store(0, Ordering::Relaxed);
+            0
+        } else {
+            self.primary_failures.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    /// Get a snapshot of the replication state
+    pub fn status(&self) -> ReplicationStatus {
+        ReplicationStatus {
+            role: self.role(),
+            connected_to_primary: self.connected.load(Ordering::Relaxed),
+            synced_since: self.synced_since(),
+            replicated_transactions: self.replicated_transactions.load(Ordering::Relaxed),
+            checksum_mismatches: self.checksum_mismatches.load(Ordering::Relaxed),
+            primary_failures: self.primary_failures.load(Ordering::Relaxed),
+        }
+    }
+
+    fn synced_since(&self) -> Option<DateTime<Utc>> {
+        self.synced_since.read().ok().and_then(|since| *since)
+    }
+
+    fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+        if let Ok(mut since) = self.synced_since.write() {
+            *since = connected.then(Utc::now);
+        }
+    }
+}
+
+/// Replicates transactions from a primary instance and monitors its health
+pub struct Replicator {
+    config: ReplicationConfig,
+    primary_url: String,
+    state: Arc<ReplicationState>,
+    kline_service: Arc<KLineService>,
+}
+
+impl Replicator {
+    /// Create a replicator for a standby instance
+    pub fn new(
+        config: ReplicationConfig,
+        state: Arc<ReplicationState>,
+        kline_service: Arc<KLineService>,
+    ) -> Self {
+        let primary_url = config
+            .primary_url
+            .clone()
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string();
+
+        Self {
+            config,
+            primary_url,
+            state,
+            kline_service,
+        }
+    }
+
+    /// Run replication until this instance is promoted
+    ///
+    /// Every transaction received from the primary is handed to `callback`,
+    /// which should apply it exactly like a locally generated one. The next
+    /// transaction is read once the future the callback returns completes.
+    pub async fn run<F, Fut>(self, mut callback: F)
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        futures::join!(
+            self.replicate_transactions(&mut callback),
+            self.monitor_primary(),
+            self.verify_checksums(),
+        );
+        tracing::info!("Replication stopped, instance is now {:?}", self.state.role());
+    }
+
+    /// HTTP client that authenticates with the configured API key
+    fn client(&self) -> awc::Client {
+        let mut builder = awc::Client::builder();
+        if let Some(api_key) = &self.config.api_key {
+            builder = builder.add_default_header((API_KEY_HEADER, api_key.as_str()));
+        }
+        builder.finish()
+    }
+
+    /// Stream transactions from the primary WebSocket endpoint
+    async fn replicate_transactions<F, Fut>(&self, callback: &mut F)
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let ws_url = format!("{}/ws", self.primary_url.replacen("http", "ws", 1));
+        let retry_delay = Duration::from_millis(self.config.health_check_interval_ms);
+
+        while !self.state.is_primary() {
+            match self.client().ws(&ws_url).connect().await {
+                Ok((_, mut framed)) => {
+                    let subscribe = serde_json::json!({
+                        "action": "subscribe",
+                        "subscription": { "type": "all_transactions" }
+                    });
+                    if framed
+                        .send(ws::Message::Text(subscribe.to_string().into()))
+                        .await
+                        .is_ok()
+                    {
+                        self.state.set_connected(true);
+                        tracing::info!("Replicating from primary at {}", ws_url);
+                    }
+
+                    while let Some(frame) = framed.next().await {
+                        if self.state.is_primary() {
+                            break;
+                        }
+                        match frame {
+                            Ok(ws::Frame::Text(bytes)) => {
+                                if let Some(transaction) = parse_transaction(&bytes) {
+                                    self.state
+                                        .replicated_transactions
+                                        .fetch_add(1, Ordering::Relaxed);
+                                    callback(transaction).await;
+                                }
+                            }
+                            Ok(ws::Frame::Ping(payload)) => {
+                                let _ = framed.send(ws::Message::Pong(payload)).await;
+                            }
+                            Ok(ws::Frame::Close(_)) | Err(_) => break,
+                            Ok(_) => {}
+                        }
+                    }
+
+                    self.state.set_connected(false);
+                    tracing::warn!("Replication stream from primary disconnected");
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to connect to primary at {}: {}", ws_url, e);
+                }
+            }
+
+            time::sleep(retry_delay).await;
+        }
+    }
+
+    /// Poll the primary health endpoint and promote on sustained failure
+    async fn monitor_primary(&self) {
+        let client = self.client();
+        let health_url = format!("{}/healthz", self.primary_url);
+        let mut interval = time::interval(Duration::from_millis(self.config.health_check_interval_ms));
+
+        while !self.state.is_primary() {
+            interval.tick().await;
+
+            let healthy = matches!(
+                client.get(&health_url).send().await,
+                Ok(response) if response.status().is_success()
+            );
+            let failures = self.state.record_health_check(healthy);
+
+            if failures >= self.config.failover_threshold && self.config.auto_failover {
+                tracing::warn!(
+                    "Primary failed {} consecutive health checks, promoting to primary",
+                    failures
+                );
+                self.state.promote();
+            }
+        }
+    }
+
+    /// Periodically compare closed-candle checksums with the primary
+    async fn verify_checksums(&self) {
+        let client = self.client();
+        let mut interval = time::interval(Duration::from_secs(self.config.checksum_interval_secs));
+
+        while !self.state.is_primary() {
+            interval.tick().await;
+
+            let Some(since) = self.state.synced_since() else {
+                continue;
+            };
+            let url = format!(
+                "{}/api/v1/replication/checksums?since={}",
+                self.primary_url,
+                since.timestamp_millis()
+            );
+
+            let primary: Vec<CandleChecksum> = match client.get(&url).send().await {
+                Ok(mut response) => match response.json().await {
+                    Ok(checksums) => checksums,
+                    Err(e) => {
+                        tracing::warn!("Invalid checksum response from primary: {}", e);
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    tracing::warn!("Failed to fetch checksums from primary: {}", e);
+                    continue;
+                }
+            };
+
+            for expected in &primary {
+                let local = self
+                    .kline_service
+                    .checksum(&expected.token, expected.interval, since);
+                if local != (expected.count, expected.checksum) {
+                    self.state.checksum_mismatches.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        "Checksum mismatch for {} {}: primary has {} candles",
+                        expected.token,
+                        expected.interval.as_str(),
+                        expected.count
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Extract a transaction from a primary broadcast message
+fn parse_transaction(bytes: &[u8]) -> Option<Transaction> {
+    let mut message: serde_json::Value = serde_json::from_slice(bytes).ok()?;
+    if message.get("type")?.as_str()? != "transaction" {
+        return None;
+    }
+    match serde_json::from_value(message.get_mut("data")?.take()) {
+        Ok(transaction) => Some(transaction),
+        Err(e) => {
+            tracing::warn!("Ignoring invalid replicated transaction: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::{KLine, TimeInterval};
+
+/// Longest interval candles can be resampled to
+pub const MAX_TARGET_SECONDS: u64 = 30 * 86_400;
+
+/// Interval candles are rolled up to at query time, such as `2h` or `3d`
+///
+/// Buckets are aligned to the Unix epoch, so daily buckets start at
+/// midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetInterval {
+    seconds: u64,
+}
+
+impl TargetInterval {
+    pub fn seconds(&self) -> u64 {
+        self.seconds
+    }
+
+    fn span(&self) -> TimeDelta {
+        TimeDelta::seconds(self.seconds as i64)
+    }
+
+    /// Open time of the bucket containing `timestamp`
+    pub fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        timestamp.duration_trunc(self.span()).unwrap_or(timestamp)
+    }
+
+    /// Whether candles of `source` can be rolled up into this interval
+    ///
+    /// Each bucket must be made of whole source candles.
+    pub fn accepts(&self, source: TimeInterval) -> bool {
+        source
+            .duration_seconds()
+            .is_some_and(|seconds| self.seconds > seconds && self.seconds.is_multiple_of(seconds))
+    }
+}
+
+impl FromStr for TargetInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid target interval: {}", s);
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let unit_seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        let count: u64 = s[..s.len() - 1]
+            .parse()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(invalid)?;
+        let seconds = count.checked_mul(unit_seconds).filter(|seconds| *seconds <= MAX_TARGET_SECONDS);
+        seconds.map(|seconds| TargetInterval { seconds }).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for TargetInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.seconds {
+            seconds if seconds.is_multiple_of(86_400) => write!(f, "{}d", seconds / 86_400),
+            seconds if seconds.is_multiple_of(3600) => write!(f, "{}h", seconds / 3600),
+            seconds if seconds.is_multiple_of(60) => write!(f, "{}m", seconds / 60),
+            seconds => write!(f, "{}s", seconds),
+        }
+    }
+}
+
+/// A candle rolled up from stored candles of a finer interval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ResampledKLine {
+    pub token: String,
+    /// Start of the bucket
+    pub timestamp: DateTime<Utc>,
+    /// Last millisecond of the bucket
+    pub close_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    /// Whether the bucket has ended and all its source candles are closed
+    pub is_closed: bool,
+    /// Source candles rolled into this one
+    pub candles: usize,
+}
+
+/// Roll candles of one token, sorted oldest first, up into `target` buckets
+pub fn resample(klines: &[KLine], target: TargetInterval, now: DateTime<Utc>) -> Vec<ResampledKLine> {
+    let mut resampled: Vec<ResampledKLine> = Vec::new();
+    for kline in klines {
+        let start = target.bucket_start(kline.timestamp);
+        match resampled.last_mut() {
+            Some(bucket) if bucket.timestamp == start => {
+                bucket.high = bucket.high.max(kline.high);
+                bucket.low = bucket.low.min(kline.low);
+                bucket.close = kline.close;
+                bucket.volume += kline.volume;
+                bucket.is_closed &= kline.is_closed;
+                bucket.candles += 1;
+            }
+            _ => {
+                let close_time = start + target.span() - TimeDelta::milliseconds(1);
+                resampled.push(ResampledKLine {
+                    token: kline.token.clone(),
+                    timestamp: start,
+                    close_time,
+                    open: kline.open,
+                    high: kline.high,
+                    low: kline.low,
+                    close: kline.close,
+                    volume: kline.volume,
+                    is_closed: kline.is_closed && close_time < now,
+                    candles: 1,
+                });
+            }
+        }
+    }
+    resampled
+}
@@ -0,0 +1,233 @@
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Market change scripted at a point of a scenario
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScenarioAction {
+    /// Prices move linearly to `price` over `duration_secs`, then stay there
+    PriceTarget {
+        price: f64,
+        #[serde(default)]
+        duration_secs: u64,
+    },
+    /// Volatility is scaled by `multiplier` until the next volatility change
+    Volatility { multiplier: f64 },
+    /// No trades for `duration_secs`
+    Halt { duration_secs: u64 },
+    /// Volumes are scaled by `multiplier` for `duration_secs`
+    VolumeSurge { multiplier: f64, duration_secs: u64 },
+}
+
+/// Timed event of a scenario
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScenarioEvent {
+    /// Seconds from the start of the scenario
+    pub at_secs: u64,
+    /// Token affected; every token when omitted
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(flatten)]
+    pub action: ScenarioAction,
+}
+
+impl ScenarioEvent {
+    fn applies_to(&self, token: &str) -> bool {
+        self.token.as_deref().is_none_or(|event_token| event_token == token)
+    }
+
+    /// Seconds from the start of the scenario until the event is over
+    fn end_secs(&self) -> u64 {
+        match self.action {
+            ScenarioAction::PriceTarget { duration_secs, .. }
+            | ScenarioAction::Halt { duration_secs }
+            | ScenarioAction::VolumeSurge { duration_secs, .. } => self.at_secs + duration_secs,
+            ScenarioAction::Volatility { .. } => self.at_secs,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let valid_multiplier = |multiplier: f64| multiplier.is_finite() && multiplier >= 0.0;
+        match self.action {
+            ScenarioAction::PriceTarget { price, .. } => {
+                if self.token.is_none() {
+                    return Err(format!("price_target at {}s needs a token", self.at_secs));
+                }
+                if !price.is_finite() || price <= 0.0 {
+                    return Err(format!("price_target at {}s needs a positive price", self.at_secs));
+                }
+            }
+            ScenarioAction::Volatility { multiplier } | ScenarioAction::VolumeSurge { multiplier, .. }
+                if !valid_multiplier(multiplier) =>
+            {
+                return Err(format!("Multiplier at {}s must not be negative", self.at_secs));
+            }
+            ScenarioAction::Halt { duration_secs: 0 } | ScenarioAction::VolumeSurge { duration_secs: 0, .. } => {
+                return Err(format!("Event at {}s needs a duration_secs of more than 0", self.at_secs));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reason a scenario could not be loaded
+#[derive(Debug)]
+pub enum ScenarioError {
+    /// The file could not be read
+    Io(std::io::Error),
+    /// The file extension is not one of the supported formats
+    UnsupportedFormat(String),
+    /// The file does not describe a valid scenario
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "Cannot read scenario file: {}", e),
+            ScenarioError::UnsupportedFormat(path) => {
+                write!(f, "Unsupported scenario file: {}. Expected a .toml or .json file", path)
+            }
+            ScenarioError::Invalid(message) => write!(f, "Invalid scenario: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(e: std::io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+/// Market conditions of a token at a point of a scenario
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScenarioState {
+    /// Price trades are generated around
+    pub price: f64,
+    /// Factor applied to the generator's volatility
+    pub volatility_multiplier: f64,
+    /// Factor applied to generated volumes
+    pub volume_multiplier: f64,
+    /// Whether the token does not trade
+    pub halted: bool,
+}
+
+/// Timed market events run by the mock generator on a virtual timeline
+///
+/// Trades are drawn from a random generator seeded with `seed`, so every run
+/// of a scenario generates the same trades at the same offsets.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Scenario {
+    /// Seed of the generated trades
+    #[serde(default)]
+    pub seed: u64,
+    /// Seconds the scenario runs; until the last event is over when omitted
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    #[serde(default)]
+    pub events: Vec<ScenarioEvent>,
+}
+
+impl Scenario {
+    /// Parse a scenario from TOML
+    pub fn from_toml(content: &str) -> Result<Self, ScenarioError> {
+        toml::from_str::<Scenario>(content)
+            .map_err(|e| ScenarioError::Invalid(e.to_string()))?
+            .validated()
+    }
+
+    /// Parse a scenario from JSON
+    pub fn from_json(content: &str) -> Result<Self, ScenarioError> {
+        serde_json::from_str::<Scenario>(content)
+            .map_err(|e| ScenarioError::Invalid(e.to_string()))?
+            .validated()
+    }
+
+    /// Load a TOML or JSON scenario file
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("toml") => Self::from_toml(&fs::read_to_string(path)?),
+            Some("json") => Self::from_json(&fs::read_to_string(path)?),
+            _ => Err(ScenarioError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    /// Check the events and order them by time, keeping the file order of simultaneous events
+    fn validated(mut self) -> Result<Self, ScenarioError> {
+        for event in &self.events {
+            event.validate().map_err(ScenarioError::Invalid)?;
+        }
+        self.events.sort_by_key(|event| event.at_secs);
+        Ok(self)
+    }
+
+    /// Check that every event names one of the tokens
+    pub fn check_tokens(&self, tokens: &[String]) -> Result<(), ScenarioError> {
+        match self
+            .events
+            .iter()
+            .filter_map(|event| event.token.as_ref())
+            .find(|token| !tokens.contains(token))
+        {
+            Some(token) => Err(ScenarioError::Invalid(format!("unknown token {}", token))),
+            None => Ok(()),
+        }
+    }
+
+    /// Length of the virtual timeline
+    pub fn duration(&self) -> Duration {
+        let secs = self
+            .duration_secs
+            .unwrap_or_else(|| self.events.iter().map(ScenarioEvent::end_secs).max().unwrap_or_default());
+        Duration::from_secs(secs)
+    }
+
+    /// Market conditions of a token `offset` into the scenario
+    ///
+    /// Each price target starts from the price reached when it begins, so a
+    /// later target takes over from an earlier one still in progress.
+    pub fn state(&self, token: &str, base_price: f64, offset: Duration) -> ScenarioState {
+        let now = offset.as_secs_f64();
+        let mut state = ScenarioState {
+            price: base_price,
+            volatility_multiplier: 1.0,
+            volume_multiplier: 1.0,
+            halted: false,
+        };
+        // Price path so far: start, starting price, target and duration of the latest target
+        let mut target: Option<(f64, f64, f64, f64)> = None;
+        let price_at = |target: Option<(f64, f64, f64, f64)>, at: f64| match target {
+            Some((start, from, to, duration)) if duration > 0.0 => {
+                from + (to - from) * ((at - start) / duration).clamp(0.0, 1.0)
+            }
+            Some((_, _, to, _)) => to,
+            None => base_price,
+        };
+
+        for event in self.events.iter().filter(|event| event.applies_to(token)) {
+            let start = event.at_secs as f64;
+            if start > now {
+                break;
+            }
+            let active = now < event.end_secs() as f64;
+            match event.action {
+                ScenarioAction::PriceTarget { price, duration_secs } => {
+                    target = Some((start, price_at(target, start), price, duration_secs as f64));
+                }
+                ScenarioAction::Volatility { multiplier } => state.volatility_multiplier = multiplier,
+                ScenarioAction::Halt { .. } => state.halted |= active,
+                ScenarioAction::VolumeSurge { multiplier, .. } if active => state.volume_multiplier *= multiplier,
+                ScenarioAction::VolumeSurge { .. } => {}
+            }
+        }
+        state.price = price_at(target, now);
+        state
+    }
+}
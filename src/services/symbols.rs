@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::config::{Config, TokenConfig};
+use crate::models::Transaction;
+use crate::services::{KLineService, TokenRegistry};
+
+/// Most decimal places a price or quantity can be rounded to
+pub const MAX_PRECISION: u32 = 15;
+
+/// Configured metadata and price and quantity rules of a token
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct SymbolSpec {
+    /// Token symbol
+    pub symbol: String,
+    /// Base price for mock data generation
+    pub base_price: f64,
+    /// Volatility percentage for mock data generation
+    pub volatility: f64,
+    /// Decimal places prices are rounded to
+    pub price_precision: Option<u32>,
+    /// Decimal places volumes are rounded to
+    pub quantity_precision: Option<u32>,
+    /// Smallest price increment
+    pub tick_size: Option<f64>,
+}
+
+impl SymbolSpec {
+    /// Take the rounding rules of a configured token
+    pub fn from_token_config(token: &TokenConfig) -> Self {
+        Self {
+            symbol: token.symbol.clone(),
+            base_price: token.base_price,
+            volatility: token.volatility,
+            price_precision: token.price_precision,
+            quantity_precision: token.quantity_precision,
+            tick_size: token.tick_size,
+        }
+    }
+
+    /// Round a price to the tick size, then to the price precision
+    ///
+    /// Without a price precision, the tick size's own decimal places are used
+    /// so multiples of the tick print without floating-point noise.
+    pub fn round_price(&self, price: f64) -> f64 {
+        let mut rounded = price;
+        let mut precision = self.price_precision;
+        if let Some(tick_size) = self.tick_size {
+            rounded = (rounded / tick_size).round() * tick_size;
+            precision = precision.or_else(|| Some(decimal_places(tick_size)));
+        }
+        match precision {
+            Some(decimals) => round_to(rounded, decimals),
+            None => rounded,
+        }
+    }
+
+    /// Round a volume to the quantity precision
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        match self.quantity_precision {
+            Some(decimals) => round_to(quantity, decimals),
+            None => quantity,
+        }
+    }
+}
+
+/// Whether a configured token is trading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolStatus {
+    /// Transactions for the token are applied
+    Trading,
+    /// The token was delisted or unregistered at runtime
+    Halted,
+}
+
+/// Open times of the earliest and latest stored candle of an interval
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct CandleRange {
+    pub earliest: DateTime<Utc>,
+    pub latest: DateTime<Utc>,
+}
+
+/// Metadata of a configured token, with the candles available for it
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct SymbolInfo {
+    /// Token symbol
+    pub symbol: String,
+    /// Base price for mock data generation
+    pub base_price: f64,
+    /// Volatility percentage for mock data generation
+    pub volatility: f64,
+    /// Decimal places prices are rounded to
+    pub price_precision: Option<u32>,
+    /// Decimal places volumes are rounded to
+    pub quantity_precision: Option<u32>,
+    /// Smallest price increment
+    pub tick_size: Option<f64>,
+    pub status: SymbolStatus,
+    /// Stored candles per interval; intervals without candles are omitted
+    pub availability: BTreeMap<String, CandleRange>,
+}
+
+impl SymbolInfo {
+    /// Describe a token from its configuration and current state
+    pub fn new(spec: SymbolSpec, kline_service: &KLineService, registry: &TokenRegistry) -> Self {
+        let availability = kline_service
+            .intervals()
+            .into_iter()
+            .filter_map(|interval| {
+                let (earliest, latest) = kline_service.get_candle_range(&spec.symbol, interval)?;
+                Some((interval.to_string(), CandleRange { earliest, latest }))
+            })
+            .collect();
+        let status = if registry.is_registered(&spec.symbol) {
+            SymbolStatus::Trading
+        } else {
+            SymbolStatus::Halted
+        };
+
+        Self {
+            symbol: spec.symbol,
+            base_price: spec.base_price,
+            volatility: spec.volatility,
+            price_precision: spec.price_precision,
+            quantity_precision: spec.quantity_precision,
+            tick_size: spec.tick_size,
+            status,
+            availability,
+        }
+    }
+}
+
+/// Check the rounding rules of a configured token
+pub fn validate_token(token: &TokenConfig) -> Result<(), String> {
+    for (name, precision) in [
+        ("price_precision", token.price_precision),
+        ("quantity_precision", token.quantity_precision),
+    ] {
+        if precision.is_some_and(|decimals| decimals > MAX_PRECISION) {
+            return Err(format!("{} of {} must be at most {}", name, token.symbol, MAX_PRECISION));
+        }
+    }
+    if let Some(tick_size) = token.tick_size {
+        if !tick_size.is_finite() || tick_size <= 0.0 {
+            return Err(format!("tick_size of {} must be positive", token.symbol));
+        }
+        if decimal_places(tick_size) > token.price_precision.unwrap_or(MAX_PRECISION) {
+            return Err(format!(
+                "tick_size of {} has more decimal places than its price_precision",
+                token.symbol
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decimal places in the shortest representation of a value
+fn decimal_places(value: f64) -> u32 {
+    let formatted = value.to_string();
+    formatted
+        .split_once('.')
+        .map_or(0, |(_, decimals)| decimals.len() as u32)
+}
+
+fn round_to(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// Metadata and rounding rules of the configured tokens
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    specs: DashMap<String, SymbolSpec>,
+}
+
+impl SymbolTable {
+    /// Create a table with the configured tokens
+    pub fn from_config(config: &Config) -> Self {
+        let table = Self::default();
+        table.apply_config(config);
+        table
+    }
+
+    /// Replace the rules with those of a configuration
+    pub fn apply_config(&self, config: &Config) {
+        let tokens = &config.tokens.supported_tokens;
+        for token in tokens {
+            self.specs.insert(token.symbol.clone(), SymbolSpec::from_token_config(token));
+        }
+        self.specs.retain(|symbol, _| tokens.iter().any(|token| token.symbol == *symbol));
+    }
+
+    /// Get the rules of a token
+    pub fn get(&self, symbol: &str) -> Option<SymbolSpec> {
+        self.specs.get(symbol).map(|spec| spec.clone())
+    }
+
+    /// Get the metadata of every configured token, sorted by symbol
+    pub fn specs(&self) -> Vec<SymbolSpec> {
+        let mut specs: Vec<SymbolSpec> = self.specs.iter().map(|spec| spec.clone()).collect();
+        specs.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        specs
+    }
+
+    /// Round a transaction's price and volume by its token's rules
+    ///
+    /// Transactions for tokens without rules are returned unchanged.
+    pub fn normalize(&self, transaction: &Transaction) -> Transaction {
+        let mut normalized = transaction.clone();
+        if let Some(spec) = self.specs.get(&transaction.token) {
+            normalized.price = spec.round_price(transaction.price);
+            normalized.volume = spec.round_quantity(transaction.volume);
+        }
+        normalized
+    }
+}
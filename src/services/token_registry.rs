@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, DashSet};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::config::{Config, UnknownTokenPolicy};
+use crate::models::Transaction;
+
+/// Default number of quarantined transactions held per token
+pub const DEFAULT_QUARANTINE_CAPACITY: usize = 1000;
+
+/// What the registry did with a token the first time it was seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAction {
+    /// A new series was started
+    Registered,
+    /// Transactions are held back until an admin registers the token
+    Quarantined,
+    /// Transactions are dropped
+    Rejected,
+}
+
+/// Notice that a transaction referenced an unconfigured token
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct NewTokenEvent {
+    /// Token symbol
+    pub token: String,
+    /// Action taken under the configured policy
+    pub action: TokenAction,
+    /// When the token was first seen
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Notice that a token was listed or delisted at runtime
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "typescript", derive(ts_rs::TS))]
+#[cfg_attr(feature = "server", derive(schemars::JsonSchema))]
+pub struct ListingEvent {
+    /// Token symbol
+    pub token: String,
+    /// Base price trades are generated around; absent for delistings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "typescript", ts(optional))]
+    pub base_price: Option<f64>,
+    /// When the token was listed or delisted
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Outcome of checking a transaction's token against the registry
+#[derive(Debug, Clone)]
+pub struct Admission {
+    /// Whether the transaction should be applied
+    pub accepted: bool,
+    /// Set the first time an unconfigured token is seen
+    pub event: Option<NewTokenEvent>,
+}
+
+/// Unconfigured token whose transactions are being dropped
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct UnregisteredToken {
+    /// Token symbol
+    pub token: String,
+    /// Whether the token is quarantined or rejected
+    pub status: TokenAction,
+    /// When the token was first seen
+    pub first_seen: DateTime<Utc>,
+    /// When the token was last seen
+    pub last_seen: DateTime<Utc>,
+    /// Number of transactions dropped or held back so far
+    pub dropped_transactions: u64,
+}
+
+/// Tracks which tokens may start K-line series
+#[derive(Debug)]
+pub struct TokenRegistry {
+    /// Policy for unconfigured tokens
+    policy: UnknownTokenPolicy,
+    /// Tokens whose transactions are applied
+    registered: DashSet<String>,
+    /// Unconfigured tokens held back by the reject or quarantine policy
+    unregistered: DashMap<String, UnregisteredToken>,
+    /// Latest quarantined transactions per token, oldest first
+    held: DashMap<String, VecDeque<Transaction>>,
+    /// Quarantined transactions held per token
+    quarantine_capacity: usize,
+}
+
+impl TokenRegistry {
+    pub fn new(policy: UnknownTokenPolicy, tokens: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            policy,
+            registered: tokens.into_iter().collect(),
+            unregistered: DashMap::new(),
+            held: DashMap::new(),
+            quarantine_capacity: DEFAULT_QUARANTINE_CAPACITY,
+        }
+    }
+
+    /// Set the number of quarantined transactions held per token; older ones are dropped
+    pub fn with_quarantine_capacity(mut self, capacity: usize) -> Self {
+        self.quarantine_capacity = capacity;
+        self
+    }
+
+    /// Create a registry seeded with the configured tokens
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.tokens.unknown_token_policy,
+            config.get_supported_tokens(),
+        )
+    }
+
+    /// Get the policy for unconfigured tokens
+    pub fn policy(&self) -> UnknownTokenPolicy {
+        self.policy
+    }
+
+    /// Decide whether a transaction for a token should be applied
+    pub fn admit(&self, token: &str) -> Admission {
+        if self.registered.contains(token) {
+            return Admission {
+                accepted: true,
+                event: None,
+            };
+        }
+
+        let status = match self.policy {
+            UnknownTokenPolicy::AutoRegister => {
+                // Only the caller that inserts the token reports it
+                let event = self
+                    .registered
+                    .insert(token.to_string())
+                    .then(|| new_token_event(token, TokenAction::Registered));
+                return Admission {
+                    accepted: true,
+                    event,
+                };
+            }
+            UnknownTokenPolicy::Quarantine => TokenAction::Quarantined,
+            UnknownTokenPolicy::Reject => TokenAction::Rejected,
+        };
+
+        let now = Utc::now();
+        let mut first_seen = false;
+        let mut entry = self
+            .unregistered
+            .entry(token.to_string())
+            .or_insert_with(|| {
+                first_seen = true;
+                UnregisteredToken {
+                    token: token.to_string(),
+                    status,
+                    first_seen: now,
+                    last_seen: now,
+                    dropped_transactions: 0,
+                }
+            });
+        entry.last_seen = now;
+        entry.dropped_transactions += 1;
+
+        Admission {
+            accepted: false,
+            event: first_seen.then(|| new_token_event(token, status)),
+        }
+    }
+
+    /// Decide whether a transaction should be applied, holding it back under the quarantine policy
+    pub fn admit_transaction(&self, transaction: &Transaction) -> Admission {
+        let admission = self.admit(&transaction.token);
+        if !admission.accepted && self.policy == UnknownTokenPolicy::Quarantine && self.quarantine_capacity > 0 {
+            let mut held = self.held.entry(transaction.token.clone()).or_default();
+            if held.len() >= self.quarantine_capacity {
+                held.pop_front();
+            }
+            held.push_back(transaction.clone());
+        }
+        admission
+    }
+
+    /// Take the quarantined transactions of a token once it is registered, oldest first
+    pub fn release(&self, token: &str) -> Vec<Transaction> {
+        if !self.held.contains_key(token) || !self.is_registered(token) {
+            return Vec::new();
+        }
+        self.held
+            .remove(token)
+            .map(|(_, held)| held.into())
+            .unwrap_or_default()
+    }
+
+    /// Register a token so its transactions are applied
+    ///
+    /// Returns `false` if the token was already registered.
+    pub fn register(&self, token: &str) -> bool {
+        self.unregistered.remove(token);
+        self.registered.insert(token.to_string())
+    }
+
+    /// Unregister a token so its transactions are handled by the policy again
+    ///
+    /// Returns `false` if the token was not registered.
+    pub fn unregister(&self, token: &str) -> bool {
+        self.registered.remove(token).is_some()
+    }
+
+    /// Whether a token's transactions are applied
+    pub fn is_registered(&self, token: &str) -> bool {
+        self.registered.contains(token)
+    }
+
+    /// Get the registered tokens, sorted by symbol
+    pub fn registered_tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = self.registered.iter().map(|token| token.clone()).collect();
+        tokens.sort();
+        tokens
+    }
+
+    /// Get the unconfigured tokens being dropped, oldest first
+    pub fn unregistered_tokens(&self) -> Vec<UnregisteredToken> {
+        let mut tokens: Vec<UnregisteredToken> =
+            self.unregistered.iter().map(|entry| entry.clone()).collect();
+        tokens.sort_by_key(|token| token.first_seen);
+        tokens
+    }
+}
+
+fn new_token_event(token: &str, action: TokenAction) -> NewTokenEvent {
+    NewTokenEvent {
+        token: token.to_string(),
+        action,
+        timestamp: Utc::now(),
+    }
+}
@@ -0,0 +1,50 @@
+use dashmap::DashMap;
+use std::collections::VecDeque;
+
+use crate::models::Transaction;
+
+/// Recent trades per token, kept in bounded ring buffers
+#[derive(Debug)]
+pub struct TradeService {
+    /// Trades kept per token
+    capacity: usize,
+    /// Trades per token, oldest first
+    trades: DashMap<String, VecDeque<Transaction>>,
+}
+
+impl TradeService {
+    /// Create a service keeping the last `capacity` trades of each token
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            trades: DashMap::new(),
+        }
+    }
+
+    /// Get the number of trades kept per token
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Record a trade, evicting the token's oldest trade when the buffer is full
+    pub fn record(&self, transaction: &Transaction) {
+        let mut trades = self.trades.entry(transaction.token.clone()).or_default();
+        if trades.len() == self.capacity {
+            trades.pop_front();
+        }
+        if self.capacity > 0 {
+            trades.push_back(transaction.clone());
+        }
+    }
+
+    /// Get up to `limit` of a token's most recent trades, oldest first
+    pub fn recent(&self, token: &str, limit: usize) -> Vec<Transaction> {
+        self.trades
+            .get(token)
+            .map(|trades| {
+                let skip = trades.len().saturating_sub(limit);
+                trades.iter().skip(skip).cloned().collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+use crate::models::{Side, Transaction};
+
+/// Price levels of a volume profile when none are requested
+pub const DEFAULT_BUCKETS: usize = 50;
+
+/// Most price levels of a volume profile
+pub const MAX_BUCKETS: usize = 1000;
+
+/// Volume traded within one price range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct VolumeLevel {
+    /// Lowest price of the level
+    pub price_low: f64,
+    /// Highest price of the level, exclusive except for the top level
+    pub price_high: f64,
+    pub volume: f64,
+    /// Volume of trades whose aggressor bought
+    pub buy_volume: f64,
+    /// Volume of trades whose aggressor sold
+    pub sell_volume: f64,
+    pub trade_count: u64,
+}
+
+/// Traded volume of a token binned by price
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct VolumeProfile {
+    pub token: String,
+    /// Width of each price level
+    pub bucket_size: f64,
+    pub total_volume: f64,
+    /// Middle price of the level with the most volume, if any trades were binned
+    pub point_of_control: Option<f64>,
+    /// Price levels from the lowest price up, including levels without volume
+    pub levels: Vec<VolumeLevel>,
+}
+
+impl VolumeProfile {
+    /// Bin trades of one token into `buckets` equal price levels between their lowest and highest price
+    ///
+    /// Trades all at one price fill a single level.
+    pub fn from_trades(token: String, trades: &[Transaction], buckets: usize) -> Self {
+        let (low, high) = trades
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), trade| {
+                (low.min(trade.price), high.max(trade.price))
+            });
+        let buckets = if trades.is_empty() {
+            0
+        } else if high > low {
+            buckets.max(1)
+        } else {
+            1
+        };
+        let bucket_size = if buckets > 0 { (high - low) / buckets as f64 } else { 0.0 };
+
+        let mut levels: Vec<VolumeLevel> = (0..buckets)
+            .map(|index| VolumeLevel {
+                price_low: low + bucket_size * index as f64,
+                price_high: if index + 1 == buckets { high } else { low + bucket_size * (index + 1) as f64 },
+                volume: 0.0,
+                buy_volume: 0.0,
+                sell_volume: 0.0,
+                trade_count: 0,
+            })
+            .collect();
+        for trade in trades {
+            let index = if bucket_size > 0.0 {
+                (((trade.price - low) / bucket_size) as usize).min(buckets - 1)
+            } else {
+                0
+            };
+            let level = &mut levels[index];
+            level.volume += trade.volume;
+            match trade.side {
+                Side::Buy => level.buy_volume += trade.volume,
+                Side::Sell => level.sell_volume += trade.volume,
+            }
+            level.trade_count += 1;
+        }
+
+        let point_of_control = levels
+            .iter()
+            .filter(|level| level.trade_count > 0)
+            .max_by(|a, b| a.volume.total_cmp(&b.volume))
+            .map(|level| (level.price_low + level.price_high) / 2.0);
+        Self {
+            token,
+            bucket_size,
+            total_volume: levels.iter().map(|level| level.volume).sum(),
+            point_of_control,
+            levels,
+        }
+    }
+}
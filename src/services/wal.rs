@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::config::{FsyncPolicy, WalConfig};
+use crate::models::Transaction;
+use crate::services::KLineAmendment;
+
+/// Extension of transaction log segment files
+pub const SEGMENT_EXTENSION: &str = "wal";
+
+/// A change to the candles recorded in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalEntry {
+    /// An ingested transaction
+    Transaction(Transaction),
+    /// A correction of a stored candle
+    Amendment(KLineAmendment),
+}
+
+/// A logged entry with its position in the log
+#[derive(Debug, Serialize, Deserialize)]
+struct WalRecord {
+    sequence: u64,
+    #[serde(flatten)]
+    entry: WalEntry,
+}
+
+/// Segment being appended to
+#[derive(Debug)]
+struct ActiveSegment {
+    file: File,
+    bytes: u64,
+    /// Sequence number the next record gets
+    next_sequence: u64,
+}
+
+/// Append-only log of ingested transactions, replayed at startup
+///
+/// Records are JSON lines numbered from 1, written to segment files named
+/// after the sequence number of their first record. A segment is closed once
+/// it reaches `segment_max_bytes`, and segments a snapshot covers are removed
+/// when it is written, so a restart restores the snapshot and replays only the
+/// transactions logged after it.
+///
+/// Writers hold an [`ApplyGuard`] from logging transactions until they are
+/// applied, and [`TransactionLog::checkpoint`] waits for none to be held, so
+/// a snapshot covers exactly the transactions logged before its sequence.
+#[derive(Debug)]
+pub struct TransactionLog {
+    dir: PathBuf,
+    segment_max_bytes: u64,
+    fsync: FsyncPolicy,
+    active: Mutex<ActiveSegment>,
+    /// Shared by writers between logging and applying, exclusive for checkpoints
+    applying: RwLock<()>,
+}
+
+/// Held from logging transactions until they are applied
+pub type ApplyGuard<'a> = RwLockReadGuard<'a, ()>;
+
+impl TransactionLog {
+    /// Open the log in the configured directory, continuing after its last complete record
+    ///
+    /// Appending always starts a new segment, so a record torn by a crash
+    /// stays the last line of its segment.
+    pub fn open(config: &WalConfig) -> io::Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)?;
+        let next_sequence = match segments(&dir)?.last() {
+            // A segment without a complete record holds nothing worth keeping
+            Some((first, path)) => match complete_records(path)? {
+                0 => {
+                    fs::remove_file(path)?;
+                    *first
+                }
+                records => first + records,
+            },
+            None => 1,
+        };
+        Ok(Self {
+            active: Mutex::new(create_segment(&dir, next_sequence)?),
+            dir,
+            segment_max_bytes: config.segment_max_bytes,
+            fsync: config.fsync,
+            applying: RwLock::new(()),
+        })
+    }
+
+    /// Lock the segment being appended to
+    ///
+    /// A writer that panicked may have left part of a record behind, which
+    /// only a restart skips, so the log refuses further writes.
+    fn active(&self) -> io::Result<MutexGuard<'_, ActiveSegment>> {
+        self.active
+            .lock()
+            .map_err(|_| io::Error::other("transaction log is unusable after a failed write"))
+    }
+
+    /// Sequence number of the last logged transaction, 0 before any
+    pub fn last_sequence(&self) -> u64 {
+        // The counter only advances after a complete write
+        self.active.lock().unwrap_or_else(PoisonError::into_inner).next_sequence - 1
+    }
+
+    /// Mark transactions as being logged and applied until the guard is dropped
+    pub fn begin_apply(&self) -> ApplyGuard<'_> {
+        self.applying.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `copy` while no logged transaction is still to be applied
+    ///
+    /// Returns the sequence number of the last logged transaction with the
+    /// result of `copy`, which saw every transaction up to it and none after.
+    pub fn checkpoint<T>(&self, copy: impl FnOnce() -> T) -> (u64, T) {
+        let _paused = self.pause_apply();
+        (self.last_sequence(), copy())
+    }
+
+    /// Wait until no logged transaction is still to be applied, and keep it so until the guard is dropped
+    pub fn pause_apply(&self) -> RwLockWriteGuard<'_, ()> {
+        self.applying.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Append transactions in one write, syncing them to disk under the `always` policy
+    pub fn append(&self, transactions: &[Transaction]) -> io::Result<()> {
+        self.write(transactions.iter().cloned().map(WalEntry::Transaction))
+    }
+
+    /// Append a candle amendment, syncing it to disk under the `always` policy
+    pub fn append_amendment(&self, amendment: &KLineAmendment) -> io::Result<()> {
+        self.write(std::iter::once(WalEntry::Amendment(amendment.clone())))
+    }
+
+    fn write(&self, entries: impl Iterator<Item = WalEntry>) -> io::Result<()> {
+        let mut active = self.active()?;
+        let mut lines = Vec::new();
+        let mut sequence = active.next_sequence;
+        for entry in entries {
+            let record = WalRecord { sequence, entry };
+            serde_json::to_writer(&mut lines, &record)?;
+            lines.push(b'\n');
+            sequence += 1;
+        }
+        active.file.write_all(&lines)?;
+        active.bytes += lines.len() as u64;
+        active.next_sequence = sequence;
+        if self.fsync == FsyncPolicy::Always {
+            active.file.sync_data()?;
+        }
+
+        if active.bytes >= self.segment_max_bytes {
+            if self.fsync != FsyncPolicy::Never {
+                active.file.sync_data()?;
+            }
+            *active = create_segment(&self.dir, sequence)?;
+        }
+        Ok(())
+    }
+
+    /// Sync the records written so far to disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.active()?.file.sync_data()
+    }
+
+    /// Entries logged after `sequence`, in the order they were logged
+    ///
+    /// Reading a segment stops at its first malformed record, which only a
+    /// crash in the middle of a write leaves behind.
+    pub fn read_after(&self, sequence: u64) -> io::Result<Vec<WalEntry>> {
+        let segments = segments(&self.dir)?;
+        let mut entries = Vec::new();
+        for (index, (_, path)) in segments.iter().enumerate() {
+            // Every record of a segment precedes the next segment's first
+            if segments.get(index + 1).is_some_and(|(next, _)| *next <= sequence + 1) {
+                continue;
+            }
+            for line in BufReader::new(File::open(path)?).lines() {
+                match serde_json::from_str::<WalRecord>(&line?) {
+                    Ok(record) if record.sequence > sequence => entries.push(record.entry),
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("Ignoring the rest of transaction log segment {}: {}", path.display(), e);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Remove the closed segments holding only records up to `sequence`, returning how many
+    pub fn remove_through(&self, sequence: u64) -> io::Result<usize> {
+        // Holding the lock keeps the active segment from rotating meanwhile
+        let _active = self.active()?;
+        let segments = segments(&self.dir)?;
+        let mut removed = 0;
+        for pair in segments.windows(2) {
+            let ((_, path), (next, _)) = (&pair[0], &pair[1]);
+            if *next > sequence + 1 {
+                break;
+            }
+            fs::remove_file(path)?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+/// Segment files in a directory with the sequence number of their first record, in log order
+fn segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(first) = path.file_stem().and_then(|stem| stem.to_str()?.parse().ok()) {
+            segments.push((first, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// Number of records in a segment before the first malformed one
+fn complete_records(path: &Path) -> io::Result<u64> {
+    let mut records = 0;
+    for line in BufReader::new(File::open(path)?).lines() {
+        if serde_json::from_str::<WalRecord>(&line?).is_err() {
+            break;
+        }
+        records += 1;
+    }
+    Ok(records)
+}
+
+fn create_segment(dir: &Path, next_sequence: u64) -> io::Result<ActiveSegment> {
+    let path = dir.join(format!("{:020}.{}", next_sequence, SEGMENT_EXTENSION));
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(ActiveSegment {
+        bytes: file.metadata()?.len(),
+        file,
+        next_sequence,
+    })
+}
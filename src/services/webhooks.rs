@@ -0,0 +1,152 @@
+use ring::hmac;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::{WebhookEventType, WebhookTargetConfig, WebhooksConfig};
+use crate::models::KLine;
+use crate::services::alerts::AlertTrigger;
+
+/// Header carrying the payload signature
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Header carrying the event type
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+/// Event delivered to webhook targets
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A K-line closed
+    KlineClosed(KLine),
+    /// A price alert triggered
+    Alert(AlertTrigger),
+}
+
+impl WebhookEvent {
+    /// Kind of the event
+    pub fn event_type(&self) -> WebhookEventType {
+        match self {
+            WebhookEvent::KlineClosed(_) => WebhookEventType::KlineClosed,
+            WebhookEvent::Alert(_) => WebhookEventType::Alert,
+        }
+    }
+
+    /// Token the event is about
+    fn token(&self) -> &str {
+        match self {
+            WebhookEvent::KlineClosed(kline) => &kline.token,
+            WebhookEvent::Alert(trigger) => &trigger.token,
+        }
+    }
+
+    /// Whether a target's filters select the event
+    fn matches(&self, target: &WebhookTargetConfig) -> bool {
+        let interval_matches = match self {
+            WebhookEvent::KlineClosed(kline) => {
+                target.intervals.is_empty()
+                    || target.intervals.iter().any(|interval| *interval == kline.interval.as_str())
+            }
+            WebhookEvent::Alert(_) => true,
+        };
+        (target.events.is_empty() || target.events.contains(&self.event_type()))
+            && (target.tokens.is_empty() || target.tokens.iter().any(|token| token == self.token()))
+            && interval_matches
+    }
+}
+
+/// An event waiting to be POSTed to a URL
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub url: String,
+    pub event: WebhookEvent,
+}
+
+/// Queues events for the configured targets whose filters select them
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    targets: Vec<WebhookTargetConfig>,
+    sender: mpsc::UnboundedSender<WebhookDelivery>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(targets: Vec<WebhookTargetConfig>, sender: mpsc::UnboundedSender<WebhookDelivery>) -> Self {
+        Self { targets, sender }
+    }
+
+    /// Queue an event for every matching target
+    pub fn dispatch(&self, event: &WebhookEvent) {
+        for target in self.targets.iter().filter(|target| event.matches(target)) {
+            // Sending only fails once the delivery task has stopped
+            let _ = self.sender.send(WebhookDelivery {
+                url: target.url.clone(),
+                event: event.clone(),
+            });
+        }
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of a payload, as sent in the signature header
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    let hex: String = tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", hex)
+}
+
+/// POST queued events until the senders are dropped
+///
+/// Each delivery runs in its own task and is retried with exponential backoff,
+/// so a slow target does not hold up the others. Must run on the actix
+/// runtime, as the HTTP client is not `Send`.
+pub async fn deliver_webhooks(mut receiver: mpsc::UnboundedReceiver<WebhookDelivery>, config: WebhooksConfig) {
+    let client = awc::Client::builder()
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .finish();
+
+    while let Some(delivery) = receiver.recv().await {
+        let body = match serde_json::to_vec(&delivery.event) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("Failed to encode webhook payload: {}", e);
+                continue;
+            }
+        };
+        let signature = config.secret.as_deref().map(|secret| sign(secret, &body));
+        let client = client.clone();
+        let (max_retries, initial_backoff_ms) = (config.max_retries, config.initial_backoff_ms);
+
+        actix_web::rt::spawn(async move {
+            let event_name = delivery.event.event_type().as_str();
+            let mut backoff = Duration::from_millis(initial_backoff_ms);
+
+            for attempt in 0..=max_retries {
+                let mut request = client
+                    .post(&delivery.url)
+                    .content_type("application/json")
+                    .insert_header((EVENT_HEADER, event_name));
+                if let Some(signature) = &signature {
+                    request = request.insert_header((SIGNATURE_HEADER, signature.as_str()));
+                }
+
+                let error = match request.send_body(body.clone()).await {
+                    Ok(response) if response.status().is_success() => return,
+                    Ok(response) => format!("status {}", response.status()),
+                    Err(e) => e.to_string(),
+                };
+                if attempt == max_retries {
+                    tracing::warn!(
+                        "Giving up on webhook {} ({}) after {} attempts: {}",
+                        delivery.url,
+                        event_name,
+                        attempt + 1,
+                        error
+                    );
+                    return;
+                }
+                tracing::debug!("Webhook {} failed ({}), retrying in {:?}", delivery.url, error, backoff);
+                actix_web::rt::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        });
+    }
+}
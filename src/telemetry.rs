@@ -0,0 +1,115 @@
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::runtime::TokioCurrentThread;
+use opentelemetry_sdk::trace::{BatchConfigBuilder, BatchSpanProcessor, Sampler, TracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::api::WsManager;
+use crate::config::TelemetryConfig;
+use crate::services::Metrics;
+
+/// Name of the instrumentation scope of every span and metric
+const SCOPE: &str = "k-line";
+
+/// Time an export may take, so an unreachable collector cannot hold up shutdown
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// OTLP export of spans and metrics
+///
+/// Spans come from the `tracing` spans of the service, so they follow the
+/// logging level. Exports run on a thread of their own; call
+/// [`Telemetry::shutdown`] to flush what is buffered before exiting.
+pub struct Telemetry {
+    tracer_provider: TracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    /// Start the exporters, or return `None` when telemetry is disabled
+    pub fn init(config: &TelemetryConfig) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let resource = Resource::new([KeyValue::new("service.name", config.service_name.clone())]);
+
+        let span_exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .with_timeout(EXPORT_TIMEOUT)
+            .build()?;
+        let span_processor = BatchSpanProcessor::builder(span_exporter, TokioCurrentThread)
+            .with_batch_config(BatchConfigBuilder::default().with_max_export_timeout(EXPORT_TIMEOUT).build())
+            .build();
+        let tracer_provider = TracerProvider::builder()
+            .with_span_processor(span_processor)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+            .with_resource(resource.clone())
+            .build();
+
+        let metric_exporter = MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .with_timeout(EXPORT_TIMEOUT)
+            .build()?;
+        let reader = PeriodicReader::builder(metric_exporter, TokioCurrentThread)
+            .with_interval(Duration::from_secs(config.metrics_interval_secs))
+            .with_timeout(EXPORT_TIMEOUT)
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(reader)
+            .with_resource(resource)
+            .build();
+
+        Ok(Some(Self {
+            tracer_provider,
+            meter_provider,
+        }))
+    }
+
+    /// Layer exporting `tracing` spans as OpenTelemetry spans
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.tracer_provider.tracer(SCOPE))
+    }
+
+    /// Export the service counters and WebSocket load at every metric interval
+    pub fn observe(&self, metrics: Arc<Metrics>, ws_manager: Arc<WsManager>) {
+        let meter = self.meter_provider.meter(SCOPE);
+        meter
+            .u64_observable_counter("kline.transactions.ingested")
+            .with_description("Transactions applied to the candles")
+            .with_callback(move |observer| observer.observe(metrics.transactions_ingested(), &[]))
+            .build();
+        let sessions = ws_manager.clone();
+        meter
+            .u64_observable_gauge("kline.websocket.sessions")
+            .with_description("Connected WebSocket sessions")
+            .with_callback(move |observer| observer.observe(sessions.session_count() as u64, &[]))
+            .build();
+        meter
+            .u64_observable_gauge("kline.websocket.subscriptions")
+            .with_description("Subscriptions across WebSocket sessions")
+            .with_callback(move |observer| observer.observe(ws_manager.subscription_count() as u64, &[]))
+            .build();
+    }
+
+    /// Export buffered spans and metrics and stop the exporters
+    pub fn shutdown(&self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            tracing::warn!("Failed to flush spans: {}", e);
+        }
+        if let Err(e) = self.meter_provider.shutdown() {
+            tracing::warn!("Failed to flush metrics: {}", e);
+        }
+    }
+}
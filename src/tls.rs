@@ -0,0 +1,37 @@
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::config::TlsConfig;
+
+/// Build the rustls server configuration from PEM files
+pub fn load_server_config(tls: &TlsConfig) -> Result<rustls::ServerConfig, Box<dyn std::error::Error>> {
+    let (Some(cert_path), Some(key_path)) = (&tls.cert_path, &tls.key_path) else {
+        return Err("TLS requires a certificate and a key path".into());
+    };
+
+    let certs = load_certs(cert_path)?;
+    let key = load_private_key(key_path)?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(config)
+}
+
+/// Read every certificate in a PEM file
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("Failed to open certificate {}: {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path).into());
+    }
+    Ok(certs)
+}
+
+/// Read the first private key in a PEM file
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, Box<dyn std::error::Error>> {
+    let file = File::open(path).map_err(|e| format!("Failed to open private key {}: {}", path, e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))?
+        .ok_or_else(|| format!("No private key found in {}", path).into())
+}
@@ -0,0 +1,85 @@
+use ts_rs::TS;
+
+use crate::api::websocket::{
+    ClientMessage, ClientRequest, ErrorCode, RejectedSubscription, RequestId, ServerMessage, SubscriptionType,
+    SystemState, SystemStatus, WireFormat,
+};
+use crate::config::ApiKeyScope;
+use crate::models::ticker::PriceExtremes;
+use crate::models::{KLine, Side, Ticker, TimeInterval, TradeId, Transaction};
+use crate::services::agg_trades::AggTrade;
+use crate::services::alerts::{AlertCondition, AlertTrigger};
+use crate::services::bars::{Bar, BarType};
+use crate::services::candle_transform::CandleType;
+use crate::services::config_reload::ConfigUpdate;
+use crate::services::delta::VolumeDelta;
+use crate::services::indicator_stream::IndicatorKind;
+use crate::services::indicators::IndicatorUpdate;
+use crate::services::metrics::OpsMetrics;
+use crate::services::order_book::{DepthSnapshot, DepthUpdate, PriceLevel};
+use crate::services::token_registry::{ListingEvent, NewTokenEvent, TokenAction};
+
+/// Where the declarations are kept, relative to the crate root
+pub const DEFINITIONS_PATH: &str = "bindings/k-line.ts";
+
+/// A server message as sent; messages on resumable topics carry the topic and a sequence number,
+/// and replies the ID of the client message they answer
+const ENVELOPE: &str = "export type Envelope = ServerMessage & { topic?: string, seq?: number, ref?: RequestId, };\n";
+
+/// TypeScript declarations of the WebSocket messages and the models they carry
+pub fn definitions() -> String {
+    let declarations = [
+        declaration::<ClientRequest>(),
+        declaration::<ClientMessage>(),
+        declaration::<RequestId>(),
+        declaration::<SubscriptionType>(),
+        declaration::<WireFormat>(),
+        declaration::<ServerMessage>(),
+        declaration::<RejectedSubscription>(),
+        declaration::<ErrorCode>(),
+        declaration::<SystemStatus>(),
+        declaration::<SystemState>(),
+        declaration::<ApiKeyScope>(),
+        declaration::<KLine>(),
+        declaration::<TimeInterval>(),
+        declaration::<Transaction>(),
+        declaration::<Side>(),
+        declaration::<TradeId>(),
+        declaration::<AggTrade>(),
+        declaration::<DepthSnapshot>(),
+        declaration::<DepthUpdate>(),
+        declaration::<PriceLevel>(),
+        declaration::<OpsMetrics>(),
+        declaration::<NewTokenEvent>(),
+        declaration::<TokenAction>(),
+        declaration::<ListingEvent>(),
+        declaration::<AlertTrigger>(),
+        declaration::<AlertCondition>(),
+        declaration::<IndicatorUpdate>(),
+        declaration::<IndicatorKind>(),
+        declaration::<Bar>(),
+        declaration::<BarType>(),
+        declaration::<CandleType>(),
+        declaration::<VolumeDelta>(),
+        declaration::<Ticker>(),
+        declaration::<PriceExtremes>(),
+        declaration::<ConfigUpdate>(),
+    ];
+
+    let mut definitions = String::from("// Generated by `cargo run --features typescript --bin typegen`. Do not edit.\n");
+    for declaration in declarations {
+        definitions.push('\n');
+        definitions.push_str(&declaration);
+    }
+    definitions.push('\n');
+    definitions.push_str(ENVELOPE);
+    definitions
+}
+
+/// Exported declaration of a type, with its doc comment
+///
+/// ts-rs declares 64-bit integers as `bigint`, but `JSON.parse` reads them as numbers.
+fn declaration<T: TS>() -> String {
+    let docs = T::docs().unwrap_or_default();
+    format!("{}export {}\n", docs, T::decl()).replace("bigint", "number")
+}
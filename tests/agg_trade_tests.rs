@@ -0,0 +1,61 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use k_line::services::agg_trades::AggTradeBuilder;
+use k_line::{Side, Transaction};
+
+fn trade(price: f64, volume: f64, side: Side, at: DateTime<Utc>) -> Transaction {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, volume, side);
+    transaction.timestamp = at;
+    transaction
+}
+
+#[test]
+fn test_same_price_trades_are_combined() {
+    let start = Utc::now();
+    let mut builder = AggTradeBuilder::new(TimeDelta::milliseconds(100));
+
+    assert!(builder.push(&trade(1.0, 10.0, Side::Buy, start)).is_none());
+    assert!(builder.push(&trade(1.0, 5.0, Side::Buy, start + TimeDelta::milliseconds(20))).is_none());
+    assert!(builder.push(&trade(1.0, 2.5, Side::Buy, start + TimeDelta::milliseconds(40))).is_none());
+
+    // A different price completes the aggregate
+    let agg_trade = builder.push(&trade(1.1, 1.0, Side::Buy, start + TimeDelta::milliseconds(50))).unwrap();
+    assert_eq!(agg_trade.token, "DOGE");
+    assert_eq!(agg_trade.price, 1.0);
+    assert_eq!(agg_trade.volume, 17.5);
+    assert_eq!(agg_trade.trades, 3);
+    assert_eq!(agg_trade.first_trade_at, start);
+    assert_eq!(agg_trade.last_trade_at, start + TimeDelta::milliseconds(40));
+    assert_eq!(agg_trade.side, Side::Buy);
+}
+
+#[test]
+fn test_side_and_window_split_aggregates() {
+    let start = Utc::now();
+    let mut builder = AggTradeBuilder::new(TimeDelta::milliseconds(100));
+
+    builder.push(&trade(1.0, 10.0, Side::Buy, start));
+    let agg_trade = builder.push(&trade(1.0, 10.0, Side::Sell, start)).unwrap();
+    assert_eq!(agg_trade.side, Side::Buy);
+    assert_eq!(agg_trade.trades, 1);
+
+    // A trade past the window starts a new aggregate at the same price
+    let agg_trade = builder.push(&trade(1.0, 10.0, Side::Sell, start + TimeDelta::milliseconds(100))).unwrap();
+    assert_eq!(agg_trade.side, Side::Sell);
+    assert_eq!(agg_trade.first_trade_at, start);
+}
+
+#[test]
+fn test_flush_expired() {
+    let start = Utc::now();
+    let mut builder = AggTradeBuilder::new(TimeDelta::milliseconds(100));
+    assert!(builder.flush_expired(start).is_none());
+
+    builder.push(&trade(1.0, 10.0, Side::Buy, start));
+    assert!(builder.flush_expired(start + TimeDelta::milliseconds(99)).is_none());
+    let agg_trade = builder.flush_expired(start + TimeDelta::milliseconds(100)).unwrap();
+    assert_eq!(agg_trade.volume, 10.0);
+    assert!(builder.flush_expired(start + TimeDelta::milliseconds(200)).is_none());
+
+    // The next trade starts afresh instead of completing a flushed aggregate
+    assert!(builder.push(&trade(1.0, 1.0, Side::Buy, start + TimeDelta::milliseconds(300))).is_none());
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "server")]
+
+use actix_web::{web, App, HttpResponse};
+use chrono::{Duration, Utc};
+use tokio::sync::mpsc;
+
+use k_line::config::WebhooksConfig;
+use k_line::services::alerts::{AlertCondition, NewAlert};
+use k_line::services::webhooks::deliver_webhooks;
+use k_line::services::AlertService;
+use k_line::{Side, Transaction};
+
+fn trade(token: &str, price: f64) -> Transaction {
+    Transaction::new(token.to_string(), price, 10.0, Side::Buy)
+}
+
+fn new_alert(token: &str, condition: AlertCondition) -> NewAlert {
+    NewAlert {
+        token: token.to_string(),
+        condition,
+        webhook_url: None,
+    }
+}
+
+#[test]
+fn test_price_alerts_trigger_once() {
+    let service = AlertService::new();
+    let above = service.create(new_alert("DOGE", AlertCondition::Above { price: 0.2 })).unwrap();
+    let below = service.create(new_alert("DOGE", AlertCondition::Below { price: 0.1 })).unwrap();
+
+    assert!(service.evaluate(&trade("DOGE", 0.15)).is_empty());
+    // Other tokens never trigger DOGE alerts
+    assert!(service.evaluate(&trade("SHIB", 0.25)).is_empty());
+
+    let triggers = service.evaluate(&trade("DOGE", 0.2));
+    assert_eq!(triggers.len(), 1);
+    assert_eq!(triggers[0].alert_id, above.id);
+    assert_eq!(triggers[0].price, 0.2);
+    assert!(service.get(&above.id).unwrap().triggered_at.is_some());
+
+    // A triggered alert does not fire again
+    assert!(service.evaluate(&trade("DOGE", 0.3)).is_empty());
+
+    let triggers = service.evaluate(&trade("DOGE", 0.05));
+    assert_eq!(triggers.len(), 1);
+    assert_eq!(triggers[0].alert_id, below.id);
+
+    assert_eq!(service.list(Some("DOGE")).len(), 2);
+    assert!(service.list(Some("SHIB")).is_empty());
+    assert!(service.delete(&above.id).is_some());
+    assert!(service.get(&above.id).is_none());
+}
+
+#[test]
+fn test_percent_move_alert_within_window() {
+    let service = AlertService::new();
+    let alert = service
+        .create(new_alert("DOGE", AlertCondition::PercentMove { percent: 10.0, window_secs: 60 }))
+        .unwrap();
+
+    let start = Utc::now();
+    let at = |price: f64, seconds: i64| {
+        let mut transaction = trade("DOGE", price);
+        transaction.timestamp = start + Duration::seconds(seconds);
+        service.evaluate(&transaction)
+    };
+
+    assert!(at(1.0, 0).is_empty());
+    assert!(at(1.05, 30).is_empty());
+    // 10% above the 1.0 low, but the low has left the window
+    assert!(at(1.1, 90).is_empty());
+    // 10% below the 1.1 high
+    let triggers = at(0.99, 100);
+    assert_eq!(triggers.len(), 1);
+    assert_eq!(triggers[0].alert_id, alert.id);
+}
+
+#[test]
+fn test_invalid_alerts_are_rejected() {
+    let service = AlertService::new();
+
+    assert!(service.create(new_alert("", AlertCondition::Above { price: 1.0 })).is_err());
+    assert!(service.create(new_alert("DOGE", AlertCondition::Below { price: -1.0 })).is_err());
+    assert!(service
+        .create(new_alert("DOGE", AlertCondition::PercentMove { percent: 5.0, window_secs: 0 }))
+        .is_err());
+    let mut request = new_alert("DOGE", AlertCondition::Above { price: 1.0 });
+    request.webhook_url = Some("ftp://example.com".to_string());
+    assert!(service.create(request).is_err());
+
+    assert!(service.list(None).is_empty());
+}
+
+#[actix_rt::test]
+async fn test_alert_webhook_delivery() {
+    let (received_sender, mut received) = mpsc::unbounded_channel::<serde_json::Value>();
+    let server = actix_test::start(move || {
+        let received_sender = received_sender.clone();
+        App::new().route(
+            "/hook",
+            web::post().to(move |payload: web::Json<serde_json::Value>| {
+                let _ = received_sender.send(payload.into_inner());
+                async { HttpResponse::Ok().finish() }
+            }),
+        )
+    });
+
+    let (webhook_sender, webhook_receiver) = mpsc::unbounded_channel();
+    actix_web::rt::spawn(deliver_webhooks(webhook_receiver, WebhooksConfig::default()));
+    let service = AlertService::new().with_webhook_sender(webhook_sender);
+
+    let mut request = new_alert("DOGE", AlertCondition::Above { price: 0.2 });
+    request.webhook_url = Some(server.url("/hook"));
+    let alert = service.create(request).unwrap();
+    service.create(new_alert("DOGE", AlertCondition::Above { price: 0.1 })).unwrap();
+
+    assert_eq!(service.evaluate(&trade("DOGE", 0.25)).len(), 2);
+
+    // Only the alert with a webhook is posted
+    let payload = tokio::time::timeout(std::time::Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(payload["event"], "alert");
+    assert_eq!(payload["data"]["alert_id"], alert.id.to_string());
+    assert_eq!(payload["data"]["price"], 0.25);
+    assert!(received.try_recv().is_err());
+}
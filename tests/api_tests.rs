@@ -1,6 +1,24 @@
+#![cfg(feature = "server")]
+
+use actix_web::body::MessageBody;
 use actix_web::{test, web, App};
+use chrono::{DateTime, Duration, DurationRound, TimeDelta, TimeZone, Utc};
+use std::future::poll_fn;
+use std::pin::Pin;
 use std::sync::Arc;
-use k_line::{KLineService, MockDataGenerator, configure_routes};
+use k_line::api::auth::{Authenticator, API_KEY_HEADER};
+use k_line::api::cors::cors;
+use k_line::api::health::{self, CheckStatus};
+use k_line::api::rate_limit::RateLimiter;
+use k_line::api::request_id::{MAX_REQUEST_ID_LEN, REQUEST_ID_HEADER};
+use k_line::config::{ApiKeyScope, CorsConfig, MarketRegime, ReplicationRole, UnknownTokenPolicy};
+use k_line::services::indicators::IndicatorCache;
+use k_line::services::replication::CandleChecksum;
+use k_line::services::{
+    AlertService, KLineQueryCache, QuoteConverter, KLineSnapshot, Metrics, OrderBookSimulator, RegimeEngine, ReplicationState, SymbolTable, TokenRegistry, TradeService,
+    TransactionPipeline,
+};
+use k_line::{KLineService, MockDataGenerator, Side, TimeInterval, Transaction, WsManager, configure_routes};
 
 #[actix_web::test]
 async fn test_get_tokens_endpoint() {
@@ -62,6 +80,371 @@ async fn test_get_klines_endpoint() {
     assert!(body["data"].is_array());
 }
 
+#[actix_web::test]
+async fn test_get_klines_conditional_requests() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    let trade = |offset: i64, price: f64| {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::seconds(offset);
+        service.process_transaction(&transaction);
+    };
+    trade(0, 0.15);
+    trade(90, 0.16);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .configure(configure_routes)
+    ).await;
+    let get = |headers: Vec<(&'static str, String)>| {
+        let mut req = test::TestRequest::get().uri("/api/v1/klines?token=DOGE&interval=1m");
+        for header in headers {
+            req = req.insert_header(header);
+        }
+        req.to_request()
+    };
+
+    // The newest candle is open, so there is an ETag but no Last-Modified
+    let resp = test::call_service(&app, get(vec![])).await;
+    assert_eq!(resp.status(), 200);
+    let etag = resp.headers().get("etag").unwrap().to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/"));
+    assert!(resp.headers().get("last-modified").is_none());
+
+    let resp = test::call_service(&app, get(vec![("if-none-match", etag.clone())])).await;
+    assert_eq!(resp.status(), 304);
+    assert_eq!(resp.headers().get("etag").unwrap(), etag.as_str());
+
+    // A trade in the open candle changes the ETag
+    trade(100, 0.17);
+    let resp = test::call_service(&app, get(vec![("if-none-match", etag.clone())])).await;
+    assert_eq!(resp.status(), 200);
+    assert_ne!(resp.headers().get("etag").unwrap(), etag.as_str());
+
+    // Once closed, a historical range is also validated by date
+    let uri = format!(
+        "/api/v1/klines?token=DOGE&interval=1m&before={}",
+        (start + Duration::minutes(1)).timestamp_millis()
+    );
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+    let last_modified = resp.headers().get("last-modified").unwrap().to_str().unwrap().to_string();
+    let req = test::TestRequest::get().uri(&uri).insert_header(("if-modified-since", last_modified)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 304);
+
+    // Bodies are compressed for clients accepting it
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m")
+        .insert_header(("accept-encoding", "gzip"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("content-encoding").unwrap(), "gzip");
+}
+
+#[actix_web::test]
+async fn test_resample_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    for minute in 0..240 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.15, 1.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/klines/resample?token=DOGE&source=1m&target=2h&limit=1").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["source"], "1m");
+    assert_eq!(body["target"], "2h");
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["timestamp"], "2024-01-15T16:00:00Z");
+    assert_eq!(data[0]["volume"], 120.0);
+
+    for uri in [
+        "/api/v1/klines/resample?token=DOGE",
+        "/api/v1/klines/resample?token=DOGE&target=2x",
+        "/api/v1/klines/resample?token=DOGE&source=5m&target=7m",
+        "/api/v1/klines/resample?token=DOGE&source=100t&target=2h",
+    ] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_pair_symbols() {
+    let service = Arc::new(KLineService::new());
+    let pipeline = TransactionPipeline::new(service.clone(), Arc::new(WsManager::new()));
+    pipeline.handle(&Transaction::new("doge/usdt".to_string(), 0.15, 100.0, Side::Buy)).unwrap();
+    pipeline.handle(&Transaction::new("DOGE-USD".to_string(), 0.16, 100.0, Side::Buy)).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    // Pairs are canonical however they are written
+    for token in ["DOGE-USDT", "doge-usdt", "DOGE%2FUSDT"] {
+        let req = test::TestRequest::get().uri(&format!("/api/v1/klines?token={}", token)).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["token"], "DOGE-USDT");
+        assert_eq!(body["data"][0]["close"], 0.15);
+    }
+
+    // Plain tokens are quoted in USD
+    let req = test::TestRequest::get().uri("/api/v1/klines?token=DOGE").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"][0]["close"], 0.16);
+    let mut tokens = service.get_available_tokens();
+    tokens.sort();
+    assert_eq!(tokens, vec!["DOGE", "DOGE-USDT"]);
+}
+
+#[actix_web::test]
+async fn test_quote_conversion() {
+    let service = Arc::new(KLineService::new());
+    service.process_transaction(&Transaction::new("DOGE-USDT".to_string(), 0.2, 100.0, Side::Buy));
+    let converter = Arc::new(QuoteConverter::new().with_rate("USDT", "EUR", 0.5));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .app_data(web::Data::new(converter))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/klines?token=DOGE-USDT&quote=eur").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["token"], "DOGE-EUR");
+    assert_eq!(body["data"][0]["close"], 0.1);
+    assert_eq!(body["data"][0]["volume"], 100.0);
+
+    let req = test::TestRequest::get().uri("/api/v1/ticker?token=DOGE-USDT&quote=EUR").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["token"], "DOGE-EUR");
+    assert_eq!(body["last_price"], 0.1);
+    let req = test::TestRequest::get().uri("/api/v1/ticker?token=DOGE-USDT").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["last_price"], 0.2);
+
+    let req = test::TestRequest::get().uri("/api/v1/klines?token=DOGE-USDT&quote=BTC").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    let req = test::TestRequest::get().uri("/api/v1/ticker?token=SHIB").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_ticker_extremes() {
+    let service = Arc::new(KLineService::new());
+    let now = Utc::now();
+    for (minutes_ago, price) in [(3 * 24 * 60, 0.4), (120, 0.1), (10, 0.2), (5, 0.15)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy);
+        transaction.timestamp = now - TimeDelta::minutes(minutes_ago);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/ticker?token=DOGE").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let extremes = &body["extremes"];
+    assert_eq!((extremes["all_time_high"].as_f64(), extremes["all_time_low"].as_f64()), (Some(0.4), Some(0.1)));
+    assert_eq!((extremes["high_1h"].as_f64(), extremes["low_1h"].as_f64()), (Some(0.2), Some(0.15)));
+    assert_eq!((extremes["high_24h"].as_f64(), extremes["low_24h"].as_f64()), (Some(0.2), Some(0.1)));
+    assert_eq!(extremes["high_7d"].as_f64(), Some(0.4));
+}
+
+#[actix_web::test]
+async fn test_ohlc_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    for (offset, price) in [(0, 0.15), (400, 0.18), (3700, 0.12), (4000, 0.14)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::seconds(offset);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    // Up to, not including, the last trade
+    let uri = format!(
+        "/api/v1/ohlc?token=DOGE&start={}&end={}",
+        start.timestamp_millis(),
+        (start + Duration::seconds(4000)).timestamp_millis()
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(body["open"], 0.15);
+    assert_eq!(body["high"], 0.18);
+    assert_eq!(body["low"], 0.12);
+    assert_eq!(body["close"], 0.12);
+    assert_eq!(body["volume"], 300.0);
+
+    // Since listing by default
+    let req = test::TestRequest::get().uri("/api/v1/ohlc?token=DOGE").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["close"], 0.14);
+    assert_eq!(body["start"], "2024-01-15T14:00:00Z");
+
+    let req = test::TestRequest::get().uri("/api/v1/ohlc?token=DOGE&start=2000&end=1000").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    let req = test::TestRequest::get().uri("/api/v1/ohlc?token=SHIB").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_get_klines_cursor_pagination() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+    // Five 1m candles, older than the default 24 hour window
+    for minute in 0..5 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 1.0, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    // The oldest-first window only covers the last 24 hours
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m&order=asc")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"], serde_json::json!([]));
+    assert!(body["next_cursor"].is_null());
+
+    let end = (start + Duration::hours(1)).timestamp_millis();
+    let mut uri = format!("/api/v1/klines?token=DOGE&interval=1m&limit=2&before={}", end);
+    let mut timestamps = Vec::new();
+    loop {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        timestamps.extend(
+            body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|kline| kline["timestamp"].as_str().unwrap().to_string()),
+        );
+        match body["next_cursor"].as_i64() {
+            Some(cursor) => {
+                uri = format!("/api/v1/klines?token=DOGE&interval=1m&limit=2&before={}", cursor)
+            }
+            None => break,
+        }
+    }
+    // Pages come newest first, and so do the candles in each page
+    assert_eq!(timestamps.len(), 5);
+    assert!(timestamps.windows(2).all(|pair| pair[0] > pair[1]));
+
+    for uri in [
+        "/api/v1/klines?token=DOGE&interval=1m&order=newest",
+        "/api/v1/klines?token=DOGE&interval=1m&before=yesterday",
+        "/api/v1/klines?token=DOGE&interval=1m&before=1&after=0",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_get_klines_limit_takes_newest() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    for minute in 0..10 {
+        let mut transaction = Transaction::new("DOGE".to_string(), minute as f64 + 1.0, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let opens = |body: serde_json::Value| -> Vec<f64> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|kline| kline["open"].as_f64().unwrap())
+            .collect()
+    };
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m&limit=3")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(opens(body), vec![10.0, 9.0, 8.0]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m&limit=3&order=asc")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(opens(body), vec![1.0, 2.0, 3.0]);
+}
+
+#[actix_web::test]
+async fn test_get_klines_batch_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let generator = MockDataGenerator::new();
+
+    for token in ["DOGE", "SHIB"] {
+        for _ in 0..10 {
+            if let Some(transaction) = generator.generate_transaction(token) {
+                service.process_transaction(&transaction);
+            }
+        }
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines/batch?tokens=DOGE,%20SHIB,PEPE&interval=1m&limit=50")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["interval"], "1m");
+    assert_eq!(body["data"]["DOGE"][0]["token"], "DOGE");
+    assert_eq!(body["data"]["SHIB"][0]["token"], "SHIB");
+    assert_eq!(body["data"]["PEPE"], serde_json::json!([]));
+
+    for uri in [
+        "/api/v1/klines/batch?interval=1m",
+        "/api/v1/klines/batch?tokens=,&interval=1m",
+        "/api/v1/klines/batch?tokens=DOGE&interval=2m",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
 #[actix_web::test]
 async fn test_get_latest_kline_endpoint() {
     let service = Arc::new(KLineService::new());
@@ -148,4 +531,1493 @@ async fn test_invalid_interval() {
 
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert!(body["error"].is_string());
-} 
\ No newline at end of file
+}
+
+#[actix_web::test]
+async fn test_info_and_promote_endpoints() {
+    let service = Arc::new(KLineService::new());
+    let replication = Arc::new(ReplicationState::new(ReplicationRole::Standby));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .app_data(web::Data::new(replication))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/info").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("X-Replication-Role").unwrap(), "standby");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["role"], "standby");
+
+    let req = test::TestRequest::post().uri("/api/v1/admin/promote").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["promoted"], true);
+
+    let req = test::TestRequest::post().uri("/api/v1/admin/promote").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["promoted"], false);
+
+    let req = test::TestRequest::get().uri("/api/v1/info").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["role"], "primary");
+}
+
+#[actix_web::test]
+async fn test_snapshot_endpoint() {
+    let service = Arc::new(KLineService::new());
+    service.process_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+    let path = std::env::temp_dir().join(format!("k-line-snapshot-{}.json", uuid::Uuid::new_v4()));
+
+    let app = |config: k_line::config::Config| {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(service.clone()))
+                .app_data(web::Data::new(config))
+                .configure(configure_routes),
+        )
+    };
+
+    let app_without_path = app(k_line::config::Config::default()).await;
+    let req = test::TestRequest::post().uri("/api/v1/admin/snapshot").to_request();
+    let resp = test::call_service(&app_without_path, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let mut config = k_line::config::Config::default();
+    config.snapshot.path = Some(path.to_string_lossy().into_owned());
+    let app = app(config).await;
+    let req = test::TestRequest::post().uri("/api/v1/admin/snapshot").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["tokens"], 1);
+    assert_eq!(body["klines"], TimeInterval::all().len());
+
+    let restored = KLineService::new();
+    restored.restore(KLineSnapshot::load(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(restored.get_current_kline("DOGE", TimeInterval::Minute1).unwrap().close, 0.15);
+}
+
+#[actix_web::test]
+async fn test_stats_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let metrics = Arc::new(Metrics::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+        metrics.record_ingest();
+    }
+    service.add_token("BONK");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .app_data(web::Data::new(metrics))
+            .app_data(web::Data::new(Arc::new(WsManager::new())))
+            .app_data(web::Data::new(k_line::config::Config::default()))
+            .app_data(web::Data::new(Arc::new(KLineQueryCache::default())))
+            .configure(configure_routes)
+    ).await;
+
+    // The second query is answered from the cache
+    for _ in 0..2 {
+        let req = test::TestRequest::get().uri("/api/v1/klines?token=DOGE&interval=1m").to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let req = test::TestRequest::get().uri("/api/v1/stats").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["statistics"]["total_tokens"], 2);
+    assert_eq!(body["query_cache"]["hits"], 1);
+    assert_eq!(body["query_cache"]["misses"], 1);
+    assert_eq!(body["query_cache"]["hit_rate"], 0.5);
+
+    // BONK has no candles, so only DOGE's series are listed, in interval order
+    let series = body["storage"]["series"].as_array().unwrap();
+    assert_eq!(series.len(), TimeInterval::all().len());
+    assert_eq!(series[1]["token"], "DOGE");
+    assert_eq!(series[1]["interval"], "1m");
+    assert_eq!(series[1]["candles"], 3);
+    assert_eq!(series[1]["oldest"], "2024-01-15T14:00:00Z");
+    assert_eq!(series[1]["newest"], "2024-01-15T14:02:00Z");
+    assert_eq!(series[4]["candles"], 1);
+    let total: u64 = series.iter().map(|series| series["candles"].as_u64().unwrap()).sum();
+    assert_eq!(body["storage"]["total_candles"], total);
+    assert!(body["storage"]["estimated_memory_bytes"].as_u64().unwrap() > 0);
+
+    assert_eq!(body["generator"]["enabled"], true);
+    assert_eq!(body["generator"]["data_source"], "mock");
+    assert_eq!(body["generator"]["transactions_ingested"], 3);
+    assert!(body["generator"]["last_transaction_at"].is_string());
+    assert_eq!(body["websocket"]["sessions"], 0);
+    assert_eq!(body["websocket"]["subscriptions"], 0);
+}
+
+#[actix_web::test]
+async fn test_liveness_and_readiness_endpoints() {
+    let metrics = Arc::new(Metrics::new());
+    let mut config = k_line::config::Config::default();
+    config.performance.max_websocket_connections = 1;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(metrics.clone()))
+            .app_data(web::Data::new(Arc::new(WsManager::new())))
+            .app_data(web::Data::new(config))
+            .configure(configure_routes)
+    ).await;
+
+    for uri in ["/healthz", "/api/v1/health"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], "healthy");
+    }
+
+    // Nothing has been ingested yet
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 503);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["checks"]["ingestion"]["status"], "failing");
+    assert_eq!(body["checks"]["storage"]["status"], "ok");
+    assert_eq!(body["checks"]["websocket"]["status"], "ok");
+
+    metrics.record_ingest();
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["checks"]["ingestion"]["status"], "ok");
+}
+
+#[actix_web::test]
+async fn test_ingestion_check() {
+    let now = Utc::now();
+    let check = |expected, last_ingest_at| health::check_ingestion(expected, last_ingest_at, 30, now).status;
+    assert_eq!(check(false, None), CheckStatus::Skipped);
+    assert_eq!(check(true, None), CheckStatus::Failing);
+    assert_eq!(check(true, Some(now - Duration::seconds(10))), CheckStatus::Ok);
+    assert_eq!(check(true, Some(now - Duration::seconds(31))), CheckStatus::Failing);
+}
+
+#[actix_web::test]
+async fn test_request_ids() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .configure(configure_routes)
+    ).await;
+
+    // A generated ID on every response
+    let req = test::TestRequest::get().uri("/api/v1/tokens").to_request();
+    let resp = test::call_service(&app, req).await;
+    let generated = resp.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(generated).is_ok());
+
+    // The caller's ID is propagated, also on failed requests
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?interval=7m")
+        .insert_header((REQUEST_ID_HEADER, "gateway-42"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "gateway-42");
+
+    // IDs that would pollute logs are replaced
+    for invalid in ["has space".to_string(), "x".repeat(MAX_REQUEST_ID_LEN + 1)] {
+        let req = test::TestRequest::get()
+            .uri("/api/v1/tokens")
+            .insert_header((REQUEST_ID_HEADER, invalid.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+    }
+}
+
+#[actix_web::test]
+async fn test_cors() {
+    let config = CorsConfig {
+        allowed_origins: vec!["https://app.example.com".to_string()],
+        ..CorsConfig::default()
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .wrap(cors(&config))
+            .configure(configure_routes)
+    ).await;
+
+    // Preflight from the allowed origin
+    let req = test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/v1/alerts")
+        .insert_header(("origin", "https://app.example.com"))
+        .insert_header(("access-control-request-method", "POST"))
+        .insert_header(("access-control-request-headers", "content-type"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("access-control-allow-origin").unwrap(), "https://app.example.com");
+    assert_eq!(resp.headers().get("access-control-max-age").unwrap(), "3600");
+
+    // Simple requests get the headers, exposing the request ID
+    let req = test::TestRequest::get()
+        .uri("/api/v1/tokens")
+        .insert_header(("origin", "https://app.example.com"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("access-control-allow-origin").unwrap(), "https://app.example.com");
+    let exposed = resp.headers().get("access-control-expose-headers").unwrap().to_str().unwrap();
+    assert!(exposed.contains("x-request-id"));
+
+    // Other origins are served without them, so browsers refuse the response
+    let req = test::TestRequest::get()
+        .uri("/api/v1/tokens")
+        .insert_header(("origin", "https://evil.example.com"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get("access-control-allow-origin").is_none());
+}
+
+#[actix_web::test]
+async fn test_replication_checksums_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+
+    // Two trades an hour apart close the first candle of every interval
+    for (offset, price) in [(0, 0.15), (3600, 0.16)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::seconds(offset);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/replication/checksums")
+        .to_request();
+    let body: Vec<CandleChecksum> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body.len(), 5);
+    assert!(body.iter().all(|checksum| checksum.count == 1));
+
+    let (_, expected) = service.checksum("DOGE", TimeInterval::Hour1, start);
+    let hour = body.iter().find(|c| c.interval == TimeInterval::Hour1).unwrap();
+    assert_eq!(hour.checksum, expected);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/replication/checksums?since=yesterday")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_sse_stream_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let ws_manager = Arc::new(WsManager::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .app_data(web::Data::new(ws_manager.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/stream?token=DOGE&interval=1m")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/event-stream");
+
+    // Only the DOGE trade matches the stream filter
+    ws_manager.broadcast_transaction(&Transaction::new("SHIB".to_string(), 0.00005, 10.0, Side::Sell));
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    let mut body = resp.into_body();
+    let chunk = poll_fn(|cx| Pin::new(&mut body).poll_next(cx))
+        .await
+        .unwrap()
+        .unwrap();
+    let frame = std::str::from_utf8(&chunk).unwrap();
+    assert!(frame.starts_with("event: trade\ndata: "));
+    assert!(frame.contains("\"token\":\"DOGE\""));
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/stream?token=DOGE&interval=2m")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_token_registry_endpoints() {
+    let registry = Arc::new(TokenRegistry::new(
+        UnknownTokenPolicy::Quarantine,
+        vec!["DOGE".to_string()],
+    ));
+    registry.admit_transaction(&Transaction::new("PEPE".to_string(), 0.1, 10.0, Side::Buy));
+    let kline_service = Arc::new(KLineService::new());
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new()))
+        .with_token_registry(registry.clone());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(registry.clone()))
+            .app_data(web::Data::new(pipeline))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/admin/tokens").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["policy"], "quarantine");
+    assert_eq!(body["registered"], serde_json::json!(["DOGE"]));
+    assert_eq!(body["unregistered"][0]["token"], "PEPE");
+    assert_eq!(body["unregistered"][0]["status"], "quarantined");
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/tokens/PEPE/register")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["registered"], true);
+    assert_eq!(body["released"], 1);
+    assert!(registry.admit("PEPE").accepted);
+    // The quarantined trade started the token's series
+    assert!(kline_service.get_current_kline("PEPE", TimeInterval::Minute1).is_some());
+}
+
+#[actix_web::test]
+async fn test_market_admin_endpoints() {
+    let regimes = Arc::new(RegimeEngine::default());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(regimes.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/admin/market").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["enabled"], false);
+    assert_eq!(body["regime"], "sideways");
+
+    let req = test::TestRequest::put()
+        .uri("/api/v1/admin/market/regime")
+        .set_json(serde_json::json!({"regime": "crash"}))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["regime"], "crash");
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/market/events")
+        .set_json(serde_json::json!({
+            "token": "DOGE", "kind": "flash_crash", "magnitude": 0.4, "duration_secs": 30, "revert": true
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["kind"], "flash_crash");
+    assert_eq!(body["token"], "DOGE");
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/market/events")
+        .set_json(serde_json::json!({"kind": "flash_crash", "magnitude": 1.5, "duration_secs": 30}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let status = regimes.status(Utc::now());
+    assert_eq!(status.events.len(), 1);
+    assert_eq!(status.regime, MarketRegime::Crash);
+}
+
+#[actix_web::test]
+async fn test_amend_kline_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    for (offset, price, side) in [(0, 0.15, Side::Buy), (20, 0.18, Side::Sell), (70, 0.16, Side::Buy)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, side);
+        transaction.timestamp = start + Duration::seconds(offset);
+        service.process_transaction(&transaction);
+    }
+
+    let pipeline = TransactionPipeline::new(service.clone(), Arc::new(WsManager::new()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .app_data(web::Data::new(pipeline))
+            .configure(configure_routes)
+    ).await;
+
+    // The first minute is closed, and its buy and sell split is scaled to the new volume
+    let amendment = serde_json::json!({
+        "token": "doge", "interval": "1m", "timestamp": start,
+        "open": 0.15, "high": 0.17, "low": 0.14, "close": 0.17, "volume": 400.0
+    });
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&amendment).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((body["high"].as_f64(), body["low"].as_f64()), (Some(0.17), Some(0.14)));
+    assert_eq!((body["buy_volume"].as_f64(), body["sell_volume"].as_f64()), (Some(200.0), Some(200.0)));
+    assert_eq!(body["is_closed"], true);
+
+    let stored = service.get_klines("DOGE", TimeInterval::Minute1, start, start, None);
+    assert_eq!((stored[0].close, stored[0].volume), (0.17, 400.0));
+
+    let mut invalid = amendment.clone();
+    invalid["high"] = serde_json::json!(0.1);
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&invalid).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    // A split must be given whole and add up to the volume
+    let mut partial = amendment.clone();
+    partial["buy_volume"] = serde_json::json!(100.0);
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&partial).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    partial["sell_volume"] = serde_json::json!(100.0);
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&partial).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    partial["sell_volume"] = serde_json::json!(300.0);
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&partial).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((body["buy_volume"].as_f64(), body["sell_volume"].as_f64()), (Some(100.0), Some(300.0)));
+
+    let mut missing = amendment;
+    missing["timestamp"] = serde_json::json!(start - Duration::hours(1));
+    let req = test::TestRequest::patch().uri("/api/v1/admin/klines").set_json(&missing).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_verify_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap();
+    for (offset, price) in [(0, 0.15), (90, 0.18), (400, 0.16)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::seconds(offset);
+        service.process_transaction(&transaction);
+    }
+
+    let pipeline = TransactionPipeline::new(service.clone(), Arc::new(WsManager::new()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .app_data(web::Data::new(pipeline))
+            .configure(configure_routes)
+    ).await;
+
+    let range = format!("start={}&end={}", start.timestamp_millis(), (start + Duration::minutes(7)).timestamp_millis());
+    let req = test::TestRequest::get().uri(&format!("/api/v1/admin/verify?token=DOGE&{}", range)).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((body["interval"].as_str(), body["source_interval"].as_str()), (Some("5m"), Some("1m")));
+    assert_eq!(body["candles_checked"], 1);
+    assert_eq!(body["consistent"], true);
+
+    // A corrected minute no longer adds up to its 5m candle
+    let req = test::TestRequest::patch()
+        .uri("/api/v1/admin/klines")
+        .set_json(serde_json::json!({
+            "token": "DOGE", "interval": "1m", "timestamp": start + Duration::minutes(1),
+            "open": 0.18, "high": 0.2, "low": 0.18, "close": 0.18, "volume": 100.0
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    let req = test::TestRequest::get().uri(&format!("/api/v1/admin/verify?token=DOGE&{}", range)).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["consistent"], false);
+    assert_eq!(body["mismatches"][0]["field"], "high");
+    assert_eq!(body["mismatches"][0]["expected"], 0.2);
+
+    for query in ["interval=1s", "interval=100t", "interval=1h&start=0"] {
+        let req = test::TestRequest::get().uri(&format!("/api/v1/admin/verify?{}", query)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400, "{}", query);
+    }
+}
+
+#[actix_web::test]
+async fn test_token_listing_endpoints() {
+    let generator = MockDataGenerator::new();
+    let kline_service = Arc::new(KLineService::new());
+    let registry = Arc::new(TokenRegistry::new(
+        UnknownTokenPolicy::Reject,
+        generator.get_available_tokens(),
+    ));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(generator.listings()))
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(registry.clone()))
+            .app_data(web::Data::new(Arc::new(WsManager::new())))
+            .configure(configure_routes)
+    ).await;
+
+    let listing = |body: serde_json::Value| {
+        test::TestRequest::post().uri("/api/v1/admin/tokens").set_json(body).to_request()
+    };
+
+    let resp = test::call_service(&app, listing(serde_json::json!({"symbol": "BONK", "base_price": 0.00002}))).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["token"], "BONK");
+    assert_eq!(body["base_price"], 0.00002);
+    assert!(generator.generate_transaction("BONK").is_some());
+    assert!(kline_service.get_available_tokens().contains(&"BONK".to_string()));
+    assert!(registry.admit("BONK").accepted);
+
+    let resp = test::call_service(&app, listing(serde_json::json!({"symbol": "BONK", "base_price": 0.00002}))).await;
+    assert_eq!(resp.status(), 409);
+    let resp = test::call_service(&app, listing(serde_json::json!({"symbol": "WIF", "base_price": -1.0}))).await;
+    assert_eq!(resp.status(), 400);
+
+    kline_service.process_transaction(&generator.generate_transaction("DOGE").unwrap());
+    let req = test::TestRequest::delete().uri("/api/v1/admin/tokens/DOGE").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert!(generator.generate_transaction("DOGE").is_none());
+    assert!(!generator.get_available_tokens().contains(&"DOGE".to_string()));
+    assert!(kline_service.get_latest_kline("DOGE", TimeInterval::Minute1).is_none());
+    assert!(!registry.admit("DOGE").accepted);
+
+    let req = test::TestRequest::delete().uri("/api/v1/admin/tokens/DOGE").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_get_symbols_endpoint() {
+    let mut config = k_line::config::Config::default();
+    config.tokens.supported_tokens[0].price_precision = Some(5);
+    config.tokens.supported_tokens[0].tick_size = Some(0.00005);
+    let kline_service = Arc::new(KLineService::new());
+    let registry = Arc::new(TokenRegistry::from_config(&config));
+    let start = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+        transaction.timestamp = start - Duration::minutes(minute);
+        kline_service.process_transaction(&transaction);
+    }
+    registry.unregister("PEPE");
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(SymbolTable::from_config(&config))))
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(registry))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/symbols").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["count"], 3);
+
+    let doge = &body["symbols"][0];
+    assert_eq!(doge["symbol"], "DOGE");
+    assert_eq!(doge["base_price"], 0.15);
+    assert_eq!(doge["volatility"], 5.0);
+    assert_eq!(doge["price_precision"], 5);
+    assert_eq!(doge["tick_size"], 0.00005);
+    assert!(doge["quantity_precision"].is_null());
+    assert_eq!(doge["status"], "trading");
+    let earliest: DateTime<Utc> = serde_json::from_value(doge["availability"]["1m"]["earliest"].clone()).unwrap();
+    let latest: DateTime<Utc> = serde_json::from_value(doge["availability"]["1m"]["latest"].clone()).unwrap();
+    assert_eq!(earliest, start - Duration::minutes(2));
+    assert_eq!(latest, start);
+
+    // Configured tokens are listed before their first trade
+    assert_eq!(body["symbols"][1]["symbol"], "PEPE");
+    assert_eq!(body["symbols"][1]["status"], "halted");
+    assert_eq!(body["symbols"][2]["availability"], serde_json::json!({}));
+}
+
+#[actix_web::test]
+async fn test_get_trades_endpoint() {
+    let trades = Arc::new(TradeService::new(50));
+    for i in 0..60 {
+        trades.record(&Transaction::new("DOGE".to_string(), 0.1 + i as f64 * 0.001, 10.0, Side::Buy));
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(trades))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/trades?token=DOGE&limit=5")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["token"], "DOGE");
+    assert_eq!(body["count"], 5);
+    // The newest trades, oldest first
+    let prices: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|trade| trade["price"].as_f64().unwrap())
+        .collect();
+    assert!(prices.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((prices[4] - 0.159).abs() < 1e-9);
+
+    // The limit is capped at the history kept
+    let req = test::TestRequest::get()
+        .uri("/api/v1/trades?token=DOGE&limit=1000")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["count"], 50);
+
+    let req = test::TestRequest::get().uri("/api/v1/trades?token=PEPE").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["count"], 0);
+}
+
+#[actix_web::test]
+async fn test_get_depth_endpoint() {
+    let order_books = Arc::new(OrderBookSimulator::new(10));
+    order_books.apply_trade(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(order_books))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/depth?token=DOGE&limit=3")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["token"], "DOGE");
+    assert_eq!(body["update_id"], 1);
+    let bids = body["bids"].as_array().unwrap();
+    let asks = body["asks"].as_array().unwrap();
+    assert_eq!(bids.len(), 3);
+    assert_eq!(asks.len(), 3);
+    assert!(bids[0]["price"].as_f64().unwrap() > bids[1]["price"].as_f64().unwrap());
+    assert!(bids[0]["price"].as_f64().unwrap() < asks[0]["price"].as_f64().unwrap());
+
+    // Tokens without trades have no book yet
+    let req = test::TestRequest::get().uri("/api/v1/depth?token=PEPE").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_api_key_auth() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::AutoRegister, Vec::new()));
+    let authenticator = Arc::new(Authenticator::new(
+        true,
+        false,
+        vec![
+            ("reader".to_string(), ApiKeyScope::Read),
+            ("operator".to_string(), ApiKeyScope::Admin),
+        ],
+    ));
+    let kline_service = Arc::new(KLineService::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(registry))
+            .app_data(web::Data::new(authenticator))
+            .configure(configure_routes)
+    ).await;
+
+    let request = |uri: &str, key: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(key) = key {
+            req = req.insert_header((API_KEY_HEADER, key));
+        }
+        req.to_request()
+    };
+
+    let resp = test::call_service(&app, request("/api/v1/tokens", None)).await;
+    assert_eq!(resp.status(), 401);
+    let resp = test::call_service(&app, request("/api/v1/tokens", Some("wrong"))).await;
+    assert_eq!(resp.status(), 401);
+    let resp = test::call_service(&app, request("/api/v1/tokens", Some("reader"))).await;
+    assert_eq!(resp.status(), 200);
+
+    let resp = test::call_service(&app, request("/api/v1/admin/tokens", Some("reader"))).await;
+    assert_eq!(resp.status(), 403);
+    let resp = test::call_service(&app, request("/api/v1/admin/tokens", Some("operator"))).await;
+    assert_eq!(resp.status(), 200);
+
+    // The health check stays open for load balancers
+    let resp = test::call_service(&app, request("/api/v1/health", None)).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_api_key_auth_allows_anonymous_reads() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::AutoRegister, Vec::new()));
+    let authenticator = Arc::new(Authenticator::new(
+        true,
+        true,
+        vec![("operator".to_string(), ApiKeyScope::Admin)],
+    ));
+    let kline_service = Arc::new(KLineService::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(registry))
+            .app_data(web::Data::new(authenticator))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/tokens").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let req = test::TestRequest::get().uri("/api/v1/admin/tokens").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+}
+
+#[actix_web::test]
+async fn test_admin_routes_need_a_key_without_auth() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::AutoRegister, Vec::new()));
+    let authenticator = Arc::new(Authenticator::new(
+        false,
+        false,
+        vec![
+            ("reader".to_string(), ApiKeyScope::Read),
+            ("operator".to_string(), ApiKeyScope::Admin),
+        ],
+    ));
+    let kline_service = Arc::new(KLineService::new());
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(registry))
+            .app_data(web::Data::new(authenticator))
+            .configure(configure_routes)
+    ).await;
+
+    let request = |uri: &str, key: Option<&str>| {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(key) = key {
+            req = req.insert_header((API_KEY_HEADER, key));
+        }
+        req.to_request()
+    };
+
+    let resp = test::call_service(&app, request("/api/v1/tokens", None)).await;
+    assert_eq!(resp.status(), 200);
+    let resp = test::call_service(&app, request("/api/v1/admin/tokens", None)).await;
+    assert_eq!(resp.status(), 401);
+    let resp = test::call_service(&app, request("/api/v1/admin/tokens", Some("reader"))).await;
+    assert_eq!(resp.status(), 403);
+    let resp = test::call_service(&app, request("/api/v1/admin/tokens", Some("operator"))).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_rate_limit_headers() {
+    let kline_service = Arc::new(KLineService::new());
+    let limiter = Arc::new(RateLimiter::new(0.01, 2));
+    let authenticator = Arc::new(Authenticator::new(false, true, [("reader".to_string(), ApiKeyScope::Read)]));
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(limiter))
+            .app_data(web::Data::new(authenticator))
+            .configure(configure_routes)
+    ).await;
+
+    for remaining in ["1", "0"] {
+        let req = test::TestRequest::get().uri("/api/v1/tokens").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("X-RateLimit-Limit").unwrap(), "2");
+        assert_eq!(resp.headers().get("X-RateLimit-Remaining").unwrap(), remaining);
+    }
+
+    let req = test::TestRequest::get().uri("/api/v1/tokens").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers().get("X-RateLimit-Remaining").unwrap(), "0");
+    let retry_after: u64 = resp.headers().get("Retry-After").unwrap().to_str().unwrap().parse().unwrap();
+    assert!(retry_after >= 1);
+
+    // Unknown keys share the bucket of the client's address
+    let req = test::TestRequest::get()
+        .uri("/api/v1/tokens")
+        .insert_header((API_KEY_HEADER, "made-up"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 429);
+
+    // Clients presenting a configured API key get their own bucket
+    let req = test::TestRequest::get()
+        .uri("/api/v1/tokens")
+        .insert_header((API_KEY_HEADER, "reader"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_openapi_spec_and_docs() {
+    let app = test::init_service(App::new().configure(configure_routes)).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/openapi.json").to_request();
+    let spec: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    for path in ["/api/v1/klines", "/api/v1/klines/batch", "/api/v1/trades", "/api/v1/admin/promote"] {
+        assert!(spec["paths"][path].is_object(), "{} is not documented", path);
+    }
+    let kline = &spec["components"]["schemas"]["KLine"];
+    assert!(kline["properties"]["timestamp"].is_object());
+    assert!(kline["properties"]["close_time"].is_object());
+    assert!(kline["properties"]["is_final"].is_object());
+    assert!(spec["components"]["securitySchemes"]["api_key"].is_object());
+
+    let req = test::TestRequest::get().uri("/api/v1/docs").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    assert!(std::str::from_utf8(&body).unwrap().contains("/api/v1/openapi.json"));
+}
+
+#[actix_web::test]
+async fn test_export_klines_csv_and_parquet() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    let service = Arc::new(KLineService::new());
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1 + minute as f64 / 100.0, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+    let range = format!(
+        "token=DOGE&interval=1m&start={}&end={}",
+        start.timestamp_millis(),
+        (start + Duration::hours(1)).timestamp_millis()
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/klines/export?{}", range))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+    assert!(resp.headers().get("content-disposition").unwrap().to_str().unwrap().contains("DOGE_1m.csv"));
+    let body = test::read_body(resp).await;
+    let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "token,interval,open_time,open,high,low,close,volume,is_closed");
+    assert!(lines[1].starts_with(&format!("DOGE,1m,{},0.1,", start.timestamp_millis())));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/klines/export?{}&format=parquet", range))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get("content-disposition").unwrap().to_str().unwrap().contains("DOGE_1m.parquet"));
+    let reader = SerializedFileReader::new(test::read_body(resp).await).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+    let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+    assert_eq!(row.get_string(0).unwrap(), "DOGE");
+    assert_eq!(row.get_double(6).unwrap(), 0.1);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines/export?token=DOGE&format=xlsx")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_get_klines_array_format() {
+    let service = Arc::new(KLineService::new());
+    let open_time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let mut transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    transaction.timestamp = open_time;
+    service.process_transaction(&transaction);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m&format=array")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let close_time = open_time.timestamp_millis() + 59_999;
+    assert_eq!(
+        body["data"][0],
+        serde_json::json!([open_time.timestamp_millis(), 0.15, 0.15, 0.15, 0.15, 100.0, close_time])
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines/batch?tokens=DOGE&interval=1m&format=array&numbers=string")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["DOGE"][0],
+        serde_json::json!([open_time.timestamp_millis(), "0.15", "0.15", "0.15", "0.15", "100", close_time])
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines/current?token=DOGE&interval=1m&format=array")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"][6], close_time);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&format=table")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_alerts_crud() {
+    let alert_service = Arc::new(AlertService::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(alert_service.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/alerts")
+        .set_json(serde_json::json!({"token": "DOGE", "condition": {"type": "above", "price": 0.2}}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let alert: serde_json::Value = test::read_body_json(resp).await;
+    let id = alert["id"].as_str().unwrap().to_string();
+    assert_eq!(alert["condition"]["type"], "above");
+    assert!(alert["triggered_at"].is_null());
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/alerts")
+        .set_json(serde_json::json!({"token": "DOGE", "condition": {"type": "percent_move", "percent": 5.0, "window_secs": 0}}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    alert_service.evaluate(&Transaction::new("DOGE".to_string(), 0.25, 10.0, Side::Buy));
+
+    let req = test::TestRequest::get().uri(&format!("/api/v1/alerts/{}", id)).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["triggered_at"].is_string());
+
+    let req = test::TestRequest::get().uri("/api/v1/alerts?token=DOGE").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["count"], 1);
+
+    let req = test::TestRequest::delete().uri(&format!("/api/v1/alerts/{}", id)).to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::get().uri(&format!("/api/v1/alerts/{}", id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn test_moving_average_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    // Closes 1..=10 in closed candles, plus an open candle that is ignored
+    for minute in 0..11 {
+        let mut transaction = Transaction::new("DOGE".to_string(), minute as f64 + 1.0, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/ma?token=DOGE&interval=1m&period=3&limit=2")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["type"], "sma");
+    assert_eq!(body["period"], 3);
+    let values: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["value"].as_f64().unwrap())
+        .collect();
+    assert_eq!(values, vec![8.0, 9.0]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/ma?token=DOGE&interval=1m&period=3&type=ema")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["type"], "ema");
+    assert_eq!(body["data"].as_array().unwrap().len(), 8);
+
+    for uri in [
+        "/api/v1/indicators/ma?token=DOGE&type=wma",
+        "/api/v1/indicators/ma?token=DOGE&period=0",
+        "/api/v1/indicators/ma?token=DOGE&interval=2m",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_rsi_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    for (minute, price) in [1.0, 2.0, 1.0, 2.0, 3.0, 3.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    // The last candle is still open, so closes are 1, 2, 1, 2, 3
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/rsi?token=DOGE&interval=1m&period=2&limit=2")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["period"], 2);
+    let values: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["value"].as_f64().unwrap())
+        .collect();
+    assert_eq!(values, vec![75.0, 87.5]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/rsi?token=DOGE&period=501")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_atr_and_volatility_endpoints() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    for (minute, price) in [10.0, 12.0, 11.0, 15.0, 15.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    // The last candle is still open, so closes are 10, 12, 11, 15 with single-trade candles
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/atr?token=DOGE&interval=1m&period=2")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["period"], 2);
+    let values: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|point| point["value"].as_f64().unwrap())
+        .collect();
+    assert_eq!(values, vec![1.5, 2.75]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/volatility?token=DOGE&interval=1m&period=3&limit=1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let points = body["data"].as_array().unwrap();
+    assert_eq!(points.len(), 1);
+    let volatility = points[0]["volatility"].as_f64().unwrap();
+    assert!(volatility > 0.0);
+    let annualized = points[0]["annualized"].as_f64().unwrap();
+    assert!((annualized / volatility - (365.0_f64 * 24.0 * 60.0).sqrt()).abs() < 1e-6);
+
+    for uri in ["/api/v1/indicators/volatility?period=1", "/api/v1/indicators/atr?period=0"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_macd_and_bollinger_endpoints() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    for minute in 0..11 {
+        let mut transaction = Transaction::new("DOGE".to_string(), minute as f64 + 1.0, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .app_data(web::Data::new(Arc::new(IndicatorCache::default())))
+            .configure(configure_routes)
+    ).await;
+
+    // A linear trend keeps the MACD line at the gap between the EMA lags
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/macd?token=DOGE&interval=1m&fast=2&slow=4&signal=3")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["slow"], 4);
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 5);
+    assert_eq!(data[4]["macd"], 1.0);
+    assert_eq!(data[4]["histogram"], 0.0);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/indicators/bollinger?token=DOGE&interval=1m&period=3&std_dev=1.5&limit=2")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["std_dev"], 1.5);
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[1]["middle"], 9.0);
+
+    for uri in [
+        "/api/v1/indicators/macd?token=DOGE&fast=26&slow=12",
+        "/api/v1/indicators/macd?token=DOGE&signal=0",
+        "/api/v1/indicators/bollinger?token=DOGE&std_dev=-1",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_heikin_ashi_candle_type() {
+    let service = Arc::new(KLineService::new());
+    let start = Utc::now() - Duration::minutes(30);
+
+    for (minute, price) in [10.0, 20.0, 30.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    // Opens average the previous Heikin-Ashi open and close: 10, 10, 15
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=1m&candle_type=heikin_ashi&limit=2")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let opens: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|kline| kline["open"].as_f64().unwrap())
+        .collect();
+    assert_eq!(opens, vec![15.0, 10.0]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines/current?token=DOGE&interval=1m&candle_type=heikin_ashi")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["open"], 15.0);
+    assert_eq!(body["data"]["close"], 30.0);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&candle_type=renko")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_renko_and_range_bar_endpoints() {
+    let trades = Arc::new(TradeService::new(100));
+    for price in [10.0, 10.5, 11.0, 12.2, 11.5, 9.0] {
+        trades.record(&Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy));
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(trades))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/renko?token=DOGE&size=1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["bar_type"], "renko");
+    assert_eq!(body["size"], 1.0);
+    let closes: Vec<f64> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bar| bar["close"].as_f64().unwrap())
+        .collect();
+    assert_eq!(closes, vec![11.0, 12.0, 10.0, 9.0]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/range-bars?token=DOGE&size=2&limit=1")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["bar_type"], "range");
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    // The newest of the two bars: 10 to 12.2, then 11.5 to 9
+    assert_eq!(body["data"][0]["open"], 11.5);
+    assert_eq!(body["data"][0]["close"], 9.0);
+
+    // Invalid sizes are rejected, and an ATR needs closed candles
+    for (uri, status) in [
+        ("/api/v1/renko?token=DOGE&size=-1", 400),
+        ("/api/v1/renko?token=DOGE&size=abc", 400),
+        ("/api/v1/renko?token=DOGE&atr_period=0", 400),
+        ("/api/v1/range-bars?token=DOGE&interval=2m", 400),
+        ("/api/v1/renko?token=DOGE", 404),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_correlation_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let now = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for (minute, price) in [0.1, 0.12, 0.11, 0.15].into_iter().enumerate() {
+        for (token, price) in [("DOGE", price), ("SHIB", price * 3.0)] {
+            let mut transaction = Transaction::new(token.to_string(), price, 100.0, Side::Buy);
+            transaction.timestamp = now - TimeDelta::minutes(3 - minute as i64);
+            service.process_transaction(&transaction);
+        }
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/analytics/correlation?interval=1m&window=1h").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["tokens"], serde_json::json!(["DOGE", "SHIB"]));
+    let correlation = body["matrix"][0][1].as_f64().unwrap();
+    assert!((correlation - 1.0).abs() < 1e-9);
+
+    for query in ["window=6w", "window=0h", "interval=2m", "interval=1s&window=7d"] {
+        let req = test::TestRequest::get().uri(&format!("/api/v1/analytics/correlation?{}", query)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400, "{}", query);
+    }
+}
+
+#[actix_web::test]
+async fn test_volume_profile_endpoint() {
+    let trades = Arc::new(TradeService::new(100));
+    let now = Utc::now();
+    for (seconds, price, volume, side) in [
+        (40, 1.0, 5.0, Side::Buy),
+        (30, 1.4, 2.0, Side::Sell),
+        (20, 1.6, 7.0, Side::Buy),
+        (10, 2.0, 1.0, Side::Sell),
+    ] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, volume, side);
+        transaction.timestamp = now - Duration::seconds(seconds);
+        trades.record(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(trades))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/volume-profile?token=DOGE&buckets=2").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["bucket_size"], 0.5);
+    assert_eq!(body["total_volume"], 15.0);
+    let levels = body["levels"].as_array().unwrap();
+    assert_eq!((levels[0]["price_low"].as_f64(), levels[0]["price_high"].as_f64()), (Some(1.0), Some(1.5)));
+    assert_eq!((levels[0]["volume"].as_f64(), levels[0]["sell_volume"].as_f64()), (Some(7.0), Some(2.0)));
+    // The top level includes the highest price
+    assert_eq!((levels[1]["volume"].as_f64(), levels[1]["trade_count"].as_u64()), (Some(8.0), Some(2)));
+    assert_eq!(body["point_of_control"], 1.75);
+
+    // The range keeps trades from start up to, but excluding, end
+    let uri = format!(
+        "/api/v1/volume-profile?token=DOGE&start={}&end={}",
+        (now - Duration::seconds(35)).timestamp_millis(),
+        (now - Duration::seconds(20)).timestamp_millis(),
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(body["total_volume"], 2.0);
+    assert_eq!(body["levels"].as_array().unwrap().len(), 1);
+    assert_eq!(body["point_of_control"], 1.4);
+
+    let req = test::TestRequest::get().uri("/api/v1/volume-profile?token=SHIB").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["levels"].as_array().unwrap().is_empty());
+    assert!(body["point_of_control"].is_null());
+
+    for uri in ["/api/v1/volume-profile?buckets=0", "/api/v1/volume-profile?buckets=1001", "/api/v1/volume-profile?start=soon"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn test_delta_endpoint() {
+    let service = Arc::new(KLineService::new());
+    let now = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for (minute, volume, side) in [(0, 10.0, Side::Buy), (0, 4.0, Side::Sell), (1, 9.0, Side::Sell), (2, 1.0, Side::Buy)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1, volume, side);
+        transaction.timestamp = now - TimeDelta::minutes(2 - minute);
+        service.process_transaction(&transaction);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/delta?token=DOGE&interval=1m").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["interval"], "1m");
+    let data = body["data"].as_array().unwrap();
+    let deltas: Vec<(f64, f64)> = data
+        .iter()
+        .map(|delta| (delta["delta"].as_f64().unwrap(), delta["cumulative_delta"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(deltas, vec![(6.0, 6.0), (-9.0, -3.0), (1.0, -2.0)]);
+    assert_eq!(data[0]["buy_volume"], 10.0);
+    assert_eq!(data[0]["sell_volume"], 4.0);
+
+    // The limit keeps the newest candles, accumulated from the first of them
+    let req = test::TestRequest::get().uri("/api/v1/delta?token=DOGE&limit=2").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"][0]["cumulative_delta"], -9.0);
+    assert_eq!(body["data"][1]["cumulative_delta"], -8.0);
+
+    let req = test::TestRequest::get().uri("/api/v1/delta?token=DOGE&interval=2m").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_trade_count_interval() {
+    let service = Arc::new(KLineService::new().with_activity_intervals([TimeInterval::Trades(2)]));
+    for price in [1.0, 2.0, 3.0] {
+        service.process_transaction(&Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy));
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=2t&order=asc")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["interval"], "2t");
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0]["interval"], "2t");
+    assert_eq!(data[0]["close"], 2.0);
+    assert_eq!(data[0]["is_closed"], true);
+    assert!(data[0]["last_trade_at"].is_string());
+
+    let req = test::TestRequest::get().uri("/api/v1/stats").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["statistics"]["supported_intervals"][5], "2t");
+
+    // Intervals that are not configured are rejected
+    let req = test::TestRequest::get()
+        .uri("/api/v1/klines?token=DOGE&interval=5t")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Invalid interval. Supported: 1s, 1m, 5m, 15m, 1h, 2t");
+}
+
+#[actix_web::test]
+async fn test_ws_schema_endpoint() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .configure(configure_routes)
+    ).await;
+
+    let req = test::TestRequest::get().uri("/api/v1/ws-schema").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = test::read_body_json(resp).await;
+
+    // Every client action is described, with the shared definitions it refers to
+    let client = &body["client_message"];
+    assert_eq!(client["title"], "ClientMessage");
+    let actions: Vec<&str> = client["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|variant| variant["properties"]["action"]["const"].as_str().unwrap())
+        .collect();
+    assert!(actions.contains(&"subscribe") && actions.contains(&"resume") && actions.contains(&"auth"));
+    assert!(client["$defs"]["SubscriptionType"]["oneOf"].is_array());
+
+    // Server messages include the topic and sequence number, and candles their serialized fields
+    let server = &body["server_message"];
+    assert_eq!(server["title"], "ServerMessage");
+    assert!(server["properties"]["seq"].is_object());
+    let kline = server["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|variant| variant["properties"]["type"]["const"] == "kline")
+        .unwrap();
+    assert_eq!(kline["properties"]["data"]["$ref"], "#/$defs/KLine");
+    let kline_schema = &server["$defs"]["KLine"];
+    assert!(kline_schema["properties"]["close_time"].is_object());
+    assert_eq!(server["$defs"]["TimeInterval"]["type"], "string");
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "server")]
+
+use actix_web::{test, web, App};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use object_store::memory::InMemory;
+use std::sync::Arc;
+
+use k_line::services::archive::ArchiveLocation;
+use k_line::services::CandleArchive;
+use k_line::{configure_routes, KLineService, Side, TimeInterval, Transaction};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 15, 13, 30, 0).unwrap()
+}
+
+/// A service with one trade per minute from 13:30 to 14:29, priced by its minute
+fn service() -> KLineService {
+    let service = KLineService::new();
+    for minute in 0..60 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 1.0 + minute as f64, 10.0, Side::Buy);
+        transaction.timestamp = start() + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+    service
+}
+
+fn archive() -> CandleArchive {
+    CandleArchive::new(Arc::new(InMemory::new()), "candles")
+}
+
+#[actix_web::test]
+async fn test_evicted_candles_are_archived_hourly() {
+    let service = service();
+    let archive = archive();
+    let evicted = service.drain_klines_before(start() + Duration::minutes(20));
+    assert!(evicted.iter().all(|kline| kline.timestamp < start() + Duration::minutes(20)));
+    assert!(archive.archive(&evicted).await.unwrap() > 0);
+
+    // Later prunings merge into the objects already archived
+    let evicted = service.drain_klines_before(start() + Duration::minutes(40));
+    archive.archive(&evicted).await.unwrap();
+
+    let archived = archive
+        .read_range("DOGE", TimeInterval::Minute1, start(), start() + Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(archived.len(), 39);
+    assert_eq!((archived[0].open, archived[0].volume, archived[0].is_closed), (1.0, 10.0, true));
+    assert_eq!(archived[38].close, 39.0);
+    assert_eq!(
+        archive.object_path("DOGE", TimeInterval::Minute1, start()).to_string(),
+        "candles/DOGE/1m/2024-01-15T13.parquet"
+    );
+
+    let newest = archive
+        .read_before("DOGE", TimeInterval::Minute1, start() + Duration::minutes(35), 3)
+        .await
+        .unwrap();
+    assert_eq!(newest.iter().map(|kline| kline.close).collect::<Vec<_>>(), vec![33.0, 34.0, 35.0]);
+}
+
+#[actix_web::test]
+async fn test_rest_ranges_read_through_the_archive() {
+    let service = Arc::new(service());
+    let archive = Arc::new(archive());
+    let evicted = service.drain_klines_before(start() + Duration::minutes(31));
+    archive.archive(&evicted).await.unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(service))
+            .app_data(web::Data::new(archive))
+            .configure(configure_routes),
+    )
+    .await;
+
+    // A page reaching past the oldest in-memory candle continues in the archive
+    let before = (start() + Duration::minutes(33)).timestamp_millis();
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/klines?token=DOGE&before={}&limit=5&order=asc", before))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let closes: Vec<f64> = body["data"].as_array().unwrap().iter().map(|k| k["close"].as_f64().unwrap()).collect();
+    assert_eq!(closes, vec![29.0, 30.0, 31.0, 32.0, 33.0]);
+    assert_eq!(body["next_cursor"], (start() + Duration::minutes(28)).timestamp_millis());
+
+    // Paging forwards from the archive into memory
+    let after = (start() + Duration::minutes(28)).timestamp_millis();
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/klines?token=DOGE&after={}&limit=4", after))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let closes: Vec<f64> = body["data"].as_array().unwrap().iter().map(|k| k["close"].as_f64().unwrap()).collect();
+    assert_eq!(closes, vec![30.0, 31.0, 32.0, 33.0]);
+    assert_eq!(body["next_cursor"], (start() + Duration::minutes(32)).timestamp_millis());
+
+    // Exports cover the archived part of their range
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/klines/export?token=DOGE&start={}&end={}",
+            start().timestamp_millis(),
+            (start() + Duration::minutes(59)).timestamp_millis()
+        ))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert_eq!(String::from_utf8(body.to_vec()).unwrap().lines().count(), 61);
+}
+
+#[actix_web::test]
+async fn test_archive_locations() {
+    assert_eq!(
+        ArchiveLocation::parse("s3://k-line/candles/").unwrap(),
+        ArchiveLocation::S3 {
+            bucket: "k-line".to_string(),
+            prefix: "candles".to_string()
+        }
+    );
+    assert_eq!(
+        ArchiveLocation::parse("file:///var/lib/k-line").unwrap(),
+        ArchiveLocation::Local("/var/lib/k-line".into())
+    );
+    assert!(ArchiveLocation::parse("s3://").is_err());
+    assert!(ArchiveLocation::parse("/var/lib/k-line").is_err());
+}
@@ -0,0 +1,101 @@
+use chrono::{Duration, Utc};
+use k_line::services::bars::{build_bars, BarBuilder, BarSize, BarType, MAX_BRICKS_PER_TRADE};
+use k_line::{KLineService, Side, TimeInterval, Transaction};
+
+fn trades(prices: &[f64]) -> Vec<Transaction> {
+    let start = Utc::now() - Duration::minutes(10);
+    prices
+        .iter()
+        .enumerate()
+        .map(|(i, &price)| {
+            let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+            transaction.timestamp = start + Duration::seconds(i as i64);
+            transaction
+        })
+        .collect()
+}
+
+fn open_close(bars: &[k_line::services::bars::Bar]) -> Vec<(f64, f64)> {
+    bars.iter().map(|bar| (bar.open, bar.close)).collect()
+}
+
+#[test]
+fn test_renko_bricks_follow_the_trend() {
+    let bars = build_bars(&trades(&[10.0, 10.5, 11.0, 12.2, 11.5]), BarType::Renko, 1.0);
+
+    // Two up bricks from 10; the pullback to 11.5 stays within a brick
+    assert_eq!(open_close(&bars), vec![(10.0, 11.0), (11.0, 12.0)]);
+    assert_eq!(bars[0].trade_count, 3);
+    assert_eq!(bars[0].volume, 30.0);
+    assert_eq!(bars[1].trade_count, 1);
+    assert_eq!((bars[1].low, bars[1].high), (11.0, 12.0));
+}
+
+#[test]
+fn test_renko_reversal_needs_two_bricks() {
+    let bars = build_bars(&trades(&[10.0, 11.0, 10.2, 9.0]), BarType::Renko, 1.0);
+
+    // After the up brick 10-11, a down brick starts below 10
+    assert_eq!(open_close(&bars), vec![(10.0, 11.0), (10.0, 9.0)]);
+    assert_eq!(bars[1].trade_count, 2);
+}
+
+#[test]
+fn test_renko_gap_emits_several_bricks() {
+    let bars = build_bars(&trades(&[10.0, 13.0]), BarType::Renko, 1.0);
+    assert_eq!(open_close(&bars), vec![(10.0, 11.0), (11.0, 12.0), (12.0, 13.0)]);
+    // The trades count towards the first brick only
+    assert_eq!(bars[0].trade_count, 2);
+    assert_eq!(bars[2].trade_count, 0);
+
+    let bars = build_bars(&trades(&[0.0, 1000.0, 999.0]), BarType::Renko, 1.0);
+    assert_eq!(bars.len(), MAX_BRICKS_PER_TRADE);
+    assert_eq!(bars.last().unwrap().close, 1000.0);
+}
+
+#[test]
+fn test_range_bars_close_at_the_range() {
+    let bars = build_bars(&trades(&[10.0, 10.3, 9.9, 10.6, 10.5, 10.0]), BarType::Range, 0.5);
+
+    // The first bar spans 9.9 to 10.6 when the fourth trade arrives
+    assert_eq!(bars.len(), 2);
+    assert_eq!((bars[0].open, bars[0].high, bars[0].low, bars[0].close), (10.0, 10.6, 9.9, 10.6));
+    assert_eq!(bars[0].trade_count, 4);
+    assert_eq!((bars[1].open, bars[1].close), (10.5, 10.0));
+    assert!(bars[1].open_time > bars[0].close_time);
+}
+
+#[test]
+fn test_builder_keeps_state_between_trades() {
+    let trades = trades(&[1.0, 1.1, 1.2, 1.3]);
+    let mut builder = BarBuilder::new("DOGE".to_string(), BarType::Renko, 0.1);
+    let bars: Vec<_> = trades.iter().flat_map(|transaction| builder.push(transaction)).collect();
+    assert_eq!(bars.len(), 3);
+    assert_eq!(bars, build_bars(&trades, BarType::Renko, 0.1));
+}
+
+#[test]
+fn test_bar_size_parameters() {
+    assert_eq!(BarSize::from_parts(Some(0.5), None, None), Ok(BarSize::Fixed(0.5)));
+    assert_eq!(
+        BarSize::from_parts(None, None, None),
+        Ok(BarSize::Atr { interval: TimeInterval::Minute1, period: 14 })
+    );
+    assert!(BarSize::from_parts(Some(0.0), None, None).is_err());
+    assert!(BarSize::from_parts(None, Some("2m"), None).is_err());
+    assert!(BarSize::from_parts(None, None, Some(0)).is_err());
+
+    // The ATR needs more closed candles than its period
+    let service = KLineService::new();
+    let size = BarSize::Atr { interval: TimeInterval::Minute1, period: 2 };
+    assert_eq!(size.resolve(&service, "DOGE"), None);
+
+    let start = Utc::now() - Duration::hours(1);
+    for (minute, price) in [10.0, 12.0, 11.0, 15.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+    // The last candle is still open, so the ATR covers the first three
+    assert_eq!(size.resolve(&service, "DOGE"), Some(1.5));
+}
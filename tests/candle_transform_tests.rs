@@ -0,0 +1,64 @@
+use chrono::{Duration, Utc};
+use k_line::services::candle_transform::{self, CandleType};
+use k_line::{KLine, KLineService, Side, TimeInterval, Transaction};
+
+fn candle(open: f64, high: f64, low: f64, close: f64) -> KLine {
+    let mut kline = KLine::new("DOGE".to_string(), Utc::now(), TimeInterval::Minute1, open, 1.0);
+    kline.high = high;
+    kline.low = low;
+    kline.close = close;
+    kline
+}
+
+#[test]
+fn test_heikin_ashi_candles() {
+    let klines = [candle(10.0, 14.0, 8.0, 12.0), candle(12.0, 16.0, 11.0, 15.0)];
+    let candles = candle_transform::heikin_ashi(&klines);
+
+    // The first open is the midpoint of the regular open and close
+    assert_eq!(candles[0].open, 11.0);
+    assert_eq!(candles[0].close, 11.0);
+    assert_eq!((candles[0].high, candles[0].low), (14.0, 8.0));
+
+    // Later opens average the previous Heikin-Ashi open and close
+    assert_eq!(candles[1].open, 11.0);
+    assert_eq!(candles[1].close, 13.5);
+    assert_eq!((candles[1].high, candles[1].low), (16.0, 11.0));
+    assert_eq!(candles[1].volume, klines[1].volume);
+}
+
+#[test]
+fn test_heikin_ashi_high_and_low_include_open_and_close() {
+    let candle = candle_transform::heikin_ashi_candle(&candle(5.0, 6.0, 4.0, 5.0), Some(10.0));
+    assert_eq!(candle.open, 10.0);
+    assert_eq!(candle.high, 10.0);
+    assert_eq!(candle.low, 4.0);
+}
+
+#[test]
+fn test_apply_seeds_from_earlier_candles() {
+    let service = KLineService::new();
+    let start = Utc::now() - Duration::hours(1);
+    for (minute, price) in [10.0, 20.0, 30.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+
+    let all = service.get_klines(
+        "DOGE",
+        TimeInterval::Minute1,
+        start - Duration::minutes(1),
+        Utc::now(),
+        None,
+    );
+    let expected = candle_transform::heikin_ashi(&all);
+
+    // Transforming only the last candle gives the same result as transforming all of them
+    let last = CandleType::HeikinAshi.apply(&service, all[2..].to_vec());
+    assert_eq!(last[0].open, expected[2].open);
+    assert_eq!(last[0].open, 15.0);
+
+    assert_eq!(CandleType::Regular.apply(&service, all.clone())[2].open, 30.0);
+    assert!("renko".parse::<CandleType>().is_err());
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "server")]
+
+mod common;
+
+use actix_web::{web, App, HttpRequest, HttpResponse};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use common::trade;
+use k_line::config::ClickHouseConfig;
+use k_line::services::clickhouse::{run_clickhouse_sink, ClickHouseRecord};
+use k_line::services::{ClickHouseWriter, TransactionPipeline};
+use k_line::{KLineService, TimeInterval, WsManager};
+
+/// An insert received by the test server: its statement, user and rows
+type Insert = (String, Option<String>, Vec<serde_json::Value>);
+
+/// A ClickHouse stand-in recording inserts, answering with the given status
+fn server(status: u16, inserts: Arc<Mutex<Vec<Insert>>>) -> actix_test::TestServer {
+    actix_test::start(move || {
+        let inserts = inserts.clone();
+        App::new().default_service(web::to(
+            move |request: HttpRequest, query: web::Query<HashMap<String, String>>, body: web::Bytes| {
+                let user = request
+                    .headers()
+                    .get("X-ClickHouse-User")
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                let rows = body
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(|line| serde_json::from_slice(line).unwrap())
+                    .collect();
+                inserts.lock().unwrap().push((query["query"].clone(), user, rows));
+                async move { HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).body("Code: 60") }
+            },
+        ))
+    })
+}
+
+fn config(url: String) -> ClickHouseConfig {
+    ClickHouseConfig {
+        enabled: true,
+        url,
+        database: "analytics".to_string(),
+        user: Some("k_line".to_string()),
+        batch_size: 2,
+        flush_interval_ms: 60_000,
+        max_attempts: 1,
+        dead_letter_path: std::env::temp_dir()
+            .join(format!("k-line-clickhouse-{}.jsonl", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned(),
+        ..ClickHouseConfig::default()
+    }
+}
+
+#[actix_rt::test]
+async fn test_pipeline_queues_trades_and_closed_candles() {
+    let (writer, mut receiver) = ClickHouseWriter::channel(100);
+    let pipeline = TransactionPipeline::new(Arc::new(KLineService::new()), Arc::new(WsManager::new()))
+        .with_clickhouse(Some(writer));
+    pipeline.handle(&trade(0, 0.1)).unwrap();
+    pipeline.handle(&trade(1, 0.2)).unwrap();
+
+    let (mut trades, mut klines) = (Vec::new(), Vec::new());
+    while let Ok(record) = receiver.try_recv() {
+        match record {
+            ClickHouseRecord::Trade(transaction) => trades.push(transaction.price),
+            ClickHouseRecord::Kline(kline) => klines.push(kline),
+        }
+    }
+    assert_eq!(trades, vec![0.1, 0.2]);
+    // Only candles the second trade closed
+    assert!(klines.iter().all(|kline| kline.is_closed && kline.close == 0.1));
+    assert_eq!(klines.iter().filter(|kline| kline.interval == TimeInterval::Minute1).count(), 1);
+}
+
+#[actix_rt::test]
+async fn test_rows_are_inserted_in_batches() {
+    let inserts = Arc::new(Mutex::new(Vec::new()));
+    let server = server(200, inserts.clone());
+    let config = config(server.url("/"));
+
+    let service = KLineService::new();
+    service.process_transaction(&trade(0, 0.1));
+    service.process_transaction(&trade(1, 0.2));
+    let kline = service
+        .get_klines("DOGE", TimeInterval::Minute1, trade(0, 0.1).timestamp, trade(0, 0.1).timestamp, None)
+        .remove(0);
+
+    let (writer, receiver) = ClickHouseWriter::channel(100);
+    let sink = actix_web::rt::spawn(run_clickhouse_sink(receiver, config.clone()));
+    for minute in 0..3 {
+        writer.write_trade(&trade(minute, 0.1));
+    }
+    writer.write_kline(&kline);
+    // Closing the queue flushes what is left
+    drop(writer);
+    sink.await.unwrap();
+
+    let inserts = inserts.lock().unwrap();
+    let sizes: Vec<(&str, usize)> = inserts.iter().map(|(query, _, rows)| (query.as_str(), rows.len())).collect();
+    assert_eq!(
+        sizes,
+        vec![
+            ("INSERT INTO analytics.trades FORMAT JSONEachRow", 2),
+            ("INSERT INTO analytics.trades FORMAT JSONEachRow", 1),
+            ("INSERT INTO analytics.klines FORMAT JSONEachRow", 1),
+        ]
+    );
+    assert_eq!(inserts[0].1.as_deref(), Some("k_line"));
+    let row = &inserts[0].2[1];
+    assert_eq!((row["token"].as_str(), row["side"].as_str()), (Some("DOGE"), Some("buy")));
+    assert_eq!(row["timestamp"], "2024-01-15 14:01:00.000");
+    let row = &inserts[2].2[0];
+    assert_eq!((row["interval"].as_str(), row["close"].as_f64()), (Some("1m"), Some(0.1)));
+    assert_eq!(row["open_time"], "2024-01-15 14:00:00.000");
+    assert_eq!(row["close_time"], "2024-01-15 14:00:59.999");
+    assert!(!std::path::Path::new(&config.dead_letter_path).exists());
+}
+
+#[actix_rt::test]
+async fn test_failed_batches_are_dead_lettered() {
+    let inserts = Arc::new(Mutex::new(Vec::new()));
+    let server = server(500, inserts.clone());
+    let config = config(server.url("/"));
+
+    let (writer, receiver) = ClickHouseWriter::channel(100);
+    let sink = actix_web::rt::spawn(run_clickhouse_sink(receiver, config.clone()));
+    writer.write_trade(&trade(0, 0.1));
+    writer.write_trade(&trade(1, 0.2));
+    drop(writer);
+    sink.await.unwrap();
+
+    assert_eq!(inserts.lock().unwrap().len(), 1);
+    let content = std::fs::read_to_string(&config.dead_letter_path).unwrap();
+    let letters: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0]["table"], "trades");
+    assert!(letters[0]["error"].as_str().unwrap().contains("500"));
+    let prices: Vec<f64> = letters[0]["rows"].as_array().unwrap().iter().map(|row| row["price"].as_f64().unwrap()).collect();
+    assert_eq!(prices, vec![0.1, 0.2]);
+    std::fs::remove_file(&config.dead_letter_path).unwrap();
+}
@@ -0,0 +1,144 @@
+#![cfg(all(feature = "server", feature = "client"))]
+
+use actix_web::{web, App};
+use chrono::{DurationRound, TimeDelta, Utc};
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use k_line::api::auth::Authenticator;
+use k_line::client::{ClientError, KLineClient, KLineQuery};
+use k_line::config::Config;
+use k_line::services::token_registry::TokenRegistry;
+use k_line::{configure_routes, configure_websocket_routes, KLine, KLineService, Side, TimeInterval, Transaction, WsManager};
+
+/// Start a test server with the REST and WebSocket routes
+fn start_server(kline_service: Arc<KLineService>, ws_manager: Arc<WsManager>) -> actix_test::TestServer {
+    let config = Config::default();
+    actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(ws_manager.clone()))
+            .app_data(web::Data::new(Arc::new(Authenticator::from_config(&config))))
+            .app_data(web::Data::new(Arc::new(TokenRegistry::from_config(&config))))
+            .app_data(web::Data::new(config.clone()))
+            .configure(configure_routes)
+            .configure(configure_websocket_routes)
+    })
+}
+
+fn kline(close: f64) -> KLine {
+    let open_time = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    let mut kline = KLine::new("DOGE".to_string(), open_time, TimeInterval::Minute1, 0.1, 10.0);
+    kline.update(close, 1.0);
+    kline
+}
+
+async fn next_close(receiver: &mut mpsc::UnboundedReceiver<KLine>) -> f64 {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("timed out waiting for a candle")
+        .unwrap()
+        .close
+}
+
+/// Wait until the client's session has subscribed
+async fn wait_for_subscription(ws_manager: &WsManager) {
+    for _ in 0..100 {
+        if ws_manager.session_infos().iter().any(|session| !session.subscriptions.is_empty()) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for the subscription");
+}
+
+#[actix_rt::test]
+async fn test_rest_requests() {
+    let service = Arc::new(KLineService::new());
+    let now = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1 + minute as f64, 10.0, Side::Buy);
+        transaction.timestamp = now - TimeDelta::minutes(2 - minute);
+        service.process_transaction(&transaction);
+    }
+    let server = start_server(service, Arc::new(WsManager::new()));
+    let client = KLineClient::new(server.url("/"));
+
+    let query = KLineQuery::new("DOGE", TimeInterval::Minute1).with_limit(2);
+    let page = client.get_klines(&query).await.unwrap();
+    assert_eq!((page.token.as_str(), page.interval.as_str()), ("DOGE", "1m"));
+    let closes: Vec<f64> = page.klines.iter().map(|kline| kline.close).collect();
+    assert_eq!(closes, vec![2.1, 1.1]);
+
+    // The cursor continues with the older candles
+    let next = page.next_page(&query).unwrap();
+    assert_eq!(next.before, Some(now - TimeDelta::minutes(1)));
+    let page = client.get_klines(&next).await.unwrap();
+    assert_eq!(page.klines.iter().map(|kline| kline.close).collect::<Vec<_>>(), vec![0.1]);
+    assert!(page.next_page(&next).is_none());
+
+    let latest = client.get_latest_kline("DOGE", TimeInterval::Minute1).await.unwrap().unwrap();
+    assert_eq!(latest.close, 2.1);
+    assert_eq!(client.get_ticker("DOGE").await.unwrap().unwrap().last_price, 2.1);
+    assert_eq!(client.get_tokens().await.unwrap(), vec!["DOGE".to_string()]);
+
+    // Tokens without candles are missing rather than errors
+    assert!(client.get_current_kline("SHIB", TimeInterval::Minute1).await.unwrap().is_none());
+    assert!(client.get_ticker("SHIB").await.unwrap().is_none());
+}
+
+#[actix_rt::test]
+async fn test_request_errors() {
+    let service = Arc::new(KLineService::new());
+    let server = start_server(service, Arc::new(WsManager::new()));
+    let client = KLineClient::new(server.url("/"));
+
+    // Trade-count candles are only served for configured intervals
+    let query = KLineQuery::new("DOGE", TimeInterval::Trades(100));
+    match client.get_klines(&query).await {
+        Err(ClientError::Status { status, message }) => {
+            assert_eq!(status, 400);
+            assert!(!message.is_empty());
+        }
+        other => panic!("expected a status error, got {:?}", other),
+    }
+
+    let unreachable = KLineClient::new("http://127.0.0.1:1").with_timeout(Duration::from_secs(1));
+    assert!(matches!(unreachable.get_tokens().await, Err(ClientError::Request(_))));
+}
+
+#[actix_rt::test]
+async fn test_subscribe_klines_resumes_after_reconnect() {
+    let ws_manager = Arc::new(WsManager::new());
+    let server = start_server(Arc::new(KLineService::new()), ws_manager.clone());
+    let client = KLineClient::new(server.url("/")).with_reconnect_delay(Duration::from_millis(50));
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut stream = client.subscribe_klines("DOGE", TimeInterval::Minute1);
+    actix_web::rt::spawn(async move {
+        while let Some(kline) = stream.next().await {
+            if sender.send(kline).is_err() {
+                break;
+            }
+        }
+    });
+
+    wait_for_subscription(&ws_manager).await;
+    ws_manager.broadcast_kline(&kline(1.0));
+    ws_manager.broadcast_kline(&kline(2.0));
+    assert_eq!(next_close(&mut receiver).await, 1.0);
+    assert_eq!(next_close(&mut receiver).await, 2.0);
+
+    // Updates while disconnected are replayed once after reconnecting
+    assert_eq!(ws_manager.close_all_sessions(), 1);
+    ws_manager.broadcast_kline(&kline(3.0));
+    ws_manager.broadcast_kline(&kline(4.0));
+    assert_eq!(next_close(&mut receiver).await, 3.0);
+    assert_eq!(next_close(&mut receiver).await, 4.0);
+
+    ws_manager.broadcast_kline(&kline(5.0));
+    assert_eq!(next_close(&mut receiver).await, 5.0);
+    assert!(receiver.try_recv().is_err());
+}
@@ -0,0 +1,9 @@
+use chrono::{Duration, TimeZone, Utc};
+use k_line::{Side, Transaction};
+
+/// A DOGE trade `minute` minutes after 2024-01-15 14:00 UTC
+pub fn trade(minute: i64, price: f64) -> Transaction {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+    transaction.timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap() + Duration::minutes(minute);
+    transaction
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "server")]
+
+use k_line::config::{Config, TokenConfig};
+use k_line::services::config_reload::reconcile;
+
+#[test]
+fn test_reconcile_applies_reloadable_fields() {
+    let current = Config::default();
+    let mut loaded = Config::default();
+    loaded.data_generation.interval_ms = 250;
+    loaded.logging.level = "debug".to_string();
+    loaded.tokens.supported_tokens.push(TokenConfig {
+        symbol: "WIF".to_string(),
+        base_price: 2.5,
+        volatility: 5.0,
+        price_precision: None,
+        quantity_precision: None,
+        tick_size: None,
+        arrival_rate: None,
+        volume: None,
+    });
+    loaded.server.port = 9090;
+
+    let (updated, update) = reconcile(&current, &loaded);
+    assert_eq!(
+        update.applied,
+        vec!["data_generation.interval_ms", "logging.level", "tokens.supported_tokens"]
+    );
+    assert_eq!(update.rejected, vec!["server.port"]);
+
+    assert_eq!(updated.data_generation.interval_ms, 250);
+    assert_eq!(updated.logging.level, "debug");
+    assert!(updated.get_supported_tokens().contains(&"WIF".to_string()));
+    // The bind address only changes on restart
+    assert_eq!(updated.server.port, 8080);
+}
+
+#[test]
+fn test_reconcile_without_changes() {
+    let (_, update) = reconcile(&Config::default(), &Config::default());
+    assert!(update.applied.is_empty());
+    assert!(update.rejected.is_empty());
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "server")]
+
+use actix_web::{web, App};
+use std::sync::Arc;
+use std::time::Duration;
+
+use k_line::api::auth::Authenticator;
+use k_line::config::Config;
+use k_line::conformance::{self, CaseStatus, ConformanceOptions};
+use k_line::services::token_registry::TokenRegistry;
+use k_line::{configure_websocket_routes, KLineService, MockDataGenerator, TimeInterval, WsManager};
+
+#[actix_rt::test]
+async fn test_server_passes_conformance_suite() {
+    let mut config = Config::default();
+    config.admin.api_key = Some("secret".to_string());
+
+    let kline_service = Arc::new(KLineService::new());
+    let ws_manager = Arc::new(WsManager::new());
+    let (service, manager) = (kline_service.clone(), ws_manager.clone());
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(service.clone()))
+            .app_data(web::Data::new(manager.clone()))
+            .app_data(web::Data::new(Arc::new(Authenticator::from_config(&config))))
+            .app_data(web::Data::new(Arc::new(TokenRegistry::from_config(&config))))
+            .app_data(web::Data::new(config.clone()))
+            .configure(configure_websocket_routes)
+    });
+
+    // Feed live traffic so the delivery cases run instead of skipping
+    actix_rt::spawn(async move {
+        let generator = MockDataGenerator::new();
+        loop {
+            let transaction = generator.generate_random_transaction();
+            kline_service.process_transaction(&transaction);
+            ws_manager.broadcast_transaction(&transaction);
+            for interval in TimeInterval::all() {
+                if let Some(kline) = kline_service.get_current_kline(&transaction.token, interval) {
+                    ws_manager.broadcast_kline(&kline);
+                }
+            }
+            actix_rt::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+
+    let mut options = ConformanceOptions::new(server.url("/ws"));
+    options.admin_api_key = Some("secret".to_string());
+    let report = conformance::run(&options).await;
+
+    let failures: Vec<_> = report
+        .cases
+        .iter()
+        .filter(|case| case.status != CaseStatus::Passed)
+        .collect();
+    assert!(failures.is_empty(), "conformance failures: {:#?}", failures);
+    assert_eq!(report.passed, report.cases.len());
+}
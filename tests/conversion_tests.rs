@@ -0,0 +1,93 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use k_line::config::ConversionRateConfig;
+use k_line::services::conversion::ConversionError;
+use k_line::services::QuoteConverter;
+use k_line::{KLineService, Side, TimeInterval, Transaction};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap()
+}
+
+fn trade(service: &KLineService, token: &str, timestamp: DateTime<Utc>, price: f64) {
+    let mut transaction = Transaction::new(token.to_string(), price, 10.0, Side::Buy);
+    transaction.timestamp = timestamp;
+    service.process_transaction(&transaction);
+}
+
+fn klines(service: &KLineService, token: &str) -> Vec<k_line::KLine> {
+    service.get_klines(token, TimeInterval::Minute1, start(), start() + Duration::hours(1), None)
+}
+
+#[test]
+fn test_pair_rates_follow_the_candle_time() {
+    let service = KLineService::new();
+    for (minute, price) in [(0, 0.1), (1, 0.2), (3, 0.3)] {
+        trade(&service, "DOGE-USDT", start() + Duration::minutes(minute), price);
+    }
+    trade(&service, "BTC-USDT", start() + Duration::minutes(1), 50_000.0);
+    trade(&service, "BTC-USDT", start() + Duration::minutes(2), 40_000.0);
+
+    let converter = QuoteConverter::new().with_pair("USDT", "BTC", "btc/usdt");
+    let converted = converter
+        .convert_klines(&service, "DOGE-USDT", klines(&service, "DOGE-USDT"), "btc")
+        .unwrap();
+
+    // The first candle predates the rate pair; the last takes the latest earlier rate
+    assert_eq!(converted.len(), 2);
+    assert_eq!(converted[0].token, "DOGE-BTC");
+    assert!((converted[0].close - 0.2 / 50_000.0).abs() < 1e-15);
+    assert!((converted[1].close - 0.3 / 40_000.0).abs() < 1e-15);
+    assert_eq!(converted[1].volume, 10.0);
+
+    // The rate pair converts the other way too
+    trade(&service, "ETH-BTC", start() + Duration::minutes(3), 0.05);
+    let converted = converter
+        .convert_klines(&service, "ETH-BTC", klines(&service, "ETH-BTC"), "USDT")
+        .unwrap();
+    assert_eq!(converted[0].token, "ETH-USDT");
+    assert!((converted[0].close - 2000.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_static_rates() {
+    let service = KLineService::new();
+    trade(&service, "DOGE", start(), 0.2);
+    let converter = QuoteConverter::from_config(&[ConversionRateConfig {
+        from: "USD".to_string(),
+        to: "EUR".to_string(),
+        rate: Some(0.9),
+        pair: None,
+    }]);
+
+    let converted = converter.convert_klines(&service, "DOGE", klines(&service, "DOGE"), "EUR").unwrap();
+    assert_eq!(converted[0].token, "DOGE-EUR");
+    assert!((converted[0].close - 0.18).abs() < 1e-12);
+
+    // Own quote is a no-op, unknown quotes are errors
+    let same = converter.convert_klines(&service, "DOGE", klines(&service, "DOGE"), "usd").unwrap();
+    assert_eq!(same[0].token, "DOGE");
+    assert_eq!(
+        converter.convert_klines(&service, "DOGE", Vec::new(), "JPY").unwrap_err(),
+        ConversionError::NoRate {
+            from: "USD".to_string(),
+            to: "JPY".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_converted_ticker() {
+    let service = KLineService::new();
+    let now = Utc::now();
+    trade(&service, "DOGE", now - Duration::minutes(5), 0.1);
+    trade(&service, "DOGE", now, 0.2);
+    let converter = QuoteConverter::new().with_rate("EUR", "USD", 1.25);
+
+    let ticker = converter.get_ticker(&service, "DOGE", "EUR").unwrap().unwrap();
+    assert_eq!(ticker.token, "DOGE-EUR");
+    assert!((ticker.open_price - 0.08).abs() < 1e-12);
+    assert!((ticker.last_price - 0.16).abs() < 1e-12);
+    assert!((ticker.price_change_percent - 100.0).abs() < 1e-9);
+    assert!(converter.get_ticker(&service, "SHIB", "EUR").unwrap().is_none());
+}
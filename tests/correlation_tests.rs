@@ -0,0 +1,48 @@
+use chrono::{TimeDelta, TimeZone, Utc};
+use k_line::services::correlation::{parse_window, CorrelationMatrix};
+use k_line::{KLineService, Side, TimeInterval, Transaction};
+
+#[test]
+fn test_parse_window() {
+    assert_eq!(parse_window("6h"), Some(TimeDelta::hours(6)));
+    assert_eq!(parse_window("90s"), Some(TimeDelta::seconds(90)));
+    assert_eq!(parse_window("7d"), Some(TimeDelta::days(7)));
+    for invalid in ["", "6", "h", "0m", "-1h", "6w", "1.5h"] {
+        assert_eq!(parse_window(invalid), None, "{}", invalid);
+    }
+}
+
+#[test]
+fn test_correlation_of_returns() {
+    let service = KLineService::new();
+    let end = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let prices = [1.0, 1.1, 1.05, 1.2, 1.3];
+    for (minute, price) in prices.iter().enumerate() {
+        let timestamp = end - TimeDelta::minutes(5 - minute as i64);
+        for (token, price) in [("DOGE", *price), ("SHIB", price * 2.0), ("PEPE", 1.0 / price), ("FLAT", 1.0)] {
+            let mut transaction = Transaction::new(token.to_string(), price, 1.0, Side::Buy);
+            transaction.timestamp = timestamp;
+            service.process_transaction(&transaction);
+        }
+    }
+
+    let correlation = CorrelationMatrix::compute(
+        &service,
+        service.get_available_tokens(),
+        TimeInterval::Minute1,
+        TimeDelta::minutes(10),
+        end,
+    )
+    .unwrap();
+    assert_eq!(correlation.tokens, vec!["DOGE", "FLAT", "PEPE", "SHIB"]);
+    let matrix = &correlation.matrix;
+    assert!((matrix[0][3].unwrap() - 1.0).abs() < 1e-9);
+    assert!(matrix[0][2].unwrap() < -0.9);
+    assert_eq!(matrix[0][2], matrix[2][0]);
+    // A token whose price never moves correlates with nothing
+    assert_eq!((matrix[1][1], matrix[0][1]), (None, None));
+
+    // Windows must span whole candles of a time interval, and not too many
+    assert!(CorrelationMatrix::compute(&service, vec![], TimeInterval::Trades(100), TimeDelta::hours(1), end).is_none());
+    assert!(CorrelationMatrix::compute(&service, vec![], TimeInterval::Second1, TimeDelta::days(1), end).is_none());
+}
@@ -0,0 +1,42 @@
+use chrono::{DurationRound, TimeDelta, Utc};
+use k_line::services::delta::{volume_deltas, DeltaTracker};
+use k_line::{KLine, Side, TimeInterval, Transaction};
+
+fn kline(minute: i64, trades: &[(f64, Side)]) -> KLine {
+    let timestamp = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap() + TimeDelta::minutes(minute);
+    let mut kline = KLine::new("DOGE".to_string(), timestamp, TimeInterval::Minute1, 1.0, 0.0);
+    for &(volume, side) in trades {
+        kline.apply(&Transaction::new("DOGE".to_string(), 1.0, volume, side));
+    }
+    kline
+}
+
+#[test]
+fn test_volume_deltas_accumulate() {
+    let klines = [
+        kline(0, &[(10.0, Side::Buy), (4.0, Side::Sell)]),
+        kline(1, &[(2.0, Side::Buy), (9.0, Side::Sell)]),
+    ];
+    let deltas = volume_deltas(&klines);
+
+    assert_eq!((deltas[0].buy_volume, deltas[0].sell_volume), (10.0, 4.0));
+    assert_eq!((deltas[0].delta, deltas[0].cumulative_delta), (6.0, 6.0));
+    assert_eq!((deltas[1].delta, deltas[1].cumulative_delta), (-7.0, -1.0));
+}
+
+#[test]
+fn test_tracker_counts_each_candle_once() {
+    let mut tracker = DeltaTracker::default();
+
+    // Updates of the open candle replace its delta rather than adding to it
+    assert_eq!(tracker.update(&kline(0, &[(5.0, Side::Buy)])).unwrap().cumulative_delta, 5.0);
+    let first = kline(0, &[(5.0, Side::Buy), (3.0, Side::Buy)]);
+    assert_eq!(tracker.update(&first).unwrap().cumulative_delta, 8.0);
+
+    // The next candle builds on the final state of the previous one
+    let delta = tracker.update(&kline(1, &[(2.0, Side::Sell)])).unwrap();
+    assert_eq!((delta.delta, delta.cumulative_delta), (-2.0, 6.0));
+
+    // Late updates of earlier candles are ignored
+    assert!(tracker.update(&first).is_none());
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "server")]
+
+use std::sync::Arc;
+
+use k_line::api::StreamEvent;
+use k_line::config::FanoutConfig;
+use k_line::services::fanout::{rebroadcast, FanoutEvent};
+use k_line::services::{FanoutPublisher, RedisFanout, TransactionPipeline};
+use k_line::{KLineService, Side, Transaction, WsManager};
+
+fn trade(price: f64) -> Transaction {
+    Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy)
+}
+
+#[actix_rt::test]
+async fn test_pipeline_publishes_broadcasts() {
+    let (publisher, mut receiver) = FanoutPublisher::channel(100);
+    let pipeline = TransactionPipeline::new(Arc::new(KLineService::new()), Arc::new(WsManager::new()))
+        .with_fanout(Some(publisher));
+    pipeline.handle(&trade(0.15)).unwrap();
+
+    let Some(FanoutEvent::Transaction(transaction)) = receiver.recv().await else {
+        panic!("Expected the transaction first");
+    };
+    assert_eq!(transaction.price, 0.15);
+    let mut klines = 0;
+    while let Ok(event) = receiver.try_recv() {
+        assert!(matches!(event, FanoutEvent::Kline(kline) if kline.token == "DOGE"));
+        klines += 1;
+    }
+    assert!(klines > 0);
+}
+
+#[actix_rt::test]
+async fn test_full_queue_drops_broadcasts() {
+    let (publisher, mut receiver) = FanoutPublisher::channel(1);
+    publisher.publish(FanoutEvent::Transaction(trade(1.0)));
+    publisher.publish(FanoutEvent::Transaction(trade(2.0)));
+
+    assert!(matches!(receiver.recv().await, Some(FanoutEvent::Transaction(t)) if t.price == 1.0));
+    assert!(receiver.try_recv().is_err());
+}
+
+#[actix_rt::test]
+async fn test_instances_skip_their_own_messages() {
+    let config = FanoutConfig::default();
+    let (first, second) = (RedisFanout::new(&config).unwrap(), RedisFanout::new(&config).unwrap());
+    assert_ne!(first.origin(), second.origin());
+
+    let payload = first.encode(FanoutEvent::Transaction(trade(0.15)));
+    assert!(first.decode(&payload).is_none());
+    let Some(event) = second.decode(&payload) else {
+        panic!("Expected the other instance's broadcast");
+    };
+    assert!(second.decode("not json").is_none());
+
+    // Other instances' broadcasts reach local subscribers
+    let ws_manager = WsManager::new();
+    let mut events = ws_manager.subscribe_events();
+    rebroadcast(&ws_manager, &event);
+    assert!(matches!(events.recv().await, Ok(StreamEvent::Transaction(t)) if t.price == 0.15));
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "server")]
+
+use actix_web::{test, web, App};
+use awc::ws;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+use k_line::api::graphql::{build_schema, KLineSchema};
+use k_line::{configure_routes, KLineService, Side, TimeInterval, Transaction, WsManager};
+
+type WsConnection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+fn create_schema() -> (KLineSchema, Arc<KLineService>, Arc<WsManager>) {
+    let kline_service = Arc::new(KLineService::new());
+    let ws_manager = Arc::new(WsManager::new());
+    let schema = build_schema(kline_service.clone(), ws_manager.clone());
+    (schema, kline_service, ws_manager)
+}
+
+/// Process a transaction and broadcast the resulting candles
+fn publish(kline_service: &KLineService, ws_manager: &WsManager, transaction: &Transaction) {
+    kline_service.process_transaction(transaction);
+    for interval in TimeInterval::all() {
+        if let Some(kline) = kline_service.get_current_kline(&transaction.token, interval) {
+            ws_manager.broadcast_kline(&kline);
+        }
+    }
+}
+
+async fn send_json(connection: &mut WsConnection, message: serde_json::Value) {
+    connection
+        .send(ws::Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+}
+
+/// Receive the next text frame as JSON, skipping control frames
+async fn next_json(connection: &mut WsConnection) -> serde_json::Value {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Text(bytes) = frame {
+            return serde_json::from_slice(&bytes).unwrap();
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_graphql_queries() {
+    let (schema, kline_service, _) = create_schema();
+    for price in [0.15, 0.16, 0.14] {
+        kline_service.process_transaction(&Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy));
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(schema))
+            .configure(configure_routes),
+    )
+    .await;
+
+    let query = r#"{
+        klines(token: "DOGE", interval: "1m", limit: 10) { token interval high low close volume }
+        tokens
+        ticker(token: "DOGE") { lastPrice openPrice high low volume priceChange }
+        missing: ticker(token: "SHIB") { lastPrice }
+    }"#;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/graphql")
+        .set_json(json!({ "query": query }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let data = &body["data"];
+    assert_eq!(data["klines"].as_array().unwrap().len(), 1);
+    assert_eq!(data["klines"][0]["interval"], "1m");
+    assert_eq!(data["klines"][0]["high"], 0.16);
+    assert_eq!(data["klines"][0]["volume"], 300.0);
+    assert_eq!(data["tokens"], json!(["DOGE"]));
+    assert_eq!(data["ticker"]["lastPrice"], 0.14);
+    assert_eq!(data["ticker"]["openPrice"], 0.15);
+    assert_eq!(data["ticker"]["high"], 0.16);
+    assert!(data["missing"].is_null());
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/graphql")
+        .set_json(json!({ "query": r#"{ klines(token: "DOGE", interval: "2m") { close } }"# }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"][0]["message"].as_str().unwrap().contains("Invalid interval"));
+}
+
+#[actix_rt::test]
+async fn test_graphql_kline_subscription_over_websocket() {
+    let (schema, kline_service, ws_manager) = create_schema();
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(schema.clone()))
+            .configure(configure_routes)
+    });
+
+    let (_, mut connection) = awc::Client::new()
+        .ws(server.url("/api/v1/graphql"))
+        .protocols(["graphql-transport-ws"])
+        .connect()
+        .await
+        .unwrap();
+
+    send_json(&mut connection, json!({ "type": "connection_init" })).await;
+    assert_eq!(next_json(&mut connection).await["type"], "connection_ack");
+
+    let subscribe = json!({
+        "id": "1",
+        "type": "subscribe",
+        "payload": { "query": r#"subscription { klines(token: "DOGE", interval: "1m") { token interval close } }"# }
+    });
+    send_json(&mut connection, subscribe).await;
+    // Give the subscription time to attach to the broadcast channel
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    publish(&kline_service, &ws_manager, &Transaction::new("SHIB".to_string(), 0.00005, 10.0, Side::Sell));
+    publish(&kline_service, &ws_manager, &Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    // SHIB and the other DOGE intervals are filtered out
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "next");
+    assert_eq!(message["id"], "1");
+    let kline = &message["payload"]["data"]["klines"];
+    assert_eq!(kline["token"], "DOGE");
+    assert_eq!(kline["interval"], "1m");
+    assert_eq!(kline["close"], 0.15);
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "server")]
+
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tonic::{Code, Request};
+
+use k_line::api::grpc::proto::k_line_api_server::KLineApi;
+use k_line::api::grpc::{proto, GrpcService};
+use k_line::{KLineService, Side, Transaction, WsManager};
+
+fn create_service() -> (GrpcService, Arc<KLineService>, Arc<WsManager>) {
+    let kline_service = Arc::new(KLineService::new());
+    let ws_manager = Arc::new(WsManager::new());
+    let service = GrpcService::new(kline_service.clone(), ws_manager.clone());
+    (service, kline_service, ws_manager)
+}
+
+#[tokio::test]
+async fn test_get_klines_and_latest() {
+    let (service, kline_service, _) = create_service();
+
+    for price in [0.15, 0.16, 0.14] {
+        kline_service.process_transaction(&Transaction::new("DOGE".to_string(), price, 100.0, Side::Buy));
+    }
+
+    let response = service
+        .get_klines(Request::new(proto::GetKlinesRequest {
+            token: "DOGE".to_string(),
+            interval: "1m".to_string(),
+            start_time: Some((Utc::now() - Duration::minutes(5)).timestamp_millis()),
+            end_time: None,
+            limit: Some(10),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(response.klines.len(), 1);
+    let kline = &response.klines[0];
+    assert_eq!(kline.token, "DOGE");
+    assert_eq!(kline.interval, "1m");
+    assert_eq!(kline.high, 0.16);
+    assert_eq!(kline.low, 0.14);
+    assert_eq!(kline.volume, 300.0);
+
+    let latest = service
+        .get_latest(Request::new(proto::GetLatestRequest {
+            token: "DOGE".to_string(),
+            interval: "1m".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(latest.close, 0.14);
+}
+
+#[tokio::test]
+async fn test_get_klines_limit_keeps_the_newest() {
+    let (service, kline_service, _) = create_service();
+    let start = Utc::now() - Duration::minutes(10);
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1 + minute as f64, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        kline_service.process_transaction(&transaction);
+    }
+
+    let response = service
+        .get_klines(Request::new(proto::GetKlinesRequest {
+            token: "DOGE".to_string(),
+            interval: "1m".to_string(),
+            start_time: Some((start - Duration::minutes(1)).timestamp_millis()),
+            end_time: None,
+            limit: Some(2),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // The same window as the REST API's first page, oldest first
+    let closes: Vec<f64> = response.klines.iter().map(|kline| kline.close).collect();
+    assert_eq!(closes, vec![1.1, 2.1]);
+}
+
+#[tokio::test]
+async fn test_invalid_requests() {
+    let (service, _, _) = create_service();
+
+    let status = service
+        .get_latest(Request::new(proto::GetLatestRequest {
+            token: "DOGE".to_string(),
+            interval: "2m".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let status = service
+        .get_latest(Request::new(proto::GetLatestRequest {
+            token: "DOGE".to_string(),
+            interval: "1m".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_subscribe_trades_and_klines() {
+    let (service, kline_service, ws_manager) = create_service();
+
+    let mut trades = service
+        .subscribe_trades(Request::new(proto::SubscribeTradesRequest {
+            tokens: vec!["DOGE".to_string()],
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut klines = service
+        .subscribe_klines(Request::new(proto::SubscribeKlinesRequest {
+            token: "DOGE".to_string(),
+            interval: "1m".to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let shib = Transaction::new("SHIB".to_string(), 0.00005, 10.0, Side::Sell);
+    let doge = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    for transaction in [&shib, &doge] {
+        kline_service.process_transaction(transaction);
+        ws_manager.broadcast_transaction(transaction);
+        for interval in k_line::TimeInterval::all() {
+            if let Some(kline) = kline_service.get_current_kline(&transaction.token, interval) {
+                ws_manager.broadcast_kline(&kline);
+            }
+        }
+    }
+
+    // The SHIB trade is filtered out, so the first item is the DOGE trade
+    let trade = trades.next().await.unwrap().unwrap();
+    assert_eq!(trade.token, "DOGE");
+    assert!(trade.is_buy);
+    assert_eq!(trade.side(), proto::Side::Buy);
+
+    let kline = klines.next().await.unwrap().unwrap();
+    assert_eq!(kline.token, "DOGE");
+    assert_eq!(kline.interval, "1m");
+    assert_eq!(kline.close, 0.15);
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "server")]
+
+use actix_web::{test, web, App};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use k_line::config::UnknownTokenPolicy;
+use k_line::services::{IndexPriceAggregator, TokenRegistry, TransactionPipeline};
+use k_line::{configure_routes, KLineService, Side, TimeInterval, Transaction, WsManager};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap()
+}
+
+fn trade(source: &str, offset_secs: i64, price: f64) -> Transaction {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+    transaction.timestamp = start() + Duration::seconds(offset_secs);
+    transaction.source = Some(source.to_string());
+    transaction
+}
+
+fn setup() -> (TransactionPipeline, Arc<KLineService>, Arc<IndexPriceAggregator>) {
+    let weights = BTreeMap::from([("binance".to_string(), 3.0), ("kafka".to_string(), 1.0)]);
+    let index = Arc::new(IndexPriceAggregator::new(weights, std::time::Duration::from_secs(30)));
+    let kline_service = Arc::new(KLineService::new());
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new()))
+        .with_index(Some(index.clone()));
+    (pipeline, kline_service, index)
+}
+
+fn index_close(kline_service: &KLineService) -> f64 {
+    kline_service.get_latest_kline("DOGE@index", TimeInterval::Minute1).unwrap().close
+}
+
+#[actix_web::test]
+async fn test_weighted_composite() {
+    let (pipeline, kline_service, _) = setup();
+    pipeline.handle(&trade("binance", 0, 1.0)).unwrap();
+    assert_eq!(index_close(&kline_service), 1.0);
+    pipeline.handle(&trade("kafka", 1, 2.0)).unwrap();
+    assert_eq!(index_close(&kline_service), 1.25);
+
+    // Unweighted sources and trades without a source do not move the index
+    pipeline.handle(&trade("mock", 2, 9.0)).unwrap();
+    pipeline.handle(&Transaction::new("DOGE".to_string(), 9.0, 1.0, Side::Sell)).unwrap();
+    let kline = kline_service.get_latest_kline("DOGE@index", TimeInterval::Minute1).unwrap();
+    assert_eq!((kline.close, kline.volume), (1.25, 20.0));
+
+    // A source's price stops counting once it is stale
+    pipeline.handle(&trade("kafka", 40, 4.0)).unwrap();
+    assert_eq!(index_close(&kline_service), 4.0);
+}
+
+#[actix_web::test]
+async fn test_strict_service_builds_the_index_of_allowed_tokens() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::Reject, ["DOGE".to_string()]));
+    let weights = BTreeMap::from([("binance".to_string(), 1.0)]);
+    let index = Arc::new(IndexPriceAggregator::new(weights, std::time::Duration::from_secs(30)));
+    let kline_service = Arc::new(KLineService::new().with_allowlist(registry));
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new()))
+        .with_index(Some(index));
+
+    pipeline.handle(&trade("binance", 0, 1.0)).unwrap();
+    assert_eq!(index_close(&kline_service), 1.0);
+    pipeline.backfill([trade("binance", 1, 3.0)].into_iter());
+    assert_eq!(index_close(&kline_service), 3.0);
+
+    // The series of tokens outside the allowlist stay rejected
+    assert!(kline_service.accepts_token("DOGE@index"));
+    assert!(!kline_service.accepts_token("SHIB@index"));
+}
+
+#[actix_web::test]
+async fn test_backfill_builds_the_index() {
+    let (pipeline, kline_service, _) = setup();
+    pipeline.backfill([trade("binance", 0, 1.0), trade("kafka", 1, 5.0)].into_iter());
+    assert_eq!(index_close(&kline_service), 2.0);
+}
+
+#[actix_web::test]
+async fn test_index_endpoint() {
+    let (pipeline, kline_service, index) = setup();
+    pipeline.handle(&trade("binance", 0, 1.0)).unwrap();
+    pipeline.handle(&trade("kafka", 45, 3.0)).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(index))
+            .configure(configure_routes),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/api/v1/index?token=doge").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["index_token"], "DOGE@index");
+    assert_eq!(body["price"], 3.0);
+    assert_eq!(body["components"][0]["source"], "binance");
+    assert_eq!(body["components"][0]["stale"], true);
+    assert_eq!(body["components"][1]["stale"], false);
+
+    // The composite candles are a series of their own
+    let req = test::TestRequest::get().uri("/api/v1/klines?token=doge@INDEX").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["token"], "DOGE@index");
+    assert_eq!(body["data"][0]["close"], 3.0);
+
+    let req = test::TestRequest::get().uri("/api/v1/index?token=SHIB").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
@@ -0,0 +1,221 @@
+use chrono::{Duration, Utc};
+use k_line::services::indicator_stream::{IndicatorKind, IndicatorSpec, IndicatorStream};
+use k_line::services::indicators::{self, BollingerParams, IndicatorCache, MacdParams, MovingAverageType};
+use k_line::{KLine, KLineService, Side, TimeInterval, Transaction};
+
+fn candles(closes: &[f64]) -> Vec<KLine> {
+    let start = Utc::now() - Duration::hours(1);
+    closes
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| {
+            let mut kline = KLine::new(
+                "DOGE".to_string(),
+                start + Duration::minutes(i as i64),
+                TimeInterval::Minute1,
+                close,
+                1.0,
+            );
+            kline.close();
+            kline
+        })
+        .collect()
+}
+
+fn assert_close(actual: &[f64], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn test_sma() {
+    let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+    assert_close(&indicators::sma(&values, 3), &[2.0, 3.0, 4.0]);
+    assert_close(&indicators::sma(&values, 1), &values);
+    assert!(indicators::sma(&values, 6).is_empty());
+    assert!(indicators::sma(&values, 0).is_empty());
+}
+
+#[test]
+fn test_ema_is_seeded_with_sma() {
+    let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+    // alpha = 0.5: 2.0, 2.0 + 0.5 * (4 - 2) = 3.0, 3.0 + 0.5 * (5 - 3) = 4.0
+    assert_close(&indicators::ema(&values, 3), &[2.0, 3.0, 4.0]);
+
+    let values = [10.0, 10.0, 10.0, 20.0];
+    // alpha = 2 / 4: 10.0, 10.0 + 0.5 * 10 = 15.0
+    assert_close(&indicators::ema(&values, 3), &[10.0, 15.0]);
+    assert!(indicators::ema(&values, 5).is_empty());
+}
+
+#[test]
+fn test_moving_average_points_align_with_candles() {
+    let klines = candles(&[1.0, 2.0, 3.0, 4.0]);
+    let points = indicators::moving_average(&klines, 2, MovingAverageType::Sma);
+
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[0].timestamp, klines[1].timestamp);
+    assert_eq!(points[2].timestamp, klines[3].timestamp);
+    assert_close(&points.iter().map(|p| p.value).collect::<Vec<_>>(), &[1.5, 2.5, 3.5]);
+}
+
+#[test]
+fn test_rsi_uses_wilder_smoothing() {
+    let values = [1.0, 2.0, 1.0, 2.0, 3.0];
+    // Changes +1, -1, +1, +1; first averages 0.5 / 0.5, then 0.75 / 0.25 and 0.875 / 0.125
+    assert_close(&indicators::rsi(&values, 2), &[50.0, 75.0, 87.5]);
+
+    assert_close(&indicators::rsi(&[1.0, 2.0, 3.0], 2), &[100.0]);
+    assert_close(&indicators::rsi(&[1.0, 1.0, 1.0], 2), &[50.0]);
+    assert!(indicators::rsi(&[1.0, 2.0], 2).is_empty());
+
+    let klines = candles(&values);
+    let points = indicators::relative_strength(&klines, 2);
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[0].timestamp, klines[2].timestamp);
+}
+
+#[test]
+fn test_macd() {
+    let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let params = MacdParams { fast: 2, slow: 3, signal: 2 };
+    // EMA(2): 1.5, 2.5, 3.5, 4.5, 5.5; EMA(3): 2, 3, 4, 5; MACD line 0.5 throughout
+    let macd = indicators::macd(&values, params);
+    assert_eq!(macd.len(), 3);
+    for (line, signal, histogram) in macd {
+        assert!((line - 0.5).abs() < 1e-9);
+        assert!((signal - 0.5).abs() < 1e-9);
+        assert!(histogram.abs() < 1e-9);
+    }
+
+    assert!(indicators::macd(&values, MacdParams { fast: 3, slow: 3, signal: 2 }).is_empty());
+    assert!(indicators::macd(&values[..3], params).is_empty());
+}
+
+#[test]
+fn test_bollinger_bands() {
+    let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+    // Mean 5, population standard deviation 2
+    let bands = indicators::bollinger_bands(&values, BollingerParams { period: 8, std_dev: 2.0 });
+    assert_eq!(bands.len(), 1);
+    let (middle, upper, lower) = bands[0];
+    assert_close(&[middle, upper, lower], &[5.0, 9.0, 1.0]);
+
+    let bands = indicators::bollinger_bands(&[1.0, 1.0, 1.0], BollingerParams { period: 2, std_dev: 2.0 });
+    assert_eq!(bands, vec![(1.0, 1.0, 1.0), (1.0, 1.0, 1.0)]);
+}
+
+#[test]
+fn test_average_true_range_includes_gaps() {
+    // Flat candles: every true range is the gap from the previous close
+    let klines = candles(&[10.0, 12.0, 11.0, 15.0]);
+    assert_close(&indicators::average_true_range(&klines, 2), &[1.5, 2.75]);
+    assert!(indicators::average_true_range(&klines, 4).is_empty());
+}
+
+#[test]
+fn test_realized_volatility() {
+    // Returns alternate between ln(2) and -ln(2)
+    let values = [1.0, 2.0, 1.0, 2.0];
+    let ln2 = 2.0_f64.ln();
+    let expected = (2.0 * ln2 * ln2).sqrt();
+    assert_close(&indicators::realized_volatility(&values, 2), &[expected, expected]);
+    assert_close(&indicators::realized_volatility(&[1.0, 1.0, 1.0], 2), &[0.0]);
+    assert!(indicators::realized_volatility(&values, 1).is_empty());
+    assert!(indicators::realized_volatility(&values, 4).is_empty());
+
+    let factor = indicators::annualization_factor(TimeInterval::Hour1).unwrap();
+    assert!((factor - (365.0_f64 * 24.0).sqrt()).abs() < 1e-9);
+    assert!(indicators::annualization_factor(TimeInterval::Trades(100)).is_none());
+}
+
+#[test]
+fn test_indicator_cache_refreshes_on_close() {
+    let service = KLineService::new();
+    let cache = IndicatorCache::default();
+    let params = BollingerParams { period: 2, std_dev: 1.0 };
+    let start = Utc::now() - Duration::hours(1);
+    let trade = |minute: i64, price: f64| {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    };
+
+    assert!(cache.bollinger(&service, "DOGE", TimeInterval::Minute1, params, 10).is_empty());
+
+    for (minute, price) in [1.0, 3.0, 5.0].into_iter().enumerate() {
+        trade(minute as i64, price);
+    }
+    let bands = cache.bollinger(&service, "DOGE", TimeInterval::Minute1, params, 10);
+    assert_eq!(bands.len(), 1);
+    assert_close(&[bands[0].middle, bands[0].upper, bands[0].lower], &[2.0, 3.0, 1.0]);
+
+    // Trades in the open candle leave the cached series as it is
+    trade(2, 7.0);
+    assert_eq!(cache.bollinger(&service, "DOGE", TimeInterval::Minute1, params, 10), bands);
+
+    // Once another candle closes the series is recomputed
+    trade(3, 7.0);
+    let bands = cache.bollinger(&service, "DOGE", TimeInterval::Minute1, params, 10);
+    assert_eq!(bands.len(), 2);
+    assert_close(&[bands[1].middle], &[5.0]);
+    assert_eq!(cache.bollinger(&service, "DOGE", TimeInterval::Minute1, params, 1).len(), 1);
+}
+
+#[test]
+fn test_indicator_spec_defaults_and_topics() {
+    let spec = IndicatorSpec::from_parts(IndicatorKind::Macd, None, None, None, None, None).unwrap();
+    assert_eq!(spec, IndicatorSpec::Macd(MacdParams::default()));
+    assert_eq!(spec.to_string().parse::<IndicatorSpec>(), Ok(spec));
+    let spec = IndicatorSpec::from_parts(IndicatorKind::Bollinger, Some(10), None, None, None, Some(1.5)).unwrap();
+    assert_eq!(spec.to_string(), "bollinger:10:1.5");
+    assert_eq!("rsi:14".parse::<IndicatorSpec>(), Ok(IndicatorSpec::Rsi { period: 14 }));
+
+    assert!(IndicatorSpec::from_parts(IndicatorKind::Volatility, Some(1), None, None, None, None).is_err());
+    assert!(IndicatorSpec::from_parts(IndicatorKind::Sma, Some(501), None, None, None, None).is_err());
+    assert!(IndicatorSpec::from_parts(IndicatorKind::Bollinger, None, None, None, None, Some(0.0)).is_err());
+}
+
+#[test]
+fn test_indicator_stream_matches_series() {
+    let service = KLineService::new();
+    let start = Utc::now() - Duration::hours(1);
+    let prices = [10.0, 12.0, 11.0, 15.0, 14.0, 16.0, 13.0, 17.0, 18.0];
+    for (minute, price) in prices.iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), *price, 1.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute as i64);
+        service.process_transaction(&transaction);
+    }
+    let closed = service.get_closed_klines("DOGE", TimeInterval::Minute1, 100);
+    let open = service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    let mut all = closed.clone();
+    all.push(open.clone());
+
+    let cases: Vec<(IndicatorSpec, Vec<f64>)> = vec![
+        (IndicatorSpec::Rsi { period: 3 }, indicators::rsi(&indicators::closes(&all), 3)),
+        (IndicatorSpec::Sma { period: 3 }, indicators::sma(&indicators::closes(&all), 3)),
+        (IndicatorSpec::Ema { period: 3 }, indicators::ema(&indicators::closes(&all), 3)),
+        (IndicatorSpec::Atr { period: 3 }, indicators::average_true_range(&all, 3)),
+        (IndicatorSpec::Volatility { period: 3 }, indicators::realized_volatility(&indicators::closes(&all), 3)),
+    ];
+    for (spec, series) in cases {
+        let mut stream = IndicatorStream::new(spec);
+        // The open candle's value extends the series of closed candles
+        let value = stream.update(&service, &open).unwrap().value;
+        assert!((value - series[series.len() - 1]).abs() < 1e-9, "{}", spec);
+        // A repeated close returns the value at it without applying it again
+        let last_closed = closed.last().unwrap();
+        let value = stream.update(&service, last_closed).unwrap().value;
+        assert!((value - series[series.len() - 2]).abs() < 1e-9, "{}", spec);
+        assert!(stream.update(&service, &closed[0]).is_none());
+    }
+
+    let params = MacdParams { fast: 2, slow: 3, signal: 2 };
+    let expected = *indicators::macd(&indicators::closes(&all), params).last().unwrap();
+    let values = IndicatorStream::new(IndicatorSpec::Macd(params)).update(&service, &open).unwrap();
+    assert!((values.value - expected.0).abs() < 1e-9);
+    assert!((values.signal.unwrap() - expected.1).abs() < 1e-9);
+    assert!((values.histogram.unwrap() - expected.2).abs() < 1e-9);
+}
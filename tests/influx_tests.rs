@@ -0,0 +1,139 @@
+#![cfg(feature = "server")]
+
+mod common;
+
+use actix_web::{web, App, HttpRequest, HttpResponse};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+
+use common::trade;
+use k_line::config::InfluxConfig;
+use k_line::models::Ticker;
+use k_line::services::influx::{kline_line, run_influx_exporter, ticker_line, InfluxEndpoint};
+use k_line::services::{InfluxWriter, TransactionPipeline};
+use k_line::{KLine, KLineService, TimeInterval, WsManager};
+
+fn closed_kline() -> KLine {
+    let mut kline = KLine::new("DOGE".to_string(), trade(0, 0.1).timestamp, TimeInterval::Minute1, 0.1, 10.0);
+    kline.update(0.3, 5.0);
+    kline.close();
+    kline
+}
+
+fn config(endpoint: String) -> InfluxConfig {
+    InfluxConfig {
+        enabled: true,
+        endpoint,
+        flush_interval_ms: 60_000,
+        ..InfluxConfig::default()
+    }
+}
+
+#[test]
+fn test_line_protocol() {
+    let kline = closed_kline();
+    assert_eq!(
+        kline_line("kline", &kline),
+        "kline,token=DOGE,interval=1m open=0.1,high=0.3,low=0.1,close=0.3,volume=15,buy_volume=0,sell_volume=0,is_partial=false 1705327200000000000"
+    );
+
+    let ticker = Ticker::from_klines("DOGE/USD token", &[kline], trade(1, 0.1).timestamp).unwrap();
+    let line = ticker_line("meme ticker", &ticker);
+    assert!(line.starts_with("meme\\ ticker,token=DOGE/USD\\ token last_price=0.3,open_price=0.1,"));
+    assert!(line.ends_with(" 1705327260000000000"));
+
+    // Line protocol has no NaN or infinity, so such fields are left out
+    let mut empty = ticker.clone();
+    empty.price_change_percent = f64::NAN;
+    assert!(!ticker_line("ticker", &empty).contains("price_change_percent"));
+}
+
+#[test]
+fn test_endpoints() {
+    assert_eq!(
+        InfluxEndpoint::parse("tcp://telegraf:8094").unwrap(),
+        InfluxEndpoint::Tcp("telegraf:8094".to_string())
+    );
+    assert_eq!(
+        InfluxEndpoint::parse("unix:///run/telegraf.sock").unwrap(),
+        InfluxEndpoint::Unix("/run/telegraf.sock".into())
+    );
+    assert!(InfluxEndpoint::parse("http://localhost:8086/api/v2/write?bucket=k-line").is_ok());
+    assert!(InfluxEndpoint::parse("tcp://:8094").is_err());
+    assert!(InfluxEndpoint::parse("telegraf:8094").is_err());
+}
+
+#[actix_rt::test]
+async fn test_pipeline_queues_closed_candles() {
+    let (writer, mut receiver) = InfluxWriter::channel(&InfluxConfig::default());
+    let pipeline =
+        TransactionPipeline::new(Arc::new(KLineService::new()), Arc::new(WsManager::new())).with_influx(Some(writer));
+    pipeline.handle(&trade(0, 0.1)).unwrap();
+    assert!(receiver.try_recv().is_err());
+
+    pipeline.handle(&trade(1, 0.2)).unwrap();
+    let lines: Vec<String> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+    assert!(lines.iter().any(|line| line.starts_with("kline,token=DOGE,interval=1m open=0.1,")));
+}
+
+#[actix_rt::test]
+async fn test_lines_are_written_to_a_tcp_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(format!("tcp://{}", listener.local_addr().unwrap()));
+
+    let (writer, receiver) = InfluxWriter::channel(&config);
+    let exporter = actix_web::rt::spawn(run_influx_exporter(receiver, config));
+    writer.write_kline(&closed_kline());
+    writer.write_kline(&closed_kline());
+    // Closing the queue writes what is left and closes the connection
+    drop(writer);
+
+    let (mut stream, _) = listener.accept().await.unwrap();
+    exporter.await.unwrap();
+    let mut received = String::new();
+    stream.read_to_string(&mut received).await.unwrap();
+    assert_eq!(received.lines().count(), 2);
+    assert!(received.lines().all(|line| line.starts_with("kline,token=DOGE,interval=1m ")));
+}
+
+#[actix_rt::test]
+async fn test_lines_are_posted_with_the_token() {
+    let received: Arc<Mutex<Vec<(String, String)>>> = Arc::new(Mutex::new(Vec::new()));
+    let server = {
+        let received = received.clone();
+        actix_test::start(move || {
+            let received = received.clone();
+            App::new().route(
+                "/api/v2/write",
+                web::post().to(move |request: HttpRequest, body: String| {
+                    let authorization = request
+                        .headers()
+                        .get("Authorization")
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    received.lock().unwrap().push((authorization, body));
+                    async { HttpResponse::NoContent().finish() }
+                }),
+            )
+        })
+    };
+    let config = InfluxConfig {
+        token: Some("secret".to_string()),
+        batch_size: 1,
+        ..config(server.url("/api/v2/write?org=k-line&bucket=k-line"))
+    };
+
+    let (writer, receiver) = InfluxWriter::channel(&config);
+    let exporter = actix_web::rt::spawn(run_influx_exporter(receiver, config));
+    writer.write_kline(&closed_kline());
+    writer.write_kline(&closed_kline());
+    drop(writer);
+    exporter.await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].0, "Token secret");
+    assert!(received[0].1.starts_with("kline,token=DOGE") && received[0].1.ends_with('\n'));
+}
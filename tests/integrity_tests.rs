@@ -0,0 +1,121 @@
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use k_line::services::integrity::{CandleField, GapKind, IntegrityReport};
+use k_line::services::KLineAmendment;
+use k_line::{KLine, KLineService, Side, TimeInterval, Transaction};
+
+fn service_with_trades(start: DateTime<Utc>) -> KLineService {
+    let service = KLineService::new();
+    for (seconds, price) in [(10, 0.1), (90, 0.12), (180, 0.09), (420, 0.11), (720, 0.1)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + TimeDelta::seconds(seconds);
+        service.process_transaction(&transaction);
+    }
+    service
+}
+
+#[test]
+fn test_aggregated_candles_are_consistent() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let service = service_with_trades(start);
+    let end = start + TimeDelta::minutes(12);
+
+    for (interval, source) in [
+        (TimeInterval::Minute1, TimeInterval::Second1),
+        (TimeInterval::Minute5, TimeInterval::Minute1),
+        (TimeInterval::Minute15, TimeInterval::Minute5),
+        (TimeInterval::Hour1, TimeInterval::Minute15),
+    ] {
+        let report = IntegrityReport::verify(&service, "DOGE", interval, start, end, end).unwrap();
+        assert_eq!(report.source_interval, source);
+        assert!(report.consistent, "{:?}", report);
+    }
+
+    // The candle still open is not compared
+    let report = IntegrityReport::verify(&service, "DOGE", TimeInterval::Minute5, start, end, end).unwrap();
+    assert_eq!(report.candles_checked, 2);
+
+    // Nothing is finer than 1s, and ranges are bounded
+    assert!(IntegrityReport::verify(&service, "DOGE", TimeInterval::Second1, start, end, end).is_none());
+    assert!(IntegrityReport::verify(&service, "DOGE", TimeInterval::Minute1, end - TimeDelta::days(30), end, end).is_none());
+}
+
+#[test]
+fn test_mismatches_and_gaps_are_reported() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let service = service_with_trades(start);
+    let end = start + TimeDelta::minutes(12);
+
+    let minute = service
+        .get_klines("DOGE", TimeInterval::Minute1, start + TimeDelta::minutes(1), start + TimeDelta::minutes(1), None)
+        .remove(0);
+    service
+        .amend_kline(&KLineAmendment {
+            token: minute.token,
+            interval: minute.interval,
+            timestamp: minute.timestamp,
+            open: minute.open,
+            high: 0.15,
+            low: minute.low,
+            close: minute.close,
+            volume: 25.0,
+            buy_volume: None,
+            sell_volume: None,
+        })
+        .unwrap();
+
+    // A 5m candle without its minutes, and minutes without their 5m candle
+    let mut orphan = KLine::new("DOGE".to_string(), start - TimeDelta::minutes(10), TimeInterval::Minute5, 0.1, 1.0);
+    orphan.is_closed = true;
+    service.insert_kline(orphan);
+    let mut orphan = KLine::new("DOGE".to_string(), start - TimeDelta::minutes(3), TimeInterval::Minute1, 0.1, 1.0);
+    orphan.is_closed = true;
+    service.insert_kline(orphan);
+
+    let report =
+        IntegrityReport::verify(&service, "DOGE", TimeInterval::Minute5, start - TimeDelta::minutes(10), end, end).unwrap();
+    assert!(!report.consistent);
+    let mismatches: Vec<_> = report.mismatches.iter().map(|mismatch| (mismatch.field, mismatch.stored, mismatch.expected)).collect();
+    assert_eq!(mismatches, [(CandleField::High, 0.12, 0.15), (CandleField::Volume, 30.0, 45.0)]);
+    assert!(report.mismatches.iter().all(|mismatch| mismatch.timestamp == start));
+
+    let gaps: Vec<_> = report.gaps.iter().map(|gap| (gap.timestamp, gap.kind)).collect();
+    assert_eq!(
+        gaps,
+        [
+            (start - TimeDelta::minutes(10), GapKind::MissingConstituents),
+            (start - TimeDelta::minutes(5), GapKind::MissingCandle),
+        ]
+    );
+}
+
+#[test]
+fn test_small_price_mismatches_are_reported() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let service = KLineService::new();
+    for seconds in [10, 70, 310] {
+        let mut transaction = Transaction::new("PEPE".to_string(), 0.000001, 10.0, Side::Buy);
+        transaction.timestamp = start + TimeDelta::seconds(seconds);
+        service.process_transaction(&transaction);
+    }
+
+    // A hundredth of a percent off is a mismatch, however small the price
+    service
+        .amend_kline(&KLineAmendment {
+            token: "PEPE".to_string(),
+            interval: TimeInterval::Minute1,
+            timestamp: start + TimeDelta::minutes(1),
+            open: 0.000001,
+            high: 0.0000010001,
+            low: 0.000001,
+            close: 0.000001,
+            volume: 10.0,
+            buy_volume: None,
+            sell_volume: None,
+        })
+        .unwrap();
+
+    let end = start + TimeDelta::minutes(6);
+    let report = IntegrityReport::verify(&service, "PEPE", TimeInterval::Minute5, start, end, end).unwrap();
+    let mismatches: Vec<_> = report.mismatches.iter().map(|mismatch| (mismatch.field, mismatch.stored, mismatch.expected)).collect();
+    assert_eq!(mismatches, [(CandleField::High, 0.000001, 0.0000010001)]);
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "server")]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::trade;
+use k_line::config::StorageConfig;
+use k_line::services::{KLineStoreWriter, RedisKLineStore, TransactionPipeline};
+use k_line::{KLineService, TimeInterval, WsManager};
+
+#[actix_rt::test]
+async fn test_pipeline_writes_candle_updates() {
+    let (writer, mut receiver) = KLineStoreWriter::channel(100);
+    let pipeline = TransactionPipeline::new(Arc::new(KLineService::new()), Arc::new(WsManager::new()))
+        .with_store(Some(writer));
+    pipeline.handle(&trade(0, 0.1)).unwrap();
+    pipeline.handle(&trade(1, 0.2)).unwrap();
+
+    let mut updates = Vec::new();
+    while let Ok(kline) = receiver.try_recv() {
+        updates.push(kline);
+    }
+    let minute = |closed: bool| {
+        updates
+            .iter()
+            .filter(|kline| kline.interval == TimeInterval::Minute1 && kline.is_closed == closed)
+            .map(|kline| kline.close)
+            .collect::<Vec<_>>()
+    };
+    // The open candle after each trade, and the candle the second trade closed
+    assert_eq!(minute(false), vec![0.1, 0.2]);
+    assert_eq!(minute(true), vec![0.1]);
+}
+
+#[actix_rt::test]
+async fn test_full_queue_drops_updates() {
+    let service = KLineService::new();
+    service.process_transaction(&trade(0, 0.1));
+    let kline = service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+
+    let (writer, mut receiver) = KLineStoreWriter::channel(1);
+    writer.write(kline.clone());
+    writer.write(kline);
+    assert!(receiver.recv().await.is_some());
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn test_stored_candles_mirror_into_memory() {
+    let leader = KLineService::new();
+    leader.process_transaction(&trade(0, 0.1));
+    leader.process_transaction(&trade(0, 0.3));
+    let kline = leader.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+
+    let store = RedisKLineStore::new(&StorageConfig::default()).unwrap();
+    assert_eq!(store.key("DOGE", TimeInterval::Minute1), "kline:DOGE:1m");
+    assert_eq!(store.tokens_key(), "kline:tokens");
+
+    let stored = RedisKLineStore::decode(&RedisKLineStore::encode(&kline)).unwrap();
+    assert_eq!((stored.timestamp, stored.high, stored.close, stored.volume), (kline.timestamp, 0.3, 0.3, 20.0));
+    assert!(RedisKLineStore::decode("not json").is_none());
+
+    // A follower serves the mirrored candle, replaced as it updates
+    let follower = KLineService::new();
+    assert!(follower.insert_kline(stored.clone()));
+    let mut updated = stored;
+    updated.close = 0.25;
+    assert!(follower.insert_kline(updated));
+    let klines = follower.get_klines("DOGE", TimeInterval::Minute1, kline.timestamp, kline.timestamp, None);
+    assert_eq!(klines.len(), 1);
+    assert_eq!(klines[0].close, 0.25);
+    assert_eq!(follower.get_available_tokens(), vec!["DOGE".to_string()]);
+
+    // Trade-count candles are only mirrored where they are aggregated
+    let mut ticks = kline;
+    ticks.interval = "100t".parse().unwrap();
+    assert!(!follower.insert_kline(ticks));
+}
@@ -1,5 +1,9 @@
-use chrono::{Duration, Utc};
-use k_line::{KLine, KLineService, MockDataGenerator, TimeInterval, Transaction};
+use chrono::{Duration, DurationRound, TimeDelta, Utc};
+use std::sync::Arc;
+use k_line::config::UnknownTokenPolicy;
+use k_line::services::kline::{token_shard, SnapshotError};
+use k_line::services::{IngestError, KLineAmendment, KLineCursor, KLineSnapshot, TokenRegistry};
+use k_line::{KLine, KLineService, MockDataGenerator, Side, TimeInterval, Transaction};
 
 #[test]
 fn test_kline_creation() {
@@ -44,6 +48,23 @@ fn test_kline_update() {
     assert_eq!(kline.volume, 175.0);
 }
 
+#[test]
+fn test_buy_and_sell_volume_breakdown() {
+    let service = KLineService::new().with_activity_intervals([TimeInterval::Trades(2)]);
+    for (volume, side) in [(10.0, Side::Buy), (4.0, Side::Sell), (6.0, Side::Buy)] {
+        service.process_transaction(&Transaction::new("DOGE".to_string(), 0.15, volume, side));
+    }
+
+    let kline = service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    assert_eq!((kline.volume, kline.buy_volume, kline.sell_volume), (20.0, 16.0, 4.0));
+    // A trade-count candle opened by a trade counts it on its side too
+    let kline = service.get_current_kline("DOGE", TimeInterval::Trades(2)).unwrap();
+    assert_eq!((kline.volume, kline.buy_volume, kline.sell_volume), (6.0, 6.0, 0.0));
+
+    let json = serde_json::to_value(&kline).unwrap();
+    assert_eq!((json["buy_volume"].as_f64(), json["sell_volume"].as_f64()), (Some(6.0), Some(0.0)));
+}
+
 #[test]
 fn test_time_interval_parsing() {
     assert_eq!("1s".parse::<TimeInterval>(), Ok(TimeInterval::Second1));
@@ -60,7 +81,7 @@ fn test_time_interval_parsing() {
 #[test]
 fn test_kline_service_basic() {
     let service = KLineService::new();
-    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, true);
+    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
 
     // Process transaction
     service.process_transaction(&transaction);
@@ -81,9 +102,9 @@ fn test_kline_service_multiple_transactions() {
     let service = KLineService::new();
 
     // Create multiple transactions
-    let t1 = Transaction::new("DOGE".to_string(), 0.15, 100.0, true);
-    let t2 = Transaction::new("DOGE".to_string(), 0.16, 50.0, true);
-    let t3 = Transaction::new("DOGE".to_string(), 0.14, 75.0, false);
+    let t1 = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    let t2 = Transaction::new("DOGE".to_string(), 0.16, 50.0, Side::Buy);
+    let t3 = Transaction::new("DOGE".to_string(), 0.14, 75.0, Side::Sell);
 
     // Process transactions
     service.process_transaction(&t1);
@@ -108,7 +129,7 @@ fn test_kline_service_get_klines() {
     let now = Utc::now();
 
     // Create transaction
-    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, true);
+    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
     service.process_transaction(&transaction);
 
     // Get K-lines for the last hour
@@ -128,8 +149,8 @@ fn test_kline_service_available_tokens() {
     assert!(service.get_available_tokens().is_empty());
 
     // Add transactions for different tokens
-    let t1 = Transaction::new("DOGE".to_string(), 0.15, 100.0, true);
-    let t2 = Transaction::new("SHIB".to_string(), 0.00001, 1000.0, true);
+    let t1 = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    let t2 = Transaction::new("SHIB".to_string(), 0.00001, 1000.0, Side::Buy);
 
     service.process_transaction(&t1);
     service.process_transaction(&t2);
@@ -141,6 +162,50 @@ fn test_kline_service_available_tokens() {
     assert!(tokens.contains(&"SHIB".to_string()));
 }
 
+#[test]
+fn test_strict_allowlist() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::Reject, vec!["DOGE".to_string()]));
+    let service = KLineService::new().with_allowlist(registry.clone());
+
+    let doge = Transaction::new("DOGE".to_string(), 0.1, 100.0, Side::Buy);
+    assert!(service.try_process_transaction(&doge).is_ok());
+
+    let unknown = Transaction::new("TOKEN0".to_string(), 1.0, 100.0, Side::Buy);
+    assert_eq!(
+        service.try_process_transaction(&unknown).unwrap_err(),
+        IngestError::UnknownToken("TOKEN0".to_string())
+    );
+    assert!(service.process_transaction(&unknown).is_empty());
+    assert_eq!(service.get_available_tokens(), vec!["DOGE"]);
+
+    registry.register("TOKEN0");
+    assert!(service.try_process_transaction(&unknown).is_ok());
+    assert_eq!(service.get_available_tokens().len(), 2);
+
+    // Permissive services accept any token
+    assert!(KLineService::new().try_process_transaction(&unknown).is_ok());
+}
+
+#[test]
+fn test_invalid_transactions_are_refused() {
+    let service = KLineService::new();
+    let valid = Transaction::new("DOGE".to_string(), 0.1, 100.0, Side::Buy);
+    service.process_transaction(&valid);
+
+    for (price, volume) in [(f64::NAN, 100.0), (0.0, 100.0), (0.2, -1.0), (0.2, f64::INFINITY)] {
+        let transaction = Transaction::new("DOGE".to_string(), price, volume, Side::Buy);
+        assert!(matches!(
+            service.try_process_transaction(&transaction),
+            Err(IngestError::InvalidTransaction(_))
+        ));
+    }
+
+    let kline = service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    assert_eq!(kline.high, 0.1);
+    assert_eq!(kline.low, 0.1);
+    assert_eq!(kline.volume, 100.0);
+}
+
 #[test]
 fn test_mock_data_generator() {
     let generator = MockDataGenerator::new();
@@ -165,6 +230,28 @@ fn test_mock_data_generator() {
     assert!(tokens.contains(&"DOGE".to_string()));
 }
 
+#[test]
+fn test_runtime_listings_survive_config_reload() {
+    let mut generator = MockDataGenerator::new();
+    let listings = generator.listings();
+
+    assert!(listings.list("BONK", 0.00002));
+    assert!(!listings.list("BONK", 0.00002));
+    assert!(listings.delist("SHIB"));
+    assert!(!listings.delist("SHIB"));
+
+    generator.apply_config(&k_line::config::Config::default());
+    let tokens = generator.get_available_tokens();
+    assert!(tokens.contains(&"BONK".to_string()));
+    assert!(tokens.contains(&"DOGE".to_string()));
+    assert!(!tokens.contains(&"SHIB".to_string()));
+
+    // Relisting a delisted token uses the new base price
+    assert!(listings.list("SHIB", 1.0));
+    let transaction = generator.generate_transaction("SHIB").unwrap();
+    assert!(transaction.price > 0.5);
+}
+
 #[test]
 fn test_mock_data_historical() {
     let generator = MockDataGenerator::new();
@@ -177,3 +264,381 @@ fn test_mock_data_historical() {
         assert!(transaction.volume > 0.0);
     }
 }
+
+#[test]
+fn test_remove_klines_before() {
+    let service = KLineService::new();
+    let now = Utc::now();
+
+    let mut old = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    old.timestamp = now - Duration::hours(30);
+    service.process_transaction(&old);
+    service.process_transaction(&Transaction::new("DOGE".to_string(), 0.16, 100.0, Side::Buy));
+
+    let removed = service.remove_klines_before(now - Duration::hours(24));
+    assert_eq!(removed, TimeInterval::all().len());
+
+    for interval in TimeInterval::all() {
+        let klines = service.get_klines("DOGE", interval, now - Duration::hours(48), now + Duration::hours(1), None);
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].close, 0.16);
+    }
+}
+
+#[test]
+fn test_klines_page_cursors() {
+    let service = KLineService::new();
+    let start = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap() - Duration::minutes(10);
+
+    // One 1m candle per minute for ten minutes
+    for minute in 0..10 {
+        let mut transaction = Transaction::new("DOGE".to_string(), minute as f64 + 1.0, 100.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(minute);
+        service.process_transaction(&transaction);
+    }
+
+    // Paging backwards from the end visits every candle once, newest page first
+    let mut cursor = KLineCursor::Before(start + Duration::hours(1));
+    let mut pages = Vec::new();
+    loop {
+        let page = service.get_klines_page("DOGE", TimeInterval::Minute1, cursor, 4);
+        pages.push(page.klines.iter().map(|kline| kline.open).collect::<Vec<_>>());
+        match page.next_cursor {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+    assert_eq!(
+        pages,
+        vec![vec![7.0, 8.0, 9.0, 10.0], vec![3.0, 4.0, 5.0, 6.0], vec![1.0, 2.0]]
+    );
+
+    // Paging forwards excludes the cursor candle
+    let page = service.get_klines_page("DOGE", TimeInterval::Minute1, KLineCursor::After(start), 3);
+    let opens: Vec<f64> = page.klines.iter().map(|kline| kline.open).collect();
+    assert_eq!(opens, vec![2.0, 3.0, 4.0]);
+    assert_eq!(page.next_cursor, Some(KLineCursor::After(start + Duration::minutes(3))));
+
+    // The last page has no cursor
+    let page = service.get_klines_page("DOGE", TimeInterval::Minute1, KLineCursor::After(start), 9);
+    assert_eq!(page.klines.len(), 9);
+    assert!(page.next_cursor.is_none());
+}
+
+#[test]
+fn test_activity_interval_parsing() {
+    assert_eq!("100t".parse::<TimeInterval>(), Ok(TimeInterval::Trades(100)));
+    assert_eq!("5000v".parse::<TimeInterval>(), Ok(TimeInterval::Volume(5000)));
+    for invalid in ["0t", "t", "+5t", "-5v", "1.5v", "5000000000t"] {
+        assert!(invalid.parse::<TimeInterval>().is_err(), "{}", invalid);
+    }
+
+    assert_eq!(TimeInterval::Trades(100).as_str(), "100t");
+    assert_eq!(serde_json::to_string(&TimeInterval::Volume(5000)).unwrap(), "\"5000v\"");
+    assert_eq!(serde_json::from_str::<TimeInterval>("\"1m\"").unwrap(), TimeInterval::Minute1);
+    assert_eq!(TimeInterval::Trades(100).duration_seconds(), None);
+}
+
+#[test]
+fn test_trade_count_klines() {
+    let service = KLineService::new().with_activity_intervals([TimeInterval::Trades(3), TimeInterval::Minute1]);
+    assert_eq!(service.intervals().len(), TimeInterval::all().len() + 1);
+    assert!(!service.supports(TimeInterval::Trades(5)));
+
+    let start = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap() - Duration::minutes(5);
+    let mut closed = Vec::new();
+    for (i, price) in [1.0, 3.0, 2.0, 4.0, 5.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        // Every trade in the same millisecond
+        transaction.timestamp = start;
+        closed.extend(
+            service
+                .process_transaction(&transaction)
+                .into_iter()
+                .filter(|kline| kline.interval == TimeInterval::Trades(3)),
+        );
+        // The full candle only closes when the fourth trade starts the next one
+        assert_eq!(closed.len(), usize::from(i >= 3));
+    }
+
+    let klines = service.get_klines("DOGE", TimeInterval::Trades(3), start, start + Duration::hours(1), None);
+    assert_eq!(klines.len(), 2);
+    assert_eq!((klines[0].open, klines[0].high, klines[0].low, klines[0].close), (1.0, 3.0, 1.0, 2.0));
+    assert_eq!(klines[0].volume, 30.0);
+    assert!(klines[0].is_closed);
+    assert_eq!(klines[0].close_time(), start);
+
+    // Candles opened in the same millisecond get distinct keys
+    assert_eq!(klines[0].timestamp, start);
+    assert_eq!(klines[1].timestamp, start + Duration::milliseconds(1));
+    assert_eq!((klines[1].open, klines[1].close), (4.0, 5.0));
+    assert!(!klines[1].is_closed);
+    assert_eq!(closed[0].close, 2.0);
+}
+
+#[test]
+fn test_volume_klines() {
+    let service = KLineService::new().with_activity_intervals([TimeInterval::Volume(100)]);
+
+    for volume in [40.0, 50.0, 30.0, 10.0] {
+        service.process_transaction(&Transaction::new("DOGE".to_string(), 1.0, volume, Side::Buy));
+    }
+
+    let now = Utc::now();
+    let klines = service.get_klines("DOGE", TimeInterval::Volume(100), now - Duration::hours(1), now + Duration::hours(1), None);
+    // A candle closes with the trade that brings its volume to at least 100
+    let volumes: Vec<f64> = klines.iter().map(|kline| kline.volume).collect();
+    assert_eq!(volumes, vec![120.0, 10.0]);
+
+    // Open candles are kept by retention, closed ones go with their last trade
+    assert_eq!(service.remove_klines_before(now + Duration::hours(1)), TimeInterval::all().len() + 1);
+    let klines = service.get_klines("DOGE", TimeInterval::Volume(100), now - Duration::hours(1), now + Duration::hours(1), None);
+    assert_eq!(klines.len(), 1);
+    assert_eq!(klines[0].volume, 10.0);
+}
+
+#[test]
+fn test_close_open_klines_as_partial() {
+    let service = KLineService::new().with_activity_intervals([TimeInterval::Trades(10)]);
+    service.process_transaction(&Transaction::new("DOGE".to_string(), 1.0, 5.0, Side::Buy));
+
+    let closed = service.close_open_klines();
+    assert_eq!(closed.len(), TimeInterval::all().len() + 1);
+    assert!(closed.iter().all(|kline| kline.is_closed && kline.is_partial));
+    assert!(service.get_current_kline("DOGE", TimeInterval::Minute1).is_none());
+    assert!(service.close_open_klines().is_empty());
+
+    // Partial candles are flagged in JSON; regular ones omit the field
+    let json = serde_json::to_value(&closed[0]).unwrap();
+    assert_eq!(json["is_partial"], true);
+    let kline = KLine::new("DOGE".to_string(), Utc::now(), TimeInterval::Minute1, 1.0, 1.0);
+    assert!(serde_json::to_value(&kline).unwrap().get("is_partial").is_none());
+}
+
+#[test]
+fn test_batch_matches_sequential_processing() {
+    let start = Utc::now().duration_trunc(TimeDelta::hours(1)).unwrap() - Duration::hours(2);
+    let mut transactions = Vec::new();
+    for i in 0..600 {
+        for (token, base) in [("DOGE", 0.15), ("SHIB", 0.00001)] {
+            let price = base * (1.0 + (i % 7) as f64 / 100.0);
+            let side = if i % 2 == 0 { Side::Buy } else { Side::Sell };
+            let mut transaction = Transaction::new(token.to_string(), price, 10.0 + i as f64, side);
+            transaction.timestamp = start + Duration::seconds(i * 7);
+            transactions.push(transaction);
+        }
+    }
+    // A late trade for an earlier candle and an invalid one
+    let mut late = Transaction::new("DOGE".to_string(), 0.2, 5.0, Side::Buy);
+    late.timestamp = start + Duration::seconds(30);
+    transactions.push(late);
+    transactions.push(Transaction::new("DOGE".to_string(), f64::NAN, 5.0, Side::Buy));
+
+    let sequential = KLineService::new().with_activity_intervals([TimeInterval::Trades(50)]);
+    let mut closed_sequentially = 0;
+    for transaction in &transactions {
+        closed_sequentially += sequential.process_transaction(transaction).len();
+    }
+    let batched = KLineService::new().with_activity_intervals([TimeInterval::Trades(50)]);
+    let outcome = batched.process_transactions(&transactions);
+
+    assert_eq!(outcome.applied, transactions.len() - 1);
+    assert_eq!(outcome.rejected.len(), 1);
+    assert_eq!(outcome.rejected[0].0, transactions.len() - 1);
+    assert!(matches!(outcome.rejected[0].1, IngestError::InvalidTransaction(_)));
+    assert_eq!(outcome.closed.len(), closed_sequentially);
+
+    let end = start + Duration::hours(3);
+    for token in ["DOGE", "SHIB"] {
+        for interval in sequential.intervals() {
+            let expected = sequential.get_klines(token, interval, start, end, None);
+            let actual = batched.get_klines(token, interval, start, end, None);
+            assert_eq!(actual.len(), expected.len(), "{} {:?}", token, interval);
+            for (actual, expected) in actual.iter().zip(&expected) {
+                assert_eq!(
+                    (actual.timestamp, actual.open, actual.high, actual.low, actual.close, actual.is_closed),
+                    (expected.timestamp, expected.open, expected.high, expected.low, expected.close, expected.is_closed)
+                );
+                assert!((actual.volume - expected.volume).abs() < 1e-9);
+                assert!((actual.buy_volume - expected.buy_volume).abs() < 1e-9);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sharded_service_routes_tokens() {
+    let tokens: Vec<String> = (0..50).map(|i| format!("TOK{}", i)).collect();
+    let shards: Vec<usize> = tokens.iter().map(|token| token_shard(token, 4)).collect();
+    assert!(shards.iter().all(|shard| *shard < 4));
+    assert!((0..4).all(|shard| shards.contains(&shard)));
+    assert_eq!(token_shard("DOGE", 4), token_shard("DOGE", 4));
+    assert_eq!(token_shard("DOGE", 0), 0);
+
+    let service = KLineService::new().with_shards(4);
+    assert_eq!(service.shard_count(), 4);
+    for (i, token) in tokens.iter().enumerate() {
+        service.process_transaction(&Transaction::new(token.clone(), 1.0 + i as f64, 10.0, Side::Buy));
+    }
+
+    let mut available = service.get_available_tokens();
+    available.sort();
+    let mut expected = tokens.clone();
+    expected.sort();
+    assert_eq!(available, expected);
+    for (i, token) in tokens.iter().enumerate() {
+        let kline = service.get_current_kline(token, TimeInterval::Minute1).unwrap();
+        assert_eq!(kline.close, 1.0 + i as f64);
+    }
+
+    assert!(service.remove_token("TOK7"));
+    assert!(service.get_latest_kline("TOK7", TimeInterval::Minute1).is_none());
+    assert_eq!(service.close_open_klines().len(), 49 * TimeInterval::all().len());
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let service = KLineService::new().with_activity_intervals([TimeInterval::Trades(3)]);
+    let start = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap() - Duration::minutes(5);
+    for (i, price) in [1.0, 2.0, 3.0, 4.0].into_iter().enumerate() {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::seconds(i as i64 * 30);
+        service.process_transaction(&transaction);
+    }
+    service.add_token("BONK");
+
+    let path = std::env::temp_dir().join(format!("k-line-snapshot-{}.json", uuid::Uuid::new_v4()));
+    service.snapshot().save(&path).unwrap();
+    let snapshot = KLineSnapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(snapshot.tokens, vec!["BONK".to_string(), "DOGE".to_string()]);
+
+    // Restoring into a differently sharded service keeps every candle
+    let restored = KLineService::new()
+        .with_activity_intervals([TimeInterval::Trades(3)])
+        .with_shards(3);
+    assert_eq!(restored.restore(snapshot.clone()), snapshot.klines.len());
+    let mut tokens = restored.get_available_tokens();
+    tokens.sort();
+    assert_eq!(tokens, snapshot.tokens);
+    for interval in service.intervals() {
+        let original = service.get_klines("DOGE", interval, start, start + Duration::hours(2), None);
+        let copy = restored.get_klines("DOGE", interval, start, start + Duration::hours(2), None);
+        assert_eq!(
+            copy.iter().map(|kline| (kline.timestamp, kline.close, kline.volume, kline.is_closed)).collect::<Vec<_>>(),
+            original.iter().map(|kline| (kline.timestamp, kline.close, kline.volume, kline.is_closed)).collect::<Vec<_>>()
+        );
+    }
+
+    // The open trade-count candle continues where it left off
+    let mut closed = Vec::new();
+    for price in [5.0, 6.0, 7.0] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(2);
+        closed.extend(
+            restored
+                .process_transaction(&transaction)
+                .into_iter()
+                .filter(|kline| kline.interval == TimeInterval::Trades(3)),
+        );
+    }
+    assert_eq!(closed.len(), 1);
+    assert_eq!((closed[0].open, closed[0].close), (4.0, 6.0));
+
+    // Intervals no longer aggregated are skipped
+    let time_only = KLineService::new();
+    assert!(time_only.restore(snapshot.clone()) < snapshot.klines.len());
+    assert!(time_only.get_latest_kline("DOGE", TimeInterval::Trades(3)).is_none());
+
+    let missing = std::env::temp_dir().join(format!("k-line-snapshot-{}.json", uuid::Uuid::new_v4()));
+    assert!(matches!(KLineSnapshot::load(&missing), Err(SnapshotError::Io(_))));
+}
+
+#[test]
+fn test_ohlc_over_arbitrary_range() {
+    let service = KLineService::new();
+    let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, 15, 13, 0, 0).unwrap();
+
+    // A trade every 7 seconds for three hours, prices rising then falling
+    let trades: Vec<Transaction> = (0..1543)
+        .map(|i| {
+            let price = 1.0 + (i as f64 * 0.01).sin();
+            let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0 + i as f64 % 5.0, Side::Buy);
+            transaction.timestamp = start + Duration::seconds(i * 7);
+            transaction
+        })
+        .collect();
+    for transaction in &trades {
+        service.process_transaction(transaction);
+    }
+
+    // 13:17:03 to 15:42:41 mixes every interval; the summary must match the raw trades
+    let (from, to) = (start + Duration::seconds(1023), start + Duration::seconds(9761));
+    let in_range: Vec<&Transaction> = trades
+        .iter()
+        .filter(|t| t.timestamp >= from.duration_trunc(TimeDelta::seconds(1)).unwrap() && t.timestamp < to)
+        .collect();
+    let summary = service.get_ohlc("DOGE", from, to).unwrap();
+    assert_eq!(summary.open, in_range.first().unwrap().price);
+    assert_eq!(summary.close, in_range.last().unwrap().price);
+    assert_eq!(summary.high, in_range.iter().map(|t| t.price).fold(f64::MIN, f64::max));
+    assert_eq!(summary.low, in_range.iter().map(|t| t.price).fold(f64::MAX, f64::min));
+    let volume: f64 = in_range.iter().map(|t| t.volume).sum();
+    assert!((summary.volume - volume).abs() < 1e-9);
+    // Hour candles cover the middle, so far fewer candles than trades are folded
+    assert!(summary.candles < in_range.len() / 4);
+
+    assert!(service.get_ohlc("DOGE", start - Duration::hours(2), start).is_none());
+    assert!(service.get_ohlc("SHIB", from, to).is_none());
+}
+
+#[test]
+fn test_serialized_close_time_and_is_final() {
+    let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, 15, 14, 0, 0).unwrap();
+    let mut kline = KLine::new("DOGE".to_string(), start, TimeInterval::Minute5, 0.15, 100.0);
+
+    let json = serde_json::to_value(&kline).unwrap();
+    assert_eq!(json["close_time"], "2024-01-15T14:05:00Z");
+    assert_eq!(json["is_final"], false);
+    assert_eq!(json["is_closed"], false);
+
+    kline.close();
+    let json = serde_json::to_value(&kline).unwrap();
+    assert_eq!(json["is_final"], true);
+    assert_eq!(json["is_closed"], true);
+
+    // The derived fields are ignored when read back
+    let parsed: KLine = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed.close_time(), kline.close_time());
+    assert!(parsed.is_closed);
+
+    // Activity candles end just after their latest trade
+    let trades = KLine::new("DOGE".to_string(), start, TimeInterval::Trades(100), 0.15, 100.0);
+    let json = serde_json::to_value(&trades).unwrap();
+    assert_eq!(json["close_time"], "2024-01-15T14:00:00.001Z");
+}
+
+#[test]
+fn test_amending_a_zero_volume_candle_splits_the_volume() {
+    let service = KLineService::new();
+    let timestamp = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap() - TimeDelta::minutes(5);
+    let mut empty = KLine::new("DOGE".to_string(), timestamp, TimeInterval::Minute1, 0.1, 0.0);
+    empty.is_closed = true;
+    service.insert_kline(empty);
+
+    let kline = service
+        .amend_kline(&KLineAmendment {
+            token: "DOGE".to_string(),
+            interval: TimeInterval::Minute1,
+            timestamp,
+            open: 0.1,
+            high: 0.1,
+            low: 0.1,
+            close: 0.1,
+            volume: 10.0,
+            buy_volume: None,
+            sell_volume: None,
+        })
+        .unwrap();
+    assert_eq!((kline.volume, kline.buy_volume, kline.sell_volume), (10.0, 5.0, 5.0));
+}
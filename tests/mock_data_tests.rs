@@ -0,0 +1,146 @@
+use chrono::{DurationRound, TimeDelta, Utc};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use k_line::config::{ArrivalProcess, Config, TokenConfig, VolumeConfig, VolumeDistribution};
+use k_line::{KLineService, MockDataGenerator, TimeInterval, Transaction};
+
+fn poisson_config() -> Config {
+    let mut config = Config::default();
+    config.data_generation.arrival = ArrivalProcess::Poisson;
+    config.tokens.supported_tokens = vec![TokenConfig {
+        symbol: "DOGE".to_string(),
+        base_price: 0.15,
+        volatility: 5.0,
+        price_precision: None,
+        quantity_precision: None,
+        tick_size: None,
+        arrival_rate: Some(50.0),
+        volume: None,
+    }];
+    config
+}
+
+#[test]
+fn test_arrival_gaps_are_exponential() {
+    let mut config = poisson_config();
+    config.tokens.supported_tokens.push(TokenConfig {
+        symbol: "SHIB".to_string(),
+        arrival_rate: None,
+        ..config.tokens.supported_tokens[0].clone()
+    });
+    let generator = MockDataGenerator::new_with_config(&config);
+    let interval = Duration::from_millis(100);
+
+    let samples = 20_000;
+    let gaps: Vec<f64> = (0..samples)
+        .map(|_| generator.arrival_gap("DOGE", interval).as_secs_f64())
+        .collect();
+    let mean = gaps.iter().sum::<f64>() / samples as f64;
+    assert!((mean - 0.02).abs() < 0.002, "mean gap {}", mean);
+
+    // Gaps vary, and longer ones leave intervals without trades:
+    // P(gap > 2 / rate) = e^-2
+    let long = gaps.iter().filter(|gap| **gap > 0.04).count() as f64 / samples as f64;
+    assert!((long - (-2.0f64).exp()).abs() < 0.02, "share of long gaps {}", long);
+
+    // Tokens without a rate average one trade per interval
+    let mean: f64 = (0..samples)
+        .map(|_| generator.arrival_gap("SHIB", interval).as_secs_f64())
+        .sum::<f64>()
+        / samples as f64;
+    assert!((mean - 0.1).abs() < 0.01, "mean gap {}", mean);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_poisson_generation_rate() {
+    let generator = MockDataGenerator::new_with_config(&poisson_config());
+    let count = Arc::new(AtomicUsize::new(0));
+    let counter = count.clone();
+
+    let task = tokio::spawn(async move {
+        generator
+            .start_continuous_generation(
+                move |transaction| {
+                    assert_eq!(transaction.token, "DOGE");
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    async {}
+                },
+                100,
+            )
+            .await;
+    });
+    tokio::time::sleep(Duration::from_secs(20)).await;
+    task.abort();
+
+    // 50 trades per second on average, not 10 as with fixed 100ms ticks
+    let trades = count.load(Ordering::Relaxed);
+    assert!((850..=1150).contains(&trades), "{} trades", trades);
+}
+
+#[test]
+fn test_backfill_covers_the_window_in_order() {
+    let generator = MockDataGenerator::new();
+    let end = Utc::now().duration_trunc(TimeDelta::hours(1)).unwrap();
+
+    // 100ms generation is spaced to one trade per token per second
+    let transactions: Vec<Transaction> = generator.generate_backfill(2, 100, end).collect();
+    assert_eq!(transactions.len(), 2 * 3600 * 3);
+    assert_eq!(transactions[0].timestamp, end - TimeDelta::hours(2));
+    assert_eq!(transactions.last().unwrap().timestamp, end - TimeDelta::seconds(1));
+    assert!(transactions.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    // Every generated trade has its own ID
+    let trade_ids: HashSet<_> = transactions.iter().filter_map(|transaction| transaction.trade_id.clone()).collect();
+    assert_eq!(trade_ids.len(), transactions.len());
+    assert!(transactions.iter().all(|transaction| transaction.source.as_deref() == Some("mock")));
+    assert!(transactions
+        .iter()
+        .filter(|transaction| transaction.token == "DOGE")
+        .all(|transaction| (transaction.price - 0.15).abs() <= 0.15 * 0.02));
+
+    // Every interval has plottable candles right away
+    let kline_service = KLineService::new();
+    for transaction in &transactions {
+        kline_service.process_transaction(transaction);
+    }
+    let start = end - TimeDelta::hours(2);
+    let hourly = kline_service.get_klines("DOGE", TimeInterval::Hour1, start, end, None);
+    assert_eq!(hourly.len(), 2);
+    assert!(hourly[0].is_closed);
+    let minutes = kline_service.get_klines("SHIB", TimeInterval::Minute1, start, end, None);
+    assert_eq!(minutes.len(), 120);
+}
+
+#[test]
+fn test_log_normal_volumes_with_whales() {
+    let mut config = poisson_config();
+    config.data_generation.volume = VolumeConfig {
+        distribution: VolumeDistribution::LogNormal,
+        mu: 5.0,
+        sigma: 0.5,
+        whale_probability: 0.01,
+        whale_multiplier: 1000.0,
+    };
+    config.tokens.supported_tokens.push(TokenConfig {
+        symbol: "SHIB".to_string(),
+        volume: Some(VolumeConfig::default()),
+        ..config.tokens.supported_tokens[0].clone()
+    });
+    let generator = MockDataGenerator::new_with_config(&config);
+
+    let samples = 20_000;
+    let mut volumes: Vec<f64> = (0..samples).map(|_| generator.trade_volume("DOGE")).collect();
+    volumes.sort_by(f64::total_cmp);
+    // The median is e^mu, and whales are far outside the log-normal body
+    let median = volumes[samples / 2];
+    assert!((median - 5.0f64.exp()).abs() < 10.0, "median volume {}", median);
+    let whales = volumes.iter().filter(|volume| **volume > 10_000.0).count() as f64 / samples as f64;
+    assert!((whales - 0.01).abs() < 0.004, "share of whales {}", whales);
+
+    // Tokens with their own distribution keep uniform volumes
+    assert!((0..1000)
+        .map(|_| generator.trade_volume("SHIB"))
+        .all(|volume| (100.0..1000.0).contains(&volume)));
+}
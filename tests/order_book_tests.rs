@@ -0,0 +1,98 @@
+use k_line::services::order_book::{DepthSnapshot, OrderBookSimulator, PriceLevel};
+use k_line::{Side, Transaction};
+
+fn trade(price: f64, volume: f64, side: Side) -> Transaction {
+    Transaction::new("DOGE".to_string(), price, volume, side)
+}
+
+/// Apply an update's changed levels to a copy of a side, best first
+fn apply_changes(side: &[PriceLevel], changes: &[PriceLevel], descending: bool) -> Vec<PriceLevel> {
+    let mut levels: Vec<PriceLevel> = side
+        .iter()
+        .filter(|level| !changes.iter().any(|change| change.price == level.price))
+        .copied()
+        .collect();
+    levels.extend(changes.iter().filter(|change| change.quantity > 0.0));
+    levels.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
+    if descending {
+        levels.reverse();
+    }
+    levels
+}
+
+fn assert_uncrossed(book: &DepthSnapshot) {
+    let best_bid = book.bids.first().unwrap().price;
+    let best_ask = book.asks.first().unwrap().price;
+    assert!(best_bid < best_ask, "crossed book: {} >= {}", best_bid, best_ask);
+    assert!(book.bids.windows(2).all(|pair| pair[0].price > pair[1].price));
+    assert!(book.asks.windows(2).all(|pair| pair[0].price < pair[1].price));
+    assert!(book.bids.iter().chain(&book.asks).all(|level| level.quantity > 0.0));
+}
+
+#[test]
+fn test_first_trade_creates_book_around_price() {
+    let books = OrderBookSimulator::new(10);
+    assert!(books.snapshot("DOGE", 10).is_none());
+
+    let update = books.apply_trade(&trade(0.15, 100.0, Side::Buy)).unwrap();
+    assert_eq!(update.token, "DOGE");
+    assert_eq!(update.update_id, 1);
+
+    let book = books.snapshot("DOGE", 10).unwrap();
+    assert_eq!(book.update_id, 1);
+    assert_uncrossed(&book);
+    // Random cancels may leave a few holes, never levels beyond the depth
+    assert!(book.bids.len() <= 10 && book.bids.len() >= 7);
+    assert!(book.asks.len() <= 10 && book.asks.len() >= 7);
+    assert!(book.bids.iter().all(|level| level.price < 0.15 && level.price >= 0.149));
+    assert!(book.asks.iter().all(|level| level.price > 0.15 && level.price <= 0.151));
+
+    // Snapshots honour the limit
+    let top = books.snapshot("DOGE", 3).unwrap();
+    assert_eq!(top.bids.len(), 3);
+    assert_eq!(top.bids[..], book.bids[..3]);
+}
+
+#[test]
+fn test_updates_rebuild_snapshot() {
+    let books = OrderBookSimulator::new(20);
+    books.apply_trade(&trade(0.15, 100.0, Side::Buy));
+    let mut book = books.snapshot("DOGE", 20).unwrap();
+
+    for (i, price) in [0.1502, 0.1497, 0.1511, 0.1489, 0.15].into_iter().enumerate() {
+        let update = books.apply_trade(&trade(price, 50.0, if i % 2 == 0 { Side::Buy } else { Side::Sell })).unwrap();
+        assert_eq!(update.update_id, book.update_id + 1);
+        book.bids = apply_changes(&book.bids, &update.bids, true);
+        book.asks = apply_changes(&book.asks, &update.asks, false);
+        book.update_id = update.update_id;
+
+        let expected = books.snapshot("DOGE", 20).unwrap();
+        assert_eq!(book.bids, expected.bids);
+        assert_eq!(book.asks, expected.asks);
+        assert_uncrossed(&expected);
+    }
+}
+
+#[test]
+fn test_trades_move_the_book() {
+    let books = OrderBookSimulator::new(5);
+    books.apply_trade(&trade(0.15, 100.0, Side::Buy));
+
+    // A buy sweeps the asks below its price; a sell sweeps the bids above it
+    books.apply_trade(&trade(0.1504, 10.0, Side::Buy));
+    let book = books.snapshot("DOGE", 5).unwrap();
+    assert!(book.asks.iter().all(|level| level.price >= 0.1504));
+    assert!(book.bids.iter().all(|level| level.price < 0.1504));
+
+    books.apply_trade(&trade(0.1495, 10.0, Side::Sell));
+    let book = books.snapshot("DOGE", 5).unwrap();
+    assert!(book.bids.iter().all(|level| level.price <= 0.1495));
+    assert!(book.asks.iter().all(|level| level.price > 0.1495));
+
+    // Tokens have separate books, and invalid prices are ignored
+    assert!(books.apply_trade(&Transaction::new("BTC".to_string(), 65000.0, 0.5, Side::Sell)).is_some());
+    let btc = books.snapshot("BTC", 5).unwrap();
+    assert!(btc.bids[0].price < 65000.0 && btc.bids[0].price >= 64950.0);
+    assert!(books.apply_trade(&trade(0.0, 10.0, Side::Buy)).is_none());
+    assert_eq!(books.snapshot("DOGE", 5).unwrap().update_id, 3);
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "server")]
+
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use k_line::config::UnknownTokenPolicy;
+use k_line::models::TradeId;
+use k_line::services::{Metrics, TokenRegistry, TradeDeduplicator, TradeService, TransactionPipeline};
+use k_line::{KLineService, Side, TimeInterval, Transaction, WsManager};
+
+fn trade(token: &str, price: f64) -> Transaction {
+    Transaction::new(token.to_string(), price, 10.0, Side::Buy)
+}
+
+fn pipeline() -> (TransactionPipeline, Arc<KLineService>, Arc<TradeService>) {
+    let kline_service = Arc::new(KLineService::new());
+    let trade_service = Arc::new(TradeService::new(1000));
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new()))
+        .with_trade_service(trade_service.clone());
+    (pipeline, kline_service, trade_service)
+}
+
+#[actix_rt::test]
+async fn test_workers_keep_the_order_of_each_token() {
+    let (pipeline, kline_service, trade_service) = pipeline();
+    let (ingest, workers) = pipeline.spawn_workers(3, 8);
+
+    for i in 1..=200 {
+        for token in ["DOGE", "SHIB", "PEPE"] {
+            ingest.send(trade(token, i as f64)).await.unwrap();
+        }
+    }
+    // Workers stop once the queues are drained and the sender is gone
+    drop(ingest);
+    for worker in workers {
+        worker.await.unwrap();
+    }
+
+    for token in ["DOGE", "SHIB", "PEPE"] {
+        let prices: Vec<f64> = trade_service.recent(token, 1000).iter().map(|t| t.price).collect();
+        assert_eq!(prices, (1..=200).map(f64::from).collect::<Vec<_>>());
+        let kline = kline_service.get_current_kline(token, TimeInterval::Minute1).unwrap();
+        assert_eq!(kline.close, 200.0);
+    }
+}
+
+#[actix_rt::test]
+async fn test_full_queue_makes_sources_wait() {
+    let (pipeline, _, trade_service) = pipeline();
+    let (ingest, _workers) = pipeline.spawn_workers(1, 2);
+
+    // The worker has not run yet, so its queue fills up
+    ingest.try_send(trade("DOGE", 1.0)).unwrap();
+    ingest.try_send(trade("DOGE", 2.0)).unwrap();
+    assert_eq!(ingest.queued(), 2);
+    assert!(matches!(ingest.try_send(trade("DOGE", 3.0)), Err(TrySendError::Full(_))));
+
+    // Waiting lets the worker make room
+    ingest.send(trade("DOGE", 3.0)).await.unwrap();
+    tokio::task::yield_now().await;
+    let prices: Vec<f64> = trade_service.recent("DOGE", 10).iter().map(|t| t.price).collect();
+    assert_eq!(prices, vec![1.0, 2.0, 3.0]);
+    assert_eq!(ingest.queued(), 0);
+}
+
+#[actix_rt::test]
+async fn test_send_fails_once_workers_stop() {
+    let (pipeline, _, _) = pipeline();
+    let (ingest, workers) = pipeline.spawn_workers(2, 4);
+    for worker in workers {
+        worker.abort();
+        assert!(worker.await.unwrap_err().is_cancelled());
+    }
+    assert!(ingest.send(trade("DOGE", 1.0)).await.is_err());
+}
+
+#[actix_rt::test]
+async fn test_unknown_token_policy_applies_before_processing() {
+    let (pipeline, kline_service, trade_service) = pipeline();
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::Reject, ["DOGE".to_string()]));
+    let pipeline = pipeline.with_token_registry(registry);
+
+    pipeline.handle(&trade("DOGE", 1.0)).unwrap();
+    // Dropped transactions are not errors
+    pipeline.handle(&trade("RUG", 1.0)).unwrap();
+
+    assert_eq!(trade_service.recent("DOGE", 10).len(), 1);
+    assert!(trade_service.recent("RUG", 10).is_empty());
+    assert!(kline_service.get_current_kline("RUG", TimeInterval::Minute1).is_none());
+}
+
+#[actix_rt::test]
+async fn test_duplicate_trade_ids_count_once() {
+    let (pipeline, kline_service, trade_service) = pipeline();
+    let metrics = Arc::new(Metrics::new());
+    let pipeline = pipeline
+        .with_metrics(metrics.clone())
+        .with_deduplicator(Some(Arc::new(TradeDeduplicator::new(2))));
+    let with_id = |token: &str, trade_id: u64| Transaction {
+        trade_id: Some(TradeId::Number(trade_id)),
+        ..trade(token, 1.0)
+    };
+
+    pipeline.handle(&with_id("DOGE", 1)).unwrap();
+    // A retried trade is dropped, while other tokens number their trades separately
+    pipeline.handle(&with_id("DOGE", 1)).unwrap();
+    pipeline.handle(&with_id("SHIB", 1)).unwrap();
+    // Trades without an ID are never duplicates
+    pipeline.handle(&trade("DOGE", 1.0)).unwrap();
+    pipeline.handle(&trade("DOGE", 1.0)).unwrap();
+    assert_eq!(kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap().volume, 30.0);
+    assert_eq!(metrics.duplicates_dropped(), 1);
+
+    // IDs slide out of the window
+    pipeline.handle(&with_id("DOGE", 2)).unwrap();
+    pipeline.handle(&with_id("DOGE", 3)).unwrap();
+    pipeline.handle(&with_id("DOGE", 1)).unwrap();
+    assert_eq!(trade_service.recent("DOGE", 10).len(), 6);
+
+    // Backfill drops duplicates too
+    assert_eq!(pipeline.backfill([with_id("SHIB", 1), with_id("SHIB", 2)].into_iter()), 1);
+    assert_eq!(metrics.duplicates_dropped(), 2);
+}
+
+#[actix_rt::test]
+async fn test_only_applied_trade_ids_are_remembered() {
+    let (pipeline, kline_service, _) = pipeline();
+    let metrics = Arc::new(Metrics::new());
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::Reject, ["DOGE".to_string()]));
+    let pipeline = pipeline
+        .with_metrics(metrics.clone())
+        .with_token_registry(registry.clone())
+        .with_deduplicator(Some(Arc::new(TradeDeduplicator::new(10))));
+    let with_id = |token: &str, price: f64| Transaction {
+        trade_id: Some(TradeId::Number(price.abs() as u64)),
+        ..trade(token, price)
+    };
+
+    // An invalid trade or one of an unregistered token does not take up its ID
+    assert!(pipeline.handle(&with_id("DOGE", -1.0)).is_err());
+    pipeline.handle(&with_id("RUG", 1.0)).unwrap();
+    registry.register("RUG");
+    pipeline.handle(&with_id("DOGE", 1.0)).unwrap();
+    pipeline.handle(&with_id("RUG", 1.0)).unwrap();
+    assert_eq!(metrics.duplicates_dropped(), 0);
+    assert!(kline_service.get_current_kline("RUG", TimeInterval::Minute1).is_some());
+
+    // Backfill likewise
+    assert_eq!(pipeline.backfill([with_id("DOGE", -2.0), with_id("DOGE", 2.0)].into_iter()), 1);
+    assert_eq!(metrics.duplicates_dropped(), 0);
+}
+
+#[actix_rt::test]
+async fn test_every_spelling_of_a_pair_reaches_one_worker() {
+    let (pipeline, kline_service, trade_service) = pipeline();
+    let (ingest, workers) = pipeline.spawn_workers(8, 1);
+
+    // The workers have not run yet, so the pair's queue is full after one trade
+    ingest.try_send(trade("DOGE", 1.0)).unwrap();
+    for token in ["doge", "DOGE/USD", "doge-usd", "Doge/usd"] {
+        assert!(matches!(ingest.try_send(trade(token, 2.0)), Err(TrySendError::Full(_))), "{}", token);
+    }
+
+    for (i, token) in ["doge", "DOGE/USD", "DOGE", "doge-usd"].into_iter().cycle().take(40).enumerate() {
+        ingest.send(trade(token, (i + 2) as f64)).await.unwrap();
+    }
+    drop(ingest);
+    for worker in workers {
+        worker.await.unwrap();
+    }
+    let prices: Vec<f64> = trade_service.recent("DOGE", 100).iter().map(|t| t.price).collect();
+    assert_eq!(prices, (1..=41).map(f64::from).collect::<Vec<_>>());
+    assert_eq!(kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap().close, 41.0);
+}
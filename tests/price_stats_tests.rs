@@ -0,0 +1,41 @@
+use chrono::{TimeDelta, TimeZone, Utc};
+use k_line::services::price_stats::PriceStats;
+use k_line::{Side, Transaction};
+
+fn trade(minutes_ago: i64, price: f64) -> Transaction {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+    transaction.timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap() - TimeDelta::minutes(minutes_ago);
+    transaction
+}
+
+#[test]
+fn test_rolling_windows_and_all_time_extremes() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let mut stats = PriceStats::new(&trade(14 * 24 * 60, 0.5));
+    for (minutes_ago, price) in [(3 * 24 * 60, 0.3), (5 * 60, 0.25), (30, 0.2), (10, 0.22)] {
+        stats.record(&trade(minutes_ago, price));
+    }
+
+    let extremes = stats.extremes(now);
+    assert_eq!((extremes.all_time_high, extremes.all_time_low), (0.5, 0.2));
+    assert_eq!(extremes.all_time_high_at, trade(14 * 24 * 60, 0.5).timestamp);
+    assert_eq!((extremes.high_1h, extremes.low_1h), (Some(0.22), Some(0.2)));
+    assert_eq!((extremes.high_24h, extremes.low_24h), (Some(0.25), Some(0.2)));
+    assert_eq!((extremes.high_7d, extremes.low_7d), (Some(0.3), Some(0.2)));
+
+    // Windows without trades have no rolling extremes, while all-time ones remain
+    let later = stats.extremes(now + TimeDelta::days(30));
+    assert_eq!((later.high_7d, later.low_1h), (None, None));
+    assert_eq!(later.all_time_low, 0.2);
+}
+
+#[test]
+fn test_late_trades_fill_their_slots() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap();
+    let mut stats = PriceStats::new(&trade(5, 1.0));
+    stats.record(&trade(40, 3.0));
+    stats.record(&trade(20, 0.5));
+
+    let extremes = stats.extremes(now);
+    assert_eq!((extremes.high_1h, extremes.low_1h), (Some(3.0), Some(0.5)));
+}
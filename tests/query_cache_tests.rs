@@ -0,0 +1,92 @@
+#![cfg(feature = "server")]
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::Arc;
+
+use k_line::services::{KLineCursor, KLineQueryCache, TransactionPipeline};
+use k_line::{KLineService, Side, TimeInterval, Transaction, WsManager};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 1, 15, 14, 0, 0).unwrap()
+}
+
+fn trade(offset_secs: i64, price: f64) -> Transaction {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy);
+    transaction.timestamp = start() + Duration::seconds(offset_secs);
+    transaction
+}
+
+fn setup(ttl: std::time::Duration) -> (TransactionPipeline, Arc<KLineService>, Arc<KLineQueryCache>) {
+    let kline_service = Arc::new(KLineService::new());
+    let query_cache = Arc::new(KLineQueryCache::new(ttl, 16));
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new()))
+        .with_query_cache(Some(query_cache.clone()));
+    (pipeline, kline_service, query_cache)
+}
+
+const LATEST: KLineCursor = KLineCursor::Before(DateTime::<Utc>::MAX_UTC);
+
+#[test]
+fn test_repeated_queries_hit_the_cache() {
+    let (pipeline, kline_service, query_cache) = setup(std::time::Duration::from_secs(60));
+    pipeline.handle(&trade(0, 1.0)).unwrap();
+
+    for _ in 0..3 {
+        let page = query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 100);
+        assert_eq!(page.klines.len(), 1);
+    }
+    let stats = query_cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+    assert!((stats.hit_rate - 2.0 / 3.0).abs() < 1e-9);
+
+    // Another limit is another query
+    query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 10);
+    assert_eq!(query_cache.stats().misses, 2);
+}
+
+#[test]
+fn test_updates_drop_the_pages_covering_the_candle() {
+    let (pipeline, kline_service, query_cache) = setup(std::time::Duration::from_secs(60));
+    pipeline.handle(&trade(0, 1.0)).unwrap();
+    pipeline.handle(&trade(60, 2.0)).unwrap();
+
+    let history = KLineCursor::Before(start() + Duration::minutes(1));
+    let latest = query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 100);
+    query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, history, 100);
+    assert_eq!(latest.klines.last().unwrap().close, 2.0);
+
+    // A trade in the open candle refreshes the latest page only
+    pipeline.handle(&trade(70, 3.0)).unwrap();
+    let latest = query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 100);
+    assert_eq!(latest.klines.last().unwrap().close, 3.0);
+    let history = query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, history, 100);
+    assert_eq!(history.klines.len(), 1);
+
+    let stats = query_cache.stats();
+    assert_eq!((stats.hits, stats.misses), (1, 3));
+    assert!(stats.invalidations >= 1);
+}
+
+#[test]
+fn test_pages_expire() {
+    let (pipeline, kline_service, query_cache) = setup(std::time::Duration::ZERO);
+    pipeline.handle(&trade(0, 1.0)).unwrap();
+
+    // Writes bypassing the pipeline are only seen once the TTL runs out
+    query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 100);
+    kline_service.process_transaction(&trade(10, 5.0));
+    let page = query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, 100);
+    assert_eq!(page.klines[0].close, 5.0);
+    assert_eq!(query_cache.stats().hits, 0);
+}
+
+#[test]
+fn test_capacity_is_bounded() {
+    let (pipeline, kline_service, query_cache) = setup(std::time::Duration::from_secs(60));
+    pipeline.handle(&trade(0, 1.0)).unwrap();
+
+    for limit in 1..=40 {
+        query_cache.get_klines_page(&kline_service, "DOGE", TimeInterval::Minute1, LATEST, limit);
+    }
+    assert!(query_cache.stats().entries <= 16);
+}
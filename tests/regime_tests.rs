@@ -0,0 +1,121 @@
+use chrono::{TimeDelta, Utc};
+use std::collections::BTreeMap;
+
+use k_line::config::{MarketRegime, RegimeConfig};
+use k_line::services::regimes::{MarketEvent, MarketEventKind, RegimeEngine};
+
+fn event(token: Option<&str>, kind: MarketEventKind, magnitude: f64, revert: bool) -> MarketEvent {
+    MarketEvent {
+        token: token.map(str::to_string),
+        kind,
+        magnitude,
+        duration_secs: 10,
+        revert,
+        start_in_secs: 0,
+    }
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn test_flash_crash_ramps_down_and_back() {
+    let engine = RegimeEngine::default();
+    let start = Utc::now();
+    let scheduled = engine.schedule(event(None, MarketEventKind::FlashCrash, 0.3, true), start);
+    assert_eq!(scheduled.peaks_at, start + TimeDelta::seconds(10));
+    assert_eq!(scheduled.ends_at, start + TimeDelta::seconds(20));
+
+    for (secs, factor) in [(0, 1.0), (5, 0.85), (10, 0.7), (15, 0.85)] {
+        let shape = engine.next_trade("DOGE", 2.0, start + TimeDelta::seconds(secs));
+        assert_close(shape.price, 2.0 * factor);
+        // Regimes are disabled, so only the event shapes trades
+        assert_eq!(shape.volatility_multiplier, 1.0);
+        assert_eq!(shape.volume_multiplier, 1.0);
+    }
+
+    // Reverted events leave no trace
+    let shape = engine.next_trade("DOGE", 2.0, start + TimeDelta::seconds(20));
+    assert_close(shape.price, 2.0);
+    assert!(engine.status(start + TimeDelta::seconds(20)).events.is_empty());
+}
+
+#[test]
+fn test_pump_without_revert_moves_the_price_level() {
+    let engine = RegimeEngine::default();
+    let start = Utc::now();
+    engine.next_trade("SHIB", 1.0, start);
+    engine.schedule(event(Some("DOGE"), MarketEventKind::Pump, 0.5, false), start);
+
+    assert_close(engine.next_trade("DOGE", 1.0, start + TimeDelta::seconds(5)).price, 1.25);
+    assert_close(engine.next_trade("SHIB", 1.0, start + TimeDelta::seconds(5)).price, 1.0);
+
+    // The full move stays after the event ends
+    for secs in [10, 60] {
+        assert_close(engine.next_trade("DOGE", 1.0, start + TimeDelta::seconds(secs)).price, 1.5);
+    }
+    assert_close(engine.next_trade("SHIB", 1.0, start + TimeDelta::seconds(60)).price, 1.0);
+}
+
+#[test]
+fn test_regime_drift_and_multipliers() {
+    let config = RegimeConfig {
+        enabled: true,
+        step_secs: 3600,
+        initial: MarketRegime::Bull,
+        ..RegimeConfig::default()
+    };
+    let engine = RegimeEngine::new(config.clone());
+    let now = Utc::now();
+
+    let mut shape = engine.next_trade("DOGE", 1.0, now);
+    for _ in 1..100 {
+        shape = engine.next_trade("DOGE", 1.0, now);
+    }
+    assert_close(shape.price, (1.0 + config.bull.drift).powi(100));
+    assert_eq!(shape.volatility_multiplier, config.bull.volatility_multiplier);
+    assert_eq!(shape.volume_multiplier, config.bull.volume_multiplier);
+
+    // A crash pulls the same level down
+    engine.force(MarketRegime::Crash, now);
+    let crashed = engine.next_trade("DOGE", 1.0, now);
+    assert_close(crashed.price, shape.price * (1.0 + config.crash.drift));
+    assert_eq!(crashed.volume_multiplier, config.crash.volume_multiplier);
+}
+
+#[test]
+fn test_markov_transitions() {
+    let mut config = RegimeConfig {
+        enabled: true,
+        step_secs: 1,
+        ..RegimeConfig::default()
+    };
+    config.crash.transitions = BTreeMap::from([(MarketRegime::Bear, 1.0)]);
+    config.bear.transitions = BTreeMap::from([(MarketRegime::Bear, 1.0)]);
+    let engine = RegimeEngine::new(config);
+
+    let start = Utc::now();
+    engine.force(MarketRegime::Crash, start);
+    let status = engine.status(start + TimeDelta::milliseconds(500));
+    assert_eq!(status.regime, MarketRegime::Crash);
+    assert_eq!(status.since, start);
+
+    // The crash always gives way to a bear market, which then persists
+    let status = engine.status(start + TimeDelta::seconds(5));
+    assert!(status.enabled);
+    assert_eq!(status.regime, MarketRegime::Bear);
+    assert_eq!(status.since, start + TimeDelta::seconds(1));
+}
+
+#[test]
+fn test_market_event_validation() {
+    assert!(event(None, MarketEventKind::FlashCrash, 0.5, true).validate().is_ok());
+    assert!(event(None, MarketEventKind::FlashCrash, 1.0, true).validate().is_err());
+    assert!(event(None, MarketEventKind::Pump, 2.0, false).validate().is_ok());
+    assert!(event(None, MarketEventKind::Pump, 0.0, false).validate().is_err());
+
+    let mut instant = event(None, MarketEventKind::Pump, 0.1, false);
+    instant.duration_secs = 0;
+    assert!(instant.validate().is_err());
+}
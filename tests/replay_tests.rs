@@ -0,0 +1,131 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use k_line::services::replay::{self, ReplayError, ReplayFormat, TransactionReplayer};
+use k_line::models::TradeId;
+use k_line::{Side, Transaction};
+
+fn transaction(token: &str, price: f64, timestamp: DateTime<Utc>) -> Transaction {
+    Transaction {
+        token: token.to_string(),
+        price,
+        volume: 10.0,
+        timestamp,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
+    }
+}
+
+#[test]
+fn test_parse_csv_columns_in_any_order() {
+    let csv = "\
+timestamp,is_buy,token,volume,price,exchange
+1700000000000,buy,DOGE,250,0.15,binance
+2023-11-14T22:13:21Z,false,BTC,0.5,37000.5,binance
+
+";
+    let transactions = replay::parse_csv(csv).unwrap();
+
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0].token, "DOGE");
+    assert_eq!(transactions[0].price, 0.15);
+    assert_eq!(transactions[0].volume, 250.0);
+    assert_eq!(transactions[0].side, Side::Buy);
+    assert_eq!(transactions[0].timestamp, DateTime::from_timestamp_millis(1_700_000_000_000).unwrap());
+    assert_eq!(transactions[1].token, "BTC");
+    assert_eq!(transactions[1].side, Side::Sell);
+    assert_eq!(transactions[1].timestamp - transactions[0].timestamp, TimeDelta::seconds(1));
+}
+
+#[test]
+fn test_parse_csv_optional_columns() {
+    let csv = "\
+token,price,volume,timestamp,side,is_maker,trade_id,source
+DOGE,0.15,250,1700000000000,buy,true,17,binance
+DOGE,0.15,250,1700000000001,sell,,a-17,
+DOGE,0.15,250,1700000000002,buy,false,,
+";
+    let transactions = replay::parse_csv(csv).unwrap();
+
+    assert_eq!(transactions[0].trade_id, Some(TradeId::Number(17)));
+    assert_eq!(transactions[1].trade_id, Some(TradeId::Text("a-17".to_string())));
+    assert_eq!(transactions[2].trade_id, None);
+    assert_eq!(transactions[0].source.as_deref(), Some("binance"));
+    assert_eq!(transactions[1].source, None);
+    assert_eq!(transactions[1].side, Side::Sell);
+    assert_eq!(
+        transactions.iter().map(|transaction| transaction.is_maker).collect::<Vec<_>>(),
+        vec![Some(true), None, Some(false)]
+    );
+
+    // The columns are optional
+    assert!(replay::parse_csv("token,price,volume,timestamp,is_buy\nDOGE,0.15,250,1700000000000,buy\n").unwrap()[0]
+        .trade_id
+        .is_none());
+}
+
+#[test]
+fn test_invalid_files_report_the_line() {
+    let missing_column = replay::parse_csv("token,price,volume,timestamp\nDOGE,0.15,250,1700000000000\n");
+    assert!(matches!(missing_column, Err(ReplayError::InvalidLine { line: 1, .. })));
+    let invalid_side = replay::parse_csv("token,price,volume,timestamp,side\nDOGE,0.15,250,1700000000000,long\n");
+    assert!(matches!(invalid_side, Err(ReplayError::InvalidLine { line: 2, .. })));
+
+    let invalid_price = replay::parse_csv(
+        "token,price,volume,timestamp,is_buy\nDOGE,0.15,250,1700000000000,buy\nDOGE,-1,250,1700000000001,buy\n",
+    );
+    assert!(matches!(invalid_price, Err(ReplayError::InvalidLine { line: 3, .. })));
+
+    let jsonl = "\
+{\"token\":\"DOGE\",\"price\":0.15,\"volume\":250.0,\"timestamp\":\"2023-11-14T22:13:20Z\",\"side\":\"buy\"}
+{\"token\":\"DOGE\",\"price\":0.15,\"volume\":0.0,\"timestamp\":\"2023-11-14T22:13:21Z\",\"side\":\"buy\"}
+";
+    assert!(matches!(replay::parse_jsonl(jsonl), Err(ReplayError::InvalidLine { line: 2, .. })));
+    assert_eq!(replay::parse_jsonl(jsonl.lines().next().unwrap()).unwrap().len(), 1);
+
+    assert_eq!(ReplayFormat::from_path(Path::new("incident.NDJSON")), Some(ReplayFormat::Jsonl));
+    assert!(matches!(
+        replay::load(Path::new("incident.parquet")),
+        Err(ReplayError::UnsupportedFormat(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_replay_keeps_scaled_spacing() {
+    let start = DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+    let transactions = vec![
+        transaction("DOGE", 0.15, start),
+        transaction("BTC", 37000.0, start + TimeDelta::seconds(1)),
+        // Recorded out of order, so played without a wait
+        transaction("DOGE", 0.16, start + TimeDelta::milliseconds(500)),
+        transaction("DOGE", 0.17, start + TimeDelta::seconds(3)),
+    ];
+    let replayer = TransactionReplayer::new(transactions, 10.0);
+    assert_eq!(replayer.len(), 4);
+    assert_eq!(replayer.duration(), Duration::from_millis(300));
+
+    let started = Instant::now();
+    let mut played = Vec::new();
+    let count = replayer
+        .run(|transaction| {
+            played.push((transaction, started.elapsed()));
+            async {}
+        })
+        .await;
+
+    assert_eq!(count, 4);
+    let prices: Vec<f64> = played.iter().map(|(transaction, _)| transaction.price).collect();
+    assert_eq!(prices, vec![0.15, 37000.0, 0.16, 0.17]);
+    assert!(played[1].1 >= Duration::from_millis(100));
+    assert!(played[3].1 >= Duration::from_millis(300));
+    assert!(played[3].1 < Duration::from_secs(1));
+
+    // Transactions are stamped with their play time, with the scaled gaps
+    let gaps: Vec<TimeDelta> = played.windows(2).map(|pair| pair[1].0.timestamp - pair[0].0.timestamp).collect();
+    assert_eq!(gaps, vec![TimeDelta::milliseconds(100), TimeDelta::zero(), TimeDelta::milliseconds(200)]);
+    assert!(played[0].0.timestamp > Utc::now() - TimeDelta::seconds(5));
+    assert_eq!(played[0].0.source.as_deref(), Some(replay::REPLAY_SOURCE));
+}
@@ -0,0 +1,50 @@
+use chrono::{Duration, TimeZone, Utc};
+
+use k_line::services::resample::{resample, TargetInterval};
+use k_line::{KLineService, Side, TimeInterval, Transaction};
+
+#[test]
+fn test_target_interval_parsing() {
+    assert_eq!("2h".parse::<TargetInterval>().unwrap().seconds(), 7200);
+    assert_eq!("90s".parse::<TargetInterval>().unwrap().seconds(), 90);
+    assert_eq!("3d".parse::<TargetInterval>().unwrap().to_string(), "3d");
+    assert_eq!("120m".parse::<TargetInterval>().unwrap().to_string(), "2h");
+    for invalid in ["", "h", "0m", "2w", "-1h", "31d", "1.5h"] {
+        assert!(invalid.parse::<TargetInterval>().is_err(), "{}", invalid);
+    }
+
+    let target: TargetInterval = "2h".parse().unwrap();
+    assert!(target.accepts(TimeInterval::Minute1));
+    assert!(target.accepts(TimeInterval::Hour1));
+    assert!(!target.accepts(TimeInterval::Trades(100)));
+    assert!(!"1h".parse::<TargetInterval>().unwrap().accepts(TimeInterval::Hour1));
+    assert!(!"7m".parse::<TargetInterval>().unwrap().accepts(TimeInterval::Minute5));
+}
+
+#[test]
+fn test_resample_rolls_up_whole_buckets() {
+    let service = KLineService::new();
+    let start = Utc.with_ymd_and_hms(2024, 1, 15, 13, 0, 0).unwrap();
+    // A trade every ten minutes for five hours, the price rising by one each time
+    for i in 0..30 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 1.0 + i as f64, 2.0, Side::Buy);
+        transaction.timestamp = start + Duration::minutes(10 * i);
+        service.process_transaction(&transaction);
+    }
+
+    let klines = service.get_klines("DOGE", TimeInterval::Minute1, start, start + Duration::hours(5), None);
+    let target: TargetInterval = "2h".parse().unwrap();
+    let resampled = resample(&klines, target, start + Duration::hours(5));
+
+    // Buckets align to the epoch: 12:00, 14:00 and 16:00
+    let opens: Vec<_> = resampled.iter().map(|kline| kline.timestamp).collect();
+    assert_eq!(opens, [-1, 1, 3].map(|hours| start + Duration::hours(hours)));
+    assert_eq!(resampled[1].close_time, start + Duration::hours(3) - Duration::milliseconds(1));
+    assert_eq!((resampled[1].open, resampled[1].close), (7.0, 18.0));
+    assert_eq!((resampled[1].low, resampled[1].high), (7.0, 18.0));
+    assert_eq!(resampled[1].volume, 24.0);
+    assert_eq!(resampled[1].candles, 12);
+    assert!(resampled[1].is_closed);
+    // The newest 1m candle is still open
+    assert!(!resampled[2].is_closed);
+}
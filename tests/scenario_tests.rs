@@ -0,0 +1,132 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::path::Path;
+use std::time::Duration;
+
+use k_line::services::scenario::{Scenario, ScenarioAction, ScenarioError};
+use k_line::{MockDataGenerator, Side, Transaction};
+
+const SCENARIO: &str = r#"
+seed = 42
+
+[[events]]
+at_secs = 10
+token = "DOGE"
+action = "price_target"
+price = 0.3
+duration_secs = 10
+
+[[events]]
+at_secs = 30
+action = "halt"
+duration_secs = 5
+
+[[events]]
+at_secs = 0
+token = "SHIB"
+action = "volume_surge"
+multiplier = 5.0
+duration_secs = 20
+
+[[events]]
+at_secs = 40
+action = "volatility"
+multiplier = 0.0
+"#;
+
+fn trades(transactions: &[Transaction]) -> Vec<(String, f64, f64, DateTime<Utc>, Side)> {
+    transactions
+        .iter()
+        .map(|t| (t.token.clone(), t.price, t.volume, t.timestamp, t.side))
+        .collect()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn test_parse_toml_and_json() {
+    let scenario = Scenario::from_toml(SCENARIO).unwrap();
+    assert_eq!(scenario.seed, 42);
+    // Events are ordered by time, and the scenario lasts until the last one is over
+    assert_eq!(scenario.events[0].at_secs, 0);
+    assert_eq!(scenario.events[1].action, ScenarioAction::PriceTarget { price: 0.3, duration_secs: 10 });
+    assert_eq!(scenario.duration(), Duration::from_secs(40));
+
+    let json = r#"{"duration_secs": 60, "events": [{"at_secs": 5, "action": "halt", "duration_secs": 2}]}"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    assert_eq!(scenario.events[0].action, ScenarioAction::Halt { duration_secs: 2 });
+    assert_eq!(scenario.duration(), Duration::from_secs(60));
+
+    let untargeted = r#"{"events": [{"at_secs": 5, "action": "price_target", "price": 1.0}]}"#;
+    assert!(matches!(Scenario::from_json(untargeted), Err(ScenarioError::Invalid(_))));
+    let unknown = r#"{"events": [{"at_secs": 5, "action": "rug_pull"}]}"#;
+    assert!(matches!(Scenario::from_json(unknown), Err(ScenarioError::Invalid(_))));
+    assert!(matches!(
+        Scenario::load(Path::new("scenario.yaml")),
+        Err(ScenarioError::UnsupportedFormat(_))
+    ));
+
+    let tokens = vec!["DOGE".to_string()];
+    assert!(Scenario::from_toml(SCENARIO).unwrap().check_tokens(&tokens).is_err());
+}
+
+#[test]
+fn test_state_follows_the_timeline() {
+    let scenario = Scenario::from_toml(SCENARIO).unwrap();
+    let at = |secs: f64| Duration::from_secs_f64(secs);
+
+    for (secs, price) in [(0.0, 0.1), (10.0, 0.1), (15.0, 0.2), (20.0, 0.3), (60.0, 0.3)] {
+        assert_close(scenario.state("DOGE", 0.1, at(secs)).price, price);
+    }
+    assert_close(scenario.state("SHIB", 0.1, at(15.0)).price, 0.1);
+
+    assert_eq!(scenario.state("SHIB", 1.0, at(5.0)).volume_multiplier, 5.0);
+    assert_eq!(scenario.state("SHIB", 1.0, at(20.0)).volume_multiplier, 1.0);
+    assert_eq!(scenario.state("DOGE", 1.0, at(5.0)).volume_multiplier, 1.0);
+
+    assert!(!scenario.state("DOGE", 1.0, at(29.0)).halted);
+    assert!(scenario.state("PEPE", 1.0, at(32.0)).halted);
+    assert!(!scenario.state("DOGE", 1.0, at(35.0)).halted);
+
+    assert_eq!(scenario.state("DOGE", 1.0, at(39.0)).volatility_multiplier, 1.0);
+    assert_eq!(scenario.state("DOGE", 1.0, at(41.0)).volatility_multiplier, 0.0);
+}
+
+#[test]
+fn test_later_target_starts_from_the_current_price() {
+    let json = r#"{"events": [
+        {"at_secs": 0, "token": "DOGE", "action": "price_target", "price": 2.0, "duration_secs": 10},
+        {"at_secs": 5, "token": "DOGE", "action": "price_target", "price": 0.5, "duration_secs": 10}
+    ]}"#;
+    let scenario = Scenario::from_json(json).unwrap();
+    assert_close(scenario.state("DOGE", 1.0, Duration::from_secs(5)).price, 1.5);
+    assert_close(scenario.state("DOGE", 1.0, Duration::from_secs(10)).price, 1.0);
+    assert_close(scenario.state("DOGE", 1.0, Duration::from_secs(15)).price, 0.5);
+}
+
+#[test]
+fn test_generated_scenarios_are_repeatable() {
+    let scenario = Scenario::from_toml(SCENARIO).unwrap();
+    let generator = MockDataGenerator::new();
+    let start = Utc::now();
+
+    let first = generator.generate_scenario(&scenario, 1000, start);
+    let second = generator.generate_scenario(&scenario, 1000, start);
+    assert_eq!(trades(&first), trades(&second));
+
+    // Three tokens for 40 seconds, less the 5 halted seconds
+    assert_eq!(first.len(), 3 * 35);
+    assert!(first
+        .iter()
+        .all(|transaction| transaction.timestamp < start + TimeDelta::seconds(30)
+            || transaction.timestamp >= start + TimeDelta::seconds(35)));
+
+    // Trades follow the scripted price
+    let last_doge = first.iter().rev().find(|transaction| transaction.token == "DOGE").unwrap();
+    assert!((last_doge.price - 0.3).abs() <= 0.3 * 0.02);
+
+    let mut reseeded = scenario.clone();
+    reseeded.seed = 7;
+    assert_ne!(trades(&generator.generate_scenario(&reseeded, 1000, start)), trades(&first));
+}
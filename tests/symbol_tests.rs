@@ -0,0 +1,75 @@
+use k_line::config::{Config, TokenConfig};
+use k_line::services::symbols::SymbolSpec;
+use k_line::services::SymbolTable;
+use k_line::{Side, Transaction};
+
+fn spec(price_precision: Option<u32>, quantity_precision: Option<u32>, tick_size: Option<f64>) -> SymbolSpec {
+    SymbolSpec {
+        symbol: "DOGE".to_string(),
+        base_price: 0.15,
+        volatility: 5.0,
+        price_precision,
+        quantity_precision,
+        tick_size,
+    }
+}
+
+#[test]
+fn test_round_price_to_precision() {
+    let decimals = spec(Some(4), None, None);
+    assert_eq!(decimals.round_price(0.152349), 0.1523);
+    assert_eq!(decimals.round_price(0.15236), 0.1524);
+    assert_eq!(decimals.round_price(0.15), 0.15);
+}
+
+#[test]
+fn test_round_price_to_tick_size() {
+    // Multiples of the tick print without floating-point noise
+    let ticks = spec(None, None, Some(0.0005));
+    assert_eq!(ticks.round_price(0.15233), 0.1525);
+    assert_eq!(ticks.round_price(0.15224), 0.152);
+    assert_eq!(ticks.round_price(0.1501), 0.15);
+
+    let quarters = spec(None, None, Some(0.25));
+    assert_eq!(quarters.round_price(101.13), 101.25);
+    assert_eq!(quarters.round_price(101.12), 101.0);
+}
+
+#[test]
+fn test_round_quantity() {
+    let hundredths = spec(None, Some(2), None);
+    assert_eq!(hundredths.round_quantity(123.456), 123.46);
+    assert_eq!(spec(None, None, None).round_quantity(123.456), 123.456);
+}
+
+#[test]
+fn test_symbol_table_normalizes_configured_tokens() {
+    let mut config = Config::default();
+    config.tokens.supported_tokens.push(TokenConfig {
+        symbol: "WIF".to_string(),
+        base_price: 2.5,
+        volatility: 5.0,
+        price_precision: Some(2),
+        quantity_precision: Some(0),
+        tick_size: Some(0.05),
+        arrival_rate: None,
+        volume: None,
+    });
+    let table = SymbolTable::from_config(&config);
+
+    let normalized = table.normalize(&Transaction::new("WIF".to_string(), 2.5371, 10.6, Side::Buy));
+    assert_eq!(normalized.price, 2.55);
+    assert_eq!(normalized.volume, 11.0);
+
+    // Tokens without rules are left alone
+    let unchanged = table.normalize(&Transaction::new("BONK".to_string(), 0.123456789, 1.5, Side::Buy));
+    assert_eq!(unchanged.price, 0.123456789);
+    assert_eq!(unchanged.volume, 1.5);
+
+    let symbols: Vec<String> = table.specs().into_iter().map(|spec| spec.symbol).collect();
+    assert_eq!(symbols, vec!["DOGE", "PEPE", "SHIB", "WIF"]);
+
+    config.tokens.supported_tokens.retain(|token| token.symbol != "WIF");
+    table.apply_config(&config);
+    assert!(table.get("WIF").is_none());
+}
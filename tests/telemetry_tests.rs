@@ -0,0 +1,33 @@
+#![cfg(feature = "server")]
+
+use std::sync::Arc;
+use tracing_subscriber::layer::SubscriberExt;
+
+use k_line::config::TelemetryConfig;
+use k_line::services::Metrics;
+use k_line::telemetry::Telemetry;
+use k_line::WsManager;
+
+#[tokio::test]
+async fn test_disabled_telemetry_starts_nothing() {
+    assert!(Telemetry::init(&TelemetryConfig::default()).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_spans_export_without_a_collector() {
+    let config = TelemetryConfig {
+        enabled: true,
+        // Nothing listens here, so every export fails without blocking the service
+        endpoint: "http://127.0.0.1:1".to_string(),
+        ..TelemetryConfig::default()
+    };
+    let telemetry = Telemetry::init(&config).unwrap().unwrap();
+    telemetry.observe(Arc::new(Metrics::new()), Arc::new(WsManager::new()));
+
+    let subscriber = tracing_subscriber::registry().with(telemetry.layer());
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("transaction", token = "DOGE").entered();
+        tracing::info!("Processed transaction");
+    });
+    telemetry.shutdown();
+}
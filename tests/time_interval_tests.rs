@@ -1,5 +1,5 @@
 use chrono::{Timelike, Utc, TimeZone};
-use k_line::models::{TimeInterval, Transaction};
+use k_line::models::{Side, TimeInterval, Transaction};
 use k_line::services::KLineService;
 
 #[tokio::test]
@@ -17,7 +17,10 @@ async fn test_hour_interval_alignment() {
         price: 0.15,
         volume: 100.0,
         timestamp: test_time,
-        is_buy: true,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
     };
     
     service.process_transaction(&transaction);
@@ -57,7 +60,10 @@ async fn test_minute_interval_alignment() {
         price: 0.15,
         volume: 100.0,
         timestamp: test_time,
-        is_buy: true,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
     };
     
     service.process_transaction(&transaction);
@@ -97,7 +103,10 @@ async fn test_5minute_interval_alignment() {
         price: 0.15,
         volume: 100.0,
         timestamp: test_time,
-        is_buy: true,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
     };
     
     service.process_transaction(&transaction);
@@ -137,7 +146,10 @@ async fn test_15minute_interval_alignment() {
         price: 0.15,
         volume: 100.0,
         timestamp: test_time,
-        is_buy: true,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
     };
     
     service.process_transaction(&transaction);
@@ -177,7 +189,10 @@ async fn test_second_interval_alignment() {
         price: 0.15,
         volume: 100.0,
         timestamp: test_time,
-        is_buy: true,
+        side: Side::Buy,
+        is_maker: None,
+        trade_id: None,
+        source: None,
     };
     
     service.process_transaction(&transaction);
@@ -221,7 +236,10 @@ async fn test_multiple_transactions_same_interval() {
             price,
             volume,
             timestamp,
-            is_buy: true,
+            side: Side::Buy,
+            is_maker: None,
+            trade_id: None,
+            source: None,
         };
         service.process_transaction(&transaction);
     }
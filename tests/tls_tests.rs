@@ -0,0 +1,95 @@
+#![cfg(feature = "server")]
+
+use actix_web::{web, App};
+use awc::ws;
+use futures_util::{SinkExt, StreamExt};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use k_line::config::TlsConfig;
+use k_line::tls::load_server_config;
+use k_line::{configure_websocket_routes, KLineService, WsManager};
+
+/// Write a self-signed certificate for localhost, returning its PEM and the TLS config
+fn self_signed_tls() -> (String, TlsConfig) {
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir: PathBuf = std::env::temp_dir().join(format!("k-line-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let cert_pem = certified.cert.pem();
+    let cert_path = dir.join("server.crt");
+    let key_path = dir.join("server.key");
+    std::fs::write(&cert_path, &cert_pem).unwrap();
+    std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+    let tls = TlsConfig {
+        enabled: true,
+        cert_path: Some(cert_path.to_string_lossy().into_owned()),
+        key_path: Some(key_path.to_string_lossy().into_owned()),
+    };
+    (cert_pem, tls)
+}
+
+#[test]
+fn test_load_server_config_errors() {
+    let (_, tls) = self_signed_tls();
+    assert!(load_server_config(&tls).is_ok());
+
+    let missing = TlsConfig {
+        enabled: true,
+        cert_path: Some("/nonexistent/server.crt".to_string()),
+        key_path: tls.key_path.clone(),
+    };
+    assert!(load_server_config(&missing).is_err());
+
+    // A certificate is not a private key
+    let swapped = TlsConfig {
+        enabled: true,
+        cert_path: tls.cert_path.clone(),
+        key_path: tls.cert_path.clone(),
+    };
+    assert!(load_server_config(&swapped).is_err());
+}
+
+#[actix_rt::test]
+async fn test_websocket_over_tls() {
+    let (cert_pem, tls) = self_signed_tls();
+    let server_config = load_server_config(&tls).unwrap();
+
+    let server = actix_test::start_with(actix_test::config().rustls_0_23(server_config), || {
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(Arc::new(WsManager::new())))
+            .configure(configure_websocket_routes)
+    });
+
+    // Trust only the test certificate
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut cert_pem.as_bytes()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let client_config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let client = awc::Client::builder()
+        .connector(awc::Connector::new().rustls_0_23(Arc::new(client_config)))
+        .finish();
+
+    let url = format!("wss://localhost:{}/ws", server.addr().port());
+    let (_, mut connection) = client.ws(url).connect().await.unwrap();
+    connection
+        .send(ws::Message::Text(r#"{"action":"ping"}"#.into()))
+        .await
+        .unwrap();
+
+    loop {
+        match connection.next().await.unwrap().unwrap() {
+            ws::Frame::Text(bytes) => {
+                let response: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+                assert_eq!(response["type"], "pong");
+                break;
+            }
+            _ => continue,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use k_line::config::UnknownTokenPolicy;
+use k_line::services::token_registry::TokenAction;
+use k_line::services::TokenRegistry;
+use k_line::{Side, Transaction};
+
+fn registry(policy: UnknownTokenPolicy) -> TokenRegistry {
+    TokenRegistry::new(policy, vec!["DOGE".to_string()])
+}
+
+#[test]
+fn test_configured_tokens_are_accepted() {
+    for policy in [
+        UnknownTokenPolicy::AutoRegister,
+        UnknownTokenPolicy::Reject,
+        UnknownTokenPolicy::Quarantine,
+    ] {
+        let admission = registry(policy).admit("DOGE");
+        assert!(admission.accepted);
+        assert!(admission.event.is_none());
+    }
+}
+
+#[test]
+fn test_auto_register_reports_first_trade_only() {
+    let registry = registry(UnknownTokenPolicy::AutoRegister);
+
+    let admission = registry.admit("PEPE");
+    assert!(admission.accepted);
+    let event = admission.event.unwrap();
+    assert_eq!(event.token, "PEPE");
+    assert_eq!(event.action, TokenAction::Registered);
+
+    let admission = registry.admit("PEPE");
+    assert!(admission.accepted);
+    assert!(admission.event.is_none());
+    assert_eq!(registry.registered_tokens(), vec!["DOGE", "PEPE"]);
+    assert!(registry.unregistered_tokens().is_empty());
+}
+
+#[test]
+fn test_reject_drops_transactions() {
+    let registry = registry(UnknownTokenPolicy::Reject);
+
+    let admission = registry.admit("PEPE");
+    assert!(!admission.accepted);
+    assert_eq!(admission.event.unwrap().action, TokenAction::Rejected);
+
+    let admission = registry.admit("PEPE");
+    assert!(!admission.accepted);
+    assert!(admission.event.is_none());
+
+    let unregistered = registry.unregistered_tokens();
+    assert_eq!(unregistered.len(), 1);
+    assert_eq!(unregistered[0].status, TokenAction::Rejected);
+    assert_eq!(unregistered[0].dropped_transactions, 2);
+}
+
+#[test]
+fn test_quarantine_until_registered() {
+    let registry = registry(UnknownTokenPolicy::Quarantine);
+
+    let admission = registry.admit("PEPE");
+    assert!(!admission.accepted);
+    assert_eq!(admission.event.unwrap().action, TokenAction::Quarantined);
+
+    assert!(registry.register("PEPE"));
+    assert!(!registry.register("PEPE"));
+    assert!(registry.unregistered_tokens().is_empty());
+
+    let admission = registry.admit("PEPE");
+    assert!(admission.accepted);
+    assert!(admission.event.is_none());
+}
+
+#[test]
+fn test_quarantined_transactions_are_released_on_registration() {
+    let quarantine = registry(UnknownTokenPolicy::Quarantine).with_quarantine_capacity(2);
+    for price in [0.1, 0.2, 0.3] {
+        let admission = quarantine.admit_transaction(&Transaction::new("PEPE".to_string(), price, 10.0, Side::Buy));
+        assert!(!admission.accepted);
+    }
+
+    // Nothing is released before registration, and only the latest are held
+    assert!(quarantine.release("PEPE").is_empty());
+    assert!(quarantine.register("PEPE"));
+    let prices: Vec<f64> = quarantine.release("PEPE").iter().map(|transaction| transaction.price).collect();
+    assert_eq!(prices, vec![0.2, 0.3]);
+    assert!(quarantine.release("PEPE").is_empty());
+
+    // Rejected transactions are not held
+    let rejecting = registry(UnknownTokenPolicy::Reject);
+    rejecting.admit_transaction(&Transaction::new("PEPE".to_string(), 0.1, 10.0, Side::Buy));
+    rejecting.register("PEPE");
+    assert!(rejecting.release("PEPE").is_empty());
+}
@@ -0,0 +1,35 @@
+use k_line::services::TradeService;
+use k_line::{Side, Transaction};
+
+fn trade(token: &str, price: f64) -> Transaction {
+    Transaction::new(token.to_string(), price, 10.0, Side::Buy)
+}
+
+#[test]
+fn test_recent_trades_oldest_first() {
+    let service = TradeService::new(10);
+    for price in [1.0, 2.0, 3.0] {
+        service.record(&trade("DOGE", price));
+    }
+    service.record(&trade("SHIB", 9.0));
+
+    let prices: Vec<f64> = service.recent("DOGE", 10).iter().map(|t| t.price).collect();
+    assert_eq!(prices, vec![1.0, 2.0, 3.0]);
+
+    // The limit keeps the newest trades
+    let prices: Vec<f64> = service.recent("DOGE", 2).iter().map(|t| t.price).collect();
+    assert_eq!(prices, vec![2.0, 3.0]);
+
+    assert!(service.recent("PEPE", 10).is_empty());
+}
+
+#[test]
+fn test_ring_buffer_evicts_oldest() {
+    let service = TradeService::new(3);
+    for price in 1..=5 {
+        service.record(&trade("DOGE", price as f64));
+    }
+
+    let prices: Vec<f64> = service.recent("DOGE", 10).iter().map(|t| t.price).collect();
+    assert_eq!(prices, vec![3.0, 4.0, 5.0]);
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "typescript")]
+
+use k_line::typescript::{definitions, DEFINITIONS_PATH};
+
+#[test]
+fn test_definitions_are_up_to_date() {
+    let committed = std::fs::read_to_string(DEFINITIONS_PATH).unwrap();
+    assert!(
+        committed == definitions(),
+        "{} is out of date, run `cargo run --features typescript --bin typegen`",
+        DEFINITIONS_PATH
+    );
+}
+
+#[test]
+fn test_definitions_follow_the_wire_format() {
+    let definitions = definitions();
+    // Messages are tagged the way serde tags them
+    assert!(definitions.contains(r#"{ "type": "kline", data: KLine, }"#));
+    assert!(definitions.contains(r#"{ "action": "resume", topic: string, since: number, }"#));
+    // Candles carry the fields added when serializing
+    assert!(definitions.contains("close_time: string"));
+    assert!(definitions.contains("is_partial?: boolean"));
+    assert!(definitions.contains("export type TimeInterval = string;"));
+    assert!(!definitions.contains("bigint"));
+}
@@ -0,0 +1,207 @@
+#![cfg(feature = "server")]
+
+use actix_web::{test, web, App};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use k_line::config::{Config, FsyncPolicy, WalConfig};
+use k_line::services::{KLineAmendment, KLineSnapshot, TransactionLog, TransactionPipeline, WalEntry};
+use k_line::{configure_routes, KLineService, Side, TimeInterval, Transaction, WsManager};
+
+fn wal_config(segment_max_bytes: u64) -> WalConfig {
+    let dir = std::env::temp_dir().join(format!("k-line-wal-{}", uuid::Uuid::new_v4()));
+    WalConfig {
+        enabled: true,
+        dir: dir.to_string_lossy().into_owned(),
+        segment_max_bytes,
+        fsync: FsyncPolicy::Always,
+        fsync_interval_ms: 1000,
+    }
+}
+
+fn segments(config: &WalConfig) -> Vec<PathBuf> {
+    let mut segments: Vec<PathBuf> = std::fs::read_dir(&config.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    segments.sort();
+    segments
+}
+
+fn trade(price: f64) -> Transaction {
+    Transaction::new("doge".to_string(), price, 10.0, Side::Buy)
+}
+
+/// Prices of the logged transactions after `sequence`
+fn logged_prices(wal: &TransactionLog, sequence: u64) -> Vec<f64> {
+    wal.read_after(sequence)
+        .unwrap()
+        .iter()
+        .filter_map(|entry| match entry {
+            WalEntry::Transaction(transaction) => Some(transaction.price),
+            WalEntry::Amendment(_) => None,
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_replay_rebuilds_candles() {
+    let config = wal_config(1024 * 1024);
+    let wal = Arc::new(TransactionLog::open(&config).unwrap());
+    let kline_service = Arc::new(KLineService::new());
+    let pipeline = TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new())).with_wal(Some(wal));
+    for price in [0.1, 0.3, 0.2] {
+        pipeline.handle(&trade(price)).unwrap();
+    }
+    pipeline.backfill([trade(0.25)].into_iter());
+
+    // A restart continues the sequence and replays every logged transaction
+    let reopened = Arc::new(TransactionLog::open(&config).unwrap());
+    assert_eq!(reopened.last_sequence(), 4);
+    let transactions = reopened.read_after(0).unwrap();
+    assert!(matches!(&transactions[0], WalEntry::Transaction(transaction) if transaction.token == "DOGE"));
+
+    let restored = Arc::new(KLineService::new());
+    let pipeline = TransactionPipeline::new(restored.clone(), Arc::new(WsManager::new())).with_wal(Some(reopened.clone()));
+    assert_eq!(pipeline.replay_wal(transactions.into_iter()), 4);
+    let (before, after) = (
+        kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap(),
+        restored.get_current_kline("DOGE", TimeInterval::Minute1).unwrap(),
+    );
+    assert_eq!((after.high, after.close, after.volume), (before.high, before.close, before.volume));
+
+    // Replayed transactions are not logged twice
+    assert_eq!(reopened.last_sequence(), 4);
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_segments_rotate_and_are_removed() {
+    let config = wal_config(1);
+    let wal = TransactionLog::open(&config).unwrap();
+    for price in [0.1, 0.2, 0.3] {
+        wal.append(&[trade(price)]).unwrap();
+    }
+    // One segment per record, and the empty one being appended to
+    assert_eq!(segments(&config).len(), 4);
+
+    assert_eq!(wal.remove_through(2).unwrap(), 2);
+    assert_eq!(segments(&config).len(), 2);
+    assert_eq!(logged_prices(&wal, 2), vec![0.3]);
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_torn_records_are_ignored() {
+    let config = wal_config(1024 * 1024);
+    let wal = TransactionLog::open(&config).unwrap();
+    wal.append(&[trade(0.1), trade(0.2)]).unwrap();
+    drop(wal);
+
+    // A crash in the middle of a write leaves part of a record behind
+    let segment = segments(&config).pop().unwrap();
+    let mut content = std::fs::read(&segment).unwrap();
+    content.extend_from_slice(b"{\"sequence\":3,\"transac");
+    std::fs::write(&segment, content).unwrap();
+
+    let wal = TransactionLog::open(&config).unwrap();
+    assert_eq!(wal.last_sequence(), 2);
+    wal.append(&[trade(0.3)]).unwrap();
+    assert_eq!(logged_prices(&wal, 0), vec![0.1, 0.2, 0.3]);
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_snapshot_records_the_log_position() {
+    let wal_config = wal_config(1);
+    let wal = Arc::new(TransactionLog::open(&wal_config).unwrap());
+    let kline_service = Arc::new(KLineService::new());
+    let pipeline =
+        TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new())).with_wal(Some(wal.clone()));
+    pipeline.handle(&trade(0.1)).unwrap();
+    pipeline.handle(&trade(0.2)).unwrap();
+
+    let path = std::env::temp_dir().join(format!("k-line-snapshot-{}.json", uuid::Uuid::new_v4()));
+    let mut config = Config::default();
+    config.snapshot.path = Some(path.to_string_lossy().into_owned());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(kline_service))
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(wal.clone()))
+            .configure(configure_routes),
+    )
+    .await;
+    let req = test::TestRequest::post().uri("/api/v1/admin/snapshot").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["wal_sequence"], 2);
+
+    // Only what the snapshot misses is replayed, and the segments it covers are gone
+    pipeline.handle(&trade(0.3)).unwrap();
+    let snapshot = KLineSnapshot::load(&path).unwrap();
+    assert_eq!(snapshot.wal_sequence, Some(2));
+    assert_eq!(logged_prices(&wal, 2), vec![0.3]);
+    assert_eq!(segments(&wal_config).len(), 2);
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_dir_all(&wal_config.dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_checkpoint_waits_for_logged_transactions_to_apply() {
+    let config = wal_config(1024 * 1024);
+    let wal = Arc::new(TransactionLog::open(&config).unwrap());
+
+    // A writer has logged a transaction but not applied it yet
+    let applying = wal.begin_apply();
+    wal.append(&[trade(0.1)]).unwrap();
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let checkpointer = wal.clone();
+    let handle = std::thread::spawn(move || sender.send(checkpointer.checkpoint(|| "copied")).unwrap());
+    assert!(receiver.recv_timeout(std::time::Duration::from_millis(100)).is_err());
+
+    drop(applying);
+    assert_eq!(receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap(), (1, "copied"));
+    handle.join().unwrap();
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
+
+#[actix_web::test]
+async fn test_amendments_are_logged_and_replayed() {
+    let config = wal_config(1024 * 1024);
+    let wal = Arc::new(TransactionLog::open(&config).unwrap());
+    let kline_service = Arc::new(KLineService::new());
+    let pipeline =
+        TransactionPipeline::new(kline_service.clone(), Arc::new(WsManager::new())).with_wal(Some(wal.clone()));
+    // Both trades fall in the same candle
+    let (first, mut second) = (trade(0.1), trade(0.2));
+    second.timestamp = first.timestamp;
+    pipeline.handle(&first).unwrap();
+    let kline = kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    pipeline
+        .amend(&KLineAmendment {
+            token: "doge".to_string(),
+            interval: TimeInterval::Minute1,
+            timestamp: kline.timestamp,
+            open: 0.1,
+            high: 0.3,
+            low: 0.1,
+            close: 0.1,
+            volume: 10.0,
+            buy_volume: None,
+            sell_volume: None,
+        })
+        .unwrap();
+    pipeline.handle(&second).unwrap();
+
+    // The amendment is replayed between the transactions it came between
+    let entries = wal.read_after(0).unwrap();
+    assert!(matches!(&entries[1], WalEntry::Amendment(amendment) if amendment.token == "DOGE"));
+    let restored = Arc::new(KLineService::new());
+    let replay = TransactionPipeline::new(restored.clone(), Arc::new(WsManager::new()));
+    assert_eq!(replay.replay_wal(entries.into_iter()), 3);
+    let kline = restored.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    assert_eq!((kline.high, kline.close, kline.volume), (0.3, 0.2, 20.0));
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "server")]
+
+use actix_web::{web, App, HttpRequest, HttpResponse};
+use chrono::Utc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use k_line::config::{WebhookEventType, WebhookTargetConfig, WebhooksConfig};
+use k_line::services::webhooks::{deliver_webhooks, sign, WebhookEvent, SIGNATURE_HEADER};
+use k_line::services::WebhookDispatcher;
+use k_line::{KLine, TimeInterval};
+
+fn closed_kline(token: &str, interval: TimeInterval) -> KLine {
+    let mut kline = KLine::new(token.to_string(), Utc::now(), interval, 1.0, 10.0);
+    kline.close();
+    kline
+}
+
+fn target(url: &str) -> WebhookTargetConfig {
+    WebhookTargetConfig {
+        url: url.to_string(),
+        events: Vec::new(),
+        tokens: Vec::new(),
+        intervals: Vec::new(),
+    }
+}
+
+#[test]
+fn test_signature_is_hex_hmac_sha256() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        sign("Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn test_dispatcher_applies_target_filters() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let dispatcher = WebhookDispatcher::new(
+        vec![
+            target("http://all"),
+            WebhookTargetConfig {
+                events: vec![WebhookEventType::KlineClosed],
+                tokens: vec!["DOGE".to_string()],
+                intervals: vec!["1m".to_string()],
+                ..target("http://doge-1m")
+            },
+            WebhookTargetConfig {
+                events: vec![WebhookEventType::Alert],
+                ..target("http://alerts")
+            },
+        ],
+        sender,
+    );
+
+    dispatcher.dispatch(&WebhookEvent::KlineClosed(closed_kline("DOGE", TimeInterval::Minute1)));
+    let urls: Vec<String> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|d| d.url).collect();
+    assert_eq!(urls, ["http://all", "http://doge-1m"]);
+
+    dispatcher.dispatch(&WebhookEvent::KlineClosed(closed_kline("DOGE", TimeInterval::Hour1)));
+    dispatcher.dispatch(&WebhookEvent::KlineClosed(closed_kline("SHIB", TimeInterval::Minute1)));
+    let urls: Vec<String> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|d| d.url).collect();
+    assert_eq!(urls, ["http://all", "http://all"]);
+}
+
+#[actix_rt::test]
+async fn test_delivery_is_signed_and_retried() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let (received_sender, mut received) = mpsc::unbounded_channel::<(String, web::Bytes)>();
+    let server = {
+        let attempts = attempts.clone();
+        actix_test::start(move || {
+            let attempts = attempts.clone();
+            let received_sender = received_sender.clone();
+            App::new().route(
+                "/hook",
+                web::post().to(move |request: HttpRequest, body: web::Bytes| {
+                    // Fail the first attempt so the delivery is retried
+                    let response = if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                        HttpResponse::InternalServerError().finish()
+                    } else {
+                        let signature = request
+                            .headers()
+                            .get(SIGNATURE_HEADER)
+                            .and_then(|value| value.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        let _ = received_sender.send((signature, body));
+                        HttpResponse::Ok().finish()
+                    };
+                    async { response }
+                }),
+            )
+        })
+    };
+
+    let config = WebhooksConfig {
+        secret: Some("secret".to_string()),
+        initial_backoff_ms: 10,
+        ..WebhooksConfig::default()
+    };
+    let (sender, receiver) = mpsc::unbounded_channel();
+    actix_web::rt::spawn(deliver_webhooks(receiver, config));
+    let dispatcher = WebhookDispatcher::new(vec![target(&server.url("/hook"))], sender);
+    dispatcher.dispatch(&WebhookEvent::KlineClosed(closed_kline("DOGE", TimeInterval::Minute1)));
+
+    let (signature, body) = tokio::time::timeout(std::time::Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    assert_eq!(signature, sign("secret", &body));
+
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["event"], "kline_closed");
+    assert_eq!(payload["data"]["token"], "DOGE");
+    assert_eq!(payload["data"]["is_closed"], true);
+}
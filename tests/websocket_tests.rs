@@ -0,0 +1,1883 @@
+#![cfg(feature = "server")]
+
+use actix_web::{web, App};
+use awc::ws;
+use chrono::{DurationRound, TimeDelta, Utc};
+use futures_util::{SinkExt, StreamExt};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+use k_line::api::websocket::{SystemStatus, WireFormat};
+use k_line::api::auth::{Authenticator, API_KEY_HEADER};
+use k_line::config::{ApiKeyConfig, ApiKeyScope, Config, SlowConsumerPolicy, UnknownTokenPolicy};
+use k_line::services::config_reload::ConfigUpdate;
+use k_line::services::metrics::OpsMetrics;
+use k_line::services::alerts::{AlertCondition, NewAlert};
+use k_line::services::token_registry::{ListingEvent, TokenRegistry};
+use k_line::services::{AlertService, KLineAmendment, OrderBookSimulator, TradeService};
+use k_line::{configure_routes, configure_websocket_routes, KLineService, Side, TimeInterval, Transaction, WsManager};
+
+type WsConnection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+/// Start a test server with the WebSocket routes and the given configuration
+fn start_server(config: Config) -> (actix_test::TestServer, Arc<WsManager>) {
+    start_server_with(config, WsManager::new(), Arc::new(KLineService::new()))
+}
+
+fn start_server_with(
+    config: Config,
+    ws_manager: WsManager,
+    kline_service: Arc<KLineService>,
+) -> (actix_test::TestServer, Arc<WsManager>) {
+    let ws_manager = Arc::new(ws_manager);
+    let manager = ws_manager.clone();
+
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(manager.clone()))
+            .app_data(web::Data::new(Arc::new(Authenticator::from_config(&config))))
+            .app_data(web::Data::new(Arc::new(TokenRegistry::from_config(&config))))
+            .app_data(web::Data::new(config.clone()))
+            .configure(configure_websocket_routes)
+    });
+
+    (server, ws_manager)
+}
+
+async fn send_json(connection: &mut WsConnection, message: serde_json::Value) {
+    connection
+        .send(ws::Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+}
+
+/// Receive the next text frame as JSON, skipping control frames
+async fn next_json(connection: &mut WsConnection) -> serde_json::Value {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Text(bytes) = frame {
+            return serde_json::from_slice(&bytes).unwrap();
+        }
+    }
+}
+
+fn sample_ops_metrics() -> OpsMetrics {
+    OpsMetrics {
+        timestamp: Utc::now(),
+        ingest_rate: 30.0,
+        transaction_broadcast_rate: 30.0,
+        kline_broadcast_rate: 150.0,
+        event_queue_depth: 0,
+        sessions: 1,
+        subscriptions: 1,
+        dropped_messages: 0,
+        slow_consumer_disconnects: 0,
+    }
+}
+
+#[actix_rt::test]
+async fn test_ops_metrics_requires_admin_key() {
+    let mut config = Config::default();
+    config.admin.api_key = Some("secret".to_string());
+    let (server, _) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "ops_metrics"}}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "ops_metrics", "api_key": "wrong"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+}
+
+#[actix_rt::test]
+async fn test_ops_metrics_stream() {
+    let mut config = Config::default();
+    config.admin.api_key = Some("secret".to_string());
+    let (server, ws_manager) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "ops_metrics", "api_key": "secret"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["type"], "ops_metrics");
+    // The key is never echoed back to the client
+    assert!(response["subscription"].get("api_key").is_none());
+
+    ws_manager.broadcast_ops_metrics(&sample_ops_metrics());
+
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "ops_metrics");
+    assert_eq!(response["data"]["kline_broadcast_rate"], 150.0);
+}
+
+#[actix_rt::test]
+async fn test_api_key_auth() {
+    let mut config = Config::default();
+    config.auth.enabled = true;
+    config.auth.keys = vec![
+        ApiKeyConfig {
+            key: "reader".to_string(),
+            scope: ApiKeyScope::Read,
+            name: None,
+        },
+        ApiKeyConfig {
+            key: "operator".to_string(),
+            scope: ApiKeyScope::Admin,
+            name: None,
+        },
+    ];
+    let (server, _) = start_server(config);
+
+    // Unknown keys are refused at the handshake
+    let result = awc::Client::new()
+        .ws(server.url("/ws?api_key=wrong"))
+        .connect()
+        .await;
+    assert!(result.is_err());
+
+    // Without a key nothing can be subscribed until an auth message is sent
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let subscribe = serde_json::json!({
+        "action": "subscribe",
+        "subscription": {"type": "klines", "token": "DOGE", "interval": "1m"}
+    });
+    send_json(&mut connection, subscribe.clone()).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert_eq!(response["message"], "Authentication required");
+    assert_eq!(response["code"], "auth_required");
+
+    send_json(&mut connection, serde_json::json!({"action": "auth", "api_key": "reader"})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "authenticated");
+    assert_eq!(response["scope"], "read");
+
+    send_json(&mut connection, subscribe).await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    // Read keys cannot open admin streams
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "ops_metrics"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "error");
+
+    // An admin key at the handshake covers admin subscriptions
+    let (_, mut connection) = awc::Client::new()
+        .ws(server.url("/ws"))
+        .header(API_KEY_HEADER, "operator")
+        .connect()
+        .await
+        .unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "ops_metrics"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+}
+
+#[actix_rt::test]
+async fn test_auth_handshake_with_token() {
+    let mut config = Config::default();
+    config.auth.enabled = true;
+    config.auth.jwt_secret = Some("jwt-secret".to_string());
+    config.auth.websocket_auth_timeout = 1;
+    let (server, _) = start_server(config);
+    let token = |scope: &str, exp: i64, secret: &str| {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({"sub": "dashboard", "scope": scope, "exp": exp}),
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    };
+    let expires = Utc::now().timestamp() + 60;
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    // Tokens signed with another secret or already expired are refused
+    for token in [token("read", expires, "other"), token("read", expires - 3600, "jwt-secret")] {
+        send_json(&mut connection, serde_json::json!({"action": "auth", "token": token})).await;
+        let response = next_json(&mut connection).await;
+        assert_eq!(response["type"], "error");
+        assert_eq!(response["message"], "Invalid or expired token");
+        assert_eq!(response["code"], "auth_failed");
+    }
+
+    // The token's scope claim is granted
+    let admin = token("admin", expires, "jwt-secret");
+    send_json(&mut connection, serde_json::json!({"action": "auth", "token": admin})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "authenticated");
+    assert_eq!(response["scope"], "admin");
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "ops_metrics"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    // Authenticated sessions outlive the auth timeout
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+
+    // Sessions that never authenticate are told and closed
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert_eq!(response["message"], "Authentication timed out, disconnecting");
+    let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(frame, ws::Frame::Close(Some(reason)) if reason.code == ws::CloseCode::Policy));
+}
+
+#[actix_rt::test]
+async fn test_websocket_rate_limit() {
+    let mut config = Config::default();
+    config.rate_limit.enabled = true;
+    config.rate_limit.websocket_messages_per_second = 0.01;
+    config.rate_limit.websocket_burst = 2;
+    config.rate_limit.websocket_max_violations = 2;
+    let (server, _) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let ping = serde_json::json!({"action": "ping"});
+
+    for _ in 0..2 {
+        send_json(&mut connection, ping.clone()).await;
+        assert_eq!(next_json(&mut connection).await["type"], "pong");
+    }
+
+    send_json(&mut connection, ping.clone()).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert!(response["message"].as_str().unwrap().starts_with("Rate limit exceeded"));
+    assert_eq!(response["code"], "rate_limited");
+
+    // The session is told before it is disconnected
+    send_json(&mut connection, ping).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["message"], "Rate limit exceeded, disconnecting");
+    let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(matches!(frame, ws::Frame::Close(Some(reason)) if reason.code == ws::CloseCode::Policy));
+}
+
+#[actix_rt::test]
+async fn test_transactions_subscription_snapshot() {
+    let trades = Arc::new(TradeService::new(100));
+    for price in [0.1, 0.2] {
+        trades.record(&Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy));
+    }
+    let kline_service = Arc::new(KLineService::new());
+    for token in ["DOGE", "PEPE"] {
+        kline_service.add_token(token);
+    }
+
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(Arc::new(WsManager::new())))
+            .app_data(web::Data::new(trades.clone()))
+            .configure(configure_websocket_routes)
+    });
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "transactions", "tokens": ["DOGE", "PEPE"]}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    let snapshot = next_json(&mut connection).await;
+    assert_eq!(snapshot["type"], "trades");
+    assert_eq!(snapshot["token"], "DOGE");
+    assert_eq!(snapshot["data"][0]["price"], 0.1);
+    assert_eq!(snapshot["data"][1]["price"], 0.2);
+
+    let snapshot = next_json(&mut connection).await;
+    assert_eq!(snapshot["token"], "PEPE");
+    assert_eq!(snapshot["data"], serde_json::json!([]));
+}
+
+#[actix_rt::test]
+async fn test_configured_heartbeat_timeout() {
+    let mut config = Config::default();
+    config.performance.websocket_heartbeat_interval = 1;
+    config.performance.client_timeout = 2;
+    let (server, _) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    // Pings go unanswered, so the session is dropped after the timeout
+    let mut pings = 0;
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("session was not closed");
+        match frame {
+            Some(Ok(ws::Frame::Ping(_))) => pings += 1,
+            Some(Ok(ws::Frame::Close(_))) | None | Some(Err(_)) => break,
+            Some(Ok(_)) => {}
+        }
+    }
+    assert!(pings >= 1);
+}
+
+#[actix_rt::test]
+async fn test_config_updated_admin_event() {
+    let mut config = Config::default();
+    config.admin.api_key = Some("secret".to_string());
+    let (server, ws_manager) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "admin_events", "api_key": "secret"}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    ws_manager.broadcast_config_updated(&ConfigUpdate {
+        applied: vec!["logging.level".to_string()],
+        rejected: vec!["server.port".to_string()],
+        timestamp: Utc::now(),
+    });
+
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "config_updated");
+    assert_eq!(response["data"]["applied"][0], "logging.level");
+    assert_eq!(response["data"]["rejected"][0], "server.port");
+}
+
+#[actix_rt::test]
+async fn test_ops_metrics_disabled_without_admin_key() {
+    let (server, _) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "ops_metrics", "api_key": "anything"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert!(response["message"].as_str().unwrap().contains("disabled"));
+}
+
+#[actix_rt::test]
+async fn test_topic_delivery_and_unsubscribe() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    for subscription in [
+        serde_json::json!({"type": "transactions", "tokens": ["DOGE"]}),
+        serde_json::json!({"type": "all_transactions"}),
+        serde_json::json!({"type": "klines", "token": "DOGE", "interval": "1m"}),
+    ] {
+        send_json(
+            &mut connection,
+            serde_json::json!({"action": "subscribe", "subscription": subscription}),
+        )
+        .await;
+        assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+    }
+
+    let kline_service = KLineService::new();
+    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    kline_service.process_transaction(&transaction);
+    ws_manager.broadcast_transaction(&transaction);
+    ws_manager.broadcast_kline(
+        &kline_service
+            .get_current_kline("DOGE", TimeInterval::Minute1)
+            .unwrap(),
+    );
+
+    // The overlapping transaction subscriptions deliver the trade only once
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "transaction");
+    assert_eq!(response["data"]["token"], "DOGE");
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "kline");
+    assert_eq!(response["data"]["close"], 0.15);
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "unsubscribe",
+            "subscription": {"type": "klines", "token": "DOGE", "interval": "1m"}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "unsubscribed");
+
+    ws_manager.broadcast_kline(
+        &kline_service
+            .get_current_kline("DOGE", TimeInterval::Minute1)
+            .unwrap(),
+    );
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+}
+
+#[actix_rt::test]
+async fn test_compressed_broadcasts() {
+    let mut config = Config::default();
+    config.performance.websocket_compression = true;
+    config.performance.websocket_compression_threshold = 0;
+    let (server, ws_manager) = start_server(config);
+
+    let (_, mut compressed) = awc::Client::new()
+        .ws(server.url("/ws?compression=deflate"))
+        .connect()
+        .await
+        .unwrap();
+    let (_, mut plain) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    for connection in [&mut compressed, &mut plain] {
+        send_json(
+            connection,
+            serde_json::json!({"action": "subscribe", "subscription": {"type": "all_transactions"}}),
+        )
+        .await;
+        // Control messages are never compressed
+        assert_eq!(next_json(connection).await["type"], "subscribed");
+    }
+
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    let frame = tokio::time::timeout(Duration::from_secs(5), compressed.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let ws::Frame::Binary(bytes) = frame else {
+        panic!("expected a binary frame, got {:?}", frame);
+    };
+    let mut json = String::new();
+    flate2::read::DeflateDecoder::new(&bytes[..])
+        .read_to_string(&mut json)
+        .unwrap();
+    let response: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(response["type"], "transaction");
+    assert_eq!(response["data"]["token"], "DOGE");
+
+    // Clients that did not opt in keep receiving text frames
+    let response = next_json(&mut plain).await;
+    assert_eq!(response["type"], "transaction");
+}
+
+/// Receive the next binary frame decoded with the given format
+async fn next_binary(connection: &mut WsConnection, format: WireFormat) -> serde_json::Value {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Binary(bytes) = frame {
+            return format.decode(&bytes).unwrap();
+        }
+    }
+}
+
+#[actix_rt::test]
+async fn test_msgpack_format() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new()
+        .ws(server.url("/ws?format=msgpack"))
+        .connect()
+        .await
+        .unwrap();
+
+    // Binary client frames are decoded with the session format
+    let subscribe = serde_json::json!({"action": "subscribe", "subscription": {"type": "all_transactions"}});
+    connection
+        .send(ws::Message::Binary(WireFormat::Msgpack.encode(&subscribe).unwrap()))
+        .await
+        .unwrap();
+    let response = next_binary(&mut connection, WireFormat::Msgpack).await;
+    assert_eq!(response["type"], "subscribed");
+
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    let response = next_binary(&mut connection, WireFormat::Msgpack).await;
+    assert_eq!(response["type"], "transaction");
+    assert_eq!(response["data"]["price"], 0.15);
+}
+
+#[actix_rt::test]
+async fn test_set_format() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "all_transactions"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    send_json(&mut connection, serde_json::json!({"action": "set_format", "format": "cbor"})).await;
+    let response = next_binary(&mut connection, WireFormat::Cbor).await;
+    assert_eq!(response["type"], "format");
+    assert_eq!(response["format"], "cbor");
+
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+
+    let response = next_binary(&mut connection, WireFormat::Cbor).await;
+    assert_eq!(response["type"], "transaction");
+    assert_eq!(response["data"]["token"], "DOGE");
+}
+
+#[actix_rt::test]
+async fn test_invalid_format_rejected() {
+    let (server, _) = start_server(Config::default());
+
+    let result = awc::Client::new().ws(server.url("/ws?format=xml")).connect().await;
+    assert!(result.is_err());
+}
+
+#[actix_rt::test]
+async fn test_sequence_numbers_and_resume() {
+    let (server, ws_manager) = start_server(Config::default());
+    let doge = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "transactions", "tokens": ["DOGE"]}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    for expected in 1..=3 {
+        ws_manager.broadcast_transaction(&doge);
+        let response = next_json(&mut connection).await;
+        assert_eq!(response["topic"], "transactions:DOGE");
+        assert_eq!(response["seq"], expected);
+    }
+    connection.close().await.unwrap();
+
+    // Messages published while disconnected are buffered
+    ws_manager.broadcast_transaction(&doge);
+    ws_manager.broadcast_transaction(&doge);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "resume", "topic": "transactions:DOGE", "since": 3}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "resumed");
+    assert_eq!(response["replayed"], 2);
+    assert_eq!(response["complete"], true);
+    assert_eq!(next_json(&mut connection).await["seq"], 4);
+    assert_eq!(next_json(&mut connection).await["seq"], 5);
+
+    // The resumed topic continues live
+    ws_manager.broadcast_transaction(&doge);
+    assert_eq!(next_json(&mut connection).await["seq"], 6);
+
+    // Sequences from another server lifetime cannot be resumed completely
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "resume", "topic": "transactions:DOGE", "since": 100}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["replayed"], 0);
+    assert_eq!(response["complete"], false);
+}
+
+#[actix_rt::test]
+async fn test_resume_invalid_topic() {
+    let (server, _) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    for topic in ["ops_metrics", "klines:DOGE:2m", "bogus"] {
+        send_json(
+            &mut connection,
+            serde_json::json!({"action": "resume", "topic": topic, "since": 0}),
+        )
+        .await;
+        assert_eq!(next_json(&mut connection).await["type"], "error");
+    }
+
+    // Tokens without a series or registration cannot hold a topic open
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "resume", "topic": "transactions:NOPE123", "since": 0}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["code"], "unknown_token");
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "transactions", "tokens": ["NOPE123"]}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["code"], "unknown_token");
+}
+
+#[actix_rt::test]
+async fn test_idle_topics_are_pruned() {
+    let ws_manager = WsManager::new().with_topic_idle_timeout(Duration::ZERO);
+    let (server, ws_manager) = start_server_with(Config::default(), ws_manager, Arc::new(KLineService::new()));
+    let doge = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    for subscription in [
+        serde_json::json!({"type": "transactions", "tokens": ["DOGE"]}),
+        serde_json::json!({"type": "agg_trades", "token": "DOGE"}),
+    ] {
+        send_json(&mut connection, serde_json::json!({"action": "subscribe", "subscription": subscription})).await;
+        assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+    }
+    ws_manager.broadcast_transaction(&doge);
+    assert_eq!(next_json(&mut connection).await["topic"], "transactions:DOGE");
+
+    // Topics with subscribers are kept however long they are idle
+    ws_manager.prune_idle_topics();
+    assert_eq!(ws_manager.topic_count(), 2);
+
+    connection.close().await.unwrap();
+    // Wait for the session to detach its topics
+    for _ in 0..50 {
+        ws_manager.prune_idle_topics();
+        if ws_manager.topic_count() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(ws_manager.topic_count(), 0);
+}
+
+/// Subscribe to all transactions, then flood the session faster than it can drain
+async fn flood_session(connection: &mut WsConnection, ws_manager: &WsManager) {
+    send_json(
+        connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "all_transactions"}}),
+    )
+    .await;
+    assert_eq!(next_json(connection).await["type"], "subscribed");
+
+    let transaction = Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy);
+    for _ in 0..10_000 {
+        ws_manager.broadcast_transaction(&transaction);
+    }
+}
+
+#[actix_rt::test]
+async fn test_slow_consumer_disconnected() {
+    let mut config = Config::default();
+    config.performance.websocket_slow_consumer_policy = SlowConsumerPolicy::Disconnect;
+    let (server, ws_manager) =
+        start_server_with(
+            config,
+            WsManager::new().with_session_backlog(1),
+            Arc::new(KLineService::new()),
+        );
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    flood_session(&mut connection, &ws_manager).await;
+
+    let reason = loop {
+        let frame = tokio::time::timeout(Duration::from_secs(10), connection.next())
+            .await
+            .expect("timed out waiting for the session to close")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Close(reason) = frame {
+            break reason.unwrap();
+        }
+    };
+    assert_eq!(reason.code, ws::CloseCode::Policy);
+
+    let stats = ws_manager.connection_stats();
+    assert!(stats.dropped_messages > 0);
+    assert_eq!(stats.slow_consumer_disconnects, 1);
+}
+
+#[actix_rt::test]
+async fn test_slow_consumer_conflated() {
+    let mut config = Config::default();
+    config.performance.websocket_max_dropped_messages = 0;
+    let (server, ws_manager) =
+        start_server_with(
+            config,
+            WsManager::new().with_session_backlog(1),
+            Arc::new(KLineService::new()),
+        );
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    flood_session(&mut connection, &ws_manager).await;
+
+    // The session survives and answers once it has drained its backlog
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    loop {
+        if next_json(&mut connection).await["type"] == "pong" {
+            break;
+        }
+    }
+
+    let stats = ws_manager.connection_stats();
+    assert!(stats.dropped_messages > 0);
+    assert_eq!(stats.slow_consumer_disconnects, 0);
+}
+
+/// Apply a DOGE trade at the given time and broadcast the current 1s K-line
+fn trade_at(
+    kline_service: &KLineService,
+    ws_manager: &WsManager,
+    price: f64,
+    timestamp: chrono::DateTime<Utc>,
+) {
+    let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+    transaction.timestamp = timestamp;
+    kline_service.process_transaction(&transaction);
+    let kline = kline_service
+        .get_current_kline("DOGE", TimeInterval::Second1)
+        .unwrap();
+    ws_manager.broadcast_kline(&kline);
+}
+
+async fn subscribe_throttled(connection: &mut WsConnection, throttle_ms: u64) {
+    send_json(
+        connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "klines", "token": "DOGE", "interval": "1s", "throttle_ms": throttle_ms}
+        }),
+    )
+    .await;
+    let response = next_json(connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["throttle_ms"], throttle_ms);
+}
+
+#[actix_rt::test]
+async fn test_kline_throttle_coalesces_updates() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    subscribe_throttled(&mut connection, 200).await;
+
+    let now = Utc::now();
+    for price in [1.0, 2.0, 3.0, 4.0] {
+        trade_at(&kline_service, &ws_manager, price, now);
+    }
+
+    // The first update goes out at once, the latest one when the window ends
+    assert_eq!(next_json(&mut connection).await["data"]["close"], 1.0);
+    assert_eq!(next_json(&mut connection).await["data"]["close"], 4.0);
+
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+}
+
+#[actix_rt::test]
+async fn test_kline_throttle_emits_closed_candle() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    subscribe_throttled(&mut connection, 60_000).await;
+
+    let candle = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap();
+    trade_at(&kline_service, &ws_manager, 1.0, candle);
+    trade_at(&kline_service, &ws_manager, 2.0, candle + TimeDelta::milliseconds(500));
+    trade_at(&kline_service, &ws_manager, 3.0, candle + TimeDelta::seconds(1));
+
+    let first = next_json(&mut connection).await;
+    assert_eq!(first["data"]["close"], 1.0);
+    assert_eq!(first["seq"], 1);
+
+    // The coalesced update is not lost: the candle's final state arrives on rollover,
+    // in place of the update it stands for
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["data"]["close"], 2.0);
+    assert_eq!(response["data"]["is_closed"], true);
+    assert_eq!(response["topic"], "klines:DOGE:1s");
+    assert_eq!(response["seq"], 2);
+
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+}
+
+#[actix_rt::test]
+async fn test_alerts_subscription() {
+    let (server, ws_manager) = start_server(Config::default());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "alerts"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    let alert_service = AlertService::new();
+    alert_service
+        .create(NewAlert {
+            token: "DOGE".to_string(),
+            condition: AlertCondition::Below { price: 0.1 },
+            webhook_url: None,
+        })
+        .unwrap();
+    for trigger in alert_service.evaluate(&Transaction::new("DOGE".to_string(), 0.09, 10.0, Side::Sell)) {
+        ws_manager.broadcast_alert(&trigger);
+    }
+
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "alert");
+    assert_eq!(message["data"]["token"], "DOGE");
+    assert_eq!(message["data"]["condition"]["type"], "below");
+    assert_eq!(message["data"]["price"], 0.09);
+}
+
+#[actix_rt::test]
+async fn test_listings_subscription() {
+    let (server, ws_manager) = start_server(Config::default());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "listings"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    ws_manager.broadcast_token_listed(&ListingEvent {
+        token: "BONK".to_string(),
+        base_price: Some(0.00002),
+        timestamp: Utc::now(),
+    });
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "token_listed");
+    assert_eq!(message["data"]["token"], "BONK");
+    assert_eq!(message["data"]["base_price"], 0.00002);
+
+    ws_manager.broadcast_token_delisted(&ListingEvent {
+        token: "BONK".to_string(),
+        base_price: None,
+        timestamp: Utc::now(),
+    });
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "token_delisted");
+    assert_eq!(message["data"]["token"], "BONK");
+    assert!(message["data"].get("base_price").is_none());
+}
+
+#[actix_rt::test]
+async fn test_indicators_subscription() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "indicators", "token": "DOGE", "interval": "1s", "period": 0}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "error");
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "indicators", "token": "DOGE", "interval": "1s", "period": 2}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["period"], 2);
+
+    // Rising closes: every candle that closes after the first two has an RSI of 100
+    let start = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap() - TimeDelta::seconds(10);
+    for second in 0..4 {
+        let mut transaction = Transaction::new("DOGE".to_string(), second as f64 + 1.0, 1.0, Side::Buy);
+        transaction.timestamp = start + TimeDelta::seconds(second);
+        for kline in kline_service.process_transaction(&transaction) {
+            ws_manager.broadcast_indicators(&kline_service, &kline);
+        }
+    }
+
+    // The candle opened at start + 2s closed when the last trade arrived
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "indicator");
+    assert_eq!(message["data"]["indicator"], "rsi");
+    assert_eq!(message["data"]["interval"], "1s");
+    assert_eq!(message["data"]["value"], 100.0);
+    assert_eq!(message["data"]["is_closed"], true);
+    assert_eq!(
+        message["data"]["timestamp"],
+        serde_json::json!(start + TimeDelta::seconds(2))
+    );
+
+    // Updates of the open candle carry provisional values
+    let open = kline_service.get_current_kline("DOGE", TimeInterval::Second1).unwrap();
+    ws_manager.broadcast_indicators(&kline_service, &open);
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["data"]["is_closed"], false);
+    assert_eq!(message["data"]["timestamp"], serde_json::json!(start + TimeDelta::seconds(3)));
+}
+
+#[actix_rt::test]
+async fn test_macd_and_bollinger_indicator_subscriptions() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "indicators", "token": "DOGE", "interval": "1s", "indicator": "macd", "fast": 3, "slow": 2}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["code"], "invalid_parameter");
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "indicators", "token": "DOGE", "interval": "1s", "indicator": "bollinger", "period": 2, "std_dev": 1.0}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["indicator"], "bollinger");
+
+    // Closes of 1 and 3, then an open candle at 5
+    let start = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap() - TimeDelta::seconds(10);
+    for (second, price) in [(0, 1.0), (1, 3.0), (2, 5.0)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+        transaction.timestamp = start + TimeDelta::seconds(second);
+        kline_service.process_transaction(&transaction);
+    }
+    let open = kline_service.get_current_kline("DOGE", TimeInterval::Second1).unwrap();
+    ws_manager.broadcast_indicators(&kline_service, &open);
+
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["data"]["indicator"], "bollinger");
+    assert_eq!(message["data"]["is_closed"], false);
+    assert_eq!(
+        (&message["data"]["value"], &message["data"]["upper"], &message["data"]["lower"]),
+        (&serde_json::json!(4.0), &serde_json::json!(5.0), &serde_json::json!(3.0))
+    );
+    assert!(message["data"].get("signal").is_none());
+}
+
+#[actix_rt::test]
+async fn test_heikin_ashi_kline_subscription() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    let candle = Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap() - TimeDelta::seconds(5);
+    trade_at(&kline_service, &ws_manager, 10.0, candle);
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "klines", "token": "DOGE", "interval": "1s", "candle_type": "heikin_ashi"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["candle_type"], "heikin_ashi");
+
+    trade_at(&kline_service, &ws_manager, 20.0, candle + TimeDelta::seconds(1));
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["data"]["open"], 10.0);
+    assert_eq!(message["data"]["close"], 20.0);
+
+    // Updates to the same candle keep its Heikin-Ashi open
+    trade_at(&kline_service, &ws_manager, 24.0, candle + TimeDelta::milliseconds(1500));
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["data"]["open"], 10.0);
+    assert_eq!(message["data"]["close"], 22.0);
+    assert!(message["seq"].is_number());
+}
+
+#[actix_rt::test]
+async fn test_bars_subscription() {
+    let kline_service = Arc::new(KLineService::new());
+    let (server, ws_manager) =
+        start_server_with(Config::default(), WsManager::new(), kline_service.clone());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "bars", "token": "DOGE", "bar_type": "renko", "size": 0}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "error");
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "subscription": {"type": "bars", "token": "DOGE", "bar_type": "renko", "size": 1.0}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["bar_type"], "renko");
+
+    // The first trade anchors the bricks; the third completes two of them
+    for price in [10.0, 10.5, 12.0] {
+        let transaction = Transaction::new("DOGE".to_string(), price, 1.0, Side::Buy);
+        ws_manager.broadcast_bars(&kline_service, &transaction);
+    }
+
+    for (open, close) in [(10.0, 11.0), (11.0, 12.0)] {
+        let message = next_json(&mut connection).await;
+        assert_eq!(message["type"], "bar");
+        assert_eq!(message["data"]["token"], "DOGE");
+        assert_eq!(message["data"]["open"], open);
+        assert_eq!(message["data"]["close"], close);
+    }
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "unsubscribe",
+            "subscription": {"type": "bars", "token": "DOGE", "bar_type": "renko", "size": 1.0}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "unsubscribed");
+}
+
+#[actix_rt::test]
+async fn test_admin_session_endpoints() {
+    let ws_manager = Arc::new(WsManager::new());
+    let manager = ws_manager.clone();
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(manager.clone()))
+            .configure(configure_websocket_routes)
+            .configure(configure_routes)
+    });
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "all_transactions"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    let client = awc::Client::new();
+    let mut resp = client.get(server.url("/api/v1/admin/sessions")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["count"], 1);
+    let session = &body["sessions"][0];
+    assert!(session["remote_addr"].as_str().unwrap().starts_with("127.0.0.1:"));
+    assert_eq!(session["subscriptions"][0]["type"], "all_transactions");
+    assert_eq!(session["messages_received"], 1);
+    assert_eq!(session["messages_sent"], 1);
+    assert_eq!(session["dropped_messages"], 0);
+    let id = session["id"].as_str().unwrap().to_string();
+
+    let resp = client.delete(server.url("/api/v1/admin/sessions/not-a-uuid")).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let unknown = format!("/api/v1/admin/sessions/{}", uuid::Uuid::new_v4());
+    let resp = client.delete(server.url(&unknown)).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = client
+        .delete(server.url(&format!("/api/v1/admin/sessions/{}", id)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let reason = loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for close")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Close(reason) = frame {
+            break reason.unwrap();
+        }
+    };
+    assert_eq!(reason.code, ws::CloseCode::Policy);
+
+    // The session is gone once the actor has stopped
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(ws_manager.session_infos().is_empty());
+}
+
+#[actix_rt::test]
+async fn test_connection_limit_per_ip() {
+    let (server, ws_manager) = start_server_with(
+        Config::default(),
+        WsManager::new().with_max_connections_per_ip(2),
+        Arc::new(KLineService::new()),
+    );
+    let localhost = "127.0.0.1".parse().unwrap();
+
+    let (_, first) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let (_, _second) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    assert_eq!(ws_manager.connections_from(localhost), 2);
+
+    // A third connection from the same address is refused at the handshake
+    match awc::Client::new().ws(server.url("/ws")).connect().await {
+        Err(awc::error::WsClientError::InvalidResponseStatus(status)) => assert_eq!(status, 429),
+        other => panic!("expected a 429 response, got {:?}", other.map(|(response, _)| response)),
+    }
+
+    // Closing a connection frees its slot
+    drop(first);
+    for _ in 0..100 {
+        if ws_manager.connections_from(localhost) < 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(ws_manager.connections_from(localhost), 1);
+    assert!(awc::Client::new().ws(server.url("/ws")).connect().await.is_ok());
+}
+
+#[actix_rt::test]
+async fn test_close_all_sessions_on_shutdown() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+
+    assert_eq!(ws_manager.close_all_sessions(), 1);
+    let reason = loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), connection.next())
+            .await
+            .expect("timed out waiting for close")
+            .unwrap()
+            .unwrap();
+        if let ws::Frame::Close(reason) = frame {
+            break reason.unwrap();
+        }
+    };
+    assert_eq!(reason.code, ws::CloseCode::Away);
+    assert_eq!(reason.description.as_deref(), Some("server_shutdown"));
+}
+
+#[actix_rt::test]
+async fn test_list_subscriptions_and_unsubscribe_all() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let subscriptions = [
+        serde_json::json!({"type": "transactions", "tokens": ["DOGE"]}),
+        serde_json::json!({"type": "klines", "token": "DOGE", "interval": "1m", "throttle_ms": 500}),
+    ];
+    for subscription in &subscriptions {
+        send_json(
+            &mut connection,
+            serde_json::json!({"action": "subscribe", "subscription": subscription}),
+        )
+        .await;
+        assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+    }
+
+    send_json(&mut connection, serde_json::json!({"action": "list_subscriptions"})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscriptions");
+    assert_eq!(response["subscriptions"], serde_json::json!(subscriptions));
+
+    send_json(&mut connection, serde_json::json!({"action": "unsubscribe_all"})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "unsubscribed_all");
+    assert_eq!(response["subscriptions"], serde_json::json!(subscriptions));
+    assert_eq!(ws_manager.subscription_count(), 0);
+
+    // Nothing is delivered once every subscription is gone
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+    send_json(&mut connection, serde_json::json!({"action": "list_subscriptions"})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscriptions");
+    assert_eq!(response["subscriptions"], serde_json::json!([]));
+}
+
+#[actix_rt::test]
+async fn test_subscribe_many() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe_many",
+            "subscriptions": [
+                {"type": "klines", "token": "DOGE", "interval": "1m"},
+                {"type": "klines", "token": "SHIB", "interval": "5m"},
+                {"type": "klines", "token": "DOGE", "interval": "2m"},
+                {"type": "teleport"},
+                {"type": "ops_metrics"}
+            ]
+        }),
+    )
+    .await;
+
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed_many");
+    assert_eq!(
+        response["accepted"],
+        serde_json::json!([
+            {"type": "klines", "token": "DOGE", "interval": "1m"},
+            {"type": "klines", "token": "SHIB", "interval": "5m"}
+        ])
+    );
+    let rejected = response["rejected"].as_array().unwrap();
+    assert_eq!(rejected.len(), 3);
+    assert_eq!(rejected[0]["subscription"]["interval"], "2m");
+    assert_eq!(rejected[0]["error"], "Invalid interval: 2m");
+    assert_eq!(rejected[1]["subscription"]["type"], "teleport");
+    assert!(rejected[1]["error"].as_str().unwrap().starts_with("Invalid subscription"));
+    assert_eq!(rejected[2]["error"], "ops_metrics is disabled on this server");
+    assert_eq!(ws_manager.subscription_count(), 2);
+
+    // Accepted subscriptions deliver like individual ones
+    let kline_service = KLineService::new();
+    kline_service.process_transaction(&Transaction::new("SHIB".to_string(), 0.00002, 100.0, Side::Buy));
+    ws_manager.broadcast_kline(&kline_service.get_current_kline("SHIB", TimeInterval::Minute5).unwrap());
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "kline");
+    assert_eq!(message["data"]["token"], "SHIB");
+
+    let subscriptions: Vec<serde_json::Value> = (0..101)
+        .map(|_| serde_json::json!({"type": "all_transactions"}))
+        .collect();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe_many", "subscriptions": subscriptions}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "error");
+}
+
+#[actix_rt::test]
+async fn test_subscription_limit_and_duplicates() {
+    let mut config = Config::default();
+    config.performance.websocket_max_subscriptions = 2;
+    let (server, ws_manager) = start_server(config);
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    let subscribe = |subscription: serde_json::Value| {
+        serde_json::json!({"action": "subscribe", "subscription": subscription})
+    };
+
+    // Subscribing to a stream again replaces its subscription instead of adding one
+    send_json(&mut connection, subscribe(serde_json::json!({"type": "klines", "token": "DOGE", "interval": "1m"}))).await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+    let throttled = serde_json::json!({"type": "klines", "token": "DOGE", "interval": "1m", "throttle_ms": 500});
+    send_json(&mut connection, subscribe(throttled.clone())).await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+    send_json(&mut connection, serde_json::json!({"action": "list_subscriptions"})).await;
+    assert_eq!(next_json(&mut connection).await["subscriptions"], serde_json::json!([throttled]));
+    assert_eq!(ws_manager.subscription_count(), 1);
+
+    send_json(&mut connection, subscribe(serde_json::json!({"type": "alerts"}))).await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    // New subscriptions past the limit are refused with a code
+    send_json(&mut connection, subscribe(serde_json::json!({"type": "listings"}))).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert_eq!(response["code"], "subscription_limit");
+    assert_eq!(response["message"], "Subscription limit reached: at most 2 per session");
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe_many",
+            "subscriptions": [{"type": "alerts"}, {"type": "listings"}, {"type": "klines", "token": "DOGE", "interval": "2m"}]
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["accepted"], serde_json::json!([{"type": "alerts"}]));
+    let rejected = response["rejected"].as_array().unwrap();
+    assert_eq!(rejected[0]["code"], "subscription_limit");
+    assert_eq!(rejected[1]["code"], "invalid_interval");
+
+    send_json(&mut connection, serde_json::json!({"action": "resume", "topic": "all_transactions", "since": 0})).await;
+    assert_eq!(next_json(&mut connection).await["code"], "subscription_limit");
+    assert_eq!(ws_manager.subscription_count(), 2);
+
+    // Unsubscribing makes room again
+    send_json(&mut connection, serde_json::json!({"action": "unsubscribe", "subscription": {"type": "alerts"}})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "unsubscribed");
+    send_json(&mut connection, subscribe(serde_json::json!({"type": "listings"}))).await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+}
+
+#[actix_rt::test]
+async fn test_error_codes_and_request_ref() {
+    let registry = Arc::new(TokenRegistry::new(UnknownTokenPolicy::Reject, ["DOGE".to_string()]));
+    let (server, _) = start_server_with(
+        Config::default(),
+        WsManager::new(),
+        Arc::new(KLineService::new().with_allowlist(registry)),
+    );
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    // Errors echo the ID of the message they answer as ref
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "id": 7,
+            "subscription": {"type": "klines", "token": "DOGE", "interval": "2m"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["code"], "invalid_interval");
+    assert_eq!(response["ref"], 7);
+
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "id": "shib-1m",
+            "subscription": {"type": "klines", "token": "SHIB", "interval": "1m"}
+        }),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["code"], "unknown_token");
+    assert_eq!(response["message"], "Unknown token: SHIB");
+    assert_eq!(response["ref"], "shib-1m");
+
+    // The ID is echoed even when the rest of the message is invalid
+    send_json(&mut connection, serde_json::json!({"action": "teleport", "id": "jump"})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["code"], "invalid_message");
+    assert_eq!(response["ref"], "jump");
+
+    send_json(&mut connection, serde_json::json!({"action": "resume", "topic": "weather", "since": 0})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["code"], "invalid_topic");
+    assert!(response.get("ref").is_none());
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "id": 8, "subscription": {"type": "ops_metrics"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["code"], "forbidden");
+
+    // Allowed tokens subscribe as before
+    send_json(
+        &mut connection,
+        serde_json::json!({
+            "action": "subscribe",
+            "id": 9,
+            "subscription": {"type": "klines", "token": "DOGE", "interval": "1m"}
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+}
+
+#[actix_rt::test]
+async fn test_replies_echo_request_id() {
+    let trades = Arc::new(TradeService::new(100));
+    trades.record(&Transaction::new("DOGE".to_string(), 0.1, 10.0, Side::Buy));
+    let kline_service = Arc::new(KLineService::new());
+    kline_service.add_token("DOGE");
+    let ws_manager = Arc::new(WsManager::new());
+    let manager = ws_manager.clone();
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(manager.clone()))
+            .app_data(web::Data::new(trades.clone()))
+            .configure(configure_websocket_routes)
+    });
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    send_json(&mut connection, serde_json::json!({"action": "ping", "id": 1})).await;
+    let response = next_json(&mut connection).await;
+    assert_eq!((response["type"].as_str(), response["ref"].as_u64()), (Some("pong"), Some(1)));
+
+    // The confirmation refers to the request, the snapshot that follows does not
+    let subscription = serde_json::json!({"type": "transactions", "tokens": ["DOGE"]});
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "id": "sub-doge", "subscription": subscription}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["ref"], "sub-doge");
+    let snapshot = next_json(&mut connection).await;
+    assert_eq!(snapshot["type"], "trades");
+    assert!(snapshot.get("ref").is_none());
+
+    send_json(&mut connection, serde_json::json!({"action": "list_subscriptions", "id": 2})).await;
+    assert_eq!(next_json(&mut connection).await["ref"], 2);
+
+    // Broadcasts never carry a ref
+    ws_manager.broadcast_transaction(&Transaction::new("DOGE".to_string(), 0.2, 10.0, Side::Sell));
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "transaction");
+    assert!(message.get("ref").is_none());
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "unsubscribe", "id": 3, "subscription": subscription}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!((response["type"].as_str(), response["ref"].as_u64()), (Some("unsubscribed"), Some(3)));
+
+    // Messages without an ID get replies without a ref
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert!(next_json(&mut connection).await.get("ref").is_none());
+}
+
+#[actix_rt::test]
+async fn test_agg_trades_subscription() {
+    let (server, ws_manager) = start_server_with(
+        Config::default(),
+        WsManager::new().with_agg_trade_window(Duration::from_secs(60)),
+        Arc::new(KLineService::new()),
+    );
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "agg_trades", "token": "DOGE"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    for (token, price, volume) in [("DOGE", 0.15, 100.0), ("SHIB", 0.00002, 5.0), ("DOGE", 0.15, 50.0), ("DOGE", 0.16, 10.0)] {
+        ws_manager.broadcast_agg_trade(&Transaction::new(token.to_string(), price, volume, Side::Buy));
+    }
+
+    // The price change completes the first aggregate; the second waits for its window
+    let message = next_json(&mut connection).await;
+    assert_eq!(message["type"], "agg_trade");
+    assert_eq!(message["data"]["token"], "DOGE");
+    assert_eq!(message["data"]["price"], 0.15);
+    assert_eq!(message["data"]["volume"], 150.0);
+    assert_eq!(message["data"]["trades"], 2);
+    assert_eq!(message["data"]["side"], "buy");
+
+    ws_manager.flush_agg_trades();
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+}
+
+#[actix_rt::test]
+async fn test_depth_subscription() {
+    let order_books = Arc::new(OrderBookSimulator::new(5));
+    order_books.apply_trade(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+    let kline_service = Arc::new(KLineService::new());
+    kline_service.add_token("DOGE");
+    let ws_manager = Arc::new(WsManager::new());
+
+    let books = order_books.clone();
+    let manager = ws_manager.clone();
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(kline_service.clone()))
+            .app_data(web::Data::new(manager.clone()))
+            .app_data(web::Data::new(books.clone()))
+            .configure(configure_websocket_routes)
+    });
+
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "depth", "token": "DOGE"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    let snapshot = next_json(&mut connection).await;
+    assert_eq!(snapshot["type"], "depth_snapshot");
+    assert_eq!(snapshot["data"]["token"], "DOGE");
+    assert_eq!(snapshot["data"]["update_id"], 1);
+    assert!(!snapshot["data"]["bids"].as_array().unwrap().is_empty());
+
+    // Only updates of the subscribed token are delivered
+    for token in ["SHIB", "DOGE"] {
+        let update = order_books
+            .apply_trade(&Transaction::new(token.to_string(), 0.1502, 10.0, Side::Buy))
+            .unwrap();
+        ws_manager.broadcast_depth(&update);
+    }
+    let update = next_json(&mut connection).await;
+    assert_eq!(update["type"], "depth_update");
+    assert_eq!(update["data"]["token"], "DOGE");
+    assert_eq!(update["data"]["update_id"], 2);
+    // The buy swept the asks below its price
+    let asks = update["data"]["asks"].as_array().unwrap();
+    assert!(asks
+        .iter()
+        .filter(|level| level["price"].as_f64().unwrap() < 0.1502)
+        .all(|level| level["quantity"] == 0.0));
+}
+
+#[actix_rt::test]
+async fn test_ping_timestamps_and_server_time() {
+    let mut config = Config::default();
+    config.performance.websocket_server_time_interval_ms = 500;
+    let (server, _) = start_server(config);
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    let before = Utc::now();
+    send_json(&mut connection, serde_json::json!({"action": "ping", "client_time": 1234.5})).await;
+    let pong = next_json(&mut connection).await;
+    assert_eq!(pong["type"], "pong");
+    assert_eq!(pong["client_time"], 1234.5);
+    let server_time: chrono::DateTime<Utc> = pong["server_time"].as_str().unwrap().parse().unwrap();
+    assert!(server_time >= before && server_time <= Utc::now());
+
+    // Pings without a client clock get no client_time back
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert!(next_json(&mut connection).await.get("client_time").is_none());
+
+    let pushed = next_json(&mut connection).await;
+    assert_eq!(pushed["type"], "server_time");
+    assert!(pushed["server_time"].is_string());
+}
+
+#[actix_rt::test]
+async fn test_stream_endpoints() {
+    let (server, ws_manager) = start_server(Config::default());
+
+    // Stream endpoints subscribe on connect, without any client message
+    let (_, mut klines) = awc::Client::new().ws(server.url("/ws/klines/doge/1m")).connect().await.unwrap();
+    let response = next_json(&mut klines).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["token"], "DOGE");
+    let (_, mut trades) = awc::Client::new().ws(server.url("/ws/trades/DOGE")).connect().await.unwrap();
+    assert_eq!(next_json(&mut trades).await["type"], "subscribed");
+
+    let kline_service = KLineService::new();
+    for token in ["SHIB", "DOGE"] {
+        let transaction = Transaction::new(token.to_string(), 0.15, 100.0, Side::Buy);
+        kline_service.process_transaction(&transaction);
+        ws_manager.broadcast_transaction(&transaction);
+        ws_manager.broadcast_kline(&kline_service.get_current_kline(token, TimeInterval::Minute1).unwrap());
+    }
+    let response = next_json(&mut klines).await;
+    assert_eq!(response["type"], "kline");
+    assert_eq!(response["data"]["token"], "DOGE");
+    let response = next_json(&mut trades).await;
+    assert_eq!(response["type"], "transaction");
+    assert_eq!(response["data"]["token"], "DOGE");
+
+    // Invalid streams are refused at the handshake
+    match awc::Client::new().ws(server.url("/ws/klines/DOGE/2m")).connect().await {
+        Err(awc::error::WsClientError::InvalidResponseStatus(status)) => assert_eq!(status, 400),
+        other => panic!("expected a 400 response, got {:?}", other.map(|(response, _)| response)),
+    }
+
+    // Without an auth message, the connection itself must carry the key
+    let mut config = Config::default();
+    config.auth.enabled = true;
+    config.auth.keys = vec![ApiKeyConfig {
+        key: "reader".to_string(),
+        scope: ApiKeyScope::Read,
+        name: None,
+    }];
+    let (server, _) = start_server(config);
+    match awc::Client::new().ws(server.url("/ws/trades/DOGE")).connect().await {
+        Err(awc::error::WsClientError::InvalidResponseStatus(status)) => assert_eq!(status, 401),
+        other => panic!("expected a 401 response, got {:?}", other.map(|(response, _)| response)),
+    }
+    let (_, mut trades) = awc::Client::new()
+        .ws(server.url("/ws/trades/DOGE?api_key=reader"))
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut trades).await["type"], "subscribed");
+}
+
+#[actix_rt::test]
+async fn test_get_klines_history() {
+    let kline_service = Arc::new(KLineService::new());
+    let now = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for minute in 0..3 {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1 + minute as f64, 10.0, Side::Buy);
+        transaction.timestamp = now - TimeDelta::minutes(2 - minute);
+        kline_service.process_transaction(&transaction);
+    }
+    let (server, _) = start_server_with(Config::default(), WsManager::new(), kline_service);
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+
+    // The newest candles come first, sorted oldest first, with a cursor to older ones
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "get_klines", "id": 1, "token": "doge", "interval": "1m", "limit": 2}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "klines_snapshot");
+    assert_eq!(response["ref"], 1);
+    assert_eq!(response["token"], "DOGE");
+    let closes: Vec<f64> = response["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|kline| kline["close"].as_f64().unwrap())
+        .collect();
+    assert_eq!(closes, vec![1.1, 2.1]);
+    let cursor = response["next_cursor"].as_i64().unwrap();
+    assert_eq!(cursor, (now - TimeDelta::minutes(1)).timestamp_millis());
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "get_klines", "id": 2, "token": "DOGE", "interval": "1m", "before": cursor}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["ref"], 2);
+    assert_eq!(response["data"].as_array().unwrap().len(), 1);
+    assert_eq!(response["data"][0]["close"], 0.1);
+    assert!(response["next_cursor"].is_null());
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "get_klines", "id": 3, "token": "DOGE", "interval": "2m"}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "error");
+    assert_eq!(response["code"], "invalid_interval");
+    assert_eq!(response["ref"], 3);
+}
+
+#[actix_rt::test]
+async fn test_system_status_notices() {
+    let ws_manager = Arc::new(WsManager::new());
+    let manager = ws_manager.clone();
+    let server = actix_test::start(move || {
+        App::new()
+            .app_data(web::Data::new(Arc::new(KLineService::new())))
+            .app_data(web::Data::new(manager.clone()))
+            .configure(configure_websocket_routes)
+            .configure(configure_routes)
+    });
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(&mut connection, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut connection).await["type"], "pong");
+
+    // Every session is told, whatever it subscribes to
+    let mut resp = awc::Client::new()
+        .post(server.url("/api/v1/admin/system-status"))
+        .send_json(&serde_json::json!({
+            "status": "maintenance",
+            "deadline": "2030-01-01T00:00:00Z",
+            "message": "Moving to a new host"
+        }))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["sessions"], 1);
+    let notice = next_json(&mut connection).await;
+    assert_eq!(notice["type"], "system_status");
+    assert_eq!(notice["data"]["status"], "maintenance");
+    assert_eq!(notice["data"]["deadline"], "2030-01-01T00:00:00Z");
+    assert_eq!(notice["data"]["message"], "Moving to a new host");
+
+    // Sessions opened later learn of it on connect, until the status is back to normal
+    let (_, mut late) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    assert_eq!(next_json(&mut late).await["data"]["status"], "maintenance");
+    assert_eq!(ws_manager.broadcast_system_status(SystemStatus::default()), 2);
+    assert_eq!(next_json(&mut connection).await["data"]["status"], "normal");
+    let (_, mut later) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(&mut later, serde_json::json!({"action": "ping"})).await;
+    assert_eq!(next_json(&mut later).await["type"], "pong");
+}
+
+#[actix_rt::test]
+async fn test_delta_subscription() {
+    let (server, ws_manager) = start_server(Config::default());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "delta", "token": "doge", "interval": "1m"}}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["token"], "DOGE");
+
+    let kline_service = KLineService::new();
+    let now = Utc::now().duration_trunc(TimeDelta::minutes(1)).unwrap();
+    for (minute, volume, side) in [(0, 10.0, Side::Buy), (0, 4.0, Side::Sell), (1, 3.0, Side::Sell)] {
+        let mut transaction = Transaction::new("DOGE".to_string(), 0.1, volume, side);
+        transaction.timestamp = now - TimeDelta::minutes(1 - minute);
+        kline_service.process_transaction(&transaction);
+        ws_manager.broadcast_kline(&kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap());
+    }
+
+    // Each update carries the open candle's delta on top of the earlier candles'
+    let mut deltas = Vec::new();
+    for _ in 0..3 {
+        let message = next_json(&mut connection).await;
+        assert_eq!(message["type"], "delta");
+        assert_eq!(message["data"]["token"], "DOGE");
+        deltas.push((message["data"]["delta"].as_f64().unwrap(), message["data"]["cumulative_delta"].as_f64().unwrap()));
+    }
+    assert_eq!(deltas, vec![(10.0, 10.0), (6.0, 6.0), (-3.0, 3.0)]);
+
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "delta", "token": "DOGE", "interval": "2m"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["code"], "invalid_interval");
+}
+
+#[actix_rt::test]
+async fn test_ticker_subscription() {
+    let (server, ws_manager) = start_server(Config::default());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "ticker", "token": "doge"}}),
+    )
+    .await;
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "subscribed");
+    assert_eq!(response["subscription"]["token"], "DOGE");
+
+    let kline_service = KLineService::new();
+    for price in [0.2, 0.1] {
+        kline_service.process_transaction(&Transaction::new("DOGE".to_string(), price, 10.0, Side::Buy));
+        ws_manager.broadcast_ticker(&kline_service, "DOGE");
+    }
+    // Tokens without subscribers are not pushed
+    kline_service.process_transaction(&Transaction::new("SHIB".to_string(), 1.0, 10.0, Side::Buy));
+    ws_manager.broadcast_ticker(&kline_service, "SHIB");
+
+    let first = next_json(&mut connection).await;
+    assert_eq!(first["type"], "ticker");
+    assert_eq!(first["data"]["extremes"]["all_time_high"], 0.2);
+    let second = next_json(&mut connection).await;
+    assert_eq!(second["data"]["token"], "DOGE");
+    assert_eq!(second["data"]["last_price"], 0.1);
+    assert_eq!(second["data"]["extremes"]["low_1h"], 0.1);
+    assert_eq!(second["data"]["extremes"]["high_24h"], 0.2);
+}
+
+#[actix_rt::test]
+async fn test_kline_amended_broadcast() {
+    let (server, ws_manager) = start_server(Config::default());
+    let (_, mut connection) = awc::Client::new().ws(server.url("/ws")).connect().await.unwrap();
+    send_json(
+        &mut connection,
+        serde_json::json!({"action": "subscribe", "subscription": {"type": "klines", "token": "DOGE", "interval": "1m"}}),
+    )
+    .await;
+    assert_eq!(next_json(&mut connection).await["type"], "subscribed");
+
+    let kline_service = KLineService::new();
+    kline_service.process_transaction(&Transaction::new("DOGE".to_string(), 0.15, 100.0, Side::Buy));
+    let mut amendment = kline_service.get_current_kline("DOGE", TimeInterval::Minute1).unwrap();
+    amendment.high = 0.2;
+    let amended = kline_service
+        .amend_kline(&KLineAmendment {
+            token: amendment.token,
+            interval: amendment.interval,
+            timestamp: amendment.timestamp,
+            open: amendment.open,
+            high: amendment.high,
+            low: amendment.low,
+            close: amendment.close,
+            volume: amendment.volume,
+            buy_volume: None,
+            sell_volume: None,
+        })
+        .unwrap();
+    ws_manager.broadcast_kline_amended(&amended);
+
+    let response = next_json(&mut connection).await;
+    assert_eq!(response["type"], "kline_amended");
+    assert_eq!(response["data"]["high"], 0.2);
+    assert_eq!(response["data"]["buy_volume"], 100.0);
+}